@@ -0,0 +1,135 @@
+//! Benchmarks of the engine over representative workloads, run with `cargo bench`.
+//!
+//! 1. `throughput`: end-to-end CSV parsing and processing, reported in records per second
+//! 2. `deposit`: a single deposit into an existing account
+//! 3. `dispute_resolve`: a dispute of an earlier deposit followed by its resolution
+//! 4. `lookup_miss`: a dispute of a transaction that does not exist
+//!
+//! Each is measured against banks of 1k, 10k, and 60k accounts.
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use rust_payment_processor::account::ClientId;
+use rust_payment_processor::amount::Amount;
+use rust_payment_processor::bank::Bank;
+use rust_payment_processor::transaction::{RecordOptions, Transaction, TransactionType, TxId};
+use std::fmt::Write;
+use std::hint::black_box;
+
+const ACCOUNT_COUNTS: [ClientId; 3] = [1_000, 10_000, 60_000];
+
+fn transaction(kind: TransactionType, client: ClientId, tx: TxId, amount: Option<u32>) -> Transaction {
+    Transaction {
+        kind,
+        client,
+        tx,
+        amount: amount.map(Amount::from),
+        under_dispute: false,
+        timestamp: None,
+        currency: None,
+        signature: None,
+        correction: false,
+        provenance: None,
+        expires: None,
+    }
+}
+
+/// A bank with `accounts` accounts, each funded by one deposit whose transaction ID is the client ID
+/// plus one.
+fn funded_bank(accounts: ClientId) -> Bank {
+    let mut bank = Bank::new();
+    bank.process_all((0..accounts).map(|client| transaction(TransactionType::Deposit, client, TxId::from(client) + 1, Some(100))));
+    bank
+}
+
+/// CSV input with four records per account: two deposits, a withdrawal, and a dispute.
+fn csv_input(accounts: ClientId) -> (String, u64) {
+    let mut input = String::from("type,client,tx,amount\n");
+    let mut tx = 0u32;
+    for client in 0..accounts {
+        let first = tx + 1;
+        for (kind, amount) in &[("deposit", "10.5"), ("deposit", "2.25"), ("withdrawal", "1.0001")] {
+            tx += 1;
+            writeln!(input, "{},{},{},{}", kind, client, tx, amount).unwrap();
+        }
+        writeln!(input, "dispute,{},{},", client, first).unwrap();
+    }
+    (input, u64::from(accounts) * 4)
+}
+
+fn throughput(c: &mut Criterion) {
+    let mut group = c.benchmark_group("throughput");
+    group.sample_size(10);
+    for &accounts in &ACCOUNT_COUNTS {
+        let (input, records) = csv_input(accounts);
+        group.throughput(Throughput::Elements(records));
+        group.bench_with_input(BenchmarkId::from_parameter(accounts), &input, |b, input| {
+            b.iter(|| {
+                let mut bank = Bank::new();
+                let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(input.as_bytes());
+                bank.process_record_set(&mut reader, &RecordOptions::default());
+                black_box(bank)
+            })
+        });
+    }
+    group.finish();
+}
+
+fn deposit(c: &mut Criterion) {
+    let mut group = c.benchmark_group("deposit");
+    for &accounts in &ACCOUNT_COUNTS {
+        let mut bank = funded_bank(accounts);
+        let mut tx = TxId::from(accounts);
+        group.bench_function(BenchmarkId::from_parameter(accounts), |b| {
+            b.iter_batched(
+                || {
+                    tx += 1;
+                    transaction(TransactionType::Deposit, (tx % TxId::from(accounts)) as ClientId, tx, Some(1))
+                },
+                |deposit| bank.process_all(std::iter::once(deposit)),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn dispute_resolve(c: &mut Criterion) {
+    let mut group = c.benchmark_group("dispute_resolve");
+    for &accounts in &ACCOUNT_COUNTS {
+        let mut bank = funded_bank(accounts);
+        let mut client: ClientId = 0;
+        group.bench_function(BenchmarkId::from_parameter(accounts), |b| {
+            b.iter_batched(
+                || {
+                    client = (client + 1) % accounts;
+                    let tx = TxId::from(client) + 1;
+                    [
+                        transaction(TransactionType::Dispute, client, tx, None),
+                        transaction(TransactionType::Resolve, client, tx, None),
+                    ]
+                },
+                |pair| bank.process_all(pair),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn lookup_miss(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lookup_miss");
+    for &accounts in &ACCOUNT_COUNTS {
+        let mut bank = funded_bank(accounts);
+        let missing = TxId::from(accounts) + 1;
+        group.bench_function(BenchmarkId::from_parameter(accounts), |b| {
+            b.iter_batched(
+                || transaction(TransactionType::Dispute, 0, missing, None),
+                |dispute| bank.process_all(std::iter::once(dispute)),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, throughput, deposit, dispute_resolve, lookup_miss);
+criterion_main!(benches);
@@ -0,0 +1,19 @@
+//! Feeds arbitrary bytes to the CSV reader and processes whatever parses, as the command-line tool
+//! does with a partner file. Must never panic, and must leave every account consistent.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use rust_payment_processor::bank::Bank;
+use rust_payment_processor::invariants;
+use rust_payment_processor::schema;
+
+fuzz_target!(|data: &[u8]| {
+    let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(data);
+    if schema::check_input(&mut reader, None).is_err() {
+        return;
+    }
+    let mut bank = Bank::new();
+    bank.process_record_set(&mut reader);
+    if let Err(violation) = invariants::check_bank(&bank) {
+        panic!("{}", violation);
+    }
+});
@@ -0,0 +1,12 @@
+//! Feeds arbitrary bytes to the serde deserializer of `Transaction` through JSON, which reaches
+//! the amount visitor with numbers as well as strings. Must never panic.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use rust_payment_processor::bank::Bank;
+use rust_payment_processor::transaction::Transaction;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(transactions) = serde_json::from_slice::<Vec<Transaction>>(data) {
+        Bank::new().process_all(transactions);
+    }
+});
@@ -0,0 +1,14 @@
+//! Feeds arbitrary transaction sequences to the bank, checking the ledger invariants after every
+//! transaction.
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use rust_payment_processor::bank::Bank;
+use rust_payment_processor::invariants;
+use rust_payment_processor::transaction::Transaction;
+
+fuzz_target!(|transactions: Vec<Transaction>| {
+    let mut bank = Bank::new();
+    if let Err(violation) = invariants::process_all_checked(&mut bank, transactions) {
+        panic!("{}", violation);
+    }
+});
@@ -0,0 +1,49 @@
+/*
+ * C API of rust-payment-processor, built with `cargo build --release --features ffi`.
+ * Keep in sync with src/ffi.rs.
+ */
+#ifndef RUST_PAYMENT_PROCESSOR_H
+#define RUST_PAYMENT_PROCESSOR_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct Bank Bank;
+
+typedef enum BankStatus {
+    BANK_STATUS_OK = 0,
+    BANK_STATUS_NULL_POINTER = 1,
+    BANK_STATUS_INVALID_INPUT = 2,
+    BANK_STATUS_SERIALIZATION_FAILED = 3,
+    BANK_STATUS_PANIC = 4,
+} BankStatus;
+
+/* Create a new, empty bank. Release it with bank_free. */
+Bank *bank_new(void);
+
+/* Release a bank created by bank_new. Passing NULL is a no-op. */
+void bank_free(Bank *bank);
+
+/* Process `len` bytes of CSV input with a header row. Records that fail to parse or process are
+ * discarded; an error is only returned if the input as a whole can not be read. */
+BankStatus bank_process_csv_buffer(Bank *bank, const uint8_t *data, size_t len);
+
+/* Export every account as a JSON array. On success `*out` must be released with bank_string_free. */
+BankStatus bank_export_accounts_json(const Bank *bank, char **out);
+
+/* Release a string returned by bank_export_accounts_json. Passing NULL is a no-op. */
+void bank_string_free(char *string);
+
+/* Describe the last failed call on this thread, or NULL if it succeeded. Owned by the library and
+ * valid until the next call on this thread. */
+const char *bank_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* RUST_PAYMENT_PROCESSOR_H */
@@ -1,42 +1,221 @@
 #![forbid(unsafe_code)] // for good measure
+use crate::amount::Amount;
 use crate::errors::BankingError;
+use crate::redact::redact;
+use crate::transaction::{Transaction, TransactionType, TxId};
 use rust_decimal::prelude::*;
-use rust_decimal_macros::dec;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// `ClientId` is the type of client IDs. Clients known by a string, such as a UUID, are given one of
+/// these by a `client::ClientInterner`.
+pub type ClientId = u32;
 
 /// `Account` contains a structured representation of an account
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Account {
-    pub client: u16,
-    pub available: Decimal,
-    pub held: Decimal,
-    pub total: Decimal,
+    pub client: ClientId,
+    pub available: Amount,
+    pub held: Amount,
+    pub total: Amount,
+    pub locked: bool,
+    /// Set by `Bank::freeze_account`, e.g. for a compliance hold. Unlike `locked`, a frozen account
+    /// still accepts deposits and the settlement of disputes; only withdrawals are blocked. Not
+    /// written in the output.
+    #[serde(skip)]
+    pub frozen: bool,
+    /// Set by `Bank::sweep_dormant` on an account without activity. Not written in the output,
+    /// except as the `status` of `ExtendedAccount`.
+    #[serde(skip)]
+    pub dormancy: Dormancy,
+    /// Incremented by every change to the account, so that a change made by someone else since
+    /// the account was last read can be detected. Only written with `--versions`; see
+    /// `VersionedAccount`.
+    #[serde(skip)]
+    pub version: u64,
+}
+
+/// `Dormancy` is whether an account has been flagged as dormant by `Bank::sweep_dormant`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Dormancy {
+    #[default]
+    Active,
+    /// Without activity for the period of the bank's `DormancyPolicy`. The next transaction applied
+    /// to the account makes it active again.
+    Dormant,
+    /// Dormant, with withdrawals rejected with `AccountDormant` until the account is reactivated by
+    /// `Bank::reactivate_account`.
+    Blocked,
+}
+
+/// `AccountStatus` is the most restrictive state an account is in, written as the `status` of
+/// `ExtendedAccount`.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum AccountStatus {
+    Active,
+    Dormant,
+    Frozen,
+    Locked,
+}
+
+/// `VersionedAccount` is the layout of an account written with its version.
+#[derive(Serialize)]
+pub struct VersionedAccount {
+    pub client: ClientId,
+    pub available: Amount,
+    pub held: Amount,
+    pub total: Amount,
+    pub locked: bool,
+    pub version: u64,
+}
+
+impl From<&Account> for VersionedAccount {
+    fn from(account: &Account) -> VersionedAccount {
+        VersionedAccount {
+            client: account.client,
+            available: account.available,
+            held: account.held,
+            total: account.total,
+            locked: account.locked,
+            version: account.version,
+        }
+    }
+}
+
+/// `ClientStatistics` counts the transactions applied to a client's account. Only kept by a bank
+/// configured with `BankBuilder::statistics`.
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct ClientStatistics {
+    pub deposits: u64,
+    pub withdrawals: u64,
+    /// Disputes opened, whether since settled or not.
+    pub disputes: u64,
+    /// Disputes opened and not yet resolved or charged back.
+    pub open_disputes: u64,
+    pub chargebacks: u64,
+    /// The ID of the last transaction applied to the account. For disputes, resolves, and
+    /// chargebacks, this is the ID of the transaction they refer to.
+    pub last_tx: Option<TxId>,
+}
+
+impl ClientStatistics {
+    /// Count a transaction that has been applied to the account.
+    pub fn record(&mut self, transaction: &Transaction) {
+        match transaction.kind {
+            TransactionType::Deposit => self.deposits += 1,
+            TransactionType::Withdrawal => self.withdrawals += 1,
+            TransactionType::Dispute => {
+                self.disputes += 1;
+                self.open_disputes += 1;
+            }
+            TransactionType::Resolve => self.open_disputes = self.open_disputes.saturating_sub(1),
+            TransactionType::Chargeback => {
+                self.open_disputes = self.open_disputes.saturating_sub(1);
+                self.chargebacks += 1;
+            }
+            TransactionType::Adjustment | TransactionType::PromoCredit | TransactionType::Custom(_) => {}
+        }
+        self.last_tx = Some(transaction.tx);
+    }
+}
+
+/// `Annotations` record investigation context against an account, kept by `Bank::annotate_account`
+/// and `Bank::label_account`: free-text notes, oldest first, and labels, which are key-value pairs
+/// such as `ticket=SUP-1234`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Annotations {
+    pub notes: Vec<String>,
+    pub labels: BTreeMap<String, String>,
+}
+
+impl Annotations {
+    pub fn is_empty(&self) -> bool {
+        self.notes.is_empty() && self.labels.is_empty()
+    }
+
+    /// Add the notes and labels of `other`, whose labels replace any with the same key.
+    pub fn merge(&mut self, other: Annotations) {
+        self.notes.extend(other.notes);
+        self.labels.extend(other.labels);
+    }
+
+    /// The labels, as `key=value`, followed by the notes, separated by `; `, for the `notes` column
+    /// of `ExtendedAccount`.
+    pub fn summary(&self) -> String {
+        let labels = self.labels.iter().map(|(key, value)| format!("{}={}", key, value));
+        labels.chain(self.notes.iter().cloned()).collect::<Vec<_>>().join("; ")
+    }
+}
+
+/// `ExtendedAccount` is the layout of an account written with its `ClientStatistics`.
+#[derive(Serialize)]
+pub struct ExtendedAccount {
+    pub client: ClientId,
+    pub available: Amount,
+    pub held: Amount,
+    pub total: Amount,
     pub locked: bool,
+    pub status: AccountStatus,
+    pub deposits: u64,
+    pub withdrawals: u64,
+    pub open_disputes: u64,
+    pub chargebacks: u64,
+    pub last_tx: Option<TxId>,
+    /// The account's `Annotations`, only written when requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+}
+
+impl ExtendedAccount {
+    pub fn new(account: &Account, statistics: &ClientStatistics) -> ExtendedAccount {
+        ExtendedAccount {
+            client: account.client,
+            available: account.available,
+            held: account.held,
+            total: account.total,
+            locked: account.locked,
+            status: account.status(),
+            deposits: statistics.deposits,
+            withdrawals: statistics.withdrawals,
+            open_disputes: statistics.open_disputes,
+            chargebacks: statistics.chargebacks,
+            last_tx: statistics.last_tx,
+            notes: None,
+        }
+    }
 }
 
 impl Account {
     /// Utility function to create a new account with a given client ID
-    pub fn new(client: u16) -> Account {
+    pub fn new(client: ClientId) -> Account {
         Account {
             client,
-            available: dec!(0),
-            held: dec!(0),
+            available: Amount::zero(),
+            held: Amount::zero(),
             locked: false,
-            total: dec!(0),
+            frozen: false,
+            dormancy: Dormancy::Active,
+            total: Amount::zero(),
+            version: 0,
         }
     }
 
     /// Deposit the specified value into the account, increasing both the total and available
     /// balances.
-    pub fn deposit(&mut self, amount: &Decimal) -> Result<(), BankingError> {
+    pub fn deposit(&mut self, amount: &Amount) -> Result<(), BankingError> {
         if self.locked {
             return Err(BankingError::AccountLocked);
         }
 
-        debug!("Pre-deposit: {:?}", self);
-        self.available += amount;
-        self.total += amount;
-        debug!("Post-deposit: {:?}", self);
+        debug!("Pre-deposit: {:?}", redact(self));
+        let (available, total) = (credit(self.available, amount)?, credit(self.total, amount)?);
+        self.available = available;
+        self.total = total;
+        self.version += 1;
+        debug!("Post-deposit: {:?}", redact(self));
 
         Ok(())
     }
@@ -44,48 +223,62 @@ impl Account {
     /// Withdraw the specified value from the account, decreasing both the total and available
     /// balances. In the event that insufficient funds are present, this function returns an
     /// appropriate `BankingError`
-    pub fn withdraw(&mut self, amount: &Decimal) -> Result<(), BankingError> {
+    pub fn withdraw(&mut self, amount: &Amount) -> Result<(), BankingError> {
         if self.locked {
             return Err(BankingError::AccountLocked);
         }
 
+        if self.frozen {
+            return Err(BankingError::AccountFrozen);
+        }
+
+        if self.dormancy == Dormancy::Blocked {
+            return Err(BankingError::AccountDormant);
+        }
+
         if self.available < *amount {
             return Err(BankingError::InsufficientFunds);
         }
 
-        debug!("Pre-withdrawal: {:?}", self);
-        self.available -= amount;
-        self.total -= amount;
-        debug!("Post-withdrawal: {:?}", self);
+        debug!("Pre-withdrawal: {:?}", redact(self));
+        let (available, total) = (debit(self.available, amount)?, debit(self.total, amount)?);
+        self.available = available;
+        self.total = total;
+        self.version += 1;
+        debug!("Post-withdrawal: {:?}", redact(self));
 
         Ok(())
     }
 
     /// Called in response to a dispute for a previous transaction, this function decreases the
     /// available balance and increases the balance held by the specified amount.
-    pub fn dispute(&mut self, amount: &Decimal) -> Result<(), BankingError> {
+    pub fn dispute(&mut self, amount: &Amount) -> Result<(), BankingError> {
         if self.locked {
             return Err(BankingError::AccountLocked);
         }
 
-        debug!("Pre-dispute: {:?}", self);
-        self.available -= amount;
-        self.held += amount;
-        debug!("Post-dispute: {:?}", self);
+        debug!("Pre-dispute: {:?}", redact(self));
+        let (available, held) = (debit(self.available, amount)?, credit(self.held, amount)?);
+        self.available = available;
+        self.held = held;
+        self.version += 1;
+        debug!("Post-dispute: {:?}", redact(self));
 
         Ok(())
     }
 
     /// Resolve a dispute, returning the held funds to the account and reducing the held amount.
-    pub fn resolve(&mut self, amount: &Decimal) -> Result<(), BankingError> {
+    pub fn resolve(&mut self, amount: &Amount) -> Result<(), BankingError> {
         if self.locked {
             return Err(BankingError::AccountLocked);
         }
 
-        debug!("Pre-resolve: {:?}", self);
-        self.held -= amount;
-        self.available += amount;
-        debug!("Post-resolve: {:?}", self);
+        debug!("Pre-resolve: {:?}", redact(self));
+        let (held, available) = (debit(self.held, amount)?, credit(self.available, amount)?);
+        self.held = held;
+        self.available = available;
+        self.version += 1;
+        debug!("Post-resolve: {:?}", redact(self));
 
         Ok(())
     }
@@ -93,17 +286,390 @@ impl Account {
     /// Follow through with a dispute, reversing the transaction by removing the funds from the
     /// account. The total and held amounts are both decreased and the account is locked,
     /// restricting any further transactions from taking place.
-    pub fn chargeback(&mut self, amount: &Decimal) -> Result<(), BankingError> {
+    pub fn chargeback(&mut self, amount: &Amount) -> Result<(), BankingError> {
         if self.locked {
             return Err(BankingError::AccountLocked);
         }
 
-        debug!("Pre-chargeback: {:?}", self);
-        self.total -= amount;
-        self.held -= amount;
+        debug!("Pre-chargeback: {:?}", redact(self));
+        let (total, held) = (debit(self.total, amount)?, debit(self.held, amount)?);
+        self.total = total;
+        self.held = held;
         self.locked = true;
-        debug!("Post-chargeback: {:?}", self);
+        self.version += 1;
+        debug!("Post-chargeback: {:?}", redact(self));
+
+        Ok(())
+    }
+
+    /// Debit a fee assessed by the bank, e.g. for a chargeback, from the available and total
+    /// balances. Fees are assessed even on a locked account, and may leave its available balance
+    /// negative.
+    pub fn charge_fee(&mut self, amount: &Amount) -> Result<(), BankingError> {
+        debug!("Pre-fee: {:?}", redact(self));
+        let (available, total) = (debit(self.available, amount)?, debit(self.total, amount)?);
+        self.available = available;
+        self.total = total;
+        self.version += 1;
+        debug!("Post-fee: {:?}", redact(self));
+
+        Ok(())
+    }
+
+    /// Take back the unspent part of an expired promotional credit from the available and total
+    /// balances. Like fees, this is done even on a locked account.
+    pub fn claw_back(&mut self, amount: &Amount) -> Result<(), BankingError> {
+        debug!("Pre-clawback: {:?}", redact(self));
+        let (available, total) = (debit(self.available, amount)?, debit(self.total, amount)?);
+        self.available = available;
+        self.total = total;
+        self.version += 1;
+        debug!("Post-clawback: {:?}", redact(self));
+
+        Ok(())
+    }
+
+    /// Hold the specified value for a prepared withdrawal, moving it from the available to the held
+    /// balance. Unlike a dispute, this requires the funds to be available.
+    pub fn reserve(&mut self, amount: &Amount) -> Result<(), BankingError> {
+        if self.locked {
+            return Err(BankingError::AccountLocked);
+        }
+
+        if self.frozen {
+            return Err(BankingError::AccountFrozen);
+        }
+
+        if self.dormancy == Dormancy::Blocked {
+            return Err(BankingError::AccountDormant);
+        }
+
+        if self.available < *amount {
+            return Err(BankingError::InsufficientFunds);
+        }
+
+        debug!("Pre-reserve: {:?}", redact(self));
+        let (available, held) = (debit(self.available, amount)?, credit(self.held, amount)?);
+        self.available = available;
+        self.held = held;
+        self.version += 1;
+        debug!("Post-reserve: {:?}", redact(self));
+
+        Ok(())
+    }
+
+    /// Return funds held by `reserve` to the available balance, when a prepared withdrawal is
+    /// aborted. This is allowed on a locked account, so that reservations can always be undone.
+    pub fn release(&mut self, amount: &Amount) -> Result<(), BankingError> {
+        debug!("Pre-release: {:?}", redact(self));
+        let (held, available) = (debit(self.held, amount)?, credit(self.available, amount)?);
+        self.held = held;
+        self.available = available;
+        self.version += 1;
+        debug!("Post-release: {:?}", redact(self));
+
+        Ok(())
+    }
+
+    /// Complete a withdrawal whose funds are held by `reserve`, removing them from the held and
+    /// total balances.
+    pub fn settle(&mut self, amount: &Amount) -> Result<(), BankingError> {
+        if self.locked {
+            return Err(BankingError::AccountLocked);
+        }
+
+        if self.frozen {
+            return Err(BankingError::AccountFrozen);
+        }
+
+        if self.dormancy == Dormancy::Blocked {
+            return Err(BankingError::AccountDormant);
+        }
+
+        debug!("Pre-settle: {:?}", redact(self));
+        let (held, total) = (debit(self.held, amount)?, debit(self.total, amount)?);
+        self.held = held;
+        self.total = total;
+        self.version += 1;
+        debug!("Post-settle: {:?}", redact(self));
 
         Ok(())
     }
+
+    /// Lock the account as a chargeback does, e.g. by an administrator, blocking transactions.
+    pub fn lock(&mut self) {
+        debug!("Pre-lock: {:?}", redact(self));
+        self.locked = true;
+        self.version += 1;
+        debug!("Post-lock: {:?}", redact(self));
+    }
+
+    /// Lift the lock placed on the account by a chargeback, allowing transactions again.
+    pub fn unlock(&mut self) {
+        debug!("Pre-unlock: {:?}", redact(self));
+        self.locked = false;
+        self.version += 1;
+        debug!("Post-unlock: {:?}", redact(self));
+    }
+
+    /// The most restrictive state the account is in.
+    pub fn status(&self) -> AccountStatus {
+        if self.locked {
+            AccountStatus::Locked
+        } else if self.frozen {
+            AccountStatus::Frozen
+        } else if self.dormancy != Dormancy::Active {
+            AccountStatus::Dormant
+        } else {
+            AccountStatus::Active
+        }
+    }
+
+    /// Flag the account as dormant, or active again, leaving any lock or freeze in place.
+    pub fn set_dormancy(&mut self, dormancy: Dormancy) {
+        debug!("Pre-dormancy: {:?}", redact(self));
+        self.dormancy = dormancy;
+        self.version += 1;
+        debug!("Post-dormancy: {:?}", redact(self));
+    }
+
+    /// Block withdrawals from the account, or allow them again, leaving any lock in place.
+    pub fn set_frozen(&mut self, frozen: bool) {
+        debug!("Pre-freeze: {:?}", redact(self));
+        self.frozen = frozen;
+        self.version += 1;
+        debug!("Post-freeze: {:?}", redact(self));
+    }
+}
+
+/// Add `amount` to a balance, failing rather than overflowing the range of `Amount`.
+fn credit(balance: Amount, amount: &Amount) -> Result<Amount, BankingError> {
+    balance.checked_add(*amount).ok_or(BankingError::AmountOverflow)
+}
+
+/// Subtract `amount` from a balance, failing rather than overflowing the range of `Amount`.
+fn debit(balance: Amount, amount: &Amount) -> Result<Amount, BankingError> {
+    balance.checked_sub(*amount).ok_or(BankingError::AmountOverflow)
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overflowing_deposit_leaves_account_unchanged() {
+        // SETUP
+        let mut account = Account::new(1);
+        account.deposit(&Amount::MAX).unwrap();
+        let before = account.clone();
+
+        // TEST
+        assert_eq!(Err(BankingError::AmountOverflow), account.deposit(&Amount::from(1u32)));
+        assert_eq!(before, account);
+    }
+
+    #[test]
+    fn overflowing_fee_leaves_account_unchanged() {
+        // SETUP
+        let mut account = Account::new(1);
+        account.charge_fee(&Amount::MAX).unwrap();
+        let before = account.clone();
+
+        // TEST
+        assert_eq!(Err(BankingError::AmountOverflow), account.charge_fee(&Amount::MAX));
+        assert_eq!(before, account);
+    }
+
+    #[test]
+    fn frozen_account_accepts_deposits_but_not_withdrawals() {
+        // SETUP
+        let mut account = Account::new(1);
+        account.set_frozen(true);
+
+        // TEST
+        assert_eq!(Ok(()), account.deposit(&Amount::from(5u32)));
+        assert_eq!(Err(BankingError::AccountFrozen), account.withdraw(&Amount::from(1u32)));
+        assert_eq!(Amount::from(5u32), account.available);
+    }
+
+    #[test]
+    fn blocked_account_rejects_withdrawals_until_active() {
+        // SETUP
+        let mut account = Account::new(1);
+        account.deposit(&Amount::from(5u32)).unwrap();
+        account.set_dormancy(Dormancy::Blocked);
+
+        // TEST
+        assert_eq!(Err(BankingError::AccountDormant), account.withdraw(&Amount::from(1u32)));
+        account.set_dormancy(Dormancy::Active);
+        assert_eq!(Ok(()), account.withdraw(&Amount::from(1u32)));
+        assert_eq!(Amount::from(4u32), account.total);
+    }
+
+    #[test]
+    fn dormant_account_still_allows_withdrawals() {
+        // SETUP
+        let mut account = Account::new(1);
+        account.deposit(&Amount::from(5u32)).unwrap();
+        account.set_dormancy(Dormancy::Dormant);
+
+        // TEST
+        assert_eq!(Ok(()), account.withdraw(&Amount::from(1u32)));
+    }
+
+    #[test]
+    fn reserve_moves_available_funds_to_held() {
+        // SETUP
+        let mut account = Account::new(1);
+        account.deposit(&Amount::from(5u32)).unwrap();
+
+        // TEST
+        assert_eq!(Ok(()), account.reserve(&Amount::from(2u32)));
+        assert_eq!(
+            (Amount::from(3u32), Amount::from(2u32), Amount::from(5u32)),
+            (account.available, account.held, account.total)
+        );
+    }
+
+    #[test]
+    fn reserve_requires_available_funds() {
+        // SETUP
+        let mut account = Account::new(1);
+        account.deposit(&Amount::from(5u32)).unwrap();
+        let before = account.clone();
+
+        // TEST
+        assert_eq!(Err(BankingError::InsufficientFunds), account.reserve(&Amount::from(6u32)));
+        assert_eq!(before, account);
+    }
+
+    #[test]
+    fn settle_removes_reserved_funds_from_held_and_total() {
+        // SETUP
+        let mut account = Account::new(1);
+        account.deposit(&Amount::from(5u32)).unwrap();
+        account.reserve(&Amount::from(2u32)).unwrap();
+
+        // TEST
+        assert_eq!(Ok(()), account.settle(&Amount::from(2u32)));
+        assert_eq!(
+            (Amount::from(3u32), Amount::zero(), Amount::from(3u32)),
+            (account.available, account.held, account.total)
+        );
+    }
+
+    #[test]
+    fn release_returns_reserved_funds_even_when_locked() {
+        // SETUP
+        let mut account = Account::new(1);
+        account.deposit(&Amount::from(5u32)).unwrap();
+        account.reserve(&Amount::from(2u32)).unwrap();
+        account.lock();
+
+        // TEST
+        assert_eq!(Err(BankingError::AccountLocked), account.settle(&Amount::from(2u32)));
+        assert_eq!(Ok(()), account.release(&Amount::from(2u32)));
+        assert_eq!((Amount::from(5u32), Amount::zero()), (account.available, account.held));
+    }
+
+    #[test]
+    fn claw_back_debits_a_locked_account() {
+        // SETUP
+        let mut account = Account::new(1);
+        account.deposit(&Amount::from(5u32)).unwrap();
+        account.lock();
+
+        // TEST
+        assert_eq!(Ok(()), account.claw_back(&Amount::from(2u32)));
+        assert_eq!((Amount::from(3u32), Amount::from(3u32)), (account.available, account.total));
+    }
+
+    #[test]
+    fn unlock_allows_transactions_again() {
+        // SETUP
+        let mut account = Account::new(1);
+        account.lock();
+
+        // TEST
+        assert_eq!(Err(BankingError::AccountLocked), account.deposit(&Amount::from(1u32)));
+        account.unlock();
+        assert_eq!(Ok(()), account.deposit(&Amount::from(1u32)));
+    }
+
+    #[test]
+    fn every_change_increments_the_version() {
+        // SETUP
+        let mut account = Account::new(1);
+
+        // TEST
+        account.deposit(&Amount::from(5u32)).unwrap();
+        account.set_frozen(true);
+        account.lock();
+        assert_eq!(3, account.version);
+        assert_eq!(Err(BankingError::AccountLocked), account.deposit(&Amount::from(1u32)));
+        assert_eq!(3, account.version);
+    }
+
+    #[test]
+    fn status_is_the_most_restrictive_state() {
+        // SETUP
+        let mut account = Account::new(1);
+
+        // TEST
+        assert_eq!(AccountStatus::Active, account.status());
+        account.set_dormancy(Dormancy::Dormant);
+        assert_eq!(AccountStatus::Dormant, account.status());
+        account.set_frozen(true);
+        assert_eq!(AccountStatus::Frozen, account.status());
+        account.lock();
+        assert_eq!(AccountStatus::Locked, account.status());
+    }
+
+    #[test]
+    fn statistics_count_open_and_settled_disputes() {
+        // SETUP
+        let mut statistics = ClientStatistics::default();
+        let transactions = [
+            Transaction::make(TransactionType::Deposit, 1, 1, 5, false),
+            Transaction::make(TransactionType::Deposit, 1, 2, 5, false),
+            Transaction::make(TransactionType::Withdrawal, 1, 3, 1, false),
+            Transaction::make_dispute(1, 1),
+            Transaction::make_dispute(1, 2),
+            Transaction::make_resolve(1, 1),
+            Transaction::make_chargeback(1, 2),
+        ];
+
+        // TEST
+        transactions.iter().for_each(|transaction| statistics.record(transaction));
+        let expected = ClientStatistics {
+            deposits: 2,
+            withdrawals: 1,
+            disputes: 2,
+            open_disputes: 0,
+            chargebacks: 1,
+            last_tx: Some(2),
+        };
+        assert_eq!(expected, statistics);
+    }
+
+    #[test]
+    fn annotations_merge_replaces_labels_and_keeps_notes_in_order() {
+        // SETUP
+        let mut annotations = Annotations {
+            notes: vec![String::from("first")],
+            labels: vec![(String::from("ticket"), String::from("SUP-1"))].into_iter().collect(),
+        };
+        let other = Annotations {
+            notes: vec![String::from("second")],
+            labels: vec![(String::from("ticket"), String::from("SUP-2"))].into_iter().collect(),
+        };
+
+        // TEST
+        annotations.merge(other);
+        assert_eq!("ticket=SUP-2; first; second", annotations.summary());
+        assert!(!annotations.is_empty());
+        assert!(Annotations::default().is_empty());
+    }
 }
+//endregion
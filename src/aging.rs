@@ -0,0 +1,341 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Dispute aging
+//! A `DisputeAging` is a sink that tracks how long each dispute of a run has been open, both in
+//! records processed since it was opened and, for input with timestamps, in time. Once the run is
+//! over, `DisputeAging::overdue` lists the disputes still open beyond an `Sla`, oldest first, so
+//! that they can be settled before the card network's deadline. It is what the
+//! `report --dispute-sla` command prints.
+//!
+//! As with the retention policy, age in time is measured against the latest timestamp seen, and a
+//! dispute opened by a record without a timestamp takes the latest one seen before it.
+use crate::account::ClientId;
+use crate::analytics::{metric_writer, MetricRow};
+use crate::config::Inactivity;
+use crate::errors::BankingError;
+use crate::locale::Locale;
+use crate::provenance::Provenance;
+use crate::sink::ResultSink;
+use crate::transaction::{Transaction, TransactionType, TxId};
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io;
+use std::str::FromStr;
+
+/// `Sla` is how long a dispute may stay open before it is overdue.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sla {
+    /// Overdue once more than this many records have been processed since it was opened.
+    Records(usize),
+    /// Overdue once open for longer than this.
+    Age(Duration),
+}
+
+impl FromStr for Sla {
+    type Err = String;
+
+    /// Parses a number of records, e.g. `10000`, or a duration as for `Inactivity`, e.g. `30d`.
+    fn from_str(value: &str) -> Result<Sla, String> {
+        match value.parse() {
+            Ok(Inactivity::Records(records)) => Ok(Sla::Records(records)),
+            Ok(Inactivity::Period(age)) => Ok(Sla::Age(age)),
+            Err(_) => Err(format!(
+                "invalid SLA: {} (expected a number of records, or a duration such as 30d)",
+                value
+            )),
+        }
+    }
+}
+
+/// `OpenedDispute` is where in the run a dispute was opened.
+#[derive(Clone, Debug)]
+struct OpenedDispute {
+    client: ClientId,
+    index: usize,
+    timestamp: Option<DateTime<Utc>>,
+    source: Option<Provenance>,
+}
+
+/// `DisputeAging` is a sink that tracks the disputes open during a run.
+#[derive(Clone, Debug, Default)]
+pub struct DisputeAging {
+    open: HashMap<TxId, OpenedDispute>,
+    /// The number of records processed.
+    records: usize,
+    latest: Option<DateTime<Utc>>,
+}
+
+impl DisputeAging {
+    pub fn new() -> DisputeAging {
+        DisputeAging::default()
+    }
+
+    /// Returns every dispute still open, oldest first.
+    pub fn open(&self) -> Vec<AgedDispute> {
+        let mut open: Vec<(usize, AgedDispute)> = self
+            .open
+            .iter()
+            .map(|(&tx, opened)| {
+                let aged = AgedDispute {
+                    client: opened.client,
+                    tx,
+                    records: self.records - opened.index - 1,
+                    seconds: opened
+                        .timestamp
+                        .zip(self.latest)
+                        .map(|(opened, latest)| (latest - opened).num_seconds()),
+                    source: opened.source.clone(),
+                };
+                (opened.index, aged)
+            })
+            .collect();
+        open.sort_unstable_by_key(|(index, _)| *index);
+        open.into_iter().map(|(_, aged)| aged).collect()
+    }
+
+    /// Returns the disputes open beyond `sla`, oldest first.
+    pub fn overdue(&self, sla: Sla) -> Vec<AgedDispute> {
+        self.open()
+            .into_iter()
+            .filter(|aged| match sla {
+                Sla::Records(records) => aged.records > records,
+                Sla::Age(age) => aged.seconds.is_some_and(|seconds| seconds > age.num_seconds()),
+            })
+            .collect()
+    }
+}
+
+impl ResultSink for DisputeAging {
+    fn record(&mut self, index: usize, transaction: &Transaction, result: Result<(), BankingError>) {
+        self.records = self.records.max(index + 1);
+        self.latest = self.latest.max(transaction.timestamp);
+        if result.is_err() {
+            return;
+        }
+        match transaction.kind {
+            TransactionType::Dispute => {
+                let opened = OpenedDispute {
+                    client: transaction.client,
+                    index,
+                    timestamp: transaction.timestamp.or(self.latest),
+                    source: transaction.provenance.clone(),
+                };
+                self.open.insert(transaction.tx, opened);
+            }
+            TransactionType::Resolve | TransactionType::Chargeback => {
+                self.open.remove(&transaction.tx);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// `AgedDispute` is a dispute still open at the end of a run, with how long it has been open.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct AgedDispute {
+    pub client: ClientId,
+    /// The disputed transaction.
+    pub tx: TxId,
+    /// The number of records processed since the dispute was opened.
+    pub records: usize,
+    /// The seconds since the dispute was opened, if the input has timestamps.
+    pub seconds: Option<i64>,
+    /// Where the record opening the dispute was read from, if known. That of the disputed
+    /// transaction is kept with it by the bank.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<Provenance>,
+}
+
+/// Write the given disputes as CSV rows of a report, one `overdue_dispute` row per dispute keyed by
+/// the disputed transaction, with its age in the unit of `sla`.
+pub fn write_rows<W: io::Write>(overdue: &[AgedDispute], sla: Sla, wtr: &mut csv::Writer<W>) -> Result<(), csv::Error> {
+    for aged in overdue {
+        let value = match sla {
+            Sla::Records(_) => aged.records.to_string(),
+            Sla::Age(_) => aged.seconds.map(|seconds| format!("{}s", seconds)).unwrap_or_default(),
+        };
+        wtr.serialize(MetricRow {
+            metric: "overdue_dispute",
+            key: &aged.tx.to_string(),
+            value,
+        })?;
+    }
+    Ok(())
+}
+
+/// As `write_rows`, writing a complete CSV report of the disputes with its header row in the given
+/// locale.
+pub fn write_csv<W: io::Write>(overdue: &[AgedDispute], sla: Sla, writer: W, locale: Locale) -> Result<(), csv::Error> {
+    let mut wtr = metric_writer(writer, locale)?;
+    write_rows(overdue, sla, &mut wtr)?;
+    wtr.flush()?;
+    Ok(())
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank::Bank;
+    use crate::testkit::Scenario;
+
+    fn at(time: &str) -> Option<DateTime<Utc>> {
+        Some(DateTime::parse_from_rfc3339(time).unwrap().with_timezone(&Utc))
+    }
+
+    fn aging_of(scenario: Scenario) -> DisputeAging {
+        let mut aging = DisputeAging::new();
+        Bank::new().process_all_with(scenario.transactions().to_vec(), &mut aging);
+        aging
+    }
+
+    #[test]
+    fn disputes_open_beyond_the_sla_are_overdue() {
+        // SETUP
+        let aging = aging_of(
+            Scenario::new()
+                .deposit(1, 5)
+                .dispute(1, 1)
+                .deposit(2, 1)
+                .deposit(2, 1)
+                .deposit(2, 1),
+        );
+
+        // TEST
+        let overdue = aging.overdue(Sla::Records(2));
+        assert_eq!(
+            vec![(1, 1, 3, None)],
+            overdue
+                .iter()
+                .map(|aged| (aged.client, aged.tx, aged.records, aged.seconds))
+                .collect::<Vec<_>>()
+        );
+        assert!(aging.overdue(Sla::Records(3)).is_empty());
+    }
+
+    #[test]
+    fn settled_disputes_are_no_longer_open() {
+        // SETUP
+        let aging = aging_of(
+            Scenario::new()
+                .deposit(1, 5)
+                .deposit(2, 5)
+                .dispute(1, 1)
+                .dispute(2, 2)
+                .resolve(1, 1)
+                .chargeback(2, 2),
+        );
+
+        // TEST
+        assert!(aging.open().is_empty());
+    }
+
+    #[test]
+    fn open_disputes_are_listed_oldest_first() {
+        // SETUP
+        let aging = aging_of(
+            Scenario::new()
+                .deposit(1, 5)
+                .deposit(2, 5)
+                .deposit(3, 5)
+                .dispute(3, 3)
+                .dispute(1, 1)
+                .dispute(2, 2),
+        );
+
+        // TEST
+        let open: Vec<TxId> = aging.open().iter().map(|aged| aged.tx).collect();
+        assert_eq!(vec![3, 1, 2], open);
+    }
+
+    #[test]
+    fn rejected_disputes_are_not_tracked() {
+        // SETUP
+        let aging = aging_of(Scenario::new().deposit(1, 5).dispute(2, 1).dispute(1, 9));
+
+        // TEST
+        assert!(aging.open().is_empty());
+    }
+
+    #[test]
+    fn age_is_measured_against_the_latest_timestamp() {
+        // SETUP
+        let scenario = Scenario::new()
+            .then(Transaction {
+                timestamp: at("2021-03-01T12:00:00Z"),
+                ..Transaction::make(TransactionType::Deposit, 1, 1, 5, false)
+            })
+            .then(Transaction {
+                timestamp: at("2021-03-02T12:00:00Z"),
+                ..Transaction::make_dispute(1, 1)
+            })
+            .then(Transaction {
+                timestamp: at("2021-03-04T12:00:00Z"),
+                ..Transaction::make(TransactionType::Deposit, 2, 2, 1, false)
+            });
+        let aging = aging_of(scenario);
+
+        // TEST
+        assert_eq!(Some(2 * 86400), aging.open()[0].seconds);
+        assert_eq!(1, aging.overdue(Sla::Age(Duration::days(1))).len());
+        assert!(aging.overdue(Sla::Age(Duration::days(2))).is_empty());
+    }
+
+    #[test]
+    fn dispute_without_a_timestamp_takes_the_latest_one_seen() {
+        // SETUP
+        let scenario = Scenario::new()
+            .then(Transaction {
+                timestamp: at("2021-03-01T12:00:00Z"),
+                ..Transaction::make(TransactionType::Deposit, 1, 1, 5, false)
+            })
+            .dispute(1, 1)
+            .then(Transaction {
+                timestamp: at("2021-03-01T18:00:00Z"),
+                ..Transaction::make(TransactionType::Deposit, 2, 3, 1, false)
+            });
+        let aging = aging_of(scenario);
+
+        // TEST
+        assert_eq!(Some(6 * 3600), aging.open()[0].seconds);
+    }
+
+    #[test]
+    fn disputes_are_not_overdue_by_age_without_timestamps() {
+        // SETUP
+        let aging = aging_of(Scenario::new().deposit(1, 5).dispute(1, 1).deposit(2, 1));
+
+        // TEST
+        assert_eq!(None, aging.open()[0].seconds);
+        assert!(aging.overdue(Sla::Age(Duration::zero())).is_empty());
+    }
+
+    #[test]
+    fn sla_is_read_as_records_or_a_duration() {
+        // TEST
+        assert_eq!(Ok(Sla::Records(10000)), "10000".parse());
+        assert_eq!(Ok(Sla::Age(Duration::days(30))), "30d".parse());
+        assert!("30 days".parse::<Sla>().is_err());
+    }
+
+    #[test]
+    fn overdue_disputes_are_written_in_the_unit_of_the_sla() {
+        // SETUP
+        let aged = AgedDispute {
+            client: 1,
+            tx: 7,
+            records: 4,
+            seconds: Some(90),
+            source: None,
+        };
+
+        // TEST
+        let mut records = Vec::new();
+        write_csv(std::slice::from_ref(&aged), Sla::Records(3), &mut records, Locale::En).unwrap();
+        assert_eq!("metric,key,value\noverdue_dispute,7,4\n", String::from_utf8(records).unwrap());
+        let mut age = Vec::new();
+        write_csv(&[aged], Sla::Age(Duration::minutes(1)), &mut age, Locale::En).unwrap();
+        assert_eq!("metric,key,value\noverdue_dispute,7,90s\n", String::from_utf8(age).unwrap());
+    }
+}
+//endregion
@@ -0,0 +1,296 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Balance alerts
+//! An `AlertMonitor` is a sink that watches every account change against a set of `Threshold`s,
+//! such as available funds falling below zero or held funds rising above a limit, and tells an
+//! `AlertObserver` as soon as an account crosses one. An account raises an alert once per
+//! crossing: it has to come back within the threshold before it can raise that alert again.
+//!
+//! On the command line, thresholds are given with `--alert`, e.g. `--alert 'available<0'
+//! --alert 'held>10000'`, and alerts are logged and, with `--alert-log`, written to a CSV file
+//! for whatever notifies treasury to pick up.
+use crate::account::{Account, ClientId};
+use crate::amount::{parse_amount, Amount, AmountFormat};
+use crate::errors::BankingError;
+use crate::redact::redact;
+use crate::sink::ResultSink;
+use crate::transaction::Transaction;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+/// `Threshold` is a limit on one of the balances of an account.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Threshold {
+    AvailableBelow(Amount),
+    HeldAbove(Amount),
+    TotalAbove(Amount),
+}
+
+impl Threshold {
+    /// Whether the account is outside this threshold.
+    pub fn breached_by(&self, account: &Account) -> bool {
+        match *self {
+            Threshold::AvailableBelow(limit) => account.available < limit,
+            Threshold::HeldAbove(limit) => account.held > limit,
+            Threshold::TotalAbove(limit) => account.total > limit,
+        }
+    }
+}
+
+/// Parses thresholds written as `available<AMOUNT`, `held>AMOUNT`, or `total>AMOUNT`.
+impl FromStr for Threshold {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Threshold, String> {
+        let (constructor, limit): (fn(Amount) -> Threshold, &str) = if let Some(limit) = value.strip_prefix("available<") {
+            (Threshold::AvailableBelow, limit)
+        } else if let Some(limit) = value.strip_prefix("held>") {
+            (Threshold::HeldAbove, limit)
+        } else if let Some(limit) = value.strip_prefix("total>") {
+            (Threshold::TotalAbove, limit)
+        } else {
+            return Err(format!(
+                "unsupported threshold: {} (expected available<AMOUNT, held>AMOUNT, or total>AMOUNT)",
+                value
+            ));
+        };
+        parse_amount(limit.trim(), &AmountFormat::new())
+            .map(constructor)
+            .map_err(|e| format!("invalid threshold amount \"{}\": {}", limit, e))
+    }
+}
+
+impl fmt::Display for Threshold {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Threshold::AvailableBelow(limit) => write!(f, "available<{}", limit),
+            Threshold::HeldAbove(limit) => write!(f, "held>{}", limit),
+            Threshold::TotalAbove(limit) => write!(f, "total>{}", limit),
+        }
+    }
+}
+
+/// `Alert` is raised when an account crosses a threshold, with the state of the account that
+/// crossed it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Alert {
+    pub threshold: Threshold,
+    pub account: Account,
+}
+
+/// `AlertObserver` is told of every alert as it is raised.
+pub trait AlertObserver {
+    fn alert(&mut self, alert: &Alert);
+}
+
+/// An absent observer ignores every alert.
+impl<O: AlertObserver> AlertObserver for Option<O> {
+    fn alert(&mut self, alert: &Alert) {
+        if let Some(observer) = self {
+            observer.alert(alert);
+        }
+    }
+}
+
+impl<F: FnMut(&Alert)> AlertObserver for F {
+    fn alert(&mut self, alert: &Alert) {
+        self(alert)
+    }
+}
+
+/// `AlertMonitor` is a sink that checks every changed account against its thresholds, logging
+/// each alert and passing it on to its observer.
+pub struct AlertMonitor<O: AlertObserver> {
+    thresholds: Vec<Threshold>,
+    observer: O,
+    /// The accounts currently outside a threshold, by the threshold's index.
+    breached: HashSet<(ClientId, usize)>,
+    raised: usize,
+}
+
+impl<O: AlertObserver> AlertMonitor<O> {
+    pub fn new(thresholds: Vec<Threshold>, observer: O) -> AlertMonitor<O> {
+        AlertMonitor {
+            thresholds,
+            observer,
+            breached: HashSet::new(),
+            raised: 0,
+        }
+    }
+
+    /// The number of alerts raised so far.
+    pub fn raised(&self) -> usize {
+        self.raised
+    }
+
+    /// Returns the observer, e.g. to flush it once processing has finished.
+    pub fn into_observer(self) -> O {
+        self.observer
+    }
+}
+
+impl<O: AlertObserver> ResultSink for AlertMonitor<O> {
+    fn record(&mut self, _index: usize, _transaction: &Transaction, _result: Result<(), BankingError>) {}
+
+    fn account_updated(&mut self, account: &Account) {
+        for (index, threshold) in self.thresholds.iter().enumerate() {
+            let key = (account.client, index);
+            if !threshold.breached_by(account) {
+                self.breached.remove(&key);
+            } else if self.breached.insert(key) {
+                warn!("Account crossed alert threshold {}: {:?}", threshold, redact(account));
+                self.raised += 1;
+                self.observer.alert(&Alert {
+                    threshold: *threshold,
+                    account: account.clone(),
+                });
+            }
+        }
+    }
+}
+
+/// `AlertRecord` is the row written for an alert.
+#[derive(Serialize)]
+struct AlertRecord {
+    threshold: String,
+    client: ClientId,
+    available: Amount,
+    held: Amount,
+    total: Amount,
+    locked: bool,
+}
+
+/// `AlertWriter` is an observer that writes every alert as a CSV row to the given writer. Failures
+/// to write are logged rather than interrupting processing.
+pub struct AlertWriter<W: io::Write> {
+    writer: csv::Writer<W>,
+}
+
+impl<W: io::Write> AlertWriter<W> {
+    pub fn new(writer: W) -> AlertWriter<W> {
+        AlertWriter {
+            writer: csv::Writer::from_writer(writer),
+        }
+    }
+
+    /// Flush any buffered alerts to the underlying writer.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl<W: io::Write> AlertObserver for AlertWriter<W> {
+    fn alert(&mut self, alert: &Alert) {
+        let record = AlertRecord {
+            threshold: alert.threshold.to_string(),
+            client: alert.account.client,
+            available: alert.account.available,
+            held: alert.account.held,
+            total: alert.account.total,
+            locked: alert.account.locked,
+        };
+        if let Err(e) = self.writer.serialize(record) {
+            error!(
+                "Failed to write alert for account {}. Aborted with error: {}",
+                redact(&alert.account.client),
+                e
+            );
+        }
+    }
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank::Bank;
+    use crate::testkit::Scenario;
+
+    /// The thresholds and clients of the alerts raised by processing `scenario`.
+    fn alerts_of(thresholds: &[&str], scenario: Scenario) -> Vec<(String, ClientId)> {
+        let thresholds = thresholds.iter().map(|threshold| threshold.parse().unwrap()).collect();
+        let mut alerts = Vec::new();
+        let observer = |alert: &Alert| alerts.push((alert.threshold.to_string(), alert.account.client));
+        let mut monitor = AlertMonitor::new(thresholds, observer);
+        Bank::new().process_all_with(scenario.transactions().to_vec(), &mut monitor);
+        alerts
+    }
+
+    #[test]
+    fn alert_is_raised_when_an_account_crosses_a_threshold() {
+        // TEST
+        let alerts = alerts_of(&["total>4"], Scenario::new().deposit(1, 3).deposit(2, 1).deposit(1, 2));
+        assert_eq!(vec![(String::from("total>4"), 1)], alerts);
+    }
+
+    #[test]
+    fn alert_is_raised_once_while_the_account_stays_outside_the_threshold() {
+        // TEST
+        let alerts = alerts_of(&["held>4"], Scenario::new().deposit(1, 5).deposit(1, 5).dispute(1, 1).dispute(1, 2));
+        assert_eq!(vec![(String::from("held>4"), 1)], alerts);
+    }
+
+    #[test]
+    fn alert_is_raised_again_after_the_account_comes_back_within_the_threshold() {
+        // TEST
+        let alerts = alerts_of(&["held>4"], Scenario::new().deposit(1, 5).dispute(1, 1).resolve(1, 1).dispute(1, 1));
+        assert_eq!(vec![(String::from("held>4"), 1), (String::from("held>4"), 1)], alerts);
+    }
+
+    #[test]
+    fn each_threshold_raises_its_own_alert() {
+        // TEST
+        let alerts = alerts_of(
+            &["held>4", "available<0"],
+            Scenario::new().deposit(1, 5).withdraw(1, 3).dispute(1, 1),
+        );
+        assert_eq!(vec![(String::from("held>4"), 1), (String::from("available<0"), 1)], alerts);
+    }
+
+    #[test]
+    fn raised_alerts_are_counted() {
+        // SETUP
+        let mut monitor = AlertMonitor::new(vec![Threshold::TotalAbove(Amount::from(1u32))], None::<AlertWriter<Vec<u8>>>);
+        let scenario = Scenario::new().deposit(1, 2).deposit(2, 2).deposit(3, 1);
+
+        // TEST
+        Bank::new().process_all_with(scenario.transactions().to_vec(), &mut monitor);
+        assert_eq!(2, monitor.raised());
+    }
+
+    #[test]
+    fn thresholds_are_read_as_written() {
+        // TEST
+        for threshold in ["available<0", "held>10000", "total>2.5"] {
+            assert_eq!(threshold, threshold.parse::<Threshold>().unwrap().to_string());
+        }
+    }
+
+    #[test]
+    fn unsupported_thresholds_are_rejected() {
+        // TEST
+        assert!("held>=4".parse::<Threshold>().is_err());
+        assert!("available>0".parse::<Threshold>().is_err());
+        assert!("total>x".parse::<Threshold>().is_err());
+    }
+
+    #[test]
+    fn alert_writer_writes_the_account_that_crossed() {
+        // SETUP
+        let mut account = Account::new(7);
+        account.deposit(&Amount::from(5u32)).unwrap();
+        let mut writer = AlertWriter::new(Vec::new());
+
+        // TEST
+        writer.alert(&Alert {
+            threshold: Threshold::TotalAbove(Amount::from(4u32)),
+            account,
+        });
+        writer.flush().unwrap();
+        let written = String::from_utf8(writer.writer.get_ref().clone()).unwrap();
+        assert_eq!("threshold,client,available,held,total,locked\ntotal>4,7,5,0,5,false\n", written);
+    }
+}
+//endregion
@@ -0,0 +1,433 @@
+#![forbid(unsafe_code)] // for good measure
+//! `Amount` is the numeric type used for every balance and transaction value in this crate.
+//!
+//! By default it is simply an alias for `rust_decimal::Decimal`. Building with the `minor-units`
+//! feature swaps it for `MinorUnits`, a thin wrapper around an `i64` count of 1/10000ths. Integer
+//! arithmetic is both faster and exact, and since we never keep more than four decimal places
+//! there is nothing to lose. Conversion to and from `Decimal` happens only at the I/O boundary
+//! (serialization and deserialization), so the on-the-wire format is identical in both modes.
+//!
+//! Incoming amounts are parsed by `parse_amount` rather than `Decimal`'s `FromStr`, which happily
+//! accepts scientific notation and other surprises. The accepted format is controlled by an
+//! `AmountFormat`, given to each reader as part of the `RecordOptions` of its source.
+use crate::errors::AmountParseError;
+#[cfg(feature = "minor-units")]
+pub use minor_units::MinorUnits;
+use rust_decimal::Decimal;
+use serde::de::{self, Visitor};
+use serde::Deserializer;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+#[cfg(not(feature = "minor-units"))]
+pub type Amount = Decimal;
+
+#[cfg(feature = "minor-units")]
+pub type Amount = MinorUnits;
+
+//region Parsing
+/// `AmountFormat` describes what an acceptable textual amount looks like.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AmountFormat {
+    /// Reject amounts with more digits after the decimal separator than this. When `None`, excess
+    /// precision is accepted and later rounded.
+    pub max_decimal_places: Option<u32>,
+    /// Use `,` as the decimal separator instead of `.` (common in European exports).
+    pub decimal_comma: bool,
+}
+
+impl AmountFormat {
+    pub const fn new() -> AmountFormat {
+        AmountFormat {
+            max_decimal_places: None,
+            decimal_comma: false,
+        }
+    }
+
+    fn decimal_separator(&self) -> char {
+        if self.decimal_comma {
+            ','
+        } else {
+            '.'
+        }
+    }
+}
+
+impl Default for AmountFormat {
+    fn default() -> AmountFormat {
+        AmountFormat::new()
+    }
+}
+
+/// Strictly parse a textual amount. An amount consists of an optional leading sign, at least one
+/// digit, and optionally a single decimal separator followed by at least one digit. Anything else
+/// (exponents, thousands separators, stray characters) is rejected.
+pub fn parse_amount(text: &str, format: &AmountFormat) -> Result<Amount, AmountParseError> {
+    let separator = format.decimal_separator();
+    let unsigned = text.strip_prefix('-').or_else(|| text.strip_prefix('+')).unwrap_or(text);
+    if unsigned.is_empty() {
+        return Err(AmountParseError::Empty);
+    }
+
+    let (integer, fraction) = match unsigned.find(separator) {
+        Some(index) => (&unsigned[..index], Some(&unsigned[index + 1..])),
+        None => (unsigned, None),
+    };
+    for c in integer.chars().chain(fraction.unwrap_or("").chars()) {
+        match c {
+            '0'..='9' => {}
+            'e' | 'E' => return Err(AmountParseError::ScientificNotation),
+            ',' | '.' | '\'' | '_' | ' ' => return Err(AmountParseError::ThousandsSeparator),
+            _ => return Err(AmountParseError::InvalidCharacter(c)),
+        }
+    }
+    if integer.is_empty() || fraction == Some("") {
+        return Err(AmountParseError::MissingDigits);
+    }
+    if let (Some(fraction), Some(max)) = (fraction, format.max_decimal_places) {
+        if fraction.len() > max as usize {
+            return Err(AmountParseError::TooManyDecimalPlaces(max));
+        }
+    }
+
+    let negative = text.starts_with('-');
+    let value = match from_digits(integer, fraction.unwrap_or(""), negative) {
+        Some(value) => value,
+        // more digits than a Decimal holds exactly, which `FromStr` rounds
+        None => {
+            let normalized = match fraction {
+                Some(fraction) => format!("{}.{}", integer, fraction),
+                None => integer.to_string(),
+            };
+            let mut value = Decimal::from_str(&normalized).map_err(|_| AmountParseError::OutOfRange)?;
+            value.set_sign_negative(negative);
+            value
+        }
+    };
+    from_decimal(value.normalize()).ok_or(AmountParseError::OutOfRange)
+}
+
+/// Build a `Decimal` from already validated digits without allocating, as this runs once per
+/// record. Returns `None` if the digits do not fit in a `Decimal` exactly.
+fn from_digits(integer: &str, fraction: &str, negative: bool) -> Option<Decimal> {
+    let mut mantissa: i128 = 0;
+    for digit in integer.bytes().chain(fraction.bytes()) {
+        mantissa = mantissa.checked_mul(10)?.checked_add(i128::from(digit - b'0'))?;
+    }
+    if negative {
+        mantissa = -mantissa;
+    }
+    Decimal::try_from_i128_with_scale(mantissa, u32::try_from(fraction.len()).ok()?).ok()
+}
+
+/// Convert a `Decimal` into an `Amount`. Returns `None` if the value can not be represented.
+#[cfg(not(feature = "minor-units"))]
+pub fn from_decimal(value: Decimal) -> Option<Amount> {
+    Some(value)
+}
+
+/// Convert a `Decimal` into an `Amount`. Returns `None` if the value can not be represented.
+#[cfg(feature = "minor-units")]
+pub fn from_decimal(value: Decimal) -> Option<Amount> {
+    MinorUnits::from_decimal(value)
+}
+
+/// Convert an `Amount` into a `Decimal`, e.g. for output formats with a native decimal type.
+#[cfg(not(feature = "minor-units"))]
+pub fn to_decimal(amount: Amount) -> Decimal {
+    amount
+}
+
+/// Convert an `Amount` into a `Decimal`, e.g. for output formats with a native decimal type.
+#[cfg(feature = "minor-units")]
+pub fn to_decimal(amount: Amount) -> Decimal {
+    amount.to_decimal()
+}
+
+/// Serde hook for optional amount fields, parsing with the default `AmountFormat`. Empty fields
+/// deserialize to `None`. Textual formats always hand us a string; self-describing binary formats
+/// may instead carry a number, which is accepted as long as it would have been accepted as text.
+pub fn deserialize_amount<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Amount>, D::Error> {
+    deserializer.deserialize_option(AmountVisitor)
+}
+
+struct AmountVisitor;
+
+impl<'de> Visitor<'de> for AmountVisitor {
+    type Value = Option<Amount>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a decimal amount")
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Option<Amount>, E> {
+        Ok(None)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Option<Amount>, E> {
+        Ok(None)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Option<Amount>, D::Error> {
+        deserializer.deserialize_str(AmountVisitor)
+    }
+
+    fn visit_str<E: de::Error>(self, text: &str) -> Result<Option<Amount>, E> {
+        if text.trim().is_empty() {
+            return Ok(None);
+        }
+        parse_amount(text.trim(), &AmountFormat::new())
+            .map(Some)
+            .map_err(|e| E::custom(format!("invalid amount \"{}\": {}", text, e)))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Option<Amount>, E> {
+        self.visit_str(&value.to_string())
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Option<Amount>, E> {
+        self.visit_str(&value.to_string())
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Option<Amount>, E> {
+        self.visit_str(&value.to_string())
+    }
+}
+//endregion
+
+#[cfg(feature = "minor-units")]
+mod minor_units {
+    use rust_decimal::prelude::*;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::convert::TryFrom;
+    use std::fmt;
+    use std::ops::{Add, AddAssign, Neg, Sub, SubAssign};
+
+    /// Number of decimal places represented by a single minor unit.
+    const MINOR_UNIT_PLACES: u32 = 4;
+    /// Number of minor units in a single major unit.
+    const SCALE: i64 = 10_000;
+
+    /// `MinorUnits` represents a monetary amount as a whole number of 1/10000ths.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct MinorUnits(i64);
+
+    impl MinorUnits {
+        /// The largest representable amount. Mirrors `Decimal::MAX`.
+        pub const MAX: MinorUnits = MinorUnits(i64::MAX);
+        /// The smallest representable amount. Mirrors `Decimal::MIN`.
+        pub const MIN: MinorUnits = MinorUnits(i64::MIN);
+
+        /// Convert a `Decimal` into minor units, rounding anything beyond four decimal places using
+        /// "Bankers Rounding". Returns `None` if the value does not fit in an `i64`.
+        pub fn from_decimal(value: Decimal) -> Option<MinorUnits> {
+            let mut rounded = value.round_dp(MINOR_UNIT_PLACES);
+            rounded.rescale(MINOR_UNIT_PLACES);
+            i64::try_from(rounded.mantissa()).ok().map(MinorUnits)
+        }
+
+        /// Convert back into a normalized `Decimal`, e.g. for output.
+        pub fn to_decimal(self) -> Decimal {
+            Decimal::new(self.0, MINOR_UNIT_PLACES).normalize()
+        }
+
+        /// Round to the specified number of decimal places. Mirrors `Decimal::round_dp` so that call
+        /// sites do not need to care which representation is in use.
+        pub fn round_dp(&self, decimal_places: u32) -> MinorUnits {
+            if decimal_places >= MINOR_UNIT_PLACES {
+                return *self;
+            }
+            MinorUnits::from_decimal(self.to_decimal().round_dp(decimal_places)).unwrap_or(*self)
+        }
+
+        /// Add, returning `None` on overflow. Mirrors `Decimal::checked_add`.
+        pub fn checked_add(self, rhs: MinorUnits) -> Option<MinorUnits> {
+            self.0.checked_add(rhs.0).map(MinorUnits)
+        }
+
+        /// Subtract, returning `None` on overflow. Mirrors `Decimal::checked_sub`.
+        pub fn checked_sub(self, rhs: MinorUnits) -> Option<MinorUnits> {
+            self.0.checked_sub(rhs.0).map(MinorUnits)
+        }
+
+        fn checked_from(value: i64) -> Option<MinorUnits> {
+            value.checked_mul(SCALE).map(MinorUnits)
+        }
+    }
+
+    impl From<u32> for MinorUnits {
+        fn from(value: u32) -> MinorUnits {
+            MinorUnits::checked_from(i64::from(value)).expect("amount overflowed")
+        }
+    }
+
+    impl From<i32> for MinorUnits {
+        fn from(value: i32) -> MinorUnits {
+            MinorUnits::checked_from(i64::from(value)).expect("amount overflowed")
+        }
+    }
+
+    impl fmt::Display for MinorUnits {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            self.to_decimal().fmt(f)
+        }
+    }
+
+    impl Add for MinorUnits {
+        type Output = MinorUnits;
+        fn add(self, rhs: MinorUnits) -> MinorUnits {
+            self.checked_add(rhs).expect("amount overflowed")
+        }
+    }
+
+    impl Sub for MinorUnits {
+        type Output = MinorUnits;
+        fn sub(self, rhs: MinorUnits) -> MinorUnits {
+            self.checked_sub(rhs).expect("amount overflowed")
+        }
+    }
+
+    impl Neg for MinorUnits {
+        type Output = MinorUnits;
+        fn neg(self) -> MinorUnits {
+            MinorUnits(self.0.checked_neg().expect("amount overflowed"))
+        }
+    }
+
+    impl AddAssign for MinorUnits {
+        fn add_assign(&mut self, rhs: MinorUnits) {
+            *self = *self + rhs;
+        }
+    }
+
+    impl AddAssign<&MinorUnits> for MinorUnits {
+        fn add_assign(&mut self, rhs: &MinorUnits) {
+            *self = *self + *rhs;
+        }
+    }
+
+    impl SubAssign for MinorUnits {
+        fn sub_assign(&mut self, rhs: MinorUnits) {
+            *self = *self - rhs;
+        }
+    }
+
+    impl SubAssign<&MinorUnits> for MinorUnits {
+        fn sub_assign(&mut self, rhs: &MinorUnits) {
+            *self = *self - *rhs;
+        }
+    }
+
+    impl Zero for MinorUnits {
+        fn zero() -> MinorUnits {
+            MinorUnits(0)
+        }
+
+        fn is_zero(&self) -> bool {
+            self.0 == 0
+        }
+    }
+
+    impl Serialize for MinorUnits {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            Serialize::serialize(&self.to_decimal(), serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for MinorUnits {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<MinorUnits, D::Error> {
+            let value = <Decimal as Deserialize>::deserialize(deserializer)?;
+            MinorUnits::from_decimal(value).ok_or_else(|| serde::de::Error::custom(format!("amount out of range: {}", value)))
+        }
+    }
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STRICT: AmountFormat = AmountFormat {
+        max_decimal_places: Some(4),
+        decimal_comma: false,
+    };
+    const EUROPEAN: AmountFormat = AmountFormat {
+        max_decimal_places: Some(4),
+        decimal_comma: true,
+    };
+
+    #[test]
+    fn parse_plain_amounts() -> Result<(), AmountParseError> {
+        assert_eq!(Amount::from(15u32), parse_amount("15", &STRICT)?);
+        assert_eq!(from_decimal(Decimal::new(15, 1)).unwrap(), parse_amount("1.5", &STRICT)?);
+        assert_eq!(from_decimal(Decimal::new(-15, 1)).unwrap(), parse_amount("-1.5", &STRICT)?);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_matches_decimal_from_str() {
+        let long = "0.12345678901234567890123456789";
+        for text in ["0", "-0", "007.50", "-12.0345", "79228162514264337593543950335", long] {
+            let expected = from_decimal(Decimal::from_str(text).unwrap().normalize());
+            assert_eq!(expected, parse_amount(text, &AmountFormat::new()).ok());
+        }
+    }
+
+    #[test]
+    fn parse_rejects_scientific_notation_and_thousands_separators() {
+        assert_eq!(Err(AmountParseError::ScientificNotation), parse_amount("1e3", &STRICT));
+        assert_eq!(Err(AmountParseError::ThousandsSeparator), parse_amount("1,000.00", &STRICT));
+        assert_eq!(Err(AmountParseError::ThousandsSeparator), parse_amount("1.000,00", &EUROPEAN));
+        assert_eq!(Err(AmountParseError::MissingDigits), parse_amount(".5", &STRICT));
+        assert_eq!(Err(AmountParseError::InvalidCharacter('$')), parse_amount("$5", &STRICT));
+    }
+
+    #[test]
+    fn parse_enforces_max_decimal_places_when_configured() -> Result<(), AmountParseError> {
+        assert_eq!(Err(AmountParseError::TooManyDecimalPlaces(4)), parse_amount("1.23456", &STRICT));
+        parse_amount("1.23456", &AmountFormat::new())?;
+        Ok(())
+    }
+
+    #[test]
+    fn parse_accepts_decimal_comma() -> Result<(), AmountParseError> {
+        assert_eq!(from_decimal(Decimal::new(15, 1)).unwrap(), parse_amount("1,5", &EUROPEAN)?);
+        Ok(())
+    }
+
+    #[cfg(feature = "minor-units")]
+    #[test]
+    fn minor_units_round_half_to_even() {
+        assert_eq!(from_decimal(Decimal::new(12342, 4)), from_decimal(Decimal::new(123425, 5)));
+        assert_eq!(from_decimal(Decimal::new(12344, 4)), from_decimal(Decimal::new(123435, 5)));
+        assert_eq!(from_decimal(Decimal::new(-12342, 4)), from_decimal(Decimal::new(-123425, 5)));
+    }
+
+    #[cfg(feature = "minor-units")]
+    #[test]
+    fn minor_units_reject_values_out_of_range() {
+        assert_eq!(Some(Amount::MAX), from_decimal(Decimal::new(i64::MAX, 4)));
+        assert_eq!(Some(Amount::MIN), from_decimal(Decimal::new(i64::MIN, 4)));
+        assert_eq!(None, from_decimal(Decimal::from(i64::MAX)));
+        assert_eq!(None, from_decimal(Decimal::from(i64::MIN)));
+    }
+
+    #[cfg(feature = "minor-units")]
+    #[test]
+    fn minor_units_checked_arithmetic_detects_overflow() {
+        let one = Amount::from(1u32);
+        assert_eq!(Some(Amount::from(2u32)), one.checked_add(one));
+        assert_eq!(Some(Amount::from(0u32)), one.checked_sub(one));
+        assert_eq!(None, Amount::MAX.checked_add(one));
+        assert_eq!(None, Amount::MIN.checked_sub(one));
+    }
+
+    #[cfg(feature = "minor-units")]
+    #[test]
+    #[should_panic(expected = "amount overflowed")]
+    fn minor_units_operators_panic_on_overflow() {
+        let _ = Amount::MAX + Amount::from(1u32);
+    }
+}
+//endregion
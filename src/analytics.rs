@@ -0,0 +1,411 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Run analytics
+//! An `AnalyticsCollector` is a sink that tallies the transactions of a run as they are processed.
+//! Once the run is over, `AnalyticsCollector::finish` combines the tallies with the bank's accounts
+//! into `Analytics`: the top accounts by total and by held funds, histograms of deposit and
+//! withdrawal amounts, how disputes were settled, and the average deposit and withdrawal.
+//!
+//! `Analytics` can be written as JSON, or as CSV with one `metric, key, value` row per figure (the
+//! header row in the installed `locale`), and
+//! is what the `report --analytics` command prints.
+use crate::account::{Account, ClientId};
+use crate::amount::{self, Amount};
+use crate::bank::Bank;
+use crate::errors::BankingError;
+use crate::locale::{Locale, Message};
+use crate::sink::ResultSink;
+use crate::transaction::{Transaction, TransactionType};
+use rust_decimal::prelude::*;
+use serde::Serialize;
+use std::io;
+
+/// The upper bounds of the histogram buckets, each bucket holding amounts from the previous bound
+/// (or 0) up to but excluding its own. A final bucket holds amounts from the last bound upwards.
+const BUCKET_BOUNDS: [u32; 6] = [1, 10, 100, 1_000, 10_000, 100_000];
+
+/// `Tally` accumulates the applied transactions of one type.
+#[derive(Clone, Debug, Default)]
+struct Tally {
+    count: usize,
+    sum: Amount,
+    buckets: [usize; BUCKET_BOUNDS.len() + 1],
+}
+
+impl Tally {
+    fn add(&mut self, amount: Amount) {
+        self.count += 1;
+        self.sum += amount;
+        let bucket = BUCKET_BOUNDS
+            .iter()
+            .position(|bound| amount < Amount::from(*bound))
+            .unwrap_or(BUCKET_BOUNDS.len());
+        self.buckets[bucket] += 1;
+    }
+
+    /// The mean amount, rounded to four decimal places, or `None` if nothing was tallied.
+    fn average(&self) -> Option<Decimal> {
+        if self.count == 0 {
+            return None;
+        }
+        Some((amount::to_decimal(self.sum) / Decimal::from(self.count)).round_dp(4).normalize())
+    }
+
+    fn histogram(&self) -> Vec<Bucket> {
+        self.buckets
+            .iter()
+            .enumerate()
+            .map(|(index, count)| Bucket {
+                range: match index {
+                    0 => format!("0-{}", BUCKET_BOUNDS[0]),
+                    _ if index == BUCKET_BOUNDS.len() => format!("{}+", BUCKET_BOUNDS[index - 1]),
+                    _ => format!("{}-{}", BUCKET_BOUNDS[index - 1], BUCKET_BOUNDS[index]),
+                },
+                count: *count,
+            })
+            .collect()
+    }
+}
+
+/// `AnalyticsCollector` is a sink that tallies applied transactions for `Analytics`.
+#[derive(Clone, Debug, Default)]
+pub struct AnalyticsCollector {
+    processed: usize,
+    rejected: usize,
+    deposits: Tally,
+    withdrawals: Tally,
+    disputes: DisputeStats,
+}
+
+impl AnalyticsCollector {
+    pub fn new() -> AnalyticsCollector {
+        AnalyticsCollector::default()
+    }
+
+    /// Combine the tallies with the accounts of `bank`, ranking the top `top` accounts.
+    pub fn finish(&self, bank: &Bank, top: usize) -> Analytics {
+        let mut disputes = self.disputes.clone();
+        let settled = disputes.resolved + disputes.charged_back;
+        if settled > 0 {
+            disputes.resolution_rate = Some((Decimal::from(disputes.resolved) / Decimal::from(settled)).round_dp(4).normalize());
+        }
+        Analytics {
+            processed: self.processed,
+            rejected: self.rejected,
+            top_by_total: rank(bank, top, |account| account.total),
+            top_by_held: rank(bank, top, |account| account.held),
+            deposit_histogram: self.deposits.histogram(),
+            withdrawal_histogram: self.withdrawals.histogram(),
+            disputes,
+            average_deposit: self.deposits.average(),
+            average_withdrawal: self.withdrawals.average(),
+        }
+    }
+}
+
+impl ResultSink for AnalyticsCollector {
+    fn record(&mut self, _index: usize, transaction: &Transaction, result: Result<(), BankingError>) {
+        self.processed += 1;
+        if result.is_err() {
+            self.rejected += 1;
+            return;
+        }
+        let amount = transaction.amount.unwrap_or_else(Amount::zero);
+        match transaction.kind {
+            TransactionType::Deposit => self.deposits.add(amount),
+            TransactionType::Withdrawal => self.withdrawals.add(amount),
+            TransactionType::Dispute => self.disputes.opened += 1,
+            TransactionType::Resolve => self.disputes.resolved += 1,
+            TransactionType::Chargeback => self.disputes.charged_back += 1,
+            TransactionType::Adjustment | TransactionType::PromoCredit | TransactionType::Custom(_) => {}
+        }
+    }
+}
+
+/// The `top` accounts with the largest `balance`, largest first. Ties go to the lower client ID.
+fn rank<F: Fn(&Account) -> Amount>(bank: &Bank, top: usize, balance: F) -> Vec<RankedAccount> {
+    let mut ranked: Vec<RankedAccount> = bank
+        .accounts()
+        .map(|account| RankedAccount {
+            client: account.client,
+            amount: balance(account),
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.amount.cmp(&a.amount).then(a.client.cmp(&b.client)));
+    ranked.truncate(top);
+    ranked
+}
+
+/// `RankedAccount` is an account in a top-N ranking, with the balance it was ranked by.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct RankedAccount {
+    pub client: ClientId,
+    pub amount: Amount,
+}
+
+/// `Bucket` is a bar of a histogram: the number of amounts in a range.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Bucket {
+    pub range: String,
+    pub count: usize,
+}
+
+/// `DisputeStats` counts the disputes opened during a run and how they were settled.
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct DisputeStats {
+    pub opened: usize,
+    pub resolved: usize,
+    pub charged_back: usize,
+    /// The share of settled disputes that were resolved rather than charged back, if any were
+    /// settled.
+    pub resolution_rate: Option<Decimal>,
+}
+
+/// `Analytics` summarizes a run for the operators reviewing it.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Analytics {
+    pub processed: usize,
+    pub rejected: usize,
+    pub top_by_total: Vec<RankedAccount>,
+    pub top_by_held: Vec<RankedAccount>,
+    pub deposit_histogram: Vec<Bucket>,
+    pub withdrawal_histogram: Vec<Bucket>,
+    pub disputes: DisputeStats,
+    pub average_deposit: Option<Decimal>,
+    pub average_withdrawal: Option<Decimal>,
+}
+
+/// `MetricRow` is a row of the CSV rendering of a report, such as `Analytics`.
+#[derive(Serialize)]
+pub(crate) struct MetricRow<'a> {
+    pub metric: &'a str,
+    pub key: &'a str,
+    pub value: String,
+}
+
+/// Create a CSV writer for a report, writing the `metric, key, value` header row in the given
+/// locale.
+pub fn metric_writer<W: io::Write>(writer: W, locale: Locale) -> Result<csv::Writer<W>, csv::Error> {
+    let mut wtr = csv::WriterBuilder::new().has_headers(false).from_writer(writer);
+    wtr.write_record(
+        [Message::ReportMetric, Message::ReportKey, Message::ReportValue]
+            .iter()
+            .map(|message| message.text(locale)),
+    )?;
+    Ok(wtr)
+}
+
+impl Analytics {
+    /// Write as CSV with the columns `metric, key, value`. Rankings are keyed by client, histograms
+    /// by range; figures absent for lack of data are written with an empty value. The header row
+    /// is written in the given locale.
+    pub fn write_csv<W: io::Write>(&self, writer: W, locale: Locale) -> Result<(), csv::Error> {
+        let mut wtr = metric_writer(writer, locale)?;
+        self.write_rows(&mut wtr)?;
+        wtr.flush()?;
+        Ok(())
+    }
+
+    /// As `write_csv`, writing the rows to a CSV writer that other reports may share.
+    pub fn write_rows<W: io::Write>(&self, wtr: &mut csv::Writer<W>) -> Result<(), csv::Error> {
+        let mut row = |metric: &str, key: &str, value: String| wtr.serialize(MetricRow { metric, key, value });
+        row("transactions", "processed", self.processed.to_string())?;
+        row("transactions", "rejected", self.rejected.to_string())?;
+        for account in &self.top_by_total {
+            row("top_by_total", &account.client.to_string(), account.amount.to_string())?;
+        }
+        for account in &self.top_by_held {
+            row("top_by_held", &account.client.to_string(), account.amount.to_string())?;
+        }
+        for bucket in &self.deposit_histogram {
+            row("deposit_histogram", &bucket.range, bucket.count.to_string())?;
+        }
+        for bucket in &self.withdrawal_histogram {
+            row("withdrawal_histogram", &bucket.range, bucket.count.to_string())?;
+        }
+        let optional = |value: Option<Decimal>| value.map(|value| value.to_string()).unwrap_or_default();
+        row("disputes", "opened", self.disputes.opened.to_string())?;
+        row("disputes", "resolved", self.disputes.resolved.to_string())?;
+        row("disputes", "charged_back", self.disputes.charged_back.to_string())?;
+        row("disputes", "resolution_rate", optional(self.disputes.resolution_rate))?;
+        row("average", "deposit", optional(self.average_deposit))?;
+        row("average", "withdrawal", optional(self.average_withdrawal))
+    }
+
+    /// Write as a JSON object with the fields of `Analytics`.
+    pub fn write_json<W: io::Write>(&self, writer: W) -> serde_json::Result<()> {
+        serde_json::to_writer_pretty(writer, self)
+    }
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testkit::Scenario;
+
+    /// The analytics of processing `scenario`, ranking the top `top` accounts.
+    fn analytics_of(scenario: Scenario, top: usize) -> Analytics {
+        let mut bank = Bank::new();
+        let mut collector = AnalyticsCollector::new();
+        bank.process_all_with(scenario.transactions().to_vec(), &mut collector);
+        collector.finish(&bank, top)
+    }
+
+    fn clients(ranked: &[RankedAccount]) -> Vec<ClientId> {
+        ranked.iter().map(|account| account.client).collect()
+    }
+
+    fn counts(histogram: &[Bucket]) -> Vec<usize> {
+        histogram.iter().map(|bucket| bucket.count).collect()
+    }
+
+    #[test]
+    fn processed_and_rejected_transactions_are_counted() {
+        // TEST
+        let analytics = analytics_of(Scenario::new().deposit(1, 5).withdraw(1, 10).withdraw(1, 2), 1);
+        assert_eq!((3, 1), (analytics.processed, analytics.rejected));
+    }
+
+    #[test]
+    fn accounts_are_ranked_by_total_largest_first() {
+        // TEST
+        let analytics = analytics_of(Scenario::new().deposit(1, 5).deposit(2, 50).deposit(3, 20), 2);
+        assert_eq!(vec![2, 3], clients(&analytics.top_by_total));
+        assert_eq!(Amount::from(50u32), analytics.top_by_total[0].amount);
+    }
+
+    #[test]
+    fn accounts_are_ranked_by_held_funds() {
+        // TEST
+        let analytics = analytics_of(
+            Scenario::new()
+                .deposit(1, 5)
+                .deposit(2, 50)
+                .deposit(3, 20)
+                .dispute(1, 1)
+                .dispute(3, 3),
+            3,
+        );
+        assert_eq!(vec![3, 1, 2], clients(&analytics.top_by_held));
+    }
+
+    #[test]
+    fn ranking_ties_go_to_the_lower_client() {
+        // TEST
+        let analytics = analytics_of(Scenario::new().deposit(3, 5).deposit(1, 5).deposit(2, 5), 2);
+        assert_eq!(vec![1, 2], clients(&analytics.top_by_total));
+    }
+
+    #[test]
+    fn amounts_are_bucketed_up_to_but_excluding_each_bound() {
+        // TEST
+        let analytics = analytics_of(Scenario::new().deposit(1, 1).deposit(1, 99).deposit(1, 100).deposit(1, 200_000), 1);
+        assert_eq!(vec![0, 1, 1, 1, 0, 0, 1], counts(&analytics.deposit_histogram));
+        assert_eq!("0-1", analytics.deposit_histogram[0].range);
+        assert_eq!("100000+", analytics.deposit_histogram[6].range);
+    }
+
+    #[test]
+    fn rejected_transactions_are_not_bucketed() {
+        // TEST
+        let analytics = analytics_of(Scenario::new().deposit(1, 5).withdraw(1, 10).withdraw(1, 2), 1);
+        assert_eq!(vec![0, 1, 0, 0, 0, 0, 0], counts(&analytics.withdrawal_histogram));
+    }
+
+    #[test]
+    fn disputes_are_counted_by_how_they_were_settled() {
+        // TEST
+        let analytics = analytics_of(
+            Scenario::new()
+                .deposit(1, 5)
+                .deposit(2, 5)
+                .deposit(3, 5)
+                .dispute(1, 1)
+                .dispute(2, 2)
+                .resolve(2, 2)
+                .dispute(3, 3)
+                .chargeback(3, 3),
+            1,
+        );
+        assert_eq!(
+            (3, 1, 1),
+            (
+                analytics.disputes.opened,
+                analytics.disputes.resolved,
+                analytics.disputes.charged_back
+            )
+        );
+        assert_eq!(Some(Decimal::new(5, 1)), analytics.disputes.resolution_rate);
+    }
+
+    #[test]
+    fn resolution_rate_is_absent_without_settled_disputes() {
+        // TEST
+        let analytics = analytics_of(Scenario::new().deposit(1, 5).dispute(1, 1), 1);
+        assert_eq!(None, analytics.disputes.resolution_rate);
+    }
+
+    #[test]
+    fn averages_are_rounded_to_four_decimal_places() {
+        // TEST
+        let analytics = analytics_of(Scenario::new().deposit(1, 1).deposit(1, 1).deposit(1, 2).withdraw(1, 3), 1);
+        assert_eq!(Some(Decimal::new(13333, 4)), analytics.average_deposit);
+        assert_eq!(Some(Decimal::new(3, 0)), analytics.average_withdrawal);
+    }
+
+    #[test]
+    fn averages_are_absent_without_transactions() {
+        // TEST
+        let analytics = analytics_of(Scenario::new().deposit(1, 5), 1);
+        assert_eq!(None, analytics.average_withdrawal);
+    }
+
+    #[test]
+    fn csv_has_a_row_per_figure() -> Result<(), csv::Error> {
+        // SETUP
+        let analytics = analytics_of(Scenario::new().deposit(1, 50).deposit(2, 20), 1);
+        let mut output = Vec::new();
+
+        // TEST
+        analytics.write_csv(&mut output, Locale::En)?;
+        let output = String::from_utf8(output).expect("UTF-8");
+        assert!(output.starts_with("metric,key,value\ntransactions,processed,2\ntransactions,rejected,0\ntop_by_total,1,50\n"));
+        assert!(output.contains("deposit_histogram,10-100,2\n"));
+        assert!(output.ends_with("disputes,resolution_rate,\naverage,deposit,35\naverage,withdrawal,\n"));
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn csv_header_is_written_in_the_locale() -> Result<(), csv::Error> {
+        // SETUP
+        let analytics = analytics_of(Scenario::new().deposit(1, 5), 1);
+        let mut output = Vec::new();
+
+        // TEST
+        analytics.write_csv(&mut output, Locale::De)?;
+        assert!(String::from_utf8(output).expect("UTF-8").starts_with("Kennzahl,Schlüssel,Wert\n"));
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn json_has_the_fields_of_the_analytics() -> serde_json::Result<()> {
+        // SETUP
+        let analytics = analytics_of(Scenario::new().deposit(1, 5), 1);
+        let mut output = Vec::new();
+
+        // TEST
+        analytics.write_json(&mut output)?;
+        let json: serde_json::Value = serde_json::from_slice(&output)?;
+        assert_eq!(1, json["processed"]);
+        assert_eq!(1, json["top_by_total"][0]["client"]);
+        assert!(json["average_withdrawal"].is_null());
+
+        // TEARDOWN
+        Ok(())
+    }
+}
+//endregion
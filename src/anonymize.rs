@@ -0,0 +1,155 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Pseudonymization
+//! Remaps client IDs so that transaction and account files can be shared as test data. The mapping
+//! is a keyed permutation of the client ID space: the same key always maps a client to the same
+//! pseudonym, in every file, so that inputs and outputs anonymized separately still line up, and no
+//! two clients are ever mapped to the same pseudonym. Without the key, the mapping can not be
+//! reversed.
+//!
+//! Only the `client` column is changed; transaction IDs and amounts are left as they are.
+use crate::account::ClientId;
+use crate::signature::SigningKey;
+use std::fmt;
+use std::io;
+
+/// The number of rounds of the Feistel network permuting client IDs. Each byte of an ID is mixed
+/// with the others twice.
+const ROUNDS: u8 = 8;
+
+/// `AnonymizeError` describes why a file could not be anonymized.
+#[derive(Debug)]
+pub enum AnonymizeError {
+    Csv(csv::Error),
+    /// The file has no `client` column.
+    MissingClientColumn,
+    /// The `client` field of the record on this line is not a client ID. The record is not copied,
+    /// as it can not be pseudonymized.
+    InvalidClient {
+        line: u64,
+    },
+}
+
+impl fmt::Display for AnonymizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AnonymizeError::Csv(e) => write!(f, "{}", e),
+            AnonymizeError::MissingClientColumn => write!(f, "the file has no `client` column"),
+            AnonymizeError::InvalidClient { line } => write!(f, "line {}: `client` is not a client ID", line),
+        }
+    }
+}
+
+impl From<csv::Error> for AnonymizeError {
+    fn from(e: csv::Error) -> AnonymizeError {
+        AnonymizeError::Csv(e)
+    }
+}
+
+/// `Pseudonymizer` maps client IDs to pseudonyms under a secret key.
+pub struct Pseudonymizer {
+    /// The round function of every round, for every possible input byte.
+    rounds: [[u8; 256]; ROUNDS as usize],
+}
+
+impl Pseudonymizer {
+    pub fn new(key: SigningKey) -> Pseudonymizer {
+        let mut rounds = [[0u8; 256]; ROUNDS as usize];
+        for (round, outputs) in rounds.iter_mut().enumerate() {
+            for (input, output) in outputs.iter_mut().enumerate() {
+                *output = key.digest(&[round as u8, input as u8])[0];
+            }
+        }
+        Pseudonymizer { rounds }
+    }
+
+    /// The pseudonym of a client. An unbalanced Feistel network over the bytes of the ID, with the
+    /// keyed HMAC as its round function, is a permutation whatever the round function is: each
+    /// round mixes the first byte with a function of the others, then rotates it to the back.
+    pub fn client(&self, client: ClientId) -> ClientId {
+        let mut bytes = client.to_be_bytes();
+        for outputs in &self.rounds {
+            let mixed = bytes[1..].iter().fold(0, |hash, &byte| outputs[usize::from(hash ^ byte)]);
+            bytes.rotate_left(1);
+            bytes[bytes.len() - 1] ^= mixed;
+        }
+        ClientId::from_be_bytes(bytes)
+    }
+
+    /// Copy a CSV file with a header row, replacing the `client` column with pseudonyms. Works on
+    /// transaction and account files alike. Returns the number of records copied.
+    pub fn anonymize_csv<R: io::Read, W: io::Write>(
+        &self,
+        reader: &mut csv::Reader<R>,
+        writer: &mut csv::Writer<W>,
+    ) -> Result<usize, AnonymizeError> {
+        let headers = reader.headers()?.clone();
+        let column = headers
+            .iter()
+            .position(|header| header == "client")
+            .ok_or(AnonymizeError::MissingClientColumn)?;
+        writer.write_record(&headers)?;
+        let mut copied = 0;
+        for record in reader.records() {
+            let record = record?;
+            let line = record.position().map_or(0, |position| position.line());
+            let client: ClientId = record[column].parse().map_err(|_| AnonymizeError::InvalidClient { line })?;
+            let pseudonym = self.client(client).to_string();
+            let fields = record
+                .iter()
+                .enumerate()
+                .map(|(index, field)| if index == column { pseudonym.as_str() } else { field });
+            writer.write_record(fields)?;
+            copied += 1;
+        }
+        writer.flush().map_err(csv::Error::from)?;
+        Ok(copied)
+    }
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn pseudonyms_are_a_keyed_permutation() {
+        let pseudonymizer = Pseudonymizer::new(SigningKey::new(b"test data key"));
+        // the ID space is too large to enumerate, so no collisions are checked for at either end of it
+        let clients: Vec<ClientId> = (0..=0xffff).chain(ClientId::MAX - 0xffff..=ClientId::MAX).collect();
+        let pseudonyms: HashSet<ClientId> = clients.iter().map(|&client| pseudonymizer.client(client)).collect();
+        assert_eq!(clients.len(), pseudonyms.len());
+        assert_eq!(
+            pseudonymizer.client(7),
+            Pseudonymizer::new(SigningKey::new(b"test data key")).client(7)
+        );
+        let other = Pseudonymizer::new(SigningKey::new(b"another key"));
+        assert!((0..16).any(|client| pseudonymizer.client(client) != other.client(client)));
+    }
+
+    #[test]
+    fn only_the_client_column_is_replaced() -> Result<(), AnonymizeError> {
+        // SETUP
+        let pseudonymizer = Pseudonymizer::new(SigningKey::new(b"test data key"));
+        let input = "type,client,tx,amount\ndeposit,1,1,1.5\nwithdrawal,2,2,0.5\n";
+        let mut reader = csv::Reader::from_reader(input.as_bytes());
+        let mut writer = csv::Writer::from_writer(Vec::new());
+
+        // TEST
+        assert_eq!(2, pseudonymizer.anonymize_csv(&mut reader, &mut writer)?);
+        let output = String::from_utf8(writer.into_inner().expect("flushed")).expect("UTF-8");
+        let expected = format!(
+            "type,client,tx,amount\ndeposit,{},1,1.5\nwithdrawal,{},2,0.5\n",
+            pseudonymizer.client(1),
+            pseudonymizer.client(2)
+        );
+        assert_eq!(expected, output);
+        let mut reader = csv::Reader::from_reader("client,tx\nx,1\n".as_bytes());
+        assert!(matches!(
+            pseudonymizer.anonymize_csv(&mut reader, &mut csv::Writer::from_writer(Vec::new())),
+            Err(AnonymizeError::InvalidClient { line: 2 })
+        ));
+        Ok(())
+    }
+}
+//endregion
@@ -0,0 +1,310 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Audit trail
+//! A bank built with `BankBuilder::audit` keeps, for each account, a trail of the operations
+//! applied to it and of the administrative actions taken on it, each attributed to the operator or
+//! source installed with `Bank::set_operator` when it was applied: a user, an API key ID, or the
+//! name of the file the transactions were read from. `Bank::audit_trail` returns the trail of an
+//! account, for auditors asking who initiated an unlock.
+//!
+//! Attempts that were refused are recorded too, with the reason they were refused: transactions
+//! rejected on the account, such as a withdrawal from a locked account, and administrative actions
+//! denied, such as an unlock of an account that is not locked.
+//!
+//! Entries for transactions also carry the `Provenance` of their record, where it is known.
+//!
+//! The operator is also recorded in the journal, by `JournalWriter::with_source`, so that a standby
+//! following the journal attributes every entry as the primary did.
+//!
+//! An `AdminCommand` is an administrative action to take on many accounts at once with
+//! `Bank::for_each_account_mut`, such as locking every account with a negative balance.
+use crate::account::{Account, Dormancy};
+use crate::amount::Amount;
+use crate::errors::BankingError;
+use crate::provenance::Provenance;
+use crate::transaction::{TransactionType, TxId};
+#[cfg(feature = "time")]
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::str::FromStr;
+#[cfg(feature = "time")]
+use std::time::SystemTime;
+
+/// `AuditAction` enumerates what an `AuditEntry` records.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum AuditAction {
+    /// A transaction was applied to the account.
+    Applied { kind: TransactionType, tx: TxId },
+    /// The account was locked by `Bank::lock_account`.
+    Locked,
+    /// The account was unlocked by `Bank::unlock_account`.
+    Unlocked,
+    /// The account was frozen by `Bank::freeze_account`.
+    Frozen,
+    /// The account was unfrozen by `Bank::unfreeze_account`.
+    Unfrozen,
+    /// The account was flagged as dormant by `Bank::sweep_dormant`.
+    Dormant,
+    /// The dormant account was reactivated, by `Bank::reactivate_account` or a transaction.
+    Reactivated,
+    /// A promotional credit expired, and the part of it the client had not spent was clawed back.
+    Expired { tx: TxId, clawed_back: Amount },
+    /// The client was forgotten by `Bank::forget`: their account and this many stored transactions
+    /// were removed, and the balance posted as an `ErasureAdjustment`. It is the only entry kept of
+    /// their audit trail.
+    Erased { transactions: usize, balance: Amount },
+    /// A note was recorded against the account; see `Bank::annotations`.
+    Annotated { note: String },
+    /// A label of the account was set, or removed if `value` is `None`.
+    Labelled { key: String, value: Option<String> },
+    /// A transaction was rejected, leaving the account unchanged.
+    Rejected { kind: TransactionType, tx: TxId, reason: BankingError },
+    /// An administrative action was denied, leaving the account unchanged.
+    Denied { command: AdminCommand, reason: BankingError },
+}
+
+/// `AuditEntry` is an operation on an account, with who initiated it and when.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct AuditEntry {
+    #[serde(flatten)]
+    pub action: AuditAction,
+    pub operator: Option<String>,
+    #[cfg(feature = "time")]
+    pub recorded_at: DateTime<Utc>,
+    /// Where the record of a transaction applied or rejected was read from, if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<Provenance>,
+}
+
+impl AuditEntry {
+    /// Record the action as taken now by `operator`.
+    pub fn now(action: AuditAction, operator: Option<String>) -> AuditEntry {
+        AuditEntry {
+            action,
+            operator,
+            #[cfg(feature = "time")]
+            recorded_at: DateTime::<Utc>::from(SystemTime::now()),
+            source: None,
+        }
+    }
+
+    /// Attribute the entry to the record it was made for.
+    pub fn with_source(mut self, source: Option<Provenance>) -> AuditEntry {
+        self.source = source;
+        self
+    }
+}
+
+/// `AdminCommand` enumerates the administrative actions that can be taken on many accounts at once.
+/// On the command line they are written as `lock`, `unlock`, `freeze`, `unfreeze`, `reactivate`,
+/// `annotate:<note>`, or `label:<key>=<value>`, where an empty value removes the label.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AdminCommand {
+    Lock,
+    Unlock,
+    Freeze,
+    Unfreeze,
+    Reactivate,
+    Annotate(String),
+    Label { key: String, value: Option<String> },
+}
+
+impl AdminCommand {
+    /// Apply the command to an account, returning the action to record, or `None` if the account
+    /// was already in the requested state and is left unchanged.
+    pub fn apply(&self, account: &mut Account) -> Option<AuditAction> {
+        match self {
+            AdminCommand::Lock if !account.locked => {
+                account.lock();
+                Some(AuditAction::Locked)
+            }
+            AdminCommand::Unlock if account.locked => {
+                account.unlock();
+                Some(AuditAction::Unlocked)
+            }
+            AdminCommand::Freeze if !account.frozen => {
+                account.set_frozen(true);
+                Some(AuditAction::Frozen)
+            }
+            AdminCommand::Unfreeze if account.frozen => {
+                account.set_frozen(false);
+                Some(AuditAction::Unfrozen)
+            }
+            AdminCommand::Reactivate if account.dormancy != Dormancy::Active => {
+                account.set_dormancy(Dormancy::Active);
+                Some(AuditAction::Reactivated)
+            }
+            AdminCommand::Annotate(note) => Some(AuditAction::Annotated { note: note.clone() }),
+            AdminCommand::Label { key, value } => Some(AuditAction::Labelled {
+                key: key.clone(),
+                value: value.clone(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for AdminCommand {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<AdminCommand, String> {
+        match value {
+            "lock" => Ok(AdminCommand::Lock),
+            "unlock" => Ok(AdminCommand::Unlock),
+            "freeze" => Ok(AdminCommand::Freeze),
+            "unfreeze" => Ok(AdminCommand::Unfreeze),
+            "reactivate" => Ok(AdminCommand::Reactivate),
+            _ => {
+                if let Some(note) = value.strip_prefix("annotate:").filter(|note| !note.trim().is_empty()) {
+                    return Ok(AdminCommand::Annotate(note.trim().to_string()));
+                }
+                match value.strip_prefix("label:").and_then(|label| label.split_once('=')) {
+                    Some((key, value)) if !key.trim().is_empty() => Ok(AdminCommand::Label {
+                        key: key.trim().to_string(),
+                        value: Some(value.trim().to_string()).filter(|value| !value.is_empty()),
+                    }),
+                    _ => Err(format!(
+                        "unsupported admin command: {} (expected lock, unlock, freeze, unfreeze, reactivate, \
+                         annotate:<note>, or label:<key>=<value>)",
+                        value
+                    )),
+                }
+            }
+        }
+    }
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn admin_commands_are_read_by_name() {
+        // TEST
+        assert_eq!(Ok(AdminCommand::Lock), "lock".parse());
+        assert_eq!(Ok(AdminCommand::Unlock), "unlock".parse());
+        assert_eq!(Ok(AdminCommand::Freeze), "freeze".parse());
+        assert_eq!(Ok(AdminCommand::Unfreeze), "unfreeze".parse());
+        assert_eq!(Ok(AdminCommand::Reactivate), "reactivate".parse());
+        assert!("delete".parse::<AdminCommand>().is_err());
+    }
+
+    #[test]
+    fn annotate_command_requires_a_note() {
+        // TEST
+        assert_eq!(
+            Ok(AdminCommand::Annotate(String::from("SAR filed"))),
+            "annotate: SAR filed ".parse()
+        );
+        assert!("annotate: ".parse::<AdminCommand>().is_err());
+    }
+
+    #[test]
+    fn label_command_with_an_empty_value_removes_the_label() {
+        // TEST
+        let set = AdminCommand::Label {
+            key: String::from("ticket"),
+            value: Some(String::from("SUP-1234")),
+        };
+        let remove = AdminCommand::Label {
+            key: String::from("ticket"),
+            value: None,
+        };
+        assert_eq!(Ok(set), "label:ticket=SUP-1234".parse());
+        assert_eq!(Ok(remove), "label:ticket=".parse());
+        assert!("label:=SUP-1234".parse::<AdminCommand>().is_err());
+        assert!("label:ticket".parse::<AdminCommand>().is_err());
+    }
+
+    #[test]
+    fn applying_a_command_changes_the_account() {
+        // SETUP
+        let mut account = Account::new(1);
+
+        // TEST
+        assert_eq!(Some(AuditAction::Locked), AdminCommand::Lock.apply(&mut account));
+        assert!(account.locked);
+        assert_eq!(Some(AuditAction::Frozen), AdminCommand::Freeze.apply(&mut account));
+        assert!(account.frozen);
+    }
+
+    #[test]
+    fn applying_a_command_to_an_account_already_in_that_state_does_nothing() {
+        // SETUP
+        let mut account = Account::new(1);
+        let before = account.clone();
+
+        // TEST
+        assert_eq!(None, AdminCommand::Unlock.apply(&mut account));
+        assert_eq!(None, AdminCommand::Unfreeze.apply(&mut account));
+        assert_eq!(None, AdminCommand::Reactivate.apply(&mut account));
+        assert_eq!(before, account);
+    }
+
+    #[test]
+    fn reactivate_command_makes_a_dormant_account_active() {
+        // SETUP
+        let mut account = Account::new(1);
+        account.set_dormancy(Dormancy::Blocked);
+
+        // TEST
+        assert_eq!(Some(AuditAction::Reactivated), AdminCommand::Reactivate.apply(&mut account));
+        assert_eq!(Dormancy::Active, account.dormancy);
+    }
+
+    #[test]
+    fn annotating_records_the_note_without_changing_the_account() {
+        // SETUP
+        let mut account = Account::new(1);
+        let before = account.clone();
+
+        // TEST
+        let action = AdminCommand::Annotate(String::from("called client")).apply(&mut account);
+        assert_eq!(
+            Some(AuditAction::Annotated {
+                note: String::from("called client")
+            }),
+            action
+        );
+        assert_eq!(before, account);
+    }
+
+    #[test]
+    fn entry_is_attributed_to_its_operator_and_source() {
+        // SETUP
+        let source = Provenance::Line {
+            file: Arc::from("transactions.csv"),
+            line: 42,
+        };
+
+        // TEST
+        let entry = AuditEntry::now(AuditAction::Locked, Some(String::from("alice"))).with_source(Some(source.clone()));
+        assert_eq!(AuditAction::Locked, entry.action);
+        assert_eq!(Some(String::from("alice")), entry.operator);
+        assert_eq!(Some(source), entry.source);
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn entry_is_written_with_its_action_inline() {
+        // SETUP
+        let entry = AuditEntry::now(
+            AuditAction::Applied {
+                kind: TransactionType::Deposit,
+                tx: 7,
+            },
+            None,
+        );
+
+        // TEST
+        let json = serde_json::to_value(&entry).unwrap();
+        assert_eq!("applied", json["action"]);
+        assert_eq!("deposit", json["kind"]);
+        assert_eq!(7, json["tx"]);
+        assert!(json.get("source").is_none());
+    }
+}
+//endregion
@@ -5,7 +5,8 @@
 //! All transactions are performed using fixed precision data types as floating point types are not
 //! suitable for financial calculations. Any amounts containing more than four digits of precision
 //! after the decimal will be rounded to four digits of precision after the decimal using
-//! "Bankers Rounding" rules. e.g. 6.5 -> 6, 7.5 -> 8.
+//! "Bankers Rounding" rules. e.g. 6.5 -> 6, 7.5 -> 8. The precision, and this and other rules, can
+//! be changed when creating a bank with `Bank::builder`; see `config`.
 //!
 //! The `Decimal` data type has a max value of 4_294_967_295 with 19 digits of precision after the
 //! decimal.
@@ -34,661 +35,3156 @@
 //!
 //! ## Usage
 //! ```
+//! # use rust_payment_processor::bank::Bank;
+//! # use rust_payment_processor::transaction::RecordOptions;
+//! # fn main() -> Result<(), csv::Error> {
+//! # let input = "type, client, tx, amount\ndeposit, 1, 1, 1.0\n";
 //! let mut bank = Bank::new();
-//! let mut reader = make_csv_reader(&args.input_file)?;
-//! bank.process_record_set(&mut reader);
-//! bank.print_accounts();
+//! let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(input.as_bytes());
+//! bank.process_record_set(&mut reader, &RecordOptions::default());
+//! bank.print_accounts()?;
+//! # Ok(())
+//! # }
 //! ```
 
 #![forbid(unsafe_code)] // for good measure
-use crate::account::Account;
+use crate::account::{Account, Annotations, ClientId, ClientStatistics, Dormancy};
+#[cfg(feature = "io")]
+use crate::account::{ExtendedAccount, VersionedAccount};
+use crate::amount::Amount;
+use crate::audit::{AdminCommand, AuditAction, AuditEntry};
+#[cfg(feature = "retention")]
+use crate::bloom::EvictedIds;
+#[cfg(feature = "sink")]
+use crate::cancel::CancellationToken;
+#[cfg(feature = "arrow")]
+use crate::columnar;
+#[cfg(feature = "retention")]
+use crate::config::RetentionPolicy;
+use crate::config::{BankBuilder, BankConfig, DuplicatePolicy, Inactivity};
 use crate::errors::BankingError;
+use crate::handler::Handlers;
+#[cfg(feature = "io")]
+use crate::journal::{AsOf, Follower, JournalError};
+use crate::locale::{self, Locale, Localize, Message};
+use crate::provenance::Provenance;
+use crate::redact::redact;
+#[cfg(feature = "io")]
+use crate::schema;
+#[cfg(feature = "signature")]
+use crate::signature;
+#[cfg(feature = "sink")]
+use crate::sink::ResultSink;
 use crate::transaction::*;
+#[cfg(feature = "arrow")]
+use arrow_array::RecordBatch;
+#[cfg(feature = "arrow")]
+use arrow_schema::ArrowError;
 use rust_decimal::prelude::*;
-use rust_decimal_macros::dec;
-use std::collections::HashMap;
-use std::fs::File;
+use serde::Serialize;
+#[cfg(feature = "io")]
+use std::collections::BTreeMap;
+#[cfg(feature = "retention")]
+use std::collections::VecDeque;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+#[cfg(feature = "io")]
 use std::io;
+use std::mem::size_of;
+
+//region ProcessingSummary
+/// `ProcessingSummary` counts the outcomes of a call to `Bank::process_all`.
+#[derive(Serialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct ProcessingSummary {
+    /// The number of transactions processed, successfully or not.
+    pub processed: usize,
+    /// The number of transactions applied to an account.
+    pub applied: usize,
+    /// The number of transactions rejected with a `BankingError`.
+    pub rejected: usize,
+    /// The number of applied transactions that reused the ID of an earlier one and were skipped
+    /// without effect under the `DuplicatePolicy`.
+    pub skipped: usize,
+}
+
+impl ProcessingSummary {
+    /// Count the result of processing a single transaction.
+    pub fn record(&mut self, result: &Result<(), BankingError>) {
+        self.processed += 1;
+        match result {
+            Ok(()) => self.applied += 1,
+            Err(_) => self.rejected += 1,
+        }
+    }
+}
+
+impl fmt::Display for ProcessingSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_localized(f, Locale::En)
+    }
+}
+
+impl Localize for ProcessingSummary {
+    fn write_localized(&self, f: &mut dyn fmt::Write, locale: Locale) -> fmt::Result {
+        locale::write_in(
+            f,
+            locale,
+            Message::ProcessingSummary,
+            &[&self.processed, &self.applied, &self.rejected],
+        )
+    }
+}
+//endregion
+
+//region DuplicateCheck
+/// `DuplicateCheck` is what `Bank::check_duplicate` decides to do with a deposit or withdrawal.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DuplicateCheck {
+    /// The ID has not been used before: the transaction is applied.
+    Unique,
+    /// The ID has been used before, and the transaction is accepted without effect.
+    Skip,
+    /// The transaction is a correction of the one stored under its ID, applied with `Bank::correct`.
+    Correct,
+}
+//endregion
+
+//region Erasure
+/// `Erasure` records the removal of a client's data by `Bank::forget`, for the audit trail of
+/// erasure requests.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Erasure {
+    pub client: ClientId,
+    /// The number of stored transactions removed.
+    pub transactions: usize,
+    /// The balance of the account, which has been posted as an `ErasureAdjustment`.
+    pub balance: Amount,
+}
+
+/// `ErasureAdjustment` is the ledger entry moving the balance of a forgotten client off their
+/// account, so that the totals of the bank account for it after the account is gone.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ErasureAdjustment {
+    pub client: ClientId,
+    pub amount: Amount,
+}
+//endregion
+
+//region Footprint
+/// `Footprint` counts the entries of a bank's largest maps, for capacity planning.
+#[derive(Serialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct Footprint {
+    pub accounts: usize,
+    /// Deposits and withdrawals stored for later disputes.
+    pub transactions: usize,
+    /// Transactions prepared and not yet committed or aborted, and open batch reservations.
+    pub pending: usize,
+    /// Entries of the audit trails of every account.
+    pub audit_entries: usize,
+}
+//endregion
+
+//region Amendment
+/// `Amendment` records a change to the amount of a stored deposit or withdrawal, by an adjustment
+/// or a correction, linking the transaction to its earlier amount.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Amendment {
+    pub client: ClientId,
+    /// The amended transaction.
+    pub tx: TxId,
+    pub previous: Amount,
+    pub amount: Amount,
+}
+//endregion
+
+//region Reservation
+/// `ReservationId` identifies a reservation made by `Bank::reserve_batch`.
+pub type ReservationId = u64;
+
+/// `Reservation` is an amount held against an account for a batch of outgoing withdrawals.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reservation {
+    pub client: ClientId,
+    /// The amount held when the reservation was made.
+    pub reserved: Amount,
+    /// The part of it not yet drawn on by withdrawals.
+    pub remaining: Amount,
+}
+//endregion
+
+//region AccountPage
+/// `AccountPage` is a page of accounts, in order of client ID, returned by `Bank::accounts_page`.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct AccountPage<'a> {
+    pub accounts: Vec<&'a Account>,
+    /// The cursor of the next page, or `None` if this is the last one.
+    pub next: Option<ClientId>,
+}
+//endregion
+
+//region Dormancy
+/// `Activity` is the last transaction applied to an account.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Activity {
+    tx: TxId,
+    /// The number of records the bank had processed by then.
+    record: usize,
+    timestamp: Option<Timestamp>,
+}
+
+/// `DormantAccount` is an account flagged as dormant by `Bank::sweep_dormant`, with its last
+/// transaction.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct DormantAccount {
+    pub client: ClientId,
+    pub last_tx: TxId,
+    /// The time of the last transaction, if it carried one.
+    pub last_active: Option<Timestamp>,
+    /// The dormancy fee assessed, if any.
+    pub fee: Option<Amount>,
+    /// Why the policy's dormancy fee could not be assessed, if it could not.
+    pub fee_error: Option<BankingError>,
+}
+//endregion
+
+//region Promotion
+/// `Promotion` is a promotional credit that has not expired yet.
+#[derive(Clone, Debug, PartialEq)]
+struct Promotion {
+    client: ClientId,
+    /// The part of the credit the client has not spent.
+    amount: Amount,
+    expires: Option<Timestamp>,
+}
+
+/// `Clawback` is the part of an expired promotional credit that the client had not spent, taken
+/// back from their account.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Clawback {
+    pub client: ClientId,
+    /// The promotional credit.
+    pub tx: TxId,
+    pub amount: Amount,
+}
+//endregion
+
+//region Fee
+/// `Fee` is a fee assessed against a client by the bank, such as the fee configured with
+/// `BankBuilder::chargeback_fee`. Fees are posted separately from the transactions of the client,
+/// and can never be disputed.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Fee {
+    pub client: ClientId,
+    /// The transaction the fee was assessed for. For a dormancy fee, the last transaction of the
+    /// account.
+    pub tx: TxId,
+    pub amount: Amount,
+}
+//endregion
 
 //region Bank
 /// `Bank` provides storage for items that would commonly be owned by a bank, such as `Account`s
 /// and `Transaction`s.
 pub struct Bank {
-    accounts: HashMap<u16, Account>,
-    transactions: HashMap<u32, Transaction>,
+    accounts: HashMap<ClientId, Account>,
+    transactions: HashMap<TxId, Transaction>,
+    config: BankConfig,
+    /// The adjustments posted by `forget` for the balances of the clients removed, in the order they
+    /// were posted, kept so that the bank's totals are unchanged by an erasure.
+    erasure_adjustments: Vec<ErasureAdjustment>,
+    /// The IDs of the transactions removed by `forget`, which may still not be reused.
+    erased_transactions: HashSet<TxId>,
+    /// The IDs of stored transactions in the order they were stored, each with the time it is aged
+    /// from. Only kept when a `RetentionPolicy` other than `KeepAll` is configured.
+    #[cfg(feature = "retention")]
+    retained: VecDeque<(TxId, Option<Timestamp>)>,
+    /// The latest timestamp seen, which transactions are aged against.
+    latest: Option<Timestamp>,
+    /// The IDs of the transactions evicted by the retention policy, if it evicts any.
+    #[cfg(feature = "retention")]
+    evicted: Option<EvictedIds>,
+    handlers: Handlers,
+    /// The transaction counts of each account. Only kept when `BankConfig::statistics` is set.
+    statistics: HashMap<ClientId, ClientStatistics>,
+    /// The outcomes of every transaction this bank has processed.
+    summary: ProcessingSummary,
+    /// Deposits and withdrawals prepared by `prepare_transaction`, awaiting `commit` or `abort`.
+    prepared: HashMap<TxId, Transaction>,
+    /// Funds held for batches of withdrawals by `reserve_batch`, awaiting `release_batch`.
+    reservations: HashMap<ReservationId, Reservation>,
+    next_reservation: ReservationId,
+    /// The operator or source operations are attributed to in the audit trail.
+    operator: Option<String>,
+    /// The audit trail of each account. Only kept when `BankConfig::audit` is set.
+    audit: HashMap<ClientId, Vec<AuditEntry>>,
+    /// The fees assessed by the bank, in the order they were posted.
+    fees: Vec<Fee>,
+    /// The amendments of each stored transaction, oldest first.
+    amendments: HashMap<TxId, Vec<Amendment>>,
+    /// The IDs of the stored transactions under dispute, by client, kept in step with their
+    /// `under_dispute` flags so that a client's disputes can be found without a scan.
+    disputes: HashMap<ClientId, BTreeSet<TxId>>,
+    /// The last transaction applied to each account. Only kept when `BankConfig::dormancy` is set.
+    activity: HashMap<ClientId, Activity>,
+    /// The promotional credits that have not expired, apart from the stored transactions so that
+    /// they can not be disputed.
+    promotions: HashMap<TxId, Promotion>,
+    /// The combined amount of each client's promotional credits they have not spent.
+    promoted: HashMap<ClientId, Amount>,
+    /// The promotional credits with an expiry, soonest first.
+    expiries: BTreeSet<(Timestamp, TxId)>,
+    /// The unspent parts of expired promotional credits, in the order they were clawed back.
+    clawbacks: Vec<Clawback>,
+    /// The notes and labels recorded against each account.
+    annotations: HashMap<ClientId, Annotations>,
 }
 
 impl Bank {
     /// Creates a new bank, capable of processing transactions and displaying account information
     pub fn new() -> Bank {
+        Bank::with_config(BankConfig::default())
+    }
+
+    /// Returns a builder for a bank with non-default options. See `config::BankBuilder`.
+    pub fn builder() -> BankBuilder {
+        BankBuilder::new()
+    }
+
+    /// Creates a new bank with the given options.
+    pub fn with_config(config: BankConfig) -> Bank {
+        Bank::with_handlers(config, Handlers::default())
+    }
+
+    /// Creates a new bank with the given options, applying transactions with the given handlers.
+    pub fn with_handlers(config: BankConfig, handlers: Handlers) -> Bank {
+        #[cfg(feature = "retention")]
+        let evicted = match config.retention {
+            RetentionPolicy::KeepAll => None,
+            _ => Some(EvictedIds::new(&config.duplicate_filter)),
+        };
         Bank {
-            accounts: HashMap::<u16, Account>::new(),
-            transactions: HashMap::<u32, Transaction>::new(),
+            accounts: HashMap::<ClientId, Account>::new(),
+            transactions: HashMap::<TxId, Transaction>::new(),
+            config,
+            erasure_adjustments: Vec::new(),
+            erased_transactions: HashSet::new(),
+            #[cfg(feature = "retention")]
+            retained: VecDeque::new(),
+            latest: None,
+            #[cfg(feature = "retention")]
+            evicted,
+            handlers,
+            statistics: HashMap::new(),
+            summary: ProcessingSummary::default(),
+            prepared: HashMap::new(),
+            reservations: HashMap::new(),
+            next_reservation: 1,
+            operator: None,
+            audit: HashMap::new(),
+            fees: Vec::new(),
+            amendments: HashMap::new(),
+            disputes: HashMap::new(),
+            activity: HashMap::new(),
+            promotions: HashMap::new(),
+            promoted: HashMap::new(),
+            expiries: BTreeSet::new(),
+            clawbacks: Vec::new(),
+            annotations: HashMap::new(),
         }
     }
 
-    /// Given a `csv::Reader<File>`, parse and process each record.
+    /// Returns the options this bank was created with.
+    pub fn config(&self) -> &BankConfig {
+        &self.config
+    }
+
+    /// Given a `csv::Reader`, parse and process each record, following the conventions of its
+    /// source given by `options`. See `schema::read_transactions`.
     /// Usage:
     /// ```
+    /// # use rust_payment_processor::bank::Bank;
+    /// # use rust_payment_processor::transaction::RecordOptions;
+    /// # let input = "type, client, tx, amount\ndeposit, 1, 1, 1.0\nwithdrawal, 1, 2, 2.0\n";
     /// let mut bank = Bank::new();
-    /// let mut reader = make_csv_reader(&args.input_file)?;
-    /// bank.process_record_set(&mut reader);
+    /// let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(input.as_bytes());
+    /// let summary = bank.process_record_set(&mut reader, &RecordOptions::default());
+    /// assert_eq!((2, 1, 1), (summary.processed, summary.applied, summary.rejected));
     /// ```
-    pub fn process_record_set(&mut self, reader: &mut csv::Reader<File>) {
-        for result in reader.deserialize() {
-            if let Ok(transaction) = result {
-                match self.process_transaction(transaction) {
-                    Err(e) => {
-                        error!("Failed to process transaction. Aborted with error: {:?}", e);
-                    }
-                    _ => {}
-                }
+    #[cfg(feature = "io")]
+    pub fn process_record_set<R: io::Read>(&mut self, reader: &mut csv::Reader<R>, options: &RecordOptions) -> ProcessingSummary {
+        self.process_all(schema::read_transactions(reader, options))
+    }
+
+    /// Process each of the given transactions in order, logging any that fail. This is the entry
+    /// point shared by every input format; each only has to adapt its input into `Transaction`s.
+    pub fn process_all<I: IntoIterator<Item = Transaction>>(&mut self, transactions: I) -> ProcessingSummary {
+        let mut summary = ProcessingSummary::default();
+        for transaction in transactions {
+            let skipped = self.summary.skipped;
+            let result = self.process_transaction(transaction);
+            if let Err(e) = &result {
+                error!("Failed to process transaction. Aborted with error: {:?}", e);
+            }
+            summary.record(&result);
+            summary.skipped += self.summary.skipped - skipped;
+        }
+        info!("{}", summary.localized(self.config.locale));
+        summary
+    }
+
+    /// As `process_all`, additionally reporting the outcome of every transaction to `sink`.
+    #[cfg(feature = "sink")]
+    pub fn process_all_with<I, S>(&mut self, transactions: I, sink: &mut S) -> ProcessingSummary
+    where
+        I: IntoIterator<Item = Transaction>,
+        S: ResultSink + ?Sized,
+    {
+        self.process_with_cancel(transactions, sink, &CancellationToken::new())
+    }
+
+    /// As `process_all_with`, stopping before the next transaction once `token` is cancelled. The
+    /// summary then only counts the transactions processed before; the rest are left unread.
+    #[cfg(feature = "sink")]
+    pub fn process_with_cancel<I, S>(&mut self, transactions: I, sink: &mut S, token: &CancellationToken) -> ProcessingSummary
+    where
+        I: IntoIterator<Item = Transaction>,
+        S: ResultSink + ?Sized,
+    {
+        let mut summary = ProcessingSummary::default();
+        for (index, transaction) in transactions.into_iter().enumerate() {
+            if token.is_cancelled() {
+                warn!("Processing cancelled after {} transactions", index);
+                break;
             }
+            // the bank takes ownership of the transaction, so the sink is handed a copy
+            let received = transaction.clone();
+            let before = self.account(received.client).cloned();
+            let skipped = self.summary.skipped;
+            let result = self.process_transaction(transaction);
+            if let Err(e) = &result {
+                error!("Failed to process transaction. Aborted with error: {:?}", e);
+            }
+            match self.account(received.client) {
+                Some(after) if before.as_ref() != Some(after) => sink.account_updated(after),
+                _ => {}
+            }
+            summary.record(&result);
+            summary.skipped += self.summary.skipped - skipped;
+            sink.record(index, &received, result);
+            sink.sample(self);
         }
+        info!("{}", summary.localized(self.config.locale));
+        summary
+    }
+
+    /// Process every row of an Arrow `RecordBatch` as a transaction, in row order. Rows that can
+    /// not be mapped onto a `Transaction` are logged and discarded; an error is returned only if
+    /// the batch is missing a required column or has one of an incompatible type.
+    #[cfg(feature = "arrow")]
+    pub fn process_record_batch(&mut self, batch: &RecordBatch) -> Result<(), ArrowError> {
+        let rows = columnar::transactions_from_record_batch(batch)?;
+        let transactions = rows.into_iter().enumerate().filter_map(|(row, result)| match result {
+            Ok(transaction) => Some(transaction),
+            Err(e) => {
+                error!("Failed to map record batch row {}. Discarded with error: {}", row, e);
+                None
+            }
+        });
+        self.process_all(transactions);
+        Ok(())
+    }
+
+    /// Export all accounts as an Arrow `RecordBatch` with one row per account. See
+    /// `columnar::account_schema` for the layout.
+    #[cfg(feature = "arrow")]
+    pub fn accounts_to_record_batch(&self) -> Result<RecordBatch, ArrowError> {
+        columnar::accounts_to_record_batch(self.accounts())
     }
 
     /// Print accounts in CSV format to stdout
     /// Usage:
     /// ```
+    /// # use rust_payment_processor::bank::Bank;
+    /// # use rust_payment_processor::transaction::RecordOptions;
+    /// # fn main() -> Result<(), csv::Error> {
+    /// # let input = "type, client, tx, amount\ndeposit, 1, 1, 1.0\n";
     /// let mut bank = Bank::new();
-    /// let mut reader = make_csv_reader(&args.input_file)?;
-    /// bank.process_record_set(&mut reader);
-    /// bank.print_accounts();
+    /// let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(input.as_bytes());
+    /// bank.process_record_set(&mut reader, &RecordOptions::default());
+    /// bank.print_accounts()?;
+    /// # Ok(())
+    /// # }
     /// ```
-    pub fn print_accounts(&self) {
-        let mut wtr = csv::WriterBuilder::new().from_writer(io::stdout());
-        for account in &self.accounts {
-            match wtr.serialize(account.1) {
-                Err(e) => {
-                    error!("Failed to print account. Aborted with error: {:?}", e);
+    #[cfg(all(feature = "io", not(target_arch = "wasm32")))]
+    pub fn print_accounts(&self) -> Result<(), csv::Error> {
+        self.write_accounts(io::stdout())
+    }
+
+    /// Write accounts in CSV format to the given writer. Fails on the first account that can not
+    /// be written, or if the writer can not be flushed.
+    #[cfg(feature = "io")]
+    pub fn write_accounts<W: io::Write>(&self, writer: W) -> Result<(), csv::Error> {
+        write_rows(writer, self.accounts())
+    }
+
+    /// Write accounts in CSV format to the given writer, with each account's version as an extra
+    /// column.
+    #[cfg(feature = "io")]
+    pub fn write_accounts_with_versions<W: io::Write>(&self, writer: W) -> Result<(), csv::Error> {
+        write_rows(writer, self.accounts().map(VersionedAccount::from))
+    }
+
+    /// Write accounts in CSV format to the given writer, with each account's `ClientStatistics` as
+    /// extra columns. Statistics are only counted by a bank configured to keep them; otherwise they
+    /// are all zero.
+    #[cfg(feature = "io")]
+    pub fn write_accounts_extended<W: io::Write>(&self, writer: W) -> Result<(), csv::Error> {
+        self.write_extended(writer, false)
+    }
+
+    /// Write accounts as `write_accounts_extended` does, with a final `notes` column summarizing
+    /// each account's `Annotations`, empty for accounts without any.
+    #[cfg(feature = "io")]
+    pub fn write_accounts_extended_with_notes<W: io::Write>(&self, writer: W) -> Result<(), csv::Error> {
+        self.write_extended(writer, true)
+    }
+
+    #[cfg(feature = "io")]
+    fn write_extended<W: io::Write>(&self, writer: W, notes: bool) -> Result<(), csv::Error> {
+        let rows = self.accounts().map(|account| {
+            let statistics = self.statistics(account.client).copied().unwrap_or_default();
+            ExtendedAccount {
+                notes: match notes {
+                    true => Some(self.annotations(account.client).map(Annotations::summary).unwrap_or_default()),
+                    false => None,
+                },
+                ..ExtendedAccount::new(account, &statistics)
+            }
+        });
+        write_rows(writer, rows)
+    }
+
+    /// Unlock an account locked by a chargeback, returning its new version. If `expected_version`
+    /// is given and the account is no longer at that version, nothing is changed and
+    /// `VersionMismatch` is returned, so that an operator acting on a stale view of the account
+    /// finds out. An account that is not locked is left as it is, with `AccountNotLocked`.
+    pub fn unlock_account(&mut self, client: ClientId, expected_version: Option<u64>) -> Result<u64, BankingError> {
+        let version = self
+            .compare_and_set(client, expected_version, |account| match account.locked {
+                true => {
+                    account.unlock();
+                    Ok(())
                 }
+                false => Err(BankingError::AccountNotLocked),
+            })
+            .map_err(|reason| self.deny(client, AdminCommand::Unlock, reason))?;
+        self.audit(client, AuditAction::Unlocked);
+        Ok(version)
+    }
+
+    /// Lock an account as a chargeback would, e.g. pending an investigation, returning its new
+    /// version. `expected_version` is checked as for `unlock_account`.
+    pub fn lock_account(&mut self, client: ClientId, expected_version: Option<u64>) -> Result<u64, BankingError> {
+        let version = self
+            .compare_and_set(client, expected_version, |account| {
+                account.lock();
+                Ok(())
+            })
+            .map_err(|reason| self.deny(client, AdminCommand::Lock, reason))?;
+        self.audit(client, AuditAction::Locked);
+        Ok(version)
+    }
+
+    /// Apply `operation` to every account matching `filter`, e.g. to lock every account with a
+    /// negative balance. `operation` returns the action it took, which is appended to the account's
+    /// audit trail, or `None` if it left the account unchanged; `AdminCommand::apply` is such an
+    /// operation. Returns each client changed, in ascending order, with the action taken.
+    pub fn for_each_account_mut<F, O>(&mut self, mut filter: F, mut operation: O) -> Vec<(ClientId, AuditAction)>
+    where
+        F: FnMut(&Account) -> bool,
+        O: FnMut(&mut Account) -> Option<AuditAction>,
+    {
+        let mut changed = Vec::new();
+        for account in self.accounts.values_mut().filter(|account| filter(account)) {
+            if let Some(action) = operation(account) {
+                changed.push((account.client, action));
+            }
+        }
+        changed.sort_unstable_by_key(|(client, _)| *client);
+        for (client, action) in &changed {
+            match action {
+                AuditAction::Reactivated => self.touch(*client),
+                AuditAction::Annotated { note } => self.annotations.entry(*client).or_default().notes.push(note.clone()),
+                AuditAction::Labelled { key, value } => self.set_label(*client, key, value.clone()),
                 _ => {}
             }
+            self.audit(*client, action.clone());
         }
+        changed
     }
 
-    /// Returns the account for the specified client id, creating it if it does not exist.
-    /// In the event the account is locked due to a chargeback, or the creation of a new
-    /// account fails, this function returns an appropriate error.
-    fn retrieve_account(client: u16, accounts: &mut HashMap<u16, Account>, create: bool) -> Result<&mut Account, BankingError> {
-        if create {
-            if !accounts.contains_key(&client) {
-                accounts.insert(client, Account::new(client));
+    /// Freeze an account, e.g. for a compliance hold, returning its new version. A frozen account
+    /// rejects withdrawals with `AccountFrozen`, but still accepts deposits, disputes, resolves, and
+    /// chargebacks. The freeze is independent of the lock placed by a chargeback, and is lifted
+    /// with `unfreeze_account`. `expected_version` is checked as for `unlock_account`.
+    pub fn freeze_account(&mut self, client: ClientId, expected_version: Option<u64>) -> Result<u64, BankingError> {
+        let version = self
+            .compare_and_set(client, expected_version, |account| {
+                account.set_frozen(true);
+                Ok(())
+            })
+            .map_err(|reason| self.deny(client, AdminCommand::Freeze, reason))?;
+        self.audit(client, AuditAction::Frozen);
+        Ok(version)
+    }
+
+    /// Lift the freeze placed on an account by `freeze_account`, returning its new version.
+    pub fn unfreeze_account(&mut self, client: ClientId, expected_version: Option<u64>) -> Result<u64, BankingError> {
+        let version = self
+            .compare_and_set(client, expected_version, |account| {
+                account.set_frozen(false);
+                Ok(())
+            })
+            .map_err(|reason| self.deny(client, AdminCommand::Unfreeze, reason))?;
+        self.audit(client, AuditAction::Unfrozen);
+        Ok(version)
+    }
+
+    /// Flag every account without a transaction for the period of the bank's `DormancyPolicy` as
+    /// dormant, assessing the policy's fee, if any, and blocking withdrawals if it does. Returns the
+    /// accounts flagged, in order of client ID; those already dormant are left as they are. Accounts
+    /// with no transaction applied by this bank, such as restored ones, are never flagged. Does
+    /// nothing unless the bank was built with a `DormancyPolicy`.
+    pub fn sweep_dormant(&mut self) -> Vec<DormantAccount> {
+        let policy = match &self.config.dormancy {
+            Some(policy) => policy.clone(),
+            None => return Vec::new(),
+        };
+        let processed = self.summary.processed;
+        #[cfg(feature = "time")]
+        let latest = self.activity.values().filter_map(|activity| activity.timestamp).max().max(self.latest);
+        let mut idle: Vec<(ClientId, Activity)> = self
+            .activity
+            .iter()
+            .filter(|(_, activity)| match policy.after {
+                Inactivity::Records(records) => processed.saturating_sub(activity.record) >= records,
+                #[cfg(feature = "time")]
+                Inactivity::Period(period) => activity.timestamp.zip(latest).is_some_and(|(last, latest)| latest - last >= period),
+            })
+            .filter(|(client, _)| self.accounts.get(client).is_some_and(|account| account.dormancy == Dormancy::Active))
+            .map(|(client, activity)| (*client, *activity))
+            .collect();
+        idle.sort_unstable_by_key(|(client, _)| *client);
+        let dormancy = if policy.block_withdrawals { Dormancy::Blocked } else { Dormancy::Dormant };
+        let mut swept = Vec::with_capacity(idle.len());
+        for (client, activity) in idle {
+            if let Some(account) = self.accounts.get_mut(&client) {
+                account.set_dormancy(dormancy);
+            }
+            self.audit(client, AuditAction::Dormant);
+            let charged = match policy.fee {
+                Some(fee) => self.charge_fee(client, activity.tx, fee).map(|_| Some(fee)),
+                None => Ok(None),
             };
+            if let Err(reason) = &charged {
+                error!("Failed to charge the dormancy fee of client {}: {}", redact(&client), reason);
+            }
+            swept.push(DormantAccount {
+                client,
+                last_tx: activity.tx,
+                last_active: activity.timestamp,
+                fee: charged.as_ref().ok().copied().flatten(),
+                fee_error: charged.err(),
+            });
         }
-        return match accounts.get_mut(&client) {
-            Some(account) => Ok(account),
-            None => Err(BankingError::NoSuchAccount),
-        };
+        swept
     }
 
-    /// Returns the transaction associated with the specified ID. If no transaction
-    /// can be found by this ID, this function returns an appropriate error.
-    fn retrieve_transaction(tx_id: u32, transactions: &mut HashMap<u32, Transaction>) -> Result<&mut Transaction, BankingError> {
-        return match transactions.get_mut(&tx_id) {
-            Some(transaction) => Ok(transaction),
-            None => Err(BankingError::NoSuchTransaction),
-        };
+    /// Reactivate a dormant account, lifting any block on its withdrawals, and returning its new
+    /// version. The reactivation counts as activity, so the account is not flagged again until the
+    /// policy's period has passed once more. An account that is not dormant is left as it is, with
+    /// `AccountNotDormant`. `expected_version` is checked as for `unlock_account`.
+    pub fn reactivate_account(&mut self, client: ClientId, expected_version: Option<u64>) -> Result<u64, BankingError> {
+        let version = self
+            .compare_and_set(client, expected_version, |account| match account.dormancy {
+                Dormancy::Active => Err(BankingError::AccountNotDormant),
+                _ => {
+                    account.set_dormancy(Dormancy::Active);
+                    Ok(())
+                }
+            })
+            .map_err(|reason| self.deny(client, AdminCommand::Reactivate, reason))?;
+        self.touch(client);
+        self.audit(client, AuditAction::Reactivated);
+        Ok(version)
     }
 
-    /// This function processes the given transaction, taking ownership of the `Transaction` so
-    /// that it can be stored for later lookup.
+    /// Record a free-text note against an account, e.g. the reason it is under investigation. The
+    /// notes of an account are kept in the order they were recorded, and written alongside its
+    /// snapshots; see `write_annotations`.
+    pub fn annotate_account(&mut self, client: ClientId, note: &str) -> Result<(), BankingError> {
+        let note = note.trim().to_string();
+        if !self.accounts.contains_key(&client) {
+            return Err(self.deny(client, AdminCommand::Annotate(note), BankingError::NoSuchAccount));
+        }
+        self.annotations.entry(client).or_default().notes.push(note.clone());
+        self.audit(client, AuditAction::Annotated { note });
+        Ok(())
+    }
+
+    /// Set the label `key` of an account, e.g. a ticket reference, replacing its previous value, or
+    /// remove the label if `value` is `None`.
+    pub fn label_account(&mut self, client: ClientId, key: &str, value: Option<&str>) -> Result<(), BankingError> {
+        let (key, value) = (key.trim().to_string(), value.map(|value| value.trim().to_string()));
+        if !self.accounts.contains_key(&client) {
+            let command = AdminCommand::Label { key, value };
+            return Err(self.deny(client, command, BankingError::NoSuchAccount));
+        }
+        self.set_label(client, &key, value.clone());
+        self.audit(client, AuditAction::Labelled { key, value });
+        Ok(())
+    }
+
+    fn set_label(&mut self, client: ClientId, key: &str, value: Option<String>) {
+        let annotations = self.annotations.entry(client).or_default();
+        match value {
+            Some(value) => {
+                annotations.labels.insert(key.to_string(), value);
+            }
+            None => {
+                annotations.labels.remove(key);
+            }
+        }
+        if annotations.is_empty() {
+            self.annotations.remove(&client);
+        }
+    }
+
+    /// Returns the notes and labels recorded against an account, if any.
+    pub fn annotations(&self, client: ClientId) -> Option<&Annotations> {
+        self.annotations.get(&client)
+    }
+
+    /// Load annotations from a JSON object of client IDs to `Annotations`, as written by
+    /// `write_annotations`, e.g. to carry notes over from a previous run:
     ///
-    /// This function can return several errors but all are BankingError variants.
-    fn process_transaction(&mut self, mut transaction: Transaction) -> Result<(), BankingError> {
-        debug!("Processing Transaction: {:?}", transaction);
+    /// ```json
+    /// {"2": {"notes": ["chargeback under review"], "labels": {"ticket": "SUP-1234"}}}
+    /// ```
+    ///
+    /// Notes are added to those already recorded, and labels replace those with the same key. The
+    /// accounts need not exist yet. Returns the number of accounts annotated.
+    #[cfg(feature = "io")]
+    pub fn read_annotations<R: io::Read>(&mut self, reader: R) -> serde_json::Result<usize> {
+        let loaded: HashMap<ClientId, Annotations> = serde_json::from_reader(reader)?;
+        let count = loaded.len();
+        for (client, annotations) in loaded.into_iter().filter(|(_, annotations)| !annotations.is_empty()) {
+            self.annotations.entry(client).or_default().merge(annotations);
+        }
+        Ok(count)
+    }
+
+    /// Write the annotations of every account, in order of client ID, as read by
+    /// `read_annotations`.
+    #[cfg(feature = "io")]
+    pub fn write_annotations<W: io::Write>(&self, writer: W) -> serde_json::Result<()> {
+        let sorted: BTreeMap<&ClientId, &Annotations> = self.annotations.iter().collect();
+        serde_json::to_writer_pretty(writer, &sorted)
+    }
+
+    /// Whether any account has been annotated.
+    pub fn has_annotations(&self) -> bool {
+        !self.annotations.is_empty()
+    }
+
+    /// Restart the inactivity period of a reactivated account.
+    fn touch(&mut self, client: ClientId) {
+        let (processed, latest) = (self.summary.processed, self.latest);
+        if let Some(activity) = self.activity.get_mut(&client) {
+            activity.record = processed;
+            activity.timestamp = activity.timestamp.max(latest);
+        }
+    }
+
+    /// Note a transaction applied to the account of `client`, making a dormant account, whose
+    /// withdrawals are not blocked, active again.
+    fn record_activity(&mut self, client: ClientId, tx: TxId, timestamp: Option<Timestamp>) {
+        if self.config.dormancy.is_none() {
+            return;
+        }
+        let record = self.summary.processed;
+        self.activity.insert(client, Activity { tx, record, timestamp });
+        if let Some(account) = self.accounts.get_mut(&client).filter(|account| account.dormancy == Dormancy::Dormant) {
+            account.set_dormancy(Dormancy::Active);
+            self.audit(client, AuditAction::Reactivated);
+        }
+    }
+
+    /// Attribute the operations that follow to `operator` in the audit trail, e.g. a user, an API
+    /// key ID, or the name of an input file.
+    pub fn set_operator(&mut self, operator: Option<String>) {
+        self.operator = operator;
+    }
+
+    /// Returns the operator the operations that follow are attributed to.
+    pub fn operator(&self) -> Option<&str> {
+        self.operator.as_deref()
+    }
+
+    /// Returns the audit trail of the specified client, oldest first. It is empty unless the bank
+    /// keeps audit trails.
+    pub fn audit_trail(&self, client: ClientId) -> &[AuditEntry] {
+        self.audit.get(&client).map_or(&[], Vec::as_slice)
+    }
+
+    /// Append an action to the audit trail of `client`, if the bank keeps audit trails.
+    fn audit(&mut self, client: ClientId, action: AuditAction) {
+        self.audit_from(client, action, None);
+    }
+
+    /// As `audit`, attributing the action to the record it was taken for.
+    fn audit_from(&mut self, client: ClientId, action: AuditAction, source: Option<Provenance>) {
+        if self.config.audit {
+            let entry = AuditEntry::now(action, self.operator.clone()).with_source(source);
+            self.audit.entry(client).or_default().push(entry);
+        }
+    }
+
+    /// Append a refused attempt to the audit trail of `client`. Refusals are only recorded against
+    /// accounts that exist, so that input naming unknown clients does not grow the audit trails.
+    fn audit_refusal(&mut self, client: ClientId, action: AuditAction, source: Option<Provenance>) {
+        if self.accounts.contains_key(&client) {
+            self.audit_from(client, action, source);
+        }
+    }
+
+    /// Record that an administrative command was denied, returning the reason.
+    fn deny(&mut self, client: ClientId, command: AdminCommand, reason: BankingError) -> BankingError {
+        self.audit_refusal(
+            client,
+            AuditAction::Denied {
+                command,
+                reason: reason.clone(),
+            },
+            None,
+        );
+        reason
+    }
+
+    /// Remove a client's account and stored transactions, e.g. on a request for erasure of their
+    /// personal data. The account's balance is posted as an `ErasureAdjustment`, so that the totals
+    /// of the bank are unchanged, and the IDs of the removed transactions are remembered so that
+    /// they are still rejected as duplicates. The client's audit trail is replaced by a single
+    /// `AuditAction::Erased` entry recording the erasure.
+    ///
+    /// A client with funds held by an open dispute, or whose account is locked, can not be
+    /// forgotten; the dispute must be settled, or the account unlocked, first. Nor can one with a
+    /// transaction prepared by `prepare_transaction` or a reservation made by `reserve_batch`,
+    /// which must be committed, aborted or released first.
+    pub fn forget(&mut self, client: ClientId) -> Result<Erasure, BankingError> {
+        let account = self.accounts.get(&client).ok_or(BankingError::NoSuchAccount)?;
+        if account.locked {
+            return Err(BankingError::AccountLocked);
+        }
+        if self.prepared.values().any(|transaction| transaction.client == client)
+            || self.reservations.values().any(|reservation| reservation.client == client)
+        {
+            return Err(BankingError::PendingTransaction);
+        }
+        if account.held != Amount::zero() {
+            return Err(BankingError::OpenDispute);
+        }
+        let balance = account.total;
+        self.accounts.remove(&client);
+        self.statistics.remove(&client);
+        self.audit.remove(&client);
+        self.disputes.remove(&client);
+        self.activity.remove(&client);
+        self.annotations.remove(&client);
+        self.promoted.remove(&client);
+        self.promotions.retain(|_, promotion| promotion.client != client);
+        let promotions = &self.promotions;
+        self.expiries.retain(|(_, tx)| promotions.contains_key(tx));
+        self.fees.retain(|fee| fee.client != client);
+        let erased: Vec<TxId> = self
+            .transactions
+            .values()
+            .filter(|transaction| transaction.client == client)
+            .map(|transaction| transaction.tx)
+            .collect();
+        for tx in &erased {
+            self.transactions.remove(tx);
+            self.amendments.remove(tx);
+        }
+        self.erased_transactions.extend(&erased);
+        self.erasure_adjustments.push(ErasureAdjustment { client, amount: balance });
+        self.audit(
+            client,
+            AuditAction::Erased {
+                transactions: erased.len(),
+                balance,
+            },
+        );
+        Ok(Erasure {
+            client,
+            transactions: erased.len(),
+            balance,
+        })
+    }
+
+    /// Prepare a deposit or withdrawal without applying it, for an orchestrator that must apply it
+    /// atomically with a change to another system. Its ID is reserved, and the funds of a
+    /// withdrawal are held, until the transaction is completed by `commit` or undone by `abort`.
+    /// Returns the ID to commit or abort it by.
+    ///
+    /// A transaction that prepares successfully will commit unless a chargeback locks the account
+    /// in between, in which case it can still be aborted.
+    pub fn prepare_transaction(&mut self, transaction: Transaction) -> Result<TxId, BankingError> {
+        debug!("Preparing Transaction: {:?}", redact(&transaction));
+        if transaction.kind != TransactionType::Deposit && transaction.kind != TransactionType::Withdrawal {
+            return Err(BankingError::InvalidTransaction);
+        }
+        let transaction = self.admit(transaction)?;
+        let amount = transaction.amount.unwrap_or_else(Amount::zero);
+        match (&transaction.kind, self.accounts.get_mut(&transaction.client)) {
+            (TransactionType::Deposit, Some(account)) if account.locked => return Err(BankingError::AccountLocked),
+            // the account of a deposit is only opened on commit
+            (TransactionType::Deposit, _) => {}
+            (_, Some(account)) => account.reserve(&amount)?,
+            (_, None) => return Err(BankingError::NoSuchAccount),
+        }
+        let tx = transaction.tx;
+        self.prepared.insert(tx, transaction);
+        Ok(tx)
+    }
+
+    /// Apply a transaction prepared by `prepare_transaction`. If it can not be applied, it stays
+    /// prepared, to be aborted.
+    pub fn commit(&mut self, tx: TxId) -> Result<(), BankingError> {
+        let transaction = self.prepared.get(&tx).ok_or(BankingError::NoSuchTransaction)?;
+        let amount = transaction.amount.unwrap_or_else(Amount::zero);
+        let create = transaction.kind == TransactionType::Deposit;
+        let account = Bank::retrieve_account(transaction.client, &mut self.accounts, create)?;
         match transaction.kind {
-            ////////////////////////////////////////////////////////////////////////////////
-            TransactionType::Deposit => {
-                transaction.validate()?;
-                if self.transactions.contains_key(&transaction.tx) {
-                    return Err(BankingError::DuplicateTransactionId);
-                }
-                let account = Bank::retrieve_account(transaction.client, &mut self.accounts, true)?;
-                account.deposit(&transaction.amount.unwrap_or_else(|| dec!(0)))?;
-                self.transactions.insert(transaction.tx, transaction);
-                Ok(())
-            }
-            ////////////////////////////////////////////////////////////////////////////////
-            TransactionType::Withdrawal => {
-                transaction.validate()?;
-                if self.transactions.contains_key(&transaction.tx) {
-                    return Err(BankingError::DuplicateTransactionId);
-                }
-                let account = Bank::retrieve_account(transaction.client, &mut self.accounts, false)?;
-                account.withdraw(&transaction.amount.unwrap_or_else(|| dec!(0)))?;
-                self.transactions.insert(transaction.tx, transaction);
-                Ok(())
+            TransactionType::Deposit => account.deposit(&amount)?,
+            _ => account.settle(&amount)?,
+        }
+        if let Some(transaction) = self.prepared.remove(&tx) {
+            self.record_applied(transaction);
+        }
+        Ok(())
+    }
+
+    /// Undo a transaction prepared by `prepare_transaction`, releasing its ID and any funds it held.
+    pub fn abort(&mut self, tx: TxId) -> Result<(), BankingError> {
+        let transaction = self.prepared.get(&tx).ok_or(BankingError::NoSuchTransaction)?;
+        if transaction.kind == TransactionType::Withdrawal {
+            if let Some(account) = self.accounts.get_mut(&transaction.client) {
+                account.release(&transaction.amount.unwrap_or_else(Amount::zero))?;
             }
-            ////////////////////////////////////////////////////////////////////////////////
-            TransactionType::Dispute => {
-                let mut stored_transaction = Bank::retrieve_transaction(transaction.tx, &mut self.transactions)?;
-                transaction.validate_against_stored(stored_transaction)?;
-                let account = Bank::retrieve_account(transaction.client, &mut self.accounts, false)?;
-                account.dispute(&stored_transaction.amount.unwrap_or_else(|| dec!(0)))?;
-                stored_transaction.under_dispute = true;
-                Ok(())
+        }
+        self.prepared.remove(&tx);
+        Ok(())
+    }
+
+    /// Hold `total` against the account of `client` for a batch of outgoing withdrawals, so that
+    /// the batch can not be short of funds part way through. Withdrawals of the batch are applied
+    /// with `withdraw_reserved`, and whatever they leave unused is returned by `release_batch`.
+    /// Returns the ID of the reservation.
+    pub fn reserve_batch(&mut self, client: ClientId, total: Amount) -> Result<ReservationId, BankingError> {
+        if total <= Amount::zero() {
+            return Err(BankingError::InvalidTransaction);
+        }
+        Bank::retrieve_account(client, &mut self.accounts, false)?.reserve(&total)?;
+        let id = self.next_reservation;
+        self.next_reservation += 1;
+        self.reservations.insert(
+            id,
+            Reservation {
+                client,
+                reserved: total,
+                remaining: total,
+            },
+        );
+        Ok(id)
+    }
+
+    /// Apply a withdrawal of a batch, drawing its amount from the funds held by `reserve_batch`
+    /// rather than from the available balance. It is rejected with `InsufficientFunds` if it exceeds
+    /// what remains of the reservation.
+    pub fn withdraw_reserved(&mut self, reservation: ReservationId, transaction: Transaction) -> Result<(), BankingError> {
+        debug!("Processing Reserved Transaction: {:?}", redact(&transaction));
+        if transaction.kind != TransactionType::Withdrawal {
+            return Err(BankingError::InvalidTransaction);
+        }
+        let transaction = self.admit(transaction)?;
+        let held = self.reservations.get_mut(&reservation).ok_or(BankingError::NoSuchTransaction)?;
+        if held.client != transaction.client {
+            return Err(BankingError::ClientMismatch);
+        }
+        let amount = transaction.amount.unwrap_or_else(Amount::zero);
+        if held.remaining < amount {
+            return Err(BankingError::InsufficientFunds);
+        }
+        let remaining = held.remaining.checked_sub(amount).ok_or(BankingError::AmountOverflow)?;
+        Bank::retrieve_account(transaction.client, &mut self.accounts, false)?.settle(&amount)?;
+        held.remaining = remaining;
+        self.record_applied(transaction);
+        Ok(())
+    }
+
+    /// Close a reservation made by `reserve_batch`, returning the part of it the batch did not use
+    /// to the available balance. Returns that amount.
+    pub fn release_batch(&mut self, reservation: ReservationId) -> Result<Amount, BankingError> {
+        let held = self.reservations.get(&reservation).ok_or(BankingError::NoSuchTransaction)?;
+        if let Some(account) = self.accounts.get_mut(&held.client) {
+            account.release(&held.remaining)?;
+        }
+        let remaining = held.remaining;
+        self.reservations.remove(&reservation);
+        Ok(remaining)
+    }
+
+    /// Returns the reservation with the given ID, if it is still open.
+    pub fn reservation(&self, reservation: ReservationId) -> Option<&Reservation> {
+        self.reservations.get(&reservation)
+    }
+
+    /// Check the signature, amount, and ID of a deposit or withdrawal that is applied outside of
+    /// `process_transaction`, returning it without its signature.
+    fn admit(&mut self, mut transaction: Transaction) -> Result<Transaction, BankingError> {
+        self.verify_signature(&mut transaction)?;
+        transaction.validate_with_precision(self.config.decimal_places_for(&transaction), self.config.strict)?;
+        // a replay can not be ignored here, as the caller expects it to be applied
+        if self.check_duplicate(&transaction)? != DuplicateCheck::Unique {
+            return Err(BankingError::DuplicateTransactionId);
+        }
+        Ok(transaction)
+    }
+
+    /// Record a deposit or withdrawal applied outside of `process_transaction`, as that would.
+    fn record_applied(&mut self, transaction: Transaction) {
+        if self.config.statistics {
+            self.statistics.entry(transaction.client).or_default().record(&transaction);
+        }
+        self.summary.record(&Ok(()));
+        self.spend_promotions(transaction.client);
+        self.audit(transaction.client, AuditAction::Applied { kind: transaction.kind.clone(), tx: transaction.tx });
+        self.store(transaction);
+    }
+
+    /// Reconstruct the account of `client` as it was at the given point of a journal written by
+    /// this bank, by replaying the journal on a new bank with the same options. Returns `None` if
+    /// the client had no account at that point.
+    #[cfg(feature = "io")]
+    pub fn balance_at<R: io::Read>(&self, journal: R, client: ClientId, at: AsOf) -> Result<Option<Account>, JournalError> {
+        let config = BankConfig {
+            // the journal records transactions once verified, without their signatures
+            #[cfg(feature = "signature")]
+            signing_key: None,
+            ..self.config.clone()
+        };
+        let mut replay = Follower::new(Bank::with_handlers(config, self.handlers.clone()));
+        replay.apply_until(journal, at)?;
+        Ok(replay.bank().account(client).cloned())
+    }
+
+    /// Assess a fee for transaction `tx` against the account of `client`, posting it as a `Fee`.
+    pub fn charge_fee(&mut self, client: ClientId, tx: TxId, amount: Amount) -> Result<(), BankingError> {
+        Bank::retrieve_account(client, &mut self.accounts, false)?.charge_fee(&amount)?;
+        self.fees.push(Fee { client, tx, amount });
+        self.spend_promotions(client);
+        Ok(())
+    }
+
+    /// Credit a promotional credit to the client's account, opening it if need be. It is kept apart
+    /// from deposits, so that it can not be disputed, until it expires, when whatever the client
+    /// has not spent of it is clawed back; one without an expiry is kept for good. Spending is taken
+    /// from the client's other funds first, and then from their promotional credits in order of
+    /// expiry. Its ID may not be reused by any other transaction.
+    pub fn credit_promotion(&mut self, transaction: Transaction) -> Result<(), BankingError> {
+        let (client, tx) = (transaction.client, transaction.tx);
+        if self.check_duplicate(&transaction)? != DuplicateCheck::Unique {
+            return Err(BankingError::DuplicateTransactionId);
+        }
+        let amount = transaction.amount.unwrap_or_else(Amount::zero);
+        self.spend_promotions(client);
+        let promoted = self.promoted.get(&client).copied().unwrap_or_else(Amount::zero);
+        let promoted = promoted.checked_add(amount).ok_or(BankingError::AmountOverflow)?;
+        self.account_mut(client, true)?.deposit(&amount)?;
+        self.promoted.insert(client, promoted);
+        if let Some(expires) = transaction.expires {
+            self.expiries.insert((expires, tx));
+        }
+        let expires = transaction.expires;
+        self.promotions.insert(tx, Promotion { client, amount, expires });
+        Ok(())
+    }
+
+    /// Take whatever the client has spent of their promotional credits out of them. Their other
+    /// funds are spent first, so the credits have been spent by as much as the client's total
+    /// funds have fallen below them; funds merely held are not spent. That is taken from the
+    /// credits in the order `credit_promotion` spends them: soonest to expire first, and those
+    /// without an expiry last.
+    fn spend_promotions(&mut self, client: ClientId) {
+        let promoted = match self.promoted.get(&client) {
+            Some(promoted) => *promoted,
+            None => return,
+        };
+        let total = self
+            .account(client)
+            .map_or_else(Amount::zero, |account| account.total)
+            .max(Amount::zero());
+        if promoted <= total {
+            return;
+        }
+        // both are positive, so neither this nor taking from the credits below can overflow
+        let mut spent = promoted - total;
+        let mut credits: Vec<(Option<Timestamp>, TxId)> = self
+            .promotions
+            .iter()
+            .filter(|(_, promotion)| promotion.client == client && !promotion.amount.is_zero())
+            .map(|(tx, promotion)| (promotion.expires, *tx))
+            .collect();
+        credits.sort_by_key(|&(expires, tx)| (expires.is_none(), expires, tx));
+        for (_, tx) in credits {
+            let promotion = self.promotions.get_mut(&tx).expect("collected from the promotions");
+            let taken = promotion.amount.min(spent);
+            promotion.amount -= taken;
+            spent -= taken;
+            if spent.is_zero() {
+                break;
             }
-            ////////////////////////////////////////////////////////////////////////////////
-            TransactionType::Resolve => {
-                let mut stored_transaction = Bank::retrieve_transaction(transaction.tx, &mut self.transactions)?;
-                transaction.validate_against_stored(stored_transaction)?;
-                let account = Bank::retrieve_account(transaction.client, &mut self.accounts, false)?;
-                account.resolve(&stored_transaction.amount.unwrap_or_else(|| dec!(0)))?;
-                stored_transaction.under_dispute = false;
-                Ok(())
+        }
+        if total.is_zero() {
+            self.promoted.remove(&client);
+        } else {
+            self.promoted.insert(client, total);
+        }
+    }
+
+    /// Returns the part of the client's promotional credits they have not spent.
+    pub fn promotional_balance(&self, client: ClientId) -> Amount {
+        let promoted = self.promoted.get(&client).copied().unwrap_or_else(Amount::zero);
+        let available = self.account(client).map_or_else(Amount::zero, |account| account.available);
+        promoted.min(available.max(Amount::zero()))
+    }
+
+    /// Expire every promotional credit expiring at or before `as_of`, soonest first, clawing back
+    /// the part of each the client has not spent. This is done before each transaction with a
+    /// timestamp, as of that timestamp; for input without timestamps, call it when the expiry time
+    /// has come. Returns the clawbacks made.
+    pub fn expire_promotions(&mut self, as_of: Timestamp) -> Vec<Clawback> {
+        let mut expired = Vec::new();
+        while let Some(&(expires, tx)) = self.expiries.first() {
+            if expires > as_of {
+                break;
             }
-            ////////////////////////////////////////////////////////////////////////////////
-            TransactionType::Chargeback => {
-                let mut stored_transaction = Bank::retrieve_transaction(transaction.tx, &mut self.transactions)?;
-                transaction.validate_against_stored(stored_transaction)?;
-                let account = Bank::retrieve_account(transaction.client, &mut self.accounts, false)?;
-                account.chargeback(&stored_transaction.amount.unwrap_or_else(|| dec!(0)))?;
-                stored_transaction.under_dispute = false;
-                Ok(())
+            self.expiries.pop_first();
+            let client = match self.promotions.get(&tx) {
+                Some(promotion) => promotion.client,
+                None => continue,
+            };
+            self.spend_promotions(client);
+            let Promotion { amount, .. } = self.promotions.remove(&tx).expect("found above");
+            let promoted = self.promoted.entry(client).or_insert_with(Amount::zero);
+            // what is left of a credit is part of the unspent credits, so this can not overflow
+            *promoted -= amount;
+            // the credits expiring later are spent last
+            let later = *promoted;
+            let clawed_back = match self.accounts.get_mut(&client) {
+                Some(account) => {
+                    // only an available balance too far below zero to hold `later` overflows
+                    let spendable = account.available.checked_sub(later).unwrap_or_else(Amount::zero);
+                    let unspent = amount.min(spendable.max(Amount::zero()));
+                    match account.claw_back(&unspent) {
+                        Ok(()) => unspent,
+                        Err(e) => {
+                            error!("Could not claw back expired promotion {} of client {}: {}", tx, client, e);
+                            Amount::zero()
+                        }
+                    }
+                }
+                None => Amount::zero(),
+            };
+            self.audit(client, AuditAction::Expired { tx, clawed_back });
+            expired.push(Clawback {
+                client,
+                tx,
+                amount: clawed_back,
+            });
+        }
+        self.promoted.retain(|_, promoted| !promoted.is_zero());
+        self.clawbacks.extend(expired.iter().cloned());
+        expired
+    }
+
+    /// Returns the clawbacks of expired promotional credits, oldest first.
+    pub fn clawbacks(&self) -> &[Clawback] {
+        &self.clawbacks
+    }
+
+    /// Returns the fees posted by the bank, oldest first.
+    pub fn fees(&self) -> &[Fee] {
+        &self.fees
+    }
+
+    /// Returns the combined amount of every fee posted by the bank, or `AmountOverflow` if it can
+    /// not be represented.
+    pub fn total_fees(&self) -> Result<Amount, BankingError> {
+        Bank::sum(Amount::zero(), self.fees.iter().map(|fee| fee.amount))
+    }
+
+    /// Returns the adjustments posted by `forget`, in the order they were posted.
+    pub fn erasure_adjustments(&self) -> &[ErasureAdjustment] {
+        &self.erasure_adjustments
+    }
+
+    /// Returns the combined balance of every client removed by `forget`, or `AmountOverflow` if it
+    /// can not be represented.
+    pub fn erased_balance(&self) -> Result<Amount, BankingError> {
+        Bank::sum(Amount::zero(), self.erasure_adjustments.iter().map(|adjustment| adjustment.amount))
+    }
+
+    /// Apply an administrative operation to an account, provided it is still at `expected_version`
+    /// if one is given. Returns the account's version afterwards.
+    fn compare_and_set<F>(&mut self, client: ClientId, expected_version: Option<u64>, operation: F) -> Result<u64, BankingError>
+    where
+        F: FnOnce(&mut Account) -> Result<(), BankingError>,
+    {
+        let account = Bank::retrieve_account(client, &mut self.accounts, false)?;
+        if let Some(expected) = expected_version {
+            if account.version != expected {
+                return Err(BankingError::VersionMismatch {
+                    expected,
+                    actual: account.version,
+                });
             }
         }
+        operation(account)?;
+        Ok(account.version)
     }
-}
-//endregion
+
+    /// Count the entries of this bank's largest maps.
+    pub fn footprint(&self) -> Footprint {
+        Footprint {
+            accounts: self.accounts.len(),
+            transactions: self.transactions.len(),
+            pending: self.prepared.len() + self.reservations.len(),
+            audit_entries: self.audit.values().map(Vec::len).sum(),
+        }
+    }
+
+    /// Returns the outcomes of every transaction this bank has processed, across every call to
+    /// `process_all` and its variants.
+    pub fn summary(&self) -> ProcessingSummary {
+        self.summary
+    }
+
+    /// Returns the transaction counts of the specified client, if the bank keeps them and any
+    /// transaction has been applied to its account.
+    pub fn statistics(&self, client: ClientId) -> Option<&ClientStatistics> {
+        self.statistics.get(&client)
+    }
+
+    /// Returns an iterator over all accounts, in no particular order.
+    pub fn accounts(&self) -> impl Iterator<Item = &Account> {
+        self.accounts.values()
+    }
+
+    /// Returns up to `limit` accounts, in order of client ID, starting after the client `cursor`, or
+    /// from the first client without one. The page's `next` is the cursor of the page after it, so
+    /// that a client of a service can walk through every account without receiving them all at
+    /// once. Accounts opened while paging appear in a later page if their client ID is past the
+    /// cursor.
+    pub fn accounts_page(&self, cursor: Option<ClientId>, limit: usize) -> AccountPage<'_> {
+        let mut clients: Vec<ClientId> = self
+            .accounts
+            .keys()
+            .copied()
+            .filter(|&client| cursor.is_none_or(|cursor| client > cursor))
+            .collect();
+        let limit = limit.max(1);
+        let more = clients.len() > limit;
+        if more {
+            clients.select_nth_unstable(limit);
+            clients.truncate(limit);
+        }
+        clients.sort_unstable();
+        AccountPage {
+            accounts: clients.iter().map(|client| &self.accounts[client]).collect(),
+            next: if more { clients.last().copied() } else { None },
+        }
+    }
+
+    /// Returns every account, in order of client ID, in chunks of up to `chunk_size` accounts, to
+    /// stream an export of a large set of accounts without collecting its rows all at once.
+    pub fn export_accounts_chunked(&self, chunk_size: usize) -> impl Iterator<Item = Vec<&Account>> + '_ {
+        let mut clients: Vec<ClientId> = self.accounts.keys().copied().collect();
+        clients.sort_unstable();
+        let chunk_size = chunk_size.max(1);
+        (0..clients.len())
+            .step_by(chunk_size)
+            .map(move |start| clients[start..].iter().take(chunk_size).map(|client| &self.accounts[client]).collect())
+    }
+
+    /// Open an account with the balances of the given one, replacing any account the client
+    /// already has, e.g. to start from a snapshot of a previous run. Only the balances are
+    /// restored: the transactions behind them are not, so they can not be disputed.
+    pub fn restore_account(&mut self, account: Account) {
+        self.accounts.insert(account.client, account);
+    }
+
+    /// Keep only the accounts for which `keep` returns true, e.g. to narrow the output down to the
+    /// accounts of interest once processing is over. The transactions of the others are kept.
+    pub fn retain_accounts<F: FnMut(&Account) -> bool>(&mut self, mut keep: F) {
+        self.accounts.retain(|_, account| keep(account));
+    }
+
+    /// Returns the account of the specified client, if it has one.
+    pub fn account(&self, client: ClientId) -> Option<&Account> {
+        self.accounts.get(&client)
+    }
+
+    /// Returns the account of the specified client for a `TransactionHandler` to change, opening it
+    /// first if `create` is set.
+    pub fn account_mut(&mut self, client: ClientId, create: bool) -> Result<&mut Account, BankingError> {
+        Bank::retrieve_account(client, &mut self.accounts, create)
+    }
+
+    /// Returns the stored transaction a dispute, resolve, or chargeback refers to, once it has
+    /// been validated against it, along with the account of its client.
+    pub fn referenced_transaction(&mut self, transaction: &mut Transaction) -> Result<(&mut Transaction, &mut Account), BankingError> {
+        if self.promotions.contains_key(&transaction.tx) {
+            return Err(BankingError::PromotionalCredit);
+        }
+        let stored_transaction = Bank::retrieve_transaction(transaction.tx, &mut self.transactions)?;
+        transaction.validate_against_stored(stored_transaction)?;
+        let account = Bank::retrieve_account(transaction.client, &mut self.accounts, false)?;
+        Ok((stored_transaction, account))
+    }
+
+    /// Returns the IDs of the client's transactions that are under dispute, in ascending order.
+    pub fn open_disputes(&self, client: ClientId) -> impl Iterator<Item = TxId> + '_ {
+        self.disputes.get(&client).into_iter().flatten().copied()
+    }
+
+    /// Returns the deposit or withdrawal with the specified ID, if one has been processed. Its
+    /// `under_dispute` flag reflects whether it is currently disputed.
+    pub fn transaction(&self, tx: TxId) -> Option<&Transaction> {
+        self.transactions.get(&tx)
+    }
+
+    /// Returns the funds available across all accounts, including the balances of forgotten
+    /// clients, or `AmountOverflow` if they can not be represented.
+    pub fn total_available(&self) -> Result<Amount, BankingError> {
+        Bank::sum(self.erased_balance()?, self.accounts().map(|account| account.available))
+    }
+
+    /// Returns the funds held across all accounts, or `AmountOverflow` if they can not be
+    /// represented.
+    pub fn total_held(&self) -> Result<Amount, BankingError> {
+        Bank::sum(Amount::zero(), self.accounts().map(|account| account.held))
+    }
+
+    /// Returns the total funds across all accounts, including the balances of forgotten clients,
+    /// or `AmountOverflow` if they can not be represented.
+    pub fn total(&self) -> Result<Amount, BankingError> {
+        Bank::sum(self.erased_balance()?, self.accounts().map(|account| account.total))
+    }
+
+    /// Add `amounts` to `initial`, failing with `AmountOverflow` rather than panicking when the sum
+    /// of many large balances can not be represented.
+    fn sum<I: Iterator<Item = Amount>>(initial: Amount, mut amounts: I) -> Result<Amount, BankingError> {
+        amounts.try_fold(initial, |sum, amount| sum.checked_add(amount).ok_or(BankingError::AmountOverflow))
+    }
+
+    /// Returns the account for the specified client id, creating it if it does not exist.
+    /// In the event the account is locked due to a chargeback, or the creation of a new
+    /// account fails, this function returns an appropriate error.
+    fn retrieve_account(client: ClientId, accounts: &mut HashMap<ClientId, Account>, create: bool) -> Result<&mut Account, BankingError> {
+        if create {
+            accounts.entry(client).or_insert_with(|| Account::new(client));
+        }
+        match accounts.get_mut(&client) {
+            Some(account) => Ok(account),
+            None => Err(BankingError::NoSuchAccount),
+        }
+    }
+
+    /// Returns the transaction associated with the specified ID. If no transaction
+    /// can be found by this ID, this function returns an appropriate error.
+    fn retrieve_transaction(tx_id: TxId, transactions: &mut HashMap<TxId, Transaction>) -> Result<&mut Transaction, BankingError> {
+        match transactions.get_mut(&tx_id) {
+            Some(transaction) => Ok(transaction),
+            None => Err(BankingError::NoSuchTransaction),
+        }
+    }
+
+    /// Checks whether a deposit or withdrawal reuses the ID of a stored transaction, and decides
+    /// what to do with it under the `DuplicatePolicy`; a reuse the policy does not accept is
+    /// rejected with `DuplicateTransactionId`. Reuses of evicted IDs are always rejected, as they
+    /// can not be compared, as are reuses of the IDs of prepared transactions.
+    pub fn check_duplicate(&mut self, transaction: &Transaction) -> Result<DuplicateCheck, BankingError> {
+        let tx = transaction.tx;
+        if !self.transactions.contains_key(&tx)
+            && (self.prepared.contains_key(&tx)
+                || self.promotions.contains_key(&tx)
+                || self.erased_transactions.contains(&tx)
+                || self.is_evicted(tx))
+        {
+            return Err(BankingError::DuplicateTransactionId);
+        }
+        match self.transactions.get(&tx) {
+            None => Ok(DuplicateCheck::Unique),
+            Some(stored)
+                if self.config.duplicate_policy == DuplicatePolicy::IgnoreReplays
+                    && stored.kind == transaction.kind
+                    && stored.client == transaction.client
+                    && stored.amount == transaction.amount =>
+            {
+                Ok(DuplicateCheck::Skip)
+            }
+            Some(_) if self.config.duplicate_policy == DuplicatePolicy::Skip => Ok(DuplicateCheck::Skip),
+            Some(_) if self.config.duplicate_policy == DuplicatePolicy::LastWriteWins && transaction.correction => {
+                Ok(DuplicateCheck::Correct)
+            }
+            Some(_) => Err(BankingError::DuplicateTransactionId),
+        }
+    }
+
+    /// Count a transaction skipped as a duplicate, for `ProcessingSummary::skipped`.
+    pub fn skip_duplicate(&mut self, transaction: &Transaction) {
+        debug!("Skipping duplicate transaction: {}", transaction.tx);
+        self.summary.skipped += 1;
+    }
+
+    /// Apply a correction of the deposit or withdrawal stored under the same ID, replacing its
+    /// amount as `adjust` does. The correction must have the same type as the stored transaction.
+    pub fn correct(&mut self, transaction: Transaction) -> Result<(), BankingError> {
+        match self.transactions.get(&transaction.tx) {
+            Some(stored) if stored.kind != transaction.kind => Err(BankingError::DuplicateTransactionId),
+            _ => self.adjust(transaction.client, transaction.tx, transaction.amount.unwrap_or_else(Amount::zero)),
+        }
+    }
+
+    /// Replace the amount of the deposit or withdrawal of `client` stored as `tx`, adjusting the
+    /// account by the difference, and record the change as an `Amendment`. The transaction must
+    /// not be under dispute.
+    pub fn adjust(&mut self, client: ClientId, tx: TxId, amount: Amount) -> Result<(), BankingError> {
+        let stored = self.transactions.get_mut(&tx).ok_or(BankingError::NoSuchTransaction)?;
+        if stored.kind != TransactionType::Deposit && stored.kind != TransactionType::Withdrawal {
+            return Err(BankingError::InvalidTransaction);
+        }
+        if stored.client != client {
+            return Err(BankingError::ClientMismatch);
+        }
+        if stored.under_dispute {
+            return Err(BankingError::OpenDispute);
+        }
+        let previous = stored.amount.unwrap_or_else(Amount::zero);
+        // amending a withdrawal moves funds the other way
+        let (credited, debited) = match stored.kind {
+            TransactionType::Deposit => (amount, previous),
+            _ => (previous, amount),
+        };
+        let account = Bank::retrieve_account(client, &mut self.accounts, false)?;
+        if credited > debited {
+            account.deposit(&credited.checked_sub(debited).ok_or(BankingError::AmountOverflow)?)?;
+        } else if debited > credited {
+            account.withdraw(&debited.checked_sub(credited).ok_or(BankingError::AmountOverflow)?)?;
+        }
+        stored.amount = Some(amount);
+        self.amendments.entry(tx).or_default().push(Amendment {
+            client,
+            tx,
+            previous,
+            amount,
+        });
+        Ok(())
+    }
+
+    /// Returns the amendments of the stored transaction `tx`, oldest first.
+    pub fn amendments(&self, tx: TxId) -> &[Amendment] {
+        self.amendments.get(&tx).map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns the approximate number of bytes taken by the accounts, stored transactions, and
+    /// eviction bookkeeping of the bank, counted from the number of entries rather than measured.
+    /// Memory held by the strings of a transaction, such as its currency, is not counted.
+    pub fn memory_usage(&self) -> usize {
+        self.accounts.len() * size_of::<(ClientId, Account)>()
+            + self.transactions.len() * size_of::<(TxId, Transaction)>()
+            + self.eviction_memory_usage()
+    }
+
+    /// Store a deposit or withdrawal for later lookup, then evict any transactions the retention
+    /// policy no longer keeps.
+    pub fn store(&mut self, transaction: Transaction) {
+        let tx = transaction.tx;
+        let time = transaction.timestamp.or(self.latest);
+        self.latest = self.latest.max(time);
+        self.transactions.insert(tx, transaction);
+        self.retain(tx, time);
+    }
+
+    /// This function processes the given transaction, taking ownership of the `Transaction` so
+    /// that it can be stored for later lookup. It is applied by the handler of its type; see
+    /// `handler`.
+    ///
+    /// This function can return several errors but all are BankingError variants.
+    pub(crate) fn process_transaction(&mut self, transaction: Transaction) -> Result<(), BankingError> {
+        let (client, kind, tx) = (transaction.client, transaction.kind.clone(), transaction.tx);
+        let (source, timestamp) = (transaction.provenance.clone(), transaction.timestamp);
+        if let Some(time) = timestamp.filter(|time| self.expiries.first().is_some_and(|(expires, _)| expires <= time)) {
+            let clawbacks = self.expire_promotions(time);
+            info!("Expired {} promotional credits as of {}", clawbacks.len(), time);
+        }
+        let result = self.apply(transaction);
+        self.summary.record(&result);
+        match &result {
+            Ok(()) => {
+                self.index_dispute(tx);
+                self.spend_promotions(client);
+                self.record_activity(client, tx, timestamp);
+                self.audit_from(client, AuditAction::Applied { kind, tx }, source);
+            }
+            Err(reason) => self.audit_refusal(
+                client,
+                AuditAction::Rejected {
+                    kind,
+                    tx,
+                    reason: reason.clone(),
+                },
+                source,
+            ),
+        }
+        result
+    }
+
+    /// Bring the index of open disputes in step with the `under_dispute` flag of the stored
+    /// transaction, which a handler may just have changed.
+    fn index_dispute(&mut self, tx: TxId) {
+        let stored = match self.transactions.get(&tx) {
+            Some(stored) => stored,
+            None => return,
+        };
+        if stored.under_dispute {
+            self.disputes.entry(stored.client).or_default().insert(tx);
+        } else if let Some(open) = self.disputes.get_mut(&stored.client) {
+            open.remove(&tx);
+            if open.is_empty() {
+                self.disputes.remove(&stored.client);
+            }
+        }
+    }
+
+    /// Verify the signature of the given transaction, if the bank checks signatures, and apply it
+    /// with the handler of its type.
+    fn apply(&mut self, mut transaction: Transaction) -> Result<(), BankingError> {
+        debug!("Processing Transaction: {:?}", redact(&transaction));
+        self.verify_signature(&mut transaction)?;
+        let stored = matches!(transaction.kind, TransactionType::Deposit | TransactionType::Withdrawal);
+        if stored && self.memory_exhausted() {
+            return Err(BankingError::MemoryLimitExceeded);
+        }
+        // custom types are resolved against this bank's handlers, and rejected if it has none
+        let handler = self.handlers.get(&transaction.kind).ok_or(BankingError::InvalidTransaction)?;
+        if !self.config.statistics {
+            return handler.process(self, transaction);
+        }
+        let applied = transaction.clone();
+        handler.process(self, transaction)?;
+        self.statistics.entry(applied.client).or_default().record(&applied);
+        Ok(())
+    }
+    /// Verify the signature of the given transaction, if the bank checks signatures, and strip it.
+    #[cfg(feature = "signature")]
+    fn verify_signature(&self, transaction: &mut Transaction) -> Result<(), BankingError> {
+        if let Some(key) = &self.config.signing_key {
+            signature::verify(key, transaction)?;
+            transaction.signature = None;
+        }
+        Ok(())
+    }
+
+    /// Without the `signature` feature, no bank checks signatures.
+    #[cfg(not(feature = "signature"))]
+    fn verify_signature(&self, _: &mut Transaction) -> Result<(), BankingError> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "retention")]
+impl Bank {
+    /// Queue a stored transaction for eviction, aged from `time`, if the retention policy evicts
+    /// any, and evict those the policy no longer keeps.
+    fn retain(&mut self, tx: TxId, time: Option<Timestamp>) {
+        if self.config.retention != RetentionPolicy::KeepAll {
+            self.retained.push_back((tx, time));
+            self.evict();
+        }
+    }
+
+    /// Whether the retention policy evicted a transaction with the given ID.
+    fn is_evicted(&mut self, tx: TxId) -> bool {
+        self.evicted.as_mut().is_some_and(|evicted| evicted.contains(tx))
+    }
+
+    /// The approximate number of bytes taken by the eviction bookkeeping of the bank.
+    fn eviction_memory_usage(&self) -> usize {
+        self.retained.len() * size_of::<(TxId, Option<Timestamp>)>() + self.evicted.as_ref().map_or(0, EvictedIds::memory_usage)
+    }
+
+    /// Whether a deposit or withdrawal may not be stored, as the memory limit is exceeded even
+    /// after evicting every transaction not under dispute.
+    fn memory_exhausted(&mut self) -> bool {
+        match self.config.retention {
+            RetentionPolicy::Memory(limit) if self.memory_usage() > limit => {
+                self.evict();
+                self.memory_usage() > limit
+            }
+            _ => false,
+        }
+    }
+
+    /// Evict the oldest transactions that fall outside the retention policy. A transaction under
+    /// dispute is moved to the back of the queue instead, to be reconsidered once it has aged out
+    /// again.
+    fn evict(&mut self) {
+        for _ in 0..self.retained.len() {
+            let expired = match (self.config.retention, self.retained.front()) {
+                (RetentionPolicy::Count(count), Some(_)) => self.retained.len() > count,
+                #[cfg(feature = "time")]
+                (RetentionPolicy::Age(age), Some((_, Some(time)))) => self.latest.is_some_and(|latest| latest - *time > age),
+                (RetentionPolicy::Memory(limit), Some(_)) => self.memory_usage() > limit,
+                _ => false,
+            };
+            if !expired {
+                return;
+            }
+            let (tx, time) = self.retained.pop_front().expect("checked above");
+            match self.transactions.get(&tx) {
+                Some(transaction) if transaction.under_dispute => self.retained.push_back((tx, self.latest.max(time))),
+                Some(_) => {
+                    self.transactions.remove(&tx);
+                    if let Some(evicted) = &mut self.evicted {
+                        evicted.insert(tx);
+                    }
+                }
+                // already removed by `forget`
+                None => {}
+            }
+        }
+    }
+}
+
+/// Without the `retention` feature, every transaction is kept for the lifetime of the bank.
+#[cfg(not(feature = "retention"))]
+impl Bank {
+    fn retain(&mut self, _: TxId, _: Option<Timestamp>) {}
+
+    fn is_evicted(&mut self, _: TxId) -> bool {
+        false
+    }
+
+    fn eviction_memory_usage(&self) -> usize {
+        0
+    }
+
+    fn memory_exhausted(&mut self) -> bool {
+        false
+    }
+}
+
+impl Default for Bank {
+    fn default() -> Bank {
+        Bank::new()
+    }
+}
+
+/// Write each row in CSV format, with a header row, and flush the writer.
+#[cfg(feature = "io")]
+fn write_rows<W: io::Write, S: Serialize, I: IntoIterator<Item = S>>(writer: W, rows: I) -> Result<(), csv::Error> {
+    let mut wtr = csv::WriterBuilder::new().from_writer(writer);
+    for row in rows {
+        wtr.serialize(row)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+//endregion
 
 //region Tests
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::amount;
+    use crate::audit::AdminCommand;
+    use crate::account::AccountStatus;
+    use crate::config::{DisputePolicy, DormancyPolicy};
+    #[cfg(feature = "io")]
+    use crate::errors::RecordError;
+    #[cfg(feature = "io")]
+    use crate::fault::{FaultPlan, FaultyWriter};
+    use crate::testkit::Scenario;
+    #[cfg(feature = "time")]
+    use chrono::{DateTime, TimeZone, Utc};
+
+    const NEGATIVE_FIVE: i32 = -5;
+    const ZERO: u32 = 0;
+    const ONE: u32 = 1;
+    const TWO: u32 = 2;
+    const THREE: u32 = 3;
+    const _FOUR: u32 = 4;
+    const FIVE: u32 = 5;
+
+    #[test]
+    fn deposit_valid_transaction_returns_ok_and_adds_to_account() -> Result<(), BankingError> {
+        // SETUP
+        let expected = Amount::from(FIVE);
+        let mut bank = Bank::new();
+        let tx1 = Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, FIVE, false);
+
+        // TEST
+        bank.process_transaction(tx1)?;
+        let actual = bank.accounts.get(&(ONE as ClientId)).unwrap().available;
+        assert_eq!(expected, actual);
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn deposit_negative_number_returns_invalid_transaction() -> Result<(), BankingError> {
+        // SETUP
+        let expected = BankingError::InvalidTransaction;
+        let mut bank = Bank::new();
+        let tx1 = Transaction::make_negative(TransactionType::Deposit, ONE as ClientId, ONE as TxId, NEGATIVE_FIVE);
+
+        // TEST
+        let actual = bank.process_transaction(tx1);
+        assert!(actual.is_err());
+        assert_eq!(expected, actual.unwrap_err());
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn withdrawal_with_insufficient_funds_returns_insufficient_funds() -> Result<(), BankingError> {
+        // SETUP
+        let expected = BankingError::InsufficientFunds;
+        let mut bank = Bank::new();
+        let tx1 = Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, ONE, false);
+        let tx2 = Transaction::make(TransactionType::Withdrawal, ONE as ClientId, TWO as TxId, TWO, false);
+
+        // TEST
+        bank.process_transaction(tx1)?;
+        let actual = bank.process_transaction(tx2);
+        assert!(actual.is_err());
+        assert_eq!(expected, actual.unwrap_err());
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn withdrawal_from_inexistent_account_returns_no_such_account() -> Result<(), BankingError> {
+        // SETUP
+        let expected = BankingError::NoSuchAccount;
+        let mut bank = Bank::new();
+        let tx1 = Transaction::make(TransactionType::Withdrawal, ONE as ClientId, TWO as TxId, TWO, false);
+
+        // TEST
+        let actual = bank.process_transaction(tx1);
+        assert!(actual.is_err());
+        assert_eq!(expected, actual.unwrap_err());
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn withdrawal_negative_number_returns_invalid_transaction() -> Result<(), BankingError> {
+        // SETUP
+        let expected = BankingError::InvalidTransaction;
+        let mut bank = Bank::new();
+        let tx1 = Transaction::make_negative(TransactionType::Withdrawal, ONE as ClientId, ONE as TxId, NEGATIVE_FIVE);
+
+        // TEST
+        let actual = bank.process_transaction(tx1);
+        assert!(actual.is_err());
+        assert_eq!(expected, actual.unwrap_err());
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn withdrawal_works_with_sufficient_funds() -> Result<(), BankingError> {
+        // SETUP
+        let expected = Amount::from(THREE);
+        let mut bank = Bank::new();
+        let tx1 = Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, FIVE, false);
+        let tx2 = Transaction::make(TransactionType::Withdrawal, ONE as ClientId, TWO as TxId, TWO, false);
+
+        // TEST
+        bank.process_transaction(tx1)?;
+        bank.process_transaction(tx2)?;
+        let actual = bank.accounts.get(&(ONE as ClientId)).unwrap().available;
+        assert_eq!(expected, actual);
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn transact_with_duplicate_transaction_id_returns_duplicate_transaction_id() -> Result<(), BankingError> {
+        // SETUP
+        let expected = BankingError::DuplicateTransactionId;
+        let mut bank = Bank::new();
+        let tx1 = Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, ONE, false);
+        let tx2 = Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, ONE, false);
+        let tx3 = Transaction::make(TransactionType::Withdrawal, ONE as ClientId, ONE as TxId, ONE, false);
+
+        // TEST
+        bank.process_transaction(tx1)?;
+        let first_actual = bank.process_transaction(tx2);
+        let second_actual = bank.process_transaction(tx3);
+        assert!(first_actual.is_err());
+        assert_eq!(expected, first_actual.unwrap_err());
+        assert!(second_actual.is_err());
+        assert_eq!(expected, second_actual.unwrap_err());
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn dispute_transaction_with_invalid_id_returns_no_such_transaction() -> Result<(), BankingError> {
+        // SETUP
+        let expected = BankingError::NoSuchTransaction;
+        let mut bank = Bank::new();
+        let tx1 = Transaction::make_dispute(ONE as ClientId, ONE as TxId);
+
+        // TEST
+        let actual = bank.process_transaction(tx1);
+        assert!(actual.is_err());
+        assert_eq!(expected, actual.unwrap_err());
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn dispute_valid_transaction() -> Result<(), BankingError> {
+        // SETUP
+        let expected_transaction = Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, FIVE, true);
+        let expected_account = Account {
+            client: ONE as ClientId,
+            available: Amount::from(ZERO),
+            total: Amount::from(FIVE),
+            held: Amount::from(FIVE),
+            locked: false,
+            frozen: false,
+            dormancy: Dormancy::Active,
+            version: 2,
+        };
+        let mut bank = Bank::new();
+        let tx1 = Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, FIVE, false);
+        let tx2 = Transaction::make_dispute(ONE as ClientId, ONE as TxId);
+
+        // TEST
+        bank.process_transaction(tx1)?;
+        bank.process_transaction(tx2)?;
+
+        assert_eq!(expected_transaction, *bank.transactions.get(&(ONE as TxId)).unwrap());
+        assert_eq!(expected_account, *bank.accounts.get(&(ONE as ClientId)).unwrap());
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn dispute_disputed_transaction_returns_already_in_dispute() -> Result<(), BankingError> {
+        // SETUP
+        let expected_result = BankingError::DuplicateDisputeRequest;
+        let expected_transaction = Transaction {
+            kind: TransactionType::Deposit,
+            client: ONE as ClientId,
+            tx: ONE as TxId,
+            amount: Some(Amount::from(FIVE)),
+            under_dispute: true,
+            timestamp: None,
+            currency: None,
+            signature: None,
+            correction: false,
+            provenance: None,
+            expires: None,
+        };
+        let expected_account = Account {
+            client: ONE as ClientId,
+            available: Amount::from(ZERO),
+            total: Amount::from(FIVE),
+            held: Amount::from(FIVE),
+            locked: false,
+            frozen: false,
+            dormancy: Dormancy::Active,
+            version: 2,
+        };
+        let mut bank = Bank::new();
+        let tx1 = Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, FIVE, false);
+        let tx2 = Transaction::make_dispute(ONE as ClientId, ONE as TxId);
+        let tx3 = Transaction::make_dispute(ONE as ClientId, ONE as TxId);
+
+        // TEST
+        bank.process_transaction(tx1)?;
+        bank.process_transaction(tx2)?;
+        let result = bank.process_transaction(tx3);
+
+        assert_eq!(expected_transaction, *bank.transactions.get(&(ONE as TxId)).unwrap());
+        assert_eq!(expected_account, *bank.accounts.get(&(ONE as ClientId)).unwrap());
+        assert_eq!(expected_result, result.unwrap_err());
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_disputed_transaction_releases_held_funds() -> Result<(), BankingError> {
+        // SETUP
+        let expected_transaction = Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, FIVE, false);
+        let expected_account = Account {
+            client: ONE as ClientId,
+            available: Amount::from(FIVE),
+            total: Amount::from(FIVE),
+            held: Amount::from(ZERO),
+            locked: false,
+            frozen: false,
+            dormancy: Dormancy::Active,
+            version: 3,
+        };
+        let mut bank = Bank::new();
+        let tx1 = Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, FIVE, false);
+        let tx2 = Transaction::make_dispute(ONE as ClientId, ONE as TxId);
+        let tx3 = Transaction::make_resolve(ONE as ClientId, ONE as TxId);
+
+        // TEST
+        bank.process_transaction(tx1)?;
+        bank.process_transaction(tx2)?;
+        bank.process_transaction(tx3)?;
+
+        assert_eq!(expected_account, *bank.accounts.get(&(ONE as ClientId)).unwrap());
+        assert_eq!(expected_transaction, *bank.transactions.get(&(ONE as TxId)).unwrap());
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn chargeback_disputed_transaction_withdraws_available_funds_and_locks_account() -> Result<(), BankingError> {
+        // SETUP
+        let expected_transaction = Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, FIVE, false);
+        let expected_account = Account {
+            client: ONE as ClientId,
+            available: Amount::from(ZERO),
+            total: Amount::from(ZERO),
+            held: Amount::from(ZERO),
+            locked: true,
+            frozen: false,
+            dormancy: Dormancy::Active,
+            version: 3,
+        };
+        let mut bank = Bank::new();
+        let tx1 = Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, FIVE, false);
+        let tx2 = Transaction::make_dispute(ONE as ClientId, ONE as TxId);
+        let tx3 = Transaction::make_chargeback(ONE as ClientId, ONE as TxId);
+
+        // TEST
+        bank.process_transaction(tx1)?;
+        bank.process_transaction(tx2)?;
+        bank.process_transaction(tx3)?;
+
+        assert_eq!(expected_account, *bank.accounts.get(&(ONE as ClientId)).unwrap());
+        assert_eq!(expected_transaction, *bank.transactions.get(&(ONE as TxId)).unwrap());
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn dispute_transaction_after_withdrawal_allows_negative_total() -> Result<(), BankingError> {
+        // SETUP
+        let expected_transaction = Transaction {
+            kind: TransactionType::Deposit,
+            client: ONE as ClientId,
+            tx: ONE as TxId,
+            amount: Some(Amount::from(FIVE)),
+            under_dispute: true,
+            timestamp: None,
+            currency: None,
+            signature: None,
+            correction: false,
+            provenance: None,
+            expires: None,
+        };
+        let expected_account = Account {
+            client: ONE as ClientId,
+            available: Amount::from(NEGATIVE_FIVE),
+            total: Amount::from(ZERO),
+            held: Amount::from(FIVE),
+            locked: false,
+            frozen: false,
+            dormancy: Dormancy::Active,
+            version: 3,
+        };
+        let mut bank = Bank::new();
+        let tx1 = Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, FIVE, false);
+        let tx2 = Transaction::make(TransactionType::Withdrawal, ONE as ClientId, TWO as TxId, FIVE, false);
+        let tx3 = Transaction::make_dispute(ONE as ClientId, ONE as TxId);
+
+        // TEST
+        bank.process_transaction(tx1)?;
+        bank.process_transaction(tx2)?;
+        bank.process_transaction(tx3)?;
+
+        assert_eq!(expected_account, *bank.accounts.get(&(ONE as ClientId)).unwrap());
+        assert_eq!(expected_transaction, *bank.transactions.get(&(ONE as TxId)).unwrap());
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn chargeback_transaction_after_withdrawal_allows_negative_total() -> Result<(), BankingError> {
+        // SETUP
+        let expected_transaction = Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, FIVE, false);
+        let expected_account = Account {
+            client: ONE as ClientId,
+            available: Amount::from(NEGATIVE_FIVE),
+            total: Amount::from(NEGATIVE_FIVE),
+            held: Amount::from(ZERO),
+            locked: true,
+            frozen: false,
+            dormancy: Dormancy::Active,
+            version: 4,
+        };
+        let mut bank = Bank::new();
+        let tx1 = Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, FIVE, false);
+        let tx2 = Transaction::make(TransactionType::Withdrawal, ONE as ClientId, TWO as TxId, FIVE, false);
+        let tx3 = Transaction::make_dispute(ONE as ClientId, ONE as TxId);
+        let tx4 = Transaction::make_chargeback(ONE as ClientId, ONE as TxId);
+
+        // TEST
+        bank.process_transaction(tx1)?;
+        bank.process_transaction(tx2)?;
+        bank.process_transaction(tx3)?;
+        bank.process_transaction(tx4)?;
+
+        assert_eq!(expected_account, *bank.accounts.get(&(ONE as ClientId)).unwrap());
+        assert_eq!(expected_transaction, *bank.transactions.get(&(ONE as TxId)).unwrap());
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn transaction_on_locked_account_returns_account_locked() -> Result<(), BankingError> {
+        // SETUP
+        let expected_result = BankingError::AccountLocked;
+        let expected_transaction = Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, FIVE, false);
+        let expected_account = Account {
+            client: ONE as ClientId,
+            available: Amount::from(NEGATIVE_FIVE),
+            total: Amount::from(NEGATIVE_FIVE),
+            held: Amount::from(ZERO),
+            locked: true,
+            frozen: false,
+            dormancy: Dormancy::Active,
+            version: 4,
+        };
+        let mut bank = Bank::new();
+        let tx1 = Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, FIVE, false);
+        let tx2 = Transaction::make(TransactionType::Withdrawal, ONE as ClientId, TWO as TxId, FIVE, false);
+        let tx3 = Transaction::make_dispute(ONE as ClientId, ONE as TxId);
+        let tx4 = Transaction::make_chargeback(ONE as ClientId, ONE as TxId);
+        let tx5 = Transaction::make(TransactionType::Deposit, ONE as ClientId, THREE as TxId, FIVE, false);
+
+        // TEST
+        bank.process_transaction(tx1)?;
+        bank.process_transaction(tx2)?;
+        bank.process_transaction(tx3)?;
+        bank.process_transaction(tx4)?;
+        let result = bank.process_transaction(tx5);
+
+        assert_eq!(expected_result, result.unwrap_err());
+        assert_eq!(expected_account, *bank.accounts.get(&(ONE as ClientId)).unwrap());
+        assert_eq!(expected_transaction, *bank.transactions.get(&(ONE as TxId)).unwrap());
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn dispute_client_with_wrong_client_returns_client_mismatch() -> Result<(), BankingError> {
+        // SETUP
+        let expected_result = BankingError::ClientMismatch;
+        let expected_transaction = Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, FIVE, false);
+        let expected_account = Account {
+            client: ONE as ClientId,
+            available: Amount::from(FIVE),
+            total: Amount::from(FIVE),
+            held: Amount::from(ZERO),
+            locked: false,
+            frozen: false,
+            dormancy: Dormancy::Active,
+            version: 1,
+        };
+        let mut bank = Bank::new();
+        let tx1 = Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, FIVE, false);
+        let tx2 = Transaction::make_dispute(TWO as ClientId, ONE as TxId);
+
+        // TEST
+        bank.process_transaction(tx1)?;
+        let result = bank.process_transaction(tx2);
+
+        assert_eq!(expected_transaction, *bank.transactions.get(&(ONE as TxId)).unwrap());
+        assert_eq!(expected_account, *bank.accounts.get(&(ONE as ClientId)).unwrap());
+        assert_eq!(expected_result, result.unwrap_err());
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_transaction_not_under_dispute_returns_undisputed_transaction() -> Result<(), BankingError> {
+        // SETUP
+        let expected_result = BankingError::UndisputedTransaction;
+        let expected_transaction = Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, FIVE, false);
+        let expected_account = Account {
+            client: ONE as ClientId,
+            available: Amount::from(FIVE),
+            total: Amount::from(FIVE),
+            held: Amount::from(ZERO),
+            locked: false,
+            frozen: false,
+            dormancy: Dormancy::Active,
+            version: 1,
+        };
+        let mut bank = Bank::new();
+        let tx1 = Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, FIVE, false);
+        let tx2 = Transaction::make_resolve(ONE as ClientId, ONE as TxId);
+
+        // TEST
+        bank.process_transaction(tx1)?;
+        let result = bank.process_transaction(tx2);
+
+        assert_eq!(expected_transaction, *bank.transactions.get(&(ONE as TxId)).unwrap());
+        assert_eq!(expected_account, *bank.accounts.get(&(ONE as ClientId)).unwrap());
+        assert_eq!(expected_result, result.unwrap_err());
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn dispute_withdrawal_returns_invalid_transaction() -> Result<(), BankingError> {
+        // SETUP
+        let expected_result = BankingError::InvalidTransaction;
+        let expected_account = Account {
+            client: ONE as ClientId,
+            available: Amount::from(ZERO),
+            total: Amount::from(ZERO),
+            held: Amount::from(ZERO),
+            locked: false,
+            frozen: false,
+            dormancy: Dormancy::Active,
+            version: 2,
+        };
+        let mut bank = Bank::new();
+        let tx1 = Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, FIVE, false);
+        let tx2 = Transaction::make(TransactionType::Withdrawal, ONE as ClientId, TWO as TxId, FIVE, false);
+        let tx3 = Transaction::make_dispute(ONE as ClientId, TWO as TxId);
+
+        // TEST
+        bank.process_transaction(tx1)?;
+        bank.process_transaction(tx2)?;
+        let result = bank.process_transaction(tx3);
+
+        assert_eq!(expected_account, *bank.accounts.get(&(ONE as ClientId)).unwrap());
+        assert_eq!(expected_result, result.unwrap_err());
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn deserialize_dispute_with_amount_returns_field_error() {
+        // SETUP
+        let data = "type,client,tx,amount\ndispute,1,1,5.0\n";
+        let mut reader = csv::ReaderBuilder::new().from_reader(data.as_bytes());
+
+        // TEST
+        let result: Option<Result<Transaction, csv::Error>> = reader.deserialize().next();
+        let message = result.unwrap().unwrap_err().to_string();
+        assert!(message.contains("field `amount`"));
+
+        // TEARDOWN
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn deserialize_deposit_without_amount_returns_field_error() {
+        // SETUP
+        let data = "type,client,tx,amount\ndeposit,1,1,\n";
+        let mut reader = csv::ReaderBuilder::new().from_reader(data.as_bytes());
+
+        // TEST
+        let result: Option<Result<Transaction, csv::Error>> = reader.deserialize().next();
+        let message = result.unwrap().unwrap_err().to_string();
+        assert!(message.contains(&RecordError::MissingAmount.to_string()));
+
+        // TEARDOWN
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn deserialize_64_bit_transaction_id_returns_ok() {
+        // SETUP
+        let data = "type,client,tx,amount\ndeposit,1,18446744073709551615,1.0\n";
+        let mut reader = csv::ReaderBuilder::new().from_reader(data.as_bytes());
+
+        // TEST
+        let result: Option<Result<Transaction, csv::Error>> = reader.deserialize().next();
+        assert_eq!(u64::MAX, result.unwrap().unwrap().tx);
+
+        // TEARDOWN
+    }
+
+    #[test]
+    #[cfg(feature = "arrow")]
+    fn process_record_batch_applies_rows_and_exports_accounts() -> Result<(), ArrowError> {
+        // SETUP
+        use arrow_array::{ArrayRef, StringArray, UInt32Array};
+        use std::sync::Arc;
+        let batch = RecordBatch::try_from_iter(vec![
+            ("type", Arc::new(StringArray::from(vec!["deposit", "withdrawal"])) as ArrayRef),
+            ("client", Arc::new(UInt32Array::from(vec![1, 1])) as ArrayRef),
+            ("tx", Arc::new(UInt32Array::from(vec![1, 2])) as ArrayRef),
+            ("amount", Arc::new(StringArray::from(vec!["5", "2"])) as ArrayRef),
+        ])?;
+        let mut bank = Bank::new();
+
+        // TEST
+        bank.process_record_batch(&batch)?;
+        assert_eq!(Amount::from(THREE), bank.accounts.get(&(ONE as ClientId)).unwrap().available);
+        assert_eq!(1, bank.accounts_to_record_batch()?.num_rows());
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn queries_return_accounts_transactions_and_totals() -> Result<(), BankingError> {
+        // SETUP
+        let mut bank = Bank::new();
+        bank.process_transaction(Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, FIVE, false))?;
+        bank.process_transaction(Transaction::make(TransactionType::Deposit, TWO as ClientId, TWO as TxId, THREE, false))?;
+        bank.process_transaction(Transaction::make_dispute(TWO as ClientId, TWO as TxId))?;
+
+        // TEST
+        assert_eq!(Some(Amount::from(FIVE)), bank.account(ONE as ClientId).map(|account| account.available));
+        assert_eq!(None, bank.account(THREE as ClientId));
+        assert!(bank.transaction(TWO as TxId).unwrap().under_dispute);
+        assert_eq!(None, bank.transaction(THREE as TxId));
+        assert_eq!(2, bank.accounts().count());
+        assert_eq!(Ok(Amount::from(FIVE)), bank.total_available());
+        assert_eq!(Ok(Amount::from(THREE)), bank.total_held());
+        assert_eq!(Ok(Amount::from(FIVE + THREE)), bank.total());
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn process_all_summarizes_outcomes() {
+        // SETUP
+        let mut bank = Bank::new();
+        let transactions = vec![
+            Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, FIVE, false),
+            Transaction::make(TransactionType::Withdrawal, ONE as ClientId, TWO as TxId, FIVE + ONE, false),
+            Transaction::make_dispute(ONE as ClientId, ONE as TxId),
+        ];
+
+        // TEST
+        let summary = bank.process_all(transactions);
+        let expected = ProcessingSummary {
+            processed: 3,
+            applied: 2,
+            rejected: 1,
+            skipped: 0,
+        };
+        assert_eq!(expected, summary);
+        bank.process_all(vec![Transaction::make_resolve(ONE as ClientId, ONE as TxId)]);
+        assert_eq!((4, 3), (bank.summary().processed, bank.summary().applied));
+
+        // TEARDOWN
+    }
+
+    #[test]
+    #[cfg(feature = "sink")]
+    fn process_all_with_reports_every_result_to_sink() {
+        // SETUP
+        let mut bank = Bank::new();
+        let mut results = Vec::new();
+        let transactions = vec![
+            Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, FIVE, false),
+            Transaction::make_resolve(ONE as ClientId, ONE as TxId),
+        ];
+
+        // TEST
+        bank.process_all_with(transactions, &mut |index, transaction: &Transaction, result| {
+            results.push((index, transaction.kind.clone(), result))
+        });
+        let expected = vec![
+            (0, TransactionType::Deposit, Ok(())),
+            (1, TransactionType::Resolve, Err(BankingError::UndisputedTransaction)),
+        ];
+        assert_eq!(expected, results);
+
+        // TEARDOWN
+    }
+
+    #[test]
+    #[cfg(feature = "sink")]
+    fn cancelled_processing_stops_between_transactions() {
+        // SETUP
+        let mut bank = Bank::new();
+        let token = CancellationToken::new();
+        let transactions = (1..=FIVE).map(|tx| Transaction::make(TransactionType::Deposit, ONE as ClientId, tx as TxId, ONE, false));
+
+        // TEST
+        let handle = token.clone();
+        let mut cancel_after_second = |index, _: &Transaction, _| {
+            if index == 1 {
+                handle.cancel();
+            }
+        };
+        let summary = bank.process_with_cancel(transactions, &mut cancel_after_second, &token);
+        assert_eq!((2, 2), (summary.processed, summary.applied));
+        assert_eq!(Amount::from(TWO), bank.account(ONE as ClientId).unwrap().available);
+
+        // TEARDOWN
+    }
+
+    #[test]
+    fn every_change_bumps_version_and_unlock_checks_it() -> Result<(), BankingError> {
+        // SETUP
+        let mut bank = Bank::new();
+        bank.process_transaction(Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, FIVE, false))?;
+        bank.process_transaction(Transaction::make_dispute(ONE as ClientId, ONE as TxId))?;
+        bank.process_transaction(Transaction::make_chargeback(ONE as ClientId, ONE as TxId))?;
+        let _ = bank.process_transaction(Transaction::make(TransactionType::Deposit, ONE as ClientId, TWO as TxId, ONE, false));
+
+        // TEST
+        assert_eq!(3, bank.account(ONE as ClientId).unwrap().version);
+        let stale = bank.unlock_account(ONE as ClientId, Some(2));
+        assert_eq!(Err(BankingError::VersionMismatch { expected: 2, actual: 3 }), stale);
+        assert!(bank.account(ONE as ClientId).unwrap().locked);
+        assert_eq!(Ok(4), bank.unlock_account(ONE as ClientId, Some(3)));
+        assert!(!bank.account(ONE as ClientId).unwrap().locked);
+        assert_eq!(Err(BankingError::NoSuchAccount), bank.unlock_account(TWO as ClientId, None));
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn frozen_account_rejects_withdrawals_only() -> Result<(), BankingError> {
+        // SETUP
+        let mut bank = Bank::new();
+        bank.process_transaction(Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, FIVE, false))?;
+
+        // TEST
+        assert_eq!(Ok(2), bank.freeze_account(ONE as ClientId, Some(1)));
+        let withdrawal = Transaction::make(TransactionType::Withdrawal, ONE as ClientId, TWO as TxId, ONE, false);
+        assert_eq!(Err(BankingError::AccountFrozen), bank.process_transaction(withdrawal.clone()));
+        bank.process_transaction(Transaction::make(TransactionType::Deposit, ONE as ClientId, THREE as TxId, ONE, false))?;
+        bank.process_transaction(Transaction::make_dispute(ONE as ClientId, ONE as TxId))?;
+        bank.process_transaction(Transaction::make_resolve(ONE as ClientId, ONE as TxId))?;
+        assert!(bank.account(ONE as ClientId).unwrap().frozen);
+        bank.unfreeze_account(ONE as ClientId, None)?;
+        bank.process_transaction(withdrawal)?;
+        assert_eq!(Amount::from(FIVE), bank.account(ONE as ClientId).unwrap().available);
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn idle_accounts_are_swept_as_dormant_until_reactivated() -> Result<(), BankingError> {
+        // SETUP
+        let policy = DormancyPolicy {
+            fee: Some(Amount::from(ONE)),
+            block_withdrawals: true,
+            ..DormancyPolicy::new(Inactivity::Records(TWO as usize))
+        };
+        let mut bank = Bank::builder().dormancy(policy).build();
+        bank.process_transaction(Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, FIVE, false))?;
+        bank.process_transaction(Transaction::make(TransactionType::Deposit, TWO as ClientId, TWO as TxId, ONE, false))?;
+        bank.process_transaction(Transaction::make(TransactionType::Deposit, TWO as ClientId, THREE as TxId, ONE, false))?;
+
+        // TEST
+        let swept = bank.sweep_dormant();
+        assert_eq!(vec![ONE as ClientId], swept.iter().map(|account| account.client).collect::<Vec<_>>());
+        assert_eq!((ONE as TxId, Some(Amount::from(ONE))), (swept[0].last_tx, swept[0].fee));
+        assert_eq!(None, swept[0].fee_error);
+        let account = bank.account(ONE as ClientId).unwrap();
+        assert_eq!((AccountStatus::Dormant, Amount::from(FIVE - ONE)), (account.status(), account.available));
+        let withdrawal = Transaction::make(TransactionType::Withdrawal, ONE as ClientId, FIVE as TxId, ONE, false);
+        assert_eq!(Err(BankingError::AccountDormant), bank.process_transaction(withdrawal.clone()));
+        assert!(bank.sweep_dormant().is_empty());
+        bank.reactivate_account(ONE as ClientId, None)?;
+        assert_eq!(Err(BankingError::AccountNotDormant), bank.reactivate_account(ONE as ClientId, None));
+        bank.process_transaction(withdrawal)?;
+        assert_eq!(AccountStatus::Active, bank.account(ONE as ClientId).unwrap().status());
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "time")]
+    fn unspent_promotional_credit_is_clawed_back_once_expired() -> Result<(), BankingError> {
+        // SETUP
+        let day = |day: u32| Some(Utc.with_ymd_and_hms(2021, 6, day, 0, 0, 0).unwrap());
+        let at = |kind, tx, amount, timestamp| Transaction {
+            timestamp,
+            ..Transaction::make(kind, ONE as ClientId, tx, amount, false)
+        };
+        let mut bank = Bank::new();
+        bank.process_transaction(at(TransactionType::Deposit, 1, 10, day(1)))?;
+        let promotion = Transaction {
+            expires: day(3),
+            ..at(TransactionType::PromoCredit, 2, FIVE, day(1))
+        };
+        bank.process_transaction(promotion)?;
+
+        // TEST
+        // the withdrawal spends the deposit first, and then two of the credit
+        bank.process_transaction(at(TransactionType::Withdrawal, 3, 12, day(2)))?;
+        assert_eq!(Amount::from(THREE), bank.promotional_balance(ONE as ClientId));
+        let dispute = Transaction::make_dispute(ONE as ClientId, TWO as TxId);
+        assert_eq!(Err(BankingError::PromotionalCredit), bank.process_transaction(dispute));
+        let reuse = at(TransactionType::Deposit, 2, ONE, day(2));
+        assert_eq!(Err(BankingError::DuplicateTransactionId), bank.process_transaction(reuse));
+        bank.process_transaction(at(TransactionType::Deposit, 4, ONE, day(4)))?;
+        let clawback = Clawback {
+            client: ONE as ClientId,
+            tx: TWO as TxId,
+            amount: Amount::from(THREE),
+        };
+        assert_eq!(&[clawback], bank.clawbacks());
+        assert_eq!(Amount::from(ONE), bank.account(ONE as ClientId).unwrap().available);
+        assert_eq!(Amount::zero(), bank.promotional_balance(ONE as ClientId));
 
-    const NEGATIVE_FIVE: i32 = -5;
-    const ZERO: u32 = 0;
-    const ONE: u32 = 1;
-    const TWO: u32 = 2;
-    const THREE: u32 = 3;
-    const _FOUR: u32 = 4;
-    const FIVE: u32 = 5;
+        // TEARDOWN
+        Ok(())
+    }
 
-    //region Transaction Test Implementation
-    // some utility functions to easily make create Transaction objects without cluttering test bodies
-    impl Transaction {
-        fn make(kind: TransactionType, client: u16, tx: u32, amount: u32, under_dispute: bool) -> Transaction {
-            Transaction {
-                kind,
-                client,
-                tx,
-                amount: Some(Decimal::from(amount)),
-                under_dispute,
-            }
-        }
+    #[test]
+    fn deposit_after_spending_promotional_credit_can_be_disputed() -> Result<(), BankingError> {
+        // SETUP
+        let make = |kind, tx, amount| Transaction::make(kind, ONE as ClientId, tx, amount, false);
+        let mut bank = Bank::builder().dispute_policy(DisputePolicy::RequireAvailableFunds).build();
+        bank.process_transaction(make(TransactionType::PromoCredit, ONE as TxId, 10))?;
+        bank.process_transaction(make(TransactionType::Withdrawal, TWO as TxId, 10))?;
+        bank.process_transaction(make(TransactionType::Deposit, THREE as TxId, 10))?;
 
-        fn make_negative(kind: TransactionType, client: u16, tx: u32, amount: i32) -> Transaction {
-            Transaction {
-                kind,
-                client,
-                tx,
-                amount: Some(Decimal::from(amount)),
-                under_dispute: false,
-            }
-        }
+        // TEST
+        assert_eq!(Amount::zero(), bank.promotional_balance(ONE as ClientId));
+        bank.process_transaction(Transaction::make_dispute(ONE as ClientId, THREE as TxId))?;
+        assert_eq!(Amount::from(10u32), bank.account(ONE as ClientId).unwrap().held);
 
-        fn make_dispute(client: u16, tx: u32) -> Transaction {
-            Transaction {
-                kind: TransactionType::Dispute,
-                client,
-                tx,
-                amount: None,
-                under_dispute: false,
-            }
-        }
+        // TEARDOWN
+        Ok(())
+    }
 
-        fn make_resolve(client: u16, tx: u32) -> Transaction {
-            Transaction {
-                kind: TransactionType::Resolve,
-                client,
-                tx,
-                amount: None,
-                under_dispute: false,
-            }
-        }
+    #[test]
+    fn promotional_credits_without_an_expiry_do_not_accumulate_once_spent() -> Result<(), BankingError> {
+        // SETUP
+        let make = |kind, tx, amount| Transaction::make(kind, ONE as ClientId, tx, amount, false);
+        let mut bank = Bank::new();
 
-        fn make_chargeback(client: u16, tx: u32) -> Transaction {
-            Transaction {
-                kind: TransactionType::Chargeback,
-                client,
-                tx,
-                amount: None,
-                under_dispute: false,
-            }
+        // TEST
+        for round in 0..100 {
+            let tx = 2 * round + 1;
+            bank.process_transaction(make(TransactionType::PromoCredit, tx, FIVE))?;
+            bank.process_transaction(make(TransactionType::Withdrawal, tx + 1, FIVE))?;
         }
+        assert_eq!(None, bank.promoted.get(&(ONE as ClientId)));
+        assert!(bank.promotions.values().all(|promotion| promotion.amount.is_zero()));
+        bank.process_transaction(make(TransactionType::PromoCredit, 201, FIVE))?;
+        assert_eq!(Amount::from(FIVE), bank.promotional_balance(ONE as ClientId));
+
+        // TEARDOWN
+        Ok(())
     }
-    //endregion
 
     #[test]
-    fn deposit_valid_transaction_returns_ok_and_adds_to_account() -> Result<(), BankingError> {
+    #[cfg(feature = "time")]
+    fn promotional_credits_are_spent_soonest_to_expire_first() -> Result<(), BankingError> {
         // SETUP
-        let expected = Decimal::from(FIVE);
+        let make = |kind, tx, amount| Transaction::make(kind, ONE as ClientId, tx, amount, false);
+        let day = |day: u32| Some(Utc.with_ymd_and_hms(2021, 6, day, 0, 0, 0).unwrap());
         let mut bank = Bank::new();
-        let tx1 = Transaction::make(TransactionType::Deposit, ONE as u16, ONE, FIVE, false);
+        let expiring = Transaction {
+            expires: day(3),
+            ..make(TransactionType::PromoCredit, ONE as TxId, FIVE)
+        };
+        bank.process_transaction(make(TransactionType::PromoCredit, TWO as TxId, FIVE))?;
+        bank.process_transaction(expiring)?;
 
         // TEST
-        bank.process_transaction(tx1)?;
-        let actual = bank.accounts.get(&(ONE as u16)).unwrap().available;
-        assert_eq!(expected, actual);
+        bank.process_transaction(make(TransactionType::Withdrawal, THREE as TxId, THREE))?;
+        let clawbacks = bank.expire_promotions(day(3).unwrap());
+        assert_eq!(Amount::from(TWO), clawbacks[0].amount);
+        assert_eq!(Amount::from(FIVE), bank.promotional_balance(ONE as ClientId));
+        assert_eq!(Amount::from(FIVE), bank.account(ONE as ClientId).unwrap().total);
 
         // TEARDOWN
         Ok(())
     }
 
     #[test]
-    fn deposit_negative_number_returns_invalid_transaction() -> Result<(), BankingError> {
+    fn held_funds_are_not_spent_from_promotional_credits() -> Result<(), BankingError> {
         // SETUP
-        let expected = BankingError::InvalidTransaction;
+        let make = |kind, tx, amount| Transaction::make(kind, ONE as ClientId, tx, amount, false);
         let mut bank = Bank::new();
-        let tx1 = Transaction::make_negative(TransactionType::Deposit, ONE as u16, ONE, NEGATIVE_FIVE);
+        bank.process_transaction(make(TransactionType::Deposit, ONE as TxId, FIVE))?;
+        bank.process_transaction(make(TransactionType::PromoCredit, TWO as TxId, FIVE))?;
+        bank.process_transaction(make(TransactionType::Withdrawal, THREE as TxId, 7))?;
 
         // TEST
-        let actual = bank.process_transaction(tx1);
-        assert!(actual.is_err());
-        assert_eq!(expected, actual.unwrap_err());
+        bank.process_transaction(Transaction::make_dispute(ONE as ClientId, ONE as TxId))?;
+        bank.process_transaction(Transaction::make_resolve(ONE as ClientId, ONE as TxId))?;
+        assert_eq!(Amount::from(THREE), bank.promotional_balance(ONE as ClientId));
 
         // TEARDOWN
         Ok(())
     }
 
     #[test]
-    fn withdrawal_with_insufficient_funds_returns_insufficient_funds() -> Result<(), BankingError> {
+    fn open_disputes_are_indexed_by_client() -> Result<(), BankingError> {
         // SETUP
-        let expected = BankingError::InsufficientFunds;
-        let mut bank = Bank::new();
-        let tx1 = Transaction::make(TransactionType::Deposit, ONE as u16, ONE, ONE, false);
-        let tx2 = Transaction::make(TransactionType::Withdrawal, ONE as u16, TWO, TWO, false);
+        let scenario = Scenario::new()
+            .deposit(ONE as ClientId, FIVE)
+            .deposit(ONE as ClientId, FIVE)
+            .deposit(TWO as ClientId, FIVE)
+            .deposit(ONE as ClientId, FIVE)
+            .dispute(ONE as ClientId, 4)
+            .dispute(ONE as ClientId, 1)
+            .dispute(TWO as ClientId, 3)
+            .dispute(ONE as ClientId, 2)
+            .resolve(ONE as ClientId, 1)
+            .chargeback(TWO as ClientId, 3);
 
         // TEST
-        bank.process_transaction(tx1)?;
-        let actual = bank.process_transaction(tx2);
-        assert!(actual.is_err());
-        assert_eq!(expected, actual.unwrap_err());
+        let bank = scenario.run();
+        assert_eq!(vec![2, 4], bank.open_disputes(ONE as ClientId).collect::<Vec<TxId>>());
+        assert_eq!(0, bank.open_disputes(TWO as ClientId).count());
+        assert_eq!(0, bank.open_disputes(THREE as ClientId).count());
 
         // TEARDOWN
         Ok(())
     }
 
     #[test]
-    fn withdrawal_from_inexistent_account_returns_no_such_account() -> Result<(), BankingError> {
+    fn forget_removes_client_but_preserves_totals() -> Result<(), BankingError> {
         // SETUP
-        let expected = BankingError::NoSuchAccount;
         let mut bank = Bank::new();
-        let tx1 = Transaction::make(TransactionType::Withdrawal, ONE as u16, TWO, TWO, false);
+        bank.process_transaction(Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, FIVE, false))?;
+        bank.process_transaction(Transaction::make(TransactionType::Withdrawal, ONE as ClientId, TWO as TxId, ONE, false))?;
+        bank.process_transaction(Transaction::make(TransactionType::Deposit, TWO as ClientId, THREE as TxId, FIVE, false))?;
+        bank.process_transaction(Transaction::make_dispute(TWO as ClientId, THREE as TxId))?;
+        let total = bank.total();
 
         // TEST
-        let actual = bank.process_transaction(tx1);
-        assert!(actual.is_err());
-        assert_eq!(expected, actual.unwrap_err());
+        let erasure = bank.forget(ONE as ClientId)?;
+        assert_eq!(
+            Erasure {
+                client: ONE as ClientId,
+                transactions: 2,
+                balance: Amount::from(FIVE - ONE),
+            },
+            erasure
+        );
+        assert_eq!(None, bank.account(ONE as ClientId));
+        assert_eq!(None, bank.transaction(ONE as TxId));
+        assert_eq!(total, bank.total());
+        let adjustment = ErasureAdjustment {
+            client: ONE as ClientId,
+            amount: Amount::from(FIVE - ONE),
+        };
+        assert_eq!(&[adjustment], bank.erasure_adjustments());
+        let replayed = bank.process_transaction(Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, FIVE, false));
+        assert_eq!(Err(BankingError::DuplicateTransactionId), replayed);
+        assert_eq!(Err(BankingError::OpenDispute), bank.forget(TWO as ClientId));
+        assert_eq!(Err(BankingError::NoSuchAccount), bank.forget(ONE as ClientId));
 
         // TEARDOWN
         Ok(())
     }
 
     #[test]
-    fn withdrawal_negative_number_returns_invalid_transaction() -> Result<(), BankingError> {
+    fn forget_keeps_an_audit_entry_of_the_erasure() -> Result<(), BankingError> {
         // SETUP
-        let expected = BankingError::InvalidTransaction;
-        let mut bank = Bank::new();
-        let tx1 = Transaction::make_negative(TransactionType::Withdrawal, ONE as u16, ONE, NEGATIVE_FIVE);
+        let mut bank = Bank::builder().audit(true).build();
+        bank.process_transaction(Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, FIVE, false))?;
+        bank.process_transaction(Transaction::make(TransactionType::Deposit, ONE as ClientId, TWO as TxId, ONE, false))?;
+        bank.process_transaction(Transaction::make(TransactionType::Deposit, TWO as ClientId, THREE as TxId, TWO, false))?;
+        let (total, available, held) = (bank.total(), bank.total_available(), bank.total_held());
 
         // TEST
-        let actual = bank.process_transaction(tx1);
-        assert!(actual.is_err());
-        assert_eq!(expected, actual.unwrap_err());
+        bank.forget(ONE as ClientId)?;
+        let erased = AuditAction::Erased {
+            transactions: 2,
+            balance: Amount::from(FIVE + ONE),
+        };
+        let trail = bank.audit_trail(ONE as ClientId);
+        assert_eq!(vec![&erased], trail.iter().map(|entry| &entry.action).collect::<Vec<_>>());
+        assert_eq!((total, available, held), (bank.total(), bank.total_available(), bank.total_held()));
+        assert_eq!(Ok(Amount::from(FIVE + ONE)), bank.erased_balance());
 
         // TEARDOWN
         Ok(())
     }
 
     #[test]
-    fn withdrawal_works_with_sufficient_funds() -> Result<(), BankingError> {
+    fn client_with_a_prepared_transaction_can_not_be_forgotten() -> Result<(), BankingError> {
         // SETUP
-        let expected = Decimal::from(THREE);
+        let make = |kind, tx, amount| Transaction::make(kind, ONE as ClientId, tx, amount, false);
         let mut bank = Bank::new();
-        let tx1 = Transaction::make(TransactionType::Deposit, ONE as u16, ONE, FIVE, false);
-        let tx2 = Transaction::make(TransactionType::Withdrawal, ONE as u16, TWO, TWO, false);
+        bank.process_transaction(make(TransactionType::Deposit, ONE as TxId, FIVE))?;
+        let deposit = bank.prepare_transaction(make(TransactionType::Deposit, TWO as TxId, ONE))?;
+        let withdrawal = make(TransactionType::Withdrawal, THREE as TxId, ONE);
 
         // TEST
-        bank.process_transaction(tx1)?;
-        bank.process_transaction(tx2)?;
-        let actual = bank.accounts.get(&(ONE as u16)).unwrap().available;
-        assert_eq!(expected, actual);
+        assert_eq!(Err(BankingError::PendingTransaction), bank.forget(ONE as ClientId));
+        bank.commit(deposit)?;
+        let withdrawal = bank.prepare_transaction(withdrawal)?;
+        assert_eq!(Err(BankingError::PendingTransaction), bank.forget(ONE as ClientId));
+        bank.abort(withdrawal)?;
+        assert_eq!(Amount::from(FIVE + ONE), bank.forget(ONE as ClientId)?.balance);
+        assert_eq!(None, bank.account(ONE as ClientId));
 
         // TEARDOWN
         Ok(())
     }
 
     #[test]
-    fn transact_with_duplicate_transaction_id_returns_duplicate_transaction_id() -> Result<(), BankingError> {
+    fn client_with_an_open_reservation_can_not_be_forgotten() -> Result<(), BankingError> {
         // SETUP
-        let expected = BankingError::DuplicateTransactionId;
+        let make = |kind, tx, amount| Transaction::make(kind, ONE as ClientId, tx, amount, false);
         let mut bank = Bank::new();
-        let tx1 = Transaction::make(TransactionType::Deposit, ONE as u16, ONE, ONE, false);
-        let tx2 = Transaction::make(TransactionType::Deposit, ONE as u16, ONE, ONE, false);
-        let tx3 = Transaction::make(TransactionType::Withdrawal, ONE as u16, ONE, ONE, false);
+        bank.process_transaction(make(TransactionType::Deposit, ONE as TxId, FIVE))?;
+        let batch = bank.reserve_batch(ONE as ClientId, Amount::from(THREE))?;
 
         // TEST
-        bank.process_transaction(tx1)?;
-        let first_actual = bank.process_transaction(tx2);
-        let second_actual = bank.process_transaction(tx3);
-        assert!(first_actual.is_err());
-        assert_eq!(expected, first_actual.unwrap_err());
-        assert!(second_actual.is_err());
-        assert_eq!(expected, second_actual.unwrap_err());
+        assert_eq!(Err(BankingError::PendingTransaction), bank.forget(ONE as ClientId));
+        bank.release_batch(batch)?;
+        assert_eq!(Amount::from(FIVE), bank.forget(ONE as ClientId)?.balance);
 
         // TEARDOWN
         Ok(())
     }
 
     #[test]
-    fn dispute_transaction_with_invalid_id_returns_no_such_transaction() -> Result<(), BankingError> {
+    #[cfg(feature = "retention")]
+    fn retention_evicts_oldest_undisputed_transactions() -> Result<(), BankingError> {
         // SETUP
-        let expected = BankingError::NoSuchTransaction;
-        let mut bank = Bank::new();
-        let tx1 = Transaction::make_dispute(ONE as u16, ONE);
+        let mut bank = Bank::builder().retention(RetentionPolicy::Count(2)).build();
+        bank.process_transaction(Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, FIVE, false))?;
+        bank.process_transaction(Transaction::make_dispute(ONE as ClientId, ONE as TxId))?;
+        bank.process_transaction(Transaction::make(TransactionType::Deposit, ONE as ClientId, TWO as TxId, FIVE, false))?;
+        bank.process_transaction(Transaction::make(TransactionType::Deposit, ONE as ClientId, THREE as TxId, FIVE, false))?;
 
         // TEST
-        let actual = bank.process_transaction(tx1);
-        assert!(actual.is_err());
-        assert_eq!(expected, actual.unwrap_err());
+        assert!(bank.transaction(ONE as TxId).is_some(), "disputed transactions are never evicted");
+        assert_eq!(None, bank.transaction(TWO as TxId));
+        assert!(bank.transaction(THREE as TxId).is_some());
+        let dispute = bank.process_transaction(Transaction::make_dispute(ONE as ClientId, TWO as TxId));
+        assert_eq!(Err(BankingError::NoSuchTransaction), dispute);
+        let reused = bank.process_transaction(Transaction::make(TransactionType::Deposit, TWO as ClientId, TWO as TxId, ONE, false));
+        assert_eq!(Err(BankingError::DuplicateTransactionId), reused);
+        bank.process_transaction(Transaction::make_resolve(ONE as ClientId, ONE as TxId))?;
+        bank.process_transaction(Transaction::make(TransactionType::Deposit, ONE as ClientId, FIVE as TxId, FIVE, false))?;
+        assert!(
+            bank.transaction(ONE as TxId).is_some(),
+            "a settled dispute is requeued behind newer transactions"
+        );
+        bank.process_transaction(Transaction::make(TransactionType::Deposit, ONE as ClientId, (FIVE + ONE) as TxId, FIVE, false))?;
+        assert_eq!(None, bank.transaction(ONE as TxId));
 
         // TEARDOWN
         Ok(())
     }
 
     #[test]
-    fn dispute_valid_transaction() -> Result<(), BankingError> {
+    #[cfg(feature = "io")]
+    fn failed_writes_of_accounts_are_returned() {
+        // SETUP
+        let bank = Scenario::new().deposit(1, 5).run();
+        let failing = || FaultyWriter::new(Vec::new(), FaultPlan::new().fail_at(0, io::ErrorKind::BrokenPipe));
+
+        // TEST
+        assert!(bank.write_accounts(failing()).is_err());
+        assert!(bank.write_accounts_with_versions(failing()).is_err());
+        assert!(bank.write_accounts_extended(failing()).is_err());
+        assert!(bank.write_accounts(Vec::new()).is_ok());
+    }
+
+    #[test]
+    fn accounts_are_paged_and_exported_in_client_order() {
         // SETUP
-        let expected_transaction = Transaction::make(TransactionType::Deposit, ONE as u16, ONE, FIVE, true);
-        let expected_account = Account {
-            client: ONE as u16,
-            available: Decimal::from(ZERO),
-            total: Decimal::from(FIVE),
-            held: Decimal::from(FIVE),
-            locked: false,
-        };
         let mut bank = Bank::new();
-        let tx1 = Transaction::make(TransactionType::Deposit, ONE as u16, ONE, FIVE, false);
-        let tx2 = Transaction::make_dispute(ONE as u16, ONE);
+        bank.process_all(Scenario::new().deposit(4, 1).deposit(1, 1).deposit(5, 1).deposit(2, 1).deposit(3, 1).transactions().to_vec());
+        let clients = |accounts: &[&Account]| accounts.iter().map(|account| account.client).collect::<Vec<_>>();
 
         // TEST
-        bank.process_transaction(tx1)?;
-        bank.process_transaction(tx2)?;
+        let first = bank.accounts_page(None, 2);
+        assert_eq!((vec![1, 2], Some(2)), (clients(&first.accounts), first.next));
+        let second = bank.accounts_page(first.next, 2);
+        assert_eq!((vec![3, 4], Some(4)), (clients(&second.accounts), second.next));
+        let last = bank.accounts_page(second.next, 2);
+        assert_eq!((vec![5], None), (clients(&last.accounts), last.next));
+        let chunks: Vec<Vec<ClientId>> = bank.export_accounts_chunked(3).map(|chunk| clients(&chunk)).collect();
+        assert_eq!(vec![vec![1, 2, 3], vec![4, 5]], chunks);
+    }
+
+    #[test]
+    #[cfg(feature = "retention")]
+    fn memory_retention_evicts_and_then_rejects_beyond_the_limit() -> Result<(), BankingError> {
+        // SETUP
+        let deposit = |tx: u32| Transaction::make(TransactionType::Deposit, ONE as ClientId, tx as TxId, FIVE, false);
+        let mut sizing = Bank::builder().retention(RetentionPolicy::Memory(usize::MAX)).build();
+        sizing.process_all(vec![deposit(ONE), deposit(TWO)]);
+        let mut bank = Bank::builder().retention(RetentionPolicy::Memory(sizing.memory_usage())).build();
+
+        // TEST
+        bank.process_all(vec![deposit(ONE), deposit(TWO), deposit(THREE)]);
+        assert_eq!(None, bank.transaction(ONE as TxId));
+        assert!(bank.memory_usage() <= sizing.memory_usage());
+        bank.process_transaction(Transaction::make_dispute(ONE as ClientId, TWO as TxId))?;
+        bank.process_transaction(Transaction::make_dispute(ONE as ClientId, THREE as TxId))?;
+        // the account of a new client takes the bank over the limit, with nothing left to evict
+        bank.process_transaction(Transaction::make(TransactionType::Deposit, TWO as ClientId, FIVE as TxId, ONE, false))?;
+        assert_eq!(None, bank.transaction(FIVE as TxId));
+        let rejected = bank.process_transaction(deposit(FIVE + ONE));
+        assert_eq!(Err(BankingError::MemoryLimitExceeded), rejected);
+        bank.process_transaction(Transaction::make_resolve(ONE as ClientId, TWO as TxId))?;
+        bank.process_transaction(deposit(FIVE + ONE))?;
+        assert_eq!(None, bank.transaction(TWO as TxId));
+        assert!(bank.transaction(THREE as TxId).is_some(), "disputed transactions are never evicted");
 
-        assert_eq!(expected_transaction, *bank.transactions.get(&ONE).unwrap());
-        assert_eq!(expected_account, *bank.accounts.get(&(ONE as u16)).unwrap());
         // TEARDOWN
         Ok(())
     }
 
     #[test]
-    fn dispute_disputed_transaction_returns_already_in_dispute() -> Result<(), BankingError> {
+    #[cfg(all(feature = "retention", feature = "time"))]
+    fn age_retention_measures_against_input_timestamps() -> Result<(), BankingError> {
         // SETUP
-        let expected_result = BankingError::DuplicateDisputeRequest;
-        let expected_transaction = Transaction {
-            kind: TransactionType::Deposit,
-            client: ONE as u16,
-            tx: ONE,
-            amount: Some(Decimal::from(FIVE)),
-            under_dispute: true,
-        };
-        let expected_account = Account {
-            client: ONE as u16,
-            available: Decimal::from(ZERO),
-            total: Decimal::from(FIVE),
-            held: Decimal::from(FIVE),
-            locked: false,
+        let mut bank = Bank::builder().retention(RetentionPolicy::Age(chrono::Duration::days(30))).build();
+        let start = DateTime::parse_from_rfc3339("2021-01-01T00:00:00Z").unwrap().with_timezone(&Utc);
+        let at = |tx: u32, day: i64| {
+            let mut deposit = Transaction::make(TransactionType::Deposit, ONE as ClientId, tx as TxId, ONE, false);
+            deposit.timestamp = Some(start + chrono::Duration::days(day));
+            deposit
         };
-        let mut bank = Bank::new();
-        let tx1 = Transaction::make(TransactionType::Deposit, ONE as u16, ONE, FIVE, false);
-        let tx2 = Transaction::make_dispute(ONE as u16, ONE);
-        let tx3 = Transaction::make_dispute(ONE as u16, ONE);
 
         // TEST
-        bank.process_transaction(tx1)?;
-        bank.process_transaction(tx2)?;
-        let result = bank.process_transaction(tx3);
+        bank.process_transaction(at(ONE, 0))?;
+        bank.process_transaction(Transaction::make(TransactionType::Deposit, ONE as ClientId, TWO as TxId, ONE, false))?;
+        bank.process_transaction(at(THREE, 30))?;
+        assert!(bank.transaction(ONE as TxId).is_some());
+        bank.process_transaction(at(FIVE, 31))?;
+        assert_eq!(None, bank.transaction(ONE as TxId));
+        assert_eq!(None, bank.transaction(TWO as TxId));
+        assert!(bank.transaction(THREE as TxId).is_some());
 
-        assert_eq!(expected_transaction, *bank.transactions.get(&ONE).unwrap());
-        assert_eq!(expected_account, *bank.accounts.get(&(ONE as u16)).unwrap());
-        assert_eq!(expected_result, result.unwrap_err());
         // TEARDOWN
         Ok(())
     }
 
     #[test]
-    fn resolve_disputed_transaction_releases_held_funds() -> Result<(), BankingError> {
+    fn builder_rounds_to_configured_decimal_places() -> Result<(), BankingError> {
         // SETUP
-        let expected_transaction = Transaction::make(TransactionType::Deposit, ONE as u16, ONE, FIVE, false);
-        let expected_account = Account {
-            client: ONE as u16,
-            available: Decimal::from(FIVE),
-            total: Decimal::from(FIVE),
-            held: Decimal::from(ZERO),
-            locked: false,
-        };
-        let mut bank = Bank::new();
-        let tx1 = Transaction::make(TransactionType::Deposit, ONE as u16, ONE, FIVE, false);
-        let tx2 = Transaction::make_dispute(ONE as u16, ONE);
-        let tx3 = Transaction::make_resolve(ONE as u16, ONE);
+        let mut bank = Bank::builder().decimal_places(0).build();
+        let mut tx1 = Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, ONE, false);
+        tx1.amount = amount::from_decimal(Decimal::new(25, 1));
 
         // TEST
         bank.process_transaction(tx1)?;
-        bank.process_transaction(tx2)?;
-        bank.process_transaction(tx3)?;
-
-        assert_eq!(expected_account, *bank.accounts.get(&(ONE as u16)).unwrap());
-        assert_eq!(expected_transaction, *bank.transactions.get(&ONE).unwrap());
+        assert_eq!(Amount::from(TWO), bank.account(ONE as ClientId).unwrap().available);
 
         // TEARDOWN
         Ok(())
     }
 
     #[test]
-    fn chargeback_disputed_transaction_withdraws_available_funds_and_locks_account() -> Result<(), BankingError> {
+    fn strict_bank_rejects_amounts_needing_rounding() {
         // SETUP
-        let expected_transaction = Transaction::make(TransactionType::Deposit, ONE as u16, ONE, FIVE, false);
-        let expected_account = Account {
-            client: ONE as u16,
-            available: Decimal::from(ZERO),
-            total: Decimal::from(ZERO),
-            held: Decimal::from(ZERO),
-            locked: true,
-        };
-        let mut bank = Bank::new();
-        let tx1 = Transaction::make(TransactionType::Deposit, ONE as u16, ONE, FIVE, false);
-        let tx2 = Transaction::make_dispute(ONE as u16, ONE);
-        let tx3 = Transaction::make_chargeback(ONE as u16, ONE);
+        let mut bank = Bank::builder().decimal_places(1).strict(true).build();
+        let mut tx1 = Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, ONE, false);
+        tx1.amount = amount::from_decimal(Decimal::new(125, 2));
 
         // TEST
-        bank.process_transaction(tx1)?;
-        bank.process_transaction(tx2)?;
-        bank.process_transaction(tx3)?;
+        assert_eq!(Err(BankingError::InvalidTransaction), bank.process_transaction(tx1));
+        assert_eq!(None, bank.account(ONE as ClientId));
+
+        // TEARDOWN
+    }
 
-        assert_eq!(expected_account, *bank.accounts.get(&(ONE as u16)).unwrap());
-        assert_eq!(expected_transaction, *bank.transactions.get(&ONE).unwrap());
+    #[test]
+    fn ignore_replays_accepts_identical_duplicates_only() -> Result<(), BankingError> {
+        // SETUP
+        let mut bank = Bank::builder().duplicate_policy(DuplicatePolicy::IgnoreReplays).build();
+        let tx1 = Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, FIVE, false);
+
+        // TEST
+        bank.process_transaction(tx1.clone())?;
+        bank.process_transaction(tx1)?;
+        let result = bank.process_transaction(Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, TWO, false));
+        assert_eq!(Err(BankingError::DuplicateTransactionId), result);
+        assert_eq!(Amount::from(FIVE), bank.account(ONE as ClientId).unwrap().available);
 
         // TEARDOWN
         Ok(())
     }
 
     #[test]
-    fn dispute_transaction_after_withdrawal_allows_negative_total() -> Result<(), BankingError> {
+    fn skip_and_last_write_wins_policies_handle_reused_ids() -> Result<(), BankingError> {
         // SETUP
-        let expected_transaction = Transaction {
-            kind: TransactionType::Deposit,
-            client: ONE as u16,
-            tx: ONE,
-            amount: Some(Decimal::from(FIVE)),
-            under_dispute: true,
+        let deposit = |amount: u32| Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, amount, false);
+        let correction = |amount: u32| Transaction {
+            correction: true,
+            ..deposit(amount)
         };
-        let expected_account = Account {
-            client: ONE as u16,
-            available: Decimal::from(NEGATIVE_FIVE),
-            total: Decimal::from(ZERO),
-            held: Decimal::from(FIVE),
-            locked: false,
+        let mut skipping = Bank::builder().duplicate_policy(DuplicatePolicy::Skip).build();
+        let mut correcting = Bank::builder().duplicate_policy(DuplicatePolicy::LastWriteWins).build();
+
+        // TEST
+        let summary = skipping.process_all(vec![deposit(FIVE), deposit(TWO), correction(THREE)]);
+        assert_eq!((3, 2), (summary.applied, summary.skipped));
+        assert_eq!(Amount::from(FIVE), skipping.account(ONE as ClientId).unwrap().total);
+        correcting.process_transaction(deposit(FIVE))?;
+        assert_eq!(Err(BankingError::DuplicateTransactionId), correcting.process_transaction(deposit(TWO)));
+        correcting.process_transaction(correction(TWO))?;
+        assert_eq!(Amount::from(TWO), correcting.account(ONE as ClientId).unwrap().total);
+        assert_eq!(Some(Amount::from(TWO)), correcting.transaction(ONE as TxId).unwrap().amount);
+        let withdrawal = Transaction {
+            correction: true,
+            ..Transaction::make(TransactionType::Withdrawal, ONE as ClientId, ONE as TxId, ONE, false)
         };
+        assert_eq!(Err(BankingError::DuplicateTransactionId), correcting.process_transaction(withdrawal));
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn adjustment_replaces_the_amount_of_a_stored_transaction() -> Result<(), BankingError> {
+        // SETUP
         let mut bank = Bank::new();
-        let tx1 = Transaction::make(TransactionType::Deposit, ONE as u16, ONE, FIVE, false);
-        let tx2 = Transaction::make(TransactionType::Withdrawal, ONE as u16, TWO, FIVE, false);
-        let tx3 = Transaction::make_dispute(ONE as u16, ONE);
+        let adjustment = |tx: u32, amount: u32| Transaction::make(TransactionType::Adjustment, ONE as ClientId, tx as TxId, amount, false);
+        bank.process_transaction(Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, FIVE, false))?;
+        bank.process_transaction(Transaction::make(TransactionType::Withdrawal, ONE as ClientId, TWO as TxId, TWO, false))?;
 
         // TEST
-        bank.process_transaction(tx1)?;
-        bank.process_transaction(tx2)?;
-        bank.process_transaction(tx3)?;
-
-        assert_eq!(expected_account, *bank.accounts.get(&(ONE as u16)).unwrap());
-        assert_eq!(expected_transaction, *bank.transactions.get(&ONE).unwrap());
+        bank.process_transaction(adjustment(ONE, THREE))?;
+        assert_eq!(Amount::from(ONE), bank.account(ONE as ClientId).unwrap().total);
+        bank.process_transaction(adjustment(TWO, ONE))?;
+        assert_eq!(Amount::from(TWO), bank.account(ONE as ClientId).unwrap().total);
+        assert_eq!(Err(BankingError::InsufficientFunds), bank.process_transaction(adjustment(TWO, FIVE)));
+        let amendment = Amendment {
+            client: ONE as ClientId,
+            tx: ONE as TxId,
+            previous: Amount::from(FIVE),
+            amount: Amount::from(THREE),
+        };
+        assert_eq!(&[amendment], bank.amendments(ONE as TxId));
+        bank.process_transaction(Transaction::make_dispute(ONE as ClientId, ONE as TxId))?;
+        assert_eq!(Amount::from(THREE), bank.account(ONE as ClientId).unwrap().held);
+        assert_eq!(Err(BankingError::OpenDispute), bank.process_transaction(adjustment(ONE, FIVE)));
+        assert_eq!(Err(BankingError::NoSuchTransaction), bank.process_transaction(adjustment(THREE, ONE)));
 
         // TEARDOWN
         Ok(())
     }
 
     #[test]
-    fn chargeback_transaction_after_withdrawal_allows_negative_total() -> Result<(), BankingError> {
+    fn adjustment_by_more_than_an_amount_can_represent_is_an_overflow() -> Result<(), BankingError> {
         // SETUP
-        let expected_transaction = Transaction::make(TransactionType::Deposit, ONE as u16, ONE, FIVE, false);
-        let expected_account = Account {
-            client: ONE as u16,
-            available: Decimal::from(NEGATIVE_FIVE),
-            total: Decimal::from(NEGATIVE_FIVE),
-            held: Decimal::from(ZERO),
-            locked: true,
-        };
         let mut bank = Bank::new();
-        let tx1 = Transaction::make(TransactionType::Deposit, ONE as u16, ONE, FIVE, false);
-        let tx2 = Transaction::make(TransactionType::Withdrawal, ONE as u16, TWO, FIVE, false);
-        let tx3 = Transaction::make_dispute(ONE as u16, ONE);
-        let tx4 = Transaction::make_chargeback(ONE as u16, ONE);
+        bank.process_transaction(Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, FIVE, false))?;
+        let before = bank.account(ONE as ClientId).cloned();
 
         // TEST
-        bank.process_transaction(tx1)?;
-        bank.process_transaction(tx2)?;
-        bank.process_transaction(tx3)?;
-        bank.process_transaction(tx4)?;
-
-        assert_eq!(expected_account, *bank.accounts.get(&(ONE as u16)).unwrap());
-        assert_eq!(expected_transaction, *bank.transactions.get(&ONE).unwrap());
+        assert_eq!(Err(BankingError::AmountOverflow), bank.adjust(ONE as ClientId, ONE as TxId, Amount::MIN));
+        assert_eq!(before.as_ref(), bank.account(ONE as ClientId));
+        assert_eq!(Some(Amount::from(FIVE)), bank.transaction(ONE as TxId).and_then(|deposit| deposit.amount));
 
         // TEARDOWN
         Ok(())
     }
 
     #[test]
-    fn transaction_on_locked_account_returns_account_locked() -> Result<(), BankingError> {
+    fn totals_larger_than_an_amount_can_represent_are_an_overflow() -> Result<(), BankingError> {
         // SETUP
-        let expected_result = BankingError::AccountLocked;
-        let expected_transaction = Transaction::make(TransactionType::Deposit, ONE as u16, ONE, FIVE, false);
-        let expected_account = Account {
-            client: ONE as u16,
-            available: Decimal::from(NEGATIVE_FIVE),
-            total: Decimal::from(NEGATIVE_FIVE),
-            held: Decimal::from(ZERO),
-            locked: true,
-        };
         let mut bank = Bank::new();
-        let tx1 = Transaction::make(TransactionType::Deposit, ONE as u16, ONE, FIVE, false);
-        let tx2 = Transaction::make(TransactionType::Withdrawal, ONE as u16, TWO, FIVE, false);
-        let tx3 = Transaction::make_dispute(ONE as u16, ONE);
-        let tx4 = Transaction::make_chargeback(ONE as u16, ONE);
-        let tx5 = Transaction::make(TransactionType::Deposit, ONE as u16, THREE, FIVE, false);
+        for client in [ONE, TWO] {
+            let mut deposit = Transaction::make(TransactionType::Deposit, client as ClientId, client as TxId, ONE, false);
+            deposit.amount = Some(Amount::MAX);
+            bank.process_transaction(deposit)?;
+        }
 
         // TEST
-        bank.process_transaction(tx1)?;
-        bank.process_transaction(tx2)?;
-        bank.process_transaction(tx3)?;
-        bank.process_transaction(tx4)?;
-        let result = bank.process_transaction(tx5);
+        assert_eq!(Err(BankingError::AmountOverflow), bank.total());
+        assert_eq!(Err(BankingError::AmountOverflow), bank.total_available());
+        assert_eq!(Ok(Amount::zero()), bank.total_held());
 
-        assert_eq!(expected_result, result.unwrap_err());
-        assert_eq!(expected_account, *bank.accounts.get(&(ONE as u16)).unwrap());
-        assert_eq!(expected_transaction, *bank.transactions.get(&ONE).unwrap());
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn require_available_funds_rejects_dispute_after_withdrawal() -> Result<(), BankingError> {
+        // SETUP
+        let mut bank = Bank::builder().dispute_policy(DisputePolicy::RequireAvailableFunds).build();
+        bank.process_transaction(Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, FIVE, false))?;
+        bank.process_transaction(Transaction::make(TransactionType::Withdrawal, ONE as ClientId, TWO as TxId, THREE, false))?;
+
+        // TEST
+        let result = bank.process_transaction(Transaction::make_dispute(ONE as ClientId, ONE as TxId));
+        assert_eq!(Err(BankingError::InsufficientFunds), result);
+        assert!(!bank.transaction(ONE as TxId).unwrap().under_dispute);
+        assert_eq!(Amount::from(TWO), bank.account(ONE as ClientId).unwrap().available);
 
         // TEARDOWN
         Ok(())
     }
 
     #[test]
-    fn dispute_client_with_wrong_client_returns_client_mismatch() -> Result<(), BankingError> {
+    #[cfg(feature = "io")]
+    fn statistics_count_applied_transactions_per_client() -> Result<(), BankingError> {
         // SETUP
-        let expected_result = BankingError::ClientMismatch;
-        let expected_transaction = Transaction::make(TransactionType::Deposit, ONE as u16, ONE, FIVE, false);
-        let expected_account = Account {
-            client: ONE as u16,
-            available: Decimal::from(FIVE),
-            total: Decimal::from(FIVE),
-            held: Decimal::from(ZERO),
-            locked: false,
+        let mut bank = Bank::builder().statistics(true).build();
+        bank.process_transaction(Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, FIVE, false))?;
+        bank.process_transaction(Transaction::make(TransactionType::Deposit, ONE as ClientId, TWO as TxId, ONE, false))?;
+        bank.process_transaction(Transaction::make(TransactionType::Withdrawal, ONE as ClientId, THREE as TxId, TWO, false))?;
+        bank.process_transaction(Transaction::make_dispute(ONE as ClientId, ONE as TxId))?;
+        bank.process_transaction(Transaction::make_dispute(ONE as ClientId, TWO as TxId))?;
+        bank.process_transaction(Transaction::make_chargeback(ONE as ClientId, TWO as TxId))?;
+        let _ = bank.process_transaction(Transaction::make(TransactionType::Withdrawal, ONE as ClientId, FIVE as TxId, ONE, false));
+
+        // TEST
+        let expected = ClientStatistics {
+            deposits: 2,
+            withdrawals: 1,
+            disputes: 2,
+            open_disputes: 1,
+            chargebacks: 1,
+            last_tx: Some(TWO as TxId),
         };
+        assert_eq!(Some(&expected), bank.statistics(ONE as ClientId));
+        let mut output = Vec::new();
+        bank.write_accounts_extended(&mut output).unwrap();
+        let output = String::from_utf8(output).expect("UTF-8");
+        assert!(output.starts_with("client,available,held,total,locked,status,deposits,withdrawals,open_disputes,chargebacks,last_tx\n"));
+        assert!(output.ends_with(",true,locked,2,1,1,1,2\n"));
+        let mut plain = Bank::new();
+        plain.process_transaction(Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, FIVE, false))?;
+        assert_eq!(None, plain.statistics(ONE as ClientId));
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn prepared_transactions_hold_funds_until_committed_or_aborted() -> Result<(), BankingError> {
+        // SETUP
         let mut bank = Bank::new();
-        let tx1 = Transaction::make(TransactionType::Deposit, ONE as u16, ONE, FIVE, false);
-        let tx2 = Transaction::make_dispute(TWO as u16, ONE);
+        bank.process_transaction(Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, FIVE, false))?;
 
         // TEST
-        bank.process_transaction(tx1)?;
-        let result = bank.process_transaction(tx2);
+        let withdrawal = bank.prepare_transaction(Transaction::make(TransactionType::Withdrawal, ONE as ClientId, TWO as TxId, THREE, false))?;
+        let account = bank.account(ONE as ClientId).unwrap();
+        assert_eq!((Amount::from(TWO), Amount::from(THREE)), (account.available, account.held));
+        let overdraw = bank.prepare_transaction(Transaction::make(TransactionType::Withdrawal, ONE as ClientId, THREE as TxId, THREE, false));
+        assert_eq!(Err(BankingError::InsufficientFunds), overdraw);
+        let reused = bank.process_transaction(Transaction::make(TransactionType::Deposit, TWO as ClientId, TWO as TxId, ONE, false));
+        assert_eq!(Err(BankingError::DuplicateTransactionId), reused);
+        bank.commit(withdrawal)?;
+        assert_eq!(Amount::from(TWO), bank.account(ONE as ClientId).unwrap().total);
+        assert_eq!(Err(BankingError::NoSuchTransaction), bank.commit(withdrawal));
+        let deposit = bank.prepare_transaction(Transaction::make(TransactionType::Deposit, TWO as ClientId, FIVE as TxId, ONE, false))?;
+        bank.abort(deposit)?;
+        assert_eq!(None, bank.account(TWO as ClientId));
+        let dispute = bank.prepare_transaction(Transaction::make_dispute(ONE as ClientId, ONE as TxId));
+        assert_eq!(Err(BankingError::InvalidTransaction), dispute);
 
-        assert_eq!(expected_transaction, *bank.transactions.get(&ONE).unwrap());
-        assert_eq!(expected_account, *bank.accounts.get(&(ONE as u16)).unwrap());
-        assert_eq!(expected_result, result.unwrap_err());
         // TEARDOWN
         Ok(())
     }
 
     #[test]
-    fn resolve_transaction_not_under_dispute_returns_undisputed_transaction() -> Result<(), BankingError> {
+    fn audit_trail_attributes_operations_to_the_operator() -> Result<(), BankingError> {
         // SETUP
-        let expected_result = BankingError::UndisputedTransaction;
-        let expected_transaction = Transaction::make(TransactionType::Deposit, ONE as u16, ONE, FIVE, false);
-        let expected_account = Account {
-            client: ONE as u16,
-            available: Decimal::from(FIVE),
-            total: Decimal::from(FIVE),
-            held: Decimal::from(ZERO),
-            locked: false,
+        let mut bank = Bank::builder().audit(true).build();
+        bank.set_operator(Some(String::from("input.csv")));
+        bank.process_transaction(Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, FIVE, false))?;
+        bank.process_transaction(Transaction::make_dispute(ONE as ClientId, ONE as TxId))?;
+        bank.process_transaction(Transaction::make_chargeback(ONE as ClientId, ONE as TxId))?;
+        bank.set_operator(Some(String::from("alice")));
+
+        // TEST
+        let _ = bank.process_transaction(Transaction::make(TransactionType::Withdrawal, ONE as ClientId, TWO as TxId, ONE, false));
+        bank.unlock_account(ONE as ClientId, None)?;
+        let again = bank.unlock_account(ONE as ClientId, None);
+        let _ = bank.unlock_account(TWO as ClientId, None);
+        let trail = bank.audit_trail(ONE as ClientId);
+        assert_eq!(6, trail.len());
+        let deposit = AuditAction::Applied {
+            kind: TransactionType::Deposit,
+            tx: ONE as TxId,
+        };
+        assert_eq!((&deposit, Some("input.csv")), (&trail[0].action, trail[0].operator.as_deref()));
+        let withdrawal = AuditAction::Rejected {
+            kind: TransactionType::Withdrawal,
+            tx: TWO as TxId,
+            reason: BankingError::AccountLocked,
+        };
+        assert_eq!((&withdrawal, Some("alice")), (&trail[3].action, trail[3].operator.as_deref()));
+        assert_eq!((&AuditAction::Unlocked, Some("alice")), (&trail[4].action, trail[4].operator.as_deref()));
+        assert_eq!(Err(BankingError::AccountNotLocked), again);
+        let denied = AuditAction::Denied {
+            command: AdminCommand::Unlock,
+            reason: BankingError::AccountNotLocked,
+        };
+        assert_eq!(&denied, &trail[5].action);
+        assert!(bank.audit_trail(TWO as ClientId).is_empty());
+        assert!(Bank::new().audit_trail(ONE as ClientId).is_empty());
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn admin_commands_apply_to_matching_accounts_and_are_audited() -> Result<(), BankingError> {
+        // SETUP
+        let mut bank = Bank::builder().audit(true).build();
+        bank.process_transaction(Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, FIVE, false))?;
+        bank.process_transaction(Transaction::make(TransactionType::Deposit, TWO as ClientId, TWO as TxId, ONE, false))?;
+        bank.process_transaction(Transaction::make(TransactionType::Deposit, THREE as ClientId, THREE as TxId, ONE, false))?;
+        bank.lock_account(THREE as ClientId, None)?;
+        bank.set_operator(Some(String::from("alice")));
+
+        // TEST
+        let small = |account: &Account| account.total < Amount::from(FIVE);
+        let locked = bank.for_each_account_mut(small, |account| AdminCommand::Lock.apply(account));
+        assert_eq!(vec![(TWO as ClientId, AuditAction::Locked)], locked);
+        assert!(bank.account(TWO as ClientId).unwrap().locked);
+        let note = AdminCommand::Annotate(String::from("under review"));
+        assert_eq!(3, bank.for_each_account_mut(|_| true, |account| note.apply(account)).len());
+        let trail = bank.audit_trail(TWO as ClientId);
+        assert_eq!(&AuditAction::Locked, &trail[1].action);
+        assert_eq!(Some("alice"), trail[1].operator.as_deref());
+        let annotated = AuditAction::Annotated {
+            note: String::from("under review"),
         };
+        assert_eq!(&annotated, &trail[2].action);
+        assert_eq!(Ok(AdminCommand::Freeze), "freeze".parse());
+        assert!("annotate:".parse::<AdminCommand>().is_err());
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn annotations_are_kept_written_and_loaded() -> Result<(), BankingError> {
+        // SETUP
         let mut bank = Bank::new();
-        let tx1 = Transaction::make(TransactionType::Deposit, ONE as u16, ONE, FIVE, false);
-        let tx2 = Transaction::make_resolve(ONE as u16, ONE);
+        bank.process_transaction(Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, FIVE, false))?;
+        bank.process_transaction(Transaction::make(TransactionType::Deposit, TWO as ClientId, TWO as TxId, ONE, false))?;
 
         // TEST
-        bank.process_transaction(tx1)?;
-        let result = bank.process_transaction(tx2);
+        assert_eq!(Err(BankingError::NoSuchAccount), bank.annotate_account(THREE as ClientId, "unknown"));
+        bank.annotate_account(ONE as ClientId, "chargeback under review")?;
+        bank.label_account(ONE as ClientId, "ticket", Some("SUP-1"))?;
+        let label: AdminCommand = "label:ticket=SUP-2".parse().expect("valid command");
+        assert_eq!(2, bank.for_each_account_mut(|_| true, |account| label.apply(account)).len());
+        assert_eq!("ticket=SUP-2; chargeback under review", bank.annotations(ONE as ClientId).unwrap().summary());
+        let mut output = Vec::new();
+        bank.write_accounts_extended_with_notes(&mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.lines().next().unwrap().ends_with(",last_tx,notes"), "{}", output);
+        assert!(output.contains(",ticket=SUP-2\n"), "{}", output);
+
+        let mut saved = Vec::new();
+        bank.write_annotations(&mut saved).unwrap();
+        let mut restored = Bank::new();
+        assert_eq!(2, restored.read_annotations(&saved[..]).unwrap());
+        assert_eq!(bank.annotations(ONE as ClientId), restored.annotations(ONE as ClientId));
+        bank.label_account(TWO as ClientId, "ticket", None)?;
+        assert_eq!(None, bank.annotations(TWO as ClientId));
 
-        assert_eq!(expected_transaction, *bank.transactions.get(&ONE).unwrap());
-        assert_eq!(expected_account, *bank.accounts.get(&(ONE as u16)).unwrap());
-        assert_eq!(expected_result, result.unwrap_err());
         // TEARDOWN
         Ok(())
     }
 
     #[test]
-    fn dispute_withdrawal_returns_invalid_transaction() -> Result<(), BankingError> {
+    fn chargeback_assesses_the_configured_fee() -> Result<(), BankingError> {
         // SETUP
-        let expected_result = BankingError::InvalidTransaction;
-        let expected_account = Account {
-            client: ONE as u16,
-            available: Decimal::from(ZERO),
-            total: Decimal::from(ZERO),
-            held: Decimal::from(ZERO),
-            locked: false,
+        let fee = Amount::from(ONE);
+        let mut bank = Bank::builder().chargeback_fee(fee).build();
+        bank.process_transaction(Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, FIVE, false))?;
+        bank.process_transaction(Transaction::make(TransactionType::Deposit, ONE as ClientId, TWO as TxId, THREE, false))?;
+        bank.process_transaction(Transaction::make_dispute(ONE as ClientId, TWO as TxId))?;
+
+        // TEST
+        bank.process_transaction(Transaction::make_chargeback(ONE as ClientId, TWO as TxId))?;
+        let account = bank.account(ONE as ClientId).unwrap();
+        assert_eq!((Amount::from(FIVE - ONE), Amount::from(FIVE - ONE)), (account.available, account.total));
+        let expected = Fee {
+            client: ONE as ClientId,
+            tx: TWO as TxId,
+            amount: fee,
         };
+        assert_eq!(&[expected], bank.fees());
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn reserved_batch_withdrawals_draw_on_the_reservation() -> Result<(), BankingError> {
+        // SETUP
         let mut bank = Bank::new();
-        let tx1 = Transaction::make(TransactionType::Deposit, ONE as u16, ONE, FIVE, false);
-        let tx2 = Transaction::make(TransactionType::Withdrawal, ONE as u16, TWO, FIVE, false);
-        let tx3 = Transaction::make_dispute(ONE as u16, TWO);
+        bank.process_transaction(Transaction::make(TransactionType::Deposit, ONE as ClientId, ONE as TxId, FIVE, false))?;
+        let withdrawal = |tx: u32, amount: u32| Transaction::make(TransactionType::Withdrawal, ONE as ClientId, tx as TxId, amount, false);
 
         // TEST
-        bank.process_transaction(tx1)?;
-        bank.process_transaction(tx2)?;
-        let result = bank.process_transaction(tx3);
+        let batch = bank.reserve_batch(ONE as ClientId, Amount::from(THREE))?;
+        assert_eq!(Err(BankingError::InsufficientFunds), bank.process_transaction(withdrawal(TWO, THREE)));
+        bank.withdraw_reserved(batch, withdrawal(THREE, TWO))?;
+        assert_eq!(Err(BankingError::InsufficientFunds), bank.withdraw_reserved(batch, withdrawal(FIVE, TWO)));
+        assert_eq!(Amount::from(ONE), bank.reservation(batch).unwrap().remaining);
+        assert_eq!(Ok(Amount::from(ONE)), bank.release_batch(batch));
+        let account = bank.account(ONE as ClientId).unwrap();
+        assert_eq!((Amount::from(THREE), Amount::from(ZERO)), (account.available, account.held));
+        assert_eq!(Err(BankingError::NoSuchTransaction), bank.release_batch(batch));
+        let overdraw = bank.reserve_batch(ONE as ClientId, Amount::from(FIVE));
+        assert_eq!(Err(BankingError::InsufficientFunds), overdraw);
 
-        assert_eq!(expected_account, *bank.accounts.get(&(ONE as u16)).unwrap());
-        assert_eq!(expected_result, result.unwrap_err());
         // TEARDOWN
         Ok(())
     }
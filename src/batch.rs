@@ -0,0 +1,406 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Batch control records
+//! Bank file exchange wraps transactions in batches, each closed by a trailer with the number of
+//! records in the batch and the total of their amounts, so that a batch cut short or altered on
+//! the way is caught before it is posted. CSV input can carry the same control records:
+//!
+//! ```text
+//! type,client,tx,amount
+//! batch_start,,,
+//! deposit,1,1,10.0
+//! withdrawal,2,2,2.5
+//! batch_end,,2,12.5
+//! ```
+//!
+//! A `batch_end` carries the record count in the `tx` column and the total of the amounts in the
+//! `amount` column. `Batches` holds back the transactions of a batch until its trailer, and only
+//! passes them on if the trailer matches them. Otherwise the whole batch is rejected and none of it
+//! is applied; so is a batch without a trailer, or one cut short by the next `batch_start`. A record
+//! of a batch that can not be parsed is left out of it, so its batch no longer matches the trailer.
+//! Transactions outside of any batch are passed on as they come.
+use crate::amount::{parse_amount, Amount};
+use crate::provenance::Provenance;
+use crate::schema;
+use crate::transaction::{RecordOptions, Transaction};
+use rust_decimal::prelude::*;
+use std::collections::VecDeque;
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+
+/// `Record` is a row of input with control records: a transaction, or the start or end of a batch.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Record {
+    Transaction(Transaction),
+    BatchStart,
+    BatchEnd { count: u64, total: Amount },
+}
+
+/// `BatchError` describes why a batch was rejected.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BatchError {
+    /// The batch holds a different number of records than its trailer.
+    CountMismatch { expected: u64, actual: u64 },
+    /// The amounts of the batch add up to a different total than its trailer's.
+    TotalMismatch { expected: Amount, actual: Amount },
+    /// The input ended before the batch's trailer.
+    Unterminated,
+    /// Another batch started before the batch's trailer.
+    Interrupted,
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BatchError::CountMismatch { expected, actual } => {
+                write!(f, "trailer expects {} records, but the batch has {}", expected, actual)
+            }
+            BatchError::TotalMismatch { expected, actual } => {
+                write!(f, "trailer expects a total of {}, but the batch adds up to {}", expected, actual)
+            }
+            BatchError::Unterminated => write!(f, "the input ended before the batch_end"),
+            BatchError::Interrupted => write!(f, "another batch started before the batch_end"),
+        }
+    }
+}
+
+/// Adapt a CSV reader into an iterator of records, transactions and control records alike. Without
+/// a header row, columns are read in the order: type, client, tx, amount. Transactions are read with
+/// the `options` of their source. Records that can not be parsed are logged and skipped.
+pub fn read_records<'a, R: io::Read>(reader: &'a mut csv::Reader<R>, options: &'a RecordOptions) -> impl Iterator<Item = Record> + 'a {
+    read(reader, None, options)
+}
+
+/// As `read_records`, recording the line of `file` each transaction was read from as its
+/// `provenance`.
+pub fn read_records_from<'a, R: io::Read>(
+    reader: &'a mut csv::Reader<R>,
+    file: &str,
+    options: &'a RecordOptions,
+) -> impl Iterator<Item = Record> + 'a {
+    read(reader, Some(Arc::from(file)), options)
+}
+
+fn read<'a, R: io::Read>(
+    reader: &'a mut csv::Reader<R>,
+    file: Option<Arc<str>>,
+    options: &'a RecordOptions,
+) -> impl Iterator<Item = Record> + 'a {
+    let headers = match reader.has_headers() {
+        true => reader.headers().ok().cloned(),
+        false => None,
+    };
+    let column = |name: &str, default: usize| {
+        headers
+            .as_ref()
+            .and_then(|headers| headers.iter().position(|header| header.trim() == name))
+            .unwrap_or(default)
+    };
+    let (kind, tx, amount) = (column("type", 0), column("tx", 2), column("amount", 3));
+    reader.records().filter_map(move |result| {
+        let parsed = result.map_err(|e| e.to_string()).and_then(|record| {
+            let field = |index: usize| record.get(index).unwrap_or_default().trim();
+            match field(kind) {
+                "batch_start" => Ok(Record::BatchStart),
+                "batch_end" => Ok(Record::BatchEnd {
+                    count: field(tx)
+                        .parse()
+                        .map_err(|e| format!("invalid batch record count \"{}\": {}", field(tx), e))?,
+                    total: parse_amount(field(amount), &options.amount_format)
+                        .map_err(|e| format!("invalid batch total \"{}\": {}", field(amount), e))?,
+                }),
+                _ => schema::parse_record(&record, headers.as_ref(), options).map(|mut transaction| {
+                    if let Some(file) = &file {
+                        let line = record.position().map_or(0, csv::Position::line);
+                        transaction.provenance = Some(Provenance::Line { file: file.clone(), line });
+                    }
+                    Record::Transaction(transaction)
+                }),
+            }
+        });
+        match parsed {
+            Ok(record) => Some(record),
+            Err(e) => {
+                error!("Failed to parse transaction. Discarded with error: {}", e);
+                None
+            }
+        }
+    })
+}
+
+/// `Batches` passes on the transactions of the records it reads, holding back those of each batch
+/// until the batch's trailer has been checked.
+pub struct Batches<I> {
+    records: I,
+    /// The transactions of the batch being read, if any.
+    open: Option<Vec<Transaction>>,
+    /// The transactions of a checked batch, still to be passed on.
+    ready: VecDeque<Transaction>,
+    started: usize,
+    rejected: usize,
+}
+
+impl<I: Iterator<Item = Record>> Batches<I> {
+    pub fn new<R: IntoIterator<IntoIter = I>>(records: R) -> Batches<I> {
+        Batches {
+            records: records.into_iter(),
+            open: None,
+            ready: VecDeque::new(),
+            started: 0,
+            rejected: 0,
+        }
+    }
+
+    /// The number of batches started so far.
+    pub fn started(&self) -> usize {
+        self.started
+    }
+
+    /// The number of batches rejected so far.
+    pub fn rejected(&self) -> usize {
+        self.rejected
+    }
+
+    fn reject(&mut self, batch: Vec<Transaction>, reason: BatchError) {
+        error!(
+            "Rejected batch {} of {} records. Aborted with error: {}",
+            self.started,
+            batch.len(),
+            reason
+        );
+        self.rejected += 1;
+    }
+}
+
+impl<I: Iterator<Item = Record>> Iterator for Batches<I> {
+    type Item = Transaction;
+
+    fn next(&mut self) -> Option<Transaction> {
+        loop {
+            if let Some(transaction) = self.ready.pop_front() {
+                return Some(transaction);
+            }
+            match self.records.next() {
+                Some(Record::Transaction(transaction)) => match &mut self.open {
+                    Some(batch) => batch.push(transaction),
+                    None => return Some(transaction),
+                },
+                Some(Record::BatchStart) => {
+                    if let Some(batch) = self.open.replace(Vec::new()) {
+                        self.reject(batch, BatchError::Interrupted);
+                    }
+                    self.started += 1;
+                }
+                Some(Record::BatchEnd { count, total }) => match self.open.take() {
+                    Some(batch) => match check(&batch, count, total) {
+                        Ok(()) => self.ready.extend(batch),
+                        Err(reason) => self.reject(batch, reason),
+                    },
+                    None => error!("Discarded a batch_end outside of any batch"),
+                },
+                None => {
+                    if let Some(batch) = self.open.take() {
+                        self.reject(batch, BatchError::Unterminated);
+                    }
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+/// Check a batch against the count and total of its trailer.
+fn check(batch: &[Transaction], count: u64, total: Amount) -> Result<(), BatchError> {
+    let actual = batch.len() as u64;
+    if actual != count {
+        return Err(BatchError::CountMismatch { expected: count, actual });
+    }
+    let sum = batch
+        .iter()
+        .filter_map(|transaction| transaction.amount)
+        .fold(Amount::zero(), |sum, amount| sum + amount);
+    match sum == total {
+        true => Ok(()),
+        false => Err(BatchError::TotalMismatch {
+            expected: total,
+            actual: sum,
+        }),
+    }
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::TxId;
+
+    /// The records of CSV `input` with a header row.
+    fn records(input: &str) -> Vec<Record> {
+        let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(input.as_bytes());
+        read_records(&mut reader, &RecordOptions::default()).collect()
+    }
+
+    /// The transactions `batches` passes on, by transaction ID.
+    fn passed<I: Iterator<Item = Record>>(batches: &mut Batches<I>) -> Vec<TxId> {
+        batches.by_ref().map(|transaction| transaction.tx).collect()
+    }
+
+    #[test]
+    fn control_records_are_read_alongside_transactions() {
+        // TEST
+        let records = records("type,client,tx,amount\nbatch_start,,,\ndeposit,1,1,10.0\nbatch_end,,1,10.0\n");
+        assert_eq!(3, records.len());
+        assert_eq!(Record::BatchStart, records[0]);
+        assert!(matches!(&records[1], Record::Transaction(transaction) if transaction.tx == 1));
+        assert_eq!(
+            Record::BatchEnd {
+                count: 1,
+                total: Amount::from(10u32)
+            },
+            records[2]
+        );
+    }
+
+    #[test]
+    fn records_are_read_by_column_name() {
+        // TEST
+        let records = records("amount,tx,client,type\n,,,batch_start\n7,2,,batch_end\n");
+        assert_eq!(
+            vec![
+                Record::BatchStart,
+                Record::BatchEnd {
+                    count: 2,
+                    total: Amount::from(7u32)
+                }
+            ],
+            records
+        );
+    }
+
+    #[test]
+    fn trailers_that_can_not_be_parsed_are_skipped() {
+        // TEST
+        let records = records("type,client,tx,amount\nbatch_end,,x,1.0\nbatch_end,,1,x\nbatch_start,,,\n");
+        assert_eq!(vec![Record::BatchStart], records);
+    }
+
+    #[test]
+    fn transactions_outside_of_batches_are_passed_on() {
+        // SETUP
+        let mut batches = Batches::new(records("type,client,tx,amount\ndeposit,1,1,5.0\nwithdrawal,1,2,1.0\n"));
+
+        // TEST
+        assert_eq!(vec![1, 2], passed(&mut batches));
+        assert_eq!((0, 0), (batches.started(), batches.rejected()));
+    }
+
+    #[test]
+    fn batch_matching_its_trailer_is_passed_on() {
+        // SETUP
+        let mut batches = Batches::new(records(
+            "type,client,tx,amount\nbatch_start,,,\ndeposit,1,1,10.0\nwithdrawal,2,2,2.5\nbatch_end,,2,12.5\n",
+        ));
+
+        // TEST
+        assert_eq!(vec![1, 2], passed(&mut batches));
+        assert_eq!((1, 0), (batches.started(), batches.rejected()));
+    }
+
+    #[test]
+    fn batch_with_a_different_count_is_rejected() {
+        // SETUP
+        let mut batches = Batches::new(records(
+            "type,client,tx,amount\nbatch_start,,,\ndeposit,1,1,10.0\nbatch_end,,2,10.0\n",
+        ));
+
+        // TEST
+        assert!(passed(&mut batches).is_empty());
+        assert_eq!(1, batches.rejected());
+    }
+
+    #[test]
+    fn batch_with_a_different_total_is_rejected() {
+        // SETUP
+        let mut batches = Batches::new(records(
+            "type,client,tx,amount\nbatch_start,,,\ndeposit,1,1,1.0\nbatch_end,,1,2.0\n",
+        ));
+
+        // TEST
+        assert!(passed(&mut batches).is_empty());
+        assert_eq!(1, batches.rejected());
+    }
+
+    #[test]
+    fn batch_without_a_trailer_is_rejected() {
+        // SETUP
+        let mut batches = Batches::new(records("type,client,tx,amount\ndeposit,1,1,1.0\nbatch_start,,,\ndeposit,1,2,1.0\n"));
+
+        // TEST
+        assert_eq!(vec![1], passed(&mut batches));
+        assert_eq!((1, 1), (batches.started(), batches.rejected()));
+    }
+
+    #[test]
+    fn batch_cut_short_by_the_next_is_rejected() {
+        // SETUP
+        let mut batches = Batches::new(records(
+            "type,client,tx,amount\nbatch_start,,,\ndeposit,1,1,1.0\nbatch_start,,,\ndeposit,1,2,1.0\nbatch_end,,1,1.0\n",
+        ));
+
+        // TEST
+        assert_eq!(vec![2], passed(&mut batches));
+        assert_eq!((2, 1), (batches.started(), batches.rejected()));
+    }
+
+    #[test]
+    fn batch_with_a_record_that_can_not_be_parsed_is_rejected() {
+        // SETUP
+        let mut batches = Batches::new(records(
+            "type,client,tx,amount\nbatch_start,,,\ndeposit,1,1,1.0\ndeposit,x,2,1.0\nbatch_end,,2,2.0\n",
+        ));
+
+        // TEST
+        assert!(passed(&mut batches).is_empty());
+        assert_eq!(1, batches.rejected());
+    }
+
+    #[test]
+    fn trailer_outside_of_a_batch_is_discarded() {
+        // SETUP
+        let mut batches = Batches::new(records("type,client,tx,amount\nbatch_end,,1,1.0\ndeposit,1,1,1.0\n"));
+
+        // TEST
+        assert_eq!(vec![1], passed(&mut batches));
+        assert_eq!((0, 0), (batches.started(), batches.rejected()));
+    }
+
+    #[test]
+    fn check_reports_the_count_before_the_total() {
+        // TEST
+        assert_eq!(
+            Err(BatchError::CountMismatch { expected: 1, actual: 0 }),
+            check(&[], 1, Amount::from(1u32))
+        );
+        assert_eq!(
+            Err(BatchError::TotalMismatch {
+                expected: Amount::from(1u32),
+                actual: Amount::zero()
+            }),
+            check(&[], 0, Amount::from(1u32))
+        );
+    }
+
+    #[test]
+    fn transactions_record_the_line_they_were_read_from() {
+        // SETUP
+        let input = "type,client,tx,amount\nbatch_start,,,\ndeposit,1,1,10.0\nbatch_end,,1,10.0\nwithdrawal,1,2,1.0\n";
+        let mut reader = csv::Reader::from_reader(input.as_bytes());
+
+        // TEST
+        let sources: Vec<String> = Batches::new(read_records_from(&mut reader, "input.csv", &RecordOptions::default()))
+            .map(|transaction| transaction.provenance.map(|source| source.to_string()).unwrap_or_default())
+            .collect();
+        assert_eq!(vec!["input.csv:3", "input.csv:5"], sources);
+    }
+}
+//endregion
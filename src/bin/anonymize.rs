@@ -0,0 +1,144 @@
+use rust_payment_processor::anonymize::Pseudonymizer;
+use rust_payment_processor::signature::SigningKey;
+use std::process;
+use structopt::StructOpt;
+
+/// Copy a transaction or account CSV file, replacing every client ID with a pseudonym derived from
+/// a secret key, to produce a dataset that can be shared. Files anonymized with the same key map
+/// each client to the same pseudonym.
+#[derive(StructOpt, Debug)]
+struct Cli {
+    #[structopt(parse(from_os_str))]
+    input: std::path::PathBuf,
+    #[structopt(parse(from_os_str))]
+    output: std::path::PathBuf,
+    /// The file holding the pseudonymization key
+    #[structopt(long, parse(from_os_str), conflicts_with = "key-env")]
+    key_file: Option<std::path::PathBuf>,
+    /// The environment variable holding the pseudonymization key
+    #[structopt(long)]
+    key_env: Option<String>,
+}
+
+fn run(args: Cli) -> Result<usize, String> {
+    let key = match (&args.key_file, &args.key_env) {
+        (Some(path), _) => SigningKey::from_file(path).map_err(|e| e.to_string())?,
+        (None, Some(name)) => SigningKey::from_env(name)?,
+        (None, None) => return Err(String::from("one of --key-file or --key-env is required")),
+    };
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_path(&args.input)
+        .map_err(|e| e.to_string())?;
+    let mut writer = csv::Writer::from_path(&args.output).map_err(|e| e.to_string())?;
+    Pseudonymizer::new(key)
+        .anonymize_csv(&mut reader, &mut writer)
+        .map_err(|e| e.to_string())
+}
+
+fn main() {
+    match run(Cli::from_args()) {
+        Ok(copied) => eprintln!("anonymized {} records", copied),
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io;
+
+    #[test]
+    fn clients_are_replaced_with_their_pseudonyms() -> io::Result<()> {
+        // SETUP
+        let dir = std::env::temp_dir().join(format!("rust-payment-processor-anonymize-replaced-{}", process::id()));
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join("key"), "secret\n")?;
+        fs::write(dir.join("input.csv"), "type, client, tx, amount\ndeposit, 1, 1, 5.0\n")?;
+        let args = Cli::from_iter(vec![
+            String::from("anonymize"),
+            dir.join("input.csv").display().to_string(),
+            dir.join("output.csv").display().to_string(),
+            format!("--key-file={}", dir.join("key").display()),
+        ]);
+
+        // TEST
+        assert_eq!(Ok(1), run(args));
+        let pseudonym = Pseudonymizer::new(SigningKey::new(b"secret")).client(1);
+        let output = fs::read_to_string(dir.join("output.csv"))?;
+        assert_eq!(format!("type,client,tx,amount\ndeposit,{},1,5.0\n", pseudonym), output);
+
+        // TEARDOWN
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn key_is_read_from_the_environment() -> io::Result<()> {
+        // SETUP
+        let dir = std::env::temp_dir().join(format!("rust-payment-processor-anonymize-env-{}", process::id()));
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join("input.csv"), "client, available\n1, 5.0\n")?;
+        std::env::set_var("RUST_PAYMENT_PROCESSOR_ANONYMIZE_TEST_KEY", "secret");
+        let args = Cli::from_iter(vec![
+            String::from("anonymize"),
+            dir.join("input.csv").display().to_string(),
+            dir.join("output.csv").display().to_string(),
+            String::from("--key-env=RUST_PAYMENT_PROCESSOR_ANONYMIZE_TEST_KEY"),
+        ]);
+
+        // TEST
+        assert_eq!(Ok(1), run(args));
+        let pseudonym = Pseudonymizer::new(SigningKey::new(b"secret")).client(1);
+        let output = fs::read_to_string(dir.join("output.csv"))?;
+        assert_eq!(format!("client,available\n{},5.0\n", pseudonym), output);
+
+        // TEARDOWN
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn a_key_is_required() {
+        // SETUP
+        let args = Cli::from_iter(vec!["anonymize", "input.csv", "output.csv"]);
+
+        // TEST
+        assert_eq!(Err(String::from("one of --key-file or --key-env is required")), run(args));
+    }
+
+    #[test]
+    fn key_file_and_key_env_conflict() {
+        // TEST
+        let args = vec!["anonymize", "input.csv", "output.csv", "--key-file=key", "--key-env=KEY"];
+        assert!(Cli::from_iter_safe(args).is_err());
+    }
+
+    #[test]
+    fn file_without_a_client_column_is_rejected() -> io::Result<()> {
+        // SETUP
+        let dir = std::env::temp_dir().join(format!("rust-payment-processor-anonymize-column-{}", process::id()));
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join("key"), "secret")?;
+        fs::write(dir.join("input.csv"), "type, tx, amount\ndeposit, 1, 5.0\n")?;
+        let args = Cli::from_iter(vec![
+            String::from("anonymize"),
+            dir.join("input.csv").display().to_string(),
+            dir.join("output.csv").display().to_string(),
+            format!("--key-file={}", dir.join("key").display()),
+        ]);
+
+        // TEST
+        assert_eq!(Err(String::from("the file has no `client` column")), run(args));
+
+        // TEARDOWN
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
+//endregion
@@ -0,0 +1,232 @@
+use env_logger::Env;
+use log::{error, info};
+use rust_payment_processor::account::Account;
+use rust_payment_processor::bank::Bank;
+use rust_payment_processor::diff;
+use rust_payment_processor::errors::BankingError;
+use rust_payment_processor::journal::Follower;
+use rust_payment_processor::schema;
+use rust_payment_processor::transaction::{RecordOptions, Transaction};
+use std::fs::File;
+use std::io::{self, BufReader, Write};
+use std::process;
+use structopt::clap::ArgGroup;
+use structopt::StructOpt;
+
+/// Apply a file of corrections (adjustments, late disputes, resolves, and chargebacks) on top of
+/// the state of an earlier run, writing the updated accounts and a report of the change to each
+/// account for reconciliation. Every transaction of the earlier run is kept regardless of age, so
+/// corrections may refer to transactions of any age. Exits with status 1 if a correction was
+/// rejected.
+#[derive(StructOpt, Debug)]
+#[structopt(group = ArgGroup::with_name("state").required(true))]
+struct Cli {
+    /// The corrections, in the processor's CSV input format
+    #[structopt(parse(from_os_str))]
+    corrections: std::path::PathBuf,
+    /// Start from the state recorded by the journal written with `--journal`, so that corrections
+    /// may refer to any of its transactions
+    #[structopt(long, parse(from_os_str), group = "state")]
+    journal: Option<std::path::PathBuf>,
+    /// Start from the accounts written by an earlier run. Only balances are restored, so
+    /// corrections can not refer to its transactions
+    #[structopt(long, parse(from_os_str), group = "state")]
+    baseline: Option<std::path::PathBuf>,
+    /// Write the updated accounts to this file instead of stdout
+    #[structopt(long, parse(from_os_str))]
+    output: Option<std::path::PathBuf>,
+    /// Write the change to each account, as CSV, to this file
+    #[structopt(long, parse(from_os_str))]
+    deltas: std::path::PathBuf,
+}
+
+fn main() {
+    env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
+    let args = Cli::from_args();
+    match backfill(&args) {
+        Ok(0) => {}
+        Ok(rejected) => {
+            error!("{} corrections were rejected", rejected);
+            process::exit(1);
+        }
+        Err(e) => {
+            error!("{}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Apply the corrections, returning the number that were rejected.
+fn backfill(args: &Cli) -> Result<usize, String> {
+    let mut bank = load_state(args)?;
+    let before: Vec<Account> = bank.accounts().cloned().collect();
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_path(&args.corrections)
+        .map_err(|e| e.to_string())?;
+    let options = RecordOptions::default();
+    schema::check_input(&mut reader, None, &options)?;
+    let mut report_rejection = |index: usize, transaction: &Transaction, result: Result<(), BankingError>| {
+        if let Err(e) = result {
+            error!("Correction {} (tx {}) was rejected: {}", index + 1, transaction.tx, e);
+        }
+    };
+    let summary = bank.process_all_with(schema::read_transactions(&mut reader, &options), &mut report_rejection);
+
+    let deltas = diff::diff(&before, &bank);
+    let file = File::create(&args.deltas).map_err(|e| e.to_string())?;
+    diff::write_deltas(&deltas, file).map_err(|e| e.to_string())?;
+    info!("Changed {} accounts", deltas.len());
+    let output: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(File::create(path).map_err(|e| e.to_string())?),
+        None => Box::new(io::stdout()),
+    };
+    bank.write_accounts(output).map_err(|e| e.to_string())?;
+    Ok(summary.rejected)
+}
+
+/// Rebuild the state of the earlier run from its journal or accounts.
+fn load_state(args: &Cli) -> Result<Bank, String> {
+    if let Some(path) = &args.journal {
+        let file = File::open(path).map_err(|e| format!("Failed to read journal {}: {}", path.display(), e))?;
+        let mut follower = Follower::new(Bank::new());
+        let applied = follower.apply(BufReader::new(file)).map_err(|e| e.to_string())?;
+        info!("Replayed {} journal entries", applied);
+        return Ok(follower.into_bank());
+    }
+    let path = args.baseline.as_ref().expect("one of --journal or --baseline is required");
+    let file = File::open(path).map_err(|e| format!("Failed to read baseline {}: {}", path.display(), e))?;
+    let mut bank = Bank::new();
+    for account in diff::read_accounts(file).map_err(|e| e.to_string())? {
+        bank.restore_account(account);
+    }
+    Ok(bank)
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_payment_processor::journal::JournalWriter;
+    use std::fs;
+
+    #[test]
+    fn corrections_are_applied_on_top_of_the_baseline() -> io::Result<()> {
+        // SETUP
+        let dir = std::env::temp_dir().join(format!("rust-payment-processor-backfill-baseline-{}", process::id()));
+        fs::create_dir_all(&dir)?;
+        fs::write(
+            dir.join("accounts.csv"),
+            "client,available,held,total,locked\n1,5,0,5,false\n2,1,0,1,false\n",
+        )?;
+        fs::write(dir.join("corrections.csv"), "type, client, tx, amount\nwithdrawal, 1, 10, 2\n")?;
+        let args = Cli::from_iter(vec![
+            String::from("backfill"),
+            dir.join("corrections.csv").display().to_string(),
+            format!("--baseline={}", dir.join("accounts.csv").display()),
+            format!("--output={}", dir.join("output.csv").display()),
+            format!("--deltas={}", dir.join("deltas.csv").display()),
+        ]);
+
+        // TEST
+        assert_eq!(Ok(0), backfill(&args));
+        let deltas = fs::read_to_string(dir.join("deltas.csv"))?;
+        assert_eq!("client,available,held,total,locked\n1,-2,0,-2,false\n", deltas);
+        let output = fs::read_to_string(dir.join("output.csv"))?;
+        assert!(output.contains("\n1,3,0,3,false\n"), "{}", output);
+        assert!(output.contains("\n2,1,0,1,false\n"), "{}", output);
+
+        // TEARDOWN
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn corrections_may_refer_to_transactions_of_the_journal() -> io::Result<()> {
+        // SETUP
+        let dir = std::env::temp_dir().join(format!("rust-payment-processor-backfill-journal-{}", process::id()));
+        fs::create_dir_all(&dir)?;
+        let input = "type, client, tx, amount\ndeposit, 1, 1, 5\n";
+        let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(input.as_bytes());
+        let mut journal = JournalWriter::new(File::create(dir.join("journal"))?);
+        Bank::new().process_all_with(schema::read_transactions(&mut reader, &RecordOptions::default()), &mut journal);
+        fs::write(
+            dir.join("corrections.csv"),
+            "type, client, tx, amount\ndispute, 1, 1,\nchargeback, 1, 1,\n",
+        )?;
+        let args = Cli::from_iter(vec![
+            String::from("backfill"),
+            dir.join("corrections.csv").display().to_string(),
+            format!("--journal={}", dir.join("journal").display()),
+            format!("--output={}", dir.join("output.csv").display()),
+            format!("--deltas={}", dir.join("deltas.csv").display()),
+        ]);
+
+        // TEST
+        assert_eq!(Ok(0), backfill(&args));
+        let output = fs::read_to_string(dir.join("output.csv"))?;
+        assert_eq!("client,available,held,total,locked\n1,0,0,0,true\n", output);
+
+        // TEARDOWN
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn rejected_corrections_are_counted() -> io::Result<()> {
+        // SETUP
+        let dir = std::env::temp_dir().join(format!("rust-payment-processor-backfill-rejected-{}", process::id()));
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join("accounts.csv"), "client,available,held,total,locked\n1,5,0,5,false\n")?;
+        fs::write(
+            dir.join("corrections.csv"),
+            "type, client, tx, amount\nwithdrawal, 1, 10, 9\ndispute, 1, 1,\n",
+        )?;
+        let args = Cli::from_iter(vec![
+            String::from("backfill"),
+            dir.join("corrections.csv").display().to_string(),
+            format!("--baseline={}", dir.join("accounts.csv").display()),
+            format!("--output={}", dir.join("output.csv").display()),
+            format!("--deltas={}", dir.join("deltas.csv").display()),
+        ]);
+
+        // TEST
+        assert_eq!(Ok(2), backfill(&args));
+        assert_eq!("", fs::read_to_string(dir.join("deltas.csv"))?);
+
+        // TEARDOWN
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn missing_baseline_is_an_error() {
+        // SETUP
+        let dir = std::env::temp_dir().join(format!("rust-payment-processor-backfill-missing-{}", process::id()));
+        let args = Cli::from_iter(vec![
+            String::from("backfill"),
+            dir.join("corrections.csv").display().to_string(),
+            format!("--baseline={}", dir.join("accounts.csv").display()),
+            format!("--deltas={}", dir.join("deltas.csv").display()),
+        ]);
+
+        // TEST
+        let error = backfill(&args).expect_err("baseline does not exist");
+        assert!(error.starts_with("Failed to read baseline"), "{}", error);
+    }
+
+    #[test]
+    fn exactly_one_of_journal_and_baseline_is_required() {
+        // TEST
+        assert!(Cli::from_iter_safe(&["backfill", "corrections.csv", "--deltas=deltas.csv"]).is_err());
+        let both = [
+            "backfill",
+            "corrections.csv",
+            "--deltas=deltas.csv",
+            "--journal=journal",
+            "--baseline=accounts.csv",
+        ];
+        assert!(Cli::from_iter_safe(&both).is_err());
+    }
+}
+//endregion
@@ -0,0 +1,189 @@
+use rust_payment_processor::account::{Account, ClientId};
+use rust_payment_processor::bank::Bank;
+use rust_payment_processor::journal::AsOf;
+use rust_payment_processor::transaction::TxId;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::process;
+use structopt::clap::ArgGroup;
+use structopt::StructOpt;
+
+/// Reconstruct a client's balances at a point in the history of a journal written with
+/// `--journal`, printing the account as CSV. Exits with status 1 if the client had no account at
+/// that point.
+#[derive(StructOpt, Debug)]
+#[structopt(group = ArgGroup::with_name("point").required(true))]
+struct Cli {
+    #[structopt(parse(from_os_str))]
+    journal: std::path::PathBuf,
+    client: ClientId,
+    /// The balances just after the journal entry with this sequence number
+    #[structopt(long, group = "point")]
+    seq: Option<u64>,
+    /// The balances as of this time, in RFC 3339 format, measured on the timestamps of the
+    /// journaled transactions
+    #[structopt(long, group = "point")]
+    at: Option<chrono::DateTime<chrono::Utc>>,
+    /// The balances just before the transaction with this ID was applied
+    #[structopt(long, group = "point")]
+    before_tx: Option<TxId>,
+}
+
+/// The point in the history of the journal given on the command line.
+fn as_of(args: &Cli) -> AsOf {
+    match (args.seq, args.at, args.before_tx) {
+        (Some(seq), _, _) => AsOf::Seq(seq),
+        (_, Some(time), _) => AsOf::Timestamp(time),
+        (_, _, Some(tx)) => AsOf::BeforeTx(tx),
+        _ => unreachable!("one of --seq, --at, or --before-tx is required"),
+    }
+}
+
+/// The client's account at that point, or `None` if the client had none.
+fn balance_at(args: &Cli) -> Result<Option<Account>, String> {
+    let file = File::open(&args.journal).map_err(|e| e.to_string())?;
+    Bank::new()
+        .balance_at(BufReader::new(file), args.client, as_of(args))
+        .map_err(|e| e.to_string())
+}
+
+fn main() {
+    let args = Cli::from_args();
+    match balance_at(&args) {
+        Ok(Some(account)) => {
+            let mut wtr = csv::Writer::from_writer(io::stdout());
+            if let Err(e) = wtr.serialize(&account).and_then(|_| wtr.flush().map_err(csv::Error::from)) {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+        Ok(None) => {
+            eprintln!("client {} had no account at that point", args.client);
+            process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_payment_processor::amount::Amount;
+    use rust_payment_processor::journal::JournalWriter;
+    use rust_payment_processor::schema;
+    use rust_payment_processor::transaction::RecordOptions;
+    use std::fs;
+
+    const INPUT: &str = "type, client, tx, amount, timestamp
+deposit, 1, 1, 5, 2021-03-01T12:00:00Z
+deposit, 1, 2, 2, 2021-03-02T12:00:00Z
+withdrawal, 1, 3, 4, 2021-03-03T12:00:00Z
+deposit, 2, 4, 1, 2021-03-04T12:00:00Z
+";
+
+    /// Write the journal of `INPUT` to a file of the given name, returning its path.
+    fn write_journal(name: &str) -> io::Result<std::path::PathBuf> {
+        let path = std::env::temp_dir().join(format!("rust-payment-processor-balance-at-{}-{}", name, process::id()));
+        let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(INPUT.as_bytes());
+        let options = RecordOptions::default();
+        let mut journal = JournalWriter::new(fs::File::create(&path)?);
+        Bank::new().process_all_with(schema::read_transactions(&mut reader, &options), &mut journal);
+        Ok(path)
+    }
+
+    fn available(args: &[&str]) -> Result<Option<Amount>, String> {
+        balance_at(&Cli::from_iter(args)).map(|account| account.map(|account| account.available))
+    }
+
+    #[test]
+    fn balance_after_an_entry() -> io::Result<()> {
+        // SETUP
+        let path = write_journal("seq")?;
+        let journal = path.display().to_string();
+
+        // TEST
+        assert_eq!(
+            Ok(Some(Amount::from(7u32))),
+            available(&["balance-at", &journal, "1", "--seq", "2"])
+        );
+        assert_eq!(
+            Ok(Some(Amount::from(3u32))),
+            available(&["balance-at", &journal, "1", "--seq", "3"])
+        );
+
+        // TEARDOWN
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn balance_before_a_transaction() -> io::Result<()> {
+        // SETUP
+        let path = write_journal("before-tx")?;
+        let journal = path.display().to_string();
+
+        // TEST
+        assert_eq!(
+            Ok(Some(Amount::from(5u32))),
+            available(&["balance-at", &journal, "1", "--before-tx", "2"])
+        );
+
+        // TEARDOWN
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn balance_at_a_time() -> io::Result<()> {
+        // SETUP
+        let path = write_journal("at")?;
+        let journal = path.display().to_string();
+
+        // TEST
+        let at = available(&["balance-at", &journal, "1", "--at", "2021-03-02T18:00:00Z"]);
+        assert_eq!(Ok(Some(Amount::from(7u32))), at);
+
+        // TEARDOWN
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn client_without_an_account_at_that_point() -> io::Result<()> {
+        // SETUP
+        let path = write_journal("no-account")?;
+        let journal = path.display().to_string();
+
+        // TEST
+        assert_eq!(Ok(None), available(&["balance-at", &journal, "2", "--seq", "3"]));
+        assert_eq!(
+            Ok(Some(Amount::from(1u32))),
+            available(&["balance-at", &journal, "2", "--seq", "4"])
+        );
+
+        // TEARDOWN
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn missing_journal_is_an_error() {
+        // SETUP
+        let path = std::env::temp_dir().join(format!("rust-payment-processor-balance-at-missing-{}", process::id()));
+
+        // TEST
+        assert!(available(&["balance-at", &path.display().to_string(), "1", "--seq", "1"]).is_err());
+    }
+
+    #[test]
+    fn exactly_one_point_is_required() {
+        // TEST
+        assert!(Cli::from_iter_safe(&["balance-at", "journal.csv", "1"]).is_err());
+        assert!(Cli::from_iter_safe(&["balance-at", "journal.csv", "1", "--seq", "1", "--before-tx", "2"]).is_err());
+    }
+}
+//endregion
@@ -0,0 +1,174 @@
+use rust_payment_processor::encryption::{self, DecryptingReader, EncryptionKey};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::process;
+use structopt::StructOpt;
+
+/// Generate encryption keys, and encrypt or decrypt files in the format read and written by
+/// `--encryption-key-file`.
+#[derive(StructOpt, Debug)]
+enum Cli {
+    /// Print a new random key, hex-encoded
+    Keygen,
+    /// Encrypt a file
+    Encrypt(Files),
+    /// Decrypt a file
+    Decrypt(Files),
+}
+
+#[derive(StructOpt, Debug)]
+struct Files {
+    #[structopt(parse(from_os_str))]
+    input: std::path::PathBuf,
+    #[structopt(parse(from_os_str))]
+    output: std::path::PathBuf,
+    /// The file holding the hex-encoded key
+    #[structopt(long, parse(from_os_str), conflicts_with = "key-env")]
+    key_file: Option<std::path::PathBuf>,
+    /// The environment variable holding the hex-encoded key
+    #[structopt(long)]
+    key_env: Option<String>,
+}
+
+impl Files {
+    fn key(&self) -> Result<EncryptionKey, String> {
+        match (&self.key_file, &self.key_env) {
+            (Some(path), _) => EncryptionKey::from_file(path),
+            (None, Some(name)) => EncryptionKey::from_env(name),
+            (None, None) => Err(String::from("one of --key-file or --key-env is required")),
+        }
+    }
+}
+
+fn run(args: Cli) -> Result<(), String> {
+    match args {
+        Cli::Keygen => println!("{}", EncryptionKey::generate().to_hex()),
+        Cli::Encrypt(files) => {
+            let key = files.key()?;
+            let input = BufReader::new(File::open(&files.input).map_err(|e| e.to_string())?);
+            let output = BufWriter::new(File::create(&files.output).map_err(|e| e.to_string())?);
+            encryption::encrypt(&key, input, output).map_err(|e| e.to_string())?;
+        }
+        Cli::Decrypt(files) => {
+            let key = files.key()?;
+            let input = BufReader::new(File::open(&files.input).map_err(|e| e.to_string())?);
+            let mut input = DecryptingReader::new(&key, input).map_err(|e| e.to_string())?;
+            // decrypt in full before creating the output, so that a file that fails to decrypt
+            // leaves no partial plaintext behind
+            let mut plaintext = Vec::new();
+            io::copy(&mut input, &mut plaintext).map_err(|e| e.to_string())?;
+            std::fs::write(&files.output, plaintext).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = run(Cli::from_args()) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn files(dir: &std::path::Path, input: &str, output: &str) -> Files {
+        Files {
+            input: dir.join(input),
+            output: dir.join(output),
+            key_file: Some(dir.join("key")),
+            key_env: None,
+        }
+    }
+
+    #[test]
+    fn decrypting_an_encrypted_file_gives_it_back() -> io::Result<()> {
+        // SETUP
+        let dir = std::env::temp_dir().join(format!("rust-payment-processor-crypt-roundtrip-{}", process::id()));
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join("key"), EncryptionKey::generate().to_hex())?;
+        fs::write(dir.join("plain.csv"), "type, client, tx, amount\ndeposit, 1, 1, 5.0\n")?;
+
+        // TEST
+        assert_eq!(Ok(()), run(Cli::Encrypt(files(&dir, "plain.csv", "encrypted.csv"))));
+        assert_ne!(fs::read(dir.join("plain.csv"))?, fs::read(dir.join("encrypted.csv"))?);
+        assert_eq!(Ok(()), run(Cli::Decrypt(files(&dir, "encrypted.csv", "decrypted.csv"))));
+        assert_eq!(fs::read(dir.join("plain.csv"))?, fs::read(dir.join("decrypted.csv"))?);
+
+        // TEARDOWN
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn decrypting_with_another_key_leaves_no_output() -> io::Result<()> {
+        // SETUP
+        let dir = std::env::temp_dir().join(format!("rust-payment-processor-crypt-wrong-key-{}", process::id()));
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join("key"), EncryptionKey::generate().to_hex())?;
+        fs::write(dir.join("plain.csv"), "type, client, tx, amount\ndeposit, 1, 1, 5.0\n")?;
+        run(Cli::Encrypt(files(&dir, "plain.csv", "encrypted.csv"))).expect("encrypted");
+        fs::write(dir.join("key"), EncryptionKey::generate().to_hex())?;
+
+        // TEST
+        assert!(run(Cli::Decrypt(files(&dir, "encrypted.csv", "decrypted.csv"))).is_err());
+        assert!(!dir.join("decrypted.csv").exists());
+
+        // TEARDOWN
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn decrypting_a_file_that_is_not_encrypted_fails() -> io::Result<()> {
+        // SETUP
+        let dir = std::env::temp_dir().join(format!("rust-payment-processor-crypt-plain-{}", process::id()));
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join("key"), EncryptionKey::generate().to_hex())?;
+        fs::write(dir.join("plain.csv"), "type, client, tx, amount\ndeposit, 1, 1, 5.0\n")?;
+
+        // TEST
+        assert_eq!(
+            Err(String::from("not an encrypted file")),
+            run(Cli::Decrypt(files(&dir, "plain.csv", "decrypted.csv")))
+        );
+
+        // TEARDOWN
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn a_key_is_required() {
+        // SETUP
+        let args = Cli::from_iter(vec!["crypt", "encrypt", "plain.csv", "encrypted.csv"]);
+
+        // TEST
+        assert_eq!(Err(String::from("one of --key-file or --key-env is required")), run(args));
+    }
+
+    #[test]
+    fn key_is_read_from_the_environment() {
+        // SETUP
+        let key = EncryptionKey::generate().to_hex();
+        std::env::set_var("RUST_PAYMENT_PROCESSOR_CRYPT_TEST_KEY", &key);
+        let args = vec![
+            "crypt",
+            "encrypt",
+            "plain.csv",
+            "encrypted.csv",
+            "--key-env=RUST_PAYMENT_PROCESSOR_CRYPT_TEST_KEY",
+        ];
+
+        // TEST
+        match Cli::from_iter(args) {
+            Cli::Encrypt(files) => assert_eq!(Ok(key), files.key().map(|key| key.to_hex())),
+            args => panic!("expected encrypt, got {:?}", args),
+        }
+    }
+}
+//endregion
@@ -0,0 +1,174 @@
+use env_logger::Env;
+use log::{error, info};
+use rust_payment_processor::bank::Bank;
+use rust_payment_processor::journal::{Follower, JournalError};
+use std::fs::File;
+use std::process;
+use std::thread;
+use std::time::Duration;
+use structopt::StructOpt;
+
+/// Maintain a hot standby by applying the journal written by a primary run with `--journal`.
+/// Polls the journal for new entries until stopped, or applies it once with `--once`.
+#[derive(StructOpt, Debug)]
+struct Cli {
+    #[structopt(parse(from_os_str))]
+    journal: std::path::PathBuf,
+    /// Apply the journal once, write the accounts, and exit
+    #[structopt(long)]
+    once: bool,
+    /// Milliseconds between polls of the journal
+    #[structopt(long, default_value = "500")]
+    poll_ms: u64,
+    /// Write the standby's accounts to this file after every poll that applied new entries,
+    /// instead of to stdout on exit
+    #[structopt(long, parse(from_os_str))]
+    output: Option<std::path::PathBuf>,
+}
+
+/// Apply the entries added to the journal since the last poll, then write the accounts to the
+/// output file, if any were applied. Returns the number applied.
+fn poll(follower: &mut Follower, args: &Cli) -> Result<usize, JournalError> {
+    let applied = follower.catch_up(&args.journal)?;
+    if applied > 0 {
+        info!("Applied {} entries, up to entry {}", applied, follower.applied());
+        if let Some(path) = &args.output {
+            let written = File::create(path)
+                .map_err(csv::Error::from)
+                .and_then(|file| follower.bank().write_accounts(file));
+            if let Err(e) = written {
+                error!("Failed to write accounts: {}", e);
+            }
+        }
+    }
+    Ok(applied)
+}
+
+fn main() {
+    env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
+    let args = Cli::from_args();
+    let mut follower = Follower::new(Bank::new());
+    loop {
+        if let Err(e) = poll(&mut follower, &args) {
+            error!("{}", e);
+            process::exit(1);
+        }
+        if args.once {
+            break;
+        }
+        thread::sleep(Duration::from_millis(args.poll_ms));
+    }
+    if args.output.is_none() {
+        if let Err(e) = follower.bank().print_accounts() {
+            error!("Failed to write accounts: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_payment_processor::journal::JournalWriter;
+    use rust_payment_processor::schema;
+    use rust_payment_processor::transaction::RecordOptions;
+    use std::fs::{self, OpenOptions};
+    use std::io;
+
+    /// Journal the transactions of a CSV input as a primary would, continuing `journal`.
+    fn journal<W: io::Write>(primary: &mut Bank, journal: &mut JournalWriter<W>, input: &str) {
+        let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(input.as_bytes());
+        let options = RecordOptions::default();
+        primary.process_all_with(schema::read_transactions(&mut reader, &options), journal);
+    }
+
+    #[test]
+    fn accounts_are_written_after_entries_are_applied() -> io::Result<()> {
+        // SETUP
+        let dir = std::env::temp_dir().join(format!("rust-payment-processor-follow-output-{}", process::id()));
+        fs::create_dir_all(&dir)?;
+        let mut writer = JournalWriter::new(File::create(dir.join("journal"))?);
+        journal(
+            &mut Bank::new(),
+            &mut writer,
+            "type, client, tx, amount\ndeposit, 1, 1, 5\nwithdrawal, 1, 2, 2\n",
+        );
+        let args = Cli::from_iter(vec![
+            String::from("follow"),
+            dir.join("journal").display().to_string(),
+            format!("--output={}", dir.join("accounts.csv").display()),
+        ]);
+        let mut follower = Follower::new(Bank::new());
+
+        // TEST
+        assert_eq!(2, poll(&mut follower, &args).expect("journal is applied"));
+        let accounts = fs::read_to_string(dir.join("accounts.csv"))?;
+        assert_eq!("client,available,held,total,locked\n1,3,0,3,false\n", accounts);
+
+        // TEARDOWN
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn only_entries_added_since_the_last_poll_are_applied() -> io::Result<()> {
+        // SETUP
+        let dir = std::env::temp_dir().join(format!("rust-payment-processor-follow-appended-{}", process::id()));
+        fs::create_dir_all(&dir)?;
+        let mut primary = Bank::new();
+        let mut writer = JournalWriter::new(File::create(dir.join("journal"))?);
+        journal(&mut primary, &mut writer, "type, client, tx, amount\ndeposit, 1, 1, 5\n");
+        let args = Cli::from_iter(vec![String::from("follow"), dir.join("journal").display().to_string()]);
+        let mut follower = Follower::new(Bank::new());
+        poll(&mut follower, &args).expect("journal is applied");
+
+        // TEST
+        assert_eq!(0, poll(&mut follower, &args).expect("nothing new"));
+        let mut writer = JournalWriter::resume(
+            OpenOptions::new().append(true).open(dir.join("journal"))?,
+            writer.seq(),
+            writer.digest(),
+        );
+        journal(&mut primary, &mut writer, "type, client, tx, amount\ndeposit, 2, 2, 3\n");
+        assert_eq!(1, poll(&mut follower, &args).expect("new entry is applied"));
+        assert_eq!(primary.accounts().count(), follower.bank().accounts().count());
+        assert_eq!(2, follower.applied());
+
+        // TEARDOWN
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn altered_journal_stops_the_follower() -> io::Result<()> {
+        // SETUP
+        let dir = std::env::temp_dir().join(format!("rust-payment-processor-follow-altered-{}", process::id()));
+        fs::create_dir_all(&dir)?;
+        let mut bytes = Vec::new();
+        journal(
+            &mut Bank::new(),
+            &mut JournalWriter::new(&mut bytes),
+            "type, client, tx, amount\ndeposit, 1, 1, 5\n",
+        );
+        let altered = String::from_utf8(bytes).expect("UTF-8").replacen(",1,1,5,", ",1,1,50,", 1);
+        fs::write(dir.join("journal"), altered)?;
+        let args = Cli::from_iter(vec![String::from("follow"), dir.join("journal").display().to_string()]);
+
+        // TEST
+        let result = poll(&mut Follower::new(Bank::new()), &args);
+        assert!(matches!(result, Err(JournalError::DigestMismatch { seq: 1 })), "{:?}", result);
+
+        // TEARDOWN
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn polls_every_500_milliseconds_by_default() {
+        // TEST
+        let args = Cli::from_iter(&["follow", "journal"]);
+        assert_eq!((500, false), (args.poll_ms, args.once));
+    }
+}
+//endregion
@@ -0,0 +1,126 @@
+use rust_payment_processor::lint::{self, Finding};
+use std::process;
+use structopt::StructOpt;
+
+/// Check a CSV input file for structural problems before processing it: reused transaction IDs,
+/// disputes, resolves, and chargebacks referring to unknown, later, or undisputed transactions,
+/// and amounts with too many decimal places. Prints every problem found, by line, and exits with
+/// status 1 if there were any.
+#[derive(StructOpt, Debug)]
+struct Cli {
+    #[structopt(parse(from_os_str))]
+    input_file: std::path::PathBuf,
+    /// The most decimal places an amount may have
+    #[structopt(long, default_value = "4")]
+    max_decimal_places: u32,
+}
+
+/// Lint the input file, returning every problem found.
+fn run(args: &Cli) -> Result<Vec<Finding>, csv::Error> {
+    csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_path(&args.input_file)
+        .and_then(|mut reader| lint::lint(&mut reader, args.max_decimal_places))
+}
+
+fn main() {
+    let args = Cli::from_args();
+    let findings = match run(&args) {
+        Ok(findings) => findings,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+    for finding in &findings {
+        println!("{}", finding);
+    }
+    if !findings.is_empty() {
+        eprintln!("{} problems found", findings.len());
+        process::exit(1);
+    }
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_payment_processor::lint::Problem;
+    use std::fs;
+    use std::io;
+
+    #[test]
+    fn clean_file_has_no_findings() -> io::Result<()> {
+        // SETUP
+        let path = std::env::temp_dir().join(format!("rust-payment-processor-lint-clean-{}", process::id()));
+        fs::write(
+            &path,
+            "type, client, tx, amount\ndeposit, 1, 1, 5.0\ndispute, 1, 1,\nresolve, 1, 1,\n",
+        )?;
+
+        // TEST
+        let findings = run(&Cli::from_iter(&["lint", &path.display().to_string()])).expect("file is read");
+        assert!(findings.is_empty(), "{:?}", findings);
+
+        // TEARDOWN
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn findings_are_written_by_line() -> io::Result<()> {
+        // SETUP
+        let path = std::env::temp_dir().join(format!("rust-payment-processor-lint-findings-{}", process::id()));
+        fs::write(
+            &path,
+            "type, client, tx, amount\ndeposit, 1, 1, 5.0\ndeposit, 1, 1, 2.0\nchargeback, 1, 9\n",
+        )?;
+
+        // TEST
+        let findings = run(&Cli::from_iter(&["lint", &path.display().to_string()])).expect("file is read");
+        let written: Vec<String> = findings.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            vec![
+                "line 3: tx 1: transaction ID already used on line 2",
+                "line 4: tx 9: refers to a transaction that does not exist",
+            ],
+            written
+        );
+
+        // TEARDOWN
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn amounts_are_checked_against_the_given_precision() -> io::Result<()> {
+        // SETUP
+        let path = std::env::temp_dir().join(format!("rust-payment-processor-lint-precision-{}", process::id()));
+        fs::write(&path, "type, client, tx, amount\ndeposit, 1, 1, 5.125\n")?;
+        let input = path.display().to_string();
+
+        // TEST
+        let default = run(&Cli::from_iter(&["lint", &input])).expect("file is read");
+        assert!(default.is_empty(), "{:?}", default);
+        let strict = run(&Cli::from_iter(&["lint", &input, "--max-decimal-places", "2"])).expect("file is read");
+        assert_eq!(
+            vec![Problem::TooPrecise { max: 2 }],
+            strict.into_iter().map(|finding| finding.problem).collect::<Vec<_>>()
+        );
+
+        // TEARDOWN
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn missing_file_is_an_error() {
+        // SETUP
+        let path = std::env::temp_dir().join(format!("rust-payment-processor-lint-missing-{}", process::id()));
+
+        // TEST
+        assert!(run(&Cli::from_iter(&["lint", &path.display().to_string()])).is_err());
+    }
+}
+//endregion
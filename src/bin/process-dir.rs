@@ -0,0 +1,201 @@
+use env_logger::Env;
+use rust_payment_processor::bank::{Bank, ProcessingSummary};
+use rust_payment_processor::schema;
+use rust_payment_processor::transaction::RecordOptions;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use structopt::StructOpt;
+
+/// Process every file in a directory as the ledger of an independent tenant, each with a bank of
+/// its own, and write the accounts of each to a file of the same name in the output directory.
+/// Files are processed concurrently. Exits with status 1 if any file could not be processed.
+#[derive(StructOpt, Debug)]
+struct Cli {
+    #[structopt(parse(from_os_str))]
+    input_dir: PathBuf,
+    /// The directory to write the accounts of each tenant to. Created if it does not exist; it may
+    /// not be the input directory
+    #[structopt(long, parse(from_os_str))]
+    output_dir: PathBuf,
+    /// The number of files processed at once. Defaults to the number of CPUs
+    #[structopt(long)]
+    jobs: Option<NonZeroUsize>,
+}
+
+/// Process one tenant's file with a new bank, writing its accounts to `output`.
+fn process_tenant(input: &Path, output: &Path) -> Result<ProcessingSummary, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_path(input)
+        .map_err(|e| e.to_string())?;
+    let options = RecordOptions::default();
+    schema::check_input(&mut reader, None, &options)?;
+    let mut bank = Bank::new();
+    let summary = bank.process_all(schema::read_transactions(&mut reader, &options));
+    let file = File::create(output).map_err(|e| e.to_string())?;
+    bank.write_accounts(BufWriter::new(file)).map_err(|e| e.to_string())?;
+    Ok(summary)
+}
+
+fn run(args: Cli) -> Result<usize, String> {
+    let mut inputs = Vec::new();
+    for entry in fs::read_dir(&args.input_dir).map_err(|e| format!("{}: {}", args.input_dir.display(), e))? {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        if path.is_file() {
+            inputs.push(path);
+        }
+    }
+    inputs.sort();
+    fs::create_dir_all(&args.output_dir).map_err(|e| format!("{}: {}", args.output_dir.display(), e))?;
+    if fs::canonicalize(&args.input_dir).ok() == fs::canonicalize(&args.output_dir).ok() {
+        return Err(String::from("--output-dir may not be the input directory"));
+    }
+    let jobs = args
+        .jobs
+        .or_else(|| thread::available_parallelism().ok())
+        .map_or(1, NonZeroUsize::get)
+        .min(inputs.len().max(1));
+    // each worker takes the next file not yet taken until none are left
+    let next = AtomicUsize::new(0);
+    let failed = Mutex::new(0);
+    thread::scope(|scope| {
+        for _ in 0..jobs {
+            scope.spawn(|| {
+                while let Some(input) = inputs.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    let name = input.file_name().expect("read_dir yields named entries");
+                    match process_tenant(input, &args.output_dir.join(name)) {
+                        Ok(summary) => eprintln!("{}: {}", input.display(), summary),
+                        Err(e) => {
+                            eprintln!("{}: {}", input.display(), e);
+                            *failed.lock().unwrap() += 1;
+                        }
+                    }
+                }
+            });
+        }
+    });
+    Ok(failed.into_inner().unwrap())
+}
+
+fn main() {
+    env_logger::Builder::from_env(Env::default().default_filter_or("off")).init();
+    match run(Cli::from_args()) {
+        Ok(0) => {}
+        Ok(failed) => {
+            eprintln!("{} files could not be processed", failed);
+            process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    #[test]
+    fn each_file_is_processed_by_a_bank_of_its_own() -> io::Result<()> {
+        // SETUP
+        let dir = std::env::temp_dir().join(format!("rust-payment-processor-process-dir-tenants-{}", process::id()));
+        fs::create_dir_all(dir.join("input"))?;
+        fs::write(dir.join("input").join("a.csv"), "type, client, tx, amount\ndeposit, 1, 1, 5\n")?;
+        fs::write(
+            dir.join("input").join("b.csv"),
+            "type, client, tx, amount\ndeposit, 1, 1, 2\nwithdrawal, 1, 2, 3\n",
+        )?;
+        let args = Cli::from_iter(vec![
+            String::from("process-dir"),
+            dir.join("input").display().to_string(),
+            format!("--output-dir={}", dir.join("output").display()),
+        ]);
+
+        // TEST
+        assert_eq!(Ok(0), run(args));
+        let a = fs::read_to_string(dir.join("output").join("a.csv"))?;
+        assert_eq!("client,available,held,total,locked\n1,5,0,5,false\n", a);
+        let b = fs::read_to_string(dir.join("output").join("b.csv"))?;
+        assert_eq!("client,available,held,total,locked\n1,2,0,2,false\n", b);
+
+        // TEARDOWN
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn files_that_can_not_be_processed_are_counted() -> io::Result<()> {
+        // SETUP
+        let dir = std::env::temp_dir().join(format!("rust-payment-processor-process-dir-failed-{}", process::id()));
+        fs::create_dir_all(dir.join("input"))?;
+        fs::write(dir.join("input").join("a.csv"), "type, client, tx, amount\ndeposit, 1, 1, 5\n")?;
+        fs::write(dir.join("input").join("b.csv"), "kind, account\ndeposit, 1\n")?;
+        let args = Cli::from_iter(vec![
+            String::from("process-dir"),
+            dir.join("input").display().to_string(),
+            format!("--output-dir={}", dir.join("output").display()),
+            String::from("--jobs=1"),
+        ]);
+
+        // TEST
+        assert_eq!(Ok(1), run(args));
+        assert!(dir.join("output").join("a.csv").exists());
+        assert!(!dir.join("output").join("b.csv").exists());
+
+        // TEARDOWN
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn output_directory_may_not_be_the_input_directory() -> io::Result<()> {
+        // SETUP
+        let dir = std::env::temp_dir().join(format!("rust-payment-processor-process-dir-same-{}", process::id()));
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join("a.csv"), "type, client, tx, amount\ndeposit, 1, 1, 5\n")?;
+        let args = Cli::from_iter(vec![
+            String::from("process-dir"),
+            dir.display().to_string(),
+            format!("--output-dir={}", dir.display()),
+        ]);
+
+        // TEST
+        assert_eq!(Err(String::from("--output-dir may not be the input directory")), run(args));
+        assert_eq!(1, fs::read_dir(&dir)?.count());
+
+        // TEARDOWN
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn missing_input_directory_is_an_error() {
+        // SETUP
+        let dir = std::env::temp_dir().join(format!("rust-payment-processor-process-dir-missing-{}", process::id()));
+        let args = Cli::from_iter(vec![
+            String::from("process-dir"),
+            dir.join("input").display().to_string(),
+            format!("--output-dir={}", dir.join("output").display()),
+        ]);
+
+        // TEST
+        assert!(run(args).is_err());
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn jobs_may_not_be_zero() {
+        // TEST
+        assert!(Cli::from_iter_safe(&["process-dir", "input", "--output-dir=output", "--jobs=0"]).is_err());
+    }
+}
+//endregion
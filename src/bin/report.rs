@@ -0,0 +1,311 @@
+use env_logger::Env;
+use rust_payment_processor::aging::{self, AgedDispute, DisputeAging, Sla};
+use rust_payment_processor::amount::{self, Amount, AmountFormat};
+use rust_payment_processor::analytics::{self, Analytics, AnalyticsCollector};
+use rust_payment_processor::bank::Bank;
+use rust_payment_processor::ctr::{self, DailyDeposits, ReportableDay};
+use rust_payment_processor::locale::Locale;
+use rust_payment_processor::position::{Position, PositionCollector};
+use rust_payment_processor::schema;
+use rust_payment_processor::transaction::RecordOptions;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{self, Write};
+use std::process;
+use std::str::FromStr;
+use structopt::StructOpt;
+
+/// `ReportFormat` enumerates the formats a report may be written in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ReportFormat {
+    Csv,
+    Json,
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<ReportFormat, String> {
+        match value {
+            "csv" => Ok(ReportFormat::Csv),
+            "json" => Ok(ReportFormat::Json),
+            _ => Err(format!("unsupported report format: {}", value)),
+        }
+    }
+}
+
+/// Process a CSV transaction file as the processor does and report on the run instead of writing
+/// the accounts.
+#[derive(StructOpt, Debug)]
+struct Cli {
+    #[structopt(parse(from_os_str))]
+    input_file: std::path::PathBuf,
+    /// Report the top accounts by total and held funds, histograms of deposit and withdrawal
+    /// amounts, dispute resolution rates, and average transaction sizes
+    #[structopt(long)]
+    analytics: bool,
+    /// Report the bank's aggregate position: total client liabilities, funds held, the amounts
+    /// deposited, withdrawn, and charged back, and the net movement of the run
+    #[structopt(long)]
+    position: bool,
+    /// Report the disputes left open for longer than this: a number of records processed since
+    /// they were opened, or a duration such as `30d` measured on the input's timestamps
+    #[structopt(long)]
+    dispute_sla: Option<Sla>,
+    /// Report every client whose deposits on a single day add up to more than this amount, e.g.
+    /// `10000`, with the deposits making up the total, for currency transaction reporting
+    #[structopt(long, parse(try_from_str = parse_threshold))]
+    ctr_threshold: Option<Amount>,
+    /// The number of accounts in each top-N ranking
+    #[structopt(long, default_value = "10")]
+    top: usize,
+    /// Format of the report: `csv` or `json`
+    #[structopt(long, default_value = "csv")]
+    format: ReportFormat,
+    /// Language of the report's column headers: `en`, `fr`, or `de`
+    #[structopt(long, default_value = "en")]
+    locale: Locale,
+    /// Write the report to this file instead of stdout
+    #[structopt(long, parse(from_os_str))]
+    output: Option<std::path::PathBuf>,
+}
+
+/// `Report` holds the sections requested on the command line.
+#[derive(Serialize)]
+struct Report {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    analytics: Option<Analytics>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    position: Option<Position>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    overdue_disputes: Option<Vec<AgedDispute>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    currency_transactions: Option<Vec<ReportableDay>>,
+    #[serde(skip)]
+    sla: Option<Sla>,
+}
+
+impl Report {
+    fn write_csv<W: Write>(&self, writer: W, locale: Locale) -> Result<(), csv::Error> {
+        let mut wtr = analytics::metric_writer(writer, locale)?;
+        if let Some(analytics) = &self.analytics {
+            analytics.write_rows(&mut wtr)?;
+        }
+        if let Some(position) = &self.position {
+            position.write_rows(&mut wtr)?;
+        }
+        if let (Some(overdue), Some(sla)) = (&self.overdue_disputes, self.sla) {
+            aging::write_rows(overdue, sla, &mut wtr)?;
+        }
+        if let Some(days) = &self.currency_transactions {
+            ctr::write_rows(days, &mut wtr)?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+}
+
+fn parse_threshold(value: &str) -> Result<Amount, String> {
+    amount::parse_amount(value, &AmountFormat::new()).map_err(|e| format!("invalid threshold \"{}\": {}", value, e))
+}
+
+fn run(args: Cli) -> Result<(), String> {
+    if !args.analytics && !args.position && args.dispute_sla.is_none() && args.ctr_threshold.is_none() {
+        return Err(String::from(
+            "nothing to report: pass --analytics, --position, --dispute-sla, and/or --ctr-threshold",
+        ));
+    }
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_path(&args.input_file)
+        .map_err(|e| e.to_string())?;
+    let options = RecordOptions::default();
+    schema::check_input(&mut reader, None, &options)?;
+    let mut bank = Bank::new();
+    let mut collectors = (
+        (
+            Some(AnalyticsCollector::new()).filter(|_| args.analytics),
+            match args.position {
+                true => Some(PositionCollector::new(&bank).map_err(|e| e.to_string())?),
+                false => None,
+            },
+        ),
+        (
+            args.dispute_sla.map(|_| DisputeAging::new()),
+            args.ctr_threshold.map(|_| DailyDeposits::new()),
+        ),
+    );
+    let file = args.input_file.display().to_string();
+    bank.process_all_with(schema::read_transactions_from(&mut reader, &file, &options), &mut collectors);
+    let ((analytics, position), (aging, deposits)) = collectors;
+    let report = Report {
+        analytics: analytics.map(|collector| collector.finish(&bank, args.top)),
+        position: position
+            .map(|collector| collector.finish(&bank))
+            .transpose()
+            .map_err(|e| e.to_string())?,
+        overdue_disputes: aging.zip(args.dispute_sla).map(|(aging, sla)| aging.overdue(sla)),
+        currency_transactions: deposits
+            .zip(args.ctr_threshold)
+            .map(|(deposits, threshold)| deposits.over(threshold)),
+        sla: args.dispute_sla,
+    };
+    let mut writer: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(File::create(path).map_err(|e| e.to_string())?),
+        None => Box::new(io::stdout()),
+    };
+    match args.format {
+        ReportFormat::Csv => report.write_csv(&mut writer, args.locale).map_err(|e| e.to_string())?,
+        ReportFormat::Json => {
+            serde_json::to_writer_pretty(&mut writer, &report).map_err(|e| e.to_string())?;
+            writeln!(writer).map_err(|e| e.to_string())?;
+        }
+    }
+    writer.flush().map_err(|e| e.to_string())
+}
+
+fn main() {
+    env_logger::Builder::from_env(Env::default().default_filter_or("off")).init();
+    if let Err(e) = run(Cli::from_args()) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    const INPUT: &str = "type, client, tx, amount, timestamp, currency
+deposit, 1, 1, 6000, 2021-03-01T09:00:00Z, USD
+deposit, 1, 2, 5000, 2021-03-01T12:00:00Z, USD
+deposit, 2, 3, 10, 2021-03-01T12:00:00Z, USD
+withdrawal, 2, 4, 4, 2021-03-02T12:00:00Z, USD
+dispute, 2, 3,, 2021-03-02T13:00:00Z, USD
+deposit, 2, 5, 1, 2021-03-03T13:00:00Z, USD
+";
+
+    /// Report on `INPUT` with the given options, returning the report.
+    fn report(name: &str, options: &[&str]) -> io::Result<Result<String, String>> {
+        let dir = std::env::temp_dir().join(format!("rust-payment-processor-report-{}-{}", name, process::id()));
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join("input.csv"), INPUT)?;
+        let mut args = vec![
+            String::from("report"),
+            dir.join("input.csv").display().to_string(),
+            format!("--output={}", dir.join("report").display()),
+        ];
+        args.extend(options.iter().map(|option| option.to_string()));
+        let report = match run(Cli::from_iter(args)) {
+            Ok(()) => Ok(fs::read_to_string(dir.join("report"))?),
+            Err(e) => Err(e),
+        };
+        fs::remove_dir_all(&dir)?;
+        Ok(report)
+    }
+
+    #[test]
+    fn position_is_reported_as_metrics() -> io::Result<()> {
+        // TEST
+        let report = report("position", &["--position"])?.expect("report is written");
+        assert!(report.starts_with("metric,key,value\n"), "{}", report);
+        assert!(report.contains("\nposition,deposited,11011\n"), "{}", report);
+        assert!(report.contains("\nposition,withdrawn,4\n"), "{}", report);
+        assert!(report.contains("\nposition,total_held,10\n"), "{}", report);
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn disputes_open_for_more_records_than_the_sla_are_reported() -> io::Result<()> {
+        // TEST
+        let overdue = report("dispute-sla-records-overdue", &["--dispute-sla", "0"])?.expect("report is written");
+        assert_eq!("metric,key,value\noverdue_dispute,3,1\n", overdue);
+        let within = report("dispute-sla-records-within", &["--dispute-sla", "1"])?.expect("report is written");
+        assert_eq!("metric,key,value\n", within);
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn disputes_open_for_longer_than_the_sla_are_reported() -> io::Result<()> {
+        // TEST
+        let overdue = report("dispute-sla-age-overdue", &["--dispute-sla", "12h"])?.expect("report is written");
+        assert_eq!("metric,key,value\noverdue_dispute,3,86400s\n", overdue);
+        let within = report("dispute-sla-age-within", &["--dispute-sla", "1d"])?.expect("report is written");
+        assert_eq!("metric,key,value\n", within);
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn daily_deposits_over_the_threshold_are_reported() -> io::Result<()> {
+        // TEST
+        let report = report("ctr", &["--ctr-threshold", "10000"])?.expect("report is written");
+        assert_eq!(
+            "metric,key,value\nctr_total,2021-03-01/1,11000\nctr_deposit,2021-03-01/1/1,6000\nctr_deposit,2021-03-01/1/2,5000\n",
+            report
+        );
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn report_is_written_as_json() -> io::Result<()> {
+        // TEST
+        let report = report("json", &["--analytics", "--format", "json"])?.expect("report is written");
+        let report: serde_json::Value = serde_json::from_str(&report).expect("report is JSON");
+        assert_eq!(6, report["analytics"]["processed"]);
+        assert!(report.get("position").is_none());
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn column_headers_are_written_in_the_locale() -> io::Result<()> {
+        // TEST
+        let report = report("locale", &["--position", "--locale", "fr"])?.expect("report is written");
+        assert!(report.starts_with("indicateur,clé,valeur\n"), "{}", report);
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn a_section_is_required() -> io::Result<()> {
+        // TEST
+        let report = report("nothing", &[])?;
+        assert_eq!(
+            Err(String::from(
+                "nothing to report: pass --analytics, --position, --dispute-sla, and/or --ctr-threshold"
+            )),
+            report
+        );
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn report_formats_are_read_by_name() {
+        // TEST
+        assert_eq!(Ok(ReportFormat::Csv), "csv".parse());
+        assert_eq!(Ok(ReportFormat::Json), "json".parse());
+        assert_eq!(Err(String::from("unsupported report format: xml")), "xml".parse::<ReportFormat>());
+    }
+
+    #[test]
+    fn threshold_must_be_an_amount() {
+        // TEST
+        assert_eq!(Ok(Amount::from(10000u32)), parse_threshold("10000"));
+        assert!(parse_threshold("ten thousand").is_err());
+    }
+}
+//endregion
@@ -0,0 +1,118 @@
+use rust_payment_processor::script::Script;
+use std::fs;
+use std::path::Path;
+use std::process;
+use structopt::StructOpt;
+
+/// Run scenario scripts (see the `script` module for the language), printing every expectation
+/// that was not met. Exits with status 1 if a script could not be read or parsed, or failed.
+#[derive(StructOpt, Debug)]
+struct Cli {
+    #[structopt(parse(from_os_str), required = true)]
+    scripts: Vec<std::path::PathBuf>,
+}
+
+/// Run the script at `path`, returning every expectation that was not met, or why the script could
+/// not be read or parsed.
+fn failures(path: &Path) -> Vec<String> {
+    let script = fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|text| text.parse::<Script>().map_err(|e| e.to_string()));
+    match script {
+        Ok(script) => script.run().1.iter().map(ToString::to_string).collect(),
+        Err(e) => vec![e],
+    }
+}
+
+fn main() {
+    let args = Cli::from_args();
+    let mut failed = 0;
+    for path in &args.scripts {
+        let failures = failures(path);
+        if failures.is_empty() {
+            println!("{}: ok", path.display());
+            continue;
+        }
+        failed += 1;
+        println!("{}: FAILED", path.display());
+        for failure in failures {
+            println!("    {}", failure);
+        }
+    }
+    println!("{} of {} scenarios passed", args.scripts.len() - failed, args.scripts.len());
+    if failed > 0 {
+        process::exit(1);
+    }
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    #[test]
+    fn script_whose_expectations_are_met_has_no_failures() -> io::Result<()> {
+        // SETUP
+        let path = std::env::temp_dir().join(format!("rust-payment-processor-scenario-ok-{}", process::id()));
+        fs::write(
+            &path,
+            "client 1 deposits 5; disputes tx 1; expect available 0 held 5\ncharges back tx 1\nexpect total 0 locked\n",
+        )?;
+
+        // TEST
+        assert_eq!(Vec::<String>::new(), failures(&path));
+
+        // TEARDOWN
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn unmet_expectations_are_failures() -> io::Result<()> {
+        // SETUP
+        let path = std::env::temp_dir().join(format!("rust-payment-processor-scenario-unmet-{}", process::id()));
+        fs::write(&path, "client 1 deposits 5\nwithdraws 2\nexpect available 5\n")?;
+
+        // TEST
+        let failures = failures(&path);
+        assert_eq!(1, failures.len(), "{:?}", failures);
+        assert!(failures[0].starts_with("line 3: "), "{:?}", failures);
+
+        // TEARDOWN
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn script_that_can_not_be_parsed_fails() -> io::Result<()> {
+        // SETUP
+        let path = std::env::temp_dir().join(format!("rust-payment-processor-scenario-invalid-{}", process::id()));
+        fs::write(&path, "client 1 deposits 5\nclient 1 borrows 5\n")?;
+
+        // TEST
+        let failures = failures(&path);
+        assert_eq!(1, failures.len(), "{:?}", failures);
+        assert!(failures[0].starts_with("line 2: "), "{:?}", failures);
+
+        // TEARDOWN
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn script_that_can_not_be_read_fails() {
+        // SETUP
+        let path = std::env::temp_dir().join(format!("rust-payment-processor-scenario-missing-{}", process::id()));
+
+        // TEST
+        assert_eq!(1, failures(&path).len());
+    }
+
+    #[test]
+    fn at_least_one_script_is_required() {
+        // TEST
+        assert!(Cli::from_iter_safe(&["scenario"]).is_err());
+    }
+}
+//endregion
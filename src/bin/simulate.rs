@@ -0,0 +1,118 @@
+use rust_payment_processor::simulation::{self, SimulationConfig};
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+use structopt::StructOpt;
+
+/// Run seeded simulations of the ledger, verifying its invariants. Exits with status 1 and prints
+/// the failing seed if any run fails.
+#[derive(StructOpt, Debug)]
+struct Cli {
+    /// Seed of the first run. Chosen from the clock when omitted.
+    #[structopt(long)]
+    seed: Option<u64>,
+    /// Number of runs, with consecutive seeds
+    #[structopt(long, default_value = "1")]
+    runs: u64,
+    /// Number of transactions per run
+    #[structopt(long, default_value = "10000")]
+    transactions: usize,
+    /// Number of clients transacting
+    #[structopt(long, default_value = "100")]
+    clients: u16,
+    /// Share of transactions, between 0 and 1, subjected to chaos
+    #[structopt(long, default_value = "0.1")]
+    chaos: f64,
+}
+
+/// The seed of the first run: the one given, or one chosen from the clock.
+fn first_seed(args: &Cli) -> u64 {
+    args.seed.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos() as u64)
+            .unwrap_or_default()
+    })
+}
+
+/// The configuration of every run, with consecutive seeds from `first`.
+fn configs(args: &Cli, first: u64) -> impl Iterator<Item = SimulationConfig> + '_ {
+    (0..args.runs).map(move |run| SimulationConfig {
+        seed: first.wrapping_add(run),
+        transactions: args.transactions,
+        clients: args.clients,
+        chaos: args.chaos,
+    })
+}
+
+fn main() {
+    let args = Cli::from_args();
+    for config in configs(&args, first_seed(&args)) {
+        match simulation::run(&config) {
+            Ok(report) => println!(
+                "seed {}: ok, {} transactions ({} rejected), {} accounts",
+                report.seed, report.processed, report.rejected, report.accounts
+            ),
+            Err(failure) => {
+                eprintln!("{}", failure);
+                eprintln!(
+                    "reproduce with: --seed {} --transactions {} --chaos {}",
+                    config.seed, args.transactions, args.chaos
+                );
+                process::exit(1);
+            }
+        }
+    }
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn given_seed_is_the_first() {
+        // SETUP
+        let args = Cli::from_iter(&["simulate", "--seed", "42"]);
+
+        // TEST
+        assert_eq!(42, first_seed(&args));
+    }
+
+    #[test]
+    fn runs_have_consecutive_seeds() {
+        // SETUP
+        let args = Cli::from_iter(&["simulate", "--runs", "3"]);
+
+        // TEST
+        let seeds: Vec<u64> = configs(&args, u64::MAX - 1).map(|config| config.seed).collect();
+        assert_eq!(vec![u64::MAX - 1, u64::MAX, 0], seeds);
+    }
+
+    #[test]
+    fn runs_are_configured_from_the_command_line() {
+        // SETUP
+        let args = Cli::from_iter(&["simulate", "--transactions", "50", "--clients", "3", "--chaos", "0.5"]);
+
+        // TEST
+        let config = configs(&args, 7).next().expect("one run by default");
+        assert_eq!((7, 50, 3, 0.5), (config.seed, config.transactions, config.clients, config.chaos));
+        assert_eq!(1, configs(&args, 7).count());
+    }
+
+    #[test]
+    fn seeded_runs_pass_and_are_reproducible() {
+        // SETUP
+        let args = Cli::from_iter(&["simulate", "--seed", "1", "--runs", "2", "--transactions", "500", "--clients", "5"]);
+
+        // TEST
+        for config in configs(&args, first_seed(&args)) {
+            let first = simulation::run(&config).expect("invariants hold");
+            let second = simulation::run(&config).expect("invariants hold");
+            assert_eq!(
+                (500, first.rejected, first.accounts),
+                (second.processed, second.rejected, second.accounts)
+            );
+        }
+    }
+}
+//endregion
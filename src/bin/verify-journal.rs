@@ -0,0 +1,125 @@
+use rust_payment_processor::journal;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::process;
+use structopt::StructOpt;
+
+/// Verify the hash chain of a journal written with `--journal`, printing the number of entries and
+/// the final digest. Exits with status 1 if the journal has been altered, or if its final digest
+/// differs from the one given with `--expect`.
+#[derive(StructOpt, Debug)]
+struct Cli {
+    #[structopt(parse(from_os_str))]
+    journal: std::path::PathBuf,
+    /// The final digest recorded when the journal was written
+    #[structopt(long)]
+    expect: Option<String>,
+}
+
+/// Verify the journal, returning the number of entries and the final digest, hex-encoded.
+fn verify(path: &Path) -> Result<(u64, String), journal::JournalError> {
+    let file = File::open(path).map_err(journal::JournalError::Io)?;
+    let (entries, digest) = journal::verify(BufReader::new(file))?;
+    Ok((entries, journal::to_hex(&digest)))
+}
+
+/// Whether `digest` is the one given with `--expect`, in either case. Any digest matches if none
+/// was given.
+fn matches(expected: Option<&str>, digest: &str) -> bool {
+    expected.is_none_or(|expected| expected.eq_ignore_ascii_case(digest))
+}
+
+fn main() {
+    let args = Cli::from_args();
+    let (entries, digest) = match verify(&args.journal) {
+        Ok(verified) => verified,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    };
+    println!("{} entries, digest {}", entries, digest);
+    if !matches(args.expect.as_deref(), &digest) {
+        eprintln!("final digest does not match: expected {}", args.expect.unwrap_or_default());
+        process::exit(1);
+    }
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_payment_processor::bank::Bank;
+    use rust_payment_processor::journal::{JournalError, JournalWriter};
+    use rust_payment_processor::schema;
+    use rust_payment_processor::transaction::RecordOptions;
+    use std::fs;
+    use std::io;
+
+    /// Journal the transactions of a CSV input, returning the journal and its final digest.
+    fn journal_of(input: &str) -> (Vec<u8>, String) {
+        let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(input.as_bytes());
+        let options = RecordOptions::default();
+        let mut bytes = Vec::new();
+        let mut journal = JournalWriter::new(&mut bytes);
+        Bank::new().process_all_with(schema::read_transactions(&mut reader, &options), &mut journal);
+        let digest = journal::to_hex(&journal.digest());
+        drop(journal);
+        (bytes, digest)
+    }
+
+    #[test]
+    fn journal_is_verified_to_its_final_digest() -> io::Result<()> {
+        // SETUP
+        let path = std::env::temp_dir().join(format!("rust-payment-processor-verify-journal-intact-{}", process::id()));
+        let (journal, digest) = journal_of("type, client, tx, amount\ndeposit, 1, 1, 5\nwithdrawal, 1, 2, 2\n");
+        fs::write(&path, journal)?;
+
+        // TEST
+        assert_eq!(Some((2, digest)), verify(&path).ok());
+
+        // TEARDOWN
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn altered_journal_fails_verification() -> io::Result<()> {
+        // SETUP
+        let path = std::env::temp_dir().join(format!("rust-payment-processor-verify-journal-altered-{}", process::id()));
+        let (journal, _) = journal_of("type, client, tx, amount\ndeposit, 1, 1, 5\nwithdrawal, 1, 2, 2\n");
+        let altered = String::from_utf8(journal).expect("UTF-8").replacen(",1,1,5,", ",1,1,50,", 1);
+        fs::write(&path, altered)?;
+
+        // TEST
+        assert!(matches!(verify(&path), Err(JournalError::DigestMismatch { seq: 1 })));
+
+        // TEARDOWN
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn missing_journal_fails_verification() {
+        // SETUP
+        let path = std::env::temp_dir().join(format!("rust-payment-processor-verify-journal-missing-{}", process::id()));
+
+        // TEST
+        assert!(matches!(verify(&path), Err(JournalError::Io(_))));
+    }
+
+    #[test]
+    fn expected_digest_matches_in_either_case() {
+        // TEST
+        assert!(matches(Some("ABCDEF"), "abcdef"));
+        assert!(!matches(Some("abcdee"), "abcdef"));
+    }
+
+    #[test]
+    fn any_digest_matches_without_an_expected_one() {
+        // TEST
+        assert!(matches(None, "abcdef"));
+    }
+}
+//endregion
@@ -0,0 +1,177 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Evicted transaction IDs
+//! When a `RetentionPolicy` evicts transactions, their IDs are remembered in a Bloom filter so that
+//! reusing one is still rejected with `DuplicateTransactionId`, in memory bounded by the filter's
+//! capacity rather than by the length of the run. A Bloom filter never misses an ID it holds, but
+//! may claim to hold one it does not; such a false positive rejects a legitimate transaction as a
+//! duplicate. With a spill file configured, every evicted ID is also written to disk, and a match of
+//! the filter is confirmed against the file before a transaction is rejected.
+use crate::config::DuplicateFilter;
+use crate::transaction::TxId;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::PathBuf;
+
+/// `BloomFilter` is a set of transaction IDs that may report false positives, but never false
+/// negatives.
+#[derive(Clone, Debug)]
+pub struct BloomFilter {
+    bits: Vec<u64>,
+    hashes: u32,
+}
+
+impl BloomFilter {
+    /// A filter holding up to `capacity` IDs with the given false positive rate.
+    pub fn new(capacity: usize, false_positive_rate: f64) -> BloomFilter {
+        let capacity = capacity.max(1) as f64;
+        let rate = false_positive_rate.clamp(f64::MIN_POSITIVE, 0.5);
+        let bits = (-capacity * rate.ln() / std::f64::consts::LN_2.powi(2)).ceil().max(64.0);
+        let hashes = (bits / capacity * std::f64::consts::LN_2).round().max(1.0) as u32;
+        BloomFilter {
+            bits: vec![0; (bits as usize).div_ceil(64)],
+            hashes,
+        }
+    }
+
+    /// The bit positions of an ID, by double hashing two halves of a 64 bit mix of it.
+    fn positions(&self, tx: TxId) -> impl Iterator<Item = usize> + 'static {
+        let mut z = tx.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        let (first, second) = (z & 0xffff_ffff, (z >> 32) | 1);
+        let len = self.bits.len() as u64 * 64;
+        (0..u64::from(self.hashes)).map(move |i| (first.wrapping_add(i.wrapping_mul(second)) % len) as usize)
+    }
+
+    pub fn insert(&mut self, tx: TxId) {
+        for position in self.positions(tx) {
+            self.bits[position / 64] |= 1 << (position % 64);
+        }
+    }
+
+    /// Whether the filter may hold the ID. `false` is always correct.
+    pub fn contains(&self, tx: TxId) -> bool {
+        self.positions(tx)
+            .all(|position| self.bits[position / 64] & (1 << (position % 64)) != 0)
+    }
+}
+
+/// `EvictedIds` remembers the IDs of evicted transactions, in a Bloom filter and optionally a spill
+/// file.
+pub struct EvictedIds {
+    filter: BloomFilter,
+    spill: Option<Spill>,
+}
+
+/// `Spill` is a file of evicted IDs, each written as 8 little-endian bytes. It is created when the
+/// first ID is evicted.
+struct Spill {
+    path: PathBuf,
+    writer: Option<BufWriter<File>>,
+}
+
+impl Spill {
+    fn append(&mut self, tx: TxId) -> io::Result<()> {
+        if self.writer.is_none() {
+            let file = OpenOptions::new().create(true).write(true).truncate(true).open(&self.path)?;
+            self.writer = Some(BufWriter::new(file));
+        }
+        self.writer.as_mut().expect("created above").write_all(&tx.to_le_bytes())
+    }
+
+    fn contains(&mut self, tx: TxId) -> io::Result<bool> {
+        let writer = match self.writer.as_mut() {
+            Some(writer) => writer,
+            None => return Ok(false),
+        };
+        writer.flush()?;
+        let mut reader = BufReader::new(File::open(&self.path)?);
+        let mut id = [0u8; std::mem::size_of::<TxId>()];
+        loop {
+            match reader.read_exact(&mut id) {
+                Ok(()) if TxId::from_le_bytes(id) == tx => return Ok(true),
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(false),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl EvictedIds {
+    pub fn new(config: &DuplicateFilter) -> EvictedIds {
+        EvictedIds {
+            filter: BloomFilter::new(config.capacity, config.false_positive_rate),
+            spill: config.spill.clone().map(|path| Spill { path, writer: None }),
+        }
+    }
+
+    /// The number of bytes taken by the filter. The spill file is not counted, as it is on disk.
+    pub fn memory_usage(&self) -> usize {
+        self.filter.bits.len() * std::mem::size_of::<u64>()
+    }
+
+    pub fn insert(&mut self, tx: TxId) {
+        self.filter.insert(tx);
+        if let Some(spill) = &mut self.spill {
+            if let Err(e) = spill.append(tx) {
+                error!("Failed to spill evicted transaction ID {}. Aborted with error: {}", tx, e);
+            }
+        }
+    }
+
+    /// Whether the ID has been evicted. Without a spill file this may be a false positive. If the
+    /// spill file can not be read, a match of the filter is trusted.
+    pub fn contains(&mut self, tx: TxId) -> bool {
+        if !self.filter.contains(tx) {
+            return false;
+        }
+        match &mut self.spill {
+            None => true,
+            Some(spill) => spill.contains(tx).unwrap_or_else(|e| {
+                error!("Failed to read spilled transaction IDs. Discarded with error: {}", e);
+                true
+            }),
+        }
+    }
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filter_has_no_false_negatives_and_few_false_positives() {
+        // SETUP
+        let mut filter = BloomFilter::new(10_000, 0.01);
+        (0..10_000).for_each(|tx| filter.insert(tx * 7));
+
+        // TEST
+        assert!((0..10_000).all(|tx| filter.contains(tx * 7)));
+        let false_positives = (0..10_000).filter(|tx| filter.contains(tx * 7 + 1)).count();
+        assert!(false_positives < 200, "{} false positives", false_positives);
+    }
+
+    #[test]
+    fn spill_file_rules_out_false_positives() {
+        // SETUP
+        let path = std::env::temp_dir().join(format!("rust-payment-processor-spill-{}", std::process::id()));
+        let config = DuplicateFilter {
+            capacity: 1,
+            false_positive_rate: 0.5,
+            spill: Some(path.clone()),
+        };
+        let mut evicted = EvictedIds::new(&config);
+        (0..100).for_each(|tx| evicted.insert(tx));
+
+        // TEST
+        assert!((0..100).all(|tx| evicted.contains(tx)));
+        assert!((100..1_000).all(|tx| !evicted.contains(tx)));
+
+        // TEARDOWN
+        let _ = std::fs::remove_file(path);
+    }
+}
+//endregion
@@ -0,0 +1,204 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Business-day calendar
+//! Operations dated for settlement, such as scheduled transactions, interest accrual, and
+//! settlement cycles, only fall on business days: a date landing on a weekend or a holiday is
+//! rolled forward to the next business day. `BusinessCalendar` knows which days those are. Its
+//! holidays are read from a list of dates, one per line, with `#` starting a comment:
+//!
+//! ```text
+//! # bank holidays
+//! 2021-12-25
+//! 2021-12-26
+//! 2022-01-01
+//! ```
+use chrono::{Datelike, NaiveDate, Weekday};
+use std::collections::BTreeSet;
+use std::io::{self, BufRead};
+
+/// `BusinessCalendar` is the set of days on which no business is done: the weekend days of every
+/// week, and a list of holidays.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BusinessCalendar {
+    weekend: Vec<Weekday>,
+    holidays: BTreeSet<NaiveDate>,
+}
+
+impl Default for BusinessCalendar {
+    /// A calendar with Saturdays and Sundays off, and no holidays.
+    fn default() -> BusinessCalendar {
+        BusinessCalendar {
+            weekend: vec![Weekday::Sat, Weekday::Sun],
+            holidays: BTreeSet::new(),
+        }
+    }
+}
+
+impl BusinessCalendar {
+    pub fn new() -> BusinessCalendar {
+        BusinessCalendar::default()
+    }
+
+    /// Take these days of the week off instead of Saturday and Sunday, e.g. Friday and Saturday.
+    pub fn with_weekend(mut self, weekend: Vec<Weekday>) -> BusinessCalendar {
+        self.weekend = weekend;
+        self
+    }
+
+    /// Take the given dates off too.
+    pub fn with_holidays<I: IntoIterator<Item = NaiveDate>>(mut self, holidays: I) -> BusinessCalendar {
+        self.holidays.extend(holidays);
+        self
+    }
+
+    /// Read a list of holidays in the format described above, added to those of this calendar.
+    pub fn read_holidays<R: io::Read>(self, reader: R) -> Result<BusinessCalendar, String> {
+        let mut holidays = Vec::new();
+        for (number, line) in io::BufReader::new(reader).lines().enumerate() {
+            let line = line.map_err(|e| e.to_string())?;
+            let date = line.split('#').next().unwrap_or_default().trim();
+            if date.is_empty() {
+                continue;
+            }
+            let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .map_err(|e| format!("invalid holiday \"{}\" on line {}: {}", date, number + 1, e))?;
+            holidays.push(date);
+        }
+        Ok(self.with_holidays(holidays))
+    }
+
+    pub fn is_business_day(&self, date: NaiveDate) -> bool {
+        !self.weekend.contains(&date.weekday()) && !self.holidays.contains(&date)
+    }
+
+    /// The given date if it is a business day, or else the next business day after it. Returns
+    /// `None` if there is none, as with a calendar taking every day of the week off.
+    pub fn roll_forward(&self, date: NaiveDate) -> Option<NaiveDate> {
+        if date.iter_days().take(7).all(|day| self.weekend.contains(&day.weekday())) {
+            return None;
+        }
+        date.iter_days().find(|&day| self.is_business_day(day))
+    }
+
+    /// The business day `days` business days after the given date, which is rolled forward first.
+    /// This is how settlement cycles such as T+2 count.
+    pub fn add_business_days(&self, date: NaiveDate, days: u32) -> Option<NaiveDate> {
+        let start = self.roll_forward(date)?;
+        start.iter_days().filter(|&day| self.is_business_day(day)).nth(days as usize)
+    }
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Christmas 2021 is a Saturday
+    fn date(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2021, month, day).unwrap()
+    }
+
+    #[test]
+    fn weekdays_are_business_days() {
+        // SETUP
+        let calendar = BusinessCalendar::new();
+
+        // TEST
+        assert!(calendar.is_business_day(date(12, 24)));
+        assert!(!calendar.is_business_day(date(12, 25)));
+        assert!(!calendar.is_business_day(date(12, 26)));
+    }
+
+    #[test]
+    fn holidays_are_not_business_days() {
+        // SETUP
+        let calendar = BusinessCalendar::new().with_holidays(vec![date(12, 27)]);
+
+        // TEST
+        assert!(!calendar.is_business_day(date(12, 27)));
+        assert!(calendar.is_business_day(date(12, 28)));
+    }
+
+    #[test]
+    fn holidays_are_read_one_per_line_with_comments() -> Result<(), String> {
+        // SETUP
+        let calendar = BusinessCalendar::new().read_holidays("# bank holidays\n2021-12-27\n\n2021-12-28 # boxing day\n".as_bytes())?;
+
+        // TEST
+        assert_eq!(BusinessCalendar::new().with_holidays(vec![date(12, 27), date(12, 28)]), calendar);
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn holidays_that_can_not_be_parsed_are_rejected_with_their_line() {
+        // TEST
+        let error = BusinessCalendar::new()
+            .read_holidays("2021-12-27\n2021-13-01\n".as_bytes())
+            .unwrap_err();
+        assert!(error.starts_with("invalid holiday \"2021-13-01\" on line 2"), "{}", error);
+    }
+
+    #[test]
+    fn business_days_do_not_roll() {
+        // TEST
+        assert_eq!(Some(date(12, 24)), BusinessCalendar::new().roll_forward(date(12, 24)));
+    }
+
+    #[test]
+    fn dates_roll_forward_over_weekends_and_holidays() {
+        // SETUP
+        let calendar = BusinessCalendar::new().with_holidays(vec![date(12, 27), date(12, 28)]);
+
+        // TEST
+        assert_eq!(Some(date(12, 29)), calendar.roll_forward(date(12, 25)));
+    }
+
+    #[test]
+    fn weekend_can_be_other_days() {
+        // SETUP
+        let calendar = BusinessCalendar::new().with_weekend(vec![Weekday::Fri, Weekday::Sat]);
+
+        // TEST
+        assert_eq!(Some(date(12, 26)), calendar.roll_forward(date(12, 24)));
+    }
+
+    #[test]
+    fn dates_do_not_roll_without_business_days() {
+        // SETUP
+        let week = vec![
+            Weekday::Mon,
+            Weekday::Tue,
+            Weekday::Wed,
+            Weekday::Thu,
+            Weekday::Fri,
+            Weekday::Sat,
+            Weekday::Sun,
+        ];
+        let calendar = BusinessCalendar::new().with_weekend(week);
+
+        // TEST
+        assert_eq!(None, calendar.roll_forward(date(12, 24)));
+        assert_eq!(None, calendar.add_business_days(date(12, 24), 1));
+    }
+
+    #[test]
+    fn business_days_are_added_skipping_weekends_and_holidays() {
+        // SETUP
+        let calendar = BusinessCalendar::new().with_holidays(vec![date(12, 27), date(12, 28)]);
+
+        // TEST
+        assert_eq!(Some(date(12, 30)), calendar.add_business_days(date(12, 23), 3));
+    }
+
+    #[test]
+    fn business_days_are_added_after_rolling_forward() {
+        // SETUP
+        let calendar = BusinessCalendar::new().with_holidays(vec![date(12, 27), date(12, 28)]);
+
+        // TEST
+        assert_eq!(Some(date(12, 31)), calendar.add_business_days(date(12, 26), 2));
+        assert_eq!(Some(date(12, 29)), calendar.add_business_days(date(12, 26), 0));
+    }
+}
+//endregion
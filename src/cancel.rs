@@ -0,0 +1,103 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Cancellation
+//! Processing a large input can take minutes. A `CancellationToken` lets whoever started it, e.g. a
+//! GUI or a service handler, stop it from another thread: `Bank::process_with_cancel` checks the
+//! token between records and returns as soon as it is cancelled, with the summary of the records
+//! processed so far. The record being processed when the token is cancelled is always finished, so
+//! the bank is left consistent.
+//!
+//! ```
+//! # use rust_payment_processor::bank::Bank;
+//! # use rust_payment_processor::cancel::CancellationToken;
+//! # use rust_payment_processor::transaction::{RecordOptions, TransactionRecord, TransactionType};
+//! # use std::sync::mpsc;
+//! # let (cancel_button, clicks) = mpsc::channel::<()>();
+//! # let wait_for_cancel_button = move || clicks.recv().unwrap();
+//! # let transactions = (1..=3).map(|tx| {
+//! #     RecordOptions::default().transaction(TransactionRecord::new(TransactionType::Deposit, 1, tx, Some("1"))).unwrap()
+//! # });
+//! # let mut sink = Vec::new();
+//! # let mut sink = |index, _: &_, _| sink.push(index);
+//! # let mut bank = Bank::new();
+//! let token = CancellationToken::new();
+//! let handle = token.clone();
+//! let canceller = std::thread::spawn(move || {
+//!     wait_for_cancel_button();
+//!     handle.cancel();
+//! });
+//! # cancel_button.send(()).unwrap();
+//! # canceller.join().unwrap();
+//! let summary = bank.process_with_cancel(transactions, &mut sink, &token);
+//! # assert_eq!(0, summary.processed);
+//! ```
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// `CancellationToken` is a flag shared between its clones, set once processing should stop.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    /// Ask processing to stop before the next record. Cancelling more than once has no further
+    /// effect.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_token_is_not_cancelled() {
+        // TEST
+        assert!(!CancellationToken::new().is_cancelled());
+    }
+
+    #[test]
+    fn cancelling_a_clone_cancels_every_clone() {
+        // SETUP
+        let token = CancellationToken::new();
+        let handle = token.clone();
+
+        // TEST
+        handle.cancel();
+        assert!(token.is_cancelled());
+        assert!(handle.is_cancelled());
+    }
+
+    #[test]
+    fn cancelling_twice_leaves_the_token_cancelled() {
+        // SETUP
+        let token = CancellationToken::new();
+
+        // TEST
+        token.cancel();
+        token.cancel();
+        assert!(token.is_cancelled());
+    }
+
+    #[test]
+    fn token_is_cancelled_from_another_thread() {
+        // SETUP
+        let token = CancellationToken::new();
+        let handle = token.clone();
+
+        // TEST
+        std::thread::spawn(move || handle.cancel()).join().unwrap();
+        assert!(token.is_cancelled());
+    }
+}
+//endregion
@@ -0,0 +1,104 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Character sets
+//! Input is read as UTF-8, but partner exports are not always UTF-8: spreadsheets save UTF-8 with a
+//! byte order mark, some systems export UTF-16LE, and older ones Windows-1252. `decode` wraps the
+//! input in a reader that transcodes it to UTF-8 before it reaches the CSV reader, so that these
+//! files are read like any other instead of failing at the header row.
+//!
+//! A byte order mark is always recognised and removed, and decides the encoding of the file. Files
+//! without one are read in the encoding given with `--encoding` or by the input profile, and as
+//! UTF-8 otherwise.
+use encoding_rs::Encoding;
+use encoding_rs_io::DecodeReaderBytesBuilder;
+use std::io;
+
+/// Look up an encoding by one of its WHATWG labels, e.g. `utf-16le` or `windows-1252`.
+pub fn parse_encoding(label: &str) -> Result<&'static Encoding, String> {
+    Encoding::for_label(label.trim().as_bytes()).ok_or_else(|| format!("unsupported encoding: {}", label))
+}
+
+/// Wrap `reader` so that it yields UTF-8, decoding from the encoding named by a byte order mark,
+/// or else from `encoding`. Without either, the input is passed through as UTF-8.
+pub fn decode<R: io::Read>(reader: R, encoding: Option<&'static Encoding>) -> impl io::Read {
+    DecodeReaderBytesBuilder::new().encoding(encoding).bom_override(true).build(reader)
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn decode_to_string(bytes: &[u8], encoding: Option<&'static Encoding>) -> io::Result<String> {
+        let mut text = String::new();
+        decode(bytes, encoding).read_to_string(&mut text)?;
+        Ok(text)
+    }
+
+    #[test]
+    fn utf8_is_passed_through() -> io::Result<()> {
+        // TEST
+        assert_eq!("Zürich", decode_to_string("Zürich".as_bytes(), None)?);
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn utf8_byte_order_mark_is_removed() -> io::Result<()> {
+        // TEST
+        assert_eq!("type", decode_to_string(b"\xef\xbb\xbftype", None)?);
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn utf16_is_decoded_by_its_byte_order_mark() -> io::Result<()> {
+        // SETUP
+        let utf16: Vec<u8> = [0xff, 0xfe]
+            .iter()
+            .copied()
+            .chain("type".encode_utf16().flat_map(u16::to_le_bytes))
+            .collect();
+
+        // TEST
+        assert_eq!("type", decode_to_string(&utf16, None)?);
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn input_without_a_byte_order_mark_is_decoded_from_the_given_encoding() -> io::Result<()> {
+        // SETUP
+        let windows_1252 = parse_encoding("windows-1252").expect("known encoding");
+
+        // TEST
+        assert_eq!("Zürich", decode_to_string(b"Z\xfcrich", Some(windows_1252))?);
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn byte_order_mark_overrides_the_given_encoding() -> io::Result<()> {
+        // SETUP
+        let windows_1252 = parse_encoding("windows-1252").expect("known encoding");
+
+        // TEST
+        assert_eq!("Zürich", decode_to_string("\u{feff}Zürich".as_bytes(), Some(windows_1252))?);
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn encodings_are_looked_up_by_label() {
+        // TEST
+        assert_eq!(encoding_rs::UTF_16LE, parse_encoding(" utf-16le ").unwrap());
+        assert_eq!(encoding_rs::WINDOWS_1252, parse_encoding("latin1").unwrap());
+        assert_eq!("unsupported encoding: klingon", parse_encoding("klingon").unwrap_err());
+    }
+}
+//endregion
@@ -0,0 +1,261 @@
+#![forbid(unsafe_code)] // for good measure
+//! # String client identifiers
+//! Clients known by a string rather than a number, such as a UUID or an alphanumeric customer
+//! code, are given a numeric `ClientId` by a `ClientInterner` the first time they are seen. The
+//! engine only ever sees the numeric IDs; the strings are put back when accounts are written out.
+//!
+//! With `--string-client-ids`, the `client` column of CSV input is read this way:
+//!
+//! ```
+//! # use rust_payment_processor::bank::Bank;
+//! # use rust_payment_processor::client::{self, ClientInterner};
+//! # use rust_payment_processor::transaction::RecordOptions;
+//! # use std::io;
+//! # fn main() -> io::Result<()> {
+//! # let input = "type, client, tx, amount\ndeposit, ACME-42, 1, 1.0\n";
+//! # let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(input.as_bytes());
+//! # let options = RecordOptions::default();
+//! # let mut bank = Bank::new();
+//! let mut clients = ClientInterner::new();
+//! bank.process_all(client::read_transactions(&mut reader, &mut clients, &options));
+//! client::write_accounts(&bank, &clients, io::stdout())?;
+//! # Ok(())
+//! # }
+//! ```
+use crate::account::ClientId;
+use crate::amount::Amount;
+use crate::bank::Bank;
+use crate::schema;
+use crate::transaction::{RecordOptions, Transaction};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io;
+
+/// `ClientInterner` assigns numeric IDs to string client identifiers, in the order they are first
+/// seen, starting at 0.
+#[derive(Debug, Default)]
+pub struct ClientInterner {
+    ids: HashMap<String, ClientId>,
+    names: Vec<String>,
+}
+
+impl ClientInterner {
+    pub fn new() -> ClientInterner {
+        ClientInterner::default()
+    }
+
+    /// Returns the ID of the client known by `name`, assigning the next one if it has none. Returns
+    /// `None` once every ID has been assigned.
+    pub fn intern(&mut self, name: &str) -> Option<ClientId> {
+        if let Some(client) = self.ids.get(name) {
+            return Some(*client);
+        }
+        let client = ClientId::try_from(self.names.len()).ok()?;
+        self.ids.insert(name.to_string(), client);
+        self.names.push(name.to_string());
+        Some(client)
+    }
+
+    /// Returns the string the given ID was assigned to, if any.
+    pub fn name(&self, client: ClientId) -> Option<&str> {
+        self.names.get(usize::try_from(client).ok()?).map(String::as_str)
+    }
+
+    /// The number of clients seen.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+/// As `schema::read_transactions`, reading the `client` column as a string and interning it. Input
+/// without a header row is expected in the usual column order.
+pub fn read_transactions<'a, R: io::Read>(
+    reader: &'a mut csv::Reader<R>,
+    clients: &'a mut ClientInterner,
+    options: &'a RecordOptions,
+) -> impl Iterator<Item = Transaction> + 'a {
+    let headers = if reader.has_headers() { reader.headers().ok().cloned() } else { None };
+    // without the column, every record fails to deserialize with a message naming it
+    let column = headers
+        .as_ref()
+        .map_or(Some(1), |headers| headers.iter().position(|header| header == "client"));
+    reader.records().filter_map(move |result| {
+        let parsed = result.map_err(|e| e.to_string()).and_then(|record| {
+            let record = match column {
+                Some(column) => intern_client(&record, column, clients)?,
+                None => record,
+            };
+            schema::parse_record(&record, headers.as_ref(), options)
+        });
+        match parsed {
+            Ok(transaction) => Some(transaction),
+            Err(e) => {
+                error!("Failed to parse transaction. Discarded with error: {}", e);
+                None
+            }
+        }
+    })
+}
+
+/// Replace the client field of a record with its interned ID.
+fn intern_client(record: &csv::StringRecord, column: usize, clients: &mut ClientInterner) -> Result<csv::StringRecord, String> {
+    let name = record.get(column).unwrap_or_default();
+    if name.is_empty() {
+        return Err(String::from("field `client` is empty"));
+    }
+    let client = clients
+        .intern(name)
+        .ok_or_else(|| String::from("no client IDs are left to assign"))?
+        .to_string();
+    Ok(record
+        .iter()
+        .enumerate()
+        .map(|(index, field)| if index == column { client.as_str() } else { field })
+        .collect())
+}
+
+/// `NamedAccount` is the layout of an account written with its string client identifier.
+#[derive(Serialize)]
+struct NamedAccount<'a> {
+    client: &'a str,
+    available: Amount,
+    held: Amount,
+    total: Amount,
+    locked: bool,
+}
+
+/// Write accounts in CSV format to the given writer, as `Bank::write_accounts` does, with each
+/// client's string identifier in place of its ID.
+pub fn write_accounts<W: io::Write>(bank: &Bank, clients: &ClientInterner, writer: W) -> io::Result<()> {
+    let mut wtr = csv::WriterBuilder::new().from_writer(writer);
+    for account in bank.accounts() {
+        let client = clients
+            .name(account.client)
+            .ok_or_else(|| io::Error::other(format!("client {} was not interned", account.client)))?;
+        wtr.serialize(NamedAccount {
+            client,
+            available: account.available,
+            held: account.held,
+            total: account.total,
+            locked: account.locked,
+        })
+        .map_err(io::Error::other)?;
+    }
+    wtr.flush()
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UUID: &str = "3f2a9c4e-0b1d-4c8e-9a6f-2d7e5b1c8a90";
+
+    /// Process CSV `input` with string client IDs.
+    fn process(input: &str, clients: &mut ClientInterner) -> Bank {
+        let mut reader = csv::Reader::from_reader(input.as_bytes());
+        let mut bank = Bank::new();
+        bank.process_all(read_transactions(&mut reader, clients, &RecordOptions::default()));
+        bank
+    }
+
+    #[test]
+    fn clients_are_interned_in_the_order_first_seen() {
+        // SETUP
+        let mut clients = ClientInterner::new();
+
+        // TEST
+        assert_eq!(Some(0), clients.intern(UUID));
+        assert_eq!(Some(1), clients.intern("CUST-0042"));
+        assert_eq!(Some(0), clients.intern(UUID));
+        assert_eq!(2, clients.len());
+    }
+
+    #[test]
+    fn names_are_looked_up_by_id() {
+        // SETUP
+        let mut clients = ClientInterner::new();
+        clients.intern("CUST-0042");
+
+        // TEST
+        assert_eq!(Some("CUST-0042"), clients.name(0));
+        assert_eq!(None, clients.name(1));
+    }
+
+    #[test]
+    fn interner_starts_empty() {
+        // TEST
+        assert!(ClientInterner::new().is_empty());
+    }
+
+    #[test]
+    fn client_column_is_read_as_a_string() {
+        // SETUP
+        let mut clients = ClientInterner::new();
+        let input = format!(
+            "type,client,tx,amount\ndeposit,{},1,2.0\ndeposit,CUST-0042,2,1.0\nwithdrawal,{},3,0.5\n",
+            UUID, UUID
+        );
+
+        // TEST
+        let bank = process(&input, &mut clients);
+        assert_eq!(2, clients.len());
+        assert_eq!("1.5", bank.account(0).expect("interned").total.to_string());
+        assert_eq!("1", bank.account(1).expect("interned").total.to_string());
+    }
+
+    #[test]
+    fn client_column_is_found_by_name() {
+        // SETUP
+        let mut clients = ClientInterner::new();
+
+        // TEST
+        let bank = process("tx,amount,type,client\n1,2.0,deposit,CUST-0042\n", &mut clients);
+        assert_eq!(Some(0), clients.intern("CUST-0042"));
+        assert!(bank.account(0).is_some());
+    }
+
+    #[test]
+    fn records_with_an_empty_client_are_discarded() {
+        // SETUP
+        let mut clients = ClientInterner::new();
+
+        // TEST
+        let bank = process("type,client,tx,amount\ndeposit,,1,1.0\n", &mut clients);
+        assert!(clients.is_empty());
+        assert_eq!(0, bank.accounts().count());
+    }
+
+    #[test]
+    fn accounts_are_written_with_their_string_client() -> io::Result<()> {
+        // SETUP
+        let mut clients = ClientInterner::new();
+        let bank = process(&format!("type,client,tx,amount\ndeposit,{},1,1.5\n", UUID), &mut clients);
+        let mut output = Vec::new();
+
+        // TEST
+        write_accounts(&bank, &clients, &mut output)?;
+        let output = String::from_utf8(output).expect("UTF-8");
+        assert_eq!(format!("client,available,held,total,locked\n{},1.5,0,1.5,false\n", UUID), output);
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn accounts_of_clients_not_interned_are_not_written() {
+        // SETUP
+        let mut clients = ClientInterner::new();
+        let bank = process("type,client,tx,amount\ndeposit,CUST-0042,1,1.0\n", &mut clients);
+
+        // TEST
+        let error = write_accounts(&bank, &ClientInterner::new(), Vec::new()).unwrap_err();
+        assert_eq!("client 0 was not interned", error.to_string());
+    }
+}
+//endregion
@@ -0,0 +1,249 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Columnar (Apache Arrow / Parquet) input and output
+//! Transactions can be ingested from, and account snapshots exported to, Arrow `RecordBatch`es so
+//! the engine can sit inside DataFusion or polars pipelines without serializing through CSV. With
+//! the `parquet` feature, the same conversions back reading and writing Parquet files. Unlike CSV,
+//! both preserve column types, so amounts are stored as `Decimal128(38, 4)` and timestamps as UTC
+//! microsecond timestamps.
+//!
+//! Input columns are cast to the expected types where possible, so e.g. a `tx` column stored as
+//! `Int64` or an `amount` column stored as text is accepted. Rows that can not be mapped onto a
+//! `Transaction` are logged and discarded, just like malformed CSV records.
+use crate::account::Account;
+use crate::amount::{self, Amount};
+use crate::transaction::{Transaction, TransactionType};
+use arrow_array::{
+    Array, ArrayRef, BooleanArray, Decimal128Array, RecordBatch, StringArray, TimestampMicrosecondArray, UInt32Array, UInt64Array,
+};
+use arrow_schema::{ArrowError, DataType, Field, Schema, TimeUnit};
+use chrono::{DateTime, Utc};
+#[cfg(feature = "parquet")]
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+#[cfg(feature = "parquet")]
+use parquet::arrow::ArrowWriter;
+#[cfg(feature = "parquet")]
+use parquet::errors::ParquetError;
+use rust_decimal::Decimal;
+#[cfg(feature = "parquet")]
+use std::fs::File;
+#[cfg(feature = "parquet")]
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Precision and scale of the `Decimal128` columns used for amounts.
+const AMOUNT_PRECISION: u8 = 38;
+const AMOUNT_SCALE: i8 = 4;
+
+fn amount_type() -> DataType {
+    DataType::Decimal128(AMOUNT_PRECISION, AMOUNT_SCALE)
+}
+
+fn timestamp_type() -> DataType {
+    DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into()))
+}
+
+/// The Arrow schema of an account snapshot.
+pub fn account_schema() -> Schema {
+    Schema::new(vec![
+        Field::new("client", DataType::UInt32, false),
+        Field::new("available", amount_type(), false),
+        Field::new("held", amount_type(), false),
+        Field::new("total", amount_type(), false),
+        Field::new("locked", DataType::Boolean, false),
+    ])
+}
+
+/// Build a `RecordBatch` containing one row per account.
+pub fn accounts_to_record_batch<'a, I: IntoIterator<Item = &'a Account>>(accounts: I) -> Result<RecordBatch, ArrowError> {
+    let mut client = Vec::new();
+    let mut available = Vec::new();
+    let mut held = Vec::new();
+    let mut total = Vec::new();
+    let mut locked = Vec::new();
+    for account in accounts {
+        client.push(account.client);
+        available.push(to_i128(account.available)?);
+        held.push(to_i128(account.held)?);
+        total.push(to_i128(account.total)?);
+        locked.push(account.locked);
+    }
+
+    let amount_column = |values: Vec<i128>| -> Result<ArrayRef, ArrowError> {
+        Ok(Arc::new(
+            Decimal128Array::from(values).with_precision_and_scale(AMOUNT_PRECISION, AMOUNT_SCALE)?,
+        ))
+    };
+    RecordBatch::try_new(
+        Arc::new(account_schema()),
+        vec![
+            Arc::new(UInt32Array::from(client)),
+            amount_column(available)?,
+            amount_column(held)?,
+            amount_column(total)?,
+            Arc::new(BooleanArray::from(locked)),
+        ],
+    )
+}
+
+/// Convert an amount into the unscaled integer of a `Decimal128(38, 4)` value.
+fn to_i128(value: Amount) -> Result<i128, ArrowError> {
+    let mut value: Decimal = amount::to_decimal(value);
+    value.rescale(AMOUNT_SCALE as u32);
+    if value.scale() != AMOUNT_SCALE as u32 {
+        return Err(ArrowError::InvalidArgumentError(format!(
+            "amount {} can not be represented with scale 4",
+            value
+        )));
+    }
+    Ok(value.mantissa())
+}
+
+/// Convert every row of the given `RecordBatch` into a `Transaction`. Each row yields either the
+/// transaction or a description of why it could not be mapped. Fails only if a required column is
+/// absent or can not be cast to the expected type.
+pub fn transactions_from_record_batch(batch: &RecordBatch) -> Result<Vec<Result<Transaction, String>>, ArrowError> {
+    let kind = required_column(batch, "type", &DataType::Utf8)?;
+    let client = required_column(batch, "client", &DataType::UInt32)?;
+    let tx = required_column(batch, "tx", &DataType::UInt64)?;
+    let amount = optional_column(batch, "amount", &amount_type())?;
+    let timestamp = optional_column(batch, "timestamp", &timestamp_type())?;
+    let currency = optional_column(batch, "currency", &DataType::Utf8)?;
+
+    let kind = downcast::<StringArray>(&kind);
+    let client = downcast::<UInt32Array>(&client);
+    let tx = downcast::<UInt64Array>(&tx);
+    let amount = amount.as_ref().map(downcast::<Decimal128Array>);
+    let timestamp = timestamp.as_ref().map(downcast::<TimestampMicrosecondArray>);
+    let currency = currency.as_ref().map(downcast::<StringArray>);
+
+    let rows = (0..batch.num_rows())
+        .map(|row| {
+            if kind.is_null(row) || client.is_null(row) || tx.is_null(row) {
+                return Err(String::from("`type`, `client` and `tx` are required"));
+            }
+            let amount = match amount.filter(|column| column.is_valid(row)) {
+                Some(column) => Some(
+                    amount::from_decimal(Decimal::from_i128_with_scale(column.value(row), AMOUNT_SCALE as u32))
+                        .ok_or("field `amount` is out of range")?,
+                ),
+                None => None,
+            };
+            let transaction = Transaction {
+                kind: TransactionType::from_str(kind.value(row))?,
+                client: client.value(row),
+                tx: tx.value(row),
+                amount,
+                under_dispute: false,
+                timestamp: timestamp
+                    .filter(|column| column.is_valid(row))
+                    .and_then(|column| DateTime::<Utc>::from_timestamp_micros(column.value(row))),
+                currency: currency
+                    .filter(|column| column.is_valid(row))
+                    .map(|column| column.value(row).to_string()),
+                signature: None,
+                correction: false,
+                provenance: None,
+                expires: None,
+            };
+            transaction.check_fields().map_err(|e| e.to_string())?;
+            Ok(transaction)
+        })
+        .collect();
+    Ok(rows)
+}
+
+fn required_column(batch: &RecordBatch, name: &str, data_type: &DataType) -> Result<ArrayRef, ArrowError> {
+    optional_column(batch, name, data_type)?.ok_or_else(|| ArrowError::SchemaError(format!("missing required column `{}`", name)))
+}
+
+/// Returns the named column cast to the given type. Values that can not be cast become null.
+fn optional_column(batch: &RecordBatch, name: &str, data_type: &DataType) -> Result<Option<ArrayRef>, ArrowError> {
+    match batch.column_by_name(name) {
+        Some(column) if column.data_type() == data_type => Ok(Some(column.clone())),
+        Some(column) => arrow_cast::cast(column, data_type).map(Some),
+        None => Ok(None),
+    }
+}
+
+fn downcast<T: 'static>(column: &ArrayRef) -> &T {
+    column.as_any().downcast_ref::<T>().expect("column was cast to the expected type")
+}
+
+/// Read every transaction in the given Parquet file. Rows that can not be mapped onto a
+/// `Transaction` are logged and discarded.
+#[cfg(feature = "parquet")]
+pub fn read_parquet(path: &Path) -> Result<Vec<Transaction>, ParquetError> {
+    let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(path)?)?.build()?;
+    let mut transactions = Vec::new();
+    let mut index = 0;
+    for batch in reader {
+        for row in transactions_from_record_batch(&batch?)? {
+            index += 1;
+            match row {
+                Ok(transaction) => transactions.push(transaction),
+                Err(e) => error!("Failed to map Parquet row {}. Discarded with error: {}", index, e),
+            }
+        }
+    }
+    Ok(transactions)
+}
+
+/// Write a record batch, such as an account snapshot, to the given Parquet file.
+#[cfg(feature = "parquet")]
+pub fn write_parquet(path: &Path, batch: &RecordBatch) -> Result<(), ParquetError> {
+    let mut writer = ArrowWriter::try_new(File::create(path)?, batch.schema(), None)?;
+    writer.write(batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::Int64Array;
+
+    #[test]
+    fn record_batch_with_castable_columns_maps_to_transactions() -> Result<(), ArrowError> {
+        // SETUP
+        let batch = RecordBatch::try_from_iter(vec![
+            ("type", Arc::new(StringArray::from(vec!["deposit", "dispute", "bogus"])) as ArrayRef),
+            ("client", Arc::new(Int64Array::from(vec![1, 1, 1])) as ArrayRef),
+            ("tx", Arc::new(Int64Array::from(vec![1, 1, 2])) as ArrayRef),
+            (
+                "amount",
+                Arc::new(StringArray::from(vec![Some("1.5"), None, Some("1")])) as ArrayRef,
+            ),
+        ])?;
+
+        // TEST
+        let rows = transactions_from_record_batch(&batch)?;
+        assert_eq!(3, rows.len());
+        let deposit = rows[0].as_ref().unwrap();
+        assert_eq!(TransactionType::Deposit, deposit.kind);
+        assert_eq!(amount::from_decimal(Decimal::new(15, 1)), deposit.amount);
+        assert_eq!(TransactionType::Dispute, rows[1].as_ref().unwrap().kind);
+        assert!(rows[2].is_err());
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn accounts_to_record_batch_uses_decimal_columns() -> Result<(), ArrowError> {
+        // SETUP
+        let mut account = Account::new(7);
+        account.deposit(&Amount::from(3u32)).unwrap();
+
+        // TEST
+        let batch = accounts_to_record_batch(vec![&account])?;
+        assert_eq!(1, batch.num_rows());
+        let total = downcast::<Decimal128Array>(batch.column_by_name("total").unwrap());
+        assert_eq!(30_000, total.value(0));
+
+        // TEARDOWN
+        Ok(())
+    }
+}
+//endregion
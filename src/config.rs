@@ -0,0 +1,574 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Bank configuration
+//! `BankBuilder` collects the options a `Bank` is created with, so that new options can be added
+//! without changing the signature of `Bank::new`. Every option defaults to the behaviour described
+//! in the crate documentation.
+//!
+//! ```
+//! # use rust_payment_processor::bank::Bank;
+//! # use rust_payment_processor::config::DuplicatePolicy;
+//! let bank = Bank::builder()
+//!     .decimal_places(2)
+//!     .duplicate_policy(DuplicatePolicy::IgnoreReplays)
+//!     .strict(true)
+//!     .build();
+//! ```
+use crate::amount::Amount;
+use crate::bank::Bank;
+#[cfg(feature = "currency")]
+use crate::currency::CurrencyExponents;
+use crate::handler::{Handlers, TransactionHandler};
+use crate::locale::Locale;
+#[cfg(feature = "signature")]
+use crate::signature::SigningKey;
+use crate::transaction::{Transaction, TransactionType};
+#[cfg(feature = "time")]
+use chrono::Duration;
+#[cfg(feature = "retention")]
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// The number of decimal places amounts are rounded to unless configured otherwise.
+pub const DEFAULT_DECIMAL_PLACES: u32 = 4;
+
+/// `DisputePolicy` decides whether a dispute may take more funds than are available.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DisputePolicy {
+    /// Disputes are always accepted, even if the disputed funds have since been withdrawn, leaving
+    /// `available` negative. This is the default.
+    AllowNegativeAvailable,
+    /// Disputes of more than the funds available are rejected with `InsufficientFunds`.
+    RequireAvailableFunds,
+}
+
+/// `DuplicatePolicy` decides how a deposit or withdrawal reusing an earlier transaction ID is
+/// handled.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DuplicatePolicy {
+    /// Every reused ID is rejected with `DuplicateTransactionId`. This is the default.
+    Reject,
+    /// An exact replay of an earlier transaction (same type, client, and amount) is accepted
+    /// without effect, so that at-least-once delivery is harmless. Any other reuse is rejected.
+    IgnoreReplays,
+    /// Every reuse is accepted without effect, and counted in `ProcessingSummary::skipped`.
+    Skip,
+    /// A reuse marked as a `correction` replaces the earlier transaction, adjusting the account by
+    /// the difference, provided it has the same type and client and is not under dispute. Any
+    /// other reuse is rejected.
+    LastWriteWins,
+}
+
+impl FromStr for DuplicatePolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<DuplicatePolicy, String> {
+        match value {
+            "reject" => Ok(DuplicatePolicy::Reject),
+            "ignore-replays" => Ok(DuplicatePolicy::IgnoreReplays),
+            "skip" => Ok(DuplicatePolicy::Skip),
+            "last-write-wins" => Ok(DuplicatePolicy::LastWriteWins),
+            _ => Err(format!(
+                "unsupported duplicate policy: {} (expected reject, ignore-replays, skip, or last-write-wins)",
+                value
+            )),
+        }
+    }
+}
+
+/// `RetentionPolicy` decides how long deposits and withdrawals are kept once processed, and so for
+/// how long they can be disputed and are recognised as duplicates. Transactions under dispute are
+/// never evicted.
+#[cfg(feature = "retention")]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RetentionPolicy {
+    /// Every transaction is kept for the lifetime of the bank. This is the default.
+    KeepAll,
+    /// Only the most recent `n` transactions are kept.
+    Count(usize),
+    /// Transactions are kept until they are older than this. Age is measured against the latest
+    /// timestamp seen in the input, and a transaction without a timestamp takes the latest one seen
+    /// before it, so input without any timestamps is kept in full.
+    #[cfg(feature = "time")]
+    Age(Duration),
+    /// Transactions are kept while the approximate memory of the bank's stores is at most this
+    /// many bytes; see `Bank::memory_usage`. Once only transactions under dispute are left to
+    /// evict, deposits and withdrawals are rejected with `MemoryLimitExceeded` instead of stored.
+    Memory(usize),
+}
+
+#[cfg(feature = "retention")]
+impl FromStr for RetentionPolicy {
+    type Err = String;
+
+    /// Parses a number of transactions, e.g. `1000000`, or a duration as for `Inactivity`, e.g.
+    /// `90d`.
+    fn from_str(value: &str) -> Result<RetentionPolicy, String> {
+        match value.parse() {
+            Ok(Inactivity::Records(count)) => Ok(RetentionPolicy::Count(count)),
+            #[cfg(feature = "time")]
+            Ok(Inactivity::Period(age)) => Ok(RetentionPolicy::Age(age)),
+            Err(_) => Err(format!(
+                "invalid retention: {} (expected a count, or a duration such as 90d)",
+                value
+            )),
+        }
+    }
+}
+
+/// `Inactivity` is how long an account must go without a transaction to be dormant.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Inactivity {
+    /// No transaction applied to the account in this many records processed since.
+    Records(usize),
+    /// No transaction applied to the account for this long, measured against the latest timestamp
+    /// seen in the input. An account whose transactions carry no timestamp is never dormant.
+    #[cfg(feature = "time")]
+    Period(Duration),
+}
+
+impl FromStr for Inactivity {
+    type Err = String;
+
+    /// Parses a number of records, e.g. `1000000`, or a duration in seconds, minutes, hours, or
+    /// days, e.g. `90d`.
+    fn from_str(value: &str) -> Result<Inactivity, String> {
+        if let Ok(records) = value.parse::<usize>() {
+            return Ok(Inactivity::Records(records));
+        }
+        #[cfg(feature = "time")]
+        if let Some(period) = parse_duration(value) {
+            return Ok(Inactivity::Period(period));
+        }
+        Err(format!(
+            "invalid inactivity: {} (expected a number of records, or a duration such as 90d)",
+            value
+        ))
+    }
+}
+
+/// Parse a duration in seconds, minutes, hours, or days, e.g. `90d`. Negative durations are
+/// rejected.
+#[cfg(feature = "time")]
+fn parse_duration(value: &str) -> Option<Duration> {
+    let split = value.len().checked_sub(1).filter(|&split| value.is_char_boundary(split))?;
+    let (number, unit) = value.split_at(split);
+    let number: i64 = number.parse().ok()?;
+    let duration = match unit {
+        "s" => Duration::try_seconds(number),
+        "m" => Duration::try_minutes(number),
+        "h" => Duration::try_hours(number),
+        "d" => Duration::try_days(number),
+        _ => None,
+    };
+    duration.filter(|duration| *duration >= Duration::zero())
+}
+
+/// `DormancyPolicy` decides when `Bank::sweep_dormant` flags an account as dormant, and what
+/// happens to it then.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DormancyPolicy {
+    pub after: Inactivity,
+    /// When set, this fee is assessed against every account flagged dormant, posted as a `Fee`.
+    pub fee: Option<Amount>,
+    /// When set, withdrawals from a dormant account are rejected with `AccountDormant` until it is
+    /// reactivated with `Bank::reactivate_account`.
+    pub block_withdrawals: bool,
+}
+
+impl DormancyPolicy {
+    pub fn new(after: Inactivity) -> DormancyPolicy {
+        DormancyPolicy {
+            after,
+            fee: None,
+            block_withdrawals: false,
+        }
+    }
+}
+
+/// `DuplicateFilter` sizes the Bloom filter remembering the IDs of transactions evicted by a
+/// `RetentionPolicy`, so that reusing one is still rejected as a duplicate. See `bloom`.
+#[cfg(feature = "retention")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct DuplicateFilter {
+    /// The number of evicted IDs the filter is sized for. Beyond it, false positives become more
+    /// likely.
+    pub capacity: usize,
+    /// The share of IDs never evicted that the filter wrongly reports as evicted, at capacity.
+    pub false_positive_rate: f64,
+    /// When set, evicted IDs are also written to this file, which is consulted to confirm a match
+    /// of the filter, so that no transaction is rejected because of a false positive.
+    pub spill: Option<PathBuf>,
+}
+
+#[cfg(feature = "retention")]
+impl Default for DuplicateFilter {
+    fn default() -> DuplicateFilter {
+        DuplicateFilter {
+            capacity: 1_000_000,
+            false_positive_rate: 1e-6,
+            spill: None,
+        }
+    }
+}
+
+/// `StorageBackend` enumerates where accounts and transactions are kept.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum StorageBackend {
+    /// Everything is kept in memory for the lifetime of the bank. This is the default.
+    InMemory,
+}
+
+/// `BankConfig` holds the options a `Bank` was built with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BankConfig {
+    /// The number of decimal places amounts are rounded to.
+    pub decimal_places: u32,
+    /// When set, amounts in a known currency are rounded to that currency's decimal places instead
+    /// of `decimal_places`; see `currency`.
+    #[cfg(feature = "currency")]
+    pub currency_exponents: Option<CurrencyExponents>,
+    pub dispute_policy: DisputePolicy,
+    pub duplicate_policy: DuplicatePolicy,
+    pub storage: StorageBackend,
+    #[cfg(feature = "retention")]
+    pub retention: RetentionPolicy,
+    #[cfg(feature = "retention")]
+    pub duplicate_filter: DuplicateFilter,
+    /// When set, amounts with more than `decimal_places` decimal places are rejected with
+    /// `InvalidTransaction` rather than rounded.
+    pub strict: bool,
+    /// When set, every record must carry a valid signature under this key, or it is rejected with
+    /// `BadSignature`.
+    #[cfg(feature = "signature")]
+    pub signing_key: Option<SigningKey>,
+    /// When set, the bank counts the transactions applied to each account; see
+    /// `Bank::write_accounts_extended`.
+    pub statistics: bool,
+    /// When set, the bank keeps an audit trail of each account; see `audit`.
+    pub audit: bool,
+    /// When set, every chargeback assesses this fee against the client, posted as a `Fee`.
+    pub chargeback_fee: Option<Amount>,
+    /// When set, the bank tracks the last transaction of each account, for `Bank::sweep_dormant`.
+    pub dormancy: Option<DormancyPolicy>,
+    /// The language the bank logs its processing summary in; see `locale`.
+    pub locale: Locale,
+}
+
+impl Default for BankConfig {
+    fn default() -> BankConfig {
+        BankConfig {
+            decimal_places: DEFAULT_DECIMAL_PLACES,
+            #[cfg(feature = "currency")]
+            currency_exponents: None,
+            dispute_policy: DisputePolicy::AllowNegativeAvailable,
+            duplicate_policy: DuplicatePolicy::Reject,
+            storage: StorageBackend::InMemory,
+            #[cfg(feature = "retention")]
+            retention: RetentionPolicy::KeepAll,
+            #[cfg(feature = "retention")]
+            duplicate_filter: DuplicateFilter::default(),
+            strict: false,
+            #[cfg(feature = "signature")]
+            signing_key: None,
+            statistics: false,
+            audit: false,
+            chargeback_fee: None,
+            dormancy: None,
+            locale: Locale::En,
+        }
+    }
+}
+
+impl BankConfig {
+    /// The number of decimal places the amount of the given transaction is rounded to: those of
+    /// its currency, if it names one with a known exponent, or else `decimal_places`.
+    #[cfg(feature = "currency")]
+    pub fn decimal_places_for(&self, transaction: &Transaction) -> u32 {
+        self.currency_exponents
+            .as_ref()
+            .zip(transaction.currency.as_deref())
+            .and_then(|(exponents, currency)| exponents.decimal_places(currency))
+            .unwrap_or(self.decimal_places)
+    }
+
+    /// The number of decimal places the amount of the given transaction is rounded to:
+    /// `decimal_places`, as currencies are not told apart without the `currency` feature.
+    #[cfg(not(feature = "currency"))]
+    pub fn decimal_places_for(&self, _: &Transaction) -> u32 {
+        self.decimal_places
+    }
+}
+
+/// `BankBuilder` creates a `Bank` with non-default options. Obtained from `Bank::builder`.
+#[derive(Clone, Debug, Default)]
+pub struct BankBuilder {
+    config: BankConfig,
+    handlers: Handlers,
+}
+
+impl BankBuilder {
+    pub fn new() -> BankBuilder {
+        BankBuilder::default()
+    }
+
+    /// Round amounts to the given number of decimal places, using "Bankers Rounding".
+    pub fn decimal_places(mut self, decimal_places: u32) -> BankBuilder {
+        self.config.decimal_places = decimal_places;
+        self
+    }
+
+    /// Round amounts in a known currency to that currency's decimal places, e.g. those of
+    /// `CurrencyExponents::iso4217`, rather than to `decimal_places`.
+    #[cfg(feature = "currency")]
+    pub fn currency_exponents(mut self, exponents: CurrencyExponents) -> BankBuilder {
+        self.config.currency_exponents = Some(exponents);
+        self
+    }
+
+    pub fn dispute_policy(mut self, policy: DisputePolicy) -> BankBuilder {
+        self.config.dispute_policy = policy;
+        self
+    }
+
+    pub fn duplicate_policy(mut self, policy: DuplicatePolicy) -> BankBuilder {
+        self.config.duplicate_policy = policy;
+        self
+    }
+
+    pub fn storage(mut self, storage: StorageBackend) -> BankBuilder {
+        self.config.storage = storage;
+        self
+    }
+
+    /// Evict settled transactions according to the given policy, bounding memory in long runs.
+    #[cfg(feature = "retention")]
+    pub fn retention(mut self, retention: RetentionPolicy) -> BankBuilder {
+        self.config.retention = retention;
+        self
+    }
+
+    /// Size the filter of evicted transaction IDs; only used with a `RetentionPolicy` other than
+    /// `KeepAll`.
+    #[cfg(feature = "retention")]
+    pub fn duplicate_filter(mut self, filter: DuplicateFilter) -> BankBuilder {
+        self.config.duplicate_filter = filter;
+        self
+    }
+
+    /// Reject amounts that would need rounding instead of rounding them.
+    pub fn strict(mut self, strict: bool) -> BankBuilder {
+        self.config.strict = strict;
+        self
+    }
+
+    /// Require every record to be signed with the given key; see `signature`.
+    #[cfg(feature = "signature")]
+    pub fn signing_key(mut self, key: SigningKey) -> BankBuilder {
+        self.config.signing_key = Some(key);
+        self
+    }
+
+    /// Keep per-client transaction counts, for `Bank::write_accounts_extended`.
+    pub fn statistics(mut self, statistics: bool) -> BankBuilder {
+        self.config.statistics = statistics;
+        self
+    }
+
+    /// Keep an audit trail of each account, for `Bank::audit_trail`.
+    pub fn audit(mut self, audit: bool) -> BankBuilder {
+        self.config.audit = audit;
+        self
+    }
+
+    /// Assess `fee` against the client of every chargeback, as acquirers pass on the fees charged
+    /// by card networks. See `bank::Fee`.
+    pub fn chargeback_fee(mut self, fee: Amount) -> BankBuilder {
+        self.config.chargeback_fee = Some(fee);
+        self
+    }
+
+    /// Track the last transaction of each account, so that `Bank::sweep_dormant` can flag those
+    /// without activity as dormant under `policy`.
+    pub fn dormancy(mut self, policy: DormancyPolicy) -> BankBuilder {
+        self.config.dormancy = Some(policy);
+        self
+    }
+
+    /// Log the processing summary in the given locale rather than in English.
+    pub fn locale(mut self, locale: Locale) -> BankBuilder {
+        self.config.locale = locale;
+        self
+    }
+
+    /// Apply transactions of the named type with `handler`, adding the type to the bank's `Handlers`
+    /// if it is not yet known. The handler of a built-in type can be replaced this way too. See
+    /// `handler`.
+    pub fn handler<H: TransactionHandler + 'static>(mut self, kind: &str, handler: H) -> Result<BankBuilder, String> {
+        let kind = TransactionType::from_str(kind).or_else(|_| TransactionType::custom(kind))?;
+        self.handlers.insert(kind, handler);
+        Ok(self)
+    }
+
+    pub fn build(self) -> Bank {
+        Bank::with_handlers(self.config, self.handlers)
+    }
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::BankingError;
+
+    #[test]
+    fn duplicate_policies_are_parsed_by_name() {
+        // TEST
+        assert_eq!(Ok(DuplicatePolicy::Reject), "reject".parse());
+        assert_eq!(Ok(DuplicatePolicy::IgnoreReplays), "ignore-replays".parse());
+        assert_eq!(Ok(DuplicatePolicy::Skip), "skip".parse());
+        assert_eq!(Ok(DuplicatePolicy::LastWriteWins), "last-write-wins".parse());
+    }
+
+    #[test]
+    fn unknown_duplicate_policies_are_rejected() {
+        // TEST
+        let error = "replace".parse::<DuplicatePolicy>().unwrap_err();
+        assert!(error.starts_with("unsupported duplicate policy: replace"), "{}", error);
+    }
+
+    #[test]
+    #[cfg(feature = "retention")]
+    fn retention_parses_counts() {
+        // TEST
+        assert_eq!(Ok(RetentionPolicy::Count(1000)), "1000".parse());
+    }
+
+    #[test]
+    #[cfg(all(feature = "retention", feature = "time"))]
+    fn retention_parses_durations() {
+        // TEST
+        assert_eq!(Ok(RetentionPolicy::Age(Duration::days(90))), "90d".parse());
+        assert_eq!(Ok(RetentionPolicy::Age(Duration::hours(12))), "12h".parse());
+    }
+
+    #[test]
+    #[cfg(feature = "retention")]
+    fn retention_rejects_unknown_units_and_negative_durations() {
+        // TEST
+        assert!("90y".parse::<RetentionPolicy>().is_err());
+        assert!("-1d".parse::<RetentionPolicy>().is_err());
+        assert!("".parse::<RetentionPolicy>().is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "time")]
+    fn inactivity_parses_records_and_periods() {
+        // TEST
+        assert_eq!(Ok(Inactivity::Records(500)), "500".parse());
+        assert_eq!(Ok(Inactivity::Period(Duration::seconds(30))), "30s".parse());
+        assert_eq!(Ok(Inactivity::Period(Duration::minutes(5))), "5m".parse());
+    }
+
+    #[test]
+    fn inactivity_rejects_what_is_neither() {
+        // TEST
+        let error = "d".parse::<Inactivity>().unwrap_err();
+        assert!(error.starts_with("invalid inactivity: d"), "{}", error);
+        assert!("1.5d".parse::<Inactivity>().is_err());
+        assert!("9é".parse::<Inactivity>().is_err());
+    }
+
+    #[test]
+    fn dormancy_policy_neither_charges_nor_blocks_by_default() {
+        // TEST
+        let policy = DormancyPolicy::new(Inactivity::Records(10));
+        assert_eq!((None, false), (policy.fee, policy.block_withdrawals));
+    }
+
+    #[test]
+    fn builder_defaults_to_the_default_config() {
+        // TEST
+        assert_eq!(&BankConfig::default(), BankBuilder::new().build().config());
+        assert_eq!(DEFAULT_DECIMAL_PLACES, BankConfig::default().decimal_places);
+    }
+
+    #[test]
+    #[cfg(feature = "retention")]
+    fn builder_sets_the_options_of_the_config() {
+        // SETUP
+        let dormancy = DormancyPolicy::new(Inactivity::Records(10));
+
+        // TEST
+        let bank = Bank::builder()
+            .decimal_places(2)
+            .dispute_policy(DisputePolicy::RequireAvailableFunds)
+            .duplicate_policy(DuplicatePolicy::Skip)
+            .retention(RetentionPolicy::Count(5))
+            .strict(true)
+            .statistics(true)
+            .audit(true)
+            .chargeback_fee(Amount::from(15u32))
+            .dormancy(dormancy.clone())
+            .locale(Locale::Fr)
+            .build();
+        let config = bank.config();
+        assert_eq!(2, config.decimal_places);
+        assert_eq!(DisputePolicy::RequireAvailableFunds, config.dispute_policy);
+        assert_eq!(DuplicatePolicy::Skip, config.duplicate_policy);
+        assert_eq!(RetentionPolicy::Count(5), config.retention);
+        assert!(config.strict && config.statistics && config.audit);
+        assert_eq!(Some(Amount::from(15u32)), config.chargeback_fee);
+        assert_eq!(Some(dormancy), config.dormancy);
+        assert_eq!(Locale::Fr, config.locale);
+    }
+
+    #[test]
+    #[cfg(feature = "currency")]
+    fn amounts_are_rounded_to_the_decimal_places_of_their_currency() {
+        // SETUP
+        let config = BankConfig {
+            decimal_places: 3,
+            currency_exponents: Some(CurrencyExponents::iso4217()),
+            ..BankConfig::default()
+        };
+        let mut transaction = Transaction::make(TransactionType::Deposit, 1, 1, 5, false);
+
+        // TEST
+        assert_eq!(3, config.decimal_places_for(&transaction));
+        transaction.currency = Some(String::from("JPY"));
+        assert_eq!(0, config.decimal_places_for(&transaction));
+        transaction.currency = Some(String::from("XYZ"));
+        assert_eq!(3, config.decimal_places_for(&transaction));
+    }
+
+    struct Ignore;
+
+    impl TransactionHandler for Ignore {
+        fn process(&self, _: &mut Bank, _: Transaction) -> Result<(), BankingError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn builder_registers_handlers_of_custom_and_built_in_types() -> Result<(), String> {
+        // SETUP
+        let mut bank = Bank::builder().handler("bonus", Ignore)?.handler("deposit", Ignore)?.build();
+        let bonus = Transaction::make(TransactionType::custom("bonus")?, 1, 1, 5, false);
+        let deposit = Transaction::make(TransactionType::Deposit, 1, 2, 5, false);
+
+        // TEST
+        assert_eq!(2, bank.process_all(vec![bonus, deposit]).applied);
+        assert!(bank.account(1).is_none());
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn builder_rejects_handlers_without_a_type_name() {
+        // TEST
+        assert!(Bank::builder().handler("", Ignore).is_err());
+    }
+}
+//endregion
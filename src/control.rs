@@ -0,0 +1,250 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Control totals
+//! Partners often send a control file alongside the transactions, declaring the net movement the
+//! file should make: the change to the total funds of some clients, of the whole file, or both.
+//!
+//! ```text
+//! client,net
+//! 1,100.00
+//! 2,-25.5
+//! ,74.5
+//! ```
+//!
+//! A row without a client declares the net movement of all accounts together. `ControlTotals::verify`
+//! compares them with what a run actually did, listing every `Discrepancy`.
+use crate::account::{Account, ClientId};
+use crate::amount::{parse_amount, Amount, AmountFormat};
+use crate::bank::Bank;
+use rust_decimal::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+
+/// `ControlRow` is the layout of a row of a control file.
+#[derive(Deserialize)]
+struct ControlRow {
+    client: Option<ClientId>,
+    net: String,
+}
+
+/// `ControlTotals` are the net movements declared by a control file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ControlTotals {
+    pub clients: BTreeMap<ClientId, Amount>,
+    pub overall: Option<Amount>,
+}
+
+/// `Discrepancy` is a declared net movement that differs from the actual one. `client` is `None`
+/// for the net movement of all accounts together.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Discrepancy {
+    pub client: Option<ClientId>,
+    pub expected: Amount,
+    pub actual: Amount,
+}
+
+impl ControlTotals {
+    /// Read a control file in CSV format with a `client,net` header row. Declaring a client, or the
+    /// overall movement, more than once is an error.
+    pub fn read<R: io::Read>(reader: R) -> Result<ControlTotals, String> {
+        let mut totals = ControlTotals::default();
+        let mut rdr = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(reader);
+        for row in rdr.deserialize::<ControlRow>() {
+            let row = row.map_err(|e| e.to_string())?;
+            let net = parse_amount(&row.net, &AmountFormat::new()).map_err(|e| format!("invalid net movement \"{}\": {}", row.net, e))?;
+            let duplicate = match row.client {
+                Some(client) => totals.clients.insert(client, net).is_some(),
+                None => totals.overall.replace(net).is_some(),
+            };
+            if duplicate {
+                let declared = row
+                    .client
+                    .map_or_else(|| String::from("all accounts"), |client| format!("client {}", client));
+                return Err(format!("the net movement of {} is declared more than once", declared));
+            }
+        }
+        Ok(totals)
+    }
+
+    /// Compare the declared net movements with the change from the accounts `before` a run to the
+    /// accounts of `after` it, returning the discrepancies ordered by client, followed by that of
+    /// all accounts together.
+    pub fn verify<'a, I: IntoIterator<Item = &'a Account>>(&self, before: I, after: &Bank) -> Vec<Discrepancy> {
+        let before: HashMap<ClientId, Amount> = before.into_iter().map(|account| (account.client, account.total)).collect();
+        let net = |client: ClientId| {
+            let total = after.account(client).map_or_else(Amount::zero, |account| account.total);
+            total - before.get(&client).copied().unwrap_or_else(Amount::zero)
+        };
+        let mut discrepancies: Vec<Discrepancy> = self
+            .clients
+            .iter()
+            .map(|(&client, &expected)| Discrepancy {
+                client: Some(client),
+                expected,
+                actual: net(client),
+            })
+            .filter(|discrepancy| discrepancy.expected != discrepancy.actual)
+            .collect();
+        if let Some(expected) = self.overall {
+            let total = |accounts: &mut dyn Iterator<Item = Amount>| accounts.fold(Amount::zero(), |sum, amount| sum + amount);
+            let actual = total(&mut after.accounts().map(|account| account.total)) - total(&mut before.values().copied());
+            if actual != expected {
+                discrepancies.push(Discrepancy {
+                    client: None,
+                    expected,
+                    actual,
+                });
+            }
+        }
+        discrepancies
+    }
+}
+
+/// Write discrepancies in CSV format to the given writer.
+pub fn write_discrepancies<W: io::Write>(discrepancies: &[Discrepancy], writer: W) -> Result<(), csv::Error> {
+    let mut wtr = csv::WriterBuilder::new().from_writer(writer);
+    for discrepancy in discrepancies {
+        wtr.serialize(discrepancy)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testkit::Scenario;
+
+    /// The accounts before, and the bank after, a run depositing 5 and 3 to clients 1 and 2 and
+    /// withdrawing 1 from client 1, which had 10 before.
+    fn run() -> (Vec<Account>, Bank) {
+        let before = Scenario::new().deposit(1, 10).run().accounts().cloned().collect();
+        let after = Scenario::new().deposit(1, 10).deposit(1, 5).deposit(2, 3).withdraw(1, 1).run();
+        (before, after)
+    }
+
+    fn actual(discrepancies: &[Discrepancy]) -> Vec<(Option<ClientId>, Amount)> {
+        discrepancies
+            .iter()
+            .map(|discrepancy| (discrepancy.client, discrepancy.actual))
+            .collect()
+    }
+
+    #[test]
+    fn control_file_declares_clients_and_the_overall_movement() -> Result<(), String> {
+        // TEST
+        let control = ControlTotals::read("client,net\n1,100.00\n2,-25.5\n,74.5\n".as_bytes())?;
+        let declared: Vec<(ClientId, String)> = control.clients.iter().map(|(&client, net)| (client, net.to_string())).collect();
+        assert_eq!(vec![(1, String::from("100")), (2, String::from("-25.5"))], declared);
+        assert_eq!(Some(String::from("74.5")), control.overall.map(|net| net.to_string()));
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn client_declared_more_than_once_is_an_error() {
+        // TEST
+        let error = ControlTotals::read("client,net\n1,1\n1,2\n".as_bytes()).unwrap_err();
+        assert_eq!("the net movement of client 1 is declared more than once", error);
+    }
+
+    #[test]
+    fn overall_movement_declared_more_than_once_is_an_error() {
+        // TEST
+        let error = ControlTotals::read("client,net\n,1\n,2\n".as_bytes()).unwrap_err();
+        assert_eq!("the net movement of all accounts is declared more than once", error);
+    }
+
+    #[test]
+    fn net_movement_that_can_not_be_parsed_is_an_error() {
+        // TEST
+        let error = ControlTotals::read("client,net\n1,lots\n".as_bytes()).unwrap_err();
+        assert!(error.starts_with("invalid net movement \"lots\""), "{}", error);
+    }
+
+    #[test]
+    fn matching_movements_have_no_discrepancies() -> Result<(), String> {
+        // SETUP
+        let (before, after) = run();
+        let control = ControlTotals::read("client,net\n1,4\n2,3\n,7\n".as_bytes())?;
+
+        // TEST
+        assert!(control.verify(&before, &after).is_empty());
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn client_movements_are_verified() -> Result<(), String> {
+        // SETUP
+        let (before, after) = run();
+        let control = ControlTotals::read("client,net\n2,3\n1,5\n".as_bytes())?;
+
+        // TEST
+        let discrepancies = control.verify(&before, &after);
+        assert_eq!(vec![(Some(1), Amount::from(4u32))], actual(&discrepancies));
+        assert_eq!(Amount::from(5u32), discrepancies[0].expected);
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn clients_without_an_account_have_not_moved() -> Result<(), String> {
+        // SETUP
+        let (before, after) = run();
+        let control = ControlTotals::read("client,net\n3,1\n".as_bytes())?;
+
+        // TEST
+        assert_eq!(vec![(Some(3), Amount::zero())], actual(&control.verify(&before, &after)));
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn overall_movement_is_verified_after_the_clients() -> Result<(), String> {
+        // SETUP
+        let (before, after) = run();
+        let control = ControlTotals::read("client,net\n,8\n2,1\n".as_bytes())?;
+
+        // TEST
+        let discrepancies = control.verify(&before, &after);
+        assert_eq!(
+            vec![(Some(2), Amount::from(3u32)), (None, Amount::from(7u32))],
+            actual(&discrepancies)
+        );
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn discrepancies_are_written_as_csv() -> Result<(), csv::Error> {
+        // SETUP
+        let discrepancies = [
+            Discrepancy {
+                client: Some(1),
+                expected: Amount::from(5u32),
+                actual: Amount::from(4u32),
+            },
+            Discrepancy {
+                client: None,
+                expected: Amount::from(8u32),
+                actual: Amount::from(7u32),
+            },
+        ];
+        let mut output = Vec::new();
+
+        // TEST
+        write_discrepancies(&discrepancies, &mut output)?;
+        assert_eq!("client,expected,actual\n1,5,4\n,8,7\n", String::from_utf8(output).unwrap());
+
+        // TEARDOWN
+        Ok(())
+    }
+}
+//endregion
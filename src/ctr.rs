@@ -0,0 +1,283 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Currency transaction reports
+//! Regulators require a report of every client whose deposits on a single day add up to more than
+//! a threshold, such as 10,000, along with the deposits making up the total. `DailyDeposits` is a
+//! sink that adds up the deposits applied to each client per day, and `DailyDeposits::over` lists
+//! the days a client went over a threshold. It is what the `report --ctr-threshold` command prints.
+//!
+//! Days are UTC calendar days of the input's timestamps. As with dispute aging, a deposit without a
+//! timestamp falls on the day of the latest timestamp seen before it, and deposits before any
+//! timestamp was seen, e.g. all those of input without timestamps, are added up without a date.
+use crate::account::ClientId;
+use crate::amount::Amount;
+use crate::analytics::{metric_writer, MetricRow};
+use crate::errors::BankingError;
+use crate::locale::Locale;
+use crate::sink::ResultSink;
+use crate::transaction::{Transaction, TransactionType, TxId};
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::prelude::*;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io;
+
+/// `ContributingDeposit` is a deposit counted towards a client's daily total.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct ContributingDeposit {
+    pub tx: TxId,
+    pub amount: Amount,
+}
+
+/// `ReportableDay` is a day on which a client's deposits added up to more than the threshold.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ReportableDay {
+    /// The day, or none for deposits made before any timestamp was seen.
+    pub date: Option<NaiveDate>,
+    pub client: ClientId,
+    pub total: Amount,
+    /// The deposits of the day, in the order they were applied.
+    pub deposits: Vec<ContributingDeposit>,
+}
+
+/// `DailyDeposits` is a sink that collects the deposits applied to each client per day.
+#[derive(Clone, Debug, Default)]
+pub struct DailyDeposits {
+    days: BTreeMap<(Option<NaiveDate>, ClientId), Vec<ContributingDeposit>>,
+    latest: Option<DateTime<Utc>>,
+}
+
+impl DailyDeposits {
+    pub fn new() -> DailyDeposits {
+        DailyDeposits::default()
+    }
+
+    /// Returns the days on which a client's deposits added up to more than `threshold`, ordered by
+    /// day and then by client.
+    pub fn over(&self, threshold: Amount) -> Vec<ReportableDay> {
+        self.days
+            .iter()
+            .filter_map(|(&(date, client), deposits)| {
+                let total = deposits.iter().fold(Amount::zero(), |total, deposit| total + deposit.amount);
+                (total > threshold).then(|| ReportableDay {
+                    date,
+                    client,
+                    total,
+                    deposits: deposits.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+impl ResultSink for DailyDeposits {
+    fn record(&mut self, _index: usize, transaction: &Transaction, result: Result<(), BankingError>) {
+        self.latest = self.latest.max(transaction.timestamp);
+        if result.is_err() || transaction.kind != TransactionType::Deposit {
+            return;
+        }
+        if let Some(amount) = transaction.amount {
+            let date = transaction.timestamp.or(self.latest).map(|timestamp| timestamp.date_naive());
+            self.days.entry((date, transaction.client)).or_default().push(ContributingDeposit {
+                tx: transaction.tx,
+                amount,
+            });
+        }
+    }
+}
+
+/// Write the given days as CSV rows of a report: a `ctr_total` row per day keyed by
+/// `<date>/<client>`, followed by a `ctr_deposit` row per contributing deposit keyed by
+/// `<date>/<client>/<tx>`. The date is left out for deposits without one.
+pub fn write_rows<W: io::Write>(days: &[ReportableDay], wtr: &mut csv::Writer<W>) -> Result<(), csv::Error> {
+    for day in days {
+        let key = match day.date {
+            Some(date) => format!("{}/{}", date, day.client),
+            None => day.client.to_string(),
+        };
+        wtr.serialize(MetricRow {
+            metric: "ctr_total",
+            key: &key,
+            value: day.total.to_string(),
+        })?;
+        for deposit in &day.deposits {
+            wtr.serialize(MetricRow {
+                metric: "ctr_deposit",
+                key: &format!("{}/{}", key, deposit.tx),
+                value: deposit.amount.to_string(),
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// As `write_rows`, writing a complete CSV report of the days with its header row in the given
+/// locale.
+pub fn write_csv<W: io::Write>(days: &[ReportableDay], writer: W, locale: Locale) -> Result<(), csv::Error> {
+    let mut wtr = metric_writer(writer, locale)?;
+    write_rows(days, &mut wtr)?;
+    wtr.flush()?;
+    Ok(())
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank::Bank;
+    use crate::testkit::Scenario;
+    use chrono::TimeZone;
+
+    /// Noon on the given day of June 2021.
+    fn june(day: u32) -> Option<DateTime<Utc>> {
+        Some(Utc.with_ymd_and_hms(2021, 6, day, 12, 0, 0).unwrap())
+    }
+
+    /// The daily deposits of processing `scenario`, its transactions taking the given timestamps in
+    /// order.
+    fn daily_deposits(scenario: Scenario, timestamps: &[Option<DateTime<Utc>>]) -> DailyDeposits {
+        let mut transactions = scenario.transactions().to_vec();
+        for (transaction, timestamp) in transactions.iter_mut().zip(timestamps) {
+            transaction.timestamp = *timestamp;
+        }
+        let mut deposits = DailyDeposits::new();
+        Bank::new().process_all_with(transactions, &mut deposits);
+        deposits
+    }
+
+    fn totals(days: &[ReportableDay]) -> Vec<(Option<NaiveDate>, ClientId, Amount)> {
+        days.iter().map(|day| (day.date, day.client, day.total)).collect()
+    }
+
+    #[test]
+    fn clients_over_the_threshold_in_a_day_are_reported() {
+        // SETUP
+        let deposits = daily_deposits(
+            Scenario::new().deposit(1, 6000).deposit(2, 9000).deposit(1, 5000),
+            &[june(1), june(1), june(1)],
+        );
+
+        // TEST
+        let over = deposits.over(Amount::from(10000u32));
+        assert_eq!(
+            vec![(NaiveDate::from_ymd_opt(2021, 6, 1), 1, Amount::from(11000u32))],
+            totals(&over)
+        );
+    }
+
+    #[test]
+    fn totals_at_the_threshold_are_not_reported() {
+        // SETUP
+        let deposits = daily_deposits(Scenario::new().deposit(1, 6000).deposit(1, 4000), &[june(1), june(1)]);
+
+        // TEST
+        assert!(deposits.over(Amount::from(10000u32)).is_empty());
+    }
+
+    #[test]
+    fn deposits_are_added_up_per_day() {
+        // SETUP
+        let deposits = daily_deposits(Scenario::new().deposit(1, 6000).deposit(1, 6000), &[june(1), june(2)]);
+
+        // TEST
+        assert!(deposits.over(Amount::from(10000u32)).is_empty());
+    }
+
+    #[test]
+    fn reportable_days_are_ordered_by_day_and_then_client() {
+        // SETUP
+        let scenario = Scenario::new().deposit(2, 20).deposit(1, 20).deposit(3, 20);
+
+        // TEST
+        let over = daily_deposits(scenario, &[june(2), june(2), june(1)]).over(Amount::from(10u32));
+        let order: Vec<(Option<NaiveDate>, ClientId)> = over.iter().map(|day| (day.date, day.client)).collect();
+        let date = |day| NaiveDate::from_ymd_opt(2021, 6, day);
+        assert_eq!(vec![(date(1), 3), (date(2), 1), (date(2), 2)], order);
+    }
+
+    #[test]
+    fn deposits_are_listed_in_the_order_applied() {
+        // SETUP
+        let deposits = daily_deposits(
+            Scenario::new().deposit(1, 6000).deposit(1, 5000).deposit(1, 1),
+            &[june(1), june(1), june(1)],
+        );
+
+        // TEST
+        let over = deposits.over(Amount::from(10000u32));
+        assert_eq!(vec![1, 2, 3], over[0].deposits.iter().map(|deposit| deposit.tx).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn withdrawals_and_rejected_deposits_are_not_counted() {
+        // SETUP
+        let scenario = Scenario::new().deposit(1, 6000).withdraw(1, 5000).deposit(1, 6000).deposit(1, 5000);
+        let mut transactions = scenario.transactions().to_vec();
+        transactions[3].tx = 1;
+        let mut deposits = DailyDeposits::new();
+
+        // TEST
+        Bank::new().process_all_with(transactions, &mut deposits);
+        assert_eq!(Amount::from(12000u32), deposits.over(Amount::from(10000u32))[0].total);
+    }
+
+    #[test]
+    fn deposit_without_a_timestamp_falls_on_the_day_of_the_latest_one() {
+        // SETUP
+        let scenario = Scenario::new().deposit(1, 6000).withdraw(1, 1).deposit(1, 5000);
+
+        // TEST
+        let over = daily_deposits(scenario, &[june(1), june(2), None]).over(Amount::from(4000u32));
+        assert_eq!(
+            vec![
+                (NaiveDate::from_ymd_opt(2021, 6, 1), 1, Amount::from(6000u32)),
+                (NaiveDate::from_ymd_opt(2021, 6, 2), 1, Amount::from(5000u32))
+            ],
+            totals(&over)
+        );
+    }
+
+    #[test]
+    fn deposits_before_any_timestamp_are_added_up_without_a_date() {
+        // SETUP
+        let deposits = daily_deposits(Scenario::new().deposit(1, 6000).deposit(1, 5000), &[]);
+
+        // TEST
+        assert_eq!(
+            vec![(None, 1, Amount::from(11000u32))],
+            totals(&deposits.over(Amount::from(10000u32)))
+        );
+    }
+
+    #[test]
+    fn days_are_written_with_their_deposits() -> Result<(), csv::Error> {
+        // SETUP
+        let deposits = daily_deposits(Scenario::new().deposit(1, 6000).deposit(1, 5000), &[june(1), june(1)]);
+        let mut output = Vec::new();
+
+        // TEST
+        write_csv(&deposits.over(Amount::from(10000u32)), &mut output, Locale::En)?;
+        assert_eq!(
+            "metric,key,value\nctr_total,2021-06-01/1,11000\nctr_deposit,2021-06-01/1/1,6000\nctr_deposit,2021-06-01/1/2,5000\n",
+            String::from_utf8(output).unwrap()
+        );
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn days_without_a_date_are_keyed_by_client() -> Result<(), csv::Error> {
+        // SETUP
+        let deposits = daily_deposits(Scenario::new().deposit(1, 6000), &[]);
+        let mut wtr = csv::WriterBuilder::new().has_headers(false).from_writer(Vec::new());
+
+        // TEST
+        write_rows(&deposits.over(Amount::zero()), &mut wtr)?;
+        let output = String::from_utf8(wtr.into_inner().unwrap()).unwrap();
+        assert_eq!("ctr_total,1,6000\nctr_deposit,1/1,6000\n", output);
+
+        // TEARDOWN
+        Ok(())
+    }
+}
+//endregion
@@ -0,0 +1,223 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Currency exponents
+//! Currencies differ in how many decimal places their minor unit has: none for the Japanese yen,
+//! two for the euro, three for the Bahraini dinar. A bank built with
+//! `BankBuilder::currency_exponents` rounds the amount of each transaction naming a currency to
+//! that currency's places, looked up in `CurrencyExponents`, rather than to
+//! `BankConfig::decimal_places`. Transactions without a currency, or with one that is not known,
+//! are still rounded to `decimal_places`.
+//!
+//! `CurrencyExponents::iso4217` knows the exponents of the active ISO 4217 currencies. Overrides
+//! take precedence over it, e.g. for a partner settling in whole units, or a currency the table
+//! does not know yet.
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// The ISO 4217 currencies whose minor unit does not have two decimal places.
+const NOT_TWO: &[(&str, u32)] = &[
+    ("BHD", 3),
+    ("BIF", 0),
+    ("CLF", 4),
+    ("CLP", 0),
+    ("DJF", 0),
+    ("GNF", 0),
+    ("IQD", 3),
+    ("ISK", 0),
+    ("JOD", 3),
+    ("JPY", 0),
+    ("KMF", 0),
+    ("KRW", 0),
+    ("KWD", 3),
+    ("LYD", 3),
+    ("OMR", 3),
+    ("PYG", 0),
+    ("RWF", 0),
+    ("TND", 3),
+    ("UGX", 0),
+    ("UYI", 0),
+    ("UYW", 4),
+    ("VND", 0),
+    ("VUV", 0),
+    ("XAF", 0),
+    ("XOF", 0),
+];
+
+/// The active ISO 4217 currencies whose minor unit has two decimal places.
+const TWO: &[&str] = &[
+    "AED", "AFN", "ALL", "AMD", "ANG", "AOA", "ARS", "AUD", "AWG", "AZN", "BAM", "BBD", "BDT", "BGN", "BMD", "BND", "BOB", "BRL", "BSD",
+    "BTN", "BWP", "BYN", "BZD", "CAD", "CDF", "CHF", "CNY", "COP", "CRC", "CUP", "CVE", "CZK", "DKK", "DOP", "DZD", "EGP", "ERN", "ETB",
+    "EUR", "FJD", "FKP", "GBP", "GEL", "GHS", "GIP", "GMD", "GTQ", "GYD", "HKD", "HNL", "HTG", "HUF", "IDR", "ILS", "INR", "IRR", "JMD",
+    "KES", "KGS", "KHR", "KPW", "KYD", "KZT", "LAK", "LBP", "LKR", "LRD", "LSL", "MAD", "MDL", "MGA", "MKD", "MMK", "MNT", "MOP", "MRU",
+    "MUR", "MVR", "MWK", "MXN", "MYR", "MZN", "NAD", "NGN", "NIO", "NOK", "NPR", "NZD", "PAB", "PEN", "PGK", "PHP", "PKR", "PLN", "QAR",
+    "RON", "RSD", "RUB", "SAR", "SBD", "SCR", "SDG", "SEK", "SGD", "SHP", "SLE", "SOS", "SRD", "SSP", "STN", "SVC", "SYP", "SZL", "THB",
+    "TJS", "TMT", "TOP", "TRY", "TTD", "TWD", "TZS", "UAH", "USD", "UYU", "UZS", "VES", "WST", "XCD", "XCG", "YER", "ZAR", "ZMW", "ZWG",
+];
+
+/// `CurrencyExponent` overrides the number of decimal places of a currency, written as
+/// `CODE=PLACES`, e.g. `JPY=0`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CurrencyExponent {
+    pub currency: String,
+    pub decimal_places: u32,
+}
+
+impl FromStr for CurrencyExponent {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<CurrencyExponent, String> {
+        let (currency, places) = value
+            .split_once('=')
+            .ok_or_else(|| format!("invalid currency exponent: {} (expected CODE=PLACES, e.g. JPY=0)", value))?;
+        let decimal_places = places
+            .trim()
+            .parse()
+            .map_err(|e| format!("invalid decimal places \"{}\": {}", places, e))?;
+        Ok(CurrencyExponent {
+            currency: currency.trim().to_ascii_uppercase(),
+            decimal_places,
+        })
+    }
+}
+
+/// `CurrencyExponents` is the number of decimal places of each known currency.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CurrencyExponents {
+    iso4217: bool,
+    overrides: HashMap<String, u32>,
+}
+
+impl CurrencyExponents {
+    /// The exponents of the active ISO 4217 currencies.
+    pub fn iso4217() -> CurrencyExponents {
+        CurrencyExponents {
+            iso4217: true,
+            overrides: HashMap::new(),
+        }
+    }
+
+    /// Use the given exponent for its currency instead of the one in the table, if any.
+    pub fn with_override(mut self, exponent: CurrencyExponent) -> CurrencyExponents {
+        self.overrides.insert(exponent.currency, exponent.decimal_places);
+        self
+    }
+
+    /// The number of decimal places of the currency with the given code, if it is known. Codes are
+    /// matched regardless of case.
+    pub fn decimal_places(&self, currency: &str) -> Option<u32> {
+        let currency = currency.trim().to_ascii_uppercase();
+        if let Some(&places) = self.overrides.get(&currency) {
+            return Some(places);
+        }
+        if !self.iso4217 {
+            return None;
+        }
+        match NOT_TWO.iter().find(|(code, _)| *code == currency) {
+            Some(&(_, places)) => Some(places),
+            None => TWO.contains(&currency.as_str()).then_some(2),
+        }
+    }
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank::Bank;
+    use crate::transaction::{Transaction, TransactionType};
+
+    fn deposit(amount: &str, currency: Option<&str>) -> Transaction {
+        let mut deposit = Transaction::make(TransactionType::Deposit, 1, 1, 1, false);
+        deposit.amount = Some(crate::amount::parse_amount(amount, &Default::default()).unwrap());
+        deposit.currency = currency.map(String::from);
+        deposit
+    }
+
+    /// The available funds of client 1 after depositing `amount` in `currency` to a bank rounding to
+    /// the places of `exponents`.
+    fn deposited(exponents: CurrencyExponents, amount: &str, currency: Option<&str>) -> String {
+        let mut bank = Bank::builder().currency_exponents(exponents).build();
+        bank.process_all(vec![deposit(amount, currency)]);
+        bank.account(1).unwrap().available.to_string()
+    }
+
+    #[test]
+    fn iso4217_knows_currencies_of_every_exponent() {
+        // SETUP
+        let exponents = CurrencyExponents::iso4217();
+
+        // TEST
+        assert_eq!(Some(0), exponents.decimal_places("JPY"));
+        assert_eq!(Some(2), exponents.decimal_places("EUR"));
+        assert_eq!(Some(3), exponents.decimal_places("BHD"));
+        assert_eq!(None, exponents.decimal_places("XYZ"));
+    }
+
+    #[test]
+    fn currencies_are_matched_regardless_of_case() {
+        // TEST
+        assert_eq!(Some(3), CurrencyExponents::iso4217().decimal_places(" bhd "));
+    }
+
+    #[test]
+    fn overrides_take_precedence_over_the_table() -> Result<(), String> {
+        // SETUP
+        let exponents = CurrencyExponents::iso4217()
+            .with_override("usd=3".parse()?)
+            .with_override("XYZ=1".parse()?);
+
+        // TEST
+        assert_eq!(Some(3), exponents.decimal_places("USD"));
+        assert_eq!(Some(1), exponents.decimal_places("XYZ"));
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn without_the_table_only_overrides_are_known() -> Result<(), String> {
+        // SETUP
+        let exponents = CurrencyExponents::default().with_override("JPY=1".parse()?);
+
+        // TEST
+        assert_eq!(Some(1), exponents.decimal_places("JPY"));
+        assert_eq!(None, exponents.decimal_places("EUR"));
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn exponents_are_parsed_as_code_and_places() {
+        // TEST
+        assert_eq!(
+            Ok(CurrencyExponent {
+                currency: String::from("JPY"),
+                decimal_places: 0
+            }),
+            " jpy = 0".parse()
+        );
+    }
+
+    #[test]
+    fn exponents_without_places_are_rejected() {
+        // TEST
+        let error = "JPY".parse::<CurrencyExponent>().unwrap_err();
+        assert!(error.starts_with("invalid currency exponent: JPY"), "{}", error);
+        assert!("JPY=-1".parse::<CurrencyExponent>().is_err());
+    }
+
+    #[test]
+    fn amounts_are_rounded_to_the_places_of_their_currency() {
+        // TEST
+        assert_eq!("101", deposited(CurrencyExponents::iso4217(), "100.6", Some("JPY")));
+        assert_eq!("1.235", deposited(CurrencyExponents::iso4217(), "1.23456", Some("BHD")));
+    }
+
+    #[test]
+    fn amounts_in_unknown_or_no_currency_are_rounded_to_the_bank_places() {
+        // TEST
+        assert_eq!("1.2346", deposited(CurrencyExponents::iso4217(), "1.23456", Some("XYZ")));
+        assert_eq!("1.2346", deposited(CurrencyExponents::iso4217(), "1.23456", None));
+    }
+}
+//endregion
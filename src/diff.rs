@@ -0,0 +1,179 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Account deltas
+//! For previewing what an input file would do before committing to it, `diff` compares the
+//! accounts of a bank before and after processing, and lists the change to each account that
+//! changed as an `AccountDelta`:
+//!
+//! ```text
+//! client,available,held,total,locked
+//! 1,-10,10,0,false
+//! 7,25.5,0,25.5,false
+//! ```
+//!
+//! The starting point is usually a snapshot of a previous run, read back with `read_accounts`.
+use crate::account::{Account, ClientId};
+use crate::amount::Amount;
+use crate::bank::Bank;
+use rust_decimal::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io;
+
+/// `AccountDelta` is the change to an account's balances. `locked` is whether the account is
+/// locked afterwards.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct AccountDelta {
+    pub client: ClientId,
+    pub available: Amount,
+    pub held: Amount,
+    pub total: Amount,
+    pub locked: bool,
+}
+
+/// Read accounts written in CSV format by `Bank::write_accounts`, e.g. a snapshot of a previous run.
+pub fn read_accounts<R: io::Read>(reader: R) -> Result<Vec<Account>, csv::Error> {
+    csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(reader)
+        .deserialize()
+        .collect()
+}
+
+/// The changes to the accounts of `after` since `before`, ordered by client. Accounts that did not
+/// change are left out, and accounts opened since are compared with an empty account.
+pub fn diff<'a, I: IntoIterator<Item = &'a Account>>(before: I, after: &Bank) -> Vec<AccountDelta> {
+    let before: HashMap<ClientId, &Account> = before.into_iter().map(|account| (account.client, account)).collect();
+    let mut deltas: Vec<AccountDelta> = after
+        .accounts()
+        .filter_map(|account| {
+            let (available, held, total, locked) = match before.get(&account.client) {
+                Some(old) => (old.available, old.held, old.total, old.locked),
+                None => (Amount::zero(), Amount::zero(), Amount::zero(), false),
+            };
+            let delta = AccountDelta {
+                client: account.client,
+                available: account.available - available,
+                held: account.held - held,
+                total: account.total - total,
+                locked: account.locked,
+            };
+            let changed = !delta.available.is_zero() || !delta.held.is_zero() || !delta.total.is_zero() || locked != account.locked;
+            changed.then_some(delta)
+        })
+        .collect();
+    deltas.sort_by_key(|delta| delta.client);
+    deltas
+}
+
+/// Write account deltas in CSV format to the given writer.
+pub fn write_deltas<W: io::Write>(deltas: &[AccountDelta], writer: W) -> Result<(), csv::Error> {
+    let mut wtr = csv::WriterBuilder::new().from_writer(writer);
+    for delta in deltas {
+        wtr.serialize(delta)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testkit::Scenario;
+
+    /// The deltas of processing the transactions of `scenario` after the first `before` of them.
+    fn deltas_of(scenario: Scenario, before: usize) -> Vec<AccountDelta> {
+        let (earlier, later) = scenario.transactions().split_at(before);
+        let mut bank = Bank::new();
+        bank.process_all(earlier.to_vec());
+        let before: Vec<Account> = bank.accounts().cloned().collect();
+        bank.process_all(later.to_vec());
+        diff(&before, &bank)
+    }
+
+    #[test]
+    fn accounts_are_read_back_as_written() {
+        // SETUP
+        let bank = Scenario::new().deposit(1, 10).deposit(2, 5).dispute(2, 2).run();
+        let mut snapshot = Vec::new();
+        bank.write_accounts(&mut snapshot).unwrap();
+
+        // TEST
+        let mut accounts = read_accounts(&snapshot[..]).unwrap();
+        accounts.sort_by_key(|account| account.client);
+        let balances: Vec<(ClientId, Amount, Amount)> = accounts
+            .iter()
+            .map(|account| (account.client, account.available, account.held))
+            .collect();
+        assert_eq!(
+            vec![(1, Amount::from(10u32), Amount::zero()), (2, Amount::zero(), Amount::from(5u32))],
+            balances
+        );
+    }
+
+    #[test]
+    fn changed_accounts_have_a_delta() {
+        // TEST
+        let deltas = deltas_of(Scenario::new().deposit(1, 10).withdraw(1, 4), 1);
+        assert_eq!(
+            vec![AccountDelta {
+                client: 1,
+                available: -Amount::from(4u32),
+                held: Amount::zero(),
+                total: -Amount::from(4u32),
+                locked: false
+            }],
+            deltas
+        );
+    }
+
+    #[test]
+    fn unchanged_accounts_are_left_out() {
+        // TEST
+        assert!(deltas_of(Scenario::new().deposit(1, 10).withdraw(1, 20), 1).is_empty());
+    }
+
+    #[test]
+    fn new_accounts_are_compared_with_an_empty_account() {
+        // TEST
+        let deltas = deltas_of(Scenario::new().deposit(3, 7), 0);
+        assert_eq!(
+            (3, Amount::from(7u32), Amount::from(7u32)),
+            (deltas[0].client, deltas[0].available, deltas[0].total)
+        );
+    }
+
+    #[test]
+    fn locking_an_account_is_a_change() {
+        // SETUP
+        let mut bank = Scenario::new().deposit(1, 10).run();
+        let before: Vec<Account> = bank.accounts().cloned().collect();
+
+        // TEST
+        bank.lock_account(1, None).unwrap();
+        let deltas = diff(&before, &bank);
+        assert_eq!((1, Amount::zero(), true), (deltas[0].client, deltas[0].total, deltas[0].locked));
+    }
+
+    #[test]
+    fn deltas_are_ordered_by_client() {
+        // TEST
+        let deltas = deltas_of(Scenario::new().deposit(3, 1).deposit(1, 1).deposit(2, 1), 0);
+        assert_eq!(vec![1, 2, 3], deltas.iter().map(|delta| delta.client).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn deltas_are_written_as_csv() {
+        // SETUP
+        let deltas = deltas_of(Scenario::new().deposit(1, 10).withdraw(1, 4).deposit(3, 7), 1);
+        let mut output = Vec::new();
+
+        // TEST
+        write_deltas(&deltas, &mut output).unwrap();
+        assert_eq!(
+            "client,available,held,total,locked\n1,-4,0,-4,false\n3,7,0,7,false\n",
+            String::from_utf8(output).unwrap()
+        );
+    }
+}
+//endregion
@@ -0,0 +1,239 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Encryption at rest
+//! Input files and account snapshots may be encrypted with AES-256-GCM, so that transaction data
+//! never sits on a batch host in the clear. Files are encrypted in chunks using the STREAM
+//! construction, so that large inputs can be decrypted as they are read rather than all at once,
+//! and so that a file that has been truncated, reordered, or altered fails to decrypt.
+//!
+//! An encrypted file is laid out as:
+//!
+//! 1. the 8 byte magic `MAGIC`
+//! 2. a random 7 byte nonce prefix
+//! 3. chunks of `CHUNK_SIZE` bytes of plaintext, each followed by its 16 byte tag. The last chunk is
+//!    always shorter than `CHUNK_SIZE`, and may be empty.
+//!
+//! Keys are 32 bytes, given hex-encoded. Use the `crypt` binary to generate keys and to encrypt or
+//! decrypt files by hand.
+use crate::hex::{from_hex, to_hex};
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::stream::{DecryptorBE32, EncryptorBE32};
+use aes_gcm::aead::{KeyInit, OsRng};
+use aes_gcm::Aes256Gcm;
+use std::convert::TryInto;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// Identifies a file encrypted by this module, and the version of its layout.
+pub const MAGIC: &[u8; 8] = b"RPPAES1\n";
+/// The number of bytes of plaintext in every chunk but the last.
+pub const CHUNK_SIZE: usize = 64 * 1024;
+const NONCE_SIZE: usize = 7;
+const TAG_SIZE: usize = 16;
+
+/// `EncryptionKey` is a 256 bit AES key. It is never printed, not even by `Debug`.
+#[derive(Clone)]
+pub struct EncryptionKey([u8; 32]);
+
+impl EncryptionKey {
+    /// Generate a random key.
+    pub fn generate() -> EncryptionKey {
+        EncryptionKey(Aes256Gcm::generate_key(OsRng).into())
+    }
+
+    /// Parse a hex-encoded key.
+    pub fn from_hex(text: &str) -> Result<EncryptionKey, String> {
+        from_hex(text.trim())
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(EncryptionKey)
+            .ok_or_else(|| String::from("encryption keys must be 64 hex digits"))
+    }
+
+    /// Read a hex-encoded key from a file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<EncryptionKey, String> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|e| format!("Failed to read encryption key {}: {}", path.display(), e))?;
+        EncryptionKey::from_hex(&text)
+    }
+
+    /// Read a hex-encoded key from the named environment variable.
+    pub fn from_env(name: &str) -> Result<EncryptionKey, String> {
+        let text = std::env::var(name).map_err(|e| format!("environment variable {}: {}", name, e))?;
+        EncryptionKey::from_hex(&text)
+    }
+
+    /// The key, hex-encoded, for storing it.
+    pub fn to_hex(&self) -> String {
+        to_hex(&self.0)
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new(&self.0.into())
+    }
+}
+
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "EncryptionKey(..)")
+    }
+}
+
+fn decryption_failed() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "failed to decrypt: the key is wrong, or the file has been altered or truncated",
+    )
+}
+
+/// Read until `buffer` is full or the reader is exhausted, returning the number of bytes read.
+fn read_full<R: Read>(reader: &mut R, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// Encrypt everything read from `reader` to `writer`.
+pub fn encrypt<R: Read, W: Write>(key: &EncryptionKey, mut reader: R, mut writer: W) -> io::Result<()> {
+    let mut nonce = [0u8; NONCE_SIZE];
+    OsRng.fill_bytes(&mut nonce);
+    writer.write_all(MAGIC)?;
+    writer.write_all(&nonce)?;
+    let mut encryptor = EncryptorBE32::from_aead(key.cipher(), &nonce.into());
+    let mut chunk = vec![0u8; CHUNK_SIZE];
+    loop {
+        let read = read_full(&mut reader, &mut chunk)?;
+        if read < CHUNK_SIZE {
+            let ciphertext = encryptor
+                .encrypt_last(&chunk[..read])
+                .map_err(|_| io::Error::other("failed to encrypt"))?;
+            writer.write_all(&ciphertext)?;
+            return writer.flush();
+        }
+        let ciphertext = encryptor
+            .encrypt_next(&chunk[..])
+            .map_err(|_| io::Error::other("failed to encrypt"))?;
+        writer.write_all(&ciphertext)?;
+    }
+}
+
+/// `DecryptingReader` decrypts a file written by `encrypt` as it is read. Reads fail with
+/// `InvalidData` as soon as a chunk fails to decrypt, so no altered plaintext is ever returned.
+pub struct DecryptingReader<R> {
+    inner: R,
+    /// `None` once the last chunk has been decrypted.
+    decryptor: Option<DecryptorBE32<Aes256Gcm>>,
+    plaintext: Vec<u8>,
+    position: usize,
+}
+
+impl<R: Read> DecryptingReader<R> {
+    /// Read the header of an encrypted file, failing if it was not written by `encrypt`.
+    pub fn new(key: &EncryptionKey, mut inner: R) -> io::Result<DecryptingReader<R>> {
+        let mut header = [0u8; MAGIC.len() + NONCE_SIZE];
+        if read_full(&mut inner, &mut header)? < header.len() || &header[..MAGIC.len()] != MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not an encrypted file"));
+        }
+        let nonce: [u8; NONCE_SIZE] = header[MAGIC.len()..].try_into().expect("header holds a nonce");
+        Ok(DecryptingReader {
+            inner,
+            decryptor: Some(DecryptorBE32::from_aead(key.cipher(), &nonce.into())),
+            plaintext: Vec::new(),
+            position: 0,
+        })
+    }
+
+    /// Decrypt the next chunk into `plaintext`. Returns `false` once there are no more chunks.
+    fn next_chunk(&mut self) -> io::Result<bool> {
+        if self.decryptor.is_none() {
+            return Ok(false);
+        }
+        let mut chunk = vec![0u8; CHUNK_SIZE + TAG_SIZE];
+        let read = read_full(&mut self.inner, &mut chunk)?;
+        self.plaintext = if read == chunk.len() {
+            let decryptor = self.decryptor.as_mut().expect("checked above");
+            decryptor.decrypt_next(&chunk[..]).map_err(|_| decryption_failed())?
+        } else {
+            let decryptor = self.decryptor.take().expect("checked above");
+            decryptor.decrypt_last(&chunk[..read]).map_err(|_| decryption_failed())?
+        };
+        self.position = 0;
+        Ok(true)
+    }
+}
+
+impl<R: Read> Read for DecryptingReader<R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.plaintext.len() {
+            if !self.next_chunk()? {
+                return Ok(0);
+            }
+        }
+        let read = buffer.len().min(self.plaintext.len() - self.position);
+        buffer[..read].copy_from_slice(&self.plaintext[self.position..self.position + read]);
+        self.position += read;
+        Ok(read)
+    }
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(plaintext: &[u8]) -> io::Result<Vec<u8>> {
+        let key = EncryptionKey::generate();
+        let mut ciphertext = Vec::new();
+        encrypt(&key, plaintext, &mut ciphertext)?;
+        let mut decrypted = Vec::new();
+        DecryptingReader::new(&key, &ciphertext[..])?.read_to_end(&mut decrypted)?;
+        Ok(decrypted)
+    }
+
+    #[test]
+    fn files_of_any_length_round_trip() -> io::Result<()> {
+        for length in [0, 1, CHUNK_SIZE - 1, CHUNK_SIZE, 2 * CHUNK_SIZE + 7] {
+            let plaintext: Vec<u8> = (0..length).map(|i| i as u8).collect();
+            assert_eq!(plaintext, round_trip(&plaintext)?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn altered_truncated_and_foreign_files_fail_to_decrypt() -> io::Result<()> {
+        // SETUP
+        let key = EncryptionKey::generate();
+        let plaintext = vec![b'x'; CHUNK_SIZE + 10];
+        let mut ciphertext = Vec::new();
+        encrypt(&key, &plaintext[..], &mut ciphertext)?;
+        let decrypt = |key: &EncryptionKey, ciphertext: &[u8]| {
+            DecryptingReader::new(key, ciphertext).and_then(|mut reader| reader.read_to_end(&mut Vec::new()))
+        };
+
+        // TEST
+        let mut altered = ciphertext.clone();
+        altered[MAGIC.len() + NONCE_SIZE + 3] ^= 1;
+        assert!(decrypt(&key, &altered).is_err());
+        let truncated = &ciphertext[..MAGIC.len() + NONCE_SIZE + CHUNK_SIZE + TAG_SIZE];
+        assert!(decrypt(&key, truncated).is_err());
+        assert!(decrypt(&EncryptionKey::generate(), &ciphertext).is_err());
+        assert!(decrypt(&key, &plaintext).is_err());
+        assert!(decrypt(&key, &ciphertext).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn keys_round_trip_through_hex() {
+        let key = EncryptionKey::generate();
+        assert_eq!(key.0, EncryptionKey::from_hex(&key.to_hex()).unwrap().0);
+        assert!(EncryptionKey::from_hex("abcd").is_err());
+    }
+}
+//endregion
@@ -1,4 +1,9 @@
-#[derive(Debug, PartialEq)]
+use crate::locale::{self, Locale, Localize, Message};
+use serde::Serialize;
+use std::fmt;
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
 pub enum BankingError {
     /// Returned if a transaction fails validation upon entering the processing function
     InvalidTransaction,
@@ -25,4 +30,153 @@ pub enum BankingError {
     DuplicateDisputeRequest,
     /// Returned when any transaction is attempted on a locked account.
     AccountLocked,
+    /// Returned when a withdrawal is attempted on an account frozen by `Bank::freeze_account`.
+    AccountFrozen,
+    /// Returned when `Bank::unlock_account` is asked to unlock an account that is not locked.
+    AccountNotLocked,
+    /// Returned when a withdrawal is attempted on a dormant account whose withdrawals are blocked
+    /// until it is reactivated; see `DormancyPolicy`.
+    AccountDormant,
+    /// Returned when `Bank::reactivate_account` is asked to reactivate an account that is not
+    /// dormant.
+    AccountNotDormant,
+    /// Returned when a dispute, resolve, or chargeback refers to a promotional credit.
+    PromotionalCredit,
+    /// Returned when an administrative operation expected an account to be at a version it is no
+    /// longer at, because it has been changed since it was read.
+    VersionMismatch { expected: u64, actual: u64 },
+    /// Returned when a bank verifies signatures and a record carries no signature, or one that does
+    /// not match its contents under the signing key.
+    BadSignature,
+    /// Returned when a client with funds held by an open dispute is to be forgotten.
+    OpenDispute,
+    /// Returned when a client with a prepared transaction, or funds held by a batch reservation,
+    /// is to be forgotten.
+    PendingTransaction,
+    /// Returned when a deposit or withdrawal would be stored beyond the memory limit of a
+    /// `RetentionPolicy::Memory`, as every transaction left to evict is under dispute.
+    MemoryLimitExceeded,
+    /// Returned when a transaction would take a balance beyond the range of `Amount`. The account
+    /// is left unchanged.
+    AmountOverflow,
+}
+
+impl fmt::Display for BankingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_localized(f, Locale::En)
+    }
+}
+
+impl Localize for BankingError {
+    fn write_localized(&self, f: &mut dyn fmt::Write, locale: Locale) -> fmt::Result {
+        let message = match self {
+            BankingError::InvalidTransaction => Message::InvalidTransaction,
+            BankingError::NoSuchAccount => Message::NoSuchAccount,
+            BankingError::NoSuchTransaction => Message::NoSuchTransaction,
+            BankingError::InsufficientFunds => Message::InsufficientFunds,
+            BankingError::ClientMismatch => Message::ClientMismatch,
+            BankingError::UndisputedTransaction => Message::UndisputedTransaction,
+            BankingError::DuplicateTransactionId => Message::DuplicateTransactionId,
+            BankingError::DuplicateDisputeRequest => Message::DuplicateDisputeRequest,
+            BankingError::AccountLocked => Message::AccountLocked,
+            BankingError::AccountFrozen => Message::AccountFrozen,
+            BankingError::AccountNotLocked => Message::AccountNotLocked,
+            BankingError::AccountDormant => Message::AccountDormant,
+            BankingError::AccountNotDormant => Message::AccountNotDormant,
+            BankingError::PromotionalCredit => Message::PromotionalCredit,
+            BankingError::VersionMismatch { expected, actual } => {
+                return locale::write_in(f, locale, Message::VersionMismatch, &[expected, actual]);
+            }
+            BankingError::BadSignature => Message::BadSignature,
+            BankingError::OpenDispute => Message::OpenDispute,
+            BankingError::PendingTransaction => Message::PendingTransaction,
+            BankingError::MemoryLimitExceeded => Message::MemoryLimitExceeded,
+            BankingError::AmountOverflow => Message::AmountOverflow,
+        };
+        f.write_str(message.text(locale))
+    }
+}
+
+/// `RecordError` describes a transaction record whose fields are inconsistent with its type.
+#[derive(Debug, PartialEq)]
+pub enum RecordError {
+    /// A deposit or withdrawal was received without an amount.
+    MissingAmount,
+    /// A deposit or withdrawal was received with an amount of zero or less.
+    NonPositiveAmount,
+    /// A dispute, resolve, or chargeback was received with a populated amount.
+    AmountNotAllowed,
+    /// A record was received without a type, which its amount columns do not imply either.
+    MissingType,
+    /// A record was received with both a debit and a credit.
+    DebitAndCredit,
+    /// A record was received with an amount its source's `AmountFormat` does not accept.
+    InvalidAmount(String, AmountParseError),
+    /// A record was received with a timestamp that can not be read, as described.
+    InvalidTimestamp(String),
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_localized(f, Locale::En)
+    }
+}
+
+impl Localize for RecordError {
+    fn write_localized(&self, f: &mut dyn fmt::Write, locale: Locale) -> fmt::Result {
+        let message = match self {
+            RecordError::MissingAmount => Message::MissingAmount,
+            RecordError::NonPositiveAmount => Message::NonPositiveAmount,
+            RecordError::AmountNotAllowed => Message::AmountNotAllowed,
+            RecordError::MissingType => Message::MissingType,
+            RecordError::DebitAndCredit => Message::DebitAndCredit,
+            RecordError::InvalidAmount(text, e) => {
+                return locale::write_in(f, locale, Message::InvalidAmount, &[text, &e.localized(locale)]);
+            }
+            RecordError::InvalidTimestamp(description) => return f.write_str(description),
+        };
+        f.write_str(message.text(locale))
+    }
+}
+
+/// `AmountParseError` describes why a textual amount was rejected by the strict amount parser.
+#[derive(Debug, PartialEq)]
+pub enum AmountParseError {
+    /// The amount field contained only a sign.
+    Empty,
+    /// The amount was written in scientific notation, e.g. `1e3`.
+    ScientificNotation,
+    /// The amount contained a thousands separator, e.g. `1,000.00`.
+    ThousandsSeparator,
+    /// The amount contained a character that can never appear in an amount.
+    InvalidCharacter(char),
+    /// There were no digits on one side of the decimal separator, e.g. `.5` or `5.`.
+    MissingDigits,
+    /// The amount had more digits after the decimal separator than the configured maximum.
+    TooManyDecimalPlaces(u32),
+    /// The amount is too large to be represented.
+    OutOfRange,
+}
+
+impl fmt::Display for AmountParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_localized(f, Locale::En)
+    }
+}
+
+impl Localize for AmountParseError {
+    fn write_localized(&self, f: &mut dyn fmt::Write, locale: Locale) -> fmt::Result {
+        let message = match self {
+            AmountParseError::Empty => Message::NoDigits,
+            AmountParseError::ScientificNotation => Message::ScientificNotation,
+            AmountParseError::ThousandsSeparator => Message::ThousandsSeparator,
+            AmountParseError::InvalidCharacter(c) => return locale::write_in(f, locale, Message::InvalidCharacter, &[c]),
+            AmountParseError::MissingDigits => Message::MissingDigits,
+            AmountParseError::TooManyDecimalPlaces(max) => {
+                return locale::write_in(f, locale, Message::TooManyDecimalPlaces, &[max]);
+            }
+            AmountParseError::OutOfRange => Message::OutOfRange,
+        };
+        f.write_str(message.text(locale))
+    }
 }
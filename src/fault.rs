@@ -0,0 +1,320 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Fault injection
+//! The journal, snapshots, and followers are only as reliable as their handling of failing I/O,
+//! which is hard to provoke on a healthy disk. `FaultyWriter` and `FaultyReader` wrap the writer or
+//! reader the engine is given and inject faults into chosen operations, following a `FaultPlan`:
+//! an error, a delay, or a partial write, as left behind by a crash in the middle of a write.
+//! Operations are counted from 0, one per call to `write` or `read`, so that a test fails the same
+//! way on every run:
+//!
+//! ```
+//! # use rust_payment_processor::bank::Bank;
+//! # use rust_payment_processor::fault::{FaultPlan, FaultyWriter};
+//! # use rust_payment_processor::journal::{self, JournalWriter};
+//! # use rust_payment_processor::transaction::{RecordOptions, TransactionRecord, TransactionType};
+//! # let transactions = (1..=3).map(|tx| {
+//! #     RecordOptions::default().transaction(TransactionRecord::new(TransactionType::Deposit, 1, tx, Some("1"))).unwrap()
+//! # });
+//! # let mut bank = Bank::new();
+//! # let mut bytes = Vec::new();
+//! // the third journal entry is torn after 5 bytes, as if the process had crashed
+//! let plan = FaultPlan::new().partial_at(2, 5);
+//! let mut journal = JournalWriter::new(FaultyWriter::new(&mut bytes, plan));
+//! bank.process_all_with(transactions, &mut journal);
+//! # drop(journal);
+//! # assert!(journal::verify(&bytes[..]).is_err());
+//! ```
+//!
+//! `JournalWriter` flushes each entry with a single write, so its operations are its entries.
+//! Enabled with the `fault-injection` feature, for tests only.
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::thread;
+use std::time::Duration;
+
+/// `Fault` is what happens to an operation instead of, or before, carrying it out.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Fault {
+    /// Fail with an error of this kind, without reading or writing anything.
+    Error(io::ErrorKind),
+    /// Sleep before carrying out the operation, as on a slow or contended disk.
+    Delay(Duration),
+    /// Write at most this many bytes, then fail, leaving a torn write behind. Reads are shortened
+    /// to this many bytes instead, without failing.
+    Partial(usize),
+}
+
+/// `FaultPlan` is the fault, if any, of every operation of a wrapped reader or writer.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FaultPlan {
+    faults: BTreeMap<u64, Fault>,
+}
+
+impl FaultPlan {
+    pub fn new() -> FaultPlan {
+        FaultPlan::default()
+    }
+
+    /// Inject `fault` into the operation numbered `op`, replacing any fault planned for it.
+    pub fn at(mut self, op: u64, fault: Fault) -> FaultPlan {
+        self.faults.insert(op, fault);
+        self
+    }
+
+    /// Fail the operation numbered `op` with an error of this kind.
+    pub fn fail_at(self, op: u64, kind: io::ErrorKind) -> FaultPlan {
+        self.at(op, Fault::Error(kind))
+    }
+
+    /// Delay the operation numbered `op`.
+    pub fn delay_at(self, op: u64, delay: Duration) -> FaultPlan {
+        self.at(op, Fault::Delay(delay))
+    }
+
+    /// Cut the operation numbered `op` short after `bytes` bytes.
+    pub fn partial_at(self, op: u64, bytes: usize) -> FaultPlan {
+        self.at(op, Fault::Partial(bytes))
+    }
+
+    fn fault(&self, op: u64) -> Option<&Fault> {
+        self.faults.get(&op)
+    }
+}
+
+fn injected(kind: io::ErrorKind, op: u64) -> io::Error {
+    io::Error::new(kind, format!("fault injected into operation {}", op))
+}
+
+/// `FaultyWriter` is a writer injecting the faults of its plan into the writes of `W`. Flushes
+/// are passed through, and not counted.
+#[derive(Debug)]
+pub struct FaultyWriter<W> {
+    inner: W,
+    plan: FaultPlan,
+    ops: u64,
+}
+
+impl<W: Write> FaultyWriter<W> {
+    pub fn new(inner: W, plan: FaultPlan) -> FaultyWriter<W> {
+        FaultyWriter { inner, plan, ops: 0 }
+    }
+
+    /// The number of writes attempted so far, including those that failed.
+    pub fn ops(&self) -> u64 {
+        self.ops
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for FaultyWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let op = self.ops;
+        self.ops += 1;
+        match self.plan.fault(op) {
+            None => self.inner.write(buf),
+            Some(Fault::Error(kind)) => Err(injected(*kind, op)),
+            Some(Fault::Delay(delay)) => {
+                thread::sleep(*delay);
+                self.inner.write(buf)
+            }
+            Some(Fault::Partial(bytes)) => {
+                self.inner.write_all(&buf[..(*bytes).min(buf.len())])?;
+                Err(injected(io::ErrorKind::WriteZero, op))
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// `FaultyReader` is a reader injecting the faults of its plan into the reads of `R`.
+#[derive(Debug)]
+pub struct FaultyReader<R> {
+    inner: R,
+    plan: FaultPlan,
+    ops: u64,
+}
+
+impl<R: Read> FaultyReader<R> {
+    pub fn new(inner: R, plan: FaultPlan) -> FaultyReader<R> {
+        FaultyReader { inner, plan, ops: 0 }
+    }
+
+    /// The number of reads attempted so far, including those that failed.
+    pub fn ops(&self) -> u64 {
+        self.ops
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for FaultyReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let op = self.ops;
+        self.ops += 1;
+        match self.plan.fault(op) {
+            None => self.inner.read(buf),
+            Some(Fault::Error(kind)) => Err(injected(*kind, op)),
+            Some(Fault::Delay(delay)) => {
+                thread::sleep(*delay);
+                self.inner.read(buf)
+            }
+            Some(Fault::Partial(bytes)) => {
+                let bytes = (*bytes).min(buf.len());
+                self.inner.read(&mut buf[..bytes])
+            }
+        }
+    }
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "io")]
+    use crate::bank::Bank;
+    #[cfg(feature = "io")]
+    use crate::journal::{Follower, JournalError, JournalWriter};
+    #[cfg(feature = "io")]
+    use crate::testkit::Scenario;
+
+    #[test]
+    fn writes_without_a_fault_are_passed_through() -> io::Result<()> {
+        // SETUP
+        let mut writer = FaultyWriter::new(Vec::new(), FaultPlan::new().fail_at(1, io::ErrorKind::Other));
+
+        // TEST
+        writer.write_all(b"entry")?;
+        writer.flush()?;
+        assert_eq!((1, &b"entry"[..]), (writer.ops(), &writer.into_inner()[..]));
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn failing_write_writes_nothing() {
+        // SETUP
+        let mut writer = FaultyWriter::new(Vec::new(), FaultPlan::new().fail_at(0, io::ErrorKind::StorageFull));
+
+        // TEST
+        let error = writer.write(b"entry").unwrap_err();
+        assert_eq!(io::ErrorKind::StorageFull, error.kind());
+        assert_eq!("fault injected into operation 0", error.to_string());
+        assert!(writer.into_inner().is_empty());
+    }
+
+    #[test]
+    fn partial_write_leaves_a_torn_write_behind() {
+        // SETUP
+        let mut writer = FaultyWriter::new(Vec::new(), FaultPlan::new().partial_at(1, 2));
+
+        // TEST
+        writer.write_all(b"first").unwrap();
+        assert_eq!(io::ErrorKind::WriteZero, writer.write(b"second").unwrap_err().kind());
+        assert_eq!(b"firstse", &writer.into_inner()[..]);
+    }
+
+    #[test]
+    fn delayed_write_is_carried_out() -> io::Result<()> {
+        // SETUP
+        let mut writer = FaultyWriter::new(Vec::new(), FaultPlan::new().delay_at(0, Duration::from_millis(1)));
+
+        // TEST
+        assert_eq!(5, writer.write(b"entry")?);
+        assert_eq!(b"entry", &writer.into_inner()[..]);
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn failed_operations_are_counted() {
+        // SETUP
+        let mut writer = FaultyWriter::new(Vec::new(), FaultPlan::new().fail_at(0, io::ErrorKind::Other));
+        let mut reader = FaultyReader::new(&b"entry"[..], FaultPlan::new().fail_at(0, io::ErrorKind::Other));
+
+        // TEST
+        assert!(writer.write(b"entry").is_err());
+        assert!(reader.read(&mut [0; 5]).is_err());
+        assert_eq!((1, 1), (writer.ops(), reader.ops()));
+    }
+
+    #[test]
+    fn later_fault_of_an_operation_replaces_the_earlier_one() -> io::Result<()> {
+        // SETUP
+        let plan = FaultPlan::new()
+            .fail_at(0, io::ErrorKind::Other)
+            .delay_at(0, Duration::from_millis(1));
+        let mut writer = FaultyWriter::new(Vec::new(), plan);
+
+        // TEST
+        assert_eq!(5, writer.write(b"entry")?);
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn partial_read_is_shortened_without_failing() -> io::Result<()> {
+        // SETUP
+        let mut reader = FaultyReader::new(&b"entry"[..], FaultPlan::new().partial_at(0, 2));
+        let mut buf = [0; 5];
+
+        // TEST
+        assert_eq!(2, reader.read(&mut buf)?);
+        assert_eq!(3, reader.read(&mut buf[2..])?);
+        assert_eq!(b"entry", &buf);
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn failing_read_reads_nothing() {
+        // SETUP
+        let mut reader = FaultyReader::new(&b"entry"[..], FaultPlan::new().fail_at(0, io::ErrorKind::Interrupted));
+        let mut text = String::new();
+
+        // TEST
+        assert_eq!(io::ErrorKind::Interrupted, reader.read(&mut [0; 5]).unwrap_err().kind());
+        reader.read_to_string(&mut text).unwrap();
+        assert_eq!("entry", text);
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn follower_recovers_the_entries_before_a_torn_journal_write() -> Result<(), JournalError> {
+        // SETUP
+        let scenario = Scenario::new().deposit(1, 5).deposit(1, 3).deposit(1, 2);
+        let mut journal = Vec::new();
+        let mut writer = JournalWriter::new(FaultyWriter::new(&mut journal, FaultPlan::new().partial_at(2, 5)));
+        Bank::new().process_all_with(scenario.transactions().to_vec(), &mut writer);
+        // crash without flushing on drop, which would write the torn entry again
+        std::mem::forget(writer);
+
+        // TEST
+        assert!(!journal.ends_with(b"\n"));
+        let mut follower = Follower::new(Bank::new());
+        let reader = FaultyReader::new(&journal[..], FaultPlan::new().fail_at(0, io::ErrorKind::Other));
+        assert!(matches!(follower.apply(reader), Err(JournalError::Io(_))));
+        assert_eq!(0, follower.applied());
+        let reader = FaultyReader::new(
+            &journal[..],
+            FaultPlan::new().partial_at(0, 3).delay_at(1, Duration::from_millis(1)),
+        );
+        assert_eq!(2, follower.apply(reader)?);
+        assert_eq!(2, follower.applied());
+
+        // TEARDOWN
+        Ok(())
+    }
+}
+//endregion
@@ -0,0 +1,186 @@
+//! # C API
+//! An `extern "C"` interface to `Bank`, so that the processor can be embedded in C and C++
+//! applications without spawning a subprocess. The declarations are in
+//! `include/rust_payment_processor.h`; link against the `cdylib` built with the `ffi` feature.
+//!
+//! Every function returns a `BankStatus`, except for the constructor and destructors. When a call
+//! fails, `bank_last_error` describes the failure until the next call on the same thread. Panics
+//! are caught at the boundary and reported as `BANK_STATUS_PANIC`.
+#![allow(unsafe_code)]
+use crate::bank::Bank;
+use crate::schema;
+use crate::transaction::RecordOptions;
+use std::cell::RefCell;
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+/// `BankStatus` enumerates the results of the C API functions.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BankStatus {
+    Ok = 0,
+    /// A required pointer argument was null.
+    NullPointer = 1,
+    /// The input could not be read, e.g. its header row does not match any schema. Individual
+    /// records that fail to parse or process are logged and discarded as usual and do not fail
+    /// the call.
+    InvalidInput = 2,
+    /// The accounts could not be serialized.
+    SerializationFailed = 3,
+    /// The processor panicked. The bank should not be used any further.
+    Panic = 4,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message).unwrap_or_else(|_| CString::new("error message contained a NUL byte").unwrap());
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// Run the body of an API function, recording any error or panic as the last error.
+fn guard<F: FnOnce() -> Result<(), (BankStatus, String)>>(body: F) -> BankStatus {
+    LAST_ERROR.with(|last| *last.borrow_mut() = None);
+    match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(Ok(())) => BankStatus::Ok,
+        Ok(Err((status, message))) => {
+            set_last_error(message);
+            status
+        }
+        Err(_) => {
+            set_last_error(String::from("the payment processor panicked"));
+            BankStatus::Panic
+        }
+    }
+}
+
+/// Create a new, empty bank. Release it with `bank_free`.
+#[no_mangle]
+pub extern "C" fn bank_new() -> *mut Bank {
+    Box::into_raw(Box::new(Bank::new()))
+}
+
+/// Release a bank created by `bank_new`. Passing null is a no-op.
+///
+/// # Safety
+/// `bank` must be null or a pointer returned by `bank_new` that has not yet been freed.
+#[no_mangle]
+pub unsafe extern "C" fn bank_free(bank: *mut Bank) {
+    if !bank.is_null() {
+        drop(Box::from_raw(bank));
+    }
+}
+
+/// Process `len` bytes of CSV input, with a header row, starting at `data`. The schema is detected
+/// from the header row.
+///
+/// # Safety
+/// `bank` must be a live pointer returned by `bank_new`, and `data` must point to at least `len`
+/// readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn bank_process_csv_buffer(bank: *mut Bank, data: *const u8, len: usize) -> BankStatus {
+    guard(|| {
+        if bank.is_null() || (data.is_null() && len > 0) {
+            return Err((BankStatus::NullPointer, String::from("bank and data must not be null")));
+        }
+        let bank = &mut *bank;
+        let input: &[u8] = if len == 0 { &[] } else { slice::from_raw_parts(data, len) };
+        let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(input);
+        let options = RecordOptions::default();
+        schema::check_input(&mut reader, None, &options).map_err(|e| (BankStatus::InvalidInput, e))?;
+        bank.process_record_set(&mut reader, &options);
+        Ok(())
+    })
+}
+
+/// Export every account as a JSON array of objects with the fields `client`, `available`, `held`,
+/// `total`, and `locked`. Amounts are written as strings to preserve their precision. On success,
+/// `*out` points to a NUL-terminated string that must be released with `bank_string_free`.
+///
+/// # Safety
+/// `bank` must be a live pointer returned by `bank_new`, and `out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn bank_export_accounts_json(bank: *const Bank, out: *mut *mut c_char) -> BankStatus {
+    guard(|| {
+        if bank.is_null() || out.is_null() {
+            return Err((BankStatus::NullPointer, String::from("bank and out must not be null")));
+        }
+        *out = ptr::null_mut();
+        let accounts: Vec<_> = (*bank).accounts().collect();
+        let json = serde_json::to_string(&accounts).map_err(|e| (BankStatus::SerializationFailed, e.to_string()))?;
+        let json = CString::new(json).map_err(|e| (BankStatus::SerializationFailed, e.to_string()))?;
+        *out = json.into_raw();
+        Ok(())
+    })
+}
+
+/// Release a string returned by `bank_export_accounts_json`. Passing null is a no-op.
+///
+/// # Safety
+/// `string` must be null or a string returned by this library that has not yet been freed.
+#[no_mangle]
+pub unsafe extern "C" fn bank_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+/// Returns a description of the last failed call on this thread, or null if the last call
+/// succeeded. The string is owned by the library and valid until the next call on this thread.
+#[no_mangle]
+pub extern "C" fn bank_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |message| message.as_ptr()))
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+
+    #[test]
+    fn process_buffer_and_export_json() {
+        // SETUP
+        let bank = bank_new();
+        let input = b"type, client, tx, amount\ndeposit, 1, 1, 2.5\n";
+        let mut json = ptr::null_mut();
+
+        // TEST
+        unsafe {
+            assert_eq!(BankStatus::Ok, bank_process_csv_buffer(bank, input.as_ptr(), input.len()));
+            assert_eq!(BankStatus::Ok, bank_export_accounts_json(bank, &mut json));
+            assert_eq!(
+                r#"[{"client":1,"available":"2.5","held":"0","total":"2.5","locked":false}]"#,
+                CStr::from_ptr(json).to_str().unwrap()
+            );
+            assert!(bank_last_error().is_null());
+
+            // TEARDOWN
+            bank_string_free(json);
+            bank_free(bank);
+        }
+    }
+
+    #[test]
+    fn invalid_input_sets_last_error() {
+        // SETUP
+        let bank = bank_new();
+        let input = b"kind,client\ndeposit,1\n";
+
+        // TEST
+        unsafe {
+            assert_eq!(BankStatus::InvalidInput, bank_process_csv_buffer(bank, input.as_ptr(), input.len()));
+            assert!(!bank_last_error().is_null());
+            assert_eq!(BankStatus::NullPointer, bank_process_csv_buffer(ptr::null_mut(), input.as_ptr(), 0));
+
+            // TEARDOWN
+            bank_free(bank);
+        }
+    }
+}
+//endregion
@@ -0,0 +1,241 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Filters
+//! An `AccountFilter` narrows the accounts written at the end of a run down to those of interest,
+//! for targeted reprocessing and focused reports without post-filtering a huge CSV. It can select
+//! clients by `ClientRanges`, given on the command line as e.g. `--clients 100-200,5000`, accounts
+//! that are locked, and accounts whose total is at least some amount.
+//!
+//! A `TransactionFilter` is applied to the input instead, so that only part of it is processed at
+//! all: the transactions of some clients, with IDs in a `TxRange`, or of some types. This allows
+//! replaying e.g. just the disputes of a corrected file.
+use crate::account::{Account, ClientId};
+use crate::amount::Amount;
+use crate::transaction::{Transaction, TransactionType, TxId};
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+/// `ClientRanges` is a set of client ids, written as a comma-separated list of single ids and
+/// inclusive ranges, such as `100-200,5000`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ClientRanges(Vec<RangeInclusive<ClientId>>);
+
+impl ClientRanges {
+    /// Whether the client is in one of the ranges.
+    pub fn contains(&self, client: ClientId) -> bool {
+        self.0.iter().any(|range| range.contains(&client))
+    }
+}
+
+impl FromStr for ClientRanges {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<ClientRanges, String> {
+        let parse = |id: &str| {
+            id.trim()
+                .parse::<ClientId>()
+                .map_err(|e| format!("invalid client id \"{}\": {}", id, e))
+        };
+        let mut ranges = Vec::new();
+        for part in value.split(',') {
+            let range = match part.split_once('-') {
+                Some((start, end)) => parse(start)?..=parse(end)?,
+                None => parse(part).map(|id| id..=id)?,
+            };
+            if range.is_empty() {
+                return Err(format!("invalid client range \"{}\": the start is after the end", part));
+            }
+            ranges.push(range);
+        }
+        Ok(ClientRanges(ranges))
+    }
+}
+
+impl fmt::Display for ClientRanges {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, range) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            match range.start() == range.end() {
+                true => write!(f, "{}", range.start())?,
+                false => write!(f, "{}-{}", range.start(), range.end())?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `AccountFilter` selects the accounts to output. An account has to match every criterion given.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AccountFilter {
+    pub clients: Option<ClientRanges>,
+    pub only_locked: bool,
+    pub min_total: Option<Amount>,
+}
+
+impl AccountFilter {
+    /// Whether the filter lets every account through.
+    pub fn is_empty(&self) -> bool {
+        *self == AccountFilter::default()
+    }
+
+    /// Whether the account should be output.
+    pub fn matches(&self, account: &Account) -> bool {
+        self.clients.as_ref().is_none_or(|clients| clients.contains(account.client))
+            && (!self.only_locked || account.locked)
+            && self.min_total.is_none_or(|min| account.total >= min)
+    }
+}
+
+/// `TxRange` is an inclusive range of transaction IDs, written as `100-200`. Either end may be left
+/// out, as in `100-` or `-200`, and a single ID selects just that transaction.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TxRange(RangeInclusive<TxId>);
+
+impl TxRange {
+    /// Whether the transaction ID is in the range.
+    pub fn contains(&self, tx: TxId) -> bool {
+        self.0.contains(&tx)
+    }
+}
+
+impl FromStr for TxRange {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<TxRange, String> {
+        let parse = |id: &str, open: TxId| match id.trim() {
+            "" => Ok(open),
+            id => id.parse::<TxId>().map_err(|e| format!("invalid transaction id \"{}\": {}", id, e)),
+        };
+        let range = match value.split_once('-') {
+            Some((start, end)) => parse(start, TxId::MIN)?..=parse(end, TxId::MAX)?,
+            None if !value.trim().is_empty() => parse(value, TxId::MIN).map(|id| id..=id)?,
+            None => return Err(String::from("empty transaction range")),
+        };
+        if range.is_empty() {
+            return Err(format!("invalid transaction range \"{}\": the start is after the end", value));
+        }
+        Ok(TxRange(range))
+    }
+}
+
+/// `TransactionTypes` is a set of transaction types, written as a comma-separated list of their
+/// names, such as `deposit,withdrawal`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransactionTypes(Vec<TransactionType>);
+
+impl TransactionTypes {
+    /// Whether the type is in the set.
+    pub fn contains(&self, kind: &TransactionType) -> bool {
+        self.0.contains(kind)
+    }
+}
+
+impl FromStr for TransactionTypes {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<TransactionTypes, String> {
+        value
+            .split(',')
+            .map(|name| name.trim().parse())
+            .collect::<Result<_, _>>()
+            .map(TransactionTypes)
+    }
+}
+
+/// `TransactionFilter` selects the transactions to process. A transaction has to match every
+/// criterion given. For disputes, resolves, and chargebacks, the range applies to the ID of the
+/// transaction they refer to.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TransactionFilter {
+    pub clients: Option<ClientRanges>,
+    pub tx_range: Option<TxRange>,
+    pub types: Option<TransactionTypes>,
+}
+
+impl TransactionFilter {
+    /// Whether the filter lets every transaction through.
+    pub fn is_empty(&self) -> bool {
+        *self == TransactionFilter::default()
+    }
+
+    /// Whether the transaction should be processed.
+    pub fn matches(&self, transaction: &Transaction) -> bool {
+        self.clients.as_ref().is_none_or(|clients| clients.contains(transaction.client))
+            && self.tx_range.as_ref().is_none_or(|range| range.contains(transaction.tx))
+            && self.types.as_ref().is_none_or(|types| types.contains(&transaction.kind))
+    }
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testkit::Scenario;
+
+    #[test]
+    fn filters_select_accounts_by_client_lock_and_total() {
+        // SETUP
+        let clients: ClientRanges = "100-200,5000".parse().unwrap();
+        let scenario = Scenario::new()
+            .deposit(5, 10)
+            .deposit(150, 1)
+            .deposit(200, 20)
+            .deposit(5000, 30)
+            .dispute(5000, 4)
+            .chargeback(5000, 4);
+
+        // TEST
+        assert_eq!("100-200,5000", clients.to_string());
+        assert!("200-100".parse::<ClientRanges>().is_err());
+        assert!("1,x".parse::<ClientRanges>().is_err());
+        let mut bank = scenario.run();
+        let filter = AccountFilter {
+            clients: Some(clients),
+            min_total: Some(Amount::from(1u32)),
+            ..AccountFilter::default()
+        };
+        bank.retain_accounts(|account| filter.matches(account));
+        let mut selected: Vec<ClientId> = bank.accounts().map(|account| account.client).collect();
+        selected.sort_unstable();
+        assert_eq!(vec![150, 200], selected);
+        let locked = AccountFilter {
+            only_locked: true,
+            ..AccountFilter::default()
+        };
+        assert!(!locked.is_empty());
+        assert!(bank.accounts().all(|account| !locked.matches(account)));
+    }
+
+    #[test]
+    fn transaction_filter_selects_by_tx_range_and_type() {
+        // SETUP
+        let scenario = Scenario::new()
+            .deposit(1, 10)
+            .deposit(1, 20)
+            .deposit(2, 30)
+            .withdraw(2, 5)
+            .dispute(1, 2)
+            .dispute(2, 3);
+        let filter = TransactionFilter {
+            tx_range: Some("2-3".parse().unwrap()),
+            types: Some("dispute,withdrawal".parse().unwrap()),
+            ..TransactionFilter::default()
+        };
+
+        // TEST
+        assert!("3-2".parse::<TxRange>().is_err());
+        assert!("deposit,refund".parse::<TransactionTypes>().is_err());
+        assert!("-200".parse::<TxRange>().unwrap().contains(0));
+        assert!("100-".parse::<TxRange>().unwrap().contains(TxId::MAX));
+        let selected: Vec<(TransactionType, TxId)> = scenario
+            .transactions()
+            .iter()
+            .filter(|transaction| filter.matches(transaction))
+            .map(|transaction| (transaction.kind.clone(), transaction.tx))
+            .collect();
+        assert_eq!(vec![(TransactionType::Dispute, 2), (TransactionType::Dispute, 3)], selected);
+    }
+}
+//endregion
@@ -0,0 +1,197 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Transaction generators
+//! `arbitrary::Arbitrary` implementations and `proptest` strategies that produce transactions for
+//! property tests and fuzzing. Client and transaction IDs are drawn from small ranges so that a
+//! generated sequence has plenty of disputes, resolves, and chargebacks that refer to earlier
+//! transactions of the same client. Amounts have up to four decimal places and are kept small
+//! enough that no realistic sequence can overflow a balance.
+//!
+//! Transactions are generated as they would be read from input: invalid combinations, such as a
+//! deposit without an amount, are produced on purpose, but `under_dispute` is never set.
+use crate::account::ClientId;
+use crate::amount::{self, Amount};
+use crate::transaction::{Transaction, TransactionType, TxId};
+use arbitrary::{Arbitrary, Unstructured};
+use proptest::prelude::*;
+use rust_decimal::Decimal;
+
+/// Clients are drawn from `0..CLIENTS`.
+pub const CLIENTS: ClientId = 8;
+/// Transaction IDs are drawn from `1..=TRANSACTIONS`.
+pub const TRANSACTIONS: TxId = 64;
+/// The largest amount generated, in 1/10000ths.
+const MAX_MANTISSA: i64 = 10_000_000_000;
+
+fn amount_from_mantissa(mantissa: i64) -> Amount {
+    amount::from_decimal(Decimal::new(mantissa, 4).normalize()).expect("generated amounts are in range")
+}
+
+//region Arbitrary
+impl<'a> Arbitrary<'a> for TransactionType {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<TransactionType> {
+        Ok(match u.int_in_range(0..=4u8)? {
+            0 => TransactionType::Deposit,
+            1 => TransactionType::Withdrawal,
+            2 => TransactionType::Dispute,
+            3 => TransactionType::Resolve,
+            _ => TransactionType::Chargeback,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Transaction {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Transaction> {
+        let kind = TransactionType::arbitrary(u)?;
+        let client = u.int_in_range(0..=CLIENTS - 1)?;
+        let tx = u.int_in_range(1..=TRANSACTIONS)?;
+        // mostly well-formed: funds movements carry an amount and references do not
+        let with_amount = match kind {
+            TransactionType::Deposit | TransactionType::Withdrawal => u.ratio(15u8, 16u8)?,
+            _ => u.ratio(1u8, 16u8)?,
+        };
+        let amount = if with_amount {
+            Some(amount_from_mantissa(u.int_in_range(-MAX_MANTISSA..=MAX_MANTISSA)?))
+        } else {
+            None
+        };
+        Ok(Transaction {
+            kind,
+            client,
+            tx,
+            amount,
+            under_dispute: false,
+            timestamp: None,
+            currency: None,
+            signature: None,
+            correction: false,
+            provenance: None,
+            expires: None,
+        })
+    }
+}
+//endregion
+
+//region Strategies
+/// A strategy producing a single transaction. Funds movements are weighted more heavily than
+/// references so that most references have something to refer to.
+pub fn transaction() -> impl Strategy<Value = Transaction> {
+    let kind = prop_oneof![
+        4 => Just(TransactionType::Deposit),
+        2 => Just(TransactionType::Withdrawal),
+        2 => Just(TransactionType::Dispute),
+        1 => Just(TransactionType::Resolve),
+        1 => Just(TransactionType::Chargeback),
+    ];
+    let amount = prop_oneof![
+        14 => (1..=MAX_MANTISSA).prop_map(|mantissa| Some(amount_from_mantissa(mantissa))),
+        1 => (-MAX_MANTISSA..=0).prop_map(|mantissa| Some(amount_from_mantissa(mantissa))),
+        1 => Just(None),
+    ];
+    (kind, 0..CLIENTS, 1..=TRANSACTIONS, amount).prop_map(|(kind, client, tx, amount)| {
+        let amount = match kind {
+            TransactionType::Deposit | TransactionType::Withdrawal => amount,
+            _ => None,
+        };
+        Transaction {
+            kind,
+            client,
+            tx,
+            amount,
+            under_dispute: false,
+            timestamp: None,
+            currency: None,
+            signature: None,
+            correction: false,
+            provenance: None,
+            expires: None,
+        }
+    })
+}
+
+/// A strategy producing a sequence of up to `max_len` transactions.
+pub fn transactions(max_len: usize) -> impl Strategy<Value = Vec<Transaction>> {
+    proptest::collection::vec(transaction(), 0..=max_len)
+}
+//endregion
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    /// Arbitrary transactions drawn from a fixed pattern of bytes, the same on every run.
+    fn arbitrary_transactions(count: usize) -> arbitrary::Result<Vec<Transaction>> {
+        let bytes: Vec<u8> = (0..=255u8).cycle().take(4096).collect();
+        let mut u = Unstructured::new(&bytes);
+        (0..count).map(|_| Transaction::arbitrary(&mut u)).collect()
+    }
+
+    #[test]
+    fn arbitrary_transactions_stay_within_generated_ranges() -> arbitrary::Result<()> {
+        // TEST
+        for transaction in arbitrary_transactions(100)? {
+            assert!(transaction.client < CLIENTS);
+            assert!((1..=TRANSACTIONS).contains(&transaction.tx));
+            assert!(!transaction.under_dispute);
+        }
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn arbitrary_transactions_are_of_every_built_in_type() -> arbitrary::Result<()> {
+        // TEST
+        let kinds: HashSet<String> = arbitrary_transactions(100)?
+            .iter()
+            .map(|transaction| transaction.kind.to_string())
+            .collect();
+        assert_eq!(5, kinds.len());
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn arbitrary_type_of_empty_input_is_a_deposit() -> arbitrary::Result<()> {
+        // TEST
+        assert_eq!(TransactionType::Deposit, TransactionType::arbitrary(&mut Unstructured::new(&[]))?);
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn amounts_have_at_most_four_decimal_places() {
+        // TEST
+        assert_eq!(Decimal::new(12345, 4), amount::to_decimal(amount_from_mantissa(12345)));
+        assert_eq!(Decimal::new(1, 0), amount::to_decimal(amount_from_mantissa(10000)));
+    }
+
+    proptest! {
+        #[test]
+        fn references_carry_no_amount(transaction in transaction()) {
+            // TEST
+            let movement = matches!(transaction.kind, TransactionType::Deposit | TransactionType::Withdrawal);
+            prop_assert!(movement || transaction.amount.is_none());
+        }
+
+        #[test]
+        fn generated_transactions_stay_within_generated_ranges(transaction in transaction()) {
+            // TEST
+            prop_assert!(transaction.client < CLIENTS);
+            prop_assert!((1..=TRANSACTIONS).contains(&transaction.tx));
+            prop_assert!(!transaction.under_dispute);
+            let limit = amount_from_mantissa(MAX_MANTISSA);
+            prop_assert!(transaction.amount.is_none_or(|amount| -limit <= amount && amount <= limit));
+        }
+
+        #[test]
+        fn sequences_are_at_most_the_given_length(transactions in transactions(10)) {
+            // TEST
+            prop_assert!(transactions.len() <= 10);
+        }
+    }
+}
+//endregion
@@ -0,0 +1,286 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Transfer graph
+//! An analysis pass over the transfers between clients, looking for two shapes laundered money
+//! tends to take:
+//!
+//! * cycles, where money sent on from client to client can come back to where it started;
+//! * rapid pass-through, where a client sends money on within a few transfers of receiving it.
+//!
+//! The engine has no transfer transaction of its own. A transfer is applied as a withdrawal and a
+//! deposit, e.g. by a `saga::Saga`, so callers add each transfer to a `TransferGraph` as they apply
+//! it. `write_findings` writes what the graph turns up as a report of its own:
+//!
+//! ```text
+//! finding,clients,transactions
+//! cycle,1 2 3,
+//! pass-through,2,1 2
+//! ```
+use crate::account::ClientId;
+use crate::amount::Amount;
+use crate::transaction::TxId;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::io;
+
+/// `Transfer` is a movement of funds from one client to another.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transfer {
+    pub tx: TxId,
+    pub from: ClientId,
+    pub to: ClientId,
+    pub amount: Amount,
+}
+
+/// `Finding` is a pattern found in the transfer graph.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Finding {
+    /// Money can go round between these clients, ordered by client.
+    Cycle { clients: Vec<ClientId> },
+    /// The client sent transfer `sent` within a few transfers of receiving transfer `received`.
+    PassThrough { client: ClientId, received: TxId, sent: TxId },
+}
+
+/// `TransferGraph` is the graph of which clients transferred money to which, with the transfers in
+/// the order they were added.
+#[derive(Clone, Debug, Default)]
+pub struct TransferGraph {
+    transfers: Vec<Transfer>,
+    edges: BTreeMap<ClientId, BTreeSet<ClientId>>,
+}
+
+impl TransferGraph {
+    pub fn new() -> TransferGraph {
+        TransferGraph::default()
+    }
+
+    pub fn add(&mut self, transfer: Transfer) {
+        self.edges.entry(transfer.from).or_default().insert(transfer.to);
+        self.edges.entry(transfer.to).or_default();
+        self.transfers.push(transfer);
+    }
+
+    /// The groups of clients between which money can go round, i.e. the strongly connected
+    /// components of the graph with more than one client, or a client transferring to itself.
+    pub fn cycles(&self) -> Vec<Vec<ClientId>> {
+        // Kosaraju's algorithm: order the clients by when a depth-first search is done with them,
+        // then search the reversed graph in the reverse of that order. Each search of the reversed
+        // graph finds one component. Both searches keep their own stack, as chains of transfers
+        // can be far deeper than the call stack.
+        let mut finished = Vec::with_capacity(self.edges.len());
+        let mut visited = HashSet::new();
+        for &start in self.edges.keys() {
+            if !visited.insert(start) {
+                continue;
+            }
+            let mut stack = vec![(start, self.edges[&start].iter())];
+            while let Some((client, next)) = stack.last_mut() {
+                match next.find(|to| visited.insert(**to)) {
+                    Some(&to) => stack.push((to, self.edges[&to].iter())),
+                    None => {
+                        finished.push(*client);
+                        stack.pop();
+                    }
+                }
+            }
+        }
+        let mut reversed: HashMap<ClientId, Vec<ClientId>> = HashMap::new();
+        for (&from, tos) in &self.edges {
+            for &to in tos {
+                reversed.entry(to).or_default().push(from);
+            }
+        }
+        let mut assigned = HashSet::new();
+        let mut cycles = Vec::new();
+        for &start in finished.iter().rev() {
+            if !assigned.insert(start) {
+                continue;
+            }
+            let mut component = vec![start];
+            let mut stack = vec![start];
+            while let Some(client) = stack.pop() {
+                for &from in reversed.get(&client).into_iter().flatten() {
+                    if assigned.insert(from) {
+                        component.push(from);
+                        stack.push(from);
+                    }
+                }
+            }
+            if component.len() > 1 || self.edges[&start].contains(&start) {
+                component.sort_unstable();
+                cycles.push(component);
+            }
+        }
+        cycles.sort();
+        cycles
+    }
+
+    /// The transfers a client sent at most `within` transfers after the last one it received,
+    /// counting every transfer added in between, in the order they were added.
+    pub fn pass_throughs(&self, within: usize) -> Vec<Finding> {
+        let mut received: HashMap<ClientId, (usize, TxId)> = HashMap::new();
+        let mut findings = Vec::new();
+        for (index, transfer) in self.transfers.iter().enumerate() {
+            if let Some(&(at, tx)) = received.get(&transfer.from) {
+                if index - at <= within {
+                    findings.push(Finding::PassThrough {
+                        client: transfer.from,
+                        received: tx,
+                        sent: transfer.tx,
+                    });
+                }
+            }
+            received.insert(transfer.to, (index, transfer.tx));
+        }
+        findings
+    }
+
+    /// Every cycle, followed by every pass-through within `within` transfers.
+    pub fn findings(&self, within: usize) -> Vec<Finding> {
+        let mut findings: Vec<Finding> = self.cycles().into_iter().map(|clients| Finding::Cycle { clients }).collect();
+        findings.extend(self.pass_throughs(within));
+        findings
+    }
+}
+
+/// `FindingRecord` is the layout of a finding in the report.
+#[derive(Serialize)]
+struct FindingRecord {
+    finding: &'static str,
+    clients: String,
+    transactions: String,
+}
+
+fn join<T: ToString>(values: &[T]) -> String {
+    values.iter().map(ToString::to_string).collect::<Vec<_>>().join(" ")
+}
+
+/// Write findings in CSV format to the given writer. Lists of clients and transactions are
+/// separated by spaces.
+pub fn write_findings<W: io::Write>(findings: &[Finding], writer: W) -> Result<(), csv::Error> {
+    let mut wtr = csv::WriterBuilder::new().from_writer(writer);
+    for finding in findings {
+        let record = match finding {
+            Finding::Cycle { clients } => FindingRecord {
+                finding: "cycle",
+                clients: join(clients),
+                transactions: String::new(),
+            },
+            Finding::PassThrough { client, received, sent } => FindingRecord {
+                finding: "pass-through",
+                clients: client.to_string(),
+                transactions: join(&[received, sent]),
+            },
+        };
+        wtr.serialize(record)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A graph of transfers of 10 given as `(tx, from, to)`.
+    fn graph(transfers: &[(TxId, ClientId, ClientId)]) -> TransferGraph {
+        let mut graph = TransferGraph::new();
+        for &(tx, from, to) in transfers {
+            graph.add(Transfer {
+                tx,
+                from,
+                to,
+                amount: Amount::from(10u32),
+            });
+        }
+        graph
+    }
+
+    fn pass_through(client: ClientId, received: TxId, sent: TxId) -> Finding {
+        Finding::PassThrough { client, received, sent }
+    }
+
+    #[test]
+    fn clients_money_can_go_round_form_a_cycle() {
+        // TEST
+        assert_eq!(vec![vec![1, 2, 3]], graph(&[(1, 3, 1), (2, 1, 2), (3, 2, 3), (4, 3, 4)]).cycles());
+    }
+
+    #[test]
+    fn chains_without_a_way_back_are_not_cycles() {
+        // TEST
+        assert!(graph(&[(1, 1, 2), (2, 2, 3), (3, 1, 3)]).cycles().is_empty());
+    }
+
+    #[test]
+    fn client_transferring_to_itself_is_a_cycle() {
+        // TEST
+        assert_eq!(vec![vec![4]], graph(&[(1, 4, 4), (2, 4, 5)]).cycles());
+    }
+
+    #[test]
+    fn separate_cycles_are_ordered_by_client() {
+        // TEST
+        assert_eq!(
+            vec![vec![1, 2], vec![5, 6]],
+            graph(&[(1, 6, 5), (2, 5, 6), (3, 2, 1), (4, 1, 2), (5, 2, 5)]).cycles()
+        );
+    }
+
+    #[test]
+    fn long_chains_do_not_overflow_the_stack() {
+        // SETUP
+        let mut transfers: Vec<(TxId, ClientId, ClientId)> = (0..100_000).map(|client| (client.into(), client, client + 1)).collect();
+        transfers.push((100_000, 100_000, 0));
+
+        // TEST
+        let cycles = graph(&transfers).cycles();
+        assert_eq!(vec![100_001], cycles.iter().map(Vec::len).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn transfers_sent_on_soon_after_receiving_pass_through() {
+        // TEST
+        let graph = graph(&[(1, 1, 2), (2, 4, 5), (3, 2, 3)]);
+        assert_eq!(vec![pass_through(2, 1, 3)], graph.pass_throughs(2));
+        assert!(graph.pass_throughs(1).is_empty());
+    }
+
+    #[test]
+    fn pass_through_counts_from_the_last_transfer_received() {
+        // TEST
+        let graph = graph(&[(1, 1, 2), (2, 4, 5), (3, 3, 2), (4, 2, 6)]);
+        assert_eq!(vec![pass_through(2, 3, 4)], graph.pass_throughs(1));
+    }
+
+    #[test]
+    fn transfers_sent_before_receiving_are_not_pass_through() {
+        // TEST
+        assert!(graph(&[(1, 2, 3), (2, 1, 2)]).pass_throughs(5).is_empty());
+    }
+
+    #[test]
+    fn findings_list_cycles_before_pass_throughs() {
+        // TEST
+        let findings = graph(&[(1, 1, 2), (2, 2, 1)]).findings(1);
+        assert_eq!(vec![Finding::Cycle { clients: vec![1, 2] }, pass_through(2, 1, 2)], findings);
+    }
+
+    #[test]
+    fn findings_are_written_as_csv() -> Result<(), csv::Error> {
+        // SETUP
+        let findings = [Finding::Cycle { clients: vec![1, 2, 3] }, pass_through(2, 1, 2)];
+        let mut output = Vec::new();
+
+        // TEST
+        write_findings(&findings, &mut output)?;
+        assert_eq!(
+            "finding,clients,transactions\ncycle,1 2 3,\npass-through,2,1 2\n",
+            String::from_utf8(output).unwrap()
+        );
+
+        // TEARDOWN
+        Ok(())
+    }
+}
+//endregion
@@ -0,0 +1,277 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Transaction handlers
+//! Every transaction is applied by the `TransactionHandler` registered for its type in the bank's
+//! `Handlers`. The built-in types are handled by default; a downstream crate can register a
+//! handler for a type of its own, or replace a built-in one, when building a bank:
+//!
+//! ```
+//! # use rust_payment_processor::bank::Bank;
+//! # use rust_payment_processor::errors::BankingError;
+//! # use rust_payment_processor::handler::TransactionHandler;
+//! # use rust_payment_processor::transaction::{RecordOptions, Transaction, TransactionRecord, TransactionType};
+//! # fn main() -> Result<(), String> {
+//! struct Bonus;
+//!
+//! impl TransactionHandler for Bonus {
+//!     fn process(&self, bank: &mut Bank, transaction: Transaction) -> Result<(), BankingError> {
+//!         let amount = transaction.amount.ok_or(BankingError::InvalidTransaction)?;
+//!         bank.account_mut(transaction.client, false)?.deposit(&amount)
+//!     }
+//! }
+//!
+//! let mut bank = Bank::builder().handler("bonus", Bonus)?.build();
+//! # let options = RecordOptions::default();
+//! # let deposit = options.transaction(TransactionRecord::new(TransactionType::Deposit, 1, 1, Some("5"))).unwrap();
+//! # let bonus = options.transaction(TransactionRecord::new(TransactionType::custom("bonus")?, 1, 2, Some("1"))).unwrap();
+//! # assert_eq!(2, bank.process_all(vec![deposit, bonus]).applied);
+//! # assert_eq!(rust_payment_processor::amount::Amount::from(6u32), bank.account(1).unwrap().total);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Handlers are given the transaction once its signature, if the bank checks signatures, has been
+//! verified. Anything else, including validating its amount and storing it so that it can be
+//! referred to later, is up to the handler; see the `Bank` methods used by the built-in handlers.
+use crate::account::Account;
+use crate::amount::Amount;
+use crate::bank::{Bank, DuplicateCheck};
+use crate::config::DisputePolicy;
+use crate::errors::BankingError;
+use crate::transaction::{Transaction, TransactionType};
+use rust_decimal::prelude::*;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// `TransactionHandler` applies transactions of one type to a bank.
+pub trait TransactionHandler: Send + Sync {
+    fn process(&self, bank: &mut Bank, transaction: Transaction) -> Result<(), BankingError>;
+}
+
+/// `Handlers` holds the handler of every transaction type a bank accepts, keyed by type name. It is
+/// the registry custom types are resolved against.
+#[derive(Clone)]
+pub struct Handlers {
+    handlers: HashMap<Arc<str>, Arc<dyn TransactionHandler>>,
+}
+
+impl Handlers {
+    /// Handlers for no types at all.
+    pub fn empty() -> Handlers {
+        Handlers {
+            handlers: HashMap::new(),
+        }
+    }
+
+    /// Handle transactions of the given type with `handler`, replacing any previous handler.
+    pub fn insert<H: TransactionHandler + 'static>(&mut self, kind: TransactionType, handler: H) {
+        let name = match kind {
+            TransactionType::Custom(name) => name,
+            kind => Arc::from(kind.name()),
+        };
+        self.handlers.insert(name, Arc::new(handler));
+    }
+
+    /// Returns the handler of the given type, if it has one.
+    pub fn get(&self, kind: &TransactionType) -> Option<Arc<dyn TransactionHandler>> {
+        self.handlers.get(kind.name()).cloned()
+    }
+}
+
+/// The handlers of the built-in types.
+impl Default for Handlers {
+    fn default() -> Handlers {
+        let mut handlers = Handlers::empty();
+        handlers.insert(TransactionType::Deposit, Deposit);
+        handlers.insert(TransactionType::Withdrawal, Withdrawal);
+        handlers.insert(TransactionType::Dispute, Dispute);
+        handlers.insert(TransactionType::Resolve, Resolve);
+        handlers.insert(TransactionType::Chargeback, Chargeback);
+        handlers.insert(TransactionType::Adjustment, Adjustment);
+        handlers.insert(TransactionType::PromoCredit, PromoCredit);
+        handlers
+    }
+}
+
+impl fmt::Debug for Handlers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut kinds: Vec<&str> = self.handlers.keys().map(|kind| &**kind).collect();
+        kinds.sort_unstable();
+        f.debug_tuple("Handlers").field(&kinds).finish()
+    }
+}
+
+//region Built-in handlers
+/// Credits the amount to the client's account, opening it if need be.
+pub struct Deposit;
+
+impl TransactionHandler for Deposit {
+    fn process(&self, bank: &mut Bank, transaction: Transaction) -> Result<(), BankingError> {
+        apply_funds(bank, transaction, true, Account::deposit)
+    }
+}
+
+/// Debits the amount from the client's account.
+pub struct Withdrawal;
+
+impl TransactionHandler for Withdrawal {
+    fn process(&self, bank: &mut Bank, transaction: Transaction) -> Result<(), BankingError> {
+        apply_funds(bank, transaction, false, Account::withdraw)
+    }
+}
+
+/// Validate a deposit or withdrawal and apply it to the client's account, storing it for later
+/// lookup. Duplicates skipped by the duplicate policy are not applied, and corrections it accepts
+/// replace the stored transaction.
+fn apply_funds<F>(bank: &mut Bank, mut transaction: Transaction, create: bool, operation: F) -> Result<(), BankingError>
+where
+    F: FnOnce(&mut Account, &Amount) -> Result<(), BankingError>,
+{
+    transaction.validate_with_precision(bank.config().decimal_places_for(&transaction), bank.config().strict)?;
+    match bank.check_duplicate(&transaction)? {
+        DuplicateCheck::Unique => {}
+        DuplicateCheck::Skip => {
+            bank.skip_duplicate(&transaction);
+            return Ok(());
+        }
+        DuplicateCheck::Correct => return bank.correct(transaction),
+    }
+    let account = bank.account_mut(transaction.client, create)?;
+    operation(account, &transaction.amount.unwrap_or_else(Amount::zero))?;
+    bank.store(transaction);
+    Ok(())
+}
+
+/// Holds the funds of a deposit, unless the `DisputePolicy` requires them to still be available.
+pub struct Dispute;
+
+impl TransactionHandler for Dispute {
+    fn process(&self, bank: &mut Bank, mut transaction: Transaction) -> Result<(), BankingError> {
+        let policy = bank.config().dispute_policy;
+        // promotional funds can not be held
+        let promotional = bank.promotional_balance(transaction.client);
+        let (stored_transaction, account) = bank.referenced_transaction(&mut transaction)?;
+        let amount = stored_transaction.amount.unwrap_or_else(Amount::zero);
+        if policy == DisputePolicy::RequireAvailableFunds && !account.locked {
+            let disputable = account.available.checked_sub(promotional).ok_or(BankingError::AmountOverflow)?;
+            if disputable < amount {
+                return Err(BankingError::InsufficientFunds);
+            }
+        }
+        account.dispute(&amount)?;
+        stored_transaction.under_dispute = true;
+        Ok(())
+    }
+}
+
+/// Releases the funds held by a dispute.
+pub struct Resolve;
+
+impl TransactionHandler for Resolve {
+    fn process(&self, bank: &mut Bank, mut transaction: Transaction) -> Result<(), BankingError> {
+        let (stored_transaction, account) = bank.referenced_transaction(&mut transaction)?;
+        account.resolve(&stored_transaction.amount.unwrap_or_else(Amount::zero))?;
+        stored_transaction.under_dispute = false;
+        Ok(())
+    }
+}
+
+/// Removes the funds held by a dispute and locks the account, assessing the bank's chargeback fee
+/// if it has one.
+pub struct Chargeback;
+
+impl TransactionHandler for Chargeback {
+    fn process(&self, bank: &mut Bank, mut transaction: Transaction) -> Result<(), BankingError> {
+        let (stored_transaction, account) = bank.referenced_transaction(&mut transaction)?;
+        account.chargeback(&stored_transaction.amount.unwrap_or_else(Amount::zero))?;
+        stored_transaction.under_dispute = false;
+        if let Some(fee) = bank.config().chargeback_fee {
+            bank.charge_fee(transaction.client, transaction.tx, fee)?;
+        }
+        Ok(())
+    }
+}
+
+/// Replaces the amount of a stored deposit or withdrawal, applying the difference to the account.
+pub struct Adjustment;
+
+impl TransactionHandler for Adjustment {
+    fn process(&self, bank: &mut Bank, mut transaction: Transaction) -> Result<(), BankingError> {
+        transaction.validate_with_precision(bank.config().decimal_places_for(&transaction), bank.config().strict)?;
+        bank.adjust(transaction.client, transaction.tx, transaction.amount.unwrap_or_else(Amount::zero))
+    }
+}
+
+/// Credits a promotional amount to the client's account, opening it if need be, until it expires.
+/// See `Bank::credit_promotion`.
+pub struct PromoCredit;
+
+impl TransactionHandler for PromoCredit {
+    fn process(&self, bank: &mut Bank, mut transaction: Transaction) -> Result<(), BankingError> {
+        transaction.validate_with_precision(bank.config().decimal_places_for(&transaction), bank.config().strict)?;
+        bank.credit_promotion(transaction)
+    }
+}
+//endregion
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "io")]
+    use crate::amount;
+    #[cfg(feature = "io")]
+    use crate::transaction::RecordOptions;
+    #[cfg(feature = "io")]
+    use rust_decimal::Decimal;
+
+    /// Credits a bonus to an existing account without storing it, so that it can not be disputed.
+    struct Bonus;
+
+    impl TransactionHandler for Bonus {
+        fn process(&self, bank: &mut Bank, transaction: Transaction) -> Result<(), BankingError> {
+            let amount = transaction.amount.ok_or(BankingError::InvalidTransaction)?;
+            bank.account_mut(transaction.client, false)?.deposit(&amount)
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn custom_types_are_applied_by_their_handler() -> Result<(), String> {
+        // SETUP
+        let mut bank = Bank::builder().handler("bonus", Bonus)?.build();
+        let data = "type,client,tx,amount\ndeposit,1,1,1.0\nbonus,1,2,0.5\nbonus,2,3,0.5\ndispute,1,2,\n";
+        let mut reader = csv::ReaderBuilder::new().from_reader(data.as_bytes());
+
+        // TEST
+        let summary = bank.process_record_set(&mut reader, &RecordOptions::default());
+        assert_eq!((2, 2), (summary.applied, summary.rejected));
+        assert_eq!(amount::from_decimal(Decimal::new(15, 1)), bank.total().ok());
+        assert!(Bank::builder().handler("", Bonus).is_err());
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn custom_types_are_resolved_against_the_handlers_of_each_bank() -> Result<(), BankingError> {
+        // SETUP
+        let mut with_handler = Bank::builder().handler("bonus", Bonus).expect("valid type name").build();
+        let mut without_handler = Bank::new();
+        let bonus = |tx| Transaction {
+            kind: TransactionType::Custom(Arc::from("bonus")),
+            ..Transaction::make(TransactionType::Deposit, 1, tx, 1u32, false)
+        };
+        for bank in [&mut with_handler, &mut without_handler] {
+            bank.process_transaction(Transaction::make(TransactionType::Deposit, 1, 1, 1u32, false))?;
+        }
+
+        // TEST
+        with_handler.process_transaction(bonus(2))?;
+        assert_eq!(Err(BankingError::InvalidTransaction), without_handler.process_transaction(bonus(2)));
+        assert_eq!(Ok(TransactionType::Custom(Arc::from("bonus"))), TransactionType::custom("bonus"));
+        assert!(TransactionType::custom("deposit").is_err());
+        Ok(())
+    }
+}
+//endregion
@@ -0,0 +1,71 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Hex encoding
+//! Lowercase hexadecimal, in which journal digests, transaction signatures, and keys are written.
+
+/// Format a 32-byte digest, signature, or key as lowercase hexadecimal.
+pub fn to_hex(digest: &[u8; 32]) -> String {
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Decode a hex string, returning `None` if it is not valid hex.
+pub fn from_hex(text: &str) -> Option<Vec<u8>> {
+    // checked up front, as `from_str_radix` would also accept a sign, as in "+1"
+    if !text.len().is_multiple_of(2) || !text.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| text.get(i..i + 2).and_then(|pair| u8::from_str_radix(pair, 16).ok()))
+        .collect()
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_hex_writes_two_lowercase_digits_per_byte() {
+        // SETUP
+        let mut digest = [0u8; 32];
+        digest[0] = 0x0a;
+        digest[31] = 0xff;
+
+        // TEST
+        let hex = to_hex(&digest);
+        assert_eq!(64, hex.len());
+        assert!(hex.starts_with("0a00"));
+        assert!(hex.ends_with("00ff"));
+    }
+
+    #[test]
+    fn from_hex_reads_what_to_hex_writes() {
+        // SETUP
+        let digest: [u8; 32] = std::array::from_fn(|i| (i * 8) as u8);
+
+        // TEST
+        assert_eq!(Some(digest.to_vec()), from_hex(&to_hex(&digest)));
+    }
+
+    #[test]
+    fn from_hex_accepts_uppercase_digits() {
+        // TEST
+        assert_eq!(Some(vec![0xab, 0xcd]), from_hex("ABcd"));
+        assert_eq!(Some(vec![]), from_hex(""));
+    }
+
+    #[test]
+    fn from_hex_rejects_an_odd_number_of_digits() {
+        // TEST
+        assert_eq!(None, from_hex("abc"));
+    }
+
+    #[test]
+    fn from_hex_rejects_characters_that_are_not_digits() {
+        // TEST
+        assert_eq!(None, from_hex("zz"));
+        assert_eq!(None, from_hex("+1"));
+        assert_eq!(None, from_hex("é"));
+    }
+}
+//endregion
@@ -0,0 +1,194 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Processed file history
+//! Running the same input file twice applies every deposit in it twice. To catch this, the SHA-256
+//! of each input file is recorded in a history file once it has been processed, and input whose
+//! hash is already recorded is refused (`--history` on the command line, with `--force` to process
+//! it anyway).
+//!
+//! The history is CSV with the columns `sha256, file, processed_at`, one row per processed file,
+//! appended to as files are processed. The hash is of the file as stored, so an encrypted file is
+//! hashed before it is decrypted.
+use crate::journal::{to_hex, Digest};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// `HistoryEntry` is a row of the history: a file that has been processed.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct HistoryEntry {
+    /// The hex-encoded SHA-256 of the file's contents.
+    pub sha256: String,
+    /// The path the file was processed from, for the operator's benefit; files are matched on their
+    /// hash alone.
+    pub file: String,
+    pub processed_at: DateTime<Utc>,
+}
+
+/// `FileHistory` is the set of files processed so far, backed by a history file.
+pub struct FileHistory {
+    path: PathBuf,
+    entries: Vec<HistoryEntry>,
+}
+
+impl FileHistory {
+    /// Load the history kept at `path`. A history that does not exist yet is empty.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<FileHistory, csv::Error> {
+        let path = path.as_ref().to_path_buf();
+        let entries = match File::open(&path) {
+            Ok(file) => csv::Reader::from_reader(file).deserialize().collect::<Result<_, _>>()?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(FileHistory { path, entries })
+    }
+
+    /// Returns the entry of an earlier file with the given hash, if there is one.
+    pub fn find(&self, digest: &Digest) -> Option<&HistoryEntry> {
+        let sha256 = to_hex(digest);
+        self.entries.iter().find(|entry| entry.sha256 == sha256)
+    }
+
+    /// Record that the file at `file`, with the given hash, has been processed.
+    pub fn record(&mut self, digest: &Digest, file: &Path) -> Result<(), csv::Error> {
+        let entry = HistoryEntry {
+            sha256: to_hex(digest),
+            file: file.display().to_string(),
+            processed_at: DateTime::<Utc>::from(SystemTime::now()),
+        };
+        let output = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        let empty = output.metadata()?.len() == 0;
+        let mut writer = csv::WriterBuilder::new().has_headers(empty).from_writer(output);
+        writer.serialize(&entry)?;
+        writer.flush()?;
+        self.entries.push(entry);
+        Ok(())
+    }
+}
+
+/// The SHA-256 of the contents of the file at `path`.
+pub fn hash_file<P: AsRef<Path>>(path: P) -> io::Result<Digest> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hasher.finalize().into())
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, process};
+
+    /// A directory of its own for the test `case`, holding an input file `input.csv`.
+    fn setup(case: &str) -> io::Result<PathBuf> {
+        let dir = std::env::temp_dir().join(format!("rust-payment-processor-history-{}-{}", case, process::id()));
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join("input.csv"), "type,client,tx,amount\ndeposit,1,1,1.0\n")?;
+        Ok(dir)
+    }
+
+    #[test]
+    fn history_that_does_not_exist_is_empty() -> Result<(), csv::Error> {
+        // SETUP
+        let dir = setup("empty")?;
+
+        // TEST
+        let history = FileHistory::open(dir.join("history.csv"))?;
+        assert_eq!(None, history.find(&hash_file(dir.join("input.csv"))?));
+
+        // TEARDOWN
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn recorded_files_are_found_by_hash() -> Result<(), csv::Error> {
+        // SETUP
+        let dir = setup("found")?;
+        let input = dir.join("input.csv");
+        let digest = hash_file(&input)?;
+        let mut history = FileHistory::open(dir.join("history.csv"))?;
+
+        // TEST
+        history.record(&digest, &input)?;
+        let entry = history.find(&digest).expect("recorded");
+        assert_eq!(
+            (to_hex(&digest), input.display().to_string()),
+            (entry.sha256.clone(), entry.file.clone())
+        );
+
+        // TEARDOWN
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn recorded_files_are_found_once_reopened() -> Result<(), csv::Error> {
+        // SETUP
+        let dir = setup("reopened")?;
+        let input = dir.join("input.csv");
+        let digest = hash_file(&input)?;
+        FileHistory::open(dir.join("history.csv"))?.record(&digest, &input)?;
+
+        // TEST
+        assert!(FileHistory::open(dir.join("history.csv"))?.find(&digest).is_some());
+
+        // TEARDOWN
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn changed_files_are_not_found() -> Result<(), csv::Error> {
+        // SETUP
+        let dir = setup("changed")?;
+        let input = dir.join("input.csv");
+        let mut history = FileHistory::open(dir.join("history.csv"))?;
+        history.record(&hash_file(&input)?, &input)?;
+
+        // TEST
+        fs::write(&input, "type,client,tx,amount\ndeposit,1,2,1.0\n")?;
+        assert_eq!(None, history.find(&hash_file(&input)?));
+
+        // TEARDOWN
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn history_is_appended_to_under_a_single_header() -> Result<(), csv::Error> {
+        // SETUP
+        let dir = setup("appended")?;
+        let (first, second) = (dir.join("input.csv"), dir.join("second.csv"));
+        fs::write(&second, "type,client,tx,amount\n")?;
+
+        // TEST
+        FileHistory::open(dir.join("history.csv"))?.record(&hash_file(&first)?, &first)?;
+        FileHistory::open(dir.join("history.csv"))?.record(&hash_file(&second)?, &second)?;
+        let written = fs::read_to_string(dir.join("history.csv"))?;
+        assert_eq!(3, written.lines().count());
+        assert!(written.starts_with("sha256,file,processed_at\n"));
+
+        // TEARDOWN
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn files_are_hashed_as_stored() -> io::Result<()> {
+        // SETUP
+        let dir = setup("hashed")?;
+
+        // TEST
+        let expected: Digest = Sha256::digest(b"type,client,tx,amount\ndeposit,1,1,1.0\n").into();
+        assert_eq!(expected, hash_file(dir.join("input.csv"))?);
+
+        // TEARDOWN
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
+//endregion
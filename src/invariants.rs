@@ -0,0 +1,112 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Ledger invariants
+//! A harness that processes transactions while checking the invariants every account must uphold,
+//! for use by property tests, fuzzing, and simulations:
+//!
+//! 1. `total` is always `available + held`
+//! 2. `held` is never negative
+//! 3. a locked account never changes, whatever transactions are applied to it
+//!
+//! `available` and `total` may legitimately become negative, when a deposit is disputed after some
+//! of its funds have been withdrawn.
+use crate::account::Account;
+use crate::amount::Amount;
+use crate::bank::Bank;
+use crate::errors::BankingError;
+use crate::transaction::Transaction;
+use rust_decimal::prelude::*;
+use std::fmt;
+
+/// `InvariantViolation` describes an account found in a state it should never reach.
+#[derive(Debug, PartialEq)]
+pub enum InvariantViolation {
+    /// `total` is not equal to `available + held`.
+    UnbalancedTotal(Account),
+    /// `held` is negative.
+    NegativeHeld(Account),
+    /// A locked account was changed by a transaction.
+    LockedAccountChanged { before: Box<Account>, after: Box<Account> },
+}
+
+impl fmt::Display for InvariantViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvariantViolation::UnbalancedTotal(account) => write!(f, "total is not available + held: {:?}", account),
+            InvariantViolation::NegativeHeld(account) => write!(f, "held is negative: {:?}", account),
+            InvariantViolation::LockedAccountChanged { before, after } => {
+                write!(f, "locked account changed from {:?} to {:?}", before, after)
+            }
+        }
+    }
+}
+
+/// Check the invariants that hold for every account at all times.
+pub fn check_account(account: &Account) -> Result<(), InvariantViolation> {
+    if account.total != account.available + account.held {
+        return Err(InvariantViolation::UnbalancedTotal(account.clone()));
+    }
+    if account.held < Amount::zero() {
+        return Err(InvariantViolation::NegativeHeld(account.clone()));
+    }
+    Ok(())
+}
+
+/// Check the invariants of every account of the bank.
+pub fn check_bank(bank: &Bank) -> Result<(), InvariantViolation> {
+    bank.accounts().try_for_each(check_account)
+}
+
+/// Process a single transaction, then check the invariants of the account it was applied to. The
+/// outer result reports a violated invariant; the inner one is the result of processing.
+pub fn process_checked(bank: &mut Bank, transaction: Transaction) -> Result<Result<(), BankingError>, InvariantViolation> {
+    let client = transaction.client;
+    let before = bank.account(client).cloned();
+    let result = bank.process_transaction(transaction);
+    if let Some(after) = bank.account(client) {
+        check_account(after)?;
+        if let Some(before) = before.filter(|before| before.locked && before != after) {
+            return Err(InvariantViolation::LockedAccountChanged {
+                before: Box::new(before),
+                after: Box::new(after.clone()),
+            });
+        }
+    }
+    Ok(result)
+}
+
+/// Process every transaction in order with `process_checked`, stopping at the first violation.
+pub fn process_all_checked<I: IntoIterator<Item = Transaction>>(bank: &mut Bank, transactions: I) -> Result<(), InvariantViolation> {
+    for transaction in transactions {
+        // transactions are expected to fail now and then; only a violation stops processing
+        let _ = process_checked(bank, transaction)?;
+    }
+    Ok(())
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate;
+    use proptest::prelude::*;
+
+    #[test]
+    fn unbalanced_account_is_reported() {
+        let mut account = Account::new(1);
+        account.total = Amount::from(1u32);
+        assert_eq!(Err(InvariantViolation::UnbalancedTotal(account.clone())), check_account(&account));
+    }
+
+    proptest! {
+        #[test]
+        fn any_transaction_sequence_preserves_invariants(transactions in generate::transactions(200)) {
+            // SETUP
+            let mut bank = Bank::new();
+
+            // TEST
+            prop_assert_eq!(Ok(()), process_all_checked(&mut bank, transactions));
+            prop_assert_eq!(Ok(()), check_bank(&bank));
+        }
+    }
+}
+//endregion
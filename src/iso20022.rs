@@ -0,0 +1,268 @@
+#![forbid(unsafe_code)] // for good measure
+//! # ISO 20022 import
+//! Maps ISO 20022 XML messages onto internal `Transaction`s so that bank-provided files can be
+//! processed without an external conversion step. Two message types are supported:
+//!
+//! 1. `pain.001` (Customer Credit Transfer Initiation). Every `CdtTrfTxInf` becomes a withdrawal
+//!    from the debtor account of its enclosing `PmtInf`.
+//! 2. `camt.054` (Bank To Customer Debit Credit Notification). Every `Ntry` becomes a deposit
+//!    (`CRDT`) or withdrawal (`DBIT`) on the account of its enclosing `Ntfctn`.
+//!
+//! Client and transaction IDs are numeric in this crate, so accounts must be identified by a
+//! numeric `Othr/Id` (not an IBAN), and the `EndToEndId` (pain.001) or `NtryRef` (camt.054) of each
+//! entry must be numeric as well. Entries that can not be mapped are logged and discarded, just
+//! like malformed CSV records.
+use crate::account::ClientId;
+use crate::amount::{parse_amount, AmountFormat};
+use crate::transaction::{Transaction, TransactionType, TxId};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use std::fmt;
+use std::io::BufRead;
+use std::str::FromStr;
+
+/// `Iso20022Error` is returned when a message can not be read at all.
+#[derive(Debug)]
+pub enum Iso20022Error {
+    /// The document is not well-formed XML, or could not be read.
+    Xml(quick_xml::Error),
+    /// The document is well-formed but is neither a pain.001 nor a camt.054 message.
+    UnsupportedMessage(String),
+}
+
+impl fmt::Display for Iso20022Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Iso20022Error::Xml(e) => write!(f, "malformed XML: {}", e),
+            Iso20022Error::UnsupportedMessage(name) => write!(f, "unsupported ISO 20022 message: {}", name),
+        }
+    }
+}
+
+impl From<quick_xml::Error> for Iso20022Error {
+    fn from(e: quick_xml::Error) -> Iso20022Error {
+        Iso20022Error::Xml(e)
+    }
+}
+
+impl From<quick_xml::events::attributes::AttrError> for Iso20022Error {
+    fn from(e: quick_xml::events::attributes::AttrError) -> Iso20022Error {
+        Iso20022Error::Xml(e.into())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Message {
+    CreditTransferInitiation,
+    DebitCreditNotification,
+}
+
+/// The fields collected for a single payment or entry while its element is open.
+#[derive(Default)]
+struct Entry {
+    kind: Option<TransactionType>,
+    tx: Option<String>,
+    amount: Option<String>,
+    currency: Option<String>,
+    timestamp: Option<String>,
+}
+
+/// Read every payment or entry in the given ISO 20022 message.
+pub fn read_transactions<R: BufRead>(input: R) -> Result<Vec<Transaction>, Iso20022Error> {
+    let mut reader = Reader::from_reader(input);
+    reader.config_mut().trim_text(true);
+
+    let mut buf = Vec::new();
+    let mut path: Vec<String> = Vec::new();
+    let mut message = None;
+    let mut client: Option<String> = None;
+    let mut created: Option<String> = None;
+    let mut entry: Option<Entry> = None;
+    let mut transactions = Vec::new();
+    let mut index = 0;
+
+    loop {
+        match reader.read_event_into(&mut buf)? {
+            Event::Start(e) => {
+                let name = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+                if path.len() == 1 {
+                    message = Some(match name.as_str() {
+                        "CstmrCdtTrfInitn" => Message::CreditTransferInitiation,
+                        "BkToCstmrDbtCdtNtfctn" => Message::DebitCreditNotification,
+                        _ => return Err(Iso20022Error::UnsupportedMessage(name)),
+                    });
+                }
+                match (message, name.as_str()) {
+                    (Some(Message::CreditTransferInitiation), "PmtInf") => client = None,
+                    (Some(Message::CreditTransferInitiation), "CdtTrfTxInf") => {
+                        entry = Some(Entry {
+                            kind: Some(TransactionType::Withdrawal),
+                            timestamp: created.clone(),
+                            ..Entry::default()
+                        })
+                    }
+                    (Some(Message::DebitCreditNotification), "Ntfctn") => client = None,
+                    (Some(Message::DebitCreditNotification), "Ntry") => entry = Some(Entry::default()),
+                    _ => {}
+                }
+                if name == "InstdAmt" || (name == "Amt" && path.last().map(String::as_str) == Some("Ntry")) {
+                    if let (Some(entry), Some(currency)) = (entry.as_mut(), e.try_get_attribute("Ccy")?) {
+                        entry.currency = Some(currency.unescape_value()?.into_owned());
+                    }
+                }
+                path.push(name);
+            }
+            Event::Text(e) => {
+                let text = e.unescape()?.into_owned();
+                let tail: Vec<&str> = path.iter().rev().take(5).rev().map(String::as_str).collect();
+                match (message, tail.as_slice()) {
+                    (_, [.., "GrpHdr", "CreDtTm"]) => created = Some(text.clone()),
+                    (Some(Message::CreditTransferInitiation), [.., "DbtrAcct", "Id", "Othr", "Id"]) => client = Some(text.clone()),
+                    (Some(Message::DebitCreditNotification), ["Ntfctn", "Acct", "Id", "Othr", "Id"]) => client = Some(text.clone()),
+                    _ => {}
+                }
+                if let Some(entry) = entry.as_mut() {
+                    match tail.as_slice() {
+                        [.., "PmtId", "EndToEndId"] | [.., "Ntry", "NtryRef"] => entry.tx = Some(text),
+                        [.., "Amt", "InstdAmt"] | [.., "Ntry", "Amt"] => entry.amount = Some(text),
+                        [.., "Ntry", "CdtDbtInd"] => {
+                            entry.kind = match text.as_str() {
+                                "CRDT" => Some(TransactionType::Deposit),
+                                "DBIT" => Some(TransactionType::Withdrawal),
+                                _ => None,
+                            }
+                        }
+                        [.., "BookgDt", "DtTm"] | [.., "BookgDt", "Dt"] => entry.timestamp = Some(text),
+                        _ => {}
+                    }
+                }
+            }
+            Event::End(e) => {
+                let name = e.local_name();
+                if name.as_ref() == b"CdtTrfTxInf" || name.as_ref() == b"Ntry" {
+                    if let Some(entry) = entry.take() {
+                        index += 1;
+                        match to_transaction(entry, client.as_deref()) {
+                            Ok(transaction) => transactions.push(transaction),
+                            Err(e) => error!("Failed to map ISO 20022 entry {}. Discarded with error: {}", index, e),
+                        }
+                    }
+                }
+                path.pop();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    match message {
+        Some(_) => Ok(transactions),
+        None => Err(Iso20022Error::UnsupportedMessage(String::from("<empty document>"))),
+    }
+}
+
+/// Map the collected fields of an entry onto a `Transaction`, describing the first field that is
+/// missing or can not be mapped.
+fn to_transaction(entry: Entry, client: Option<&str>) -> Result<Transaction, String> {
+    let client = client.ok_or("missing account Othr/Id")?;
+    let client = ClientId::from_str(client).map_err(|_| format!("account id `{}` is not a valid client id", client))?;
+    let tx = entry.tx.ok_or("missing transaction reference")?;
+    let tx = TxId::from_str(&tx).map_err(|_| format!("reference `{}` is not a valid transaction id", tx))?;
+    let kind = entry.kind.ok_or("missing or unsupported CdtDbtInd")?;
+    let amount = entry.amount.ok_or("missing amount")?;
+    let amount = parse_amount(&amount, &AmountFormat::new()).map_err(|e| format!("invalid amount `{}`: {}", amount, e))?;
+
+    let transaction = Transaction {
+        kind,
+        client,
+        tx,
+        amount: Some(amount),
+        under_dispute: false,
+        timestamp: entry.timestamp.as_deref().and_then(parse_timestamp),
+        currency: entry.currency,
+        signature: None,
+        correction: false,
+        provenance: None,
+        expires: None,
+    };
+    transaction.check_fields().map_err(|e| e.to_string())?;
+    Ok(transaction)
+}
+
+/// ISO 20022 date-times frequently omit the UTC offset; those are taken to be UTC already. Plain
+/// dates are taken to mean midnight UTC.
+fn parse_timestamp(text: &str) -> Option<DateTime<Utc>> {
+    if let Ok(timestamp) = DateTime::<Utc>::from_str(text) {
+        return Some(timestamp);
+    }
+    if let Ok(timestamp) = NaiveDateTime::from_str(text) {
+        return Some(timestamp.and_utc());
+    }
+    NaiveDate::from_str(text)
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|timestamp| timestamp.and_utc())
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amount::Amount;
+
+    const CAMT_054: &str = r#"<Document xmlns="urn:iso:std:iso:20022:tech:xsd:camt.054.001.08">
+      <BkToCstmrDbtCdtNtfctn>
+        <Ntfctn>
+          <Acct><Id><Othr><Id>7</Id></Othr></Id></Acct>
+          <Ntry><NtryRef>1</NtryRef><Amt Ccy="EUR">10.00</Amt><CdtDbtInd>CRDT</CdtDbtInd></Ntry>
+          <Ntry><NtryRef>abc</NtryRef><Amt Ccy="EUR">2.5</Amt><CdtDbtInd>DBIT</CdtDbtInd></Ntry>
+        </Ntfctn>
+      </BkToCstmrDbtCdtNtfctn>
+    </Document>"#;
+
+    const PAIN_001: &str = r#"<Document xmlns="urn:iso:std:iso:20022:tech:xsd:pain.001.001.09">
+      <CstmrCdtTrfInitn>
+        <GrpHdr><CreDtTm>2024-01-02T10:00:00</CreDtTm></GrpHdr>
+        <PmtInf>
+          <DbtrAcct><Id><Othr><Id>3</Id></Othr></Id></DbtrAcct>
+          <CdtTrfTxInf><PmtId><EndToEndId>11</EndToEndId></PmtId><Amt><InstdAmt Ccy="USD">1</InstdAmt></Amt></CdtTrfTxInf>
+        </PmtInf>
+      </CstmrCdtTrfInitn>
+    </Document>"#;
+
+    #[test]
+    fn camt_054_credit_maps_to_deposit_and_unmappable_entries_are_skipped() -> Result<(), Iso20022Error> {
+        // TEST
+        let transactions = read_transactions(CAMT_054.as_bytes())?;
+        assert_eq!(1, transactions.len());
+        assert_eq!(TransactionType::Deposit, transactions[0].kind);
+        assert_eq!((7, 1), (transactions[0].client, transactions[0].tx));
+        assert_eq!(Some(Amount::from(10u32)), transactions[0].amount);
+        assert_eq!(Some(String::from("EUR")), transactions[0].currency);
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn pain_001_transfer_maps_to_withdrawal_from_debtor() -> Result<(), Iso20022Error> {
+        // TEST
+        let transactions = read_transactions(PAIN_001.as_bytes())?;
+        assert_eq!(1, transactions.len());
+        assert_eq!(TransactionType::Withdrawal, transactions[0].kind);
+        assert_eq!((3, 11), (transactions[0].client, transactions[0].tx));
+        assert!(transactions[0].timestamp.is_some());
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn unsupported_message_returns_error() {
+        let result = read_transactions("<Document><Other/></Document>".as_bytes());
+        assert!(matches!(result, Err(Iso20022Error::UnsupportedMessage(_))));
+    }
+}
+//endregion
@@ -0,0 +1,275 @@
+#![forbid(unsafe_code)] // for good measure
+//! # ISO 8583 adapter
+//! Maps a simplified form of ISO 8583 financial messages onto internal `Transaction`s so that the
+//! engine can sit behind a card-switch simulator. Bitmaps and binary encodings are left to the
+//! switch; a message is a single line of `|`-separated data elements, each written as
+//! `<field>=<value>`, with the message type indicator written as `mti`:
+//!
+//! ```text
+//! mti=0200|2=9f86d081884c7d65|3=000000|4=000000001050|11=000123|49=840
+//! ```
+//!
+//! The data elements used are:
+//!
+//! | field | name                               | use                                       |
+//! |-------|------------------------------------|-------------------------------------------|
+//! | `mti` | message type indicator             | only `0200` and `0220` are mapped          |
+//! | 2     | primary account number             | a hash of the PAN, never the PAN itself    |
+//! | 3     | processing code                    | the transaction type (first two digits)    |
+//! | 4     | amount, transaction                | 12 digits in minor units (1/100ths)        |
+//! | 11    | system trace audit number          | the transaction ID                         |
+//! | 49    | currency code, transaction         | optional ISO 4217 numeric code             |
+//!
+//! Client IDs are numeric in this crate, so each PAN hash is assigned a client ID the first time it
+//! is seen. Known cards can be registered up front with `Iso8583Adapter::register`.
+use crate::account::ClientId;
+use crate::amount::{self, Amount};
+use crate::transaction::{Transaction, TransactionType, TxId};
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::BufRead;
+use std::str::FromStr;
+
+/// Field 4 amounts are carried in minor units with this many decimal places.
+const AMOUNT_EXPONENT: u32 = 2;
+
+/// `Iso8583Error` describes why a message could not be mapped onto a `Transaction`.
+#[derive(Debug, PartialEq)]
+pub enum Iso8583Error {
+    /// A data element was not written as `<field>=<value>`.
+    MalformedElement(String),
+    /// A data element required for the message type was absent.
+    MissingField(&'static str),
+    /// A data element was present but its value is invalid.
+    InvalidField(&'static str, String),
+    /// The message type indicator is not a financial message.
+    UnsupportedMessageType(String),
+    /// The processing code does not correspond to a supported transaction type.
+    UnsupportedProcessingCode(String),
+    /// Every client ID has already been assigned to a PAN hash.
+    ClientIdsExhausted,
+}
+
+impl fmt::Display for Iso8583Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Iso8583Error::MalformedElement(element) => write!(f, "malformed data element `{}`", element),
+            Iso8583Error::MissingField(field) => write!(f, "missing field {}", field),
+            Iso8583Error::InvalidField(field, value) => write!(f, "invalid value `{}` in field {}", value, field),
+            Iso8583Error::UnsupportedMessageType(mti) => write!(f, "unsupported message type {}", mti),
+            Iso8583Error::UnsupportedProcessingCode(code) => write!(f, "unsupported processing code {}", code),
+            Iso8583Error::ClientIdsExhausted => write!(f, "no client IDs left to assign"),
+        }
+    }
+}
+
+/// `Message` holds the data elements of a single simplified ISO 8583 message.
+#[derive(Debug, Default, PartialEq)]
+pub struct Message {
+    pub mti: String,
+    pub pan_hash: Option<String>,
+    pub processing_code: Option<String>,
+    pub amount: Option<String>,
+    pub stan: Option<String>,
+    pub currency: Option<String>,
+}
+
+impl FromStr for Message {
+    type Err = Iso8583Error;
+
+    fn from_str(line: &str) -> Result<Message, Iso8583Error> {
+        let mut message = Message::default();
+        for element in line.trim().split('|').filter(|element| !element.is_empty()) {
+            let (field, value) = element
+                .split_once('=')
+                .ok_or_else(|| Iso8583Error::MalformedElement(element.to_string()))?;
+            let value = value.trim().to_string();
+            match field.trim() {
+                "mti" => message.mti = value,
+                "2" => message.pan_hash = Some(value),
+                "3" => message.processing_code = Some(value),
+                "4" => message.amount = Some(value),
+                "11" => message.stan = Some(value),
+                "49" => message.currency = Some(value),
+                // other data elements are not needed to post the transaction
+                _ => {}
+            }
+        }
+        if message.mti.is_empty() {
+            return Err(Iso8583Error::MissingField("mti"));
+        }
+        Ok(message)
+    }
+}
+
+/// `Iso8583Adapter` maps messages onto transactions, keeping track of which client ID has been
+/// assigned to each PAN hash.
+#[derive(Debug, Default)]
+pub struct Iso8583Adapter {
+    clients: HashMap<String, ClientId>,
+    next_client: u64,
+}
+
+impl Iso8583Adapter {
+    pub fn new() -> Iso8583Adapter {
+        Iso8583Adapter::default()
+    }
+
+    /// Assign the given client ID to a PAN hash. Later messages for that card post to this client.
+    pub fn register(&mut self, pan_hash: &str, client: ClientId) {
+        self.clients.insert(pan_hash.to_string(), client);
+    }
+
+    /// Returns the client ID assigned to a PAN hash, if any.
+    pub fn client_for(&self, pan_hash: &str) -> Option<ClientId> {
+        self.clients.get(pan_hash).copied()
+    }
+
+    /// Returns the client ID of a PAN hash, assigning the lowest unused one if it has none.
+    fn client_or_assign(&mut self, pan_hash: &str) -> Result<ClientId, Iso8583Error> {
+        if let Some(client) = self.client_for(pan_hash) {
+            return Ok(client);
+        }
+        loop {
+            let client = ClientId::try_from(self.next_client).map_err(|_| Iso8583Error::ClientIdsExhausted)?;
+            self.next_client += 1;
+            if !self.clients.values().any(|assigned| *assigned == client) {
+                self.clients.insert(pan_hash.to_string(), client);
+                return Ok(client);
+            }
+        }
+    }
+
+    /// Map a message onto a `Transaction`.
+    ///
+    /// Only financial request and advice messages (`0200`, `0220`) move funds. The processing code
+    /// determines the transaction type:
+    /// 1. `00` (purchase) and `01` (cash withdrawal) become withdrawals
+    /// 2. `20` (refund) and `21` (deposit) become deposits
+    pub fn to_transaction(&mut self, message: &Message) -> Result<Transaction, Iso8583Error> {
+        if message.mti != "0200" && message.mti != "0220" {
+            return Err(Iso8583Error::UnsupportedMessageType(message.mti.clone()));
+        }
+        let code = message.processing_code.as_deref().ok_or(Iso8583Error::MissingField("3"))?;
+        let kind = match code.get(0..2) {
+            Some("00") | Some("01") => TransactionType::Withdrawal,
+            Some("20") | Some("21") => TransactionType::Deposit,
+            _ => return Err(Iso8583Error::UnsupportedProcessingCode(code.to_string())),
+        };
+        let amount = parse_minor_units(message.amount.as_deref().ok_or(Iso8583Error::MissingField("4"))?)?;
+        let stan = message.stan.as_deref().ok_or(Iso8583Error::MissingField("11"))?;
+        let tx = TxId::from_str(stan).map_err(|_| Iso8583Error::InvalidField("11", stan.to_string()))?;
+        let pan_hash = message.pan_hash.as_deref().ok_or(Iso8583Error::MissingField("2"))?;
+
+        let transaction = Transaction {
+            kind,
+            client: self.client_or_assign(pan_hash)?,
+            tx,
+            amount: Some(amount),
+            under_dispute: false,
+            timestamp: None,
+            currency: message.currency.clone(),
+            signature: None,
+            correction: false,
+            provenance: None,
+            expires: None,
+        };
+        transaction
+            .check_fields()
+            .map_err(|_| Iso8583Error::InvalidField("4", message.amount.clone().unwrap_or_default()))?;
+        Ok(transaction)
+    }
+
+    /// Read every message in the given input, one per line. Blank lines are ignored; messages that
+    /// can not be mapped are logged and discarded, just like malformed CSV records.
+    pub fn read_transactions<R: BufRead>(&mut self, input: R) -> std::io::Result<Vec<Transaction>> {
+        let mut transactions = Vec::new();
+        for (index, line) in input.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match Message::from_str(&line).and_then(|message| self.to_transaction(&message)) {
+                Ok(transaction) => transactions.push(transaction),
+                Err(e) => error!("Failed to map ISO 8583 message on line {}. Discarded with error: {}", index + 1, e),
+            }
+        }
+        Ok(transactions)
+    }
+}
+
+/// Parse a field 4 amount, which is a string of digits in minor units.
+fn parse_minor_units(value: &str) -> Result<Amount, Iso8583Error> {
+    let invalid = || Iso8583Error::InvalidField("4", value.to_string());
+    if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let minor = i64::from_str(value).map_err(|_| invalid())?;
+    amount::from_decimal(Decimal::new(minor, AMOUNT_EXPONENT).normalize()).ok_or_else(invalid)
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn purchase_and_refund_map_to_withdrawal_and_deposit_for_same_client() -> Result<(), Iso8583Error> {
+        // SETUP
+        let mut adapter = Iso8583Adapter::new();
+        adapter.register("cafe", 7);
+        let refund = Message::from_str("mti=0200|2=cafe|3=200000|4=000000001050|11=000001|49=840")?;
+        let purchase = Message::from_str("mti=0220|2=cafe|3=000000|4=000000000500|11=000002")?;
+
+        // TEST
+        let deposit = adapter.to_transaction(&refund)?;
+        assert_eq!(TransactionType::Deposit, deposit.kind);
+        assert_eq!((7, 1), (deposit.client, deposit.tx));
+        assert_eq!(amount::from_decimal(Decimal::new(105, 1)), deposit.amount);
+        assert_eq!(Some(String::from("840")), deposit.currency);
+        let withdrawal = adapter.to_transaction(&purchase)?;
+        assert_eq!(TransactionType::Withdrawal, withdrawal.kind);
+        assert_eq!(7, withdrawal.client);
+        assert_eq!(Some(Amount::from(5u32)), withdrawal.amount);
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_pan_hashes_are_assigned_unused_client_ids() -> Result<(), Iso8583Error> {
+        // SETUP
+        let mut adapter = Iso8583Adapter::new();
+        adapter.register("known", 0);
+        let message = Message::from_str("mti=0200|2=new|3=010000|4=000000000100|11=1")?;
+
+        // TEST
+        assert_eq!(1, adapter.to_transaction(&message)?.client);
+        assert_eq!(Some(1), adapter.client_for("new"));
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn non_financial_messages_and_bad_fields_are_rejected() {
+        let mut adapter = Iso8583Adapter::new();
+        let reject = |adapter: &mut Iso8583Adapter, line: &str| Message::from_str(line).and_then(|m| adapter.to_transaction(&m));
+        assert_eq!(
+            Err(Iso8583Error::UnsupportedMessageType(String::from("0100"))),
+            reject(&mut adapter, "mti=0100|2=a|3=000000|4=000000000100|11=1")
+        );
+        assert_eq!(
+            Err(Iso8583Error::UnsupportedProcessingCode(String::from("310000"))),
+            reject(&mut adapter, "mti=0200|2=a|3=310000|4=000000000100|11=1")
+        );
+        assert_eq!(
+            Err(Iso8583Error::InvalidField("4", String::from("000000000000"))),
+            reject(&mut adapter, "mti=0200|2=a|3=000000|4=000000000000|11=1")
+        );
+        assert_eq!(Err(Iso8583Error::MissingField("mti")), reject(&mut adapter, "3=000000"));
+    }
+}
+//endregion
@@ -0,0 +1,533 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Transaction journal
+//! Log-shipping replication to a hot standby. The primary appends every transaction it applies to
+//! a journal with `JournalWriter`; a `Follower` reads the journal and applies the same
+//! transactions, in the same order, to its own `Bank`.
+//!
+//! The journal is headerless CSV with the columns `seq, type, client, tx, amount, digest`. Before
+//! `digest`, an entry has a `timestamp` column if its transaction carries one, and a `source`
+//! column, naming the operator it is attributed to, if the journal was given one with
+//! `JournalWriter::with_source`; the timestamp is then left empty if there is none. Sequence
+//! numbers start at 1 and increase by one per entry, which is what lets a follower catch up after
+//! losing its place: entries it has already applied are skipped, and a gap is reported rather than
+//! silently applied around. Primary and follower must be built with the same `BankConfig`.
+//!
+//! The journal is tamper-evident: `digest` is the SHA-256 of the previous entry's digest (32 zero
+//! bytes for the first entry) followed by the other columns of the entry as written, joined with
+//! commas. Altering, removing, or reordering any entry changes every digest after it, so the final
+//! digest, recorded when the journal was written, vouches for the whole history. `verify` checks a
+//! journal without applying it.
+//!
+//! A journal also records the history of every account: `Bank::balance_at` replays one up to an
+//! `AsOf` point, to find the balances an account had at that point.
+use crate::account::ClientId;
+use crate::amount::{deserialize_amount, Amount};
+use crate::bank::Bank;
+use crate::errors::BankingError;
+pub use crate::hex::{from_hex, to_hex};
+use crate::sink::ResultSink;
+use crate::transaction::{Transaction, TransactionType, TxId};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Deserialize;
+use sha2::{Digest as _, Sha256};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// `Digest` is the SHA-256 digest closing a journal entry.
+pub type Digest = [u8; 32];
+
+/// The digest preceding the first entry of a journal.
+pub const GENESIS: Digest = [0; 32];
+
+/// The number of columns of an entry without a timestamp or source, including the digest.
+const COLUMNS: usize = 6;
+
+/// Chain the columns of an entry, other than its digest, onto the digest of the previous entry.
+fn chain<'a, I: IntoIterator<Item = &'a str>>(previous: &Digest, columns: I) -> Digest {
+    let mut hasher = Sha256::new();
+    hasher.update(previous);
+    for (index, column) in columns.into_iter().enumerate() {
+        if index > 0 {
+            hasher.update(b",");
+        }
+        hasher.update(column.as_bytes());
+    }
+    hasher.finalize().into()
+}
+
+/// Check the digest of an entry against the previous one, returning the entry's digest.
+fn check_digest(record: &csv::StringRecord, seq: u64, previous: &Digest) -> Result<Digest, JournalError> {
+    if record.len() < COLUMNS || record.len() > COLUMNS + 2 {
+        return Err(JournalError::Malformed { seq });
+    }
+    let digest = chain(previous, record.iter().take(record.len() - 1));
+    if to_hex(&digest) != record[record.len() - 1] {
+        return Err(JournalError::DigestMismatch { seq });
+    }
+    Ok(digest)
+}
+
+/// Read the sequence number of an entry.
+fn seq_of(record: &csv::StringRecord) -> Result<u64, JournalError> {
+    record
+        .get(0)
+        .and_then(|seq| seq.parse().ok())
+        .ok_or(JournalError::Malformed { seq: 0 })
+}
+
+/// `JournalEntry` is a single applied transaction.
+#[derive(Deserialize, Debug, PartialEq)]
+struct JournalEntry {
+    seq: u64,
+    #[serde(rename = "type")]
+    kind: TransactionType,
+    client: ClientId,
+    tx: TxId,
+    #[serde(deserialize_with = "deserialize_amount")]
+    amount: Option<Amount>,
+    /// Read from the optional columns by `read`.
+    #[serde(skip)]
+    timestamp: Option<DateTime<Utc>>,
+    #[serde(skip)]
+    source: Option<String>,
+}
+
+impl JournalEntry {
+    /// Read an entry whose digest has been checked.
+    fn read(record: &csv::StringRecord) -> Result<JournalEntry, JournalError> {
+        let seq = seq_of(record)?;
+        let columns: csv::StringRecord = record.iter().take(COLUMNS - 1).collect();
+        let mut entry: JournalEntry = columns.deserialize(None)?;
+        // the columns between the amount and the digest
+        let optional: Vec<&str> = record.iter().skip(COLUMNS - 1).take(record.len() - COLUMNS).collect();
+        if let Some(timestamp) = optional.first().filter(|timestamp| !timestamp.is_empty()) {
+            let timestamp = DateTime::parse_from_rfc3339(timestamp).map_err(|_| JournalError::Malformed { seq })?;
+            entry.timestamp = Some(timestamp.with_timezone(&Utc));
+        }
+        entry.source = optional.get(1).map(|source| source.to_string());
+        Ok(entry)
+    }
+
+    fn into_transaction(self) -> Transaction {
+        Transaction {
+            kind: self.kind,
+            client: self.client,
+            tx: self.tx,
+            amount: self.amount,
+            under_dispute: false,
+            timestamp: self.timestamp,
+            currency: None,
+            signature: None,
+            correction: false,
+            provenance: None,
+            expires: None,
+        }
+    }
+}
+
+/// `AsOf` is a point in the history recorded by a journal.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AsOf {
+    /// Just after the entry with this sequence number.
+    Seq(u64),
+    /// Just after the last entry at or before this time. An entry without a timestamp is taken to
+    /// be at the time of the entry before it.
+    Timestamp(DateTime<Utc>),
+    /// Just before the first entry of the transaction with this ID.
+    BeforeTx(TxId),
+}
+
+impl AsOf {
+    /// Whether an entry falls after this point.
+    fn precedes(&self, entry: &JournalEntry) -> bool {
+        match *self {
+            AsOf::Seq(seq) => entry.seq > seq,
+            AsOf::Timestamp(time) => entry.timestamp.is_some_and(|timestamp| timestamp > time),
+            AsOf::BeforeTx(tx) => entry.tx == tx,
+        }
+    }
+}
+
+/// `JournalError` describes why a journal could not be verified or applied.
+#[derive(Debug)]
+pub enum JournalError {
+    Io(io::Error),
+    /// An entry could not be parsed.
+    Parse(csv::Error),
+    /// An entry was found out of sequence; entries between the two were lost.
+    Gap {
+        expected: u64,
+        found: u64,
+    },
+    /// An entry applied by the primary was rejected by the follower, so the two have diverged.
+    Diverged {
+        seq: u64,
+        error: BankingError,
+    },
+    /// An entry does not have the expected columns. `seq` is 0 if it could not be read either.
+    Malformed {
+        seq: u64,
+    },
+    /// The digest of an entry does not match its contents and the entries before it: the journal
+    /// has been altered.
+    DigestMismatch {
+        seq: u64,
+    },
+}
+
+impl fmt::Display for JournalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JournalError::Io(e) => write!(f, "failed to read journal: {}", e),
+            JournalError::Parse(e) => write!(f, "failed to parse journal entry: {}", e),
+            JournalError::Gap { expected, found } => write!(f, "journal gap: expected entry {}, found {}", expected, found),
+            JournalError::Diverged { seq, error } => write!(f, "entry {} was rejected with {:?}", seq, error),
+            JournalError::Malformed { seq } => write!(f, "entry {} is malformed", seq),
+            JournalError::DigestMismatch { seq } => write!(f, "digest of entry {} does not match: the journal has been altered", seq),
+        }
+    }
+}
+
+impl From<io::Error> for JournalError {
+    fn from(e: io::Error) -> JournalError {
+        JournalError::Io(e)
+    }
+}
+
+impl From<csv::Error> for JournalError {
+    fn from(e: csv::Error) -> JournalError {
+        JournalError::Parse(e)
+    }
+}
+
+//region JournalWriter
+/// `JournalWriter` is a sink that appends every applied transaction to the journal. Each entry is
+/// flushed as it is written so that followers see it as soon as possible.
+pub struct JournalWriter<W: io::Write> {
+    writer: csv::Writer<W>,
+    seq: u64,
+    digest: Digest,
+    source: Option<String>,
+}
+
+impl<W: io::Write> JournalWriter<W> {
+    /// Start a new journal.
+    pub fn new(writer: W) -> JournalWriter<W> {
+        JournalWriter::resume(writer, 0, GENESIS)
+    }
+
+    /// Continue a journal whose last entry has the given sequence number and digest.
+    pub fn resume(writer: W, seq: u64, digest: Digest) -> JournalWriter<W> {
+        JournalWriter {
+            // entries with a timestamp or source have more columns
+            writer: csv::WriterBuilder::new().has_headers(false).flexible(true).from_writer(writer),
+            seq,
+            digest,
+            source: None,
+        }
+    }
+
+    /// Attribute every entry written from now on to `source`, the operator or source of the
+    /// transactions, e.g. the name of their input file.
+    pub fn with_source(mut self, source: String) -> JournalWriter<W> {
+        self.source = Some(source);
+        self
+    }
+
+    /// The sequence number of the last entry written.
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    /// The digest of the last entry written, vouching for the journal up to and including it.
+    pub fn digest(&self) -> Digest {
+        self.digest
+    }
+
+    fn append(&mut self, transaction: &Transaction) -> csv::Result<()> {
+        let mut columns = vec![
+            (self.seq + 1).to_string(),
+            transaction.kind.to_string(),
+            transaction.client.to_string(),
+            transaction.tx.to_string(),
+            transaction.amount.map(|amount| amount.to_string()).unwrap_or_default(),
+        ];
+        let timestamp = transaction.timestamp.map(|timestamp| timestamp.to_rfc3339_opts(SecondsFormat::AutoSi, true));
+        match (timestamp, &self.source) {
+            (timestamp, Some(source)) => columns.extend([timestamp.unwrap_or_default(), source.clone()]),
+            (Some(timestamp), None) => columns.push(timestamp),
+            (None, None) => {}
+        }
+        let digest = chain(&self.digest, columns.iter().map(String::as_str));
+        self.writer
+            .write_record(columns.iter().map(String::as_str).chain(Some(to_hex(&digest).as_str())))?;
+        self.writer.flush()?;
+        self.seq += 1;
+        self.digest = digest;
+        Ok(())
+    }
+}
+
+impl<W: io::Write> ResultSink for JournalWriter<W> {
+    fn record(&mut self, _index: usize, transaction: &Transaction, result: Result<(), BankingError>) {
+        if result.is_err() {
+            return;
+        }
+        if let Err(e) = self.append(transaction) {
+            error!("Failed to journal transaction {}. Aborted with error: {}", transaction.tx, e);
+        }
+    }
+}
+
+/// Verify a journal from its first entry, without applying it. Returns the number of entries and
+/// the final digest, which can be compared with the one recorded when the journal was written.
+pub fn verify<R: Read>(input: R) -> Result<(u64, Digest), JournalError> {
+    let mut reader = csv::ReaderBuilder::new().has_headers(false).flexible(true).from_reader(input);
+    let (mut seq, mut digest) = (0, GENESIS);
+    for record in reader.records() {
+        let record = record?;
+        let found = seq_of(&record)?;
+        if found != seq + 1 {
+            return Err(JournalError::Gap { expected: seq + 1, found });
+        }
+        digest = check_digest(&record, found, &digest)?;
+        seq = found;
+    }
+    Ok((seq, digest))
+}
+//endregion
+
+//region Follower
+/// `Follower` maintains a standby `Bank` by applying a journal written by the primary.
+pub struct Follower {
+    bank: Bank,
+    /// The sequence number of the last entry applied.
+    applied: u64,
+    /// The digest of the last entry applied.
+    digest: Digest,
+    /// How far into the journal file complete entries have been read.
+    offset: u64,
+}
+
+impl Follower {
+    pub fn new(bank: Bank) -> Follower {
+        Follower {
+            bank,
+            applied: 0,
+            digest: GENESIS,
+            offset: 0,
+        }
+    }
+
+    pub fn bank(&self) -> &Bank {
+        &self.bank
+    }
+
+    /// The standby's bank, e.g. to carry on processing from the journaled state.
+    pub fn into_bank(self) -> Bank {
+        self.bank
+    }
+
+    /// The sequence number of the last entry applied.
+    pub fn applied(&self) -> u64 {
+        self.applied
+    }
+
+    /// Apply every complete entry of `input` that has not been applied yet, returning how many were
+    /// applied. A trailing partial line, as left by a primary in the middle of a write, is ignored;
+    /// it is picked up once complete by reading the journal again.
+    pub fn apply<R: Read>(&mut self, input: R) -> Result<usize, JournalError> {
+        self.apply_complete(input, None).map(|(applied, _)| applied)
+    }
+
+    /// As `apply`, stopping at the given point of the journal's history. Returns how many entries
+    /// were applied.
+    pub fn apply_until<R: Read>(&mut self, input: R, until: AsOf) -> Result<usize, JournalError> {
+        self.apply_complete(input, Some(until)).map(|(applied, _)| applied)
+    }
+
+    /// Apply new entries of the journal file at `path`, reading on from where the previous call
+    /// stopped. If the file is shorter than before it is assumed to have been replaced, and is read
+    /// again from the start; entries already applied are skipped by sequence number.
+    pub fn catch_up<P: AsRef<Path>>(&mut self, path: P) -> Result<usize, JournalError> {
+        let mut file = File::open(path)?;
+        if file.metadata()?.len() < self.offset {
+            self.offset = 0;
+        }
+        file.seek(SeekFrom::Start(self.offset))?;
+        let (applied, consumed) = self.apply_complete(file, None)?;
+        self.offset += consumed as u64;
+        Ok(applied)
+    }
+
+    /// Apply the complete lines of `input`, up to `until` if given, returning the number of entries
+    /// applied and the number of bytes consumed.
+    fn apply_complete<R: Read>(&mut self, mut input: R, until: Option<AsOf>) -> Result<(usize, usize), JournalError> {
+        let mut buffer = Vec::new();
+        input.read_to_end(&mut buffer)?;
+        let complete = buffer.iter().rposition(|&byte| byte == b'\n').map_or(0, |last| last + 1);
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(&buffer[..complete]);
+        let mut applied = 0;
+        for record in reader.records() {
+            let record = record?;
+            let seq = seq_of(&record)?;
+            if seq <= self.applied {
+                continue;
+            }
+            if seq != self.applied + 1 {
+                return Err(JournalError::Gap {
+                    expected: self.applied + 1,
+                    found: seq,
+                });
+            }
+            let digest = check_digest(&record, seq, &self.digest)?;
+            let mut entry = JournalEntry::read(&record)?;
+            if until.is_some_and(|until| until.precedes(&entry)) {
+                break;
+            }
+            // attributed as on the primary
+            self.bank.set_operator(entry.source.take());
+            self.bank
+                .process_transaction(entry.into_transaction())
+                .map_err(|error| JournalError::Diverged { seq, error })?;
+            self.applied = seq;
+            self.digest = digest;
+            applied += 1;
+        }
+        Ok((applied, complete))
+    }
+}
+//endregion
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testkit::Scenario;
+
+    /// Run the scenario on a primary, returning the primary and its journal.
+    fn journal_of(scenario: Scenario) -> (Bank, Vec<u8>) {
+        let mut primary = Bank::new();
+        let mut journal = JournalWriter::new(Vec::new());
+        primary.process_all_with(scenario.transactions().to_vec(), &mut journal);
+        (primary, journal.writer.into_inner().unwrap())
+    }
+
+    #[test]
+    fn follower_replays_only_applied_transactions() -> Result<(), JournalError> {
+        // SETUP
+        let scenario = Scenario::new().deposit(1, 5).withdraw(1, 9).dispute(1, 1).deposit(2, 3);
+        let (primary, journal) = journal_of(scenario);
+        let mut follower = Follower::new(Bank::new());
+
+        // TEST
+        assert_eq!(3, follower.apply(&journal[..])?);
+        assert_eq!(3, follower.applied());
+        assert_eq!(primary.account(1), follower.bank().account(1));
+        assert_eq!(primary.account(2), follower.bank().account(2));
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn follower_catches_up_without_reapplying_and_waits_for_partial_entries() -> Result<(), JournalError> {
+        // SETUP
+        let (_, journal) = journal_of(Scenario::new().deposit(1, 5).deposit(1, 2));
+        let mut follower = Follower::new(Bank::new());
+
+        // TEST
+        assert_eq!(1, follower.apply(&journal[..journal.len() - 3])?);
+        assert_eq!(1, follower.apply(&journal[..])?);
+        assert_eq!(0, follower.apply(&journal[..])?);
+        assert_eq!(Ok(Amount::from(7u32)), follower.bank().total());
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn verify_detects_altered_entries() -> Result<(), JournalError> {
+        // SETUP
+        let mut journal = JournalWriter::new(Vec::new());
+        Bank::new().process_all_with(Scenario::new().deposit(1, 5).deposit(1, 2).transactions().to_vec(), &mut journal);
+        let digest = journal.digest();
+        let written = String::from_utf8(journal.writer.into_inner().unwrap()).unwrap();
+        let altered = written.replacen(",1,1,5,", ",1,1,50,", 1);
+
+        // TEST
+        assert_eq!((2, digest), verify(written.as_bytes())?);
+        match verify(altered.as_bytes()) {
+            Err(JournalError::DigestMismatch { seq: 1 }) => {}
+            other => panic!("expected a digest mismatch, got {:?}", other),
+        }
+        match Follower::new(Bank::new()).apply(altered.as_bytes()) {
+            Err(JournalError::DigestMismatch { seq: 1 }) => {}
+            other => panic!("expected a digest mismatch, got {:?}", other),
+        }
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn balance_at_replays_the_journal_up_to_the_point() -> Result<(), JournalError> {
+        // SETUP
+        let at = |day: u32| Some(DateTime::parse_from_rfc3339(&format!("2021-03-0{}T12:00:00Z", day)).unwrap().with_timezone(&Utc));
+        let scenario = Scenario::new()
+            .then(Transaction { timestamp: at(1), ..Transaction::make(TransactionType::Deposit, 1, 1, 5, false) })
+            .then(Transaction { timestamp: at(2), ..Transaction::make(TransactionType::Withdrawal, 1, 2, 2, false) })
+            .then(Transaction { timestamp: at(3), ..Transaction::make(TransactionType::Deposit, 1, 3, 4, false) });
+        let (primary, journal) = journal_of(scenario);
+        let available = |at| primary.balance_at(&journal[..], 1, at).map(|account| account.map(|account| account.available));
+
+        // TEST
+        assert_eq!(3, verify(&journal[..])?.0);
+        assert_eq!(Some(Amount::from(5u32)), available(AsOf::Seq(1))?);
+        assert_eq!(Some(Amount::from(3u32)), available(AsOf::BeforeTx(3))?);
+        assert_eq!(Some(Amount::from(3u32)), available(AsOf::Timestamp(at(2).unwrap()))?);
+        assert_eq!(None, available(AsOf::BeforeTx(1))?);
+        assert_eq!(Some(Amount::from(7u32)), available(AsOf::Seq(9))?);
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn follower_attributes_entries_to_their_source() -> Result<(), JournalError> {
+        // SETUP
+        let mut journal = JournalWriter::new(Vec::new()).with_source(String::from("batch-7.csv"));
+        Bank::new().process_all_with(Scenario::new().deposit(1, 5).transactions().to_vec(), &mut journal);
+        let journal = journal.writer.into_inner().unwrap();
+        let mut follower = Follower::new(Bank::builder().audit(true).build());
+
+        // TEST
+        assert_eq!(1, verify(&journal[..])?.0);
+        assert_eq!(1, follower.apply(&journal[..])?);
+        let trail = follower.bank().audit_trail(1);
+        assert_eq!(Some("batch-7.csv"), trail[0].operator.as_deref());
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn follower_reports_gaps() {
+        // SETUP
+        let mut follower = Follower::new(Bank::new());
+
+        // TEST
+        match follower.apply(&b"2,deposit,1,2,5,00\n"[..]) {
+            Err(JournalError::Gap { expected: 1, found: 2 }) => {}
+            other => panic!("expected a gap, got {:?}", other),
+        }
+
+        // TEARDOWN
+    }
+}
+//endregion
@@ -0,0 +1,115 @@
+#![cfg_attr(not(feature = "ffi"), forbid(unsafe_code))] // for good measure
+// the C API needs raw pointers; every other module still forbids unsafe code itself
+#![cfg_attr(feature = "ffi", deny(unsafe_code))]
+#[macro_use]
+extern crate log;
+
+pub mod account;
+#[cfg(feature = "io")]
+pub mod aging;
+#[cfg(feature = "io")]
+pub mod alert;
+pub mod amount;
+#[cfg(feature = "io")]
+pub mod analytics;
+#[cfg(all(feature = "io", feature = "signature"))]
+pub mod anonymize;
+pub mod audit;
+pub mod bank;
+#[cfg(feature = "io")]
+pub mod batch;
+#[cfg(feature = "retention")]
+pub mod bloom;
+#[cfg(feature = "calendar")]
+pub mod calendar;
+#[cfg(feature = "sink")]
+pub mod cancel;
+#[cfg(feature = "io")]
+pub mod charset;
+#[cfg(feature = "io")]
+pub mod client;
+#[cfg(feature = "arrow")]
+pub mod columnar;
+pub mod config;
+#[cfg(feature = "io")]
+pub mod control;
+#[cfg(feature = "io")]
+pub mod ctr;
+#[cfg(feature = "currency")]
+pub mod currency;
+#[cfg(feature = "io")]
+pub mod diff;
+#[cfg(feature = "encryption")]
+pub mod encryption;
+pub mod errors;
+#[cfg(any(test, feature = "fault-injection"))]
+pub mod fault;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "io")]
+pub mod filter;
+#[cfg(any(test, feature = "property-testing"))]
+pub mod generate;
+#[cfg(feature = "io")]
+pub mod graph;
+pub mod handler;
+#[cfg(any(feature = "io", feature = "signature", feature = "encryption"))]
+pub mod hex;
+#[cfg(feature = "io")]
+pub mod history;
+#[cfg(any(test, feature = "property-testing"))]
+pub mod invariants;
+#[cfg(feature = "iso20022")]
+pub mod iso20022;
+#[cfg(feature = "iso8583")]
+pub mod iso8583;
+#[cfg(feature = "io")]
+pub mod journal;
+#[cfg(feature = "io")]
+pub mod lint;
+pub mod locale;
+#[cfg(feature = "io")]
+pub mod manifest;
+#[cfg(any(test, feature = "differential"))]
+pub mod model;
+#[cfg(feature = "msgpack")]
+pub mod msgpack;
+#[cfg(feature = "parallel")]
+pub mod pipeline;
+#[cfg(feature = "io")]
+pub mod position;
+#[cfg(feature = "io")]
+pub mod profile;
+pub mod provenance;
+#[cfg(feature = "io")]
+pub mod recovery;
+#[cfg(feature = "protobuf")]
+pub mod protobuf;
+pub mod redact;
+#[cfg(feature = "io")]
+pub mod risk;
+#[cfg(feature = "saga")]
+pub mod saga;
+#[cfg(feature = "io")]
+pub mod schema;
+#[cfg(any(test, feature = "testkit"))]
+pub mod script;
+#[cfg(feature = "signature")]
+pub mod signature;
+#[cfg(any(test, feature = "simulation"))]
+pub mod simulation;
+#[cfg(feature = "sink")]
+pub mod sink;
+#[cfg(feature = "io")]
+pub mod snapshot;
+#[cfg(feature = "io")]
+pub mod stats;
+#[cfg(feature = "table")]
+pub mod table;
+#[cfg(any(test, feature = "testkit"))]
+pub mod testkit;
+pub mod transaction;
+#[cfg(feature = "tui")]
+pub mod tui;
+#[cfg(feature = "wasm")]
+pub mod wasm;
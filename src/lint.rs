@@ -0,0 +1,333 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Input lint
+//! A pre-flight check of a CSV input file for structural problems that would otherwise only show
+//! up as rejections during a run, or not at all:
+//!
+//! * a deposit or withdrawal reusing the ID of an earlier one;
+//! * a dispute, resolve, chargeback, or adjustment referring to a transaction that does not exist,
+//!   comes later in the file, or belongs to another client;
+//! * a resolve or chargeback of a transaction that is not under dispute at that point;
+//! * a dispute of a transaction that is already under dispute;
+//! * an amount with more decimal places than allowed, or a record that can not be read at all.
+//!
+//! `lint` reads the file once, keeping the line and client of every deposit and withdrawal.
+use crate::account::ClientId;
+use crate::amount::{parse_amount, AmountFormat};
+use crate::errors::AmountParseError;
+use crate::transaction::{TransactionType, TxId};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io;
+
+/// `Problem` enumerates the problems found by `lint`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Problem {
+    /// The transaction ID was already used on the given line.
+    DuplicateTx { first_line: u64 },
+    /// The referenced transaction does not exist anywhere in the file.
+    UnknownReference,
+    /// The referenced transaction only comes later, on the given line.
+    LaterReference { line: u64 },
+    /// The referenced transaction belongs to another client.
+    ClientMismatch { owner: ClientId },
+    /// A resolve or chargeback of a transaction that is not under dispute.
+    NotDisputed,
+    /// A dispute of a transaction that is already under dispute.
+    AlreadyDisputed,
+    /// The amount has more than the allowed number of decimal places.
+    TooPrecise { max: u32 },
+    /// The record can not be read as a transaction.
+    Malformed(String),
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::DuplicateTx { first_line } => write!(f, "transaction ID already used on line {}", first_line),
+            Problem::UnknownReference => write!(f, "refers to a transaction that does not exist"),
+            Problem::LaterReference { line } => write!(f, "refers to a transaction that only comes later, on line {}", line),
+            Problem::ClientMismatch { owner } => write!(f, "refers to a transaction of client {}", owner),
+            Problem::NotDisputed => write!(f, "refers to a transaction that is not under dispute"),
+            Problem::AlreadyDisputed => write!(f, "refers to a transaction that is already under dispute"),
+            Problem::TooPrecise { max } => write!(f, "amount has more than {} decimal places", max),
+            Problem::Malformed(reason) => write!(f, "malformed record: {}", reason),
+        }
+    }
+}
+
+/// `Finding` is a problem found on a line of the input.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Finding {
+    pub line: u64,
+    pub tx: Option<TxId>,
+    pub problem: Problem,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.tx {
+            Some(tx) => write!(f, "line {}: tx {}: {}", self.line, tx, self.problem),
+            None => write!(f, "line {}: {}", self.line, self.problem),
+        }
+    }
+}
+
+/// Check every record of a CSV input with a header row, allowing amounts with up to
+/// `max_decimal_places` decimal places. Findings are ordered by line.
+pub fn lint<R: io::Read>(reader: &mut csv::Reader<R>, max_decimal_places: u32) -> Result<Vec<Finding>, csv::Error> {
+    let headers = reader.headers()?.clone();
+    let column = |name: &str| headers.iter().position(|header| header.trim() == name);
+    let (kind, client, tx, amount) = match (column("type"), column("client"), column("tx"), column("amount")) {
+        (Some(kind), Some(client), Some(tx), amount) => (kind, client, tx, amount),
+        _ => {
+            return Ok(vec![Finding {
+                line: 1,
+                tx: None,
+                problem: Problem::Malformed(String::from("expected type, client, and tx columns")),
+            }])
+        }
+    };
+    let format = AmountFormat {
+        max_decimal_places: Some(max_decimal_places),
+        ..AmountFormat::new()
+    };
+    let mut findings = Vec::new();
+    // the line and client of every deposit and withdrawal seen so far
+    let mut funds: HashMap<TxId, (u64, ClientId)> = HashMap::new();
+    let mut disputed: HashSet<TxId> = HashSet::new();
+    // references to transactions not seen yet, which may still come later
+    let mut forward: Vec<(u64, TxId)> = Vec::new();
+    let mut record = csv::StringRecord::new();
+    while reader.read_record(&mut record)? {
+        let line = record.position().map_or(0, |position| position.line());
+        let field = |index: usize| record.get(index).unwrap_or_default().trim();
+        let parsed = field(kind)
+            .parse::<TransactionType>()
+            .and_then(|kind| {
+                field(client)
+                    .parse::<ClientId>()
+                    .map(|client| (kind, client))
+                    .map_err(|e| e.to_string())
+            })
+            .and_then(|(kind, client)| field(tx).parse::<TxId>().map(|tx| (kind, client, tx)).map_err(|e| e.to_string()));
+        let (kind, client, tx) = match parsed {
+            Ok(parsed) => parsed,
+            Err(reason) => {
+                findings.push(Finding {
+                    line,
+                    tx: None,
+                    problem: Problem::Malformed(reason),
+                });
+                continue;
+            }
+        };
+        let mut found = |problem| {
+            findings.push(Finding {
+                line,
+                tx: Some(tx),
+                problem,
+            })
+        };
+        if let Some(text) = amount.map(field).filter(|text| !text.is_empty()) {
+            match parse_amount(text, &format) {
+                Ok(_) => {}
+                Err(AmountParseError::TooManyDecimalPlaces(max)) => found(Problem::TooPrecise { max }),
+                Err(e) => found(Problem::Malformed(e.to_string())),
+            }
+        }
+        match kind {
+            TransactionType::Deposit | TransactionType::Withdrawal => match funds.get(&tx) {
+                Some(&(first_line, _)) => found(Problem::DuplicateTx { first_line }),
+                None => {
+                    funds.insert(tx, (line, client));
+                }
+            },
+            TransactionType::Dispute | TransactionType::Resolve | TransactionType::Chargeback | TransactionType::Adjustment => {
+                match funds.get(&tx) {
+                    None => forward.push((line, tx)),
+                    Some(&(_, owner)) if owner != client => found(Problem::ClientMismatch { owner }),
+                    Some(_) => match kind {
+                        TransactionType::Dispute if !disputed.insert(tx) => found(Problem::AlreadyDisputed),
+                        TransactionType::Resolve | TransactionType::Chargeback if !disputed.remove(&tx) => found(Problem::NotDisputed),
+                        _ => {}
+                    },
+                }
+            }
+            TransactionType::PromoCredit | TransactionType::Custom(_) => {}
+        }
+    }
+    for (line, tx) in forward {
+        let problem = match funds.get(&tx) {
+            Some(&(line, _)) => Problem::LaterReference { line },
+            None => Problem::UnknownReference,
+        };
+        findings.push(Finding {
+            line,
+            tx: Some(tx),
+            problem,
+        });
+    }
+    findings.sort_by_key(|finding| finding.line);
+    Ok(findings)
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The lines and problems found in CSV `records` under a `type,client,tx,amount` header row,
+    /// allowing four decimal places.
+    fn problems(records: &str) -> Result<Vec<(u64, Problem)>, csv::Error> {
+        let input = format!("type,client,tx,amount\n{}", records);
+        let mut reader = csv::ReaderBuilder::new().from_reader(input.as_bytes());
+        Ok(lint(&mut reader, 4)?
+            .into_iter()
+            .map(|finding| (finding.line, finding.problem))
+            .collect())
+    }
+
+    #[test]
+    fn well_formed_input_has_no_findings() -> Result<(), csv::Error> {
+        // TEST
+        assert!(problems("deposit,1,1,1.0\ndispute,1,1,\nresolve,1,1,\ndispute,1,1,\nchargeback,1,1,\n")?.is_empty());
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn reused_transaction_id_is_a_duplicate() -> Result<(), csv::Error> {
+        // TEST
+        let problems = problems("deposit,1,1,1.0\nwithdrawal,2,1,1.0\n")?;
+        assert_eq!(vec![(3, Problem::DuplicateTx { first_line: 2 })], problems);
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn reference_to_a_transaction_that_does_not_exist_is_reported() -> Result<(), csv::Error> {
+        // TEST
+        assert_eq!(vec![(2, Problem::UnknownReference)], problems("chargeback,1,9,\n")?);
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn reference_to_a_later_transaction_is_reported_with_its_line() -> Result<(), csv::Error> {
+        // TEST
+        assert_eq!(
+            vec![(2, Problem::LaterReference { line: 3 })],
+            problems("dispute,1,1,\ndeposit,1,1,1.0\n")?
+        );
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn reference_to_a_transaction_of_another_client_is_reported() -> Result<(), csv::Error> {
+        // TEST
+        assert_eq!(
+            vec![(3, Problem::ClientMismatch { owner: 1 })],
+            problems("deposit,1,1,1.0\ndispute,2,1,\n")?
+        );
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn settling_a_transaction_not_under_dispute_is_reported() -> Result<(), csv::Error> {
+        // TEST
+        let problems = problems("deposit,1,1,1.0\nresolve,1,1,\ndispute,1,1,\nresolve,1,1,\nchargeback,1,1,\n")?;
+        assert_eq!(vec![(3, Problem::NotDisputed), (6, Problem::NotDisputed)], problems);
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn disputing_a_transaction_already_under_dispute_is_reported() -> Result<(), csv::Error> {
+        // TEST
+        assert_eq!(
+            vec![(4, Problem::AlreadyDisputed)],
+            problems("deposit,1,1,1.0\ndispute,1,1,\ndispute,1,1,\n")?
+        );
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn amounts_with_too_many_decimal_places_are_reported() -> Result<(), csv::Error> {
+        // TEST
+        assert_eq!(vec![(2, Problem::TooPrecise { max: 4 })], problems("deposit,1,1,0.123456\n")?);
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn records_that_can_not_be_read_are_malformed() -> Result<(), csv::Error> {
+        // TEST
+        let problems = problems("refund,1,1,1.0\ndeposit,x,2,1.0\ndeposit,1,3,lots\n")?;
+        assert_eq!(3, problems.len());
+        assert_eq!(
+            (2, Problem::Malformed(String::from("unknown transaction type: refund"))),
+            problems[0]
+        );
+        assert!(problems.iter().all(|(_, problem)| matches!(problem, Problem::Malformed(_))));
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn input_without_the_required_columns_is_malformed() -> Result<(), csv::Error> {
+        // SETUP
+        let mut reader = csv::ReaderBuilder::new().from_reader("type,client,amount\ndeposit,1,1.0\n".as_bytes());
+
+        // TEST
+        let findings = lint(&mut reader, 4)?;
+        assert_eq!(
+            "line 1: malformed record: expected type, client, and tx columns",
+            findings[0].to_string()
+        );
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn findings_are_ordered_by_line() -> Result<(), csv::Error> {
+        // TEST
+        let lines: Vec<u64> = problems("dispute,1,1,\ndeposit,1,1,0.123456\n")?
+            .into_iter()
+            .map(|(line, _)| line)
+            .collect();
+        assert_eq!(vec![2, 3], lines);
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn findings_are_displayed_with_their_line_and_transaction() {
+        // SETUP
+        let finding = Finding {
+            line: 7,
+            tx: Some(3),
+            problem: Problem::LaterReference { line: 9 },
+        };
+
+        // TEST
+        assert_eq!(
+            "line 7: tx 3: refers to a transaction that only comes later, on line 9",
+            finding.to_string()
+        );
+    }
+}
+//endregion
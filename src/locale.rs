@@ -0,0 +1,300 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Message catalog
+//! The `Display` output of errors, the processing summary, and the column headers of reports are
+//! looked up in a catalog of translations, so that they can be read by operations staff in their
+//! own language. `Display` always writes English; the types in the catalog also implement
+//! `Localize`, so that whoever renders a message picks its locale, e.g.
+//! `format!("{}", error.localized(Locale::Fr))` (`--locale` on the command line).
+//!
+//! Messages that take arguments mark each with `{}`, filled in order by `write`. Translations keep
+//! the arguments in the same order as the English message.
+use std::fmt;
+use std::str::FromStr;
+
+/// `Locale` enumerates the languages messages are available in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Locale {
+    En,
+    Fr,
+    De,
+}
+
+impl FromStr for Locale {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Locale, String> {
+        match value {
+            "en" => Ok(Locale::En),
+            "fr" => Ok(Locale::Fr),
+            "de" => Ok(Locale::De),
+            _ => Err(format!("unsupported locale: {} (expected en, fr, or de)", value)),
+        }
+    }
+}
+
+/// `Message` identifies an entry of the catalog.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Message {
+    InvalidTransaction,
+    NoSuchAccount,
+    NoSuchTransaction,
+    InsufficientFunds,
+    ClientMismatch,
+    UndisputedTransaction,
+    DuplicateTransactionId,
+    DuplicateDisputeRequest,
+    AccountLocked,
+    AccountFrozen,
+    AccountNotLocked,
+    AccountDormant,
+    AccountNotDormant,
+    PromotionalCredit,
+    VersionMismatch,
+    BadSignature,
+    OpenDispute,
+    PendingTransaction,
+    MemoryLimitExceeded,
+    AmountOverflow,
+    MissingAmount,
+    NonPositiveAmount,
+    AmountNotAllowed,
+    MissingType,
+    DebitAndCredit,
+    InvalidAmount,
+    NoDigits,
+    ScientificNotation,
+    ThousandsSeparator,
+    InvalidCharacter,
+    MissingDigits,
+    TooManyDecimalPlaces,
+    OutOfRange,
+    ProcessingSummary,
+    ReportMetric,
+    ReportKey,
+    ReportValue,
+}
+
+impl Message {
+    /// The text of this message in the given locale.
+    pub fn text(self, locale: Locale) -> &'static str {
+        let [en, fr, de] = self.translations();
+        match locale {
+            Locale::En => en,
+            Locale::Fr => fr,
+            Locale::De => de,
+        }
+    }
+
+    /// The English, French, and German text of this message.
+    fn translations(self) -> [&'static str; 3] {
+        match self {
+            Message::InvalidTransaction => ["invalid transaction", "transaction invalide", "ungültige Transaktion"],
+            Message::NoSuchAccount => ["no such account", "compte inexistant", "Konto existiert nicht"],
+            Message::NoSuchTransaction => ["no such transaction", "transaction inexistante", "Transaktion existiert nicht"],
+            Message::InsufficientFunds => ["insufficient funds", "fonds insuffisants", "unzureichende Deckung"],
+            Message::ClientMismatch => [
+                "client does not match the referenced transaction",
+                "le client ne correspond pas à la transaction référencée",
+                "Kunde stimmt nicht mit der referenzierten Transaktion überein",
+            ],
+            Message::UndisputedTransaction => [
+                "transaction is not under dispute",
+                "la transaction n'est pas contestée",
+                "Transaktion ist nicht angefochten",
+            ],
+            Message::DuplicateTransactionId => [
+                "duplicate transaction ID",
+                "identifiant de transaction en double",
+                "doppelte Transaktions-ID",
+            ],
+            Message::DuplicateDisputeRequest => [
+                "transaction is already under dispute",
+                "la transaction est déjà contestée",
+                "Transaktion ist bereits angefochten",
+            ],
+            Message::AccountLocked => ["account is locked", "le compte est bloqué", "Konto ist gesperrt"],
+            Message::AccountFrozen => ["account is frozen", "le compte est gelé", "Konto ist eingefroren"],
+            Message::AccountNotLocked => ["account is not locked", "le compte n'est pas bloqué", "Konto ist nicht gesperrt"],
+            Message::AccountDormant => ["account is dormant", "le compte est inactif", "Konto ist ruhend"],
+            Message::AccountNotDormant => ["account is not dormant", "le compte n'est pas inactif", "Konto ist nicht ruhend"],
+            Message::PromotionalCredit => [
+                "promotional credits can not be disputed",
+                "les crédits promotionnels ne peuvent pas être contestés",
+                "Aktionsgutschriften können nicht angefochten werden",
+            ],
+            Message::VersionMismatch => [
+                "expected account version {} but found {}",
+                "version de compte {} attendue, mais {} trouvée",
+                "Kontoversion {} erwartet, aber {} vorgefunden",
+            ],
+            Message::BadSignature => [
+                "missing or invalid signature",
+                "signature manquante ou invalide",
+                "fehlende oder ungültige Signatur",
+            ],
+            Message::OpenDispute => [
+                "account has an open dispute",
+                "le compte fait l'objet d'une contestation en cours",
+                "Konto hat eine offene Anfechtung",
+            ],
+            Message::PendingTransaction => [
+                "account has a prepared transaction or reservation",
+                "le compte a une transaction préparée ou une réservation",
+                "Konto hat eine vorbereitete Transaktion oder Reservierung",
+            ],
+            Message::MemoryLimitExceeded => [
+                "memory limit exceeded",
+                "limite de mémoire dépassée",
+                "Speicherlimit überschritten",
+            ],
+            Message::AmountOverflow => ["balance out of range", "solde hors limites", "Saldo außerhalb des Wertebereichs"],
+            Message::MissingAmount => [
+                "field `amount` is required for deposits and withdrawals",
+                "le champ `amount` est obligatoire pour les dépôts et les retraits",
+                "das Feld `amount` ist für Einzahlungen und Abhebungen erforderlich",
+            ],
+            Message::NonPositiveAmount => [
+                "field `amount` must be greater than zero",
+                "le champ `amount` doit être supérieur à zéro",
+                "das Feld `amount` muss größer als null sein",
+            ],
+            Message::AmountNotAllowed => [
+                "field `amount` must be empty for disputes, resolves, and chargebacks",
+                "le champ `amount` doit être vide pour les contestations, résolutions et rétrofacturations",
+                "das Feld `amount` muss bei Anfechtungen, Auflösungen und Rückbuchungen leer sein",
+            ],
+            Message::MissingType => [
+                "field `type` is required",
+                "le champ `type` est obligatoire",
+                "das Feld `type` ist erforderlich",
+            ],
+            Message::DebitAndCredit => [
+                "only one of the fields `debit` and `credit` may be set",
+                "un seul des champs `debit` et `credit` peut être renseigné",
+                "nur eines der Felder `debit` und `credit` darf gesetzt sein",
+            ],
+            Message::InvalidAmount => [
+                "invalid amount \"{}\": {}",
+                "montant invalide « {} » : {}",
+                "ungültiger Betrag \"{}\": {}",
+            ],
+            Message::NoDigits => ["no digits", "aucun chiffre", "keine Ziffern"],
+            Message::ScientificNotation => [
+                "scientific notation is not supported",
+                "la notation scientifique n'est pas prise en charge",
+                "wissenschaftliche Notation wird nicht unterstützt",
+            ],
+            Message::ThousandsSeparator => [
+                "thousands separators are not supported",
+                "les séparateurs de milliers ne sont pas pris en charge",
+                "Tausendertrennzeichen werden nicht unterstützt",
+            ],
+            Message::InvalidCharacter => ["unexpected character '{}'", "caractère inattendu '{}'", "unerwartetes Zeichen '{}'"],
+            Message::MissingDigits => [
+                "digits are required on both sides of the decimal separator",
+                "des chiffres sont requis de part et d'autre du séparateur décimal",
+                "auf beiden Seiten des Dezimaltrennzeichens sind Ziffern erforderlich",
+            ],
+            Message::TooManyDecimalPlaces => [
+                "more than {} decimal places",
+                "plus de {} décimales",
+                "mehr als {} Nachkommastellen",
+            ],
+            Message::OutOfRange => [
+                "value out of range",
+                "valeur hors limites",
+                "Wert außerhalb des zulässigen Bereichs",
+            ],
+            Message::ProcessingSummary => [
+                "processed {} transactions: {} applied, {} rejected",
+                "{} transactions traitées : {} appliquées, {} rejetées",
+                "{} Transaktionen verarbeitet: {} angewendet, {} abgelehnt",
+            ],
+            Message::ReportMetric => ["metric", "indicateur", "Kennzahl"],
+            Message::ReportKey => ["key", "clé", "Schlüssel"],
+            Message::ReportValue => ["value", "valeur", "Wert"],
+        }
+    }
+}
+
+/// Write the message in the given locale, filling each `{}` with the next argument.
+pub fn write_in(f: &mut dyn fmt::Write, locale: Locale, message: Message, args: &[&dyn fmt::Display]) -> fmt::Result {
+    let mut args = args.iter();
+    let mut parts = message.text(locale).split("{}");
+    if let Some(first) = parts.next() {
+        f.write_str(first)?;
+    }
+    for part in parts {
+        if let Some(arg) = args.next() {
+            write!(f, "{}", arg)?;
+        }
+        f.write_str(part)?;
+    }
+    Ok(())
+}
+
+/// `Localize` is implemented by the types whose text is looked up in the catalog, to write them in
+/// a given locale rather than in the English of their `Display`.
+pub trait Localize {
+    /// Write this in the given locale.
+    fn write_localized(&self, f: &mut dyn fmt::Write, locale: Locale) -> fmt::Result;
+
+    /// Adapt this to `Display` in the given locale.
+    fn localized(&self, locale: Locale) -> Localized<'_, Self> {
+        Localized { value: self, locale }
+    }
+}
+
+/// `Localized` displays a `Localize` value in a given locale. Obtained from `Localize::localized`.
+pub struct Localized<'a, T: ?Sized> {
+    value: &'a T,
+    locale: Locale,
+}
+
+impl<T: Localize + ?Sized> fmt::Display for Localized<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.value.write_localized(f, self.locale)
+    }
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::{AmountParseError, BankingError, RecordError};
+
+    #[test]
+    fn messages_are_written_in_the_requested_locale() -> fmt::Result {
+        // SETUP
+        let mut french = String::new();
+        let mut german = String::new();
+
+        // TEST
+        write_in(&mut french, Locale::Fr, Message::ProcessingSummary, &[&3, &2, &1])?;
+        assert_eq!("3 transactions traitées : 2 appliquées, 1 rejetées", french);
+        write_in(&mut german, Locale::De, Message::VersionMismatch, &[&2, &3])?;
+        assert_eq!("Kontoversion 2 erwartet, aber 3 vorgefunden", german);
+        assert_eq!("clé", Message::ReportKey.text(Locale::Fr));
+        assert_eq!(Ok(Locale::De), "de".parse());
+        assert!("es".parse::<Locale>().is_err());
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn errors_are_displayed_in_english_unless_localized() {
+        // SETUP
+        let error = RecordError::InvalidAmount(String::from("1e3"), AmountParseError::ScientificNotation);
+
+        // TEST
+        assert_eq!("invalid amount \"1e3\": scientific notation is not supported", error.to_string());
+        assert_eq!(
+            "montant invalide « 1e3 » : la notation scientifique n'est pas prise en charge",
+            error.localized(Locale::Fr).to_string()
+        );
+        assert_eq!("Konto ist gesperrt", BankingError::AccountLocked.localized(Locale::De).to_string());
+        assert_eq!("account is locked", BankingError::AccountLocked.to_string());
+    }
+}
+//endregion
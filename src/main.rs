@@ -1,32 +1,1332 @@
-mod account;
-mod bank;
-mod errors;
-mod transaction;
-use bank::Bank;
-use log::{error, info};
-use structopt::StructOpt;
-#[macro_use]
-extern crate log;
+use chrono::{DateTime, Utc};
 use env_logger::Env;
+use log::{error, info, warn};
+use rust_payment_processor::account::{Account, ClientId};
+use rust_payment_processor::alert::{AlertMonitor, AlertWriter, Threshold};
+use rust_payment_processor::amount::{self, Amount, AmountFormat};
+use rust_payment_processor::audit::{AdminCommand, AuditEntry};
+use rust_payment_processor::bank::Bank;
+use rust_payment_processor::batch::{self, Batches};
+use rust_payment_processor::charset;
+use rust_payment_processor::client::{self, ClientInterner};
+#[cfg(feature = "parquet")]
+use rust_payment_processor::columnar;
+use rust_payment_processor::config::{DormancyPolicy, DuplicateFilter, DuplicatePolicy, Inactivity, RetentionPolicy};
+use rust_payment_processor::control::{self, ControlTotals};
+use rust_payment_processor::currency::{CurrencyExponent, CurrencyExponents};
+use rust_payment_processor::encryption::{self, DecryptingReader, EncryptionKey};
+use rust_payment_processor::diff;
+use rust_payment_processor::errors::BankingError;
+use rust_payment_processor::filter::{AccountFilter, ClientRanges, TransactionFilter, TransactionTypes, TxRange};
+use rust_payment_processor::history::{self, FileHistory};
+#[cfg(feature = "iso20022")]
+use rust_payment_processor::iso20022;
+#[cfg(feature = "iso8583")]
+use rust_payment_processor::iso8583::Iso8583Adapter;
+use rust_payment_processor::journal::{self, Digest, JournalWriter};
+use rust_payment_processor::locale::Locale;
+use rust_payment_processor::manifest::Manifest;
+#[cfg(feature = "differential")]
+use rust_payment_processor::model::{self, ReferenceModel};
+#[cfg(feature = "msgpack")]
+use rust_payment_processor::msgpack;
+#[cfg(feature = "parallel")]
+use rust_payment_processor::pipeline;
+#[cfg(feature = "protobuf")]
+use rust_payment_processor::protobuf;
+use rust_payment_processor::profile::{self, InputProfile};
+use rust_payment_processor::recovery::RecoveringReader;
+use rust_payment_processor::redact;
+use rust_payment_processor::risk::{self, WeightedRiskScorer};
+use rust_payment_processor::schema::{self, Schema};
+use rust_payment_processor::signature::SigningKey;
+use rust_payment_processor::sink::{AccountStreamWriter, DeadLetterWriter, ResultSink};
+use rust_payment_processor::snapshot::SnapshotEmitter;
+use rust_payment_processor::stats::StatsReporter;
+#[cfg(feature = "table")]
+use rust_payment_processor::table;
+use rust_payment_processor::transaction::{NegativeDeposits, RecordOptions, Transaction};
+#[cfg(feature = "tui")]
+use rust_payment_processor::tui::{self, Dashboard};
+use serde::Serialize;
+#[cfg(feature = "alloc-stats")]
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, Read, Write};
+use std::process;
+use std::str::FromStr;
+#[cfg(feature = "alloc-stats")]
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime};
+use structopt::StructOpt;
+
+/// `CountingAllocator` is the system allocator, counting allocations for `--stats`. Only built with
+/// the `alloc-stats` feature, so that other builds do not pay for the count on every allocation.
+#[cfg(feature = "alloc-stats")]
+struct CountingAllocator;
+
+#[cfg(feature = "alloc-stats")]
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+
+// SAFETY: every call is passed through unchanged to the system allocator.
+#[cfg(feature = "alloc-stats")]
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[cfg(feature = "alloc-stats")]
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// The number of allocations made so far, including reallocations.
+#[cfg(feature = "alloc-stats")]
+fn allocations() -> u64 {
+    ALLOCATIONS.load(Ordering::Relaxed)
+}
+
+/// `InputFormat` enumerates the formats the input file may be supplied in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum InputFormat {
+    Csv,
+    #[cfg(feature = "iso20022")]
+    Iso20022,
+    #[cfg(feature = "iso8583")]
+    Iso8583,
+    #[cfg(feature = "msgpack")]
+    Msgpack,
+    #[cfg(feature = "parquet")]
+    Parquet,
+    #[cfg(feature = "protobuf")]
+    Protobuf,
+}
+
+/// `OutputFormat` enumerates the formats the account snapshot may be written in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum OutputFormat {
+    Csv,
+    #[cfg(feature = "msgpack")]
+    Msgpack,
+    #[cfg(feature = "parquet")]
+    Parquet,
+    #[cfg(feature = "protobuf")]
+    Protobuf,
+    #[cfg(feature = "table")]
+    Table,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<OutputFormat, String> {
+        match value {
+            "csv" => Ok(OutputFormat::Csv),
+            #[cfg(feature = "msgpack")]
+            "msgpack" => Ok(OutputFormat::Msgpack),
+            #[cfg(feature = "parquet")]
+            "parquet" => Ok(OutputFormat::Parquet),
+            #[cfg(feature = "protobuf")]
+            "protobuf" => Ok(OutputFormat::Protobuf),
+            #[cfg(feature = "table")]
+            "table" => Ok(OutputFormat::Table),
+            _ => Err(format!("unsupported output format: {}", value)),
+        }
+    }
+}
+
+impl FromStr for InputFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<InputFormat, String> {
+        match value {
+            "csv" => Ok(InputFormat::Csv),
+            #[cfg(feature = "iso20022")]
+            "iso20022" => Ok(InputFormat::Iso20022),
+            #[cfg(feature = "iso8583")]
+            "iso8583" => Ok(InputFormat::Iso8583),
+            #[cfg(feature = "msgpack")]
+            "msgpack" => Ok(InputFormat::Msgpack),
+            #[cfg(feature = "parquet")]
+            "parquet" => Ok(InputFormat::Parquet),
+            #[cfg(feature = "protobuf")]
+            "protobuf" => Ok(InputFormat::Protobuf),
+            _ => Err(format!("unsupported input format: {}", value)),
+        }
+    }
+}
+
+/// `Exit` enumerates the exit codes of a run, so that schedulers can tell its outcomes apart.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Exit {
+    /// Every file was written, and no transaction was rejected that `--fail-on` fails the run for.
+    Success = 0,
+    /// Every file was written, but transactions were rejected and `--fail-on rejections` was given.
+    Rejections = 1,
+    /// The input could not be read or processed, or an output could not be written.
+    Fatal = 2,
+    /// The command line was invalid, or a key it names could not be loaded. Nothing was processed.
+    InvalidConfig = 3,
+    /// Every file was written, but the run did not match the `--control` file and
+    /// `--fail-on discrepancies` was given.
+    Discrepancies = 4,
+}
+
+/// `FailOn` enumerates the outcomes `--fail-on` can fail an otherwise successful run for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum FailOn {
+    Rejections,
+    Discrepancies,
+}
+
+impl FromStr for FailOn {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<FailOn, String> {
+        match value {
+            "rejections" => Ok(FailOn::Rejections),
+            "discrepancies" => Ok(FailOn::Discrepancies),
+            _ => Err(format!("unsupported --fail-on condition: {}", value)),
+        }
+    }
+}
 
 #[derive(StructOpt, Debug)]
 struct Cli {
     #[structopt(parse(from_os_str))]
     input_file: std::path::PathBuf,
+    /// Format of the input file: `csv`, or `iso20022` / `iso8583` / `msgpack` / `parquet` / `protobuf` when built with those features
+    #[structopt(long, default_value = "csv")]
+    input_format: InputFormat,
+    /// Format of the account output: `csv`, or `msgpack` / `parquet` / `protobuf` / `table` when built with those features
+    #[structopt(long, default_value = "csv")]
+    output_format: OutputFormat,
+    /// Write accounts to this file instead of stdout
+    #[structopt(long, parse(from_os_str))]
+    output: Option<std::path::PathBuf>,
+    /// Reject amounts with more than this many decimal places instead of rounding them
+    #[structopt(long)]
+    max_decimal_places: Option<u32>,
+    /// Language of error messages and the processing summary: `en`, `fr`, or `de`
+    #[structopt(long, default_value = "en")]
+    locale: Locale,
+    /// Treat `,` as the decimal separator in amounts
+    #[structopt(long)]
+    decimal_comma: bool,
+    /// The input has no header row; columns are read in the order: type, client, tx, amount
+    #[structopt(long)]
+    no_header: bool,
+    /// Read `batch_start` and `batch_end` control records in the CSV input, rejecting every batch
+    /// whose records do not match the count and total of its `batch_end`
+    #[structopt(long, conflicts_with = "string-client-ids")]
+    batch_control: bool,
+    /// Field delimiter: a single character, or one of `comma`, `tab`, `semicolon`, `pipe`. Defaults to `,`
+    #[structopt(long, parse(try_from_str = profile::parse_delimiter))]
+    delimiter: Option<u8>,
+    /// Encoding of input files without a byte order mark, e.g. `utf-16le` or `windows-1252`.
+    /// Defaults to UTF-8
+    #[structopt(long, parse(try_from_str = charset::parse_encoding))]
+    encoding: Option<&'static encoding_rs::Encoding>,
+    /// Read the input with the conventions of this profile from the `--profiles` file: its
+    /// encoding, delimiter, decimal separator, sign conventions, default currency, UTC offset, and column names
+    #[structopt(long, requires = "profiles")]
+    profile: Option<String>,
+    /// Parse CSV input on a thread pool, applying the parsed transactions in input order
+    #[cfg(feature = "parallel")]
+    #[structopt(long, conflicts_with = "string-client-ids")]
+    parallel: bool,
+    /// Skip lines that are not well-formed records, such as those with an unescaped quote or a stray
+    /// delimiter, logging their byte ranges, instead of letting them desynchronize the reader
+    #[structopt(long)]
+    recover: bool,
+    /// Read the input column with this header as the given field, e.g. `customer=client`. May be
+    /// repeated
+    #[structopt(long = "column", parse(try_from_str = schema::parse_column_mapping))]
+    columns: Vec<(String, String)>,
+    /// A JSON file of named input profiles, selected with `--profile`
+    #[structopt(long, parse(from_os_str), requires = "profile")]
+    profiles: Option<std::path::PathBuf>,
+    /// The input profile selected with `--profile`
+    #[structopt(skip)]
+    input_profile: InputProfile,
+    /// Also run the input through the reference model and report any accounts on which it disagrees with the engine
+    #[cfg(feature = "differential")]
+    #[structopt(long)]
+    differential: bool,
+    /// Write rejected transactions, with the error each was rejected with, to this CSV file
+    #[structopt(long, parse(from_os_str))]
+    dead_letter: Option<std::path::PathBuf>,
+    /// Include each account's version, incremented by every change to it, as an extra CSV column
+    #[structopt(long)]
+    versions: bool,
+    /// Add columns with each account's status (`active`, `dormant`, `frozen`, or `locked`), counting
+    /// each client's deposits, withdrawals, open disputes, and chargebacks, and with the ID of the
+    /// last transaction applied to its account, to the CSV account output
+    #[structopt(long, conflicts_with_all = &["versions", "stream", "string-client-ids"])]
+    extended_output: bool,
+    /// Add a final `notes` column with each account's labels and notes to the extended output
+    #[structopt(long, requires = "extended-output")]
+    with_notes: bool,
+    /// Load notes and labels recorded against accounts from this JSON file, as written by
+    /// `--save-annotations`, before processing
+    #[structopt(long, parse(from_os_str))]
+    annotations: Option<std::path::PathBuf>,
+    /// Write the notes and labels of every account to this JSON file after processing
+    #[structopt(long, parse(from_os_str))]
+    save_annotations: Option<std::path::PathBuf>,
+    /// Add a risk score from 0 to 100 for each account, weighing its dispute rate, chargebacks,
+    /// withdrawals, negative balance, and lock, as an extra column to the CSV account output
+    #[structopt(long, conflicts_with_all = &["versions", "extended-output", "stream", "string-client-ids"])]
+    risk_score: bool,
+    /// Append every applied transaction to this journal, for a standby running `follow`. The
+    /// journal's final digest is printed to stderr; check it later with `verify-journal`
+    #[structopt(long, parse(from_os_str))]
+    journal: Option<std::path::PathBuf>,
+    /// The operator or source the transactions of this run are attributed to in the journal.
+    /// Defaults to the name of the input file
+    #[structopt(long)]
+    operator: Option<String>,
+    /// Write each account as a CSV row whenever its balances change, instead of a final snapshot
+    #[structopt(long)]
+    stream: bool,
+    /// Show a live dashboard of throughput, top accounts, and recent errors while processing
+    #[cfg(feature = "tui")]
+    #[structopt(long)]
+    tui: bool,
+    /// Require every record to carry a valid HMAC-SHA256 `signature` under the key in this file
+    #[structopt(long, parse(from_os_str), conflicts_with = "signing-key-env")]
+    signing_key_file: Option<std::path::PathBuf>,
+    /// As `--signing-key-file`, reading the key from the named environment variable instead
+    #[structopt(long)]
+    signing_key_env: Option<String>,
+    /// Decrypt the input with, and encrypt the account output with, the hex-encoded AES-256 key in this file
+    #[structopt(long, parse(from_os_str), conflicts_with = "encryption-key-env")]
+    encryption_key_file: Option<std::path::PathBuf>,
+    /// As `--encryption-key-file`, reading the key from the named environment variable instead
+    #[structopt(long)]
+    encryption_key_env: Option<String>,
+    /// The key loaded from `--encryption-key-file` or `--encryption-key-env`
+    #[structopt(skip)]
+    encryption_key: Option<EncryptionKey>,
+    /// After processing, remove this client's account and transactions, folding its balance into the
+    /// bank's erased balance. May be repeated. Requires `--erasure-log`
+    #[structopt(long, requires = "erasure-log")]
+    forget: Vec<ClientId>,
+    /// Append a record of every erasure made with `--forget` to this CSV file
+    #[structopt(long, parse(from_os_str))]
+    erasure_log: Option<std::path::PathBuf>,
+    /// Evict settled deposits and withdrawals once they are older than this: a number of
+    /// transactions, or a duration such as `90d` measured on the input's timestamps. Evicted
+    /// transactions can no longer be disputed
+    #[structopt(long)]
+    retain_transactions: Option<RetentionPolicy>,
+    /// Evict settled deposits and withdrawals, oldest first, to keep the approximate memory of the
+    /// bank's stores within this many bytes, e.g. `512M` or `2G`. Once only transactions under
+    /// dispute are left to evict, further deposits and withdrawals are rejected
+    #[structopt(long, parse(try_from_str = parse_memory), conflicts_with = "retain-transactions")]
+    max_memory: Option<usize>,
+    /// Assess this fee against the client of every chargeback, debited from their account
+    #[structopt(long, parse(try_from_str = parse_fee))]
+    chargeback_fee: Option<Amount>,
+    /// After processing, flag every account without a transaction in this many records, or for a
+    /// duration such as `90d` measured on the `v2` timestamps, as dormant
+    #[structopt(long)]
+    dormant_after: Option<Inactivity>,
+    /// Assess this fee against every account flagged dormant by `--dormant-after`
+    #[structopt(long, parse(try_from_str = parse_fee), requires = "dormant-after")]
+    dormancy_fee: Option<Amount>,
+    /// Write every account flagged dormant by `--dormant-after`, with its last transaction and the
+    /// fee assessed, or why it could not be, as a CSV row to this file
+    #[structopt(long, parse(from_os_str), requires = "dormant-after")]
+    dormancy_report: Option<std::path::PathBuf>,
+    /// Round amounts in a known ISO 4217 currency to that currency's decimal places, e.g. none for
+    /// `JPY`, instead of four
+    #[structopt(long)]
+    currency_precision: bool,
+    /// Round amounts in this currency to this many decimal places, written as `CODE=PLACES`, e.g.
+    /// `JPY=0`. May be repeated. Implies `--currency-precision`
+    #[structopt(long)]
+    currency_exponent: Vec<CurrencyExponent>,
+    /// What to do with a deposit or withdrawal reusing an earlier transaction ID: `reject` it,
+    /// ignore exact replays (`ignore-replays`), `skip` every reuse, or let records marked in a
+    /// `correction` column replace the earlier one (`last-write-wins`)
+    #[structopt(long, default_value = "reject")]
+    duplicate_policy: DuplicatePolicy,
+    /// What to do with a deposit of a negative amount, as some partners encode returns: `reject` it,
+    /// the default, or read it as a `withdrawal` of the amount. Overrides the input profile's policy
+    #[structopt(long)]
+    negative_deposits: Option<NegativeDeposits>,
+    /// The number of evicted transaction IDs the duplicate filter is sized for, with
+    /// `--retain-transactions` or `--max-memory`
+    #[structopt(long)]
+    duplicate_filter_capacity: Option<usize>,
+    /// Also write evicted transaction IDs to this file, to confirm suspected duplicates against, with
+    /// `--retain-transactions` or `--max-memory`
+    #[structopt(long, parse(from_os_str))]
+    duplicate_spill: Option<std::path::PathBuf>,
+    /// Include client IDs and amounts in log messages, which leave them out by default
+    #[structopt(long)]
+    log_sensitive: bool,
+    /// Input schema version (`v1` or `v2`). Detected from the header row when omitted.
+    #[structopt(long)]
+    schema: Option<Schema>,
+    /// Read the `client` column as a string, such as a UUID or customer code, rather than a number.
+    /// Accounts are written with the same strings
+    #[structopt(long, conflicts_with_all = &["dead-letter", "journal", "stream", "versions", "forget", "alert-log"])]
+    string_client_ids: bool,
+    /// Refuse input that this history file records as already processed, and record the input in it
+    /// once it has been processed. Files are recognized by the SHA-256 of their contents
+    #[structopt(long, parse(from_os_str))]
+    history: Option<std::path::PathBuf>,
+    /// Process the input even if `--history` records it as already processed
+    #[structopt(long, requires = "history")]
+    force: bool,
+    /// Raise an alert whenever an account crosses this threshold: `available<AMOUNT`,
+    /// `held>AMOUNT`, or `total>AMOUNT`. May be repeated
+    #[structopt(long)]
+    alert: Vec<Threshold>,
+    /// Write every alert raised by `--alert` as a CSV row to this file
+    #[structopt(long, parse(from_os_str), requires = "alert")]
+    alert_log: Option<std::path::PathBuf>,
+    /// At the end of the run, write a JSON manifest of it to this file: the engine version and
+    /// arguments, the SHA-256 of the input and of every file written, and transaction counts
+    #[structopt(long, parse(from_os_str))]
+    manifest: Option<std::path::PathBuf>,
+    /// Periodically write resident memory, map sizes, records per second, and allocation counts to
+    /// stderr while processing
+    #[structopt(long)]
+    stats: bool,
+    /// Seconds between the lines written by `--stats`
+    #[structopt(long, default_value = "10")]
+    stats_interval: u64,
+    /// Every this many records, write the accounts processed so far as CSV to a new timestamped
+    /// file in `--snapshot-dir`, so that partial results of a long run can be consumed early
+    #[structopt(long)]
+    emit_snapshot_every: Option<usize>,
+    /// Directory to write the snapshots requested by `--emit-snapshot-every` to
+    #[structopt(long, parse(from_os_str), default_value = ".")]
+    snapshot_dir: std::path::PathBuf,
+    /// Only output the accounts of these clients, given as a comma-separated list of ids and
+    /// inclusive ranges, e.g. `100-200,5000`
+    #[structopt(long, conflicts_with = "string-client-ids")]
+    clients: Option<ClientRanges>,
+    /// Also skip the transactions of clients not selected by `--clients`, rather than only leaving
+    /// their accounts out of the output
+    #[structopt(long, requires = "clients")]
+    filter_input: bool,
+    /// Only output locked accounts
+    #[structopt(long)]
+    only_locked: bool,
+    /// Only output accounts whose total is at least this amount
+    #[structopt(long, parse(try_from_str = parse_min_total))]
+    min_total: Option<Amount>,
+    /// Only process transactions with IDs in this inclusive range, e.g. `100-200`, `100-`, or
+    /// `-200`. Disputes, resolves, and chargebacks are selected by the ID they refer to
+    #[structopt(long)]
+    tx_range: Option<TxRange>,
+    /// Only process transactions of these types, given as a comma-separated list, e.g.
+    /// `dispute,resolve,chargeback`
+    #[structopt(long)]
+    types: Option<TransactionTypes>,
+    /// Start from the accounts in this CSV file, as written by a previous run or by
+    /// `--emit-snapshot-every`. Only balances are restored, so earlier transactions can not be disputed
+    #[structopt(long, parse(from_os_str))]
+    baseline: Option<std::path::PathBuf>,
+    /// Instead of the accounts, write the change the input would make to each account, as CSV, and
+    /// write nothing else
+    #[structopt(
+        long,
+        conflicts_with_all = &[
+            "stream",
+            "journal",
+            "dead-letter",
+            "history",
+            "manifest",
+            "emit-snapshot-every",
+            "forget",
+            "alert-log",
+            "control-report",
+            "string-client-ids"
+        ]
+    )]
+    dry_run: bool,
+    /// After processing, apply this administrative command to every account matching all of the
+    /// `--admin-where` thresholds: `lock`, `unlock`, `freeze`, `unfreeze`, `reactivate`,
+    /// `annotate:<note>`, or `label:<key>=<value>`, where an empty value removes the label
+    #[structopt(long, requires = "admin-where")]
+    admin: Option<AdminCommand>,
+    /// Select the accounts `--admin` applies to by a threshold, written as for `--alert`, e.g.
+    /// `available<0`. May be repeated
+    #[structopt(long, requires = "admin")]
+    admin_where: Vec<Threshold>,
+    /// Append an audit entry for every account changed by `--admin` to this file, as JSON lines
+    #[structopt(long, parse(from_os_str), requires = "admin")]
+    audit_log: Option<std::path::PathBuf>,
+    /// Verify the run against this CSV control file of `client,net` rows, declaring the net movement
+    /// of each client listed, and of all accounts together in a row without a client
+    #[structopt(long, parse(from_os_str), conflicts_with = "string-client-ids")]
+    control: Option<std::path::PathBuf>,
+    /// Write every net movement that does not match `--control` as a CSV row to this file
+    #[structopt(long, parse(from_os_str), requires = "control")]
+    control_report: Option<std::path::PathBuf>,
+    /// Exit with status 1 instead of 0 if any transaction was rejected (`rejections`), or with
+    /// status 4 if the run does not match `--control` (`discrepancies`). May be repeated
+    #[structopt(long)]
+    fail_on: Vec<FailOn>,
+}
+
+impl Cli {
+    /// The filter selecting the accounts to output.
+    fn account_filter(&self) -> AccountFilter {
+        AccountFilter {
+            clients: self.clients.clone(),
+            only_locked: self.only_locked,
+            min_total: self.min_total,
+        }
+    }
+
+    /// The filter selecting the transactions to process.
+    fn transaction_filter(&self) -> TransactionFilter {
+        TransactionFilter {
+            clients: self.clients.clone().filter(|_| self.filter_input),
+            tx_range: self.tx_range.clone(),
+            types: self.types.clone(),
+        }
+    }
+
+    /// The conventions input records are read with: those of the input profile, overridden by the
+    /// command line.
+    fn record_options(&self) -> RecordOptions {
+        let profile = self.input_profile.record_options();
+        RecordOptions {
+            negative_deposits: self.negative_deposits.unwrap_or(profile.negative_deposits),
+            amount_format: AmountFormat {
+                max_decimal_places: self.max_decimal_places,
+                decimal_comma: self.decimal_comma,
+            },
+            locale: self.locale,
+            ..profile
+        }
+    }
+}
+
+/// Parse a fee given on the command line, which must be greater than zero.
+fn parse_fee(value: &str) -> Result<Amount, String> {
+    match amount::parse_amount(value, &AmountFormat::new()) {
+        Ok(fee) if fee > Amount::from(0u32) => Ok(fee),
+        Ok(_) => Err(format!("fees must be greater than zero: {}", value)),
+        Err(e) => Err(format!("invalid fee \"{}\": {}", value, e)),
+    }
+}
+
+/// Parse a number of bytes given with `--max-memory`, optionally followed by `K`, `M`, or `G` for
+/// multiples of 1024.
+fn parse_memory(value: &str) -> Result<usize, String> {
+    let invalid = || format!("invalid memory limit: {} (expected a number of bytes, e.g. 512M)", value);
+    let (number, multiple) = match value.trim().to_ascii_uppercase() {
+        text if text.ends_with('K') => (text[..text.len() - 1].to_string(), 1 << 10),
+        text if text.ends_with('M') => (text[..text.len() - 1].to_string(), 1 << 20),
+        text if text.ends_with('G') => (text[..text.len() - 1].to_string(), 1 << 30),
+        text => (text, 1),
+    };
+    let number: usize = number.parse().map_err(|_| invalid())?;
+    number.checked_mul(multiple).ok_or_else(invalid)
+}
+
+/// Parse the minimum total given with `--min-total`.
+fn parse_min_total(value: &str) -> Result<Amount, String> {
+    amount::parse_amount(value, &AmountFormat::new()).map_err(|e| format!("invalid minimum total \"{}\": {}", value, e))
+}
+
+/// Load the profile selected with `--profile`, filling in the options not given on the command line.
+fn apply_profile(args: &mut Cli) -> Result<(), String> {
+    if let (Some(path), Some(name)) = (&args.profiles, &args.profile) {
+        let profile = profile::load(path, name).map_err(|e| e.to_string())?;
+        info!("Reading input with profile {}", name);
+        args.encoding = args.encoding.or(profile.encoding);
+        args.delimiter = args.delimiter.or(profile.delimiter);
+        args.no_header |= profile.no_header;
+        args.decimal_comma |= profile.decimal_comma;
+        args.input_profile = profile;
+    }
+    // mappings given on the command line are applied over the profile's
+    let columns: Vec<(String, String)> = args.columns.drain(..).collect();
+    args.input_profile.columns.extend(columns);
+    Ok(())
+}
+
+/// Open the input file, decrypting it as it is read if an encryption key was given.
+fn open_input(args: &Cli) -> Result<Box<dyn Read + Send>, String> {
+    let file = File::open(&args.input_file).map_err(|e| e.to_string())?;
+    match &args.encryption_key {
+        Some(key) => Ok(Box::new(
+            DecryptingReader::new(key, BufReader::new(file)).map_err(|e| e.to_string())?,
+        )),
+        None => Ok(Box::new(file)),
+    }
+}
+
+/// Build a CSV reader for the input file according to the command-line options, decoded to UTF-8
+/// and with its columns renamed as mapped by `--column` and the input profile.
+fn make_csv_reader(args: &Cli) -> Result<csv::Reader<Box<dyn Read + Send>>, String> {
+    let mut input: Box<dyn Read + Send> = Box::new(charset::decode(open_input(args)?, args.encoding));
+    if args.recover {
+        input = Box::new(RecoveringReader::new(BufReader::new(input), args.delimiter.unwrap_or(b',')));
+    }
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .has_headers(!args.no_header)
+        .delimiter(args.delimiter.unwrap_or(b','))
+        .from_reader(input);
+    if !args.no_header {
+        schema::map_columns(&mut reader, &args.input_profile.columns).map_err(|e| e.to_string())?;
+    }
+    Ok(reader)
 }
 
 fn main() {
     env_logger::Builder::from_env(Env::default().default_filter_or("off")).init();
     info!("Rust Payment Processor Started");
-    let args = Cli::from_args();
-    let mut bank = Bank::new();
-    match csv::ReaderBuilder::new().trim(csv::Trim::All).from_path(args.input_file) {
-        Ok(mut reader) => {
-            bank.process_record_set(&mut reader);
-            bank.print_accounts();
+    let args = match Cli::from_args_safe() {
+        Ok(args) => args,
+        Err(e) if e.kind == structopt::clap::ErrorKind::HelpDisplayed || e.kind == structopt::clap::ErrorKind::VersionDisplayed => e.exit(),
+        Err(e) => {
+            eprintln!("{}", e.message);
+            process::exit(Exit::InvalidConfig as i32);
+        }
+    };
+    process::exit(run(args) as i32);
+}
+
+/// Process the input as configured on the command line, returning how the run went.
+fn run(mut args: Cli) -> Exit {
+    redact::set_log_sensitive(args.log_sensitive);
+    if let Err(e) = apply_profile(&mut args) {
+        error!("{}", e);
+        return Exit::InvalidConfig;
+    }
+    if args.stream && args.output_format != OutputFormat::Csv {
+        error!("--stream is only supported with --output-format csv");
+        return Exit::InvalidConfig;
+    }
+    if args.extended_output && args.output_format != OutputFormat::Csv {
+        error!("--extended-output is only supported with --output-format csv");
+        return Exit::InvalidConfig;
+    }
+    if args.risk_score && args.output_format != OutputFormat::Csv {
+        error!("--risk-score is only supported with --output-format csv");
+        return Exit::InvalidConfig;
+    }
+    if args.string_client_ids && (args.input_format != InputFormat::Csv || args.output_format != OutputFormat::Csv) {
+        error!("--string-client-ids is only supported with CSV input and output");
+        return Exit::InvalidConfig;
+    }
+    if !args.forget.is_empty() && args.stream {
+        error!("--forget can not be combined with --stream, which writes accounts before they are forgotten");
+        return Exit::InvalidConfig;
+    }
+    if args.dormant_after.is_some() && args.stream {
+        error!("--dormant-after can not be combined with --stream, which writes accounts before they are swept");
+        return Exit::InvalidConfig;
+    }
+    if args.admin.is_some() && args.stream {
+        error!("--admin can not be combined with --stream, which writes accounts before they are changed");
+        return Exit::InvalidConfig;
+    }
+    if !args.account_filter().is_empty() && args.stream {
+        error!("--clients, --only-locked, and --min-total can not be combined with --stream, which writes every changed account");
+        return Exit::InvalidConfig;
+    }
+    #[cfg(feature = "tui")]
+    if args.tui && args.stream && args.output.is_none() {
+        error!("--tui and --stream can only be combined with --output");
+        return Exit::InvalidConfig;
+    }
+    args.encryption_key = match load_encryption_key(&args) {
+        Ok(key) => key,
+        Err(e) => {
+            error!("{}", e);
+            return Exit::InvalidConfig;
         }
+    };
+    let unencrypted = args.dead_letter.is_some()
+        || args.journal.is_some()
+        || args.stream
+        || args.alert_log.is_some()
+        || args.audit_log.is_some()
+        || args.control_report.is_some();
+    if args.encryption_key.is_some() && unencrypted {
+        error!(
+            "--dead-letter, --journal, --stream, --alert-log, --audit-log, and --control-report write unencrypted files and can not be \
+             used with encryption"
+        );
+        return Exit::InvalidConfig;
+    }
+    if args.dry_run && (args.output_format != OutputFormat::Csv || args.encryption_key.is_some()) {
+        error!("--dry-run is only supported with unencrypted --output-format csv");
+        return Exit::InvalidConfig;
+    }
+    let mut bank = match make_bank(&args) {
+        Ok(bank) => bank,
         Err(e) => {
             error!("{}", e);
+            return Exit::InvalidConfig;
+        }
+    };
+    let history = match check_history(&args) {
+        Ok(history) => history,
+        Err(e) => {
+            error!("{}", e);
+            return Exit::Fatal;
+        }
+    };
+    // with --dry-run, only the changes to these accounts are written; with --control, the net
+    // movement is measured from them
+    let before: Vec<Account> = match args.dry_run || args.control.is_some() {
+        true => bank.accounts().cloned().collect(),
+        false => Vec::new(),
+    };
+    let mut clients = ClientInterner::new();
+    let processed = match args.input_format {
+        InputFormat::Csv => process_csv(&args, &mut bank, &mut clients),
+        #[cfg(feature = "iso20022")]
+        InputFormat::Iso20022 => process_iso20022(&args, &mut bank),
+        #[cfg(feature = "iso8583")]
+        InputFormat::Iso8583 => process_iso8583(&args, &mut bank),
+        #[cfg(feature = "msgpack")]
+        InputFormat::Msgpack => process_msgpack(&args, &mut bank),
+        #[cfg(feature = "parquet")]
+        InputFormat::Parquet => process_parquet(&args, &mut bank),
+        #[cfg(feature = "protobuf")]
+        InputFormat::Protobuf => process_protobuf(&args, &mut bank),
+    };
+    // with --stream, accounts have already been written as they changed
+    let mut discrepancies = 0;
+    let written =
+        processed
+            .and_then(|_| verify_control(&args, &before, &bank).map(|found| discrepancies = found))
+            .and_then(|_| forget_clients(&args, &mut bank))
+            .and_then(|_| sweep_dormant(&args, &mut bank))
+            .and_then(|_| administer_accounts(&args, &mut bank))
+            .and_then(|_| save_annotations(&args, &bank))
+            .map(|_| filter_accounts(&args, &mut bank))
+            .and_then(|_| match (args.dry_run, args.stream) {
+                (true, _) => write_deltas(&args, &before, &bank),
+                (false, true) => Ok(()),
+                (false, false) => write_accounts(&args, &bank, &clients),
+            })
+            .and_then(|_| record_history(&args, history))
+            .and_then(|_| write_manifest(&args, &bank));
+    if let Err(e) = written {
+        error!("{}", e);
+        return Exit::Fatal;
+    }
+    let skipped = bank.summary().skipped;
+    if skipped > 0 {
+        info!("Skipped {} duplicate transactions", skipped);
+    }
+    let rejected = bank.summary().rejected;
+    if args.fail_on.contains(&FailOn::Rejections) && rejected > 0 {
+        warn!("Failing the run because {} transactions were rejected", rejected);
+        return Exit::Rejections;
+    }
+    if args.fail_on.contains(&FailOn::Discrepancies) && discrepancies > 0 {
+        warn!(
+            "Failing the run because it does not match the control file in {} places",
+            discrepancies
+        );
+        return Exit::Discrepancies;
+    }
+    Exit::Success
+}
+
+/// Check the input file against `--history`, if given, returning the history and the hash of the
+/// input, to record once it has been processed.
+fn check_history(args: &Cli) -> Result<Option<(FileHistory, Digest)>, String> {
+    let path = match &args.history {
+        Some(path) => path,
+        None => return Ok(None),
+    };
+    let history = FileHistory::open(path).map_err(|e| format!("Failed to read history {}: {}", path.display(), e))?;
+    let digest = history::hash_file(&args.input_file).map_err(|e| e.to_string())?;
+    if let Some(earlier) = history.find(&digest) {
+        let processed = format!(
+            "{} was already processed at {}, as {}",
+            args.input_file.display(),
+            earlier.processed_at,
+            earlier.file
+        );
+        if !args.force {
+            return Err(format!("{}. Pass --force to process it again", processed));
+        }
+        warn!("{}. Processing it again because of --force", processed);
+    }
+    Ok(Some((history, digest)))
+}
+
+/// Record the input file in `--history`, once it has been processed and the accounts written.
+fn record_history(args: &Cli, history: Option<(FileHistory, Digest)>) -> Result<(), String> {
+    match history {
+        Some((mut history, digest)) => history.record(&digest, &args.input_file).map_err(|e| e.to_string()),
+        None => Ok(()),
+    }
+}
+
+/// Write the `--manifest` of the run, if requested, once every other file has been written.
+fn write_manifest(args: &Cli, bank: &Bank) -> Result<(), String> {
+    let path = match &args.manifest {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+    let outputs = [
+        &args.output,
+        &args.dead_letter,
+        &args.journal,
+        &args.alert_log,
+        &args.erasure_log,
+        &args.audit_log,
+        &args.control_report,
+    ];
+    let manifest = Manifest::new(
+        bank,
+        std::env::args().skip(1).collect(),
+        Some(&args.input_file),
+        outputs.iter().copied().flatten(),
+    )
+    .map_err(|e| e.to_string())?;
+    let file = File::create(path).map_err(|e| e.to_string())?;
+    manifest.write_json(file).map_err(|e| e.to_string())
+}
+
+/// The operator or source the transactions of this run are attributed to.
+fn operator(args: &Cli) -> String {
+    args.operator.clone().unwrap_or_else(|| args.input_file.display().to_string())
+}
+
+/// Build the bank according to the command-line options.
+fn make_bank(args: &Cli) -> Result<Bank, String> {
+    let mut builder = Bank::builder()
+        .statistics(args.extended_output || args.risk_score)
+        .duplicate_policy(args.duplicate_policy)
+        .locale(args.locale);
+    if let Some(retention) = args.retain_transactions {
+        builder = builder.retention(retention);
+    }
+    if let Some(limit) = args.max_memory {
+        builder = builder.retention(RetentionPolicy::Memory(limit));
+    }
+    if let Some(fee) = args.chargeback_fee {
+        builder = builder.chargeback_fee(fee);
+    }
+    if let Some(after) = args.dormant_after {
+        builder = builder.dormancy(DormancyPolicy {
+            fee: args.dormancy_fee,
+            ..DormancyPolicy::new(after)
+        });
+    }
+    if args.currency_precision || !args.currency_exponent.is_empty() {
+        let exponents = args
+            .currency_exponent
+            .iter()
+            .cloned()
+            .fold(CurrencyExponents::iso4217(), CurrencyExponents::with_override);
+        builder = builder.currency_exponents(exponents);
+    }
+    let default_filter = DuplicateFilter::default();
+    builder = builder.duplicate_filter(DuplicateFilter {
+        capacity: args.duplicate_filter_capacity.unwrap_or(default_filter.capacity),
+        spill: args.duplicate_spill.clone(),
+        ..default_filter
+    });
+    if let Some(path) = &args.signing_key_file {
+        let key = SigningKey::from_file(path).map_err(|e| format!("Failed to read signing key {}: {}", path.display(), e))?;
+        builder = builder.signing_key(key);
+    }
+    if let Some(name) = &args.signing_key_env {
+        builder = builder.signing_key(SigningKey::from_env(name)?);
+    }
+    let mut bank = builder.build();
+    bank.set_operator(Some(operator(args)));
+    if let Some(path) = &args.baseline {
+        let file = File::open(path).map_err(|e| format!("Failed to read baseline {}: {}", path.display(), e))?;
+        let accounts = diff::read_accounts(file).map_err(|e| format!("Failed to read baseline {}: {}", path.display(), e))?;
+        info!("Starting from {} accounts in {}", accounts.len(), path.display());
+        for account in accounts {
+            bank.restore_account(account);
+        }
+    }
+    if let Some(path) = &args.annotations {
+        let file = File::open(path).map_err(|e| format!("Failed to read annotations {}: {}", path.display(), e))?;
+        let annotated = bank
+            .read_annotations(file)
+            .map_err(|e| format!("Failed to read annotations {}: {}", path.display(), e))?;
+        info!("Loaded annotations of {} accounts from {}", annotated, path.display());
+    }
+    Ok(bank)
+}
+
+/// Load the encryption key given on the command line, if any.
+fn load_encryption_key(args: &Cli) -> Result<Option<EncryptionKey>, String> {
+    match (&args.encryption_key_file, &args.encryption_key_env) {
+        (Some(path), _) => EncryptionKey::from_file(path).map(Some),
+        (None, Some(name)) => EncryptionKey::from_env(name).map(Some),
+        (None, None) => Ok(None),
+    }
+}
+
+/// `ErasureRecord` is a row of the `--erasure-log`.
+#[derive(Serialize)]
+struct ErasureRecord {
+    erased_at: DateTime<Utc>,
+    client: ClientId,
+    transactions: usize,
+    balance: Amount,
+}
+
+/// `AuditRecord` is a line of the `--audit-log`.
+#[derive(Serialize)]
+struct AuditRecord {
+    client: ClientId,
+    #[serde(flatten)]
+    entry: AuditEntry,
+}
+
+/// Flag the accounts without activity for `--dormant-after` as dormant, writing each to the
+/// `--dormancy-report`, if given.
+fn sweep_dormant(args: &Cli, bank: &mut Bank) -> Result<(), String> {
+    if args.dormant_after.is_none() {
+        return Ok(());
+    }
+    let swept = bank.sweep_dormant();
+    info!("Flagged {} accounts as dormant", swept.len());
+    let path = match &args.dormancy_report {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+    let mut writer = csv::Writer::from_path(path).map_err(|e| e.to_string())?;
+    for account in swept {
+        writer.serialize(account).map_err(|e| e.to_string())?;
+    }
+    writer.flush().map_err(|e| e.to_string())
+}
+
+/// Write the notes and labels of every account to `--save-annotations`, if given.
+fn save_annotations(args: &Cli, bank: &Bank) -> Result<(), String> {
+    let path = match &args.save_annotations {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+    let file = File::create(path).map_err(|e| e.to_string())?;
+    bank.write_annotations(file).map_err(|e| e.to_string())
+}
+
+/// Apply the `--admin` command to the accounts matching every `--admin-where` threshold, appending
+/// an entry for each account changed to the `--audit-log`, if given.
+fn administer_accounts(args: &Cli, bank: &mut Bank) -> Result<(), String> {
+    let command = match &args.admin {
+        Some(command) => command,
+        None => return Ok(()),
+    };
+    let selected = |account: &Account| args.admin_where.iter().all(|threshold| threshold.breached_by(account));
+    let changed = bank.for_each_account_mut(selected, |account| command.apply(account));
+    info!("Applied {:?} to {} accounts", command, changed.len());
+    let path = match &args.audit_log {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+    let mut log = OpenOptions::new().create(true).append(true).open(path).map_err(|e| e.to_string())?;
+    for (client, action) in changed {
+        let record = AuditRecord {
+            client,
+            entry: AuditEntry::now(action, Some(operator(args))),
+        };
+        serde_json::to_writer(&mut log, &record).map_err(|e| e.to_string())?;
+        writeln!(log).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Leave the accounts not selected by `--clients`, `--only-locked`, or `--min-total` out of the output.
+fn filter_accounts(args: &Cli, bank: &mut Bank) {
+    let filter = args.account_filter();
+    if !filter.is_empty() {
+        bank.retain_accounts(|account| filter.matches(account));
+    }
+}
+
+/// Verify the net movement of the run against the `--control` file, if given, logging each
+/// discrepancy and writing them to the `--control-report`. Returns the number of discrepancies.
+fn verify_control(args: &Cli, before: &[Account], bank: &Bank) -> Result<usize, String> {
+    let path = match &args.control {
+        Some(path) => path,
+        None => return Ok(0),
+    };
+    let file = File::open(path).map_err(|e| format!("Failed to read control file {}: {}", path.display(), e))?;
+    let control = ControlTotals::read(file)?;
+    let discrepancies = control.verify(before, bank);
+    for discrepancy in &discrepancies {
+        let declared = discrepancy
+            .client
+            .map_or_else(|| String::from("all accounts"), |client| format!("client {}", redact::redact(&client)));
+        error!(
+            "Net movement of {} is {}, but the control file declares {}",
+            declared, discrepancy.actual, discrepancy.expected
+        );
+    }
+    info!("Verified {} net movements against the control file", control.clients.len() + control.overall.iter().count());
+    if let Some(path) = &args.control_report {
+        let file = File::create(path).map_err(|e| e.to_string())?;
+        control::write_discrepancies(&discrepancies, file).map_err(|e| e.to_string())?;
+    }
+    Ok(discrepancies.len())
+}
+
+/// Forget the clients given with `--forget`, appending a record of each erasure to the
+/// `--erasure-log`. The log is written before the accounts, so that no erasure goes unrecorded.
+fn forget_clients(args: &Cli, bank: &mut Bank) -> Result<(), String> {
+    let path = match &args.erasure_log {
+        Some(path) if !args.forget.is_empty() => path,
+        _ => return Ok(()),
+    };
+    let file = OpenOptions::new().create(true).append(true).open(path).map_err(|e| e.to_string())?;
+    let empty = file.metadata().map_err(|e| e.to_string())?.len() == 0;
+    let mut log = csv::WriterBuilder::new().has_headers(empty).from_writer(file);
+    for &client in &args.forget {
+        let erasure = match bank.forget(client) {
+            Ok(erasure) => erasure,
+            Err(e) => {
+                error!("Failed to forget client {}. Aborted with error: {:?}", redact::redact(&client), e);
+                continue;
+            }
+        };
+        let record = ErasureRecord {
+            erased_at: DateTime::<Utc>::from(SystemTime::now()),
+            client: erasure.client,
+            transactions: erasure.transactions,
+            balance: erasure.balance,
+        };
+        log.serialize(record).map_err(|e| e.to_string())?;
+        log.flush().map_err(|e| e.to_string())?;
+        info!(
+            "Forgot client {} and {} transactions",
+            redact::redact(&client),
+            erasure.transactions
+        );
+    }
+    Ok(())
+}
+
+/// Read and process a CSV input file. With `--string-client-ids`, the client identifiers seen are
+/// interned into `clients`.
+fn process_csv(args: &Cli, bank: &mut Bank, clients: &mut ClientInterner) -> Result<(), String> {
+    let mut reader = make_csv_reader(args)?;
+    let options = args.record_options();
+    if !args.no_header {
+        let schema = schema::check_input(&mut reader, args.schema, &options)?;
+        info!("Reading input using schema {}", schema);
+    }
+    if args.string_client_ids {
+        process(args, bank, client::read_transactions(&mut reader, clients, &options))?;
+        info!("Interned {} client identifiers", clients.len());
+        return Ok(());
+    }
+    let file = args.input_file.display().to_string();
+    if args.batch_control {
+        #[cfg(feature = "parallel")]
+        if args.parallel {
+            return Err(String::from("--parallel can not be combined with --batch-control"));
+        }
+        let mut batches = Batches::new(batch::read_records_from(&mut reader, &file, &options));
+        process(args, bank, &mut batches)?;
+        info!("Read {} batches, rejecting {}", batches.started(), batches.rejected());
+        return Ok(());
+    }
+    #[cfg(feature = "parallel")]
+    if args.parallel {
+        return process(args, bank, pipeline::read_transactions_from(reader, &file, options));
+    }
+    process(args, bank, schema::read_transactions_from(&mut reader, &file, &options))
+}
+
+/// Process transactions adapted from any input format, routing rejected ones to `--dead-letter`
+/// and account changes to the output with `--stream`, if given. With `--differential`, the
+/// transactions that pass the filters are also applied to the reference model, failing if it
+/// disagrees with the engine on any account.
+fn process<I: IntoIterator<Item = Transaction>>(args: &Cli, bank: &mut Bank, transactions: I) -> Result<(), String> {
+    let filter = args.transaction_filter();
+    let transactions = transactions.into_iter().filter(|transaction| filter.matches(transaction));
+    #[cfg(feature = "differential")]
+    if args.differential {
+        let mut model = ReferenceModel::new();
+        let transactions = transactions.inspect(|transaction| {
+            model.apply(transaction);
+        });
+        apply(args, bank, transactions)?;
+        return compare_with_model(bank, &model);
+    }
+    apply(args, bank, transactions)
+}
+
+/// Apply transactions that passed the filters, with the sinks requested on the command line.
+fn apply<I: Iterator<Item = Transaction>>(args: &Cli, bank: &mut Bank, transactions: I) -> Result<(), String> {
+    #[cfg(feature = "tui")]
+    if args.tui {
+        return process_with_dashboard(args, bank, transactions);
+    }
+    if args.dead_letter.is_none()
+        && !args.stream
+        && args.journal.is_none()
+        && args.alert.is_empty()
+        && !args.stats
+        && args.emit_snapshot_every.is_none()
+    {
+        bank.process_all(transactions);
+        return Ok(());
+    }
+    let mut sinks = Sinks::new(args)?;
+    bank.process_all_with(transactions, &mut sinks);
+    sinks.flush(bank)
+}
+
+/// `Sinks` holds the sinks requested on the command line.
+struct Sinks {
+    dead_letters: Option<DeadLetterWriter<File>>,
+    stream: Option<AccountStreamWriter<Box<dyn Write>>>,
+    journal: Option<JournalWriter<File>>,
+    alerts: Option<AlertMonitor<Option<AlertWriter<File>>>>,
+    stats: Option<StatsReporter<std::io::Stderr>>,
+    snapshots: Option<SnapshotEmitter>,
+}
+
+impl Sinks {
+    fn new(args: &Cli) -> Result<Sinks, String> {
+        let create = |path: &std::path::PathBuf| File::create(path).map_err(|e| e.to_string());
+        let stream = if args.stream {
+            let writer: Box<dyn Write> = match &args.output {
+                Some(path) => Box::new(create(path)?),
+                None => Box::new(std::io::stdout()),
+            };
+            Some(AccountStreamWriter::new(writer))
+        } else {
+            None
+        };
+        Ok(Sinks {
+            dead_letters: args.dead_letter.as_ref().map(create).transpose()?.map(DeadLetterWriter::new),
+            stream,
+            journal: args
+                .journal
+                .as_ref()
+                .map(create)
+                .transpose()?
+                .map(|file| JournalWriter::new(file).with_source(operator(args))),
+            alerts: if args.alert.is_empty() {
+                None
+            } else {
+                let log = args.alert_log.as_ref().map(create).transpose()?.map(AlertWriter::new);
+                Some(AlertMonitor::new(args.alert.clone(), log))
+            },
+            stats: if args.stats {
+                let reporter = StatsReporter::new(std::io::stderr(), Duration::from_secs(args.stats_interval));
+                #[cfg(feature = "alloc-stats")]
+                let reporter = reporter.with_allocations(allocations);
+                Some(reporter)
+            } else {
+                None
+            },
+            snapshots: args.emit_snapshot_every.map(|every| SnapshotEmitter::new(&args.snapshot_dir, every)),
+        })
+    }
+
+    fn flush(self, bank: &Bank) -> Result<(), String> {
+        if let Some(mut dead_letters) = self.dead_letters {
+            dead_letters.flush().map_err(|e| e.to_string())?;
+            info!("Wrote {} dead letters", dead_letters.written());
+        }
+        if let Some(mut stream) = self.stream {
+            stream.flush().map_err(|e| e.to_string())?;
+        }
+        if let Some(journal) = self.journal {
+            info!("Journaled {} transactions", journal.seq());
+            // recorded alongside the output so that the journal can later be verified against it
+            eprintln!("journal digest: {}", journal::to_hex(&journal.digest()));
+        }
+        if let Some(alerts) = self.alerts {
+            info!("Raised {} alerts", alerts.raised());
+            if let Some(mut log) = alerts.into_observer() {
+                log.flush().map_err(|e| e.to_string())?;
+            }
+        }
+        if let Some(mut stats) = self.stats {
+            stats.report(bank).map_err(|e| e.to_string())?;
+        }
+        if let Some(snapshots) = self.snapshots {
+            info!("Wrote {} intermediate snapshots", snapshots.written().len());
+        }
+        Ok(())
+    }
+}
+
+impl ResultSink for Sinks {
+    fn record(&mut self, index: usize, transaction: &Transaction, result: Result<(), BankingError>) {
+        self.dead_letters.record(index, transaction, result.clone());
+        self.journal.record(index, transaction, result.clone());
+        self.stats.record(index, transaction, result.clone());
+        self.snapshots.record(index, transaction, result.clone());
+        self.stream.record(index, transaction, result);
+    }
+
+    fn account_updated(&mut self, account: &Account) {
+        self.stream.account_updated(account);
+        self.alerts.account_updated(account);
+    }
+
+    fn sample(&mut self, bank: &Bank) {
+        self.stats.sample(bank);
+        self.snapshots.sample(bank);
+    }
+}
+
+/// Process transactions as `process` does, showing the dashboard until it is dismissed.
+#[cfg(feature = "tui")]
+fn process_with_dashboard<I: IntoIterator<Item = Transaction>>(args: &Cli, bank: &mut Bank, transactions: I) -> Result<(), String> {
+    let sinks = Sinks::new(args)?;
+    let mut sinks = (sinks, Dashboard::new(ratatui::init()));
+    bank.process_all_with(transactions, &mut sinks);
+    let shown = sinks.1.finish().and_then(|_| tui::wait_for_exit());
+    ratatui::restore();
+    shown.map_err(|e| e.to_string())?;
+    sinks.0.flush(bank)
+}
+
+/// Compare the engine with the reference model after both have been given the same transactions,
+/// failing if they disagree on any account.
+#[cfg(feature = "differential")]
+fn compare_with_model(bank: &Bank, model: &ReferenceModel) -> Result<(), String> {
+    let differences = model::diff(bank, model);
+    for difference in &differences {
+        error!("Engine and model disagree on {}", redact::redact(difference));
+    }
+    match differences.len() {
+        0 => Ok(()),
+        count => Err(format!("Engine and model disagree on {} accounts", count)),
+    }
+}
+
+/// Read and process an ISO 20022 XML input file.
+#[cfg(feature = "iso20022")]
+fn process_iso20022(args: &Cli, bank: &mut Bank) -> Result<(), String> {
+    let file = open_input(args)?;
+    let transactions = iso20022::read_transactions(std::io::BufReader::new(file)).map_err(|e| e.to_string())?;
+    process(args, bank, transactions)
+}
+
+/// Read and process a file of simplified ISO 8583 messages, one per line.
+#[cfg(feature = "iso8583")]
+fn process_iso8583(args: &Cli, bank: &mut Bank) -> Result<(), String> {
+    let file = open_input(args)?;
+    let transactions = Iso8583Adapter::new()
+        .read_transactions(std::io::BufReader::new(file))
+        .map_err(|e| e.to_string())?;
+    process(args, bank, transactions)
+}
+
+/// Read and process a MessagePack input file.
+#[cfg(feature = "msgpack")]
+fn process_msgpack(args: &Cli, bank: &mut Bank) -> Result<(), String> {
+    let file = open_input(args)?;
+    let transactions =
+        msgpack::read_transactions(std::io::BufReader::new(file), &args.record_options()).map_err(|e| e.to_string())?;
+    process(args, bank, transactions)
+}
+
+/// Read and process a Parquet input file.
+#[cfg(feature = "parquet")]
+fn process_parquet(args: &Cli, bank: &mut Bank) -> Result<(), String> {
+    if args.encryption_key.is_some() {
+        return Err(String::from("Encrypted Parquet input is not supported"));
+    }
+    let transactions = columnar::read_parquet(&args.input_file).map_err(|e| e.to_string())?;
+    process(args, bank, transactions)
+}
+
+/// Read and process a length-delimited protobuf input file.
+#[cfg(feature = "protobuf")]
+fn process_protobuf(args: &Cli, bank: &mut Bank) -> Result<(), String> {
+    let file = open_input(args)?;
+    let transactions = protobuf::read_transactions(std::io::BufReader::new(file)).map_err(|e| e.to_string())?;
+    process(args, bank, transactions)
+}
+
+/// Write the resulting accounts in the requested output format, to `--output` if given or to
+/// stdout otherwise. If an encryption key was given, the output is encrypted.
+fn write_accounts(args: &Cli, bank: &Bank, clients: &ClientInterner) -> Result<(), String> {
+    #[cfg(feature = "parquet")]
+    if args.output_format == OutputFormat::Parquet {
+        let path = match (&args.output, &args.encryption_key) {
+            (_, Some(_)) => return Err(String::from("Encrypted Parquet output is not supported")),
+            (None, None) => return Err(String::from("--output is required for Parquet output")),
+            (Some(path), None) => path,
+        };
+        let batch = bank.accounts_to_record_batch().map_err(|e| e.to_string())?;
+        return columnar::write_parquet(path, &batch).map_err(|e| e.to_string());
+    }
+    let output: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(File::create(path).map_err(|e| e.to_string())?),
+        None => Box::new(std::io::stdout()),
+    };
+    match &args.encryption_key {
+        Some(key) => {
+            // snapshots are small, so are written out in full before being encrypted
+            let mut plaintext = Vec::new();
+            write_accounts_to(args, bank, clients, &mut plaintext)?;
+            encryption::encrypt(key, &plaintext[..], output).map_err(|e| e.to_string())
+        }
+        None => write_accounts_to(args, bank, clients, output),
+    }
+}
+
+/// Write the changes made to the accounts since `before`, for `--dry-run`.
+fn write_deltas(args: &Cli, before: &[Account], bank: &Bank) -> Result<(), String> {
+    let output: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(File::create(path).map_err(|e| e.to_string())?),
+        None => Box::new(std::io::stdout()),
+    };
+    let deltas = diff::diff(before, bank);
+    info!("The input would change {} accounts", deltas.len());
+    diff::write_deltas(&deltas, output).map_err(|e| e.to_string())
+}
+
+/// Write the resulting accounts in the requested output format to the given writer.
+fn write_accounts_to<W: Write>(args: &Cli, bank: &Bank, clients: &ClientInterner, writer: W) -> Result<(), String> {
+    match args.output_format {
+        OutputFormat::Csv if args.string_client_ids => client::write_accounts(bank, clients, writer).map_err(|e| e.to_string())?,
+        OutputFormat::Csv if args.versions => bank.write_accounts_with_versions(writer).map_err(|e| e.to_string())?,
+        OutputFormat::Csv if args.extended_output && args.with_notes => {
+            bank.write_accounts_extended_with_notes(writer).map_err(|e| e.to_string())?
+        }
+        OutputFormat::Csv if args.extended_output => bank.write_accounts_extended(writer).map_err(|e| e.to_string())?,
+        OutputFormat::Csv if args.risk_score => {
+            risk::write_accounts(bank, &WeightedRiskScorer::default(), writer).map_err(|e| e.to_string())?
         }
+        OutputFormat::Csv => bank.write_accounts(writer).map_err(|e| e.to_string())?,
+        #[cfg(feature = "msgpack")]
+        OutputFormat::Msgpack => msgpack::write_accounts(bank, writer).map_err(|e| e.to_string())?,
+        #[cfg(feature = "parquet")]
+        OutputFormat::Parquet => unreachable!("Parquet output is written by write_accounts"),
+        #[cfg(feature = "protobuf")]
+        OutputFormat::Protobuf => protobuf::write_accounts(bank, writer).map_err(|e| e.to_string())?,
+        #[cfg(feature = "table")]
+        OutputFormat::Table => table::write_accounts(bank, writer).map_err(|e| e.to_string())?,
     }
+    Ok(())
 }
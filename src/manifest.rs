@@ -0,0 +1,171 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Run manifest
+//! A `Manifest` records the provenance of a run, for a pipeline orchestrator to verify and archive
+//! alongside its output: the engine version and command line used, the SHA-256 of every file read
+//! and written, and how many transactions were applied and rejected. It is written as JSON at the
+//! end of a run with `--manifest <path>`.
+//!
+//! Files are hashed as stored, so encrypted input and output are hashed encrypted. Output written
+//! to stdout can not be hashed, and is left out.
+use crate::bank::{Bank, ProcessingSummary};
+use crate::history;
+use crate::journal::to_hex;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::io;
+use std::path::Path;
+use std::time::SystemTime;
+
+/// The version of the engine, as released.
+pub const ENGINE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// `FileDigest` identifies a file by its path and the SHA-256 of its contents.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct FileDigest {
+    pub file: String,
+    pub sha256: String,
+}
+
+impl FileDigest {
+    /// Hash the file at `path`.
+    pub fn of<P: AsRef<Path>>(path: P) -> io::Result<FileDigest> {
+        let path = path.as_ref();
+        Ok(FileDigest {
+            file: path.display().to_string(),
+            sha256: to_hex(&history::hash_file(path)?),
+        })
+    }
+}
+
+/// `Manifest` describes a completed run.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Manifest {
+    pub engine_version: &'static str,
+    pub completed_at: DateTime<Utc>,
+    /// The command-line arguments of the run, which determine its configuration.
+    pub arguments: Vec<String>,
+    pub inputs: Vec<FileDigest>,
+    pub outputs: Vec<FileDigest>,
+    /// The outcomes of the transactions processed. Records that could not be parsed into a
+    /// transaction are not counted.
+    pub transactions: ProcessingSummary,
+    /// The number of accounts at the end of the run.
+    pub accounts: usize,
+}
+
+impl Manifest {
+    /// Describe a run of `bank` with the given arguments, hashing its input and output files.
+    pub fn new<I, O>(bank: &Bank, arguments: Vec<String>, inputs: I, outputs: O) -> io::Result<Manifest>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<Path>,
+        O: IntoIterator,
+        O::Item: AsRef<Path>,
+    {
+        Ok(Manifest {
+            engine_version: ENGINE_VERSION,
+            completed_at: DateTime::<Utc>::from(SystemTime::now()),
+            arguments,
+            inputs: inputs.into_iter().map(FileDigest::of).collect::<io::Result<_>>()?,
+            outputs: outputs.into_iter().map(FileDigest::of).collect::<io::Result<_>>()?,
+            transactions: bank.summary(),
+            accounts: bank.accounts().count(),
+        })
+    }
+
+    /// Write as a JSON object.
+    pub fn write_json<W: io::Write>(&self, writer: W) -> serde_json::Result<()> {
+        serde_json::to_writer_pretty(writer, self)
+    }
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testkit::Scenario;
+    use std::{fs, process};
+
+    /// The SHA-256 of `abc`.
+    const ABC: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    /// A directory of its own for the test `case`, holding a file `abc` with the contents `abc`.
+    fn setup(case: &str) -> io::Result<std::path::PathBuf> {
+        let dir = std::env::temp_dir().join(format!("rust-payment-processor-manifest-{}-{}", case, process::id()));
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join("abc"), "abc")?;
+        Ok(dir)
+    }
+
+    #[test]
+    fn files_are_identified_by_path_and_hash() -> io::Result<()> {
+        // SETUP
+        let dir = setup("digest")?;
+
+        // TEST
+        let digest = FileDigest::of(dir.join("abc"))?;
+        assert_eq!((dir.join("abc").display().to_string(), ABC), (digest.file, digest.sha256.as_str()));
+
+        // TEARDOWN
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn inputs_and_outputs_are_hashed() -> io::Result<()> {
+        // SETUP
+        let dir = setup("files")?;
+        fs::write(dir.join("output"), "")?;
+
+        // TEST
+        let manifest = Manifest::new(&Bank::new(), Vec::new(), vec![dir.join("abc")], vec![dir.join("output")])?;
+        assert_eq!(
+            vec![ABC],
+            manifest.inputs.iter().map(|input| input.sha256.as_str()).collect::<Vec<_>>()
+        );
+        assert_eq!(1, manifest.outputs.len());
+
+        // TEARDOWN
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn missing_files_can_not_be_described() {
+        // TEST
+        let missing = std::env::temp_dir().join(format!("rust-payment-processor-manifest-missing-{}", process::id()));
+        assert!(Manifest::new(&Bank::new(), Vec::new(), vec![missing], Vec::<&Path>::new()).is_err());
+    }
+
+    #[test]
+    fn transactions_and_accounts_are_counted() -> io::Result<()> {
+        // SETUP
+        let bank = Scenario::new().deposit(1, 5).withdraw(1, 7).deposit(2, 1).run();
+
+        // TEST
+        let manifest = Manifest::new(&bank, vec![String::from("input.csv")], Vec::<&Path>::new(), Vec::<&Path>::new())?;
+        assert_eq!((3, 1), (manifest.transactions.processed, manifest.transactions.rejected));
+        assert_eq!(2, manifest.accounts);
+        assert_eq!(vec![String::from("input.csv")], manifest.arguments);
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn manifest_is_written_as_json_with_the_engine_version() -> io::Result<()> {
+        // SETUP
+        let manifest = Manifest::new(&Bank::new(), Vec::new(), Vec::<&Path>::new(), Vec::<&Path>::new())?;
+        let mut json = Vec::new();
+
+        // TEST
+        manifest.write_json(&mut json)?;
+        let json: serde_json::Value = serde_json::from_slice(&json)?;
+        assert_eq!(ENGINE_VERSION, json["engine_version"]);
+        assert_eq!(0, json["accounts"]);
+
+        // TEARDOWN
+        Ok(())
+    }
+}
+//endregion
@@ -0,0 +1,289 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Reference model
+//! A deliberately naive re-implementation of the ledger rules, keeping balances as integer counts
+//! of 1/10000ths, for differential testing: run the engine and the model over the same
+//! transactions and compare the final balances. The model favours being obviously correct over
+//! being fast, so any difference points at the engine, or at a rule that needs writing down.
+//!
+//! Only final balances are compared. Which error a rejected transaction is reported with is not
+//! part of the comparison.
+use crate::account::ClientId;
+use crate::amount::{self, Amount};
+use crate::bank::Bank;
+use crate::redact::{log_sensitive, redact, Redacted};
+use crate::transaction::{Transaction, TransactionType, TxId};
+use rust_decimal::prelude::*;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+
+/// Balances of a single account in the model, in 1/10000ths.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ModelAccount {
+    pub available: i128,
+    pub held: i128,
+    pub total: i128,
+    pub locked: bool,
+}
+
+/// A deposit as remembered by the model.
+struct ModelDeposit {
+    client: ClientId,
+    amount: i128,
+    disputed: bool,
+}
+
+/// `ReferenceModel` applies transactions according to the rules documented on `TransactionType`.
+#[derive(Default)]
+pub struct ReferenceModel {
+    accounts: BTreeMap<ClientId, ModelAccount>,
+    deposits: HashMap<TxId, ModelDeposit>,
+    /// IDs of every accepted deposit and withdrawal.
+    seen: BTreeSet<TxId>,
+}
+
+/// Convert an amount into a count of 1/10000ths, rounding as the engine does.
+pub fn to_units(amount: Amount) -> i128 {
+    let mut value = amount::to_decimal(amount).round_dp(4);
+    value.rescale(4);
+    value.mantissa()
+}
+
+/// The amount of a deposit or withdrawal in 1/10000ths, if it is positive. As in the engine, the
+/// amount must be positive before rounding; an amount that rounds to zero is accepted.
+fn positive_units(amount: Option<Amount>) -> Option<i128> {
+    amount.filter(|amount| *amount > Amount::zero()).map(to_units)
+}
+
+impl ReferenceModel {
+    pub fn new() -> ReferenceModel {
+        ReferenceModel::default()
+    }
+
+    /// The accounts of the model, ordered by client ID.
+    pub fn accounts(&self) -> &BTreeMap<ClientId, ModelAccount> {
+        &self.accounts
+    }
+
+    /// Apply a transaction, returning whether it was accepted.
+    pub fn apply(&mut self, transaction: &Transaction) -> bool {
+        let client = transaction.client;
+        let tx = transaction.tx;
+        match transaction.kind {
+            TransactionType::Deposit => {
+                let amount = match positive_units(transaction.amount) {
+                    Some(amount) => amount,
+                    None => return false,
+                };
+                if self.seen.contains(&tx) {
+                    return false;
+                }
+                let account = self.accounts.entry(client).or_default();
+                if account.locked {
+                    return false;
+                }
+                account.available += amount;
+                account.total += amount;
+                self.seen.insert(tx);
+                self.deposits.insert(
+                    tx,
+                    ModelDeposit {
+                        client,
+                        amount,
+                        disputed: false,
+                    },
+                );
+                true
+            }
+            TransactionType::Withdrawal => {
+                let amount = match positive_units(transaction.amount) {
+                    Some(amount) => amount,
+                    None => return false,
+                };
+                if self.seen.contains(&tx) {
+                    return false;
+                }
+                let account = match self.accounts.get_mut(&client) {
+                    Some(account) if !account.locked && account.available >= amount => account,
+                    _ => return false,
+                };
+                account.available -= amount;
+                account.total -= amount;
+                self.seen.insert(tx);
+                true
+            }
+            TransactionType::Dispute | TransactionType::Resolve | TransactionType::Chargeback => {
+                let deposit = match self.deposits.get_mut(&tx) {
+                    Some(deposit) if deposit.client == client => deposit,
+                    _ => return false,
+                };
+                let wants_disputed = transaction.kind != TransactionType::Dispute;
+                if deposit.disputed != wants_disputed {
+                    return false;
+                }
+                let account = self.accounts.get_mut(&client).expect("deposits open their account");
+                if account.locked {
+                    return false;
+                }
+                match transaction.kind {
+                    TransactionType::Dispute => {
+                        account.available -= deposit.amount;
+                        account.held += deposit.amount;
+                        deposit.disputed = true;
+                    }
+                    TransactionType::Resolve => {
+                        account.held -= deposit.amount;
+                        account.available += deposit.amount;
+                        deposit.disputed = false;
+                    }
+                    _ => {
+                        account.held -= deposit.amount;
+                        account.total -= deposit.amount;
+                        account.locked = true;
+                        deposit.disputed = false;
+                    }
+                }
+                true
+            }
+            // the model only knows the types the generators produce
+            TransactionType::Adjustment | TransactionType::PromoCredit | TransactionType::Custom(_) => false,
+        }
+    }
+}
+
+/// `Difference` describes an account on which the engine and the model disagree.
+#[derive(Debug, PartialEq)]
+pub struct Difference {
+    pub client: ClientId,
+    /// The account according to the engine, if it has one.
+    pub engine: Option<ModelAccount>,
+    /// The account according to the model, if it has one.
+    pub model: Option<ModelAccount>,
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "client {}: engine has {:?}, model has {:?}",
+            self.client, self.engine, self.model
+        )
+    }
+}
+
+/// Leaves out the balances, and the client, unless sensitive logging is enabled.
+impl fmt::Display for Redacted<'_, Difference> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if log_sensitive() {
+            fmt::Display::fmt(self.0, f)
+        } else {
+            write!(
+                f,
+                "client {}: the engine and the model hold different balances",
+                redact(&self.0.client)
+            )
+        }
+    }
+}
+
+/// Compare the accounts of the engine and the model, returning every account on which they
+/// disagree, ordered by client ID.
+pub fn diff(bank: &Bank, model: &ReferenceModel) -> Vec<Difference> {
+    let engine: BTreeMap<ClientId, ModelAccount> = bank
+        .accounts()
+        .map(|account| {
+            (
+                account.client,
+                ModelAccount {
+                    available: to_units(account.available),
+                    held: to_units(account.held),
+                    total: to_units(account.total),
+                    locked: account.locked,
+                },
+            )
+        })
+        .collect();
+    let clients: BTreeSet<ClientId> = engine.keys().chain(model.accounts().keys()).copied().collect();
+    clients
+        .into_iter()
+        .filter_map(|client| {
+            let engine = engine.get(&client).copied();
+            let model = model.accounts().get(&client).copied();
+            if engine == model {
+                None
+            } else {
+                Some(Difference { client, engine, model })
+            }
+        })
+        .collect()
+}
+
+/// Run the engine, on the given bank, and the model over the same transactions, returning every
+/// account on which the two disagree. The model knows only the default rules, so options of the
+/// bank that change balances, such as fees, show up as differences.
+pub fn run_differential<I: IntoIterator<Item = Transaction>>(bank: &mut Bank, transactions: I) -> Vec<Difference> {
+    let mut model = ReferenceModel::new();
+    for transaction in transactions {
+        model.apply(&transaction);
+        // failures are expected; they are compared through the resulting balances
+        let _ = bank.process_transaction(transaction);
+    }
+    diff(bank, &model)
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate;
+    use crate::testkit::Scenario;
+    use proptest::prelude::*;
+
+    #[test]
+    fn model_agrees_with_engine_on_chargeback_after_withdrawal() {
+        // SETUP
+        let scenario = Scenario::new()
+            .deposit(1, 5)
+            .withdraw(1, 3)
+            .dispute(1, 1)
+            .chargeback(1, 1)
+            .deposit(1, 1);
+
+        // TEST
+        let differences = run_differential(&mut Bank::new(), scenario.transactions().to_vec());
+        assert_eq!(Vec::<Difference>::new(), differences);
+    }
+
+    #[test]
+    fn differential_runs_on_the_given_bank() {
+        // SETUP
+        let mut bank = Bank::builder().audit(true).build();
+        let scenario = Scenario::new().deposit(1, 5).withdraw(1, 3);
+
+        // TEST
+        let differences = run_differential(&mut bank, scenario.transactions().to_vec());
+        assert_eq!(Vec::<Difference>::new(), differences);
+        assert_eq!(2, bank.audit_trail(1).len());
+        assert_eq!(Amount::from(2u32), bank.account(1).unwrap().available);
+    }
+
+    #[test]
+    fn diff_reports_missing_accounts() {
+        // SETUP
+        let mut model = ReferenceModel::new();
+        model.apply(&Transaction::make(TransactionType::Deposit, 2, 1, 1, false));
+
+        // TEST
+        let differences = diff(&Bank::new(), &model);
+        assert_eq!(1, differences.len());
+        assert_eq!((2, None), (differences[0].client, differences[0].engine));
+    }
+
+    proptest! {
+        #[test]
+        fn engine_matches_model(transactions in generate::transactions(200)) {
+            let differences = run_differential(&mut Bank::new(), transactions);
+            prop_assert_eq!(Vec::<Difference>::new(), differences);
+        }
+    }
+}
+//endregion
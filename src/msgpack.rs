@@ -0,0 +1,209 @@
+#![forbid(unsafe_code)] // for good measure
+//! # MessagePack input and output
+//! Transactions can be read from, and accounts written to, streams of concatenated MessagePack
+//! values. Each transaction is a map (or, as `rmp-serde` writes structs by default, an array in
+//! column order) with the same fields as a CSV record. Amounts may be strings or numbers.
+//!
+//! Every value is read in full before being mapped onto a `Transaction`, so a value that can not
+//! be mapped is logged and discarded without losing our place in the stream.
+use crate::bank::Bank;
+use crate::locale::Localize;
+use crate::transaction::{RecordOptions, Transaction, TransactionRecord};
+use std::fmt;
+use std::io::{self, Read, Write};
+
+/// `MsgpackError` is returned when a stream can not be read.
+#[derive(Debug)]
+pub enum MsgpackError {
+    /// The stream could not be read, or is not valid MessagePack.
+    Decode(rmpv::decode::Error),
+}
+
+impl fmt::Display for MsgpackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MsgpackError::Decode(e) => write!(f, "failed to read MessagePack stream: {}", e),
+        }
+    }
+}
+
+/// Read every transaction in a stream of concatenated MessagePack values, with the `options` of its
+/// source.
+pub fn read_transactions<R: Read>(mut reader: R, options: &RecordOptions) -> Result<Vec<Transaction>, MsgpackError> {
+    let mut transactions = Vec::new();
+    let mut buf = Vec::new();
+    let mut index = 0;
+    loop {
+        let value = match rmpv::decode::read_value(&mut reader) {
+            Ok(value) => value,
+            Err(rmpv::decode::Error::InvalidMarkerRead(e)) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(MsgpackError::Decode(e)),
+        };
+        index += 1;
+        // `rmpv`'s own deserializer does not accept enum variants written as strings, so the value is
+        // re-encoded and handed to `rmp-serde` instead.
+        buf.clear();
+        rmpv::encode::write_value(&mut buf, &value).expect("writing to a Vec can not fail");
+        let parsed = rmp_serde::from_slice::<TransactionRecord>(&buf)
+            .map_err(|e| e.to_string())
+            .and_then(|record| options.transaction(record).map_err(|e| e.localized(options.locale).to_string()));
+        match parsed {
+            Ok(transaction) => transactions.push(transaction),
+            Err(e) => error!("Failed to map MessagePack value {}. Discarded with error: {}", index, e),
+        }
+    }
+    Ok(transactions)
+}
+
+/// Write every account of the bank to the given writer as concatenated MessagePack maps.
+pub fn write_accounts<W: Write>(bank: &Bank, mut writer: W) -> io::Result<()> {
+    for account in bank.accounts() {
+        rmp_serde::encode::write_named(&mut writer, account).map_err(io::Error::other)?;
+    }
+    writer.flush()
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amount::{self, Amount};
+    use crate::transaction::TransactionType;
+    use rmpv::Value;
+    use rust_decimal::Decimal;
+
+    fn record(kind: &str, tx: u32, amount: Value) -> Value {
+        Value::Map(vec![
+            (Value::from("type"), Value::from(kind)),
+            (Value::from("client"), Value::from(1)),
+            (Value::from("tx"), Value::from(tx)),
+            (Value::from("amount"), amount),
+        ])
+    }
+
+    fn stream(values: &[Value]) -> Vec<u8> {
+        let mut stream = Vec::new();
+        for value in values {
+            rmpv::encode::write_value(&mut stream, value).unwrap();
+        }
+        stream
+    }
+
+    fn amounts(values: &[Value]) -> Result<Vec<Option<Amount>>, MsgpackError> {
+        let transactions = read_transactions(stream(values).as_slice(), &RecordOptions::default())?;
+        Ok(transactions.iter().map(|transaction| transaction.amount).collect())
+    }
+
+    #[test]
+    fn string_amounts_are_read() -> Result<(), MsgpackError> {
+        // TEST
+        assert_eq!(
+            vec![amount::from_decimal(Decimal::new(15, 1))],
+            amounts(&[record("deposit", 1, Value::from("1.5"))])?
+        );
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn numeric_amounts_are_read() -> Result<(), MsgpackError> {
+        // TEST
+        let amounts = amounts(&[record("deposit", 1, Value::from(2)), record("deposit", 2, Value::from(1.25))])?;
+        assert_eq!(vec![Some(Amount::from(2u32)), amount::from_decimal(Decimal::new(125, 2))], amounts);
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn references_are_read_without_an_amount() -> Result<(), MsgpackError> {
+        // SETUP
+        let stream = stream(&[record("dispute", 1, Value::Nil)]);
+
+        // TEST
+        let transactions = read_transactions(stream.as_slice(), &RecordOptions::default())?;
+        assert_eq!(
+            (TransactionType::Dispute, None),
+            (transactions[0].kind.clone(), transactions[0].amount)
+        );
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn arrays_are_read_in_column_order() -> Result<(), MsgpackError> {
+        // SETUP
+        let value = Value::Array(vec![Value::from("withdrawal"), Value::from(3), Value::from(7), Value::from("2")]);
+
+        // TEST
+        let transactions = read_transactions(stream(&[value]).as_slice(), &RecordOptions::default())?;
+        let transaction = &transactions[0];
+        assert_eq!(
+            (TransactionType::Withdrawal, 3, 7, Some(Amount::from(2u32))),
+            (transaction.kind.clone(), transaction.client, transaction.tx, transaction.amount)
+        );
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn values_that_can_not_be_mapped_are_discarded() -> Result<(), MsgpackError> {
+        // TEST
+        let amounts = amounts(&[
+            record("deposit", 1, Value::Nil),
+            Value::from("deposit"),
+            record("deposit", 2, Value::from(1)),
+        ])?;
+        assert_eq!(vec![Some(Amount::from(1u32))], amounts);
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn stream_cut_short_is_an_error() {
+        // SETUP
+        let mut stream = stream(&[record("deposit", 1, Value::from(1))]);
+        // a string of 10 bytes, cut short after 2
+        stream.extend_from_slice(&[0xd9, 10, b'a', b'b']);
+
+        // TEST
+        let error = read_transactions(stream.as_slice(), &RecordOptions::default()).unwrap_err();
+        assert!(error.to_string().starts_with("failed to read MessagePack stream"), "{}", error);
+    }
+
+    #[test]
+    fn accounts_are_written_as_maps() -> io::Result<()> {
+        // SETUP
+        let mut bank = Bank::new();
+        bank.process_all(
+            read_transactions(
+                stream(&[record("deposit", 1, Value::from(2))]).as_slice(),
+                &RecordOptions::default(),
+            )
+            .unwrap(),
+        );
+        let mut output = Vec::new();
+
+        // TEST
+        write_accounts(&bank, &mut output)?;
+        let value = rmpv::decode::read_value(&mut output.as_slice()).map_err(io::Error::other)?;
+        let field = |name: &str| {
+            value
+                .as_map()
+                .unwrap()
+                .iter()
+                .find(|(key, _)| key.as_str() == Some(name))
+                .map(|(_, value)| value.clone())
+        };
+        assert_eq!(Some(Value::from(1)), field("client"));
+        assert_eq!(Some(Value::from(false)), field("locked"));
+
+        // TEARDOWN
+        Ok(())
+    }
+}
+//endregion
@@ -0,0 +1,226 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Parallel parsing
+//! Parsing and validating records takes most of the time of a run, while applying them to a
+//! `Bank` is cheap but must happen in input order on one thread. `read_transactions` splits the
+//! two: a reader thread reads raw records in batches and parses each batch on the rayon pool, and
+//! the parsed batches are passed, in input order, through a bounded channel to the thread applying
+//! them. The channel holds at most a few batches, so a slow consumer holds back the reader rather
+//! than the whole input being parsed into memory.
+//!
+//! Records that can not be parsed are logged and skipped, as by `schema::read_transactions`.
+//! `read_transactions_from` records the line each transaction was read from, as
+//! `schema::read_transactions_from` does.
+use crate::locale::Localize;
+use crate::provenance::Provenance;
+use crate::transaction::{RecordOptions, Transaction, TransactionRecord};
+use rayon::prelude::*;
+use std::io;
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+/// The number of records parsed together on the rayon pool.
+pub const BATCH_SIZE: usize = 4096;
+
+/// The number of parsed batches that may wait to be applied before the reader waits too.
+const QUEUE_DEPTH: usize = 4;
+
+/// Adapt a CSV reader into an iterator of transactions, parsed in parallel with the `options` of
+/// their source and yielded in input order. The reader's header row, if it has one, must already
+/// have been checked.
+pub fn read_transactions<R: io::Read + Send + 'static>(
+    reader: csv::Reader<R>,
+    options: RecordOptions,
+) -> impl Iterator<Item = Transaction> {
+    read(reader, None, options)
+}
+
+/// As `read_transactions`, recording the line of `file` each transaction was read from as its
+/// `provenance`. Records that can not be parsed are logged with their line.
+pub fn read_transactions_from<R: io::Read + Send + 'static>(
+    reader: csv::Reader<R>,
+    file: &str,
+    options: RecordOptions,
+) -> impl Iterator<Item = Transaction> {
+    read(reader, Some(Arc::from(file)), options)
+}
+
+fn read<R: io::Read + Send + 'static>(
+    mut reader: csv::Reader<R>,
+    file: Option<Arc<str>>,
+    options: RecordOptions,
+) -> impl Iterator<Item = Transaction> {
+    let (sender, receiver) = mpsc::sync_channel::<Vec<Option<Transaction>>>(QUEUE_DEPTH);
+    thread::spawn(move || {
+        let headers = match reader.has_headers() {
+            true => reader.byte_headers().ok().cloned(),
+            false => None,
+        };
+        // records are read into the same buffers batch after batch, rather than allocated per row
+        let mut batch = vec![csv::ByteRecord::new(); BATCH_SIZE];
+        let mut len = 0;
+        loop {
+            let more = match reader.read_byte_record(&mut batch[len]) {
+                Ok(more) => more,
+                Err(e) => {
+                    error!("Failed to read transaction. Discarded with error: {}", e);
+                    // the input itself can not be read any further
+                    !matches!(e.kind(), csv::ErrorKind::Io(_))
+                }
+            };
+            if more && !batch[len].is_empty() {
+                len += 1;
+            }
+            if len == BATCH_SIZE || (!more && len > 0) {
+                let parsed = batch[..len]
+                    .par_iter()
+                    .map(|record| parse(record, headers.as_ref(), file.as_ref(), &options))
+                    .collect();
+                len = 0;
+                // the consumer has stopped reading
+                if sender.send(parsed).is_err() {
+                    return;
+                }
+            }
+            if !more {
+                return;
+            }
+        }
+    });
+    receiver.into_iter().flatten().flatten()
+}
+
+fn parse(
+    record: &csv::ByteRecord,
+    headers: Option<&csv::ByteRecord>,
+    file: Option<&Arc<str>>,
+    options: &RecordOptions,
+) -> Option<Transaction> {
+    let parsed = record
+        .deserialize::<TransactionRecord>(headers)
+        .map_err(|e| e.to_string())
+        .and_then(|record| options.transaction(record).map_err(|e| e.localized(options.locale).to_string()));
+    let line = record.position().map_or(0, csv::Position::line);
+    match (parsed, file) {
+        (Ok(mut transaction), Some(file)) => {
+            transaction.provenance = Some(Provenance::Line { file: file.clone(), line });
+            Some(transaction)
+        }
+        (Ok(transaction), None) => Some(transaction),
+        (Err(e), Some(file)) => {
+            error!("Failed to parse transaction at {}:{}. Discarded with error: {}", file, line, e);
+            None
+        }
+        (Err(e), None) => {
+            error!("Failed to parse transaction. Discarded with error: {}", e);
+            None
+        }
+    }
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema;
+    use crate::transaction::TxId;
+
+    /// CSV input with a header row and a deposit of 3 for every transaction ID in `txs`.
+    fn deposits(txs: std::ops::RangeInclusive<TxId>) -> String {
+        let mut input = String::from("type,client,tx,amount\n");
+        for tx in txs {
+            input.push_str(&format!("deposit,{},{},3\n", tx % 7, tx));
+        }
+        input
+    }
+
+    fn parallel(input: String) -> impl Iterator<Item = Transaction> {
+        read_transactions(
+            csv::Reader::from_reader(io::Cursor::new(input.into_bytes())),
+            RecordOptions::default(),
+        )
+    }
+
+    #[test]
+    fn parallel_parsing_preserves_input_order() {
+        // SETUP
+        let mut input = String::from("type,client,tx,amount\n");
+        for tx in 1..=(2 * BATCH_SIZE as u64 + 10) {
+            match tx % 3 {
+                0 => input.push_str(&format!("withdrawal,{},{},2\n", tx % 7, tx)),
+                1 => input.push_str(&format!("deposit,{},{},3\n", tx % 7, tx)),
+                _ => input.push_str(&format!("deposit,{},{},oops\n", tx % 7, tx)),
+            }
+        }
+        let serial = csv::Reader::from_reader(input.as_bytes());
+
+        // TEST
+        let expected: Vec<Transaction> = schema::read_transactions(&mut { serial }, &RecordOptions::default()).collect();
+        let transactions: Vec<Transaction> = parallel(input).collect();
+        assert_eq!(expected, transactions);
+    }
+
+    #[test]
+    fn records_that_can_not_be_parsed_are_skipped() {
+        // SETUP
+        let input = String::from("type,client,tx,amount\ndeposit,1,1,oops\ndeposit,x,2,1\ndeposit,1,3,1\n");
+
+        // TEST
+        let txs: Vec<TxId> = parallel(input).map(|transaction| transaction.tx).collect();
+        assert_eq!(vec![3], txs);
+    }
+
+    #[test]
+    fn input_without_a_header_row_is_read_in_column_order() {
+        // SETUP
+        let input = "deposit,1,1,1\nwithdrawal,2,2,1\n";
+        let reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .from_reader(io::Cursor::new(input.as_bytes().to_vec()));
+
+        // TEST
+        let transactions: Vec<Transaction> = read_transactions(reader, RecordOptions::default()).collect();
+        assert_eq!(
+            vec![(1, 1), (2, 2)],
+            transactions
+                .iter()
+                .map(|transaction| (transaction.client, transaction.tx))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn provenance_is_the_line_each_transaction_was_read_from() {
+        // SETUP
+        let input = "type,client,tx,amount\ndeposit,1,1,1\ndeposit,1,2,oops\nwithdrawal,1,3,1\n";
+        let reader = csv::Reader::from_reader(io::Cursor::new(input.as_bytes().to_vec()));
+
+        // TEST
+        let sources: Vec<String> = read_transactions_from(reader, "input.csv", RecordOptions::default())
+            .map(|transaction| transaction.provenance.map(|source| source.to_string()).unwrap_or_default())
+            .collect();
+        assert_eq!(vec!["input.csv:2", "input.csv:4"], sources);
+    }
+
+    #[test]
+    fn input_without_records_yields_nothing() {
+        // TEST
+        assert_eq!(0, parallel(String::from("type,client,tx,amount\n")).count());
+    }
+
+    #[test]
+    fn batch_cut_short_by_the_end_of_input_is_yielded() {
+        // TEST
+        assert_eq!(BATCH_SIZE + 1, parallel(deposits(1..=BATCH_SIZE as TxId + 1)).count());
+    }
+
+    #[test]
+    fn reader_stops_once_the_consumer_does() {
+        // TEST
+        let first: Vec<TxId> = parallel(deposits(1..=20 * BATCH_SIZE as TxId))
+            .take(2)
+            .map(|transaction| transaction.tx)
+            .collect();
+        assert_eq!(vec![1, 2], first);
+    }
+}
+//endregion
@@ -0,0 +1,236 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Bank position
+//! The `Position` of a bank is what finance signs off at the end of the day: what the bank owes its
+//! clients, how much of it is held by disputes, and how the run moved it. A `PositionCollector` is a
+//! sink that sums the deposits and withdrawals applied during a run, and
+//! `PositionCollector::finish` combines the sums with the bank's balances before and after it.
+//!
+//! Chargebacks do not carry an amount of their own, so the amount charged back is derived from the
+//! others: it is the part of the movement in liabilities that deposits and withdrawals do not
+//! and fees account for. Handlers of custom transaction types that move funds are counted there too.
+use crate::amount::Amount;
+use crate::analytics::{metric_writer, MetricRow};
+use crate::bank::Bank;
+use crate::errors::BankingError;
+use crate::locale::Locale;
+use crate::sink::ResultSink;
+use crate::transaction::{Transaction, TransactionType};
+use rust_decimal::prelude::*;
+use serde::Serialize;
+use std::io;
+
+/// `PositionCollector` is a sink that sums the funds moved by a run for `Position`.
+#[derive(Clone, Debug)]
+pub struct PositionCollector {
+    opening_liabilities: Amount,
+    opening_fees: Amount,
+    deposited: Amount,
+    withdrawn: Amount,
+}
+
+impl PositionCollector {
+    /// Start collecting for a run on `bank`, taking its current liabilities as the opening balance.
+    /// Fails with `AmountOverflow` if the bank's balances can not be summed.
+    pub fn new(bank: &Bank) -> Result<PositionCollector, BankingError> {
+        Ok(PositionCollector {
+            opening_liabilities: bank.total()?,
+            opening_fees: bank.total_fees()?,
+            deposited: Amount::zero(),
+            withdrawn: Amount::zero(),
+        })
+    }
+
+    /// Combine the sums with the balances of `bank` once the run is over. Fails with
+    /// `AmountOverflow` if the balances or the movement between them can not be represented.
+    pub fn finish(&self, bank: &Bank) -> Result<Position, BankingError> {
+        let difference = |a: Amount, b: Amount| a.checked_sub(b).ok_or(BankingError::AmountOverflow);
+        let closing = bank.total()?;
+        let net_movement = difference(closing, self.opening_liabilities)?;
+        let fees = difference(bank.total_fees()?, self.opening_fees)?;
+        let received = difference(self.deposited, self.withdrawn)?;
+        let charged_back = difference(difference(received, fees)?, net_movement)?;
+        Ok(Position {
+            total_liabilities: closing,
+            total_available: bank.total_available()?,
+            total_held: bank.total_held()?,
+            erased_balance: bank.erased_balance()?,
+            locked_accounts: bank.accounts().filter(|account| account.locked).count(),
+            opening_liabilities: self.opening_liabilities,
+            deposited: self.deposited,
+            withdrawn: self.withdrawn,
+            charged_back,
+            fees,
+            net_movement,
+        })
+    }
+}
+
+impl ResultSink for PositionCollector {
+    fn record(&mut self, _index: usize, transaction: &Transaction, result: Result<(), BankingError>) {
+        if result.is_err() {
+            return;
+        }
+        let amount = transaction.amount.unwrap_or_else(Amount::zero);
+        match transaction.kind {
+            TransactionType::Deposit => self.deposited += amount,
+            TransactionType::Withdrawal => self.withdrawn += amount,
+            _ => {}
+        }
+    }
+}
+
+/// `Position` is the aggregate position of a bank at the end of a run.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Position {
+    /// The total funds of every client, including the erased balance of forgotten clients.
+    pub total_liabilities: Amount,
+    pub total_available: Amount,
+    pub total_held: Amount,
+    pub erased_balance: Amount,
+    pub locked_accounts: usize,
+    /// The total liabilities before the run.
+    pub opening_liabilities: Amount,
+    pub deposited: Amount,
+    pub withdrawn: Amount,
+    pub charged_back: Amount,
+    /// The fees assessed by the bank, such as chargeback fees.
+    pub fees: Amount,
+    /// The change in total liabilities over the run.
+    pub net_movement: Amount,
+}
+
+impl Position {
+    /// Write as CSV with the columns `metric, key, value`, one `position` row per figure. The header
+    /// row is written in the given locale.
+    pub fn write_csv<W: io::Write>(&self, writer: W, locale: Locale) -> Result<(), csv::Error> {
+        let mut wtr = metric_writer(writer, locale)?;
+        self.write_rows(&mut wtr)?;
+        wtr.flush()?;
+        Ok(())
+    }
+
+    /// As `write_csv`, writing the rows to a CSV writer that other reports may share.
+    pub fn write_rows<W: io::Write>(&self, wtr: &mut csv::Writer<W>) -> Result<(), csv::Error> {
+        let figures = [
+            ("total_liabilities", self.total_liabilities.to_string()),
+            ("total_available", self.total_available.to_string()),
+            ("total_held", self.total_held.to_string()),
+            ("erased_balance", self.erased_balance.to_string()),
+            ("locked_accounts", self.locked_accounts.to_string()),
+            ("opening_liabilities", self.opening_liabilities.to_string()),
+            ("deposited", self.deposited.to_string()),
+            ("withdrawn", self.withdrawn.to_string()),
+            ("charged_back", self.charged_back.to_string()),
+            ("fees", self.fees.to_string()),
+            ("net_movement", self.net_movement.to_string()),
+        ];
+        for (key, value) in figures {
+            wtr.serialize(MetricRow {
+                metric: "position",
+                key,
+                value,
+            })?;
+        }
+        Ok(())
+    }
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testkit::Scenario;
+
+    /// The position of `bank` after processing the transactions of `scenario` after the first
+    /// `opening` of them, which make up its opening balance.
+    fn position_of(mut bank: Bank, scenario: Scenario, opening: usize) -> Position {
+        let (earlier, later) = scenario.transactions().split_at(opening);
+        bank.process_all(earlier.to_vec());
+        let mut collector = PositionCollector::new(&bank).expect("small balances");
+        bank.process_all_with(later.to_vec(), &mut collector);
+        collector.finish(&bank).expect("small balances")
+    }
+
+    #[test]
+    fn opening_liabilities_are_those_before_the_run() {
+        // TEST
+        let position = position_of(Bank::new(), Scenario::new().deposit(1, 10).deposit(2, 20), 1);
+        assert_eq!(Amount::from(10u32), position.opening_liabilities);
+        assert_eq!(Amount::from(30u32), position.total_liabilities);
+        assert_eq!(Amount::from(20u32), position.net_movement);
+    }
+
+    #[test]
+    fn applied_deposits_and_withdrawals_are_summed() {
+        // TEST
+        let position = position_of(
+            Bank::new(),
+            Scenario::new().deposit(1, 20).deposit(2, 5).withdraw(1, 4).withdraw(2, 50),
+            0,
+        );
+        assert_eq!((Amount::from(25u32), Amount::from(4u32)), (position.deposited, position.withdrawn));
+    }
+
+    #[test]
+    fn disputed_funds_are_held() {
+        // TEST
+        let position = position_of(Bank::new(), Scenario::new().deposit(1, 10).deposit(1, 5).dispute(1, 1), 1);
+        assert_eq!(
+            (Amount::from(5u32), Amount::from(10u32)),
+            (position.total_available, position.total_held)
+        );
+    }
+
+    #[test]
+    fn charged_back_is_derived_from_the_movement() {
+        // TEST
+        let position = position_of(
+            Bank::new(),
+            Scenario::new().deposit(1, 10).deposit(2, 5).dispute(2, 2).chargeback(2, 2),
+            1,
+        );
+        assert_eq!(Amount::from(5u32), position.charged_back);
+        assert_eq!(Amount::zero(), position.net_movement);
+        assert_eq!(1, position.locked_accounts);
+    }
+
+    #[test]
+    fn chargeback_fees_are_not_charged_back() {
+        // TEST
+        let bank = Bank::builder().chargeback_fee(Amount::from(1u32)).build();
+        let position = position_of(bank, Scenario::new().deposit(1, 5).dispute(1, 1).chargeback(1, 1), 0);
+        assert_eq!((Amount::from(5u32), Amount::from(1u32)), (position.charged_back, position.fees));
+    }
+
+    #[test]
+    fn erased_balances_are_still_owed() {
+        // SETUP
+        let mut bank = Scenario::new().deposit(1, 10).deposit(2, 5).run();
+        bank.forget(1).unwrap();
+
+        // TEST
+        let position = PositionCollector::new(&bank).unwrap().finish(&bank).unwrap();
+        assert_eq!(
+            (Amount::from(10u32), Amount::from(15u32)),
+            (position.erased_balance, position.total_liabilities)
+        );
+    }
+
+    #[test]
+    fn position_is_written_with_a_row_per_figure() -> Result<(), csv::Error> {
+        // SETUP
+        let position = position_of(Bank::new(), Scenario::new().deposit(1, 10).withdraw(1, 3), 0);
+        let mut output = Vec::new();
+
+        // TEST
+        position.write_csv(&mut output, Locale::En)?;
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(12, output.lines().count());
+        assert!(output.starts_with("metric,key,value\nposition,total_liabilities,7\n"));
+        assert!(output.ends_with("position,fees,0\nposition,net_movement,7\n"));
+
+        // TEARDOWN
+        Ok(())
+    }
+}
+//endregion
@@ -0,0 +1,274 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Input profiles
+//! Partners export transactions with their own conventions: a different delimiter, a decimal
+//! comma, returns encoded as negative deposits, timestamps in local time, or no currency at all. An
+//! `InputProfile` names the conventions of one source, so that its files can be read as they are
+//! delivered. Profiles are kept in a JSON file of named profiles, selected on the command line
+//! with `--profiles <path> --profile <name>`:
+//!
+//! ```json
+//! {
+//!     "partner-x": {
+//!         "delimiter": "semicolon",
+//!         "decimal_comma": true,
+//!         "encoding": "windows-1252",
+//!         "negative_deposits": "withdrawal",
+//!         "amounts": "debit-credit",
+//!         "currency": "EUR",
+//!         "utc_offset": "+01:00",
+//!         "columns": {"txn_type": "type", "customer": "client"}
+//!     }
+//! }
+//! ```
+//!
+//! `amounts` is how deposits and withdrawals are told apart: by the `type` column (`typed`, the
+//! default), by the sign of the `amount` (`signed`), or by separate `debit` and `credit` columns
+//! (`debit-credit`). See `AmountColumns`.
+//!
+//! Every setting is optional, and defaults to the behaviour without a profile. Options given on
+//! the command line take precedence over the profile.
+use crate::amount::AmountFormat;
+use crate::transaction::{AmountColumns, NegativeDeposits, RecordOptions};
+use crate::charset;
+use chrono::FixedOffset;
+use encoding_rs::Encoding;
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::Path;
+
+/// `InputProfile` describes the conventions of a source's input files.
+#[derive(Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct InputProfile {
+    /// The field delimiter, as for `parse_delimiter`.
+    #[serde(deserialize_with = "deserialize_delimiter")]
+    pub delimiter: Option<u8>,
+    /// The files have no header row.
+    pub no_header: bool,
+    /// Amounts use `,` as the decimal separator.
+    pub decimal_comma: bool,
+    /// The encoding of files without a byte order mark, as for `charset::parse_encoding`.
+    #[serde(deserialize_with = "deserialize_encoding")]
+    pub encoding: Option<&'static Encoding>,
+    pub negative_deposits: Option<NegativeDeposits>,
+    /// How deposits and withdrawals are told apart, e.g. by the sign of the amount.
+    pub amounts: Option<AmountColumns>,
+    /// The currency of records that carry none.
+    pub currency: Option<String>,
+    /// The offset of timestamps written without one, e.g. `+01:00`.
+    #[serde(deserialize_with = "deserialize_offset")]
+    pub utc_offset: Option<FixedOffset>,
+    /// The header names of the source mapped to the fields they hold, e.g. `customer` to `client`.
+    /// See `schema::map_columns`.
+    pub columns: HashMap<String, String>,
+}
+
+impl InputProfile {
+    /// The `RecordOptions` records of this source are read with.
+    pub fn record_options(&self) -> RecordOptions {
+        RecordOptions {
+            negative_deposits: self.negative_deposits.unwrap_or(NegativeDeposits::Reject),
+            amounts: self.amounts.unwrap_or(AmountColumns::Typed),
+            amount_format: AmountFormat {
+                decimal_comma: self.decimal_comma,
+                ..AmountFormat::new()
+            },
+            currency: self.currency.clone(),
+            utc_offset: self.utc_offset,
+            ..RecordOptions::new()
+        }
+    }
+}
+
+/// `ProfileError` enumerates the ways a profile can fail to load.
+#[derive(Debug)]
+pub enum ProfileError {
+    Io(io::Error),
+    /// The profiles file is not valid JSON, or a profile has an invalid setting.
+    Parse(serde_json::Error),
+    /// The profiles file has no profile of this name.
+    Unknown(String),
+}
+
+impl fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProfileError::Io(e) => write!(f, "failed to read profiles: {}", e),
+            ProfileError::Parse(e) => write!(f, "invalid profiles: {}", e),
+            ProfileError::Unknown(name) => write!(f, "no such profile: {}", name),
+        }
+    }
+}
+
+/// Read the named profile from a JSON object of profiles.
+pub fn read<R: io::Read>(reader: R, name: &str) -> Result<InputProfile, ProfileError> {
+    let mut profiles: HashMap<String, InputProfile> = serde_json::from_reader(reader).map_err(ProfileError::Parse)?;
+    profiles.remove(name).ok_or_else(|| ProfileError::Unknown(name.to_string()))
+}
+
+/// As `read`, from the file at `path`.
+pub fn load<P: AsRef<Path>>(path: P, name: &str) -> Result<InputProfile, ProfileError> {
+    read(BufReader::new(File::open(path).map_err(ProfileError::Io)?), name)
+}
+
+/// Parse a delimiter, accepting either a name or a single ASCII character.
+pub fn parse_delimiter(value: &str) -> Result<u8, String> {
+    match value {
+        "comma" => Ok(b','),
+        "tab" | "\\t" => Ok(b'\t'),
+        "semicolon" => Ok(b';'),
+        "pipe" => Ok(b'|'),
+        _ if value.len() == 1 && value.is_ascii() => Ok(value.as_bytes()[0]),
+        _ => Err(format!("unsupported delimiter: {}", value)),
+    }
+}
+
+fn deserialize_delimiter<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u8>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|value| parse_delimiter(&value).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+fn deserialize_encoding<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<&'static Encoding>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|label| charset::parse_encoding(&label).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+fn deserialize_offset<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<FixedOffset>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| serde::de::Error::custom(format!("invalid UTC offset: {} (expected e.g. +01:00)", value)))
+        })
+        .transpose()
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::parse_timestamp;
+    use std::process;
+
+    const PROFILES: &str = r#"{
+        "partner-x": {"delimiter": "semicolon", "negative_deposits": "withdrawal", "currency": "EUR", "utc_offset": "+01:00"},
+        "partner-y": {"no_header": true, "amounts": "debit-credit", "decimal_comma": true, "encoding": "windows-1252"}
+    }"#;
+
+    #[test]
+    fn profile_is_read_by_name() -> Result<(), ProfileError> {
+        // TEST
+        let profile = read(PROFILES.as_bytes(), "partner-x")?;
+        assert_eq!(Some(b';'), profile.delimiter);
+        assert!(!profile.no_header);
+        assert!(read(PROFILES.as_bytes(), "partner-y")?.no_header);
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_profile_is_an_error() {
+        // TEST
+        let error = read(PROFILES.as_bytes(), "partner-z").unwrap_err();
+        assert_eq!("no such profile: partner-z", error.to_string());
+    }
+
+    #[test]
+    fn unknown_settings_are_rejected() {
+        // TEST
+        assert!(matches!(
+            read(r#"{"p": {"quote": "'"}}"#.as_bytes(), "p"),
+            Err(ProfileError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn invalid_settings_are_rejected() {
+        // TEST
+        for profile in [
+            r#"{"p": {"delimiter": "::"}}"#,
+            r#"{"p": {"encoding": "klingon"}}"#,
+            r#"{"p": {"utc_offset": "CET"}}"#,
+        ] {
+            assert!(matches!(read(profile.as_bytes(), "p"), Err(ProfileError::Parse(_))), "{}", profile);
+        }
+    }
+
+    #[test]
+    fn empty_profile_reads_records_as_without_one() -> Result<(), ProfileError> {
+        // TEST
+        let options = read(r#"{"p": {}}"#.as_bytes(), "p")?.record_options();
+        assert_eq!(NegativeDeposits::Reject, options.negative_deposits);
+        assert_eq!(AmountColumns::Typed, options.amounts);
+        assert_eq!((None, None), (options.currency, options.utc_offset));
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn records_are_read_with_the_conventions_of_the_profile() -> Result<(), ProfileError> {
+        // TEST
+        let options = read(PROFILES.as_bytes(), "partner-x")?.record_options();
+        assert_eq!(NegativeDeposits::Withdrawal, options.negative_deposits);
+        assert_eq!(Some("EUR"), options.currency.as_deref());
+        let options = read(PROFILES.as_bytes(), "partner-y")?.record_options();
+        assert_eq!(AmountColumns::DebitCredit, options.amounts);
+        assert!(options.amount_format.decimal_comma);
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn timestamps_without_an_offset_are_read_in_that_of_the_profile() -> Result<(), ProfileError> {
+        // SETUP
+        let options = read(PROFILES.as_bytes(), "partner-x")?.record_options();
+
+        // TEST
+        assert_eq!(
+            parse_timestamp("2021-06-01T08:30:00Z", None),
+            parse_timestamp("2021-06-01 09:30:00", options.utc_offset)
+        );
+        assert!(parse_timestamp("2021-06-01 09:30:00", None).is_err());
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn delimiters_are_named_or_given_as_a_character() {
+        // TEST
+        assert_eq!(Ok(b','), parse_delimiter("comma"));
+        assert_eq!(Ok(b'\t'), parse_delimiter("tab"));
+        assert_eq!(Ok(b'\t'), parse_delimiter("\\t"));
+        assert_eq!(Ok(b'|'), parse_delimiter("pipe"));
+        assert_eq!(Ok(b'#'), parse_delimiter("#"));
+        assert_eq!(Err(String::from("unsupported delimiter: §")), parse_delimiter("§"));
+    }
+
+    #[test]
+    fn profile_is_loaded_from_a_file() -> Result<(), ProfileError> {
+        // SETUP
+        let path = std::env::temp_dir().join(format!("rust-payment-processor-profile-{}", process::id()));
+        std::fs::write(&path, PROFILES).map_err(ProfileError::Io)?;
+
+        // TEST
+        assert_eq!(read(PROFILES.as_bytes(), "partner-y")?, load(&path, "partner-y")?);
+        assert!(matches!(
+            load(path.with_extension("missing"), "partner-y"),
+            Err(ProfileError::Io(_))
+        ));
+
+        // TEARDOWN
+        std::fs::remove_file(&path).map_err(ProfileError::Io)?;
+        Ok(())
+    }
+}
+//endregion
@@ -0,0 +1,264 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Protobuf input and output
+//! Transactions can be read from, and accounts written to, streams of length-delimited protobuf
+//! messages as described by `proto/payments.proto`. This is a compact, high-throughput
+//! interchange format for talking to other services.
+//!
+//! The message types in `messages` mirror what `prost-build` would generate from the schema. They
+//! are maintained by hand so that building this crate does not require `protoc`; any change to
+//! one must be reflected in the other.
+use crate::account::Account;
+use crate::amount::{parse_amount, AmountFormat};
+use crate::bank::Bank;
+use crate::transaction::{Transaction, TransactionType};
+use chrono::{DateTime, Utc};
+use prost::Message;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+/// Messages of the `payments` protobuf package.
+pub mod messages {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+    #[repr(i32)]
+    pub enum TransactionType {
+        Deposit = 0,
+        Withdrawal = 1,
+        Dispute = 2,
+        Resolve = 3,
+        Chargeback = 4,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Transaction {
+        #[prost(enumeration = "TransactionType", tag = "1")]
+        pub r#type: i32,
+        #[prost(uint32, tag = "2")]
+        pub client: u32,
+        #[prost(uint64, tag = "3")]
+        pub tx: u64,
+        #[prost(string, optional, tag = "4")]
+        pub amount: Option<String>,
+        #[prost(int64, optional, tag = "5")]
+        pub timestamp_micros: Option<i64>,
+        #[prost(string, optional, tag = "6")]
+        pub currency: Option<String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Account {
+        #[prost(uint32, tag = "1")]
+        pub client: u32,
+        #[prost(string, tag = "2")]
+        pub available: String,
+        #[prost(string, tag = "3")]
+        pub held: String,
+        #[prost(string, tag = "4")]
+        pub total: String,
+        #[prost(bool, tag = "5")]
+        pub locked: bool,
+    }
+}
+
+/// `ProtobufError` is returned when a message can not be read from a stream.
+#[derive(Debug)]
+pub enum ProtobufError {
+    /// The underlying stream could not be read, or ended part way through a message.
+    Io(io::Error),
+    /// The length prefix of a message was not a valid varint.
+    InvalidLength,
+    /// A message could not be decoded.
+    Decode(prost::DecodeError),
+}
+
+impl fmt::Display for ProtobufError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtobufError::Io(e) => write!(f, "failed to read protobuf stream: {}", e),
+            ProtobufError::InvalidLength => write!(f, "invalid message length prefix"),
+            ProtobufError::Decode(e) => write!(f, "failed to decode protobuf message: {}", e),
+        }
+    }
+}
+
+impl From<io::Error> for ProtobufError {
+    fn from(e: io::Error) -> ProtobufError {
+        ProtobufError::Io(e)
+    }
+}
+
+impl From<&Account> for messages::Account {
+    fn from(account: &Account) -> messages::Account {
+        messages::Account {
+            client: account.client,
+            available: account.available.to_string(),
+            held: account.held.to_string(),
+            total: account.total.to_string(),
+            locked: account.locked,
+        }
+    }
+}
+
+impl TryFrom<messages::Transaction> for Transaction {
+    type Error = String;
+
+    fn try_from(message: messages::Transaction) -> Result<Transaction, String> {
+        let kind = match messages::TransactionType::try_from(message.r#type) {
+            Ok(messages::TransactionType::Deposit) => TransactionType::Deposit,
+            Ok(messages::TransactionType::Withdrawal) => TransactionType::Withdrawal,
+            Ok(messages::TransactionType::Dispute) => TransactionType::Dispute,
+            Ok(messages::TransactionType::Resolve) => TransactionType::Resolve,
+            Ok(messages::TransactionType::Chargeback) => TransactionType::Chargeback,
+            Err(_) => return Err(format!("unknown transaction type: {}", message.r#type)),
+        };
+        let amount = match message.amount {
+            Some(amount) => Some(parse_amount(&amount, &AmountFormat::new()).map_err(|e| format!("invalid amount \"{}\": {}", amount, e))?),
+            None => None,
+        };
+        let transaction = Transaction {
+            kind,
+            client: message.client,
+            tx: message.tx,
+            amount,
+            under_dispute: false,
+            timestamp: message.timestamp_micros.and_then(DateTime::<Utc>::from_timestamp_micros),
+            currency: message.currency,
+            signature: None,
+            correction: false,
+            provenance: None,
+            expires: None,
+        };
+        transaction.check_fields().map_err(|e| e.to_string())?;
+        Ok(transaction)
+    }
+}
+
+/// `DelimitedReader` iterates over the length-delimited messages of a stream.
+pub struct DelimitedReader<R: Read> {
+    reader: R,
+    buf: Vec<u8>,
+}
+
+impl<R: Read> DelimitedReader<R> {
+    pub fn new(reader: R) -> DelimitedReader<R> {
+        DelimitedReader { reader, buf: Vec::new() }
+    }
+
+    /// Read the varint length prefix of the next message, or `None` at a clean end of stream.
+    fn read_length(&mut self) -> Result<Option<usize>, ProtobufError> {
+        let mut length: u64 = 0;
+        for index in 0..10 {
+            let mut byte = [0u8; 1];
+            if self.reader.read(&mut byte)? == 0 {
+                if index == 0 {
+                    return Ok(None);
+                }
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+            }
+            length |= u64::from(byte[0] & 0x7f) << (7 * index);
+            if byte[0] & 0x80 == 0 {
+                return Ok(Some(length as usize));
+            }
+        }
+        Err(ProtobufError::InvalidLength)
+    }
+}
+
+impl<R: Read> Iterator for DelimitedReader<R> {
+    type Item = Result<messages::Transaction, ProtobufError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let length = match self.read_length() {
+            Ok(Some(length)) => length,
+            Ok(None) => return None,
+            Err(e) => return Some(Err(e)),
+        };
+        self.buf.resize(length, 0);
+        if let Err(e) = self.reader.read_exact(&mut self.buf) {
+            return Some(Err(e.into()));
+        }
+        Some(messages::Transaction::decode(self.buf.as_slice()).map_err(ProtobufError::Decode))
+    }
+}
+
+/// Read every transaction in a length-delimited protobuf stream. Messages that decode but can not
+/// be mapped onto a `Transaction` are logged and discarded; a stream that can not be decoded at all
+/// stops reading with an error.
+pub fn read_transactions<R: Read>(reader: R) -> Result<Vec<Transaction>, ProtobufError> {
+    let mut transactions = Vec::new();
+    for (index, message) in DelimitedReader::new(reader).enumerate() {
+        match Transaction::try_from(message?) {
+            Ok(transaction) => transactions.push(transaction),
+            Err(e) => error!("Failed to map protobuf message {}. Discarded with error: {}", index + 1, e),
+        }
+    }
+    Ok(transactions)
+}
+
+/// Write every account of the bank to the given writer as a length-delimited protobuf stream.
+pub fn write_accounts<W: Write>(bank: &Bank, mut writer: W) -> io::Result<()> {
+    let mut buf = Vec::new();
+    for account in bank.accounts() {
+        buf.clear();
+        messages::Account::from(account)
+            .encode_length_delimited(&mut buf)
+            .map_err(io::Error::other)?;
+        writer.write_all(&buf)?;
+    }
+    writer.flush()
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delimited_stream_round_trips_into_transactions() -> Result<(), ProtobufError> {
+        // SETUP
+        let mut stream = Vec::new();
+        let deposit = messages::Transaction {
+            r#type: messages::TransactionType::Deposit as i32,
+            client: 1,
+            tx: 1,
+            amount: Some(String::from("1.5")),
+            ..Default::default()
+        };
+        let bad_type = messages::Transaction {
+            r#type: 42,
+            ..deposit.clone()
+        };
+        let dispute = messages::Transaction {
+            r#type: messages::TransactionType::Dispute as i32,
+            amount: None,
+            ..deposit.clone()
+        };
+        for message in &[deposit, bad_type, dispute] {
+            message.encode_length_delimited(&mut stream).unwrap();
+        }
+
+        // TEST
+        let transactions = read_transactions(stream.as_slice())?;
+        assert_eq!(2, transactions.len());
+        assert_eq!(TransactionType::Deposit, transactions[0].kind);
+        assert_eq!(
+            crate::amount::from_decimal(rust_decimal::Decimal::new(15, 1)),
+            transactions[0].amount
+        );
+        assert_eq!(TransactionType::Dispute, transactions[1].kind);
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn truncated_stream_returns_error() {
+        let mut stream = Vec::new();
+        messages::Account::from(&Account::new(1))
+            .encode_length_delimited(&mut stream)
+            .unwrap();
+        stream.truncate(stream.len() - 1);
+        assert!(read_transactions(stream.as_slice()).is_err());
+    }
+}
+//endregion
@@ -0,0 +1,102 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Record provenance
+//! A `Provenance` is where a transaction was read from: a line of an input file, or an offset in a
+//! topic of a message broker. It travels with the transaction into the bank's store, the audit
+//! trail, and the dispute report, so that an investigator can go straight to the original record
+//! instead of searching inputs many gigabytes long. It is written as `<file>:<line>`, e.g.
+//! `transactions.csv:42`, or `<topic>@<offset>`.
+//!
+//! CSV input read with `schema::read_transactions_from`, `pipeline::read_transactions_from` or
+//! `batch::read_records_from` records the line of each transaction; so does every run of the
+//! binary, except with `--string-client-ids`.
+//! Embedders reading from a broker set `Transaction::provenance` themselves.
+use serde::{Serialize, Serializer};
+use std::fmt;
+use std::sync::Arc;
+
+/// `Provenance` is the source of a transaction record. The name of the file or topic is shared
+/// between the records read from it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Provenance {
+    /// A line of an input file, counting from 1.
+    Line { file: Arc<str>, line: u64 },
+    /// A message at an offset of a topic.
+    Offset { topic: Arc<str>, offset: u64 },
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Provenance::Line { file, line } => write!(f, "{}:{}", file, line),
+            Provenance::Offset { topic, offset } => write!(f, "{}@{}", topic, offset),
+        }
+    }
+}
+
+impl Serialize for Provenance {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_is_written_as_file_and_line() {
+        // SETUP
+        let provenance = Provenance::Line {
+            file: Arc::from("transactions.csv"),
+            line: 42,
+        };
+
+        // TEST
+        assert_eq!("transactions.csv:42", provenance.to_string());
+    }
+
+    #[test]
+    fn offset_is_written_as_topic_and_offset() {
+        // SETUP
+        let provenance = Provenance::Offset {
+            topic: Arc::from("payments"),
+            offset: 7,
+        };
+
+        // TEST
+        assert_eq!("payments@7", provenance.to_string());
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn provenance_is_serialized_as_a_string() {
+        // SETUP
+        let provenance = Provenance::Line {
+            file: Arc::from("transactions.csv"),
+            line: 42,
+        };
+
+        // TEST
+        assert_eq!("\"transactions.csv:42\"", serde_json::to_string(&provenance).unwrap());
+    }
+
+    #[test]
+    fn records_read_from_one_file_share_its_name() {
+        // SETUP
+        let file: Arc<str> = Arc::from("transactions.csv");
+        let first = Provenance::Line {
+            file: file.clone(),
+            line: 1,
+        };
+        let second = Provenance::Line {
+            file: file.clone(),
+            line: 2,
+        };
+
+        // TEST
+        assert_eq!(3, Arc::strong_count(&file));
+        assert_ne!(first, second);
+    }
+}
+//endregion
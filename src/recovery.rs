@@ -0,0 +1,253 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Recovering from malformed CSV
+//! A single malformed row, such as one with an unescaped quote, can desynchronize the CSV reader:
+//! everything up to the next quote is read as one field, swallowing the records in between. A
+//! `RecoveringReader` sits between the input and the CSV reader and passes through only the lines
+//! that are well-formed records: balanced quotes and the same number of fields as the first line.
+//! Any other line is dropped, and its byte range logged and kept in `RecoveringReader::skipped`, so
+//! that reading resumes at the next well-formed record. It is enabled with `--recover`.
+//!
+//! Records are expected to fit on one line, as transactions do; a quoted field spanning lines is
+//! treated as malformed too. Byte ranges are positions in the input as decoded to UTF-8.
+use std::io::{self, BufRead};
+
+/// `SkippedRange` is a line of input dropped as malformed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SkippedRange {
+    /// The line number, starting at 1.
+    pub line: u64,
+    /// The offset of the first byte of the line.
+    pub start: u64,
+    /// The offset of the byte after the line, including its line terminator.
+    pub end: u64,
+}
+
+/// `RecoveringReader` passes through the well-formed lines of a CSV input, dropping the others.
+pub struct RecoveringReader<R> {
+    input: R,
+    delimiter: u8,
+    /// The number of fields of the first line, which every other line must have.
+    fields: Option<usize>,
+    line: Vec<u8>,
+    /// The part of `line` not yet read.
+    consumed: usize,
+    line_number: u64,
+    position: u64,
+    skipped: Vec<SkippedRange>,
+}
+
+impl<R: BufRead> RecoveringReader<R> {
+    pub fn new(input: R, delimiter: u8) -> RecoveringReader<R> {
+        RecoveringReader {
+            input,
+            delimiter,
+            fields: None,
+            line: Vec::new(),
+            consumed: 0,
+            line_number: 0,
+            position: 0,
+            skipped: Vec::new(),
+        }
+    }
+
+    /// Returns the lines dropped so far, in input order.
+    pub fn skipped(&self) -> &[SkippedRange] {
+        &self.skipped
+    }
+
+    /// Read lines until a well-formed one is found, or the input ends.
+    fn next_line(&mut self) -> io::Result<()> {
+        loop {
+            self.line.clear();
+            self.consumed = 0;
+            let read = self.input.read_until(b'\n', &mut self.line)?;
+            if read == 0 {
+                return Ok(());
+            }
+            self.line_number += 1;
+            let start = self.position;
+            self.position += read as u64;
+            if self.is_well_formed() {
+                return Ok(());
+            }
+            let skipped = SkippedRange {
+                line: self.line_number,
+                start,
+                end: self.position,
+            };
+            error!(
+                "Skipped malformed line {} (bytes {}..{}) to resynchronize",
+                skipped.line, skipped.start, skipped.end
+            );
+            self.skipped.push(skipped);
+        }
+    }
+
+    /// Whether the current line is a single record with balanced quotes and the expected number of
+    /// fields. The first non-empty line sets the number expected.
+    fn is_well_formed(&mut self) -> bool {
+        if self.line.iter().all(u8::is_ascii_whitespace) {
+            return true;
+        }
+        if self.line.iter().filter(|&&byte| byte == b'"').count() % 2 != 0 {
+            return false;
+        }
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .delimiter(self.delimiter)
+            .from_reader(self.line.as_slice());
+        let mut records = reader.records();
+        let fields = match (records.next(), records.next()) {
+            (Some(Ok(record)), None) => record.len(),
+            _ => return false,
+        };
+        *self.fields.get_or_insert(fields) == fields
+    }
+}
+
+impl<R: BufRead> io::Read for RecoveringReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.consumed == self.line.len() {
+            self.next_line()?;
+        }
+        let pending = &self.line[self.consumed..];
+        let count = pending.len().min(buf.len());
+        buf[..count].copy_from_slice(&pending[..count]);
+        self.consumed += count;
+        Ok(count)
+    }
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema;
+    use crate::transaction::{RecordOptions, TxId};
+    use std::io::Read;
+
+    /// The IDs of the transactions read through `recovering`.
+    fn txs<R: BufRead>(recovering: &mut RecoveringReader<R>) -> Vec<TxId> {
+        let mut reader = csv::Reader::from_reader(recovering);
+        schema::read_transactions(&mut reader, &RecordOptions::default())
+            .map(|transaction| transaction.tx)
+            .collect()
+    }
+
+    #[test]
+    fn well_formed_input_is_passed_through() -> io::Result<()> {
+        // SETUP
+        let input = "type,client,tx,amount\ndeposit,1,1,\"5\"\n\ndeposit,1,2,3";
+        let mut output = String::new();
+
+        // TEST
+        RecoveringReader::new(input.as_bytes(), b',').read_to_string(&mut output)?;
+        assert_eq!(input, output);
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn line_with_an_unbalanced_quote_is_skipped() {
+        // SETUP
+        let input = "type,client,tx,amount\ndeposit,1,1,\"5\ndeposit,1,2,3\n";
+        let mut recovering = RecoveringReader::new(input.as_bytes(), b',');
+
+        // TEST
+        assert_eq!(vec![2], txs(&mut recovering));
+        assert_eq!(
+            &[SkippedRange {
+                line: 2,
+                start: 22,
+                end: 37
+            }],
+            recovering.skipped()
+        );
+    }
+
+    #[test]
+    fn line_with_a_different_number_of_fields_is_skipped() {
+        // SETUP
+        let input = "type,client,tx,amount\ndeposit,1,1,1,2\ndeposit,1,2\ndeposit,1,3,1\n";
+        let mut recovering = RecoveringReader::new(input.as_bytes(), b',');
+
+        // TEST
+        assert_eq!(vec![3], txs(&mut recovering));
+        assert_eq!(
+            vec![2, 3],
+            recovering.skipped().iter().map(|skipped| skipped.line).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn fields_are_counted_by_the_delimiter() {
+        // SETUP
+        let input = "type;client;tx;amount\ndeposit;1;1;1,5\ndeposit,1,2,1\n";
+        let mut recovering = RecoveringReader::new(input.as_bytes(), b';');
+
+        // TEST
+        let mut output = String::new();
+        recovering.read_to_string(&mut output).unwrap();
+        assert_eq!("type;client;tx;amount\ndeposit;1;1;1,5\n", output);
+        assert_eq!(3, recovering.skipped()[0].line);
+    }
+
+    #[test]
+    fn quoted_field_spanning_lines_is_skipped() {
+        // SETUP
+        let input = "type,client,tx,amount\ndeposit,1,1,\"5\n\",\ndeposit,1,2,3\n";
+        let mut recovering = RecoveringReader::new(input.as_bytes(), b',');
+
+        // TEST
+        assert_eq!(vec![2], txs(&mut recovering));
+        assert_eq!(2, recovering.skipped().len());
+    }
+
+    #[test]
+    fn skipped_ranges_are_byte_offsets_in_input_order() {
+        // SETUP
+        let input = "type,client,tx,amount\ndeposit,1,1,\"5\ndeposit,1,2,3\ndeposit,1,3,1,2\ndeposit,1,4,1\n";
+        let mut recovering = RecoveringReader::new(input.as_bytes(), b',');
+
+        // TEST
+        assert_eq!(vec![2, 4], txs(&mut recovering));
+        let expected = [
+            SkippedRange {
+                line: 2,
+                start: 22,
+                end: 37,
+            },
+            SkippedRange {
+                line: 4,
+                start: 51,
+                end: 67,
+            },
+        ];
+        assert_eq!(&expected, recovering.skipped());
+    }
+
+    #[test]
+    fn small_reads_resume_within_a_line() -> io::Result<()> {
+        // SETUP
+        let mut recovering = RecoveringReader::new("type,client\ndeposit,1\n".as_bytes(), b',');
+        let mut buf = [0; 4];
+        let mut output = Vec::new();
+
+        // TEST
+        loop {
+            let read = recovering.read(&mut buf)?;
+            if read == 0 {
+                break;
+            }
+            assert!(read <= 4);
+            output.extend_from_slice(&buf[..read]);
+        }
+        assert_eq!(b"type,client\ndeposit,1\n", output.as_slice());
+
+        // TEARDOWN
+        Ok(())
+    }
+}
+//endregion
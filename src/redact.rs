@@ -0,0 +1,173 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Log redaction
+//! Client IDs and amounts are personal data, and are kept out of log messages unless sensitive
+//! logging has been enabled with `set_log_sensitive` (`--log-sensitive` on the command line). Wrap
+//! them in `redact` when logging:
+//!
+//! ```
+//! # use log::debug;
+//! # use rust_payment_processor::redact::{redact, REDACTED};
+//! # use rust_payment_processor::transaction::{RecordOptions, TransactionRecord, TransactionType};
+//! # let transaction = RecordOptions::default().transaction(TransactionRecord::new(TransactionType::Deposit, 7, 1, Some("5"))).unwrap();
+//! debug!("Processing Transaction: {:?}", redact(&transaction));
+//! # assert!(format!("{:?}", redact(&transaction)).contains(REDACTED));
+//! ```
+use crate::account::{Account, ClientId};
+use crate::transaction::Transaction;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Written in place of a value that has been redacted.
+pub const REDACTED: &str = "<redacted>";
+
+static LOG_SENSITIVE: AtomicBool = AtomicBool::new(false);
+
+/// Enable or disable logging of client IDs and amounts.
+pub fn set_log_sensitive(enabled: bool) {
+    LOG_SENSITIVE.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether client IDs and amounts are logged.
+pub fn log_sensitive() -> bool {
+    LOG_SENSITIVE.load(Ordering::Relaxed)
+}
+
+/// `Redacted` formats a value for logging, leaving out its sensitive parts unless sensitive logging
+/// is enabled.
+pub struct Redacted<'a, T: ?Sized>(pub(crate) &'a T);
+
+pub fn redact<T: ?Sized>(value: &T) -> Redacted<'_, T> {
+    Redacted(value)
+}
+
+/// Formats a client ID.
+impl fmt::Display for Redacted<'_, ClientId> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if log_sensitive() {
+            write!(f, "{}", self.0)
+        } else {
+            f.write_str(REDACTED)
+        }
+    }
+}
+
+impl fmt::Debug for Redacted<'_, Account> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if log_sensitive() {
+            return fmt::Debug::fmt(self.0, f);
+        }
+        let redacted = format_args!("{}", REDACTED);
+        f.debug_struct("Account")
+            .field("client", &redacted)
+            .field("available", &redacted)
+            .field("held", &redacted)
+            .field("total", &redacted)
+            .field("locked", &self.0.locked)
+            .field("version", &self.0.version)
+            .finish()
+    }
+}
+
+impl fmt::Debug for Redacted<'_, Transaction> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if log_sensitive() {
+            return fmt::Debug::fmt(self.0, f);
+        }
+        let redacted = format_args!("{}", REDACTED);
+        f.debug_struct("Transaction")
+            .field("kind", &self.0.kind)
+            .field("client", &redacted)
+            .field("tx", &self.0.tx)
+            .field("amount", &redacted)
+            .field("under_dispute", &self.0.under_dispute)
+            .field("timestamp", &self.0.timestamp)
+            .field("currency", &self.0.currency)
+            .finish()
+    }
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::TransactionType;
+    use std::sync::Mutex;
+
+    /// Held by every test while it depends on `LOG_SENSITIVE`, which tests running in parallel share.
+    static SENSITIVE: Mutex<()> = Mutex::new(());
+
+    /// Format with sensitive logging `enabled`, then disable it again.
+    fn logged<F: FnOnce() -> String>(enabled: bool, format: F) -> String {
+        let _guard = SENSITIVE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        set_log_sensitive(enabled);
+        let logged = format();
+        set_log_sensitive(false);
+        logged
+    }
+
+    fn transaction() -> Transaction {
+        Transaction::make(TransactionType::Deposit, 4321, 17, 98765, false)
+    }
+
+    #[test]
+    fn sensitive_logging_is_disabled_by_default() {
+        // TEST
+        let _guard = SENSITIVE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        assert!(!log_sensitive());
+    }
+
+    #[test]
+    fn client_ids_are_redacted() {
+        // TEST
+        assert_eq!(REDACTED, logged(false, || redact(&4321u32).to_string()));
+    }
+
+    #[test]
+    fn client_ids_are_logged_when_enabled() {
+        // TEST
+        assert_eq!("4321", logged(true, || redact(&4321u32).to_string()));
+    }
+
+    #[test]
+    fn transaction_client_and_amount_are_redacted() {
+        // TEST
+        let logged = logged(false, || format!("{:?}", redact(&transaction())));
+        assert!(!logged.contains("4321") && !logged.contains("98765"), "{}", logged);
+        assert!(logged.contains("client: <redacted>") && logged.contains("tx: 17"), "{}", logged);
+    }
+
+    #[test]
+    fn transactions_are_logged_in_full_when_enabled() {
+        // TEST
+        assert_eq!(
+            format!("{:?}", transaction()),
+            logged(true, || format!("{:?}", redact(&transaction())))
+        );
+    }
+
+    #[test]
+    fn account_balances_are_redacted() {
+        // SETUP
+        let mut account = Account::new(4321);
+        account.locked = true;
+
+        // TEST
+        let logged = logged(false, || format!("{:?}", redact(&account)));
+        assert!(!logged.contains("4321"), "{}", logged);
+        assert!(
+            logged.contains("total: <redacted>") && logged.contains("locked: true"),
+            "{}",
+            logged
+        );
+    }
+
+    #[test]
+    fn accounts_are_logged_in_full_when_enabled() {
+        // SETUP
+        let account = Account::new(4321);
+
+        // TEST
+        assert_eq!(format!("{:?}", account), logged(true, || format!("{:?}", redact(&account))));
+    }
+}
+//endregion
@@ -0,0 +1,284 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Risk scores
+//! A first-pass fraud score for each account, computed where the data lives from what the engine
+//! already tracks. `RiskFeatures` gathers an account's balances and `ClientStatistics`, and a
+//! `RiskScorer` turns them into a score from 0 (no concern) to 100. `WeightedRiskScorer` is the
+//! default; a fraud team with its own model implements `RiskScorer` instead.
+//!
+//! The statistics are only counted by a bank built with `BankBuilder::statistics`; without them,
+//! scores only reflect balances and locks.
+use crate::account::{Account, ClientId, ClientStatistics};
+use crate::amount::Amount;
+use crate::bank::Bank;
+use rust_decimal::prelude::*;
+use serde::Serialize;
+use std::io;
+
+/// `RiskFeatures` are the facts about an account a `RiskScorer` scores.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RiskFeatures {
+    /// Disputes opened per deposit.
+    pub dispute_rate: f64,
+    pub chargebacks: u64,
+    /// Withdrawals per deposit. Input carries no reliable timestamps, so this stands in for the
+    /// rate at which funds leave the account.
+    pub withdrawal_velocity: f64,
+    /// Whether the available balance is below zero, as a chargeback after a withdrawal leaves it.
+    pub negative_balance: bool,
+    pub locked: bool,
+}
+
+impl RiskFeatures {
+    pub fn new(account: &Account, statistics: &ClientStatistics) -> RiskFeatures {
+        let deposits = statistics.deposits.max(1) as f64;
+        RiskFeatures {
+            dispute_rate: statistics.disputes as f64 / deposits,
+            chargebacks: statistics.chargebacks,
+            withdrawal_velocity: statistics.withdrawals as f64 / deposits,
+            negative_balance: account.available < Amount::zero(),
+            locked: account.locked,
+        }
+    }
+}
+
+/// `RiskScorer` scores the features of an account from 0 (no concern) to 100.
+pub trait RiskScorer {
+    fn score(&self, features: &RiskFeatures) -> u8;
+}
+
+impl<F: Fn(&RiskFeatures) -> u8> RiskScorer for F {
+    fn score(&self, features: &RiskFeatures) -> u8 {
+        self(features)
+    }
+}
+
+/// `WeightedRiskScorer` adds up the weight of each feature, scaled by how far the feature is
+/// towards its limit, at which it contributes its full weight.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WeightedRiskScorer {
+    pub dispute_rate: (f64, f64),
+    pub chargebacks: (f64, f64),
+    pub withdrawal_velocity: (f64, f64),
+    pub negative_balance: f64,
+    pub locked: f64,
+}
+
+impl Default for WeightedRiskScorer {
+    /// Weights summing to 100, as (weight, limit): a client disputing half its deposits, with two
+    /// chargebacks, or withdrawing four times per deposit scores that feature's full weight.
+    fn default() -> WeightedRiskScorer {
+        WeightedRiskScorer {
+            dispute_rate: (35.0, 0.5),
+            chargebacks: (25.0, 2.0),
+            withdrawal_velocity: (15.0, 4.0),
+            negative_balance: 15.0,
+            locked: 10.0,
+        }
+    }
+}
+
+impl RiskScorer for WeightedRiskScorer {
+    fn score(&self, features: &RiskFeatures) -> u8 {
+        let scaled = |(weight, limit): (f64, f64), value: f64| weight * (value / limit).min(1.0);
+        let score = scaled(self.dispute_rate, features.dispute_rate)
+            + scaled(self.chargebacks, features.chargebacks as f64)
+            + scaled(self.withdrawal_velocity, features.withdrawal_velocity)
+            + if features.negative_balance { self.negative_balance } else { 0.0 }
+            + if features.locked { self.locked } else { 0.0 };
+        score.round().clamp(0.0, 100.0) as u8
+    }
+}
+
+/// `ScoredAccount` is the layout of an account written with its risk score.
+#[derive(Serialize)]
+struct ScoredAccount {
+    client: ClientId,
+    available: Amount,
+    held: Amount,
+    total: Amount,
+    locked: bool,
+    risk_score: u8,
+}
+
+/// Write accounts in CSV format to the given writer, with each account's risk score as an extra
+/// column.
+pub fn write_accounts<W: io::Write>(bank: &Bank, scorer: &dyn RiskScorer, writer: W) -> Result<(), csv::Error> {
+    let mut wtr = csv::WriterBuilder::new().from_writer(writer);
+    for account in bank.accounts() {
+        let statistics = bank.statistics(account.client).copied().unwrap_or_default();
+        wtr.serialize(ScoredAccount {
+            client: account.client,
+            available: account.available,
+            held: account.held,
+            total: account.total,
+            locked: account.locked,
+            risk_score: scorer.score(&RiskFeatures::new(account, &statistics)),
+        })?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testkit::Scenario;
+
+    /// A bank counting statistics, after processing `scenario`.
+    fn run(scenario: Scenario) -> Bank {
+        let mut bank = Bank::builder().statistics(true).build();
+        bank.process_all(scenario.transactions().to_vec());
+        bank
+    }
+
+    fn features(bank: &Bank, client: ClientId) -> RiskFeatures {
+        RiskFeatures::new(bank.account(client).unwrap(), bank.statistics(client).unwrap())
+    }
+
+    fn none() -> RiskFeatures {
+        RiskFeatures {
+            dispute_rate: 0.0,
+            chargebacks: 0,
+            withdrawal_velocity: 0.0,
+            negative_balance: false,
+            locked: false,
+        }
+    }
+
+    #[test]
+    fn features_are_taken_from_balances_and_statistics() {
+        // SETUP
+        let bank = run(Scenario::new()
+            .deposit(1, 5)
+            .deposit(1, 5)
+            .withdraw(1, 8)
+            .dispute(1, 2)
+            .chargeback(1, 2));
+
+        // TEST
+        assert_eq!(
+            RiskFeatures {
+                dispute_rate: 0.5,
+                chargebacks: 1,
+                withdrawal_velocity: 0.5,
+                negative_balance: true,
+                locked: true,
+            },
+            features(&bank, 1)
+        );
+    }
+
+    #[test]
+    fn rates_of_an_account_without_deposits_are_per_single_deposit() {
+        // SETUP
+        let account = Account::new(1);
+        let statistics = ClientStatistics {
+            withdrawals: 3,
+            ..ClientStatistics::default()
+        };
+
+        // TEST
+        assert_eq!(3.0, RiskFeatures::new(&account, &statistics).withdrawal_velocity);
+    }
+
+    #[test]
+    fn account_without_concerns_scores_zero() {
+        // TEST
+        assert_eq!(0, WeightedRiskScorer::default().score(&none()));
+    }
+
+    #[test]
+    fn features_score_in_proportion_to_their_limit() {
+        // SETUP
+        let scorer = WeightedRiskScorer::default();
+
+        // TEST
+        assert_eq!(
+            18,
+            scorer.score(&RiskFeatures {
+                dispute_rate: 0.25,
+                ..none()
+            })
+        );
+        assert_eq!(13, scorer.score(&RiskFeatures { chargebacks: 1, ..none() }));
+        assert_eq!(
+            4,
+            scorer.score(&RiskFeatures {
+                withdrawal_velocity: 1.0,
+                ..none()
+            })
+        );
+        assert_eq!(
+            15,
+            scorer.score(&RiskFeatures {
+                negative_balance: true,
+                ..none()
+            })
+        );
+        assert_eq!(10, scorer.score(&RiskFeatures { locked: true, ..none() }));
+    }
+
+    #[test]
+    fn features_beyond_their_limit_score_their_full_weight() {
+        // TEST
+        let features = RiskFeatures {
+            dispute_rate: 3.0,
+            chargebacks: 9,
+            withdrawal_velocity: 40.0,
+            negative_balance: true,
+            locked: true,
+        };
+        assert_eq!(100, WeightedRiskScorer::default().score(&features));
+    }
+
+    #[test]
+    fn disputed_overdrawn_and_locked_accounts_score_high() {
+        // SETUP
+        let bank = run(Scenario::new()
+            .deposit(1, 5)
+            .deposit(2, 5)
+            .withdraw(2, 4)
+            .dispute(2, 2)
+            .chargeback(2, 2));
+        let scorer = WeightedRiskScorer::default();
+
+        // TEST
+        assert_eq!(0, scorer.score(&features(&bank, 1)));
+        // every deposit disputed (35), one chargeback (12.5), a withdrawal per deposit (3.75),
+        // overdrawn (15), and locked (10)
+        assert_eq!(76, scorer.score(&features(&bank, 2)));
+    }
+
+    #[test]
+    fn accounts_are_written_with_the_score_of_any_scorer() -> Result<(), csv::Error> {
+        // SETUP
+        let bank = run(Scenario::new().deposit(1, 5));
+        let mut output = Vec::new();
+
+        // TEST
+        write_accounts(&bank, &|_: &RiskFeatures| 42, &mut output)?;
+        assert_eq!(
+            "client,available,held,total,locked,risk_score\n1,5,0,5,false,42\n",
+            String::from_utf8(output).unwrap()
+        );
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn accounts_without_statistics_are_scored_on_balances() -> Result<(), csv::Error> {
+        // SETUP
+        let bank = Scenario::new().deposit(1, 5).withdraw(1, 5).dispute(1, 1).run();
+        let mut output = Vec::new();
+
+        // TEST
+        write_accounts(&bank, &WeightedRiskScorer::default(), &mut output)?;
+        assert!(String::from_utf8(output).unwrap().ends_with(",15\n"));
+
+        // TEARDOWN
+        Ok(())
+    }
+}
+//endregion
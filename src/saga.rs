@@ -0,0 +1,267 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Sagas
+//! A `Saga` applies the legs of a multi-leg operation, such as the two sides of a transfer and its
+//! fee, one after another. If a leg is rejected, the legs already applied are undone by applying
+//! their compensating transactions in reverse order, so that callers do not have to write the
+//! rollback themselves:
+//!
+//! ```
+//! # use rust_payment_processor::account::ClientId;
+//! # use rust_payment_processor::amount::Amount;
+//! # use rust_payment_processor::bank::Bank;
+//! # use rust_payment_processor::saga::{Saga, SagaError};
+//! # use rust_payment_processor::transaction::{RecordOptions, Transaction, TransactionRecord, TransactionType, TxId};
+//! # fn record(kind: TransactionType, client: ClientId, tx: TxId, amount: &str) -> Transaction {
+//! #     RecordOptions::default().transaction(TransactionRecord::new(kind, client, tx, Some(amount))).unwrap()
+//! # }
+//! # fn deposit(client: ClientId, tx: TxId, amount: u32) -> Transaction {
+//! #     record(TransactionType::Deposit, client, tx, &amount.to_string())
+//! # }
+//! # fn withdrawal(client: ClientId, tx: TxId, amount: u32) -> Transaction {
+//! #     record(TransactionType::Withdrawal, client, tx, &amount.to_string())
+//! # }
+//! # fn main() -> Result<(), SagaError> {
+//! # let mut bank = Bank::new();
+//! # bank.process_all(vec![deposit(1, 1, 20)]);
+//! // transfer 10 from client 1 to client 2, charging client 1 a fee of 1 into client 99
+//! Saga::new()
+//!     .leg(withdrawal(1, 100, 10), 200)
+//!     .leg(deposit(2, 101, 10), 201)
+//!     .leg(withdrawal(1, 102, 1), 202)
+//!     .leg(deposit(99, 103, 1), 203)
+//!     .run(&mut bank)?;
+//! # assert_eq!(Amount::from(9u32), bank.account(1).unwrap().total);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Compensating transactions are ordinary transactions, applied and stored like any other, so a
+//! compensated saga leaves both its legs and their reversals in the ledger.
+use crate::bank::Bank;
+use crate::errors::BankingError;
+use crate::transaction::{Transaction, TransactionType, TxId};
+
+/// `Leg` is a transaction of a saga, with the transaction that undoes it.
+#[derive(Clone, Debug)]
+struct Leg {
+    transaction: Transaction,
+    compensation: Option<Transaction>,
+}
+
+/// `SagaError` describes a saga that was rejected part way through.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SagaError {
+    /// The index of the leg that was rejected, starting at 0.
+    pub leg: usize,
+    /// The error the leg was rejected with.
+    pub error: BankingError,
+    /// The legs whose compensation was rejected too, with the error each was rejected with. When
+    /// this is empty, the saga has been fully undone.
+    pub uncompensated: Vec<(usize, BankingError)>,
+}
+
+/// `Saga` is a sequence of transactions to apply as one.
+#[derive(Clone, Debug, Default)]
+pub struct Saga {
+    legs: Vec<Leg>,
+}
+
+impl Saga {
+    pub fn new() -> Saga {
+        Saga::default()
+    }
+
+    /// Add a deposit or withdrawal, compensated by the reverse movement of the same amount on the
+    /// same account, under the ID `compensation_tx`. A leg of any other type is rejected by `run`
+    /// with `InvalidTransaction`; use `leg_with` for those.
+    pub fn leg(self, transaction: Transaction, compensation_tx: TxId) -> Saga {
+        let kind = match transaction.kind {
+            TransactionType::Deposit => Some(TransactionType::Withdrawal),
+            TransactionType::Withdrawal => Some(TransactionType::Deposit),
+            _ => None,
+        };
+        let compensation = kind.map(|kind| Transaction {
+            kind,
+            tx: compensation_tx,
+            timestamp: None,
+            signature: None,
+            correction: false,
+            ..transaction.clone()
+        });
+        self.push(transaction, compensation)
+    }
+
+    /// Add a transaction with the given compensating transaction, e.g. one of a custom type, or one
+    /// signed for a bank that checks signatures.
+    pub fn leg_with(self, transaction: Transaction, compensation: Transaction) -> Saga {
+        self.push(transaction, Some(compensation))
+    }
+
+    fn push(mut self, transaction: Transaction, compensation: Option<Transaction>) -> Saga {
+        self.legs.push(Leg { transaction, compensation });
+        self
+    }
+
+    /// Apply every leg in order. If one is rejected, the legs before it are compensated in reverse
+    /// order and the rejection is returned.
+    pub fn run(self, bank: &mut Bank) -> Result<(), SagaError> {
+        for (index, leg) in self.legs.iter().enumerate() {
+            let result = match &leg.compensation {
+                Some(_) => bank.process_transaction(leg.transaction.clone()),
+                None => Err(BankingError::InvalidTransaction),
+            };
+            if let Err(error) = result {
+                warn!("Saga leg {} was rejected with {:?}. Compensating {} legs", index, error, index);
+                return Err(SagaError {
+                    leg: index,
+                    error,
+                    uncompensated: Saga::compensate(bank, &self.legs[..index]),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply the compensations of the given legs in reverse order, returning those that failed.
+    fn compensate(bank: &mut Bank, legs: &[Leg]) -> Vec<(usize, BankingError)> {
+        let mut failed = Vec::new();
+        for (index, leg) in legs.iter().enumerate().rev() {
+            let compensation = leg.compensation.clone().expect("applied legs have a compensation");
+            if let Err(e) = bank.process_transaction(compensation) {
+                error!("Failed to compensate saga leg {}. Aborted with error: {:?}", index, e);
+                failed.push((index, e));
+            }
+        }
+        failed
+    }
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::ClientId;
+    use crate::amount::Amount;
+    use crate::testkit::Scenario;
+
+    fn deposit(client: ClientId, tx: TxId, amount: u32) -> Transaction {
+        Transaction::make(TransactionType::Deposit, client, tx, amount, false)
+    }
+
+    fn withdrawal(client: ClientId, tx: TxId, amount: u32) -> Transaction {
+        Transaction::make(TransactionType::Withdrawal, client, tx, amount, false)
+    }
+
+    /// Transfer 8 from client 1 to client 2, charging client 1 a fee.
+    fn transfer(fee: u32) -> Saga {
+        Saga::new()
+            .leg(withdrawal(1, 100, 8), 200)
+            .leg(deposit(2, 110, 8), 210)
+            .leg(withdrawal(1, 120, fee), 220)
+    }
+
+    fn available(bank: &Bank, client: ClientId) -> Amount {
+        bank.account(client).unwrap().available
+    }
+
+    #[test]
+    fn every_leg_is_applied() {
+        // SETUP
+        let mut bank = Scenario::new().deposit(1, 10).run();
+
+        // TEST
+        assert_eq!(Ok(()), transfer(2).run(&mut bank));
+        assert_eq!((Amount::from(0u32), Amount::from(8u32)), (available(&bank, 1), available(&bank, 2)));
+    }
+
+    #[test]
+    fn rejected_leg_compensates_earlier_legs() {
+        // SETUP
+        let mut bank = Scenario::new().deposit(1, 10).run();
+
+        // TEST
+        let expected = SagaError {
+            leg: 2,
+            error: BankingError::InsufficientFunds,
+            uncompensated: Vec::new(),
+        };
+        assert_eq!(Err(expected), transfer(3).run(&mut bank));
+        assert_eq!(
+            (Amount::from(10u32), Amount::from(0u32)),
+            (available(&bank, 1), available(&bank, 2))
+        );
+    }
+
+    #[test]
+    fn compensations_are_kept_in_the_ledger() {
+        // SETUP
+        let mut bank = Scenario::new().deposit(1, 10).run();
+        transfer(3).run(&mut bank).unwrap_err();
+
+        // TEST
+        assert_eq!(
+            Err(BankingError::DuplicateTransactionId),
+            bank.process_transaction(deposit(1, 200, 1))
+        );
+    }
+
+    #[test]
+    fn rejected_first_leg_has_nothing_to_compensate() {
+        // SETUP
+        let mut bank = Scenario::new().deposit(1, 10).run();
+
+        // TEST
+        let error = Saga::new()
+            .leg(withdrawal(1, 100, 11), 200)
+            .leg(deposit(2, 110, 11), 210)
+            .run(&mut bank)
+            .unwrap_err();
+        assert_eq!((0, Vec::new()), (error.leg, error.uncompensated));
+        assert!(bank.account(2).is_none());
+    }
+
+    #[test]
+    fn leg_of_another_type_is_rejected() {
+        // SETUP
+        let mut bank = Scenario::new().deposit(1, 10).run();
+
+        // TEST
+        let error = Saga::new()
+            .leg(deposit(1, 100, 5), 200)
+            .leg(Transaction::make_dispute(1, 1), 300)
+            .run(&mut bank)
+            .unwrap_err();
+        assert_eq!((1, BankingError::InvalidTransaction), (error.leg, error.error));
+        assert_eq!(Amount::from(10u32), available(&bank, 1));
+    }
+
+    #[test]
+    fn leg_is_compensated_by_the_given_transaction() {
+        // SETUP
+        let mut bank = Scenario::new().deposit(1, 10).run();
+        let saga = Saga::new()
+            .leg_with(deposit(2, 100, 5), withdrawal(2, 200, 2))
+            .leg(withdrawal(1, 110, 11), 210);
+
+        // TEST
+        assert!(saga.run(&mut bank).is_err());
+        assert_eq!(Amount::from(3u32), available(&bank, 2));
+    }
+
+    #[test]
+    fn rejected_compensations_are_reported() {
+        // SETUP
+        let mut bank = Scenario::new().deposit(1, 10).run();
+        // the compensation of the first leg reuses the ID of the deposit of client 1
+        let saga = Saga::new()
+            .leg_with(deposit(2, 100, 5), withdrawal(2, 1, 5))
+            .leg(deposit(3, 110, 5), 210)
+            .leg(withdrawal(1, 120, 11), 220);
+
+        // TEST
+        let error = saga.run(&mut bank).unwrap_err();
+        assert_eq!(vec![(0, BankingError::DuplicateTransactionId)], error.uncompensated);
+        assert_eq!((Amount::from(5u32), Amount::from(0u32)), (available(&bank, 2), available(&bank, 3)));
+    }
+}
+//endregion
@@ -0,0 +1,280 @@
+#![forbid(unsafe_code)] // for good measure
+use crate::locale::Localize;
+use crate::provenance::Provenance;
+use crate::transaction::{RecordOptions, Transaction, TransactionRecord};
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// `Schema` enumerates the supported layouts of the input data. Every schema maps onto the same
+/// internal `Transaction` type; newer schemas only add columns.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Schema {
+    /// The original layout: `type, client, tx, amount`.
+    V1,
+    /// The extended layout: `type, client, tx, amount, timestamp, currency`.
+    V2,
+}
+
+impl Schema {
+    /// The columns of this schema, in the order expected when the input has no header row.
+    pub fn columns(&self) -> &'static [&'static str] {
+        match self {
+            Schema::V1 => &["type", "client", "tx", "amount"],
+            Schema::V2 => &["type", "client", "tx", "amount", "timestamp", "currency"],
+        }
+    }
+
+    /// Determine the schema of a file from its header row. Any of the columns introduced by v2
+    /// marks the file as v2.
+    pub fn detect(headers: &csv::StringRecord) -> Schema {
+        let v1 = Schema::V1.columns();
+        if headers
+            .iter()
+            .any(|header| !v1.contains(&header) && Schema::V2.columns().contains(&header))
+        {
+            Schema::V2
+        } else {
+            Schema::V1
+        }
+    }
+
+    /// Verify that every column of this schema is present in the given header row, returning the
+    /// names of any that are missing.
+    pub fn check_headers(&self, headers: &csv::StringRecord) -> Result<(), Vec<&'static str>> {
+        let missing: Vec<&'static str> = self
+            .columns()
+            .iter()
+            .filter(|column| !headers.iter().any(|header| header == **column))
+            .copied()
+            .collect();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(missing)
+        }
+    }
+}
+
+/// Read the header row of the given reader and check it against a schema, which is detected from the
+/// header row when not given. Columns the `options` of the source make optional may be missing.
+/// Returns the schema in use, or a description of why the header row does not match it.
+pub fn check_input<R: io::Read>(reader: &mut csv::Reader<R>, schema: Option<Schema>, options: &RecordOptions) -> Result<Schema, String> {
+    let headers = reader.headers().map_err(|e| e.to_string())?;
+    let schema = schema.unwrap_or_else(|| Schema::detect(headers));
+    let amounts = options.amounts;
+    match schema.check_headers(headers) {
+        Err(missing) if missing.iter().any(|column| !amounts.is_optional(column)) => Err(format!(
+            "Input does not match schema {}. Missing columns: {}",
+            schema,
+            missing.join(", ")
+        )),
+        _ => Ok(schema),
+    }
+}
+
+/// Rename the columns of the given reader's header row, from the names a source uses to the names
+/// of this crate's fields, e.g. `txn_type` to `type`. Columns not in `columns` keep their name. As
+/// the header row is renamed in place, the records themselves are read unchanged.
+pub fn map_columns<R: io::Read>(reader: &mut csv::Reader<R>, columns: &HashMap<String, String>) -> Result<(), csv::Error> {
+    if columns.is_empty() {
+        return Ok(());
+    }
+    let headers: csv::StringRecord = reader
+        .headers()?
+        .iter()
+        .map(|header| columns.get(header).map_or(header, String::as_str))
+        .collect();
+    reader.set_headers(headers);
+    Ok(())
+}
+
+/// Parse a column mapping given on the command line, `source=field`.
+pub fn parse_column_mapping(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((source, field)) if !source.is_empty() && !field.is_empty() => Ok((source.to_string(), field.to_string())),
+        _ => Err(format!("invalid column mapping: {} (expected e.g. txn_type=type)", value)),
+    }
+}
+
+/// Map a CSV record onto a transaction, following the conventions of its source. `headers` is the
+/// header row of the input, if it has one.
+pub fn parse_record(
+    record: &csv::StringRecord,
+    headers: Option<&csv::StringRecord>,
+    options: &RecordOptions,
+) -> Result<Transaction, String> {
+    let record: TransactionRecord = record.deserialize(headers).map_err(|e| e.to_string())?;
+    options.transaction(record).map_err(|e| e.localized(options.locale).to_string())
+}
+
+/// Adapt a CSV reader into an iterator of transactions, for `Bank::process_all`, reading each record
+/// with the `options` of its source. Records that can not be parsed are logged and skipped.
+pub fn read_transactions<'a, R: io::Read>(
+    reader: &'a mut csv::Reader<R>,
+    options: &'a RecordOptions,
+) -> impl Iterator<Item = Transaction> + 'a {
+    let headers = headers(reader);
+    // one record is read into at a time, rather than allocating one per record
+    let mut record = csv::StringRecord::new();
+    std::iter::from_fn(move || loop {
+        let parsed = match reader.read_record(&mut record) {
+            Ok(false) => return None,
+            Ok(true) => parse_record(&record, headers.as_ref(), options),
+            Err(e) => Err(e.to_string()),
+        };
+        match parsed {
+            Ok(transaction) => return Some(transaction),
+            Err(e) => error!("Failed to parse transaction. Discarded with error: {}", e),
+        }
+    })
+}
+
+/// As `read_transactions`, recording the line of `file` each transaction was read from as its
+/// `provenance`. Records that can not be parsed are logged with their line.
+pub fn read_transactions_from<'a, R: io::Read>(
+    reader: &'a mut csv::Reader<R>,
+    file: &str,
+    options: &'a RecordOptions,
+) -> impl Iterator<Item = Transaction> + 'a {
+    let file: Arc<str> = Arc::from(file);
+    let headers = headers(reader);
+    reader.records().filter_map(move |result| {
+        let record = match result {
+            Ok(record) => record,
+            Err(e) => {
+                error!("Failed to parse transaction. Discarded with error: {}", e);
+                return None;
+            }
+        };
+        let line = record.position().map_or(0, csv::Position::line);
+        match parse_record(&record, headers.as_ref(), options) {
+            Ok(mut transaction) => {
+                transaction.provenance = Some(Provenance::Line { file: file.clone(), line });
+                Some(transaction)
+            }
+            Err(e) => {
+                error!("Failed to parse transaction at {}:{}. Discarded with error: {}", file, line, e);
+                None
+            }
+        }
+    })
+}
+
+/// The header row of the given reader, if the input has one.
+fn headers<R: io::Read>(reader: &mut csv::Reader<R>) -> Option<csv::StringRecord> {
+    match reader.has_headers() {
+        true => reader.headers().ok().cloned(),
+        false => None,
+    }
+}
+
+impl FromStr for Schema {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Schema, String> {
+        match value {
+            "v1" => Ok(Schema::V1),
+            "v2" => Ok(Schema::V2),
+            _ => Err(format!("unsupported schema: {} (expected v1 or v2)", value)),
+        }
+    }
+}
+
+impl fmt::Display for Schema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Schema::V1 => write!(f, "v1"),
+            Schema::V2 => write!(f, "v2"),
+        }
+    }
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::{NegativeDeposits, TransactionType};
+    use chrono::FixedOffset;
+
+    #[test]
+    fn each_reader_is_read_with_its_own_options() {
+        // SETUP
+        let input = "type,client,tx,amount,timestamp,currency\ndeposit,1,1,2.5,2021-06-01 09:30:00,\n";
+        let partner = RecordOptions {
+            currency: Some(String::from("EUR")),
+            utc_offset: FixedOffset::east_opt(3600),
+            ..RecordOptions::new()
+        };
+        let read = |options: &RecordOptions| -> Vec<Transaction> {
+            let mut reader = csv::Reader::from_reader(input.as_bytes());
+            read_transactions(&mut reader, options).collect()
+        };
+
+        // TEST
+        let transactions = read(&partner);
+        assert_eq!(Some("EUR"), transactions[0].currency.as_deref());
+        assert_eq!("2021-06-01T08:30:00Z".parse().ok(), transactions[0].timestamp);
+        // without an offset, the timestamp can not be read
+        assert!(read(&RecordOptions::default()).is_empty());
+        assert_eq!(1, read(&partner).len());
+    }
+
+    #[test]
+    fn negative_deposits_follow_the_policy_of_each_reader() {
+        // SETUP
+        let input = "type,client,tx,amount\ndeposit,1,1,-2.5\n";
+        let returns = RecordOptions {
+            negative_deposits: NegativeDeposits::Withdrawal,
+            ..RecordOptions::new()
+        };
+        let read = |options: &RecordOptions| -> Vec<Transaction> {
+            let mut reader = csv::Reader::from_reader(input.as_bytes());
+            read_transactions(&mut reader, options).collect()
+        };
+
+        // TEST
+        let transactions = read(&returns);
+        assert_eq!(TransactionType::Withdrawal, transactions[0].kind);
+        assert!(read(&RecordOptions::default()).is_empty());
+        assert_eq!(1, read(&returns).len());
+    }
+
+    #[test]
+    fn transactions_record_the_line_they_were_read_from() {
+        // SETUP
+        let input = "type,client,tx,amount\ndeposit,1,1,2.5\ndeposit,1,2,oops\nwithdrawal,1,3,1.0\n";
+        let mut reader = csv::Reader::from_reader(input.as_bytes());
+
+        // TEST
+        let sources: Vec<String> = read_transactions_from(&mut reader, "input.csv", &RecordOptions::default())
+            .map(|transaction| transaction.provenance.map(|source| source.to_string()).unwrap_or_default())
+            .collect();
+        assert_eq!(vec!["input.csv:2", "input.csv:4"], sources);
+    }
+
+    #[test]
+    fn mapped_columns_are_read_as_the_fields_they_map_to() -> Result<(), csv::Error> {
+        // SETUP
+        let input = "txn_type,customer,tx,amount\ndeposit,1,7,2.5\n";
+        let mut reader = csv::Reader::from_reader(input.as_bytes());
+        let columns: HashMap<String, String> = vec!["txn_type=type", "customer=client"]
+            .into_iter()
+            .map(|mapping| parse_column_mapping(mapping).expect("valid mapping"))
+            .collect();
+
+        // TEST
+        map_columns(&mut reader, &columns)?;
+        let options = RecordOptions::default();
+        assert_eq!(Ok(Schema::V1), check_input(&mut reader, None, &options));
+        let transactions: Vec<Transaction> = read_transactions(&mut reader, &options).collect();
+        assert_eq!((1, 7), (transactions[0].client, transactions[0].tx));
+        assert!(parse_column_mapping("customer").is_err());
+
+        // TEARDOWN
+        Ok(())
+    }
+}
+//endregion
@@ -0,0 +1,364 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Scenario scripts
+//! A small text language for describing scenarios and what should come of them, so that test
+//! cases and demos can be written without writing Rust:
+//!
+//! ```text
+//! # a chargeback locks the account
+//! client 1 deposits 5; disputes tx 1; expect available 0 held 5
+//! charges back tx 1
+//! expect total 0 locked
+//! client 2 withdraws 1.5
+//! expect rejected
+//! ```
+//!
+//! Statements are separated by new lines or `;`, and `#` starts a comment. A statement may start
+//! with `client <id>`, which is the client of it and of the statements after it, followed by one
+//! of:
+//!
+//! * `deposits <amount>` or `withdraws <amount>`, which are given the next transaction ID,
+//!   starting at 1, as `testkit::Scenario` does;
+//! * `disputes tx <id>`, `resolves tx <id>`, or `charges back tx <id>`;
+//! * `expect` and any of `available <amount>`, `held <amount>`, `total <amount>`, `locked`,
+//!   `unlocked`, and `rejected` (the previous transaction was rejected) or `applied`.
+//!
+//! Scripts are run by the `scenario` binary, or from tests with `script::check`.
+use crate::account::ClientId;
+use crate::amount::{parse_amount, Amount, AmountFormat};
+use crate::bank::Bank;
+use crate::errors::BankingError;
+use crate::transaction::{Transaction, TransactionType, TxId};
+use std::fmt;
+use std::str::FromStr;
+
+/// `Expectation` is something a script expects of the client's account, or of the previous
+/// transaction.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expectation {
+    Available(Amount),
+    Held(Amount),
+    Total(Amount),
+    Locked(bool),
+    Rejected(bool),
+}
+
+/// `Step` is what a statement does.
+#[derive(Clone, Debug, PartialEq)]
+enum Step {
+    Process(Transaction),
+    Expect(Vec<Expectation>),
+}
+
+/// `Statement` is a step of a script, with the client it applies to and where it was written.
+#[derive(Clone, Debug, PartialEq)]
+struct Statement {
+    line: usize,
+    client: ClientId,
+    step: Step,
+}
+
+/// `ScriptError` is a statement that could not be parsed, or an expectation that was not met.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScriptError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+/// `Script` is a parsed scenario script.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Script {
+    statements: Vec<Statement>,
+}
+
+impl FromStr for Script {
+    type Err = ScriptError;
+
+    fn from_str(text: &str) -> Result<Script, ScriptError> {
+        let mut parser = Parser { client: None, next_tx: 1 };
+        let mut statements = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let code = line.split('#').next().unwrap_or_default();
+            for statement in code.split(';') {
+                let words: Vec<&str> = statement.split_whitespace().collect();
+                if words.is_empty() {
+                    continue;
+                }
+                statements.push(parser.parse(line_number, &words).map_err(|message| ScriptError {
+                    line: line_number,
+                    message,
+                })?);
+            }
+        }
+        Ok(Script { statements })
+    }
+}
+
+impl Script {
+    /// Run the script on a new bank, returning the bank and every expectation that was not met.
+    pub fn run(&self) -> (Bank, Vec<ScriptError>) {
+        let mut bank = Bank::new();
+        let mut failures = Vec::new();
+        let mut last: Option<Result<(), BankingError>> = None;
+        for statement in &self.statements {
+            match &statement.step {
+                Step::Process(transaction) => last = Some(bank.process_transaction(transaction.clone())),
+                Step::Expect(expectations) => {
+                    for expectation in expectations {
+                        if let Err(message) = check_expectation(&bank, statement.client, last.as_ref(), expectation) {
+                            failures.push(ScriptError {
+                                line: statement.line,
+                                message,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        (bank, failures)
+    }
+}
+
+/// Parse and run a script, panicking with every failure if it does not parse or an expectation
+/// is not met.
+pub fn check(text: &str) {
+    let script: Script = text.parse().unwrap_or_else(|e| panic!("{}", e));
+    let (_, failures) = script.run();
+    let report: Vec<String> = failures.iter().map(ToString::to_string).collect();
+    assert!(failures.is_empty(), "scenario failed:\n{}", report.join("\n"));
+}
+
+/// `Parser` keeps the state carried from one statement to the next.
+struct Parser {
+    client: Option<ClientId>,
+    next_tx: TxId,
+}
+
+impl Parser {
+    fn parse(&mut self, line: usize, mut words: &[&str]) -> Result<Statement, String> {
+        if let ["client", id, rest @ ..] = words {
+            self.client = Some(id.parse().map_err(|e| format!("invalid client \"{}\": {}", id, e))?);
+            words = rest;
+        }
+        let client = self.client.ok_or("no client given; start the statement with `client <id>`")?;
+        let step = match words {
+            ["deposits", amount] => Step::Process(self.funds(TransactionType::Deposit, client, amount)?),
+            ["withdraws", amount] => Step::Process(self.funds(TransactionType::Withdrawal, client, amount)?),
+            ["disputes", "tx", tx] => Step::Process(Transaction::make_dispute(client, parse_tx(tx)?)),
+            ["resolves", "tx", tx] => Step::Process(Transaction::make_resolve(client, parse_tx(tx)?)),
+            ["charges", "back", "tx", tx] => Step::Process(Transaction::make_chargeback(client, parse_tx(tx)?)),
+            ["expect", rest @ ..] if !rest.is_empty() => Step::Expect(parse_expectations(rest)?),
+            _ => return Err(format!("unknown statement: {}", words.join(" "))),
+        };
+        Ok(Statement { line, client, step })
+    }
+
+    /// A deposit or withdrawal, given the next transaction ID.
+    fn funds(&mut self, kind: TransactionType, client: ClientId, amount: &str) -> Result<Transaction, String> {
+        let mut transaction = Transaction::make(kind, client, self.next_tx, 0, false);
+        transaction.amount = Some(parse(amount)?);
+        self.next_tx += 1;
+        Ok(transaction)
+    }
+}
+
+fn parse_tx(tx: &str) -> Result<TxId, String> {
+    tx.parse().map_err(|e| format!("invalid transaction \"{}\": {}", tx, e))
+}
+
+fn parse(amount: &str) -> Result<Amount, String> {
+    parse_amount(amount, &AmountFormat::new()).map_err(|e| format!("invalid amount \"{}\": {}", amount, e))
+}
+
+fn parse_expectations(mut words: &[&str]) -> Result<Vec<Expectation>, String> {
+    let mut expectations = Vec::new();
+    while !words.is_empty() {
+        let (expectation, rest) = match words {
+            ["available", amount, rest @ ..] => (Expectation::Available(parse(amount)?), rest),
+            ["held", amount, rest @ ..] => (Expectation::Held(parse(amount)?), rest),
+            ["total", amount, rest @ ..] => (Expectation::Total(parse(amount)?), rest),
+            ["locked", rest @ ..] => (Expectation::Locked(true), rest),
+            ["unlocked", rest @ ..] => (Expectation::Locked(false), rest),
+            ["rejected", rest @ ..] => (Expectation::Rejected(true), rest),
+            ["applied", rest @ ..] => (Expectation::Rejected(false), rest),
+            [word, ..] => return Err(format!("unknown expectation: {}", word)),
+            [] => unreachable!("words is not empty"),
+        };
+        expectations.push(expectation);
+        words = rest;
+    }
+    Ok(expectations)
+}
+
+/// Check an expectation, describing how it was not met.
+fn check_expectation(
+    bank: &Bank,
+    client: ClientId,
+    last: Option<&Result<(), BankingError>>,
+    expectation: &Expectation,
+) -> Result<(), String> {
+    if let Expectation::Rejected(rejected) = *expectation {
+        return match (last, rejected) {
+            (None, _) => Err(String::from("expected a previous transaction")),
+            (Some(Ok(())), true) => Err(String::from("expected the previous transaction to be rejected, but it was applied")),
+            (Some(Err(e)), false) => Err(format!(
+                "expected the previous transaction to be applied, but it was rejected: {}",
+                e
+            )),
+            _ => Ok(()),
+        };
+    }
+    let account = bank.account(client).ok_or_else(|| format!("client {} has no account", client))?;
+    let (name, met, expected, actual) = match *expectation {
+        Expectation::Available(amount) => (
+            "available",
+            account.available == amount,
+            amount.to_string(),
+            account.available.to_string(),
+        ),
+        Expectation::Held(amount) => ("held", account.held == amount, amount.to_string(), account.held.to_string()),
+        Expectation::Total(amount) => ("total", account.total == amount, amount.to_string(), account.total.to_string()),
+        Expectation::Locked(locked) => ("locked", account.locked == locked, locked.to_string(), account.locked.to_string()),
+        Expectation::Rejected(_) => unreachable!("checked above"),
+    };
+    match met {
+        true => Ok(()),
+        false => Err(format!("expected client {} {} {}, but it was {}", client, name, expected, actual)),
+    }
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The failures of running `text`, as they are displayed.
+    fn failures(text: &str) -> Vec<String> {
+        let (_, failures) = text.parse::<Script>().unwrap().run();
+        failures.iter().map(ToString::to_string).collect()
+    }
+
+    /// The error of parsing `text`, as it is displayed.
+    fn parse_error(text: &str) -> String {
+        text.parse::<Script>().unwrap_err().to_string()
+    }
+
+    #[test]
+    fn met_expectations_pass() {
+        // TEST
+        check(
+            "# a chargeback locks the account\n\
+             client 1 deposits 5; disputes tx 1; expect available 0 held 5\n\
+             charges back tx 1\n\
+             expect total 0 locked",
+        );
+    }
+
+    #[test]
+    fn deposits_and_withdrawals_are_given_the_next_transaction_id() {
+        // TEST
+        check("client 1 deposits 5; client 2 deposits 3; client 1 withdraws 1; client 2 disputes tx 2; expect held 3; client 1 disputes tx 1; expect held 5");
+    }
+
+    #[test]
+    fn client_carries_over_to_later_statements() {
+        // SETUP
+        let script: Script = "client 7 deposits 1\nwithdraws 1".parse().unwrap();
+
+        // TEST
+        let (bank, _) = script.run();
+        assert_eq!(
+            ("0", 1),
+            (bank.account(7).unwrap().total.to_string().as_str(), bank.accounts().count())
+        );
+    }
+
+    #[test]
+    fn unmet_expectations_are_reported_with_their_line() {
+        // TEST
+        assert_eq!(
+            vec![String::from("line 2: expected client 1 available 4, but it was 5")],
+            failures("client 1 deposits 5\nexpect available 4 unlocked")
+        );
+    }
+
+    #[test]
+    fn every_unmet_expectation_of_a_statement_is_reported() {
+        // TEST
+        assert_eq!(2, failures("client 1 deposits 5; expect held 1 total 4 unlocked").len());
+    }
+
+    #[test]
+    fn rejected_and_applied_refer_to_the_previous_transaction() {
+        // TEST
+        check("client 2 deposits 2.5; expect applied; withdraws 3; expect rejected available 2.5");
+        assert_eq!(
+            vec![String::from(
+                "line 1: expected the previous transaction to be rejected, but it was applied"
+            )],
+            failures("client 1 deposits 1; expect rejected")
+        );
+    }
+
+    #[test]
+    fn rejection_is_expected_of_a_previous_transaction() {
+        // TEST
+        assert_eq!(
+            vec![String::from("line 1: expected a previous transaction")],
+            failures("client 1 expect applied")
+        );
+    }
+
+    #[test]
+    fn expectations_of_clients_without_an_account_are_not_met() {
+        // TEST
+        assert_eq!(
+            vec![String::from("line 1: client 3 has no account")],
+            failures("client 3 expect unlocked")
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "scenario failed:\nline 1: expected client 1 locked true, but it was false")]
+    fn check_panics_with_every_failure() {
+        // TEST
+        check("client 1 deposits 1; expect locked");
+    }
+
+    #[test]
+    fn statement_without_a_client_is_an_error() {
+        // TEST
+        assert_eq!(
+            "line 1: no client given; start the statement with `client <id>`",
+            parse_error("deposits 5")
+        );
+    }
+
+    #[test]
+    fn unknown_statements_and_expectations_are_errors() {
+        // TEST
+        assert_eq!(
+            "line 2: unknown statement: refunds 3",
+            parse_error("client 1 deposits 5\nrefunds 3")
+        );
+        assert_eq!("line 1: unknown statement: expect", parse_error("client 1 expect"));
+        assert_eq!("line 1: unknown expectation: frozen", parse_error("client 1 expect frozen"));
+    }
+
+    #[test]
+    fn invalid_clients_transactions_and_amounts_are_errors() {
+        // TEST
+        assert!(parse_error("client x deposits 1").starts_with("line 1: invalid client \"x\""));
+        assert!(parse_error("client 1 disputes tx y").starts_with("line 1: invalid transaction \"y\""));
+        assert!(parse_error("client 1 deposits lots").starts_with("line 1: invalid amount \"lots\""));
+    }
+}
+//endregion
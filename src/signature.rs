@@ -0,0 +1,163 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Record signatures
+//! Partners may sign each record they send with an HMAC-SHA256 over its canonical form, carried
+//! hex-encoded in an optional `signature` column. When a bank is built with a `SigningKey`, every
+//! record must carry a valid signature; any other is rejected with `BankingError::BadSignature`
+//! before it is otherwise looked at.
+//!
+//! The canonical form of a record is its fields joined by `,`, without spaces:
+//! `type,client,tx,amount`, followed by `,timestamp,currency` when either is present. Amounts are
+//! written without trailing zeros (`1.5`, not `1.5000`) and may be empty; timestamps are RFC 3339 in
+//! UTC, e.g. `2021-06-01T12:00:00Z`.
+use crate::amount;
+use crate::errors::BankingError;
+use crate::hex::{from_hex, to_hex};
+use crate::transaction::Transaction;
+use chrono::SecondsFormat;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// `SigningKey` is the secret shared with a partner. It is never printed, not even by `Debug`.
+#[derive(Clone, PartialEq)]
+pub struct SigningKey(Arc<[u8]>);
+
+impl SigningKey {
+    pub fn new(key: &[u8]) -> SigningKey {
+        SigningKey(Arc::from(key))
+    }
+
+    /// Read a key from a file. Trailing whitespace, such as the newline most editors add, is not
+    /// part of the key.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<SigningKey> {
+        let contents = fs::read(path)?;
+        let len = contents
+            .iter()
+            .rposition(|byte| !byte.is_ascii_whitespace())
+            .map_or(0, |last| last + 1);
+        if len == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "signing key file is empty"));
+        }
+        Ok(SigningKey::new(&contents[..len]))
+    }
+
+    /// Read a key from the named environment variable.
+    pub fn from_env(name: &str) -> Result<SigningKey, String> {
+        match std::env::var(name) {
+            Ok(value) if !value.is_empty() => Ok(SigningKey::new(value.as_bytes())),
+            Ok(_) => Err(format!("environment variable {} is empty", name)),
+            Err(e) => Err(format!("environment variable {}: {}", name, e)),
+        }
+    }
+
+    /// The HMAC of an arbitrary message under this key.
+    #[cfg(feature = "io")]
+    pub(crate) fn digest(&self, message: &[u8]) -> [u8; 32] {
+        let mut mac = HmacSha256::new_from_slice(&self.0).expect("HMAC accepts keys of any length");
+        mac.update(message);
+        mac.finalize().into_bytes().into()
+    }
+
+    fn mac(&self, transaction: &Transaction) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.0).expect("HMAC accepts keys of any length");
+        mac.update(canonical(transaction).as_bytes());
+        mac
+    }
+}
+
+impl fmt::Debug for SigningKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SigningKey(..)")
+    }
+}
+
+/// The canonical form of a transaction, over which its signature is computed.
+pub fn canonical(transaction: &Transaction) -> String {
+    let amount = transaction
+        .amount
+        .map(|amount| amount::to_decimal(amount).normalize().to_string())
+        .unwrap_or_default();
+    let mut fields = vec![
+        transaction.kind.to_string(),
+        transaction.client.to_string(),
+        transaction.tx.to_string(),
+        amount,
+    ];
+    if transaction.timestamp.is_some() || transaction.currency.is_some() {
+        fields.push(
+            transaction
+                .timestamp
+                .map(|timestamp| timestamp.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+                .unwrap_or_default(),
+        );
+        fields.push(transaction.currency.clone().unwrap_or_default());
+    }
+    // so that the marker can not be added to a signed record
+    if transaction.correction {
+        fields.push(String::from("correction"));
+    }
+    fields.join(",")
+}
+
+/// The hex-encoded signature of a transaction, as a partner would send it.
+pub fn sign(key: &SigningKey, transaction: &Transaction) -> String {
+    to_hex(&key.mac(transaction).finalize().into_bytes().into())
+}
+
+/// Check the signature a transaction carries. Signatures are compared in constant time.
+pub fn verify(key: &SigningKey, transaction: &Transaction) -> Result<(), BankingError> {
+    let signature = transaction
+        .signature
+        .as_deref()
+        .and_then(from_hex)
+        .ok_or(BankingError::BadSignature)?;
+    key.mac(transaction)
+        .verify_slice(&signature)
+        .map_err(|_| BankingError::BadSignature)
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amount::Amount;
+    use crate::bank::Bank;
+    use crate::transaction::TransactionType;
+
+    #[test]
+    fn canonical_form_strips_trailing_zeros() {
+        let mut transaction = Transaction::make(TransactionType::Deposit, 1, 2, 1, false);
+        transaction.amount = amount::from_decimal(rust_decimal::Decimal::new(15000, 4));
+        assert_eq!("deposit,1,2,1.5", canonical(&transaction));
+        assert_eq!("dispute,1,2,", canonical(&Transaction::make_dispute(1, 2)));
+    }
+
+    #[test]
+    fn only_correctly_signed_records_are_processed() {
+        // SETUP
+        let key = SigningKey::new(b"partner secret");
+        let mut bank = Bank::builder().signing_key(key.clone()).build();
+        let mut signed = Transaction::make(TransactionType::Deposit, 1, 1, 5, false);
+        signed.signature = Some(sign(&key, &signed));
+        let mut tampered = Transaction::make(TransactionType::Deposit, 1, 2, 5, false);
+        tampered.signature = Some(sign(&key, &tampered));
+        tampered.amount = Some(Amount::from(500u32));
+        let unsigned = Transaction::make(TransactionType::Deposit, 1, 3, 5, false);
+        let mut foreign = Transaction::make(TransactionType::Deposit, 1, 4, 5, false);
+        foreign.signature = Some(sign(&SigningKey::new(b"another secret"), &foreign));
+
+        // TEST
+        assert_eq!(Ok(()), bank.process_transaction(signed));
+        assert_eq!(Err(BankingError::BadSignature), bank.process_transaction(tampered));
+        assert_eq!(Err(BankingError::BadSignature), bank.process_transaction(unsigned));
+        assert_eq!(Err(BankingError::BadSignature), bank.process_transaction(foreign));
+        assert_eq!(Some(Amount::from(5u32)), bank.account(1).map(|account| account.total));
+    }
+}
+//endregion
@@ -0,0 +1,255 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Result sinks
+//! A `ResultSink` is told the outcome of every transaction processed by `Bank::process_all_with`,
+//! so that integrators can acknowledge records, set rejected ones aside, or bill per record
+//! without re-implementing the processing loop.
+//!
+//! Any closure taking `(record_index, &Transaction, Result<(), BankingError>)` is a sink:
+//!
+//! ```
+//! # use rust_payment_processor::bank::Bank;
+//! # use rust_payment_processor::errors::BankingError;
+//! # use rust_payment_processor::transaction::{RecordOptions, Transaction, TransactionRecord, TransactionType};
+//! # let options = RecordOptions::default();
+//! # let transactions = vec![
+//! #     options.transaction(TransactionRecord::new(TransactionType::Deposit, 1, 1, Some("5"))).unwrap(),
+//! #     options.transaction(TransactionRecord::new(TransactionType::Withdrawal, 1, 2, Some("7"))).unwrap(),
+//! # ];
+//! # let mut bank = Bank::new();
+//! let mut rejected = Vec::new();
+//! bank.process_all_with(transactions, &mut |index, _: &Transaction, result: Result<(), BankingError>| {
+//!     if let Err(e) = result {
+//!         rejected.push((index, e));
+//!     }
+//! });
+//! # assert_eq!(vec![(1, BankingError::InsufficientFunds)], rejected);
+//! ```
+use crate::account::Account;
+#[cfg(feature = "io")]
+use crate::account::ClientId;
+#[cfg(feature = "io")]
+use crate::amount::Amount;
+use crate::bank::Bank;
+use crate::errors::BankingError;
+#[cfg(feature = "io")]
+use crate::redact::redact;
+use crate::transaction::Transaction;
+#[cfg(feature = "io")]
+use crate::transaction::{TransactionType, TxId};
+#[cfg(feature = "io")]
+use serde::Serialize;
+#[cfg(feature = "io")]
+use std::io;
+
+/// `ResultSink` receives the outcome of each transaction, in processing order.
+pub trait ResultSink {
+    /// Called once per transaction after it has been processed. `index` is the position of the
+    /// transaction in the processed sequence, starting at 0; `transaction` is as it was received.
+    fn record(&mut self, index: usize, transaction: &Transaction, result: Result<(), BankingError>);
+
+    /// Called with the new state of an account whenever a transaction changes it, before `record`
+    /// is called for that transaction. Does nothing unless overridden.
+    fn account_updated(&mut self, _account: &Account) {}
+
+    /// Called with the bank after `record`, for sinks that sample its state as processing goes.
+    /// Does nothing unless overridden; as it is called for every transaction, overrides should
+    /// return quickly when there is nothing to do.
+    fn sample(&mut self, _bank: &Bank) {}
+}
+
+/// An absent sink ignores everything.
+impl<S: ResultSink> ResultSink for Option<S> {
+    fn record(&mut self, index: usize, transaction: &Transaction, result: Result<(), BankingError>) {
+        if let Some(sink) = self {
+            sink.record(index, transaction, result);
+        }
+    }
+
+    fn account_updated(&mut self, account: &Account) {
+        if let Some(sink) = self {
+            sink.account_updated(account);
+        }
+    }
+
+    fn sample(&mut self, bank: &Bank) {
+        if let Some(sink) = self {
+            sink.sample(bank);
+        }
+    }
+}
+
+/// Both sinks of a pair receive everything, the first before the second.
+impl<A: ResultSink, B: ResultSink> ResultSink for (A, B) {
+    fn record(&mut self, index: usize, transaction: &Transaction, result: Result<(), BankingError>) {
+        self.0.record(index, transaction, result.clone());
+        self.1.record(index, transaction, result);
+    }
+
+    fn account_updated(&mut self, account: &Account) {
+        self.0.account_updated(account);
+        self.1.account_updated(account);
+    }
+
+    fn sample(&mut self, bank: &Bank) {
+        self.0.sample(bank);
+        self.1.sample(bank);
+    }
+}
+
+impl<F: FnMut(usize, &Transaction, Result<(), BankingError>)> ResultSink for F {
+    fn record(&mut self, index: usize, transaction: &Transaction, result: Result<(), BankingError>) {
+        self(index, transaction, result)
+    }
+}
+
+/// `DeadLetter` is the record written for a rejected transaction: the input columns followed by the
+/// error it was rejected with. The extra column is ignored when the file is read back as input,
+/// so dead letters can be replayed once the cause has been dealt with.
+#[cfg(feature = "io")]
+#[derive(Serialize)]
+struct DeadLetter {
+    #[serde(rename = "type")]
+    kind: TransactionType,
+    client: ClientId,
+    tx: TxId,
+    amount: Option<Amount>,
+    error: String,
+}
+
+/// `DeadLetterWriter` is a sink that writes every rejected transaction, with its error, as CSV to
+/// the given writer. Failures to write are logged rather than interrupting processing.
+#[cfg(feature = "io")]
+pub struct DeadLetterWriter<W: io::Write> {
+    writer: csv::Writer<W>,
+    written: usize,
+}
+
+#[cfg(feature = "io")]
+impl<W: io::Write> DeadLetterWriter<W> {
+    pub fn new(writer: W) -> DeadLetterWriter<W> {
+        DeadLetterWriter {
+            writer: csv::Writer::from_writer(writer),
+            written: 0,
+        }
+    }
+
+    /// The number of dead letters written so far.
+    pub fn written(&self) -> usize {
+        self.written
+    }
+
+    /// Flush any buffered dead letters to the underlying writer.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(feature = "io")]
+impl<W: io::Write> ResultSink for DeadLetterWriter<W> {
+    fn record(&mut self, _index: usize, transaction: &Transaction, result: Result<(), BankingError>) {
+        if let Err(e) = result {
+            let letter = DeadLetter {
+                kind: transaction.kind.clone(),
+                client: transaction.client,
+                tx: transaction.tx,
+                amount: transaction.amount,
+                error: format!("{:?}", e),
+            };
+            match self.writer.serialize(letter) {
+                Ok(()) => self.written += 1,
+                Err(e) => error!(
+                    "Failed to write dead letter for transaction {}. Aborted with error: {}",
+                    transaction.tx, e
+                ),
+            }
+        }
+    }
+}
+
+/// `AccountStreamWriter` is a sink that writes an account as a CSV row, in the same layout as the
+/// final snapshot, every time its balances change. The last row written for a client is its
+/// current state, so downstream consumers can keep an up-to-date view while a long run is still
+/// in progress.
+#[cfg(feature = "io")]
+pub struct AccountStreamWriter<W: io::Write> {
+    writer: csv::Writer<W>,
+}
+
+#[cfg(feature = "io")]
+impl<W: io::Write> AccountStreamWriter<W> {
+    pub fn new(writer: W) -> AccountStreamWriter<W> {
+        AccountStreamWriter {
+            writer: csv::Writer::from_writer(writer),
+        }
+    }
+
+    /// Flush any buffered rows to the underlying writer.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(feature = "io")]
+impl<W: io::Write> ResultSink for AccountStreamWriter<W> {
+    fn record(&mut self, _index: usize, _transaction: &Transaction, _result: Result<(), BankingError>) {}
+
+    fn account_updated(&mut self, account: &Account) {
+        if let Err(e) = self.writer.serialize(account) {
+            error!("Failed to stream account {}. Aborted with error: {}", redact(&account.client), e);
+        }
+    }
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank::Bank;
+
+    #[test]
+    fn dead_letter_writer_records_rejected_transactions_only() -> io::Result<()> {
+        // SETUP
+        let mut bank = Bank::new();
+        let mut sink = DeadLetterWriter::new(Vec::new());
+        let transactions = vec![
+            Transaction::make(TransactionType::Deposit, 1, 1, 5, false),
+            Transaction::make(TransactionType::Withdrawal, 1, 2, 7, false),
+            Transaction::make_dispute(2, 1),
+        ];
+
+        // TEST
+        bank.process_all_with(transactions, &mut sink);
+        sink.flush()?;
+        assert_eq!(2, sink.written());
+        let written = String::from_utf8(sink.writer.get_ref().clone()).unwrap();
+        assert_eq!(
+            "type,client,tx,amount,error\nwithdrawal,1,2,7,InsufficientFunds\ndispute,2,1,,ClientMismatch\n",
+            written
+        );
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn account_stream_writer_emits_a_row_per_change() -> io::Result<()> {
+        // SETUP
+        let mut bank = Bank::new();
+        let mut sink = AccountStreamWriter::new(Vec::new());
+        let transactions = vec![
+            Transaction::make(TransactionType::Deposit, 1, 1, 5, false),
+            Transaction::make(TransactionType::Withdrawal, 1, 2, 7, false),
+            Transaction::make_dispute(1, 1),
+        ];
+
+        // TEST
+        bank.process_all_with(transactions, &mut sink);
+        sink.flush()?;
+        let written = String::from_utf8(sink.writer.get_ref().clone()).unwrap();
+        assert_eq!("client,available,held,total,locked\n1,5,0,5,false\n1,0,5,5,false\n", written);
+
+        // TEARDOWN
+        Ok(())
+    }
+}
+//endregion
@@ -0,0 +1,227 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Intermediate snapshots
+//! A `SnapshotEmitter` is a sink that writes the bank's accounts, as CSV, to a new file in a
+//! directory every `every` records, so that downstream systems can start consuming partial results
+//! of a long run instead of waiting for it to complete. Files are named after the time they were
+//! taken and the number of records processed by then:
+//!
+//! ```text
+//! accounts-20240131T235959.123Z-1000000.csv
+//! ```
+//!
+//! Each snapshot is first written to a `.tmp` file, which is renamed once complete, so that a
+//! consumer watching the directory for `.csv` files never reads one that is half written. If any
+//! account has been annotated, the annotations are written next to the snapshot, as JSON, before
+//! it is renamed:
+//!
+//! ```text
+//! accounts-20240131T235959.123Z-1000000.annotations.json
+//! ```
+use crate::bank::Bank;
+use crate::errors::BankingError;
+use crate::sink::ResultSink;
+use crate::transaction::Transaction;
+use chrono::{DateTime, Utc};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// `SnapshotEmitter` writes the bank's accounts to a timestamped file every `every` records.
+pub struct SnapshotEmitter {
+    dir: PathBuf,
+    every: usize,
+    records: usize,
+    written: Vec<PathBuf>,
+}
+
+impl SnapshotEmitter {
+    pub fn new<P: AsRef<Path>>(dir: P, every: usize) -> SnapshotEmitter {
+        SnapshotEmitter {
+            dir: dir.as_ref().to_path_buf(),
+            every: every.max(1),
+            records: 0,
+            written: Vec::new(),
+        }
+    }
+
+    /// The snapshot files written so far, oldest first.
+    pub fn written(&self) -> &[PathBuf] {
+        &self.written
+    }
+
+    /// Write a snapshot of the bank's accounts now, returning the path it was written to.
+    pub fn emit(&mut self, bank: &Bank) -> io::Result<PathBuf> {
+        let taken_at = DateTime::<Utc>::from(SystemTime::now()).format("%Y%m%dT%H%M%S%.3fZ");
+        let name = format!("accounts-{}-{}", taken_at, self.records);
+        if bank.has_annotations() {
+            write_atomically(&self.dir.join(format!("{}.annotations.json", name)), |writer| {
+                bank.write_annotations(writer).map_err(io::Error::from)
+            })?;
+        }
+        let path = self.dir.join(format!("{}.csv", name));
+        write_atomically(&path, |writer| bank.write_accounts(writer).map_err(io::Error::from))?;
+        self.written.push(path.clone());
+        Ok(path)
+    }
+}
+
+/// Write a file to `path` by way of a `.tmp` file, renamed once complete.
+fn write_atomically<F>(path: &Path, write: F) -> io::Result<()>
+where
+    F: FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+{
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".tmp");
+    let mut writer = BufWriter::new(File::create(&partial)?);
+    write(&mut writer)?;
+    writer.flush()?;
+    drop(writer);
+    fs::rename(&partial, path)
+}
+
+impl ResultSink for SnapshotEmitter {
+    fn record(&mut self, _index: usize, _transaction: &Transaction, _result: Result<(), BankingError>) {
+        self.records += 1;
+    }
+
+    fn sample(&mut self, bank: &Bank) {
+        if self.records == 0 || !self.records.is_multiple_of(self.every) {
+            return;
+        }
+        match self.emit(bank) {
+            Ok(path) => info!("Wrote snapshot {} after {} records", path.display(), self.records),
+            Err(e) => error!("Failed to write snapshot. Aborted with error: {}", e),
+        }
+    }
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testkit::Scenario;
+    use std::process;
+
+    /// A new, empty directory for the snapshots of one test case.
+    fn snapshot_dir(case: &str) -> io::Result<PathBuf> {
+        let dir = std::env::temp_dir().join(format!("rust-payment-processor-snapshot-{}-{}", case, process::id()));
+        fs::create_dir_all(&dir)?;
+        Ok(dir)
+    }
+
+    /// A scenario of five records across three clients.
+    fn five_records() -> Scenario {
+        Scenario::new()
+            .deposit(1, 5)
+            .deposit(2, 5)
+            .withdraw(1, 2)
+            .deposit(3, 1)
+            .deposit(3, 1)
+    }
+
+    #[test]
+    fn snapshots_are_written_every_n_records() -> io::Result<()> {
+        // SETUP
+        let dir = snapshot_dir("every")?;
+        let mut emitter = SnapshotEmitter::new(&dir, 2);
+
+        // TEST
+        Bank::new().process_all_with(five_records().transactions().to_vec(), &mut emitter);
+        assert_eq!(2, emitter.written().len());
+        assert!(emitter.written()[0].to_string_lossy().ends_with("-2.csv"));
+        assert!(emitter.written()[1].to_string_lossy().ends_with("-4.csv"));
+
+        // TEARDOWN
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn snapshots_hold_the_accounts_at_the_time_they_were_taken() -> io::Result<()> {
+        // SETUP
+        let dir = snapshot_dir("accounts")?;
+        let mut emitter = SnapshotEmitter::new(&dir, 2);
+
+        // TEST
+        Bank::new().process_all_with(five_records().transactions().to_vec(), &mut emitter);
+        let first = fs::read_to_string(&emitter.written()[0])?;
+        assert_eq!(3, first.lines().count(), "{}", first);
+        let second = fs::read_to_string(&emitter.written()[1])?;
+        assert_eq!(4, second.lines().count(), "{}", second);
+
+        // TEARDOWN
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn no_partial_files_are_left_behind() -> io::Result<()> {
+        // SETUP
+        let dir = snapshot_dir("partial")?;
+        let mut emitter = SnapshotEmitter::new(&dir, 1);
+
+        // TEST
+        Bank::new().process_all_with(five_records().transactions().to_vec(), &mut emitter);
+        let names: Vec<PathBuf> = fs::read_dir(&dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<_>>()?;
+        assert_eq!(5, names.len());
+        assert!(names.iter().all(|name| name.extension() == Some("csv".as_ref())), "{:?}", names);
+
+        // TEARDOWN
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn snapshot_is_written_every_record_when_every_is_zero() -> io::Result<()> {
+        // SETUP
+        let dir = snapshot_dir("zero")?;
+        let mut emitter = SnapshotEmitter::new(&dir, 0);
+
+        // TEST
+        Bank::new().process_all_with(five_records().transactions().to_vec(), &mut emitter);
+        assert_eq!(5, emitter.written().len());
+
+        // TEARDOWN
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn annotations_are_written_next_to_the_snapshot() -> io::Result<()> {
+        // SETUP
+        let dir = snapshot_dir("annotations")?;
+        let mut emitter = SnapshotEmitter::new(&dir, 100);
+        let mut bank = five_records().run();
+        bank.label_account(2, "ticket", Some("SUP-1234")).expect("account 2 exists");
+
+        // TEST
+        let path = emitter.emit(&bank)?;
+        let annotations = fs::read_to_string(path.with_extension("annotations.json"))?;
+        assert!(annotations.contains("SUP-1234"), "{}", annotations);
+        assert_eq!(2, fs::read_dir(&dir)?.count());
+
+        // TEARDOWN
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn annotations_are_left_out_when_there_are_none() -> io::Result<()> {
+        // SETUP
+        let dir = snapshot_dir("no-annotations")?;
+        let mut emitter = SnapshotEmitter::new(&dir, 100);
+
+        // TEST
+        let path = emitter.emit(&five_records().run())?;
+        assert!(!path.with_extension("annotations.json").exists());
+        assert_eq!(1, fs::read_dir(&dir)?.count());
+
+        // TEARDOWN
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
+//endregion
@@ -0,0 +1,179 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Run statistics
+//! A `StatsReporter` is a sink that periodically writes a line of statistics about the run, so that
+//! the capacity needed for large runs can be planned without an external profiler:
+//!
+//! ```text
+//! stats: records=1200000 rate=241000/s rss_kb=181240 accounts=65535 transactions=1180322 pending=0 audit_entries=0 allocations=2400113
+//! ```
+//!
+//! `rate` is the number of records processed per second since the previous line. Resident memory
+//! is read from `/proc/self/status`, and is left out where that is not available. Allocations can
+//! only be counted by the binary's global allocator, which hands the reporter its count with
+//! `StatsReporter::with_allocations`; without it, they are left out too.
+use crate::bank::Bank;
+use crate::errors::BankingError;
+use crate::sink::ResultSink;
+use crate::transaction::Transaction;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+/// The clock is only read every this many records, to keep the sink cheap.
+const CHECK_EVERY: usize = 1024;
+
+/// `StatsReporter` writes a line of statistics to a writer every `interval`.
+pub struct StatsReporter<W: Write> {
+    writer: W,
+    interval: Duration,
+    allocations: Option<fn() -> u64>,
+    records: usize,
+    last_records: usize,
+    last_report: Instant,
+}
+
+impl<W: Write> StatsReporter<W> {
+    pub fn new(writer: W, interval: Duration) -> StatsReporter<W> {
+        StatsReporter {
+            writer,
+            interval,
+            allocations: None,
+            records: 0,
+            last_records: 0,
+            last_report: Instant::now(),
+        }
+    }
+
+    /// Report the number of allocations made so far, as returned by `count`.
+    pub fn with_allocations(mut self, count: fn() -> u64) -> StatsReporter<W> {
+        self.allocations = Some(count);
+        self
+    }
+
+    /// Write a line of statistics now, e.g. once processing has finished.
+    pub fn report(&mut self, bank: &Bank) -> io::Result<()> {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_report).as_secs_f64();
+        let rate = match elapsed > 0.0 {
+            true => ((self.records - self.last_records) as f64 / elapsed) as u64,
+            false => 0,
+        };
+        let footprint = bank.footprint();
+        write!(self.writer, "stats: records={} rate={}/s", self.records, rate)?;
+        if let Some(rss) = resident_kb() {
+            write!(self.writer, " rss_kb={}", rss)?;
+        }
+        write!(
+            self.writer,
+            " accounts={} transactions={} pending={} audit_entries={}",
+            footprint.accounts, footprint.transactions, footprint.pending, footprint.audit_entries
+        )?;
+        if let Some(count) = self.allocations {
+            write!(self.writer, " allocations={}", count())?;
+        }
+        writeln!(self.writer)?;
+        self.last_records = self.records;
+        self.last_report = now;
+        self.writer.flush()
+    }
+}
+
+impl<W: Write> ResultSink for StatsReporter<W> {
+    fn record(&mut self, _index: usize, _transaction: &Transaction, _result: Result<(), BankingError>) {
+        self.records += 1;
+    }
+
+    fn sample(&mut self, bank: &Bank) {
+        if !self.records.is_multiple_of(CHECK_EVERY) || self.last_report.elapsed() < self.interval {
+            return;
+        }
+        if let Err(e) = self.report(bank) {
+            error!("Failed to write statistics. Aborted with error: {}", e);
+        }
+    }
+}
+
+/// The resident memory of this process in kB, where `/proc` provides it.
+fn resident_kb() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))
+        .and_then(|value| value.trim().trim_end_matches("kB").trim().parse().ok())
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testkit::Scenario;
+
+    /// The lines a reporter reporting once an hour writes while processing `scenario`, and then
+    /// once more when asked to.
+    fn report(scenario: Scenario, allocations: Option<fn() -> u64>) -> String {
+        let mut output = Vec::new();
+        let mut reporter = StatsReporter::new(&mut output, Duration::from_secs(3600));
+        if let Some(count) = allocations {
+            reporter = reporter.with_allocations(count);
+        }
+        let mut bank = Bank::new();
+        bank.process_all_with(scenario.transactions().to_vec(), &mut reporter);
+        reporter.report(&bank).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn statistics_count_records() {
+        // TEST
+        let line = report(Scenario::new().deposit(1, 5).deposit(2, 5).withdraw(1, 9), None);
+        assert!(line.starts_with("stats: records=3 rate="), "{}", line);
+    }
+
+    #[test]
+    fn statistics_count_map_sizes() {
+        // SETUP
+        let scenario = Scenario::new().deposit(1, 5).deposit(2, 5).withdraw(1, 9);
+
+        // TEST
+        let line = report(scenario, None);
+        assert!(line.ends_with(" accounts=2 transactions=2 pending=0 audit_entries=0\n"), "{}", line);
+    }
+
+    #[test]
+    fn allocations_are_reported_when_counted() {
+        // TEST
+        let line = report(Scenario::new().deposit(1, 5), Some(|| 42));
+        assert!(line.ends_with(" allocations=42\n"), "{}", line);
+    }
+
+    #[test]
+    fn statistics_are_only_written_once_the_interval_has_passed() {
+        // SETUP
+        let scenario = (0..CHECK_EVERY).fold(Scenario::new(), |scenario, _| scenario.deposit(1, 1));
+
+        // TEST
+        let output = report(scenario, None);
+        assert_eq!(1, output.lines().count(), "{}", output);
+    }
+
+    #[test]
+    fn statistics_are_written_every_check_once_the_interval_has_passed() {
+        // SETUP
+        let mut output = Vec::new();
+        let mut reporter = StatsReporter::new(&mut output, Duration::ZERO);
+        let scenario = (0..2 * CHECK_EVERY).fold(Scenario::new(), |scenario, _| scenario.deposit(1, 1));
+
+        // TEST
+        Bank::new().process_all_with(scenario.transactions().to_vec(), &mut reporter);
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(2, output.lines().count(), "{}", output);
+        assert!(output.lines().nth(1).unwrap().starts_with("stats: records=2048 "), "{}", output);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn resident_memory_is_read_from_proc() {
+        // TEST
+        assert!(resident_kb().unwrap() > 0);
+    }
+}
+//endregion
@@ -0,0 +1,123 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Table output
+//! Renders accounts as a table for reading in a terminal, with `--output-format table`. Unlike the
+//! CSV output, accounts are ordered by client ID and amounts are right-aligned, so the table is
+//! meant for people rather than for other programs.
+use crate::account::Account;
+use crate::bank::Bank;
+use comfy_table::presets::UTF8_FULL;
+use comfy_table::{CellAlignment, ContentArrangement, Table};
+use std::io;
+
+/// Build a table of every account of the bank, ordered by client ID.
+pub fn accounts_table(bank: &Bank) -> Table {
+    let mut accounts: Vec<&Account> = bank.accounts().collect();
+    accounts.sort_by_key(|account| account.client);
+
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_header(vec!["client", "available", "held", "total", "locked"]);
+    for account in accounts {
+        table.add_row(vec![
+            account.client.to_string(),
+            account.available.to_string(),
+            account.held.to_string(),
+            account.total.to_string(),
+            account.locked.to_string(),
+        ]);
+    }
+    for index in 0..4 {
+        if let Some(column) = table.column_mut(index) {
+            column.set_cell_alignment(CellAlignment::Right);
+        }
+    }
+    table
+}
+
+/// Write a table of every account of the bank to the given writer.
+pub fn write_accounts<W: io::Write>(bank: &Bank, mut writer: W) -> io::Result<()> {
+    writeln!(writer, "{}", accounts_table(bank))
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testkit::Scenario;
+
+    /// The table of accounts as written to a terminal.
+    fn written(bank: &Bank) -> io::Result<String> {
+        let mut output = Vec::new();
+        write_accounts(bank, &mut output)?;
+        Ok(String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn table_lists_accounts_in_client_order() -> io::Result<()> {
+        // SETUP
+        let bank = Scenario::new().deposit(2, 3).deposit(1, 5).run();
+
+        // TEST
+        let output = written(&bank)?;
+        let first = output.find("│      1 ┆").expect("client 1 is listed");
+        let second = output.find("│      2 ┆").expect("client 2 is listed");
+        assert!(first < second);
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn table_has_a_header() -> io::Result<()> {
+        // SETUP
+        let bank = Scenario::new().deposit(1, 5).run();
+
+        // TEST
+        let header = written(&bank)?.lines().nth(1).unwrap().to_string();
+        let columns: Vec<&str> = header
+            .split(['│', '┆'])
+            .map(str::trim)
+            .filter(|column| !column.is_empty())
+            .collect();
+        assert_eq!(vec!["client", "available", "held", "total", "locked"], columns);
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn amounts_are_right_aligned() -> io::Result<()> {
+        // SETUP
+        let bank = Scenario::new().deposit(1, 5).deposit(2, 1000).run();
+
+        // TEST
+        let output = written(&bank)?;
+        let available: Vec<&str> = output
+            .lines()
+            .filter(|line| line.contains('┆'))
+            .skip(1)
+            .map(|row| row.split('┆').nth(1).unwrap())
+            .collect();
+        assert_eq!(2, available.len());
+        assert_eq!(available[0].len(), available[1].len());
+        assert!(available[0].ends_with(" 5 "), "{:?}", available);
+        assert!(available[1].ends_with(" 1000 "), "{:?}", available);
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn table_of_a_bank_without_accounts_has_only_a_header() -> io::Result<()> {
+        // TEST
+        let output = written(&Bank::new())?;
+        assert!(output.contains("available"));
+        assert_eq!(4, output.lines().count(), "{}", output);
+
+        // TEARDOWN
+        Ok(())
+    }
+}
+//endregion
@@ -0,0 +1,241 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Test kit
+//! Fixtures for writing tests against the engine: terse `Transaction` constructors and a
+//! `Scenario` builder that numbers transactions automatically. Enabled in downstream crates with
+//! the `testkit` feature, typically as a dev-dependency:
+//!
+//! ```toml
+//! [dev-dependencies]
+//! rust-payment-processor = { version = "0.1", features = ["testkit"] }
+//! ```
+//!
+//! ```
+//! # use rust_payment_processor::amount::Amount;
+//! # use rust_payment_processor::testkit::Scenario;
+//! let bank = Scenario::new().deposit(1, 5).withdraw(1, 2).dispute(1, 1).run();
+//! assert_eq!(Amount::from(3u32), bank.account(1).unwrap().total);
+//! ```
+use crate::account::ClientId;
+use crate::amount::Amount;
+use crate::bank::Bank;
+use crate::errors::BankingError;
+use crate::transaction::{Transaction, TransactionType, TxId};
+
+//region Transaction constructors
+// some utility functions to easily make create Transaction objects without cluttering test bodies
+impl Transaction {
+    pub fn make(kind: TransactionType, client: ClientId, tx: TxId, amount: u32, under_dispute: bool) -> Transaction {
+        Transaction {
+            kind,
+            client,
+            tx,
+            amount: Some(Amount::from(amount)),
+            under_dispute,
+            timestamp: None,
+            currency: None,
+            signature: None,
+            correction: false,
+            provenance: None,
+            expires: None,
+        }
+    }
+
+    pub fn make_negative(kind: TransactionType, client: ClientId, tx: TxId, amount: i32) -> Transaction {
+        Transaction {
+            kind,
+            client,
+            tx,
+            amount: Some(Amount::from(amount)),
+            under_dispute: false,
+            timestamp: None,
+            currency: None,
+            signature: None,
+            correction: false,
+            provenance: None,
+            expires: None,
+        }
+    }
+
+    pub fn make_dispute(client: ClientId, tx: TxId) -> Transaction {
+        Transaction::make_reference(TransactionType::Dispute, client, tx)
+    }
+
+    pub fn make_resolve(client: ClientId, tx: TxId) -> Transaction {
+        Transaction::make_reference(TransactionType::Resolve, client, tx)
+    }
+
+    pub fn make_chargeback(client: ClientId, tx: TxId) -> Transaction {
+        Transaction::make_reference(TransactionType::Chargeback, client, tx)
+    }
+
+    /// A dispute, resolve, or chargeback referring to an earlier transaction.
+    fn make_reference(kind: TransactionType, client: ClientId, tx: TxId) -> Transaction {
+        Transaction {
+            kind,
+            client,
+            tx,
+            amount: None,
+            under_dispute: false,
+            timestamp: None,
+            currency: None,
+            signature: None,
+            correction: false,
+            provenance: None,
+            expires: None,
+        }
+    }
+}
+//endregion
+
+//region Scenario
+/// `Scenario` builds a sequence of transactions. Deposits and withdrawals are given the next
+/// transaction ID, starting at 1, so that later steps can refer to them by position.
+#[derive(Debug)]
+pub struct Scenario {
+    transactions: Vec<Transaction>,
+    next_tx: TxId,
+}
+
+impl Scenario {
+    pub fn new() -> Scenario {
+        Scenario {
+            transactions: Vec::new(),
+            next_tx: 1,
+        }
+    }
+
+    /// The ID the next deposit or withdrawal will be given.
+    pub fn next_tx(&self) -> TxId {
+        self.next_tx
+    }
+
+    pub fn deposit(self, client: ClientId, amount: u32) -> Scenario {
+        self.funds(TransactionType::Deposit, client, amount)
+    }
+
+    pub fn withdraw(self, client: ClientId, amount: u32) -> Scenario {
+        self.funds(TransactionType::Withdrawal, client, amount)
+    }
+
+    pub fn dispute(self, client: ClientId, tx: TxId) -> Scenario {
+        self.then(Transaction::make_dispute(client, tx))
+    }
+
+    pub fn resolve(self, client: ClientId, tx: TxId) -> Scenario {
+        self.then(Transaction::make_resolve(client, tx))
+    }
+
+    pub fn chargeback(self, client: ClientId, tx: TxId) -> Scenario {
+        self.then(Transaction::make_chargeback(client, tx))
+    }
+
+    /// Append an arbitrary transaction, e.g. one with a duplicate ID or a negative amount. The
+    /// automatic numbering is not affected.
+    pub fn then(mut self, transaction: Transaction) -> Scenario {
+        self.transactions.push(transaction);
+        self
+    }
+
+    fn funds(mut self, kind: TransactionType, client: ClientId, amount: u32) -> Scenario {
+        let tx = self.next_tx;
+        self.next_tx += 1;
+        self.then(Transaction::make(kind, client, tx, amount, false))
+    }
+
+    /// The transactions of this scenario, in order.
+    pub fn transactions(&self) -> &[Transaction] {
+        &self.transactions
+    }
+
+    /// Process every transaction on a new bank, returning the bank and the result of each
+    /// transaction in order.
+    pub fn run_with_results(self) -> (Bank, Vec<Result<(), BankingError>>) {
+        let mut bank = Bank::new();
+        let results = self
+            .transactions
+            .into_iter()
+            .map(|transaction| bank.process_transaction(transaction))
+            .collect();
+        (bank, results)
+    }
+
+    /// Process every transaction on a new bank, ignoring failed transactions as the command-line
+    /// tool does.
+    pub fn run(self) -> Bank {
+        self.run_with_results().0
+    }
+}
+
+impl Default for Scenario {
+    fn default() -> Scenario {
+        Scenario::new()
+    }
+}
+//endregion
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deposits_and_withdrawals_are_numbered_from_one() {
+        // SETUP
+        let scenario = Scenario::new().deposit(1, 5).withdraw(2, 1).deposit(1, 3);
+
+        // TEST
+        let ids: Vec<TxId> = scenario.transactions().iter().map(|transaction| transaction.tx).collect();
+        assert_eq!(vec![1, 2, 3], ids);
+        assert_eq!(4, scenario.next_tx());
+    }
+
+    #[test]
+    fn references_and_arbitrary_transactions_are_not_numbered() {
+        // SETUP
+        let scenario = Scenario::new()
+            .deposit(1, 5)
+            .dispute(1, 1)
+            .resolve(1, 1)
+            .chargeback(1, 1)
+            .then(Transaction::make(TransactionType::Deposit, 1, 9, 1, false));
+
+        // TEST
+        assert_eq!(2, scenario.next_tx());
+        assert_eq!(9, scenario.transactions()[4].tx);
+        let reference = &scenario.transactions()[1];
+        assert_eq!(
+            (TransactionType::Dispute, 1, None),
+            (reference.kind.clone(), reference.tx, reference.amount)
+        );
+    }
+
+    #[test]
+    fn every_result_is_reported_in_order() {
+        // SETUP
+        let scenario = Scenario::new().deposit(1, 5).withdraw(1, 7).dispute(1, 1).chargeback(1, 1);
+
+        // TEST
+        let (_, results) = scenario.run_with_results();
+        assert_eq!(vec![Ok(()), Err(BankingError::InsufficientFunds), Ok(()), Ok(())], results);
+    }
+
+    #[test]
+    fn failed_transactions_are_ignored_by_run() {
+        // SETUP
+        let scenario = Scenario::new().deposit(1, 5).withdraw(1, 7).dispute(1, 1).chargeback(1, 1);
+
+        // TEST
+        let bank = scenario.run();
+        let account = bank.account(1).unwrap();
+        assert!(account.locked);
+        assert_eq!(Amount::from(0u32), account.total);
+    }
+
+    #[test]
+    fn negative_amounts_are_kept() {
+        // TEST
+        let transaction = Transaction::make_negative(TransactionType::Deposit, 1, 1, -5);
+        assert_eq!(Some(Amount::from(-5)), transaction.amount);
+    }
+}
+//endregion
@@ -1,17 +1,23 @@
 #![forbid(unsafe_code)] // for good measure
-use crate::errors::BankingError;
+use crate::account::ClientId;
+use crate::amount::{parse_amount, Amount, AmountFormat};
+use crate::config::DEFAULT_DECIMAL_PLACES;
+use crate::errors::{BankingError, RecordError};
+use crate::locale::Locale;
+use crate::provenance::Provenance;
+#[cfg(feature = "time")]
+use chrono::{DateTime, FixedOffset, NaiveDateTime, TimeZone, Utc};
 use rust_decimal::prelude::*;
-use rust_decimal_macros::dec;
-use serde::{Deserialize, Serialize};
-
-const DECIMAL_PLACES: u32 = 4;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
 
 /// `TransactionType` enumerates the supported transaction types of this crate
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum TransactionType {
-    #[serde(rename = "deposit")]
     Deposit,
-    #[serde(rename = "withdrawal")]
     Withdrawal,
     /// represents a client’s claim that a transaction was erroneous and should be reversed.
     /// The transaction shouldn’t be reversed yet but the associated funds should be held.
@@ -20,7 +26,6 @@ pub enum TransactionType {
     /// 1. the clients' available funds should decrease by the amount disputed
     /// 2. the clients' held funds should increase by the amount disputed
     /// 3. the clients' total funds should remain the same
-    #[serde(rename = "dispute")]
     Dispute,
     ///  represents a resolution to a dispute, releasing the associated held funds.
     ///     
@@ -28,7 +33,6 @@ pub enum TransactionType {
     /// 1. the clients' held funds should decrease by the amount no longer disputed
     /// 2. the clients' available funds should increase by the amount no longer disputed
     /// 3. the clients' total funds should remain the same
-    #[serde(rename = "resolve")]
     Resolve,
     /// A chargeback is the final state of a dispute and represents the client reversing a
     /// transaction.Funds that were held have now been withdrawn
@@ -36,22 +40,488 @@ pub enum TransactionType {
     /// This means that:
     /// 1. the clients' held funds and total funds should decrease by the amount previously disputed
     /// 2. the client’s account should be immediately frozen.
-    #[serde(rename = "chargeback")]
     Chargeback,
+    /// Corrects the amount of an earlier deposit or withdrawal of the client, referenced by `tx`,
+    /// replacing it with the adjustment's amount.
+    ///
+    /// This means that:
+    /// 1. the clients' available and total funds change by the difference between the amounts
+    /// 2. the referenced transaction keeps its ID, and is disputed at its new amount
+    Adjustment,
+    /// A promotional credit, such as a fee rebate, which the client may spend until it expires, as
+    /// given by its `expires` column. Promotional credits are tracked apart from deposits.
+    ///
+    /// This means that:
+    /// 1. the clients' available and total funds increase by the amount credited
+    /// 2. whatever the client has not spent of it is clawed back once it expires
+    /// 3. the credit can not be disputed
+    PromoCredit,
+    /// A type defined outside this crate, applied by the `TransactionHandler` a bank was built with
+    /// for it. Any name other than those of the built-in types is read as a custom type; the bank
+    /// resolves it against its `Handlers`, and rejects it if it has no handler for it. See `handler`.
+    Custom(Arc<str>),
+}
+
+impl TransactionType {
+    /// The built-in transaction types.
+    pub const BUILT_IN: [TransactionType; 7] = [
+        TransactionType::Deposit,
+        TransactionType::Withdrawal,
+        TransactionType::Dispute,
+        TransactionType::Resolve,
+        TransactionType::Chargeback,
+        TransactionType::Adjustment,
+        TransactionType::PromoCredit,
+    ];
+
+    /// The custom transaction type of the given name. The name of a built-in type is not a custom
+    /// type.
+    pub fn custom(name: &str) -> Result<TransactionType, String> {
+        if TransactionType::BUILT_IN.iter().any(|kind| kind.name() == name) {
+            return Err(format!("`{}` is a built-in transaction type", name));
+        }
+        if name.is_empty() {
+            return Err(String::from("transaction type names can not be empty"));
+        }
+        Ok(TransactionType::Custom(Arc::from(name)))
+    }
+
+    /// The name of the type, as it appears in input.
+    pub fn name(&self) -> &str {
+        match self {
+            TransactionType::Deposit => "deposit",
+            TransactionType::Withdrawal => "withdrawal",
+            TransactionType::Dispute => "dispute",
+            TransactionType::Resolve => "resolve",
+            TransactionType::Chargeback => "chargeback",
+            TransactionType::Adjustment => "adjustment",
+            TransactionType::PromoCredit => "promo_credit",
+            TransactionType::Custom(name) => name,
+        }
+    }
+}
+
+impl FromStr for TransactionType {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<TransactionType, String> {
+        match value {
+            "deposit" => Ok(TransactionType::Deposit),
+            "withdrawal" => Ok(TransactionType::Withdrawal),
+            "dispute" => Ok(TransactionType::Dispute),
+            "resolve" => Ok(TransactionType::Resolve),
+            "chargeback" => Ok(TransactionType::Chargeback),
+            "adjustment" => Ok(TransactionType::Adjustment),
+            "promo_credit" => Ok(TransactionType::PromoCredit),
+            _ => Err(format!("unknown transaction type: {}", value)),
+        }
+    }
 }
 
-/// `Transaction` provides a structured representation of each transaction record. It derives
-/// deserialize so that we may create Transaction structs easily by reading serialized data from a
-/// CSV file  
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+impl fmt::Display for TransactionType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl Serialize for TransactionType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for TransactionType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<TransactionType, D::Error> {
+        struct TypeVisitor;
+
+        impl de::Visitor<'_> for TypeVisitor {
+            type Value = TransactionType;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a transaction type")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<TransactionType, E> {
+                TransactionType::from_str(value)
+                    .or_else(|_| TransactionType::custom(value))
+                    .map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(TypeVisitor)
+    }
+}
+
+/// `TxId` is the type of transaction IDs. Upstream systems issue 64 bit IDs.
+pub type TxId = u64;
+
+/// `Timestamp` is a point in time, such as when a transaction took place.
+#[cfg(feature = "time")]
+pub type Timestamp = DateTime<Utc>;
+
+/// `Timestamp` is a point in time, such as when a transaction took place. Without the `time`
+/// feature there are none: records with a timestamp are rejected, so this type has no values.
+#[cfg(not(feature = "time"))]
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Timestamp {}
+
+#[cfg(not(feature = "time"))]
+impl fmt::Display for Timestamp {
+    fn fmt(&self, _: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {}
+    }
+}
+
+/// `Transaction` provides a structured representation of each transaction record. Deserialization
+/// goes through `TransactionRecord` so that a record whose fields are inconsistent with its type is
+/// rejected at parse time, with an error naming the offending field. A transaction deserialized
+/// directly is read with the default `RecordOptions`; readers of a source with other conventions
+/// deserialize the `TransactionRecord` and map it with `RecordOptions::transaction`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
 pub struct Transaction {
     #[serde(rename = "type")]
     pub kind: TransactionType,
-    pub client: u16,
-    pub tx: u32,
-    pub amount: Option<Decimal>,
-    #[serde(default)]
+    pub client: ClientId,
+    pub tx: TxId,
+    pub amount: Option<Amount>,
     pub under_dispute: bool,
+    /// When the transaction took place. Only present in v2 input.
+    pub timestamp: Option<Timestamp>,
+    /// ISO 4217 currency code of the amount. Only present in v2 input.
+    pub currency: Option<String>,
+    /// Hex-encoded HMAC of the record, from the optional `signature` column. See `signature`.
+    #[serde(skip_serializing)]
+    pub signature: Option<String>,
+    /// Whether the record amends an earlier one with the same ID, from the optional `correction`
+    /// column. See `DuplicatePolicy::LastWriteWins`.
+    #[serde(skip_serializing)]
+    pub correction: bool,
+    /// Where the record was read from, if known. See `provenance`.
+    #[serde(skip)]
+    pub provenance: Option<Provenance>,
+    /// When a promotional credit expires, from the optional `expires` column. See `PromoCredit`.
+    #[serde(skip_serializing)]
+    pub expires: Option<Timestamp>,
+}
+
+//region NegativeDeposits
+/// `NegativeDeposits` decides how a deposit with a negative amount is read. Some partners encode
+/// returns, such as ACH returns, this way.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum NegativeDeposits {
+    /// The record is rejected as a deposit without a positive amount. This is the default.
+    Reject,
+    /// The record is read as a withdrawal of the amount's absolute value, under the same ID.
+    Withdrawal,
+}
+
+impl NegativeDeposits {
+    /// Map the type and amount of a record according to this policy. Only negative deposits are
+    /// changed.
+    pub fn map(self, kind: TransactionType, amount: Option<Amount>) -> (TransactionType, Option<Amount>) {
+        match (self, &kind, amount) {
+            (NegativeDeposits::Withdrawal, TransactionType::Deposit, Some(amount)) if amount < Amount::zero() => {
+                (TransactionType::Withdrawal, Some(-amount))
+            }
+            _ => (kind, amount),
+        }
+    }
+}
+
+impl FromStr for NegativeDeposits {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<NegativeDeposits, String> {
+        match value {
+            "reject" => Ok(NegativeDeposits::Reject),
+            "withdrawal" => Ok(NegativeDeposits::Withdrawal),
+            _ => Err(format!("unsupported negative deposit policy: {} (expected reject or withdrawal)", value)),
+        }
+    }
+}
+
+//endregion
+
+//region AmountColumns
+/// `AmountColumns` is how a source writes whether a record moves funds into or out of an account.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum AmountColumns {
+    /// The `type` column names the transaction, and amounts are positive. This is the default.
+    Typed,
+    /// The `amount` is signed: a record without a type is a deposit if its amount is positive, or
+    /// a withdrawal of the amount's absolute value if it is negative. A deposit or withdrawal of a
+    /// negative amount is read as the opposite transaction of the absolute value.
+    Signed,
+    /// Amounts are written in a `credit` column for deposits and a `debit` column for withdrawals,
+    /// as in general ledger exports. A zero in the other column is ignored. The `type` column
+    /// is only needed for disputes, resolves, and chargebacks.
+    DebitCredit,
+}
+
+impl AmountColumns {
+    /// Whether input written this way may leave out the column.
+    pub fn is_optional(self, column: &str) -> bool {
+        match self {
+            AmountColumns::Typed => false,
+            AmountColumns::Signed => column == "type",
+            AmountColumns::DebitCredit => column == "type" || column == "amount",
+        }
+    }
+
+    /// Map the type and amount columns of a record onto its type and positive amount.
+    fn map(self, record: Fields) -> Result<(TransactionType, Option<Amount>), RecordError> {
+        let funds = matches!(record.kind, None | Some(TransactionType::Deposit) | Some(TransactionType::Withdrawal));
+        match self {
+            AmountColumns::Signed if funds => match record.amount {
+                Some(amount) if amount < Amount::zero() => {
+                    let kind = match record.kind {
+                        Some(TransactionType::Withdrawal) => TransactionType::Deposit,
+                        _ => TransactionType::Withdrawal,
+                    };
+                    Ok((kind, Some(-amount)))
+                }
+                amount => Ok((record.kind.unwrap_or(TransactionType::Deposit), amount)),
+            },
+            AmountColumns::DebitCredit if funds => {
+                let nonzero = |amount: Option<Amount>| amount.filter(|amount| !amount.is_zero());
+                match (nonzero(record.debit), nonzero(record.credit)) {
+                    (Some(_), Some(_)) => Err(RecordError::DebitAndCredit),
+                    (Some(debit), None) => Ok((TransactionType::Withdrawal, Some(debit))),
+                    (None, Some(credit)) => Ok((TransactionType::Deposit, Some(credit))),
+                    (None, None) => Ok((record.kind.ok_or(RecordError::MissingType)?, record.amount)),
+                }
+            }
+            _ => Ok((record.kind.ok_or(RecordError::MissingType)?, record.amount)),
+        }
+    }
+}
+
+//endregion
+
+//region RecordOptions
+/// `RecordOptions` are the conventions of the source input records are read from, applied as each
+/// is mapped onto a `Transaction`. Each reader is given its own, from the command line or an input
+/// profile (see `profile`).
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordOptions {
+    pub negative_deposits: NegativeDeposits,
+    pub amounts: AmountColumns,
+    /// What an acceptable amount looks like, e.g. with a decimal comma.
+    pub amount_format: AmountFormat,
+    /// The currency of records that carry none.
+    pub currency: Option<String>,
+    /// The offset of timestamps written without one, e.g. `2021-06-01 09:30:00`. Without it, such
+    /// timestamps are rejected.
+    #[cfg(feature = "time")]
+    pub utc_offset: Option<FixedOffset>,
+    /// The language records that can not be read are reported in.
+    pub locale: Locale,
+}
+
+impl RecordOptions {
+    pub const fn new() -> RecordOptions {
+        RecordOptions {
+            negative_deposits: NegativeDeposits::Reject,
+            amounts: AmountColumns::Typed,
+            amount_format: AmountFormat::new(),
+            currency: None,
+            #[cfg(feature = "time")]
+            utc_offset: None,
+            locale: Locale::En,
+        }
+    }
+}
+
+impl Default for RecordOptions {
+    fn default() -> RecordOptions {
+        RecordOptions::new()
+    }
+}
+
+/// Parse a timestamp, reading one without an offset in `utc_offset`.
+#[cfg(feature = "time")]
+pub fn parse_timestamp(text: &str, utc_offset: Option<FixedOffset>) -> Result<Timestamp, String> {
+    if let Ok(timestamp) = text.parse::<DateTime<Utc>>() {
+        return Ok(timestamp);
+    }
+    let offset = utc_offset.ok_or_else(|| format!("invalid timestamp \"{}\": no UTC offset", text))?;
+    ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+        .and_then(|local| offset.from_local_datetime(&local).single())
+        .map(|timestamp| timestamp.with_timezone(&Utc))
+        .ok_or_else(|| format!("invalid timestamp \"{}\"", text))
+}
+
+/// `RawField` is the text of a field that is parsed according to the `RecordOptions` of its source,
+/// such as an amount or a timestamp. It is borrowed from the record where the input allows.
+/// Self-describing binary formats may carry a number instead, which is read as its text.
+struct RawField<'a>(Cow<'a, str>);
+
+impl<'de: 'a, 'a> Deserialize<'de> for RawField<'a> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<RawField<'a>, D::Error> {
+        deserializer.deserialize_str(RawFieldVisitor)
+    }
+}
+
+struct RawFieldVisitor;
+
+impl<'de> de::Visitor<'de> for RawFieldVisitor {
+    type Value = RawField<'de>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a string or number")
+    }
+
+    fn visit_borrowed_str<E: de::Error>(self, text: &'de str) -> Result<RawField<'de>, E> {
+        Ok(RawField(Cow::Borrowed(text)))
+    }
+
+    fn visit_str<E: de::Error>(self, text: &str) -> Result<RawField<'de>, E> {
+        Ok(RawField(Cow::Owned(text.to_string())))
+    }
+
+    fn visit_string<E: de::Error>(self, text: String) -> Result<RawField<'de>, E> {
+        Ok(RawField(Cow::Owned(text)))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<RawField<'de>, E> {
+        Ok(RawField(Cow::Owned(value.to_string())))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<RawField<'de>, E> {
+        Ok(RawField(Cow::Owned(value.to_string())))
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<RawField<'de>, E> {
+        Ok(RawField(Cow::Owned(value.to_string())))
+    }
+}
+
+impl RawField<'_> {
+    /// The text of the field, or `None` if it is blank.
+    fn text(&self) -> Option<&str> {
+        Some(self.0.trim()).filter(|text| !text.is_empty())
+    }
+}
+//endregion
+
+/// `TransactionRecord` is the raw, unvalidated shape of a transaction as it appears in the input.
+/// Fields are declared in column order (see `Schema::columns`) so that headerless input can be
+/// deserialized positionally. The v2 columns are defaulted so that v1 input maps onto it as well.
+/// Amounts and timestamps are kept as text until `RecordOptions::transaction` parses them.
+#[derive(Deserialize)]
+pub struct TransactionRecord<'a> {
+    #[serde(rename = "type", default)]
+    kind: Option<TransactionType>,
+    client: ClientId,
+    tx: TxId,
+    #[serde(default, borrow)]
+    amount: Option<RawField<'a>>,
+    #[serde(default, borrow)]
+    timestamp: Option<RawField<'a>>,
+    #[serde(default)]
+    currency: Option<String>,
+    #[serde(default)]
+    signature: Option<String>,
+    #[serde(default)]
+    correction: Option<bool>,
+    #[serde(default, borrow)]
+    debit: Option<RawField<'a>>,
+    #[serde(default, borrow)]
+    credit: Option<RawField<'a>>,
+    #[serde(default, borrow)]
+    expires: Option<RawField<'a>>,
+}
+
+impl<'a> TransactionRecord<'a> {
+    /// A record of the given fields, as if read from input with only the v1 columns.
+    pub fn new(kind: TransactionType, client: ClientId, tx: TxId, amount: Option<&'a str>) -> TransactionRecord<'a> {
+        TransactionRecord {
+            kind: Some(kind),
+            client,
+            tx,
+            amount: amount.map(|amount| RawField(Cow::Borrowed(amount))),
+            timestamp: None,
+            currency: None,
+            signature: None,
+            correction: None,
+            debit: None,
+            credit: None,
+            expires: None,
+        }
+    }
+}
+
+/// The fields of a `TransactionRecord` once parsed, for `AmountColumns::map`.
+struct Fields {
+    kind: Option<TransactionType>,
+    amount: Option<Amount>,
+    debit: Option<Amount>,
+    credit: Option<Amount>,
+}
+
+impl RecordOptions {
+    /// Map a record read from this source onto a `Transaction`, parsing its amounts and timestamps
+    /// and applying the source's conventions.
+    pub fn transaction(&self, record: TransactionRecord<'_>) -> Result<Transaction, RecordError> {
+        let fields = Fields {
+            kind: record.kind,
+            amount: self.amount(&record.amount)?,
+            debit: self.amount(&record.debit)?,
+            credit: self.amount(&record.credit)?,
+        };
+        let (kind, amount) = self.amounts.map(fields)?;
+        let (kind, amount) = self.negative_deposits.map(kind, amount);
+        let transaction = Transaction {
+            kind,
+            client: record.client,
+            tx: record.tx,
+            amount,
+            under_dispute: false,
+            timestamp: self.timestamp(&record.timestamp)?,
+            currency: record.currency.or_else(|| self.currency.clone()),
+            signature: record.signature,
+            correction: record.correction.unwrap_or(false),
+            provenance: None,
+            expires: self.timestamp(&record.expires)?,
+        };
+        transaction.check_fields()?;
+        Ok(transaction)
+    }
+
+    fn amount(&self, field: &Option<RawField<'_>>) -> Result<Option<Amount>, RecordError> {
+        match field.as_ref().and_then(RawField::text) {
+            Some(text) => parse_amount(text, &self.amount_format)
+                .map(Some)
+                .map_err(|e| RecordError::InvalidAmount(text.to_string(), e)),
+            None => Ok(None),
+        }
+    }
+
+    fn timestamp(&self, field: &Option<RawField<'_>>) -> Result<Option<Timestamp>, RecordError> {
+        match field.as_ref().and_then(RawField::text) {
+            #[cfg(feature = "time")]
+            Some(text) => parse_timestamp(text, self.utc_offset)
+                .map(Some)
+                .map_err(RecordError::InvalidTimestamp),
+            #[cfg(not(feature = "time"))]
+            Some(text) => Err(RecordError::InvalidTimestamp(format!(
+                "timestamp \"{}\" can not be read without the `time` feature",
+                text
+            ))),
+            None => Ok(None),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Transaction {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Transaction, D::Error> {
+        let record = TransactionRecord::deserialize(deserializer)?;
+        RecordOptions::new().transaction(record).map_err(de::Error::custom)
+    }
 }
 
 impl Transaction {
@@ -62,34 +532,61 @@ impl Transaction {
         }
     }
 
-    /// Determines if a transaction is valid. A valid transaction must be for an amount greater
-    /// than 0 for deposits and withdrawals. In time, I would probably favor implementing a custom
-    /// deserializer to take responsibility of this functionality, but for now, this is fine.
-    pub fn validate(&mut self) -> Result<(), BankingError> {
+    /// Checks that the fields present are consistent with the transaction type:
+    /// 1. deposits, withdrawals, and adjustments must carry an amount greater than 0
+    /// 2. disputes, resolves, and chargebacks must not carry an amount
+    ///
+    /// Custom types are not checked.
+    pub fn check_fields(&self) -> Result<(), RecordError> {
         match self.kind {
-            TransactionType::Deposit | TransactionType::Withdrawal => {
-                if let Some(amount) = self.amount {
-                    if amount <= dec![0] {
-                        return Err(BankingError::InvalidTransaction);
-                    }
-                } else {
-                    return Err(BankingError::InvalidTransaction);
+            TransactionType::Deposit | TransactionType::Withdrawal | TransactionType::Adjustment | TransactionType::PromoCredit => {
+                match self.amount {
+                    None => Err(RecordError::MissingAmount),
+                    Some(amount) if amount <= Amount::zero() => Err(RecordError::NonPositiveAmount),
+                    Some(_) => Ok(()),
                 }
             }
-            _ => {}
+            TransactionType::Dispute | TransactionType::Resolve | TransactionType::Chargeback => match self.amount {
+                Some(_) => Err(RecordError::AmountNotAllowed),
+                None => Ok(()),
+            },
+            // left to the type's handler
+            TransactionType::Custom(_) => Ok(()),
         }
+    }
+
+    /// Determines if a transaction is valid and rounds its amount. Transactions read from input
+    /// have already passed `check_fields` during deserialization; this guards those constructed
+    /// programmatically.
+    pub fn validate(&mut self) -> Result<(), BankingError> {
+        self.validate_with_precision(DEFAULT_DECIMAL_PLACES, false)
+    }
 
-        self.round_to(DECIMAL_PLACES);
+    /// As `validate`, rounding to `decimal_places` instead. If `strict` is set, an amount that
+    /// would need rounding is rejected instead.
+    pub fn validate_with_precision(&mut self, decimal_places: u32, strict: bool) -> Result<(), BankingError> {
+        self.check_fields().map_err(|_| BankingError::InvalidTransaction)?;
+        if strict && self.amount.is_some_and(|amount| amount.round_dp(decimal_places) != amount) {
+            return Err(BankingError::InvalidTransaction);
+        }
+        self.round_to(decimal_places);
         Ok(())
     }
 
     /// Disputes, resolves, and chargebacks all reference a previous transaction. This function
-    /// validates that the incoming dispute, resolve, or chargeback is valid.
+    /// validates that the incoming dispute, resolve, or chargeback is valid against the stored
+    /// transaction it references, which `Bank::referenced_transaction` looks up for the dispute,
+    /// resolve, and chargeback handlers, and for any custom handler registered in `Handlers`.
     /// In order to be valid:
-    /// 1. the referenced transaction type must be `TransactionType::Deposit`
+    /// 1. the referenced transaction type must be `TransactionType::Deposit`. Stored withdrawals,
+    ///    adjustments, and custom types can not be disputed and are rejected with
+    ///    `InvalidTransaction`; promotional credits are never stored as transactions, and are
+    ///    rejected with `PromotionalCredit` by `Bank::referenced_transaction` before this is called
     /// 2. the referenced transaction client must match that of the current transaction
     /// 3. a resolve or chargeback can only occur if the transaction is under dispute
     /// 4. a dispute should not be processed if that transaction is already under dispute
+    ///
+    /// Other transaction types are not checked.
     pub fn validate_against_stored(&mut self, stored_transaction: &mut Transaction) -> Result<(), BankingError> {
         match self.kind {
             TransactionType::Dispute => {
@@ -119,3 +616,107 @@ impl Transaction {
         Ok(())
     }
 }
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "io")]
+    use crate::amount;
+    #[cfg(feature = "io")]
+    use crate::errors::AmountParseError;
+
+    #[test]
+    fn negative_deposits_are_read_as_withdrawals_when_configured() {
+        // SETUP
+        let refund = Some(-Amount::from(5u32));
+
+        // TEST
+        let mapped = NegativeDeposits::Withdrawal.map(TransactionType::Deposit, refund);
+        assert_eq!((TransactionType::Withdrawal, Some(Amount::from(5u32))), mapped);
+        assert_eq!((TransactionType::Deposit, refund), NegativeDeposits::Reject.map(TransactionType::Deposit, refund));
+        let withdrawal = NegativeDeposits::Withdrawal.map(TransactionType::Withdrawal, refund);
+        assert_eq!((TransactionType::Withdrawal, refund), withdrawal);
+        assert_eq!(Ok(NegativeDeposits::Withdrawal), "withdrawal".parse());
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn amounts_are_parsed_with_the_format_of_their_source() -> Result<(), csv::Error> {
+        // SETUP
+        let european = RecordOptions {
+            amount_format: AmountFormat {
+                max_decimal_places: Some(2),
+                decimal_comma: true,
+            },
+            ..RecordOptions::new()
+        };
+        let read = |input: &str, options: &RecordOptions| -> Result<Vec<Result<Transaction, RecordError>>, csv::Error> {
+            let mut reader = csv::ReaderBuilder::new().delimiter(b';').from_reader(input.as_bytes());
+            let headers = reader.headers()?.clone();
+            let mut transactions = Vec::new();
+            for record in reader.records() {
+                let record = record?;
+                transactions.push(options.transaction(record.deserialize(Some(&headers))?));
+            }
+            Ok(transactions)
+        };
+        let input = "type;client;tx;amount\ndeposit;1;1;2,5\ndeposit;1;2;2,505\n";
+
+        // TEST
+        let transactions = read(input, &european)?;
+        let amount = transactions[0].as_ref().ok().and_then(|transaction| transaction.amount);
+        assert_eq!(amount::from_decimal(Decimal::new(25, 1)), amount);
+        let precise = RecordError::InvalidAmount(String::from("2,505"), AmountParseError::TooManyDecimalPlaces(2));
+        assert_eq!(Err(precise), transactions[1]);
+        let default = read(input, &RecordOptions::default())?;
+        let separator = RecordError::InvalidAmount(String::from("2,5"), AmountParseError::ThousandsSeparator);
+        assert_eq!(Err(separator), default[0]);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn signed_and_debit_credit_amounts_are_read_as_deposits_and_withdrawals() -> Result<(), csv::Error> {
+        // SETUP
+        type Mapped = Result<(TransactionType, Option<Amount>), RecordError>;
+        let read = |input: &str, columns: AmountColumns| -> Result<Vec<Mapped>, csv::Error> {
+            let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(input.as_bytes());
+            let headers = reader.headers()?.clone();
+            let options = RecordOptions {
+                amounts: columns,
+                ..RecordOptions::new()
+            };
+            let mut mapped = Vec::new();
+            for record in reader.records() {
+                let record = record?;
+                let record: TransactionRecord = record.deserialize(Some(&headers))?;
+                let transaction = options.transaction(record);
+                mapped.push(transaction.map(|transaction| (transaction.kind, transaction.amount)));
+            }
+            Ok(mapped)
+        };
+        let amount = |amount: u32| Some(Amount::from(amount));
+
+        // TEST
+        let signed = read("client,tx,amount\n1,1,5\n1,2,-2\n", AmountColumns::Signed)?;
+        assert_eq!(vec![Ok((TransactionType::Deposit, amount(5))), Ok((TransactionType::Withdrawal, amount(2)))], signed);
+        let typed = read("type,client,tx,amount\nwithdrawal,1,3,-1\ndispute,1,1,\n", AmountColumns::Signed)?;
+        assert_eq!(vec![Ok((TransactionType::Deposit, amount(1))), Ok((TransactionType::Dispute, None))], typed);
+        let ledger = read(
+            "type,client,tx,debit,credit\n,1,1,0.00,5\n,1,2,2,\n,1,3,1,1\ndispute,1,1,,\n,1,4,,\n",
+            AmountColumns::DebitCredit,
+        )?;
+        let expected = vec![
+            Ok((TransactionType::Deposit, amount(5))),
+            Ok((TransactionType::Withdrawal, amount(2))),
+            Err(RecordError::DebitAndCredit),
+            Ok((TransactionType::Dispute, None)),
+            Err(RecordError::MissingType),
+        ];
+        assert_eq!(expected, ledger);
+        assert!(AmountColumns::DebitCredit.is_optional("amount") && !AmountColumns::Signed.is_optional("amount"));
+        Ok(())
+    }
+}
+//endregion
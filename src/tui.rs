@@ -0,0 +1,297 @@
+#![forbid(unsafe_code)] // for good measure
+//! # Terminal dashboard
+//! A live view of a run for `--tui`: throughput, the accounts with the largest balances, and the
+//! most recent errors, redrawn at most every `REDRAW_INTERVAL` while a large file processes. The
+//! dashboard is a `ResultSink`, so it sees exactly what the bank reports and nothing more.
+use crate::account::{Account, ClientId};
+use crate::errors::BankingError;
+use crate::sink::ResultSink;
+use crate::transaction::Transaction;
+use ratatui::backend::Backend;
+use ratatui::crossterm::event::{self, Event, KeyCode};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::widgets::{Block, List, Paragraph, Row, Table};
+use ratatui::{Frame, Terminal};
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::time::{Duration, Instant};
+
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+/// The number of accounts listed.
+const TOP_ACCOUNTS: usize = 10;
+/// The number of errors listed.
+const RECENT_ERRORS: usize = 8;
+
+/// `DashboardState` holds what the dashboard shows, gathered from the results of processing.
+pub struct DashboardState {
+    started: Instant,
+    processed: usize,
+    rejected: usize,
+    /// The latest state of every account seen to change.
+    accounts: HashMap<ClientId, Account>,
+    recent_errors: VecDeque<String>,
+    finished: bool,
+}
+
+impl DashboardState {
+    pub fn new() -> DashboardState {
+        DashboardState {
+            started: Instant::now(),
+            processed: 0,
+            rejected: 0,
+            accounts: HashMap::new(),
+            recent_errors: VecDeque::with_capacity(RECENT_ERRORS),
+            finished: false,
+        }
+    }
+
+    /// Transactions processed per second since the dashboard was created.
+    fn throughput(&self) -> f64 {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        if elapsed > 0.0 {
+            self.processed as f64 / elapsed
+        } else {
+            0.0
+        }
+    }
+
+    /// The accounts with the largest total balance, largest first.
+    fn top_accounts(&self) -> Vec<&Account> {
+        let mut accounts: Vec<&Account> = self.accounts.values().collect();
+        accounts.sort_by(|a, b| b.total.cmp(&a.total).then(a.client.cmp(&b.client)));
+        accounts.truncate(TOP_ACCOUNTS);
+        accounts
+    }
+
+    /// Draw the dashboard onto the whole frame.
+    pub fn render(&self, frame: &mut Frame) {
+        let [stats, accounts, errors] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(TOP_ACCOUNTS as u16 / 2 + 3),
+            Constraint::Length(RECENT_ERRORS as u16 + 2),
+        ])
+        .areas(frame.area());
+
+        let status = if self.finished { "done, press q to exit" } else { "processing" };
+        let summary = format!(
+            "{} processed | {} rejected | {:.0} transactions/s | {:.1}s elapsed | {}",
+            self.processed,
+            self.rejected,
+            self.throughput(),
+            self.started.elapsed().as_secs_f64(),
+            status
+        );
+        frame.render_widget(Paragraph::new(summary).block(Block::bordered().title("Throughput")), stats);
+
+        let rows = self.top_accounts().into_iter().map(|account| {
+            Row::new(vec![
+                account.client.to_string(),
+                account.available.to_string(),
+                account.held.to_string(),
+                account.total.to_string(),
+                account.locked.to_string(),
+            ])
+        });
+        let widths = [
+            Constraint::Length(8),
+            Constraint::Fill(1),
+            Constraint::Fill(1),
+            Constraint::Fill(1),
+            Constraint::Length(6),
+        ];
+        let table = Table::new(rows, widths)
+            .header(Row::new(vec!["client", "available", "held", "total", "locked"]))
+            .block(Block::bordered().title("Top accounts by balance"));
+        frame.render_widget(table, accounts);
+
+        let list = List::new(self.recent_errors.iter().map(String::as_str)).block(Block::bordered().title("Recent errors"));
+        frame.render_widget(list, errors);
+    }
+}
+
+impl Default for DashboardState {
+    fn default() -> DashboardState {
+        DashboardState::new()
+    }
+}
+
+/// `Dashboard` draws a `DashboardState` onto a terminal as results come in.
+pub struct Dashboard<B: Backend> {
+    terminal: Terminal<B>,
+    state: DashboardState,
+    last_draw: Option<Instant>,
+}
+
+impl<B: Backend> Dashboard<B> {
+    pub fn new(terminal: Terminal<B>) -> Dashboard<B> {
+        Dashboard {
+            terminal,
+            state: DashboardState::new(),
+            last_draw: None,
+        }
+    }
+
+    pub fn state(&self) -> &DashboardState {
+        &self.state
+    }
+
+    /// Redraw the dashboard now.
+    pub fn draw(&mut self) -> io::Result<()> {
+        let state = &self.state;
+        self.terminal.draw(|frame| state.render(frame))?;
+        self.last_draw = Some(Instant::now());
+        Ok(())
+    }
+
+    /// Mark the run as finished and draw the final state.
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.state.finished = true;
+        self.draw()
+    }
+
+    pub fn terminal(&self) -> &Terminal<B> {
+        &self.terminal
+    }
+}
+
+impl<B: Backend> ResultSink for Dashboard<B> {
+    fn record(&mut self, _index: usize, transaction: &Transaction, result: Result<(), BankingError>) {
+        self.state.processed += 1;
+        if let Err(e) = result {
+            self.state.rejected += 1;
+            if self.state.recent_errors.len() == RECENT_ERRORS {
+                self.state.recent_errors.pop_back();
+            }
+            self.state.recent_errors.push_front(format!(
+                "{} {} of client {}: {:?}",
+                transaction.kind, transaction.tx, transaction.client, e
+            ));
+        }
+        if self.last_draw.is_none_or(|last| last.elapsed() >= REDRAW_INTERVAL) {
+            if let Err(e) = self.draw() {
+                error!("Failed to draw dashboard. Aborted with error: {}", e);
+            }
+        }
+    }
+
+    fn account_updated(&mut self, account: &Account) {
+        self.state.accounts.insert(account.client, account.clone());
+    }
+}
+
+/// Block until `q` or `Esc` is pressed.
+pub fn wait_for_exit() -> io::Result<()> {
+    loop {
+        if let Event::Key(key) = event::read()? {
+            if matches!(key.code, KeyCode::Char('q') | KeyCode::Esc) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bank::Bank;
+    use crate::transaction::{TransactionType, TxId};
+    use ratatui::backend::TestBackend;
+
+    /// A dashboard drawing onto a test terminal.
+    fn dashboard() -> io::Result<Dashboard<TestBackend>> {
+        Ok(Dashboard::new(Terminal::new(TestBackend::new(100, 30))?))
+    }
+
+    /// A deposit of `amount` to `client`, as transaction `tx`.
+    fn deposit(client: ClientId, tx: TxId, amount: u32) -> Transaction {
+        Transaction::make(TransactionType::Deposit, client, tx, amount, false)
+    }
+
+    #[test]
+    fn dashboard_counts_processed_and_rejected_transactions() -> io::Result<()> {
+        // SETUP
+        let mut dashboard = dashboard()?;
+        let transactions = vec![deposit(7, 1, 5), Transaction::make_dispute(7, 2)];
+
+        // TEST
+        Bank::new().process_all_with(transactions, &mut dashboard);
+        dashboard.draw()?;
+        let screen = dashboard.terminal().backend().to_string();
+        assert!(screen.contains("2 processed | 1 rejected"), "{}", screen);
+        assert!(screen.contains("processing"), "{}", screen);
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn finished_dashboard_asks_to_exit() -> io::Result<()> {
+        // SETUP
+        let mut dashboard = dashboard()?;
+
+        // TEST
+        dashboard.finish()?;
+        let screen = dashboard.terminal().backend().to_string();
+        assert!(screen.contains("done, press q to exit"), "{}", screen);
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn recent_errors_are_listed_newest_first() -> io::Result<()> {
+        // SETUP
+        let mut dashboard = dashboard()?;
+        let transactions: Vec<Transaction> = (1..=RECENT_ERRORS as TxId + 2).map(|tx| Transaction::make_dispute(7, tx)).collect();
+
+        // TEST
+        Bank::new().process_all_with(transactions, &mut dashboard);
+        let errors = &dashboard.state().recent_errors;
+        assert_eq!(RECENT_ERRORS, errors.len());
+        assert_eq!("dispute 10 of client 7: NoSuchTransaction", errors[0]);
+        assert_eq!("dispute 3 of client 7: NoSuchTransaction", errors[RECENT_ERRORS - 1]);
+        dashboard.finish()?;
+        assert!(dashboard
+            .terminal()
+            .backend()
+            .to_string()
+            .contains("dispute 10 of client 7: NoSuchTransaction"));
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn top_accounts_are_the_largest_balances() -> io::Result<()> {
+        // SETUP
+        let mut dashboard = dashboard()?;
+        let transactions: Vec<Transaction> = (1..=TOP_ACCOUNTS as u32 + 2)
+            .map(|amount| deposit(amount as ClientId, amount.into(), amount))
+            .collect();
+
+        // TEST
+        Bank::new().process_all_with(transactions, &mut dashboard);
+        let clients: Vec<ClientId> = dashboard.state().top_accounts().iter().map(|account| account.client).collect();
+        assert_eq!((3..=12).rev().collect::<Vec<ClientId>>(), clients);
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    fn top_accounts_of_equal_balance_are_in_client_order() -> io::Result<()> {
+        // SETUP
+        let mut dashboard = dashboard()?;
+        let transactions = vec![deposit(3, 1, 5), deposit(1, 2, 5), deposit(2, 3, 7)];
+
+        // TEST
+        Bank::new().process_all_with(transactions, &mut dashboard);
+        let clients: Vec<ClientId> = dashboard.state().top_accounts().iter().map(|account| account.client).collect();
+        assert_eq!(vec![2, 1, 3], clients);
+
+        // TEARDOWN
+        Ok(())
+    }
+}
+//endregion
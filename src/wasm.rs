@@ -0,0 +1,161 @@
+#![forbid(unsafe_code)] // for good measure
+//! # JavaScript bindings
+//! Exposes `Bank` to JavaScript through `wasm-bindgen`, so that browser-based demo and
+//! reconciliation tools run exactly the same engine as the command-line tool. Build with:
+//!
+//! ```shell
+//! wasm-pack build --target web -- --features wasm
+//! ```
+//!
+//! Transactions are processed one at a time, and accounts read back one at a time:
+//!
+//! ```js
+//! const bank = new Bank();
+//! bank.process("deposit", 1, 1, "1.0");
+//! console.log(bank.account(1).available);
+//! ```
+//!
+//! With the `io` feature as well, input and output can be exchanged as CSV text:
+//!
+//! ```js
+//! bank.processCsv("type,client,tx,amount\ndeposit,1,1,1.0\n");
+//! console.log(bank.accountsCsv());
+//! ```
+use crate::account::{self, ClientId};
+use crate::bank;
+#[cfg(feature = "io")]
+use crate::schema;
+use crate::transaction::{RecordOptions, TransactionRecord, TransactionType, TxId};
+use std::str::FromStr;
+use wasm_bindgen::prelude::*;
+
+/// `Bank` wraps the engine's `Bank` for use from JavaScript.
+#[wasm_bindgen]
+pub struct Bank {
+    bank: bank::Bank,
+}
+
+#[wasm_bindgen]
+impl Bank {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Bank {
+        Bank { bank: bank::Bank::new() }
+    }
+
+    /// Process a single transaction, read as a record of input with the same fields would be.
+    /// Returns the reason the record could not be read, or the transaction was rejected.
+    pub fn process(&mut self, kind: &str, client: ClientId, tx: TxId, amount: Option<String>) -> Result<(), JsError> {
+        let kind = TransactionType::from_str(kind)
+            .or_else(|_| TransactionType::custom(kind))
+            .map_err(|e| JsError::new(&e))?;
+        let record = TransactionRecord::new(kind, client, tx, amount.as_deref());
+        let transaction = RecordOptions::default()
+            .transaction(record)
+            .map_err(|e| JsError::new(&e.to_string()))?;
+        self.bank.process_transaction(transaction).map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Returns the account of `client`, or `undefined` if the client has none.
+    pub fn account(&self, client: ClientId) -> Option<Account> {
+        self.bank.account(client).cloned().map(|account| Account { account })
+    }
+
+    /// Process CSV input with a header row. The schema is detected from the header, as it is on the
+    /// command line. Records that fail to parse or process are discarded, so an error is only
+    /// returned if the header row itself can not be read or does not match the schema.
+    #[cfg(feature = "io")]
+    #[wasm_bindgen(js_name = processCsv)]
+    pub fn process_csv(&mut self, input: &str) -> Result<(), JsError> {
+        let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(input.as_bytes());
+        let options = RecordOptions::default();
+        schema::check_input(&mut reader, None, &options).map_err(|e| JsError::new(&e))?;
+        self.bank.process_record_set(&mut reader, &options);
+        Ok(())
+    }
+
+    /// Returns every account as CSV text, including the header row.
+    #[cfg(feature = "io")]
+    #[wasm_bindgen(js_name = accountsCsv)]
+    pub fn accounts_csv(&self) -> Result<String, JsError> {
+        let mut output = Vec::new();
+        self.bank.write_accounts(&mut output).map_err(|e| JsError::new(&e.to_string()))?;
+        Ok(String::from_utf8(output).expect("accounts are written as UTF-8"))
+    }
+}
+
+impl Default for Bank {
+    fn default() -> Bank {
+        Bank::new()
+    }
+}
+
+/// `Account` is a copy of an account of a `Bank`, for use from JavaScript. Amounts are given as
+/// text, so that none is rounded to a JavaScript number.
+#[wasm_bindgen]
+pub struct Account {
+    account: account::Account,
+}
+
+#[wasm_bindgen]
+impl Account {
+    #[wasm_bindgen(getter)]
+    pub fn available(&self) -> String {
+        self.account.available.to_string()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn held(&self) -> String {
+        self.account.held.to_string()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn total(&self) -> String {
+        self.account.total.to_string()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn locked(&self) -> bool {
+        self.account.locked
+    }
+}
+
+//region Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transactions_in_and_accounts_out() -> Result<(), JsError> {
+        // SETUP
+        let mut bank = Bank::new();
+
+        // TEST
+        bank.process("deposit", 1, 1, Some(String::from("2.5")))?;
+        bank.process("withdrawal", 1, 2, Some(String::from("1")))?;
+        let account = bank.account(1).expect("account of client 1");
+        assert_eq!(
+            ("1.5", "0", "1.5", false),
+            (&*account.available(), &*account.held(), &*account.total(), account.locked())
+        );
+        assert!(bank.account(2).is_none());
+
+        // TEARDOWN
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "io")]
+    fn csv_text_in_and_out() -> Result<(), JsError> {
+        // SETUP
+        let mut bank = Bank::new();
+
+        // TEST
+        bank.process_csv("type, client, tx, amount\ndeposit, 1, 1, 1.5\n")?;
+        let csv = bank.accounts_csv().ok();
+        assert_eq!(Some("client,available,held,total,locked\n1,1.5,0,1.5,false\n"), csv.as_deref());
+
+        // TEARDOWN
+        Ok(())
+    }
+}
+//endregion